@@ -0,0 +1,69 @@
+// Lifecycle events of items (added, started, episode watched, finished, rated,
+// review edited), derived from what each save changed. They back the per-item
+// timeline and the "what did I do this month" feed; unlike the edit history
+// they keep no snapshots and survive the item being edited many times.
+
+use serde::{Deserialize, Serialize};
+use crate::models::{CollectionCategory, MediaItem};
+
+/// Oldest events are dropped past this many per user.
+pub const ACTIVITY_MAX: usize = 5000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ActivityKind {
+    Added,
+    /// First progress entered.
+    Started,
+    /// Progress moved on; `detail` is the new progress.
+    EpisodeWatched,
+    Finished,
+    /// `detail` is the new rating.
+    Rated,
+    ReviewEdited,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityEvent {
+    pub at: i64,
+    pub item_id: String,
+    /// Title when it happened, so the feed still reads after the item is deleted.
+    pub title: String,
+    pub kind: ActivityKind,
+    pub detail: Option<String>,
+}
+
+fn text(value: &Option<String>) -> &str {
+    value.as_deref().map(str::trim).unwrap_or("")
+}
+
+/// Events for saving `after` over `before` (None for a new item).
+pub fn events_for(before: Option<&MediaItem>, after: &MediaItem, at: i64) -> Vec<ActivityEvent> {
+    let blank = MediaItem::default();
+    let prev = before.unwrap_or(&blank);
+    let mut kinds: Vec<(ActivityKind, Option<String>)> = Vec::new();
+    if before.is_none() {
+        kinds.push((ActivityKind::Added, None));
+    }
+    let (was, now) = (text(&prev.user_progress), text(&after.user_progress));
+    if !now.is_empty() && now != was {
+        let kind = if was.is_empty() { ActivityKind::Started } else { ActivityKind::EpisodeWatched };
+        kinds.push((kind, Some(now.to_string())));
+    }
+    let became_watched = after.category == Some(CollectionCategory::Watched) && prev.category != Some(CollectionCategory::Watched);
+    if became_watched || after.completions.len() > prev.completions.len() {
+        kinds.push((ActivityKind::Finished, None));
+    }
+    if let Some(rating) = after.user_rating.filter(|r| Some(*r) != prev.user_rating) {
+        kinds.push((ActivityKind::Rated, Some(rating.to_string())));
+    }
+    let review = text(&after.user_review);
+    if !review.is_empty() && review != text(&prev.user_review) {
+        kinds.push((ActivityKind::ReviewEdited, None));
+    }
+    kinds
+        .into_iter()
+        .map(|(kind, detail)| ActivityEvent { at, item_id: after.id.clone(), title: after.title.clone(), kind, detail })
+        .collect()
+}
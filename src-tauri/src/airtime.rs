@@ -0,0 +1,150 @@
+// Air times. Providers give air dates and times in the broadcaster's zone:
+// Bangumi schedules are Japan time and TMDB dates are local to the show's origin
+// country; only AniList gives exact instants. An item's next airing is kept as a
+// UTC instant together with the zone it came from, and only turned into the
+// user's local time (the `utc_offset_minutes` setting) for notifications and the
+// calendar export.
+
+use serde::{Deserialize, Serialize};
+use crate::database::DAY_MS;
+use crate::models::MediaItem;
+
+/// Zone of Bangumi air dates.
+pub const JST: &str = "Asia/Tokyo";
+
+const MINUTE_MS: i64 = 60_000;
+
+/// Standard offsets of the zones broadcasts come from. Daylight saving is not
+/// applied; the zones that observe it only come from TMDB, which gives dates only.
+const ZONES: [(&str, i32); 14] = [
+    ("UTC", 0),
+    ("Asia/Tokyo", 540),
+    ("Asia/Seoul", 540),
+    ("Asia/Shanghai", 480),
+    ("Asia/Taipei", 480),
+    ("Asia/Hong_Kong", 480),
+    ("Asia/Bangkok", 420),
+    ("Asia/Kolkata", 330),
+    ("Europe/London", 0),
+    ("Europe/Paris", 60),
+    ("America/New_York", -300),
+    ("America/Los_Angeles", -480),
+    ("America/Sao_Paulo", -180),
+    ("Australia/Sydney", 600),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Airing {
+    /// "Ep 6" or "S2E3", as in `latest_update_info`.
+    pub episode: String,
+    /// UTC milliseconds; midnight in `source_timezone` when only the date is known.
+    pub at: i64,
+    pub source_timezone: String,
+    /// False when the provider gave only a date.
+    pub time_known: bool,
+}
+
+pub fn offset_minutes(zone: &str) -> i32 {
+    ZONES.iter().find(|(name, _)| *name == zone).map(|(_, offset)| *offset).unwrap_or(0)
+}
+
+/// Zone of a TMDB `origin_country` code.
+pub fn country_zone(country: &str) -> &'static str {
+    match country.to_ascii_uppercase().as_str() {
+        "JP" => JST,
+        "KR" => "Asia/Seoul",
+        "CN" => "Asia/Shanghai",
+        "TW" => "Asia/Taipei",
+        "HK" => "Asia/Hong_Kong",
+        "TH" => "Asia/Bangkok",
+        "IN" => "Asia/Kolkata",
+        "GB" | "IE" => "Europe/London",
+        "FR" | "DE" | "ES" | "IT" | "NL" | "BE" | "SE" | "DK" | "NO" => "Europe/Paris",
+        "US" | "CA" => "America/New_York",
+        "BR" => "America/Sao_Paulo",
+        "AU" => "Australia/Sydney",
+        _ => "UTC",
+    }
+}
+
+/// "2024-01-05", with an optional "23:30" wall-clock time, in `zone` as UTC
+/// milliseconds. Japanese late-night listings write "25:30" for 1:30 the next day.
+pub fn to_utc(date: &str, time: Option<&str>, zone: &str) -> Option<i64> {
+    let mut parts = date.trim().splitn(3, '-').map(|p| p.parse::<u32>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if !crate::dates::valid_day(y, m, d) {
+        return None;
+    }
+    let minutes = match time {
+        Some(t) => {
+            let mut hm = t.trim().split(':').map(|p| p.parse::<i64>().ok());
+            let (h, min) = (hm.next()??, hm.next()??);
+            if !(0..48).contains(&h) || !(0..60).contains(&min) {
+                return None;
+            }
+            h * 60 + min
+        }
+        None => 0,
+    };
+    let days = crate::smart::days_from_civil(y as i64, m, d);
+    Some(days * DAY_MS + (minutes - offset_minutes(zone) as i64) * MINUTE_MS)
+}
+
+/// Date and minute of day of `at` for a user `utc_offset_minutes` from UTC.
+fn local(at: i64, utc_offset_minutes: i32) -> ((i64, u32, u32), i64) {
+    let shifted = at + utc_offset_minutes as i64 * MINUTE_MS;
+    let date = crate::updates::civil_from_days(shifted.div_euclid(DAY_MS));
+    (date, shifted.rem_euclid(DAY_MS) / MINUTE_MS)
+}
+
+/// "2024-01-05 23:30" in the user's time, or just the date when the time is unknown.
+pub fn local_time(airing: &Airing, utc_offset_minutes: i32) -> String {
+    let ((y, m, d), minute) = local(airing.at, utc_offset_minutes);
+    if airing.time_known {
+        format!("{:04}-{:02}-{:02} {:02}:{:02}", y, m, d, minute / 60, minute % 60)
+    } else {
+        format!("{:04}-{:02}-{:02}", y, m, d)
+    }
+}
+
+fn ics_text(s: &str) -> String {
+    s.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+fn ics_utc(at: i64) -> String {
+    let ((y, m, d), minute) = local(at, 0);
+    format!("{:04}{:02}{:02}T{:02}{:02}00Z", y, m, d, minute / 60, minute % 60)
+}
+
+/// An iCalendar file with the next airing of every item that has one. Timed
+/// airings are written in UTC for the calendar app to show locally; date-only
+/// ones become all-day events on the user's local date.
+pub fn calendar(items: &[MediaItem], utc_offset_minutes: i32, now: i64) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//MediaTracker//Airing//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for item in items {
+        let Some(airing) = item.next_airing.as_ref() else {
+            continue;
+        };
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}@mediatracker", item.id, airing.at));
+        lines.push(format!("DTSTAMP:{}", ics_utc(now)));
+        if airing.time_known {
+            lines.push(format!("DTSTART:{}", ics_utc(airing.at)));
+            lines.push("DURATION:PT30M".to_string());
+        } else {
+            let ((y, m, d), _) = local(airing.at, utc_offset_minutes);
+            lines.push(format!("DTSTART;VALUE=DATE:{:04}{:02}{:02}", y, m, d));
+        }
+        lines.push(format!("SUMMARY:{}", ics_text(&format!("{} {}", item.title, airing.episode))));
+        lines.push(format!("DESCRIPTION:{}", ics_text(&format!("Airs in {}", airing.source_timezone))));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.join("\r\n") + "\r\n"
+}
@@ -0,0 +1,234 @@
+// AniList, for the seasonal anime chart and exact air times. A season's shows
+// come from the public GraphQL API, most popular first, and are kept in memory
+// per season for a few hours; a show quick-added from the chart keeps its AniList
+// id, which the update checker then uses to learn when the next episode airs to
+// the minute (AniList gives air times as UTC timestamps).
+
+use std::collections::HashMap;
+use std::time::Duration;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use crate::airtime::{Airing, JST};
+use crate::database::new_id;
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+use crate::ratings::SourceRating;
+
+/// Provider id key of the AniList media id.
+pub const KEY: &str = "anilist";
+
+const API: &str = "https://graphql.anilist.co";
+const CACHE_TTL_MS: i64 = 6 * 60 * 60 * 1000;
+const PER_PAGE: usize = 50;
+/// A busy season has around 150 shows worth listing.
+const MAX_PAGES: usize = 4;
+
+const SEASON_QUERY: &str = "query ($season: MediaSeason, $year: Int, $page: Int, $perPage: Int) {
+  Page(page: $page, perPage: $perPage) {
+    pageInfo { hasNextPage }
+    media(season: $season, seasonYear: $year, type: ANIME, isAdult: false, sort: POPULARITY_DESC) {
+      id format status episodes averageScore genres
+      title { romaji english native }
+      coverImage { large }
+      startDate { year month day }
+      studios(isMain: true) { nodes { name } }
+      nextAiringEpisode { airingAt episode }
+    }
+  }
+}";
+
+const AIRING_QUERY: &str = "query ($id: Int) {
+  Media(id: $id, type: ANIME) { status episodes nextAiringEpisode { airingAt episode } }
+}";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Season {
+    Winter,
+    Spring,
+    Summer,
+    Fall,
+}
+
+impl Season {
+    fn as_anilist(self) -> &'static str {
+        match self {
+            Season::Winter => "WINTER",
+            Season::Spring => "SPRING",
+            Season::Summer => "SUMMER",
+            Season::Fall => "FALL",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SeasonalShow {
+    pub anilist_id: u64,
+    pub title: String,
+    /// Titles by language code ("en", "ja"), as in `MediaItem::alt_titles`.
+    pub alt_titles: HashMap<String, String>,
+    pub cover_url: Option<String>,
+    pub studios: Vec<String>,
+    /// AniList's average score out of 100.
+    pub score: Option<f64>,
+    /// "TV", "TV_SHORT", "MOVIE", "ONA", ...
+    pub format: Option<String>,
+    pub episodes: Option<u32>,
+    /// "2024-01-05", or shorter when AniList only knows the month or year.
+    pub start_date: String,
+    pub genres: Vec<String>,
+    /// "RELEASING", "NOT_YET_RELEASED" or "FINISHED".
+    pub status: Option<String>,
+    pub next_airing: Option<Airing>,
+    /// Filled in per user when the chart is returned; never cached.
+    #[serde(default)]
+    pub in_collection: bool,
+}
+
+/// Seasons by "2024-WINTER", with when they were fetched.
+#[derive(Default)]
+pub struct SeasonCache(RwLock<HashMap<String, (i64, Vec<SeasonalShow>)>>);
+
+fn cache_key(year: i32, season: Season) -> String {
+    format!("{}-{}", year, season.as_anilist())
+}
+
+impl SeasonCache {
+    pub async fn get(&self, year: i32, season: Season, now: i64) -> Option<Vec<SeasonalShow>> {
+        let cache = self.0.read().await;
+        cache.get(&cache_key(year, season)).filter(|(at, _)| now - at <= CACHE_TTL_MS).map(|(_, shows)| shows.clone())
+    }
+
+    pub async fn put(&self, year: i32, season: Season, shows: Vec<SeasonalShow>, now: i64) {
+        self.0.write().await.insert(cache_key(year, season), (now, shows));
+    }
+}
+
+async fn query(client: &Client, query: &str, variables: Value) -> Result<Value, String> {
+    let fut = client.post(API).json(&json!({ "query": query, "variables": variables })).send();
+    let resp = tokio::time::timeout(Duration::from_secs(15), fut)
+        .await
+        .map_err(|_| "Request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("AniList Error: {}", resp.status()));
+    }
+    let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+    if let Some(message) = v["errors"][0]["message"].as_str() {
+        return Err(format!("AniList Error: {}", message));
+    }
+    Ok(v)
+}
+
+fn next_airing(media: &Value) -> Option<Airing> {
+    let next = &media["nextAiringEpisode"];
+    Some(Airing {
+        episode: format!("Ep {}", next["episode"].as_u64()?),
+        at: next["airingAt"].as_i64()? * 1000,
+        source_timezone: JST.to_string(),
+        time_known: true,
+    })
+}
+
+/// One entry of a season's `media` list.
+pub fn parse_show(media: &Value) -> Option<SeasonalShow> {
+    let title = &media["title"];
+    let text = |v: &Value| v.as_str().map(str::trim).filter(|t| !t.is_empty()).map(str::to_string);
+    let display = text(&title["romaji"]).or_else(|| text(&title["english"])).or_else(|| text(&title["native"]))?;
+    let mut alt_titles = HashMap::new();
+    for (lang, key) in [("en", "english"), ("ja", "native")] {
+        if let Some(t) = text(&title[key]).filter(|t| *t != display) {
+            alt_titles.insert(lang.to_string(), t);
+        }
+    }
+    let date = &media["startDate"];
+    let start_date = match (date["year"].as_u64(), date["month"].as_u64(), date["day"].as_u64()) {
+        (Some(y), Some(m), Some(d)) => format!("{:04}-{:02}-{:02}", y, m, d),
+        (Some(y), Some(m), None) => format!("{:04}-{:02}", y, m),
+        (Some(y), _, _) => y.to_string(),
+        _ => String::new(),
+    };
+    Some(SeasonalShow {
+        anilist_id: media["id"].as_u64()?,
+        title: display,
+        alt_titles,
+        cover_url: text(&media["coverImage"]["large"]),
+        studios: media["studios"]["nodes"].as_array().into_iter().flatten().filter_map(|s| text(&s["name"])).collect(),
+        score: media["averageScore"].as_f64(),
+        format: text(&media["format"]),
+        episodes: media["episodes"].as_u64().map(|e| e as u32),
+        start_date,
+        genres: media["genres"].as_array().into_iter().flatten().filter_map(text).collect(),
+        status: text(&media["status"]),
+        next_airing: next_airing(media),
+        in_collection: false,
+    })
+}
+
+/// Every show of a season, most popular first.
+pub async fn season(client: &Client, year: i32, season: Season) -> Result<Vec<SeasonalShow>, String> {
+    let mut shows = Vec::new();
+    for page in 1..=MAX_PAGES {
+        let variables = json!({ "season": season.as_anilist(), "year": year, "page": page, "perPage": PER_PAGE });
+        let v = query(client, SEASON_QUERY, variables).await?;
+        let list = &v["data"]["Page"];
+        shows.extend(list["media"].as_array().into_iter().flatten().filter_map(parse_show));
+        if list["pageInfo"]["hasNextPage"].as_bool() != Some(true) {
+            break;
+        }
+    }
+    Ok(shows)
+}
+
+/// Flags shows the user already has, by AniList id or title.
+pub fn mark_collected(shows: &mut [SeasonalShow], items: &[MediaItem]) {
+    for show in shows.iter_mut() {
+        let id = show.anilist_id.to_string();
+        show.in_collection = items.iter().any(|i| {
+            i.provider_ids.as_ref().and_then(|ids| ids.get(KEY)) == Some(&id)
+                || crate::dedupe::title_similarity(&i.title, &show.title) >= 0.9
+                || show.alt_titles.values().any(|t| crate::dedupe::title_similarity(&i.title, t) >= 0.9)
+        });
+    }
+}
+
+/// A chart entry as a new To Watch item.
+pub fn to_item(show: &SeasonalShow, now: i64) -> MediaItem {
+    let mut tags = vec!["Anime".to_string()];
+    tags.extend(show.genres.iter().cloned());
+    let ratings = show.score.map(|score| HashMap::from([(KEY.to_string(), SourceRating { score, scale: 100.0, votes: None, updated_at: Some(now) })]));
+    MediaItem {
+        id: new_id(),
+        title: show.title.clone(),
+        director_or_author: show.studios.join(", "),
+        release_date: show.start_date.clone(),
+        media_type: if show.format.as_deref() == Some("MOVIE") { MediaType::Movie } else { MediaType::TvSeries },
+        is_ongoing: matches!(show.status.as_deref(), Some("RELEASING" | "NOT_YET_RELEASED")),
+        category: Some(CollectionCategory::ToWatch),
+        status: Some("To Watch".to_string()),
+        saved_at: Some(now),
+        poster_url: show.cover_url.clone(),
+        ratings,
+        tags: Some(tags),
+        provider_ids: Some(HashMap::from([(KEY.to_string(), show.anilist_id.to_string())])),
+        alt_titles: show.alt_titles.clone(),
+        next_airing: show.next_airing.clone(),
+        ..Default::default()
+    }
+}
+
+/// The latest aired episode and the next airing, for the update checker.
+pub async fn airing(client: &Client, id: &str) -> Result<crate::updates::Check, String> {
+    let id: u64 = id.parse().map_err(|_| format!("Invalid AniList id '{}'", id))?;
+    let v = query(client, AIRING_QUERY, json!({ "id": id })).await?;
+    let media = &v["data"]["Media"];
+    let next_airing = next_airing(media);
+    let latest = match media["nextAiringEpisode"]["episode"].as_u64() {
+        Some(next) => next.checked_sub(1).filter(|n| *n > 0),
+        None if media["status"].as_str() == Some("FINISHED") => media["episodes"].as_u64(),
+        None => None,
+    };
+    Ok(crate::updates::Check { latest: latest.map(|n| format!("Ep {}", n)), next_airing })
+}
@@ -0,0 +1,144 @@
+// Fanfiction from Archive of Our Own (archiveofourown.org). AO3 has no API, so
+// the work page's stats block is scraped: chapters posted out of the planned
+// total, word count, and the last update date. Incomplete works are tracked by
+// the update checker like an airing show, by their posted chapter count.
+// AO3 rate limits scrapers hard, so requests are spaced out.
+
+use std::time::Duration;
+use reqwest::Client;
+use serde::Serialize;
+use crate::content_rating::ContentRating;
+use crate::rate_limit::RateLimiter;
+use crate::scrape::{element, html_text, link_texts};
+
+const SITE: &str = "https://archiveofourown.org";
+
+static LIMITER: RateLimiter = RateLimiter::new(Duration::from_secs(5));
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Work {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub authors: Vec<String>,
+    pub fandoms: Vec<String>,
+    /// "General Audiences", "Teen And Up Audiences", "Mature", "Explicit" or "Not Rated".
+    pub rating: Option<String>,
+    pub tags: Vec<String>,
+    pub summary: Option<String>,
+    pub language: Option<String>,
+    /// YYYY-MM-DD.
+    pub published: Option<String>,
+    /// Last chapter posted (or completion) date, YYYY-MM-DD.
+    pub updated: Option<String>,
+    pub words: Option<u64>,
+    pub chapters_posted: u32,
+    /// None while the author hasn't said ("12/?").
+    pub chapters_total: Option<u32>,
+    pub complete: bool,
+    pub kudos: Option<u64>,
+    pub content_rating: Option<ContentRating>,
+}
+
+impl Work {
+    /// "Ch. 12/?" or "Ch. 12/20", what the update checker stores.
+    pub fn progress(&self) -> String {
+        match self.chapters_total {
+            Some(total) => format!("Ch. {}/{}", self.chapters_posted, total),
+            None => format!("Ch. {}/?", self.chapters_posted),
+        }
+    }
+}
+
+fn content_rating(rating: &str) -> Option<ContentRating> {
+    let min_age = match rating {
+        "General Audiences" => 0,
+        "Teen And Up Audiences" => 13,
+        "Mature" => 17,
+        "Explicit" => 18,
+        _ => return None,
+    };
+    Some(ContentRating { system: "AO3".to_string(), value: rating.to_string(), min_age: Some(min_age) })
+}
+
+/// Work id from a work or chapter URL, or a bare id.
+pub fn work_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let rest = input.split_once("/works/").map(|(_, r)| r).unwrap_or(input);
+    let id: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (!id.is_empty()).then_some(id)
+}
+
+fn number(text: &str) -> Option<u64> {
+    text.chars().filter(|c| c.is_ascii_digit()).collect::<String>().parse().ok()
+}
+
+/// Text of the `<dd class="{class}">` stat.
+fn stat(html: &str, class: &str) -> Option<String> {
+    element(html, &format!("dd class=\"{}\"", class)).map(html_text).filter(|s| !s.is_empty())
+}
+
+pub fn parse_work(id: &str, html: &str) -> Work {
+    let chapters = stat(html, "chapters").unwrap_or_default();
+    let (posted, total) = chapters.split_once('/').unwrap_or((chapters.as_str(), "?"));
+    let chapters_posted = number(posted).unwrap_or(1) as u32;
+    let chapters_total = number(total).map(|t| t as u32);
+    let tags = |class: &str| element(html, &format!("dd class=\"{} tags\"", class)).map(link_texts).unwrap_or_default();
+    let rating = tags("rating").into_iter().next();
+    Work {
+        id: id.to_string(),
+        title: element(html, "h2 class=\"title heading\"").map(html_text).unwrap_or_default(),
+        url: format!("{}/works/{}", SITE, id),
+        authors: element(html, "h3 class=\"byline heading\"").map(link_texts).unwrap_or_default(),
+        fandoms: tags("fandom"),
+        content_rating: rating.as_deref().and_then(content_rating),
+        rating,
+        tags: tags("freeform"),
+        summary: element(html, "div class=\"summary module\"")
+            .and_then(|s| element(s, "blockquote"))
+            .map(html_text)
+            .filter(|s| !s.is_empty()),
+        language: stat(html, "language"),
+        published: stat(html, "published"),
+        // Single-chapter works have no "status" stat; they were last updated when published
+        updated: stat(html, "status").or_else(|| stat(html, "published")),
+        words: stat(html, "words").and_then(|w| number(&w)),
+        chapters_posted,
+        chapters_total,
+        complete: chapters_total == Some(chapters_posted),
+        kudos: stat(html, "kudos").and_then(|k| number(&k)),
+    }
+}
+
+pub async fn work(client: &Client, id: &str) -> Result<Work, String> {
+    let id = work_id(id).ok_or_else(|| "Invalid AO3 work".to_string())?;
+    LIMITER.wait().await;
+    // view_adult skips the "this work could have adult content" interstitial
+    let url = format!("{}/works/{}?view_adult=true", SITE, id);
+    let resp = tokio::time::timeout(Duration::from_secs(15), client.get(&url).send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    match resp.status().as_u16() {
+        404 => return Err("Work not found".to_string()),
+        429 => return Err("AO3 rate limit reached; try again later".to_string()),
+        _ if !resp.status().is_success() => return Err(format!("AO3 Error: {}", resp.status())),
+        _ => {}
+    }
+    let html = resp.text().await.map_err(|e| e.to_string())?;
+    // Works restricted to logged-in users redirect to the login page
+    if html.contains("id=\"signin\"") && !html.contains("<dl class=\"stats\"") {
+        return Err("This work is only visible to logged-in AO3 users".to_string());
+    }
+    let work = parse_work(&id, &html);
+    if work.title.is_empty() {
+        return Err("Work not found".to_string());
+    }
+    Ok(work)
+}
+
+/// Posted chapters of an incomplete work, for the update checker.
+pub async fn latest(client: &Client, id: &str) -> Result<Option<String>, String> {
+    Ok(Some(work(client, id).await?.progress()))
+}
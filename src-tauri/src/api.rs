@@ -0,0 +1,205 @@
+// REST API on the embedded server for scripts and mobile shortcuts. Every
+// route takes `Authorization: Bearer <token>` with the user's API token
+// (`create_extension_token`) and works on that user's collection only.
+// Deleted items go to the trash, as they do in the app. `/ws` streams the
+// user's item changes as JSON `ItemEvent`s; browsers, which cannot set headers
+// on a WebSocket, authenticate with `?token=` or the web view's cookie.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Json, Router};
+use tokio::sync::broadcast::error::RecvError;
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use crate::database::{new_id, now_ms};
+use crate::models::{CollectionCategory, MediaItem};
+use crate::sync::{token_hash, user_from_bearer, SyncState};
+
+/// Emitted after an API call changed an item, so the open window can reload.
+pub const API_CHANGE_EVENT: &str = "api-change";
+
+type ApiError = (StatusCode, String);
+
+pub fn routes() -> Router<SyncState> {
+    Router::new()
+        .route("/api/users/me", get(me))
+        .route("/api/items", get(list_items).post(create_item))
+        .route("/api/items/{id}", get(get_item).put(update_item).delete(delete_item))
+        .route("/ws", get(live))
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ApiChange {
+    username: String,
+    item_id: String,
+    action: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Me {
+    username: String,
+    created_at: i64,
+    item_count: usize,
+    trash_count: usize,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ItemsQuery {
+    category: Option<CollectionCategory>,
+    #[serde(rename = "type")]
+    media_type: Option<String>,
+    q: Option<String>,
+}
+
+fn internal(e: String) -> ApiError {
+    (StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+fn not_found() -> ApiError {
+    (StatusCode::NOT_FOUND, "Item not found".to_string())
+}
+
+async fn authorize(state: &SyncState, headers: &HeaderMap) -> Result<String, ApiError> {
+    user_from_bearer(&state.db, headers)
+        .await
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid or missing token".to_string()))
+}
+
+fn notify(state: &SyncState, username: &str, item_id: &str, action: &'static str) {
+    let change = ApiChange { username: username.to_string(), item_id: item_id.to_string(), action };
+    let _ = state.app.emit(API_CHANGE_EVENT, change);
+}
+
+async fn me(State(state): State<SyncState>, headers: HeaderMap) -> Result<Json<Me>, ApiError> {
+    let username = authorize(&state, &headers).await?;
+    let user = state.db.find_user(&username).await.ok_or((StatusCode::UNAUTHORIZED, "User not found".to_string()))?;
+    let items = state.db.get_all_for_user(&username).await.map_err(internal)?;
+    let trash = state.db.get_trash_for_user(&username).await.map_err(internal)?;
+    Ok(Json(Me { username, created_at: user.created_at, item_count: items.len(), trash_count: trash.len() }))
+}
+
+async fn list_items(State(state): State<SyncState>, headers: HeaderMap, Query(query): Query<ItemsQuery>) -> Result<Json<Vec<MediaItem>>, ApiError> {
+    let username = authorize(&state, &headers).await?;
+    let mut items = state.db.get_all_for_user(&username).await.map_err(internal)?;
+    if let Some(category) = &query.category {
+        items.retain(|i| i.category.as_ref() == Some(category));
+    }
+    if let Some(media_type) = query.media_type.as_deref().filter(|t| !t.is_empty()) {
+        items.retain(|i| serde_json::to_value(&i.media_type).ok().and_then(|v| v.as_str().map(|s| s == media_type)).unwrap_or(false));
+    }
+    if let Some(q) = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+        let q = q.to_lowercase();
+        items.retain(|i| i.title.to_lowercase().contains(&q) || i.director_or_author.to_lowercase().contains(&q));
+    }
+    Ok(Json(items))
+}
+
+async fn get_item(State(state): State<SyncState>, headers: HeaderMap, Path(id): Path<String>) -> Result<Json<MediaItem>, ApiError> {
+    let username = authorize(&state, &headers).await?;
+    state.db.find_item(&username, &id).await.map(Json).ok_or_else(not_found)
+}
+
+async fn create_item(State(state): State<SyncState>, headers: HeaderMap, Json(mut item): Json<MediaItem>) -> Result<(StatusCode, Json<MediaItem>), ApiError> {
+    let username = authorize(&state, &headers).await?;
+    if item.title.trim().is_empty() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, "title is required".to_string()));
+    }
+    if item.id.trim().is_empty() {
+        item.id = new_id();
+    } else if state.db.find_item(&username, &item.id).await.is_some() {
+        return Err((StatusCode::CONFLICT, "An item with this id already exists".to_string()));
+    }
+    item.category.get_or_insert(CollectionCategory::ToWatch);
+    item.saved_at.get_or_insert_with(now_ms);
+    state.db.add_item_for_user(&username, item.clone()).await.map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    let item = state.db.find_item(&username, &item.id).await.unwrap_or(item);
+    notify(&state, &username, &item.id, "created");
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+/// Fields missing from the body keep their stored value, so a shortcut can send
+/// just `{"category": "Watched"}`.
+async fn update_item(
+    State(state): State<SyncState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Json<MediaItem>, ApiError> {
+    let username = authorize(&state, &headers).await?;
+    let existing = state.db.find_item(&username, &id).await.ok_or_else(not_found)?;
+    let serde_json::Value::Object(fields) = patch else {
+        return Err((StatusCode::BAD_REQUEST, "Expected a JSON object".to_string()));
+    };
+    let mut merged = serde_json::to_value(&existing).map_err(|e| internal(e.to_string()))?;
+    if let Some(target) = merged.as_object_mut() {
+        target.extend(fields);
+        target.insert("id".to_string(), serde_json::Value::String(id.clone()));
+    }
+    let mut item: MediaItem = serde_json::from_value(merged).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    item.last_edited_at = Some(now_ms());
+    state.db.add_item_for_user(&username, item).await.map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+    let item = state.db.find_item(&username, &id).await.ok_or_else(not_found)?;
+    notify(&state, &username, &id, "updated");
+    Ok(Json(item))
+}
+
+async fn delete_item(State(state): State<SyncState>, headers: HeaderMap, Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    let username = authorize(&state, &headers).await?;
+    if state.db.find_item(&username, &id).await.is_none() {
+        return Err(not_found());
+    }
+    state.db.remove_item_for_user(&username, &id).await.map_err(internal)?;
+    notify(&state, &username, &id, "deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct LiveQuery {
+    token: Option<String>,
+}
+
+async fn live(State(state): State<SyncState>, headers: HeaderMap, Query(query): Query<LiveQuery>, upgrade: WebSocketUpgrade) -> Result<Response, ApiError> {
+    let mut username = user_from_bearer(&state.db, &headers).await;
+    if let (None, Some(token)) = (&username, query.token.as_deref()) {
+        let hash = token_hash(token);
+        username = match state.db.user_for_extension_token(&hash).await {
+            Some(u) => Some(u),
+            None => state.db.user_for_web_token(&hash).await,
+        };
+    }
+    if username.is_none() {
+        username = crate::web::viewer(&state, &headers).await;
+    }
+    let username = username.ok_or((StatusCode::UNAUTHORIZED, "Invalid or missing token".to_string()))?;
+    let events = state.db.subscribe();
+    Ok(upgrade.on_upgrade(move |socket| forward_events(socket, username, events)))
+}
+
+async fn forward_events(mut socket: WebSocket, username: String, mut events: tokio::sync::broadcast::Receiver<crate::database::ItemEvent>) {
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let text = match event {
+                    Ok(e) if e.username == username => serde_json::to_string(&e).unwrap_or_default(),
+                    Ok(_) => continue,
+                    // Missed events: tell the client to reload everything
+                    Err(RecvError::Lagged(_)) => r#"{"kind":"resync"}"#.to_string(),
+                    Err(RecvError::Closed) => break,
+                };
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by the library; anything else is ignored
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
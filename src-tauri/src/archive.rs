@@ -0,0 +1,128 @@
+// Portable per-user archive: one ZIP with `archive.json` (items with their
+// tags, progress and reviews, plus trash, edit history, collections, smart
+// lists, custom field definitions, relations, people and feed subscriptions)
+// and the cached covers those items use under `covers/`. Importing merges an
+// archive into the signed-in account without replacing anything it has.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::images::ImageCache;
+use crate::models::{ItemRevision, MediaItem};
+
+pub const ARCHIVE_VERSION: u32 = 1;
+const MANIFEST: &str = "archive.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UserArchive {
+    pub version: u32,
+    pub username: String,
+    pub exported_at: i64,
+    #[serde(default)]
+    pub items: Vec<MediaItem>,
+    #[serde(default)]
+    pub trash: Vec<MediaItem>,
+    #[serde(default)]
+    pub history: HashMap<String, Vec<ItemRevision>>,
+    #[serde(default)]
+    pub collections: Vec<crate::collections::Collection>,
+    #[serde(default)]
+    pub smart_lists: Vec<crate::smart::SmartList>,
+    #[serde(default)]
+    pub custom_fields: Vec<crate::custom_fields::CustomFieldDef>,
+    #[serde(default)]
+    pub relations: Vec<crate::relations::Relation>,
+    #[serde(default)]
+    pub people: HashMap<String, crate::people::PersonMeta>,
+    #[serde(default)]
+    pub feeds: Vec<crate::feeds::FeedSubscription>,
+}
+
+/// What an import added; everything else in the archive was already there.
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveImport {
+    pub items: usize,
+    pub trash: usize,
+    pub collections: usize,
+    pub smart_lists: usize,
+    pub custom_fields: usize,
+    pub relations: usize,
+    pub feeds: usize,
+    pub covers: usize,
+    /// Items skipped because one with the same id exists.
+    pub skipped: usize,
+}
+
+impl UserArchive {
+    fn cover_files(&self) -> HashSet<&str> {
+        self.items
+            .iter()
+            .chain(self.trash.iter())
+            .filter_map(|i| i.poster_cache.as_ref().map(|c| c.file.as_str()))
+            .collect()
+    }
+}
+
+/// Writes the archive and the covers it references; returns how many covers were included.
+pub fn write(target: &Path, archive: &UserArchive, images: &ImageCache) -> Result<usize, String> {
+    use zip::write::SimpleFileOptions;
+    use zip::CompressionMethod;
+    let file = std::fs::File::create(target).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated).large_file(true);
+    let manifest = serde_json::to_vec_pretty(archive).map_err(|e| e.to_string())?;
+    zip.start_file(MANIFEST, deflated).map_err(|e| e.to_string())?;
+    zip.write_all(&manifest).map_err(|e| e.to_string())?;
+
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut covers = 0;
+    for name in archive.cover_files() {
+        // A cover missing from the cache is left out; the item keeps its URL
+        let Some(bytes) = images.path_of(name).and_then(|p| std::fs::read(p).ok()) else {
+            continue;
+        };
+        zip.start_file(format!("covers/{}", name), stored).map_err(|e| e.to_string())?;
+        zip.write_all(&bytes).map_err(|e| e.to_string())?;
+        covers += 1;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(covers)
+}
+
+/// Reads an archive, copying its covers into the image cache. Returns the
+/// archive and how many covers were new to the cache.
+pub fn read(source: &Path, images: &ImageCache) -> Result<(UserArchive, usize), String> {
+    let file = std::fs::File::open(source).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("Not a MediaTracker archive: {}", e))?;
+    let archive: UserArchive = {
+        let entry = zip.by_name(MANIFEST).map_err(|_| "Not a MediaTracker archive: archive.json is missing".to_string())?;
+        serde_json::from_reader(entry).map_err(|e| e.to_string())?
+    };
+    if archive.version > ARCHIVE_VERSION {
+        return Err(format!("This archive was made by a newer version (format {})", archive.version));
+    }
+
+    let wanted = archive.cover_files();
+    let mut covers = 0;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+        let Some(name) = entry.name().strip_prefix("covers/").map(str::to_string) else {
+            continue;
+        };
+        // Only `<hash>.<ext>` names the items use; anything else is ignored
+        let Some(path) = images.path_of(&name).filter(|_| wanted.contains(name.as_str())) else {
+            continue;
+        };
+        if path.exists() {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+        std::fs::write(&path, bytes).map_err(|e| e.to_string())?;
+        covers += 1;
+    }
+    Ok((archive, covers))
+}
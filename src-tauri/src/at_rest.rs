@@ -0,0 +1,90 @@
+// Optional encryption of collection.json and its local copies (.bak, snapshot
+// backups, cloud backups). The file becomes `MAGIC || nonce || ciphertext`
+// (ChaCha20-Poly1305) under a random 256-bit key kept in the OS keychain as
+// `database-key`, so the data can't be read by anything that only has the
+// file. The key is shown once when encryption is turned on (or rotated); if the
+// keychain entry is lost it can be put back with `set_secret`, which only takes
+// it while the collection is locked and the key opens it. Turning encryption on
+// or off and rotating the key go through the password-checked
+// `set_database_encryption`.
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use rand_core::{OsRng, RngCore};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use std::borrow::Cow;
+use std::path::Path;
+
+const MAGIC: &[u8] = b"MTENC1\n";
+pub const KEY_SECRET: &str = "database-key";
+
+pub type Key = [u8; 32];
+
+pub fn new_key() -> Key {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+pub fn key_to_base64(key: &Key) -> String {
+    B64.encode(key)
+}
+
+pub fn key_from_base64(encoded: &str) -> Option<Key> {
+    B64.decode(encoded.trim()).ok().and_then(|b| b.try_into().ok())
+}
+
+/// The key in the keychain, if encryption was turned on on this computer.
+pub fn stored_key() -> Result<Option<Key>, String> {
+    let Some(encoded) = crate::secrets::get(KEY_SECRET)? else {
+        return Ok(None);
+    };
+    key_from_base64(&encoded).map(Some).ok_or_else(|| "The database key in the keychain is invalid".to_string())
+}
+
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn file_is_encrypted(path: &Path) -> bool {
+    use std::io::Read;
+    let mut head = [0u8; MAGIC.len()];
+    std::fs::File::open(path).and_then(|mut f| f.read_exact(&mut head)).is_ok() && is_encrypted(&head)
+}
+
+fn cipher(key: &Key) -> Result<LessSafeKey, String> {
+    UnboundKey::new(&CHACHA20_POLY1305, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| "Invalid key".to_string())
+}
+
+/// Encrypts `plaintext` when there is a key, otherwise passes it through.
+pub fn encode(plaintext: Vec<u8>, key: Option<&Key>) -> Result<Vec<u8>, String> {
+    let Some(key) = key else {
+        return Ok(plaintext);
+    };
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let mut data = plaintext;
+    cipher(key)?
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(MAGIC), &mut data)
+        .map_err(|_| "Encryption failed".to_string())?;
+    Ok([MAGIC, &nonce, &data].concat())
+}
+
+/// Reverses `encode`; plaintext files are returned as they are.
+pub fn decode<'a>(bytes: &'a [u8], key: Option<&Key>) -> Result<Cow<'a, [u8]>, String> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Ok(Cow::Borrowed(bytes));
+    };
+    let key = key.ok_or_else(|| "The file is encrypted and its key is not in the keychain".to_string())?;
+    if rest.len() < NONCE_LEN {
+        return Err("Encrypted file is truncated".to_string());
+    }
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let nonce: [u8; NONCE_LEN] = nonce.try_into().map_err(|_| "Invalid nonce".to_string())?;
+    let mut data = sealed.to_vec();
+    let plaintext = cipher(key)?
+        .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(MAGIC), &mut data)
+        .map_err(|_| "Could not decrypt the file (wrong key or damaged data)".to_string())?;
+    Ok(Cow::Owned(plaintext.to_vec()))
+}
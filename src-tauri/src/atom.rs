@@ -0,0 +1,132 @@
+// Atom feed of a user's recent additions and completions at `/feed.xml`, for
+// friends to follow in a feed reader. The token from `create_feed_token` goes
+// in the query string (`/feed.xml?token=...`) since readers can't send headers;
+// it grants nothing but the feed and the covers it links to.
+
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use quick_xml::escape::escape;
+use serde::Deserialize;
+use crate::models::{CollectionCategory, ItemRevision, MediaItem};
+use crate::sync::{token_hash, SyncState};
+
+const FEED_ENTRIES: usize = 50;
+
+pub fn routes() -> Router<SyncState> {
+    Router::new().route("/feed.xml", get(feed))
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct TokenQuery {
+    pub token: Option<String>,
+}
+
+/// User a feed token belongs to.
+pub(crate) async fn feed_user(state: &SyncState, token: Option<&str>) -> Option<String> {
+    state.db.user_for_feed_token(&token_hash(token?)).await
+}
+
+struct Entry<'a> {
+    item: &'a MediaItem,
+    completed: bool,
+    at: i64,
+}
+
+/// `2024-01-31T23:59:59Z` for a unix time in milliseconds.
+fn rfc3339(ms: i64) -> String {
+    let secs = ms.div_euclid(1000);
+    let (y, m, d) = crate::updates::civil_from_days(secs.div_euclid(86_400));
+    let t = secs.rem_euclid(86_400);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", y, m, d, t / 3600, t % 3600 / 60, t % 60)
+}
+
+/// When the item last moved into Watched: the edit after the newest revision that
+/// wasn't Watched yet, or when it was saved if it was added as watched.
+pub(crate) fn completed_at(item: &MediaItem, history: &[ItemRevision]) -> Option<i64> {
+    if item.category != Some(CollectionCategory::Watched) {
+        return None;
+    }
+    history
+        .iter()
+        .rev()
+        .find(|r| r.snapshot.category != Some(CollectionCategory::Watched))
+        .map(|r| r.at)
+        .or(item.saved_at)
+}
+
+fn entry_xml(entry: &Entry, username: &str, base: &str, token: &str) -> String {
+    let item = entry.item;
+    let (verb, tag) = if entry.completed { ("Finished", "completed") } else { ("Added", "added") };
+    let mut content = String::new();
+    let cover = match &item.poster_cache {
+        Some(cached) => Some(format!("{}/web/covers/{}?token={}", base, urlencoding::encode(&cached.file), token)),
+        None => item.custom_poster_url.clone().or_else(|| item.poster_url.clone()).filter(|u| u.starts_with("http")),
+    };
+    if let Some(src) = cover {
+        content.push_str(&format!("<p><img src=\"{}\" alt=\"\" style=\"max-width:200px\"></p>", escape(&src)));
+    }
+    if let Some(rating) = item.user_rating {
+        content.push_str(&format!("<p>Rating: {}</p>", rating));
+    }
+    if let Some(review) = item.user_review.as_deref().filter(|r| !r.trim().is_empty()) {
+        content.push_str(&format!("<blockquote>{}</blockquote>", escape(review)));
+    } else if !item.description.trim().is_empty() {
+        content.push_str(&format!("<p>{}</p>", escape(&item.description)));
+    }
+    let media_type = serde_json::to_value(&item.media_type).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+    format!(
+        "<entry><id>urn:mediatracker:{}:{}:{}</id><title>{}: {}</title><updated>{}</updated>\
+         <author><name>{}</name></author><category term=\"{}\"/><content type=\"html\">{}</content></entry>",
+        escape(username),
+        escape(&item.id),
+        tag,
+        verb,
+        escape(&item.title),
+        rfc3339(entry.at),
+        escape(username),
+        escape(&media_type),
+        escape(&content)
+    )
+}
+
+async fn feed(State(state): State<SyncState>, headers: HeaderMap, Query(query): Query<TokenQuery>) -> Response {
+    let Some(username) = feed_user(&state, query.token.as_deref()).await else {
+        return (StatusCode::UNAUTHORIZED, "Invalid or missing token").into_response();
+    };
+    let items = match state.db.get_all_for_user(&username).await {
+        Ok(items) => items,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    let mut entries = Vec::new();
+    for item in items.iter().filter(|i| i.is_collection != Some(true)) {
+        if let Some(at) = item.saved_at {
+            entries.push(Entry { item, completed: false, at });
+        }
+        let history = state.db.get_item_history(&username, &item.id).await.unwrap_or_default();
+        if let Some(at) = completed_at(item, &history) {
+            entries.push(Entry { item, completed: true, at });
+        }
+    }
+    entries.sort_by_key(|e| std::cmp::Reverse(e.at));
+    entries.truncate(FEED_ENTRIES);
+
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok()).unwrap_or("localhost");
+    let base = format!("http://{}", host);
+    let token = query.token.as_deref().map(str::trim).unwrap_or("");
+    let updated = entries.first().map(|e| e.at).unwrap_or(0);
+    let body: String = entries.iter().map(|e| entry_xml(e, &username, &base, token)).collect();
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\
+         <id>urn:mediatracker:{}</id><title>{}'s MediaTracker</title><updated>{}</updated>\
+         <link rel=\"self\" href=\"{}/feed.xml\"/>{}</feed>",
+        escape(&username),
+        escape(&username),
+        rfc3339(updated),
+        escape(&base),
+        body
+    );
+    ([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")], xml).into_response()
+}
@@ -0,0 +1,151 @@
+// Audiobooks: search through Audible's public catalog API and details (narrators,
+// length, series) from Audnexus, which mirrors Audible by ASIN. Both are keyless.
+// Audible and Audnexus are per region; a book's ASIN is usually the same across
+// regions but not always, so the region is passed through to both.
+
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use crate::metadata::ItemDetails;
+
+const AUDNEXUS: &str = "https://api.audnex.us";
+const REGIONS: &[(&str, &str)] = &[
+    ("us", "com"),
+    ("uk", "co.uk"),
+    ("ca", "ca"),
+    ("au", "com.au"),
+    ("de", "de"),
+    ("fr", "fr"),
+    ("es", "es"),
+    ("it", "it"),
+    ("jp", "co.jp"),
+    ("in", "in"),
+];
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Audiobook {
+    pub asin: String,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub authors: Vec<String>,
+    pub narrators: Vec<String>,
+    pub description: Option<String>,
+    pub cover_url: Option<String>,
+    /// YYYY-MM-DD.
+    pub release_date: Option<String>,
+    pub duration_minutes: Option<u32>,
+    pub publisher: Option<String>,
+    pub series: Option<String>,
+    pub series_position: Option<String>,
+    pub genres: Vec<String>,
+    pub language: Option<String>,
+    pub rating: Option<f64>,
+    pub url: String,
+}
+
+impl Audiobook {
+    pub fn details(&self) -> ItemDetails {
+        ItemDetails {
+            description: self.description.clone(),
+            release_date: self.release_date.clone(),
+            director_or_author: (!self.authors.is_empty()).then(|| self.authors.join(", ")),
+            poster_url: self.cover_url.clone(),
+            narrators: (!self.narrators.is_empty()).then(|| self.narrators.clone()),
+            duration_minutes: self.duration_minutes,
+            ..Default::default()
+        }
+    }
+}
+
+/// Audible's domain suffix for a region code; unknown regions are the US store.
+fn domain(region: &str) -> &'static str {
+    REGIONS.iter().find(|(r, _)| *r == region).map(|(_, d)| *d).unwrap_or("com")
+}
+
+fn region_code(region: &str) -> &'static str {
+    REGIONS.iter().find(|(r, _)| *r == region).map(|(r, _)| *r).unwrap_or("us")
+}
+
+fn text(v: &Value) -> Option<String> {
+    v.as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+fn names(v: &Value) -> Vec<String> {
+    v.as_array().into_iter().flatten().filter_map(|p| text(&p["name"])).collect()
+}
+
+/// ISO timestamps ("2021-05-04T00:00:00.000Z") as just the date.
+fn date(v: &Value) -> Option<String> {
+    text(v).map(|d| d.chars().take(10).collect())
+}
+
+pub fn valid_asin(asin: &str) -> bool {
+    asin.len() == 10 && asin.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn from_audible(v: &Value, region: &str) -> Option<Audiobook> {
+    let asin = text(&v["asin"])?;
+    let series = &v["series"][0];
+    Some(Audiobook {
+        title: text(&v["title"])?,
+        subtitle: text(&v["subtitle"]),
+        authors: names(&v["authors"]),
+        narrators: names(&v["narrators"]),
+        description: text(&v["merchandising_summary"]).map(|d| crate::scrape::html_text(&d)),
+        cover_url: text(&v["product_images"]["500"]),
+        release_date: date(&v["release_date"]),
+        duration_minutes: v["runtime_length_min"].as_u64().map(|m| m as u32),
+        publisher: text(&v["publisher_name"]),
+        series: text(&series["title"]),
+        series_position: text(&series["sequence"]),
+        genres: Vec::new(),
+        language: text(&v["language"]),
+        rating: v["rating"]["overall_distribution"]["display_average_rating"].as_str().and_then(|r| r.parse().ok()),
+        url: format!("https://www.audible.{}/pd/{}", domain(region), asin),
+        asin,
+    })
+}
+
+fn from_audnexus(v: &Value, region: &str) -> Option<Audiobook> {
+    let asin = text(&v["asin"])?;
+    Some(Audiobook {
+        title: text(&v["title"])?,
+        subtitle: text(&v["subtitle"]),
+        authors: names(&v["authors"]),
+        narrators: names(&v["narrators"]),
+        // `summary` is HTML; `description` is a plain but shorter blurb
+        description: text(&v["summary"]).map(|d| crate::scrape::html_text(&d)).or_else(|| text(&v["description"])),
+        cover_url: text(&v["image"]),
+        release_date: date(&v["releaseDate"]),
+        duration_minutes: v["runtimeLengthMin"].as_u64().map(|m| m as u32),
+        publisher: text(&v["publisherName"]),
+        series: text(&v["seriesPrimary"]["name"]),
+        series_position: text(&v["seriesPrimary"]["position"]),
+        genres: v["genres"].as_array().into_iter().flatten().filter(|g| g["type"].as_str() == Some("genre")).filter_map(|g| text(&g["name"])).collect(),
+        language: text(&v["language"]),
+        rating: v["rating"].as_str().and_then(|r| r.parse().ok()).filter(|r: &f64| *r > 0.0),
+        url: format!("https://www.audible.{}/pd/{}", domain(region), asin),
+        asin,
+    })
+}
+
+pub async fn search(client: &Client, query: &str, region: &str) -> Result<Vec<Audiobook>, String> {
+    let url = format!(
+        "https://api.audible.{}/1.0/catalog/products?keywords={}&num_results=20&products_sort_by=Relevance&response_groups=contributors,media,product_attrs,product_desc,rating,series",
+        domain(region),
+        urlencoding::encode(query)
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    Ok(v["products"].as_array().into_iter().flatten().filter_map(|p| from_audible(p, region)).collect())
+}
+
+pub async fn details(client: &Client, asin: &str, region: &str) -> Result<Audiobook, String> {
+    let asin = asin.trim().to_uppercase();
+    if !valid_asin(&asin) {
+        return Err("Invalid ASIN".to_string());
+    }
+    let url = format!("{}/books/{}?region={}", AUDNEXUS, asin, region_code(region));
+    let v = crate::fetch_json(client, &url).await?;
+    from_audnexus(&v, region).ok_or_else(|| "Audiobook not found".to_string())
+}
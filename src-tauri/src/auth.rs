@@ -0,0 +1,199 @@
+// Brute-force protection and the auth event log. Failed `login_user` attempts
+// are counted per username: after `FREE_ATTEMPTS` each further attempt has to
+// wait twice as long as the previous one, and `LOCKOUT_ATTEMPTS` failures lock
+// the name for `LOCKOUT_MS`. Counters live in memory and reset on success.
+//
+// Recovery codes reset a forgotten password. Ten are made at registration and
+// shown once; only their SHA-256 is stored, and each works a single time.
+//
+// The log keeps app logins plus requests the embedded server rejected although
+// they carried credentials (a token, a cookie or a sealed sync request), with
+// the address they came from.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use serde::{Deserialize, Serialize};
+use rand_core::{OsRng, RngCore};
+use crate::database::now_ms;
+use crate::sync::{token_hash, SyncState};
+
+const FREE_ATTEMPTS: u32 = 3;
+const BASE_DELAY_MS: i64 = 1_000;
+const MAX_DELAY_MS: i64 = 5 * 60 * 1000;
+const LOCKOUT_ATTEMPTS: u32 = 10;
+const LOCKOUT_MS: i64 = 15 * 60 * 1000;
+/// A name with no failures for this long starts from zero again.
+const FORGET_AFTER_MS: i64 = 60 * 60 * 1000;
+
+const RECOVERY_CODES: usize = 10;
+/// No 0/O or 1/I, so codes survive being written down.
+const RECOVERY_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Prefix of the error a throttled login fails with; followed by `:<seconds to wait>`.
+pub const TOO_MANY_ATTEMPTS: &str = "TOO_MANY_ATTEMPTS";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthSource {
+    App,
+    Api,
+    Web,
+    Feed,
+    Sync,
+    Pairing,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthOutcome {
+    Success,
+    Failure,
+    /// Refused without checking the password because of earlier failures.
+    Throttled,
+    /// This failure started a lockout.
+    LockedOut,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthEvent {
+    pub at: i64,
+    pub source: AuthSource,
+    /// None for LAN requests, whose token matched no account.
+    pub username: Option<String>,
+    pub outcome: AuthOutcome,
+    /// Client address for requests to the embedded server.
+    pub remote: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl AuthEvent {
+    pub fn login(username: Option<&str>, outcome: AuthOutcome, detail: Option<String>) -> Self {
+        AuthEvent { at: now_ms(), source: AuthSource::App, username: username.map(str::to_string), outcome, remote: None, detail }
+    }
+}
+
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    last_at: i64,
+    blocked_until: i64,
+}
+
+#[derive(Default)]
+pub struct LoginThrottle {
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+fn retry_error(until: i64, now: i64) -> String {
+    format!("{}:{}", TOO_MANY_ATTEMPTS, ((until - now) as f64 / 1000.0).ceil() as i64)
+}
+
+impl LoginThrottle {
+    /// Fails while `username` has to wait before the next attempt.
+    pub fn check(&self, username: &str) -> Result<(), String> {
+        let now = now_ms();
+        let mut failures = self.failures.lock().map_err(|e| e.to_string())?;
+        failures.retain(|_, f| f.blocked_until > now || now - f.last_at < FORGET_AFTER_MS);
+        match failures.get(&username.to_lowercase()) {
+            Some(f) if f.blocked_until > now => Err(retry_error(f.blocked_until, now)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts a failed attempt; returns true when it started a lockout.
+    pub fn failed(&self, username: &str) -> bool {
+        let now = now_ms();
+        let Ok(mut failures) = self.failures.lock() else {
+            return false;
+        };
+        let f = failures.entry(username.to_lowercase()).or_default();
+        f.count += 1;
+        f.last_at = now;
+        if f.count >= LOCKOUT_ATTEMPTS {
+            f.blocked_until = now + LOCKOUT_MS;
+            // Once the lockout ends the name gets a few attempts, then backoff resumes
+            f.count = LOCKOUT_ATTEMPTS - FREE_ATTEMPTS;
+            return true;
+        }
+        if f.count >= FREE_ATTEMPTS {
+            let delay = BASE_DELAY_MS.saturating_mul(1 << (f.count - FREE_ATTEMPTS).min(20));
+            f.blocked_until = now + delay.min(MAX_DELAY_MS);
+        }
+        false
+    }
+
+    pub fn succeeded(&self, username: &str) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.remove(&username.to_lowercase());
+        }
+    }
+}
+
+/// Fresh recovery codes (`XXXX-XXXX-XXXX-XXXX`, 80 bits each) and their hashes.
+pub fn new_recovery_codes() -> (Vec<String>, Vec<String>) {
+    let codes: Vec<String> = (0..RECOVERY_CODES)
+        .map(|_| {
+            let mut bytes = [0u8; 16];
+            OsRng.fill_bytes(&mut bytes);
+            let chars: Vec<char> = bytes.iter().map(|b| RECOVERY_ALPHABET[(*b & 31) as usize] as char).collect();
+            chars.chunks(4).map(|c| c.iter().collect::<String>()).collect::<Vec<_>>().join("-")
+        })
+        .collect();
+    let hashes = codes.iter().map(|c| recovery_code_hash(c)).collect();
+    (codes, hashes)
+}
+
+/// Hash of a code as typed: case, spaces and dashes don't matter.
+pub fn recovery_code_hash(code: &str) -> String {
+    let normalized: String = code.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_uppercase()).collect();
+    token_hash(&normalized)
+}
+
+fn source_for(path: &str) -> Option<AuthSource> {
+    match path {
+        "/sync/pair" => Some(AuthSource::Pairing),
+        "/feed.xml" => Some(AuthSource::Feed),
+        "/ws" => Some(AuthSource::Api),
+        p if p.starts_with("/sync/") => Some(AuthSource::Sync),
+        p if p.starts_with("/api/") => Some(AuthSource::Api),
+        p if p == "/web" || p.starts_with("/web/") => Some(AuthSource::Web),
+        _ => None,
+    }
+}
+
+/// Whether a request tried to authenticate; a browser opening `/web` for the
+/// first time is turned away too, but isn't worth logging.
+fn carried_credentials(req: &Request, source: AuthSource) -> bool {
+    let query = req.uri().query().unwrap_or("");
+    matches!(source, AuthSource::Sync | AuthSource::Pairing)
+        || req.headers().contains_key(header::AUTHORIZATION)
+        || req.headers().contains_key(header::COOKIE)
+        || query.split('&').any(|pair| pair.starts_with("token="))
+}
+
+/// Middleware for the embedded server: logs rejected requests that carried credentials.
+pub(crate) async fn log_rejections(State(state): State<SyncState>, ConnectInfo(remote): ConnectInfo<SocketAddr>, req: Request, next: Next) -> Response {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let source = source_for(&path).filter(|s| carried_credentials(&req, *s));
+    let resp = next.run(req).await;
+    let status = resp.status();
+    if let Some(source) = source.filter(|_| status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN) {
+        let event = AuthEvent {
+            at: now_ms(),
+            source,
+            username: None,
+            outcome: AuthOutcome::Failure,
+            remote: Some(remote.ip().to_string()),
+            detail: Some(format!("{} {} -> {}", method, path, status.as_u16())),
+        };
+        state.db.record_auth_event(event).await;
+    }
+    resp
+}
@@ -0,0 +1,194 @@
+// Board games from BoardGameGeek's XML API2: search by name and "thing" details
+// with player counts, play time and ratings. BGG answers in XML with most data in
+// `value` attributes, and asks for a registered application token; requests
+// without one still work where BGG allows it.
+
+use std::time::Duration;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use reqwest::Client;
+use serde::Serialize;
+use crate::metadata::ItemDetails;
+use crate::ratings::SourceRating;
+
+const API: &str = "https://boardgamegeek.com/xmlapi2";
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BoardGame {
+    pub id: String,
+    pub name: String,
+    pub alt_names: Vec<String>,
+    pub year: Option<i32>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub min_players: Option<u32>,
+    pub max_players: Option<u32>,
+    pub min_playtime: Option<u32>,
+    pub max_playtime: Option<u32>,
+    pub min_age: Option<u32>,
+    pub designers: Vec<String>,
+    pub publishers: Vec<String>,
+    pub categories: Vec<String>,
+    pub mechanics: Vec<String>,
+    /// Average user rating out of 10.
+    pub rating: Option<f64>,
+    pub users_rated: Option<u64>,
+    /// Complexity from 1 (light) to 5 (heavy).
+    pub weight: Option<f64>,
+    pub url: String,
+}
+
+impl BoardGame {
+    pub fn details(&self, now: i64) -> ItemDetails {
+        let mut ratings = std::collections::HashMap::new();
+        if let Some(score) = self.rating.filter(|r| *r > 0.0) {
+            ratings.insert("bgg".to_string(), SourceRating { score, scale: 10.0, votes: self.users_rated, updated_at: Some(now) });
+        }
+        ItemDetails {
+            description: self.description.clone(),
+            release_date: self.year.map(|y| y.to_string()),
+            director_or_author: (!self.designers.is_empty()).then(|| self.designers.join(", ")),
+            poster_url: self.image_url.clone(),
+            ratings,
+            players: self.min_players.map(|min| (min, self.max_players.unwrap_or(min))),
+            playtime: self.min_playtime.or(self.max_playtime).map(|min| (min, self.max_playtime.unwrap_or(min))),
+            ..Default::default()
+        }
+    }
+}
+
+fn attr(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.trim().to_string()))
+        .filter(|v| !v.is_empty())
+}
+
+fn num<T: std::str::FromStr>(e: &BytesStart) -> Option<T> {
+    attr(e, b"value").and_then(|v| v.parse().ok())
+}
+
+/// Positive counts only; BGG writes 0 for unknown.
+fn count(e: &BytesStart) -> Option<u32> {
+    num::<u32>(e).filter(|n| *n > 0)
+}
+
+/// Fields BGG keeps in attributes; text content is handled by the caller.
+fn read_tag(game: &mut BoardGame, name: &[u8], e: &BytesStart) {
+    match name {
+        b"name" => {
+            if let Some(value) = attr(e, b"value") {
+                if attr(e, b"type").as_deref() == Some("primary") && game.name.is_empty() {
+                    game.name = value;
+                } else {
+                    game.alt_names.push(value);
+                }
+            }
+        }
+        b"yearpublished" => game.year = num::<i32>(e).filter(|y| *y != 0),
+        b"minplayers" => game.min_players = count(e),
+        b"maxplayers" => game.max_players = count(e),
+        b"minplaytime" => game.min_playtime = count(e),
+        b"maxplaytime" => game.max_playtime = count(e),
+        b"playingtime" => game.max_playtime = game.max_playtime.or(count(e)),
+        b"minage" => game.min_age = count(e),
+        b"usersrated" => game.users_rated = num(e),
+        b"average" => game.rating = num(e),
+        b"averageweight" => game.weight = num::<f64>(e).filter(|w| *w > 0.0),
+        b"link" => {
+            let list = match attr(e, b"type").as_deref() {
+                Some("boardgamedesigner") => &mut game.designers,
+                Some("boardgamepublisher") => &mut game.publishers,
+                Some("boardgamecategory") => &mut game.categories,
+                Some("boardgamemechanic") => &mut game.mechanics,
+                _ => return,
+            };
+            list.extend(attr(e, b"value"));
+        }
+        _ => {}
+    }
+}
+
+/// Every `<item>` of a search or thing response.
+pub fn parse_items(xml: &str) -> Result<Vec<BoardGame>, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut out = Vec::new();
+    let mut cur: Option<BoardGame> = None;
+    let mut field: Vec<u8> = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                let name = e.local_name().as_ref().to_vec();
+                if name == b"item" {
+                    let id = attr(&e, b"id").unwrap_or_default();
+                    cur = Some(BoardGame { url: format!("https://boardgamegeek.com/boardgame/{}", id), id, ..Default::default() });
+                } else if let Some(game) = cur.as_mut() {
+                    read_tag(game, &name, &e);
+                    field = name;
+                }
+            }
+            Ok(Event::Text(t)) => {
+                if let Some(game) = cur.as_mut() {
+                    let val = t.unescape().unwrap_or_default().trim().to_string();
+                    match field.as_slice() {
+                        // Descriptions are escaped twice ("&amp;#10;" for a newline)
+                        b"description" => game.description = Some(crate::scrape::decode_entities(&val)).filter(|d| !d.is_empty()),
+                        b"image" => game.image_url = Some(val).filter(|u| !u.is_empty()),
+                        b"thumbnail" => game.thumbnail_url = Some(val).filter(|u| !u.is_empty()),
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Event::End(e)) => {
+                if e.local_name().as_ref() == b"item" {
+                    out.extend(cur.take().filter(|g| !g.id.is_empty()));
+                }
+                field.clear();
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid BGG response: {}", e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(out)
+}
+
+async fn get(client: &Client, path: &str, token: Option<&str>) -> Result<String, String> {
+    let mut request = client.get(format!("{}{}", API, path)).header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)");
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    let resp = tokio::time::timeout(Duration::from_secs(15), request.send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    match resp.status().as_u16() {
+        // BGG queues some requests and answers 202 until the result is ready
+        202 => return Err("BGG is preparing the result; try again in a few seconds".to_string()),
+        401 => return Err("BGG requires an application token".to_string()),
+        429 => return Err("BGG rate limit reached; try again later".to_string()),
+        _ if !resp.status().is_success() => return Err(format!("BGG Error: {}", resp.status())),
+        _ => {}
+    }
+    resp.text().await.map_err(|e| e.to_string())
+}
+
+pub async fn search(client: &Client, query: &str, token: Option<&str>) -> Result<Vec<BoardGame>, String> {
+    let xml = get(client, &format!("/search?type=boardgame,boardgameexpansion&query={}", urlencoding::encode(query)), token).await?;
+    parse_items(&xml)
+}
+
+pub async fn thing(client: &Client, id: &str, token: Option<&str>) -> Result<BoardGame, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return Err("Invalid BGG id".to_string());
+    }
+    let xml = get(client, &format!("/thing?id={}&stats=1", id), token).await?;
+    parse_items(&xml)?.into_iter().next().ok_or_else(|| "Board game not found".to_string())
+}
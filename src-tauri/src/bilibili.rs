@@ -0,0 +1,137 @@
+// Bilibili 追番 (followed bangumi) import. The follow list is read from the web
+// API with the user's SESSDATA cookie, which also tells us their account id;
+// each followed season becomes a TV series tagged Anime, placed by its follow
+// status (想看 / 在看 / 看过) with the episode reached as progress. Bilibili is a
+// domestic service, so requests go out on the direct client.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::Value;
+use crate::database::{new_id, now_ms};
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+use crate::ratings::SourceRating;
+
+/// Provider id key of the season id.
+pub const KEY: &str = "bilibili";
+
+const API: &str = "https://api.bilibili.com";
+const PAGE_SIZE: usize = 30;
+/// Follow lists are rarely this long; stops a runaway loop if `total` is wrong.
+const MAX_PAGES: usize = 100;
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BilibiliImport {
+    pub found: usize,
+    pub added: usize,
+    /// Already in the collection.
+    pub skipped: usize,
+    /// Not added: titles that look like items already in the collection.
+    pub probable_duplicates: Vec<crate::dedupe::ImportDecision>,
+}
+
+/// The Cookie header for a bare SESSDATA value or a cookie string copied from the browser.
+fn cookie_header(cookie: &str) -> String {
+    let cookie = cookie.trim();
+    if cookie.contains('=') {
+        cookie.to_string()
+    } else {
+        format!("SESSDATA={}", cookie)
+    }
+}
+
+async fn get(request: RequestBuilder) -> Result<Value, String> {
+    let resp = tokio::time::timeout(Duration::from_secs(15), request.header("Referer", "https://www.bilibili.com/").send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("Bilibili Error: {}", resp.status()));
+    }
+    let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+    match v["code"].as_i64().unwrap_or(0) {
+        0 => Ok(v),
+        -101 => Err("Bilibili cookie is invalid or expired".to_string()),
+        53013 => Err("Bilibili follow list is private".to_string()),
+        code => Err(format!("Bilibili Error: {}", v["message"].as_str().filter(|m| !m.is_empty()).map(str::to_string).unwrap_or_else(|| code.to_string()))),
+    }
+}
+
+/// Account id (mid) of the signed-in user.
+async fn account_id(client: &Client, cookie: &str) -> Result<u64, String> {
+    let v = get(client.get(format!("{}/x/web-interface/nav", API)).header("Cookie", cookie_header(cookie))).await?;
+    if v["data"]["isLogin"].as_bool() != Some(true) {
+        return Err("Bilibili cookie is invalid or expired".to_string());
+    }
+    v["data"]["mid"].as_u64().ok_or_else(|| "Bilibili returned no account id".to_string())
+}
+
+/// "看到第3话 12:34" -> "3"; PVs, specials and unstarted seasons have no number.
+fn progress(text: &str) -> Option<String> {
+    let rest = text.split_once('第')?.1;
+    let number: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (!number.is_empty()).then_some(number)
+}
+
+fn https(url: &str) -> String {
+    match url.strip_prefix("http://") {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    }
+}
+
+/// One entry of the follow list as a new item.
+pub fn parse_entry(v: &Value, now: i64) -> Option<MediaItem> {
+    let season_id = v["season_id"].as_u64()?;
+    let title = v["title"].as_str().map(str::trim).filter(|t| !t.is_empty())?;
+    let mut tags = vec!["Anime".to_string()];
+    tags.extend(v["styles"].as_array().into_iter().flatten().filter_map(|s| s.as_str()).map(str::to_string));
+    let (category, status) = match v["follow_status"].as_u64() {
+        Some(1) => (Some(CollectionCategory::ToWatch), "To Watch"),
+        Some(3) => (Some(CollectionCategory::Watched), "Watched"),
+        _ => (None, "Watching"),
+    };
+    let ratings = v["rating"]["score"].as_f64().filter(|s| *s > 0.0).map(|score| {
+        let rating = SourceRating { score, scale: 10.0, votes: v["rating"]["count"].as_u64(), updated_at: Some(now) };
+        HashMap::from([(KEY.to_string(), rating)])
+    });
+    Some(MediaItem {
+        id: new_id(),
+        title: title.to_string(),
+        description: v["evaluate"].as_str().unwrap_or_default().trim().to_string(),
+        // "2023-10-01 00:00:00"
+        release_date: v["publish"]["pub_time"].as_str().and_then(|t| t.split(' ').next()).unwrap_or_default().to_string(),
+        media_type: MediaType::TvSeries,
+        is_ongoing: v["is_finish"].as_i64() == Some(0),
+        latest_update_info: v["new_ep"]["index_show"].as_str().filter(|s| !s.is_empty()).map(str::to_string),
+        category,
+        status: Some(status.to_string()),
+        user_progress: v["progress"].as_str().and_then(progress),
+        saved_at: Some(now),
+        poster_url: v["cover"].as_str().filter(|c| !c.is_empty()).map(https),
+        ratings,
+        tags: Some(tags),
+        provider_ids: Some(HashMap::from([(KEY.to_string(), season_id.to_string())])),
+        ..Default::default()
+    })
+}
+
+/// Every anime season the cookie's account follows, as new items.
+pub async fn follow_list(client: &Client, cookie: &str) -> Result<Vec<MediaItem>, String> {
+    let mid = account_id(client, cookie).await?;
+    let now = now_ms();
+    let mut items = Vec::new();
+    for page in 1..=MAX_PAGES {
+        let url = format!("{}/x/space/bangumi/follow/list?type=1&follow_status=0&pn={}&ps={}&vmid={}", API, page, PAGE_SIZE, mid);
+        let v = get(client.get(url).header("Cookie", cookie_header(cookie))).await?;
+        let list = v["data"]["list"].as_array().cloned().unwrap_or_default();
+        items.extend(list.iter().filter_map(|entry| parse_entry(entry, now)));
+        let total = v["data"]["total"].as_u64().unwrap_or(0) as usize;
+        if list.len() < PAGE_SIZE || page * PAGE_SIZE >= total {
+            break;
+        }
+    }
+    Ok(items)
+}
@@ -0,0 +1,63 @@
+// Optional clipboard watcher (`Settings::clipboard_watch_enabled`). When a
+// Douban/IMDb/Bangumi/TMDB detail page link is copied, the page is scraped (and
+// enriched from the provider API when we have an id for it) and offered to the
+// frontend as a ready-made draft, plus a system notification.
+
+use std::sync::Arc;
+use std::time::Duration;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use crate::database::Database;
+use crate::scrape::ScrapedPage;
+
+pub const CLIPBOARD_EVENT: &str = "clipboard-link";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClipboardLink {
+    pub url: String,
+    pub page: Option<ScrapedPage>,
+    pub error: Option<String>,
+}
+
+/// The copied text if it is a single link to a page we know how to import.
+pub fn supported_link(text: &str) -> Option<String> {
+    let text = text.trim();
+    let is_url = (text.starts_with("http://") || text.starts_with("https://")) && !text.contains(char::is_whitespace);
+    (is_url && !crate::scrape::provider_ids_from_url(text).is_empty()).then(|| text.to_string())
+}
+
+/// Spawns the watcher loop; it idles while the setting is off.
+pub fn start(app: AppHandle, db: Arc<Database>) {
+    tauri::async_runtime::spawn(async move {
+        // None while disabled, so whatever was copied before enabling is ignored
+        let mut last: Option<String> = None;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if !db.get_settings().await.clipboard_watch_enabled {
+                last = None;
+                continue;
+            }
+            let Ok(text) = app.clipboard().read_text() else {
+                continue;
+            };
+            let previous = last.replace(text.clone());
+            if previous.is_none() || previous.as_deref() == Some(text.as_str()) {
+                continue;
+            }
+            let Some(url) = supported_link(&text) else {
+                continue;
+            };
+            let link = match crate::scrape::draft_for(&app, &url).await {
+                Ok(page) => ClipboardLink { url, page: Some(page), error: None },
+                Err(e) => ClipboardLink { url, page: None, error: Some(e) },
+            };
+            let title = link.page.as_ref().and_then(|p| p.title.clone()).unwrap_or_else(|| link.url.clone());
+            let _ = app.emit(CLIPBOARD_EVENT, link);
+            crate::notify::general(&app, &db, "Add to collection?", &title).await;
+        }
+    });
+}
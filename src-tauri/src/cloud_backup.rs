@@ -0,0 +1,375 @@
+// Off-site backups to any S3-compatible store (AWS S3, MinIO, Backblaze B2, ...).
+// The scheduler's `cloudBackup` job zips collection.json, plus the cover cache
+// when enabled, and uploads it; archives larger than one part go up as a
+// multipart upload. Requests are signed with AWS Signature V4 directly, so no
+// SDK is needed. The secret key lives in the keychain (`secrets::S3_SECRET`);
+// settings only keep where to upload and the key id.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::{Client, Method};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use crate::database::{now_ms, Database};
+
+// S3 requires at least 5 MiB for every part but the last
+const PART_SIZE: usize = 8 * 1024 * 1024;
+const ARCHIVE_PREFIX: &str = "mediatracker-";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct S3Config {
+    /// e.g. `https://s3.us-east-1.amazonaws.com`, `https://s3.us-west-004.backblazeb2.com`, `http://nas:9000`
+    pub endpoint: String,
+    /// MinIO accepts any region; AWS and B2 need the bucket's.
+    pub region: String,
+    pub bucket: String,
+    pub access_key_id: String,
+    /// Only passed in (to save it, or to test an unsaved config); saving moves it
+    /// to the keychain, see `move_secret_to_keychain`.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub secret_access_key: String,
+    /// Prepended to object keys, e.g. `backups/laptop/`.
+    pub prefix: String,
+    /// Address objects as `endpoint/bucket/key` (MinIO) instead of `bucket.endpoint/key`.
+    pub path_style: bool,
+    /// Also upload the local cover cache.
+    pub include_images: bool,
+    /// Remote archives to keep; older ones are deleted after a successful upload. 0 keeps all.
+    pub keep: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteBackup {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadedBackup {
+    pub key: String,
+    pub size: u64,
+    pub parts: usize,
+    pub images: usize,
+    pub pruned: usize,
+}
+
+/// Moves a secret key given with the config into the keychain. It stays in the
+/// config only while the keychain can't take it. Returns true if the config changed.
+pub fn move_secret_to_keychain(cfg: &mut S3Config) -> bool {
+    if cfg.secret_access_key.trim().is_empty() {
+        return false;
+    }
+    match crate::secrets::set(crate::secrets::S3_SECRET, &cfg.secret_access_key) {
+        Ok(()) => {
+            cfg.secret_access_key.clear();
+            true
+        }
+        Err(e) => {
+            eprintln!("Could not move the cloud backup secret to the keychain: {}", e);
+            false
+        }
+    }
+}
+
+impl S3Config {
+    /// The config with the secret key from the keychain when none was passed, checked for completeness.
+    fn resolved(&self) -> Result<S3Config, String> {
+        let mut cfg = self.clone();
+        cfg.secret_access_key = crate::secrets::resolve(Some(cfg.secret_access_key), crate::secrets::S3_SECRET).unwrap_or_default();
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let missing = [
+            ("endpoint", &self.endpoint),
+            ("bucket", &self.bucket),
+            ("access key", &self.access_key_id),
+            ("secret key", &self.secret_access_key),
+        ]
+        .iter()
+        .find(|(_, v)| v.trim().is_empty())
+        .map(|(name, _)| *name);
+        match missing {
+            Some(name) => Err(format!("Cloud backup {} is not set", name)),
+            None if !self.endpoint.starts_with("http://") && !self.endpoint.starts_with("https://") => {
+                Err("Cloud backup endpoint must start with http:// or https://".to_string())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn region(&self) -> &str {
+        if self.region.trim().is_empty() { "us-east-1" } else { self.region.trim() }
+    }
+
+    /// (base URL, host header, canonical path) for an object key; an empty key addresses the bucket.
+    fn locate(&self, key: &str) -> (String, String, String) {
+        let endpoint = self.endpoint.trim().trim_end_matches('/');
+        let (scheme, host) = endpoint.split_once("://").unwrap_or(("https", endpoint));
+        let encoded_key: Vec<String> = key.split('/').map(|seg| urlencoding::encode(seg).into_owned()).collect();
+        let encoded_key = encoded_key.join("/");
+        let (host, path) = if self.path_style {
+            (host.to_string(), format!("/{}/{}", self.bucket, encoded_key))
+        } else {
+            (format!("{}.{}", self.bucket, host), format!("/{}", encoded_key))
+        };
+        (format!("{}://{}{}", scheme, host, path), host, path)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes()).as_ref().to_vec()
+}
+
+/// `20240131T235959Z` for a unix time in milliseconds.
+fn amz_date(ms: i64) -> String {
+    let secs = ms.div_euclid(1000);
+    let (y, m, d) = crate::updates::civil_from_days(secs.div_euclid(86_400));
+    let t = secs.rem_euclid(86_400);
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", y, m, d, t / 3600, t % 3600 / 60, t % 60)
+}
+
+/// Signs and sends one request; non-2xx answers become errors carrying S3's message.
+async fn send(client: &Client, cfg: &S3Config, method: Method, key: &str, query: &[(&str, String)], body: Vec<u8>) -> Result<reqwest::Response, String> {
+    let (base, host, path) = cfg.locate(key);
+    let mut params: Vec<(String, String)> = query
+        .iter()
+        .map(|(k, v)| (urlencoding::encode(k).into_owned(), urlencoding::encode(v).into_owned()))
+        .collect();
+    params.sort();
+    let canonical_query = params.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+
+    let now = amz_date(now_ms());
+    let date = &now[..8];
+    let payload_hash = hex(&Sha256::digest(&body));
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method, path, canonical_query, host, payload_hash, now, signed_headers, payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, cfg.region());
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", now, scope, hex(&Sha256::digest(canonical_request.as_bytes())));
+    let mut signing_key = hmac_sha256(format!("AWS4{}", cfg.secret_access_key.trim()).as_bytes(), date);
+    for part in [cfg.region(), "s3", "aws4_request"] {
+        signing_key = hmac_sha256(&signing_key, part);
+    }
+    let signature = hex(&hmac_sha256(&signing_key, &string_to_sign));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        cfg.access_key_id.trim(),
+        scope,
+        signed_headers,
+        signature
+    );
+
+    let url = if canonical_query.is_empty() { base } else { format!("{}?{}", base, canonical_query) };
+    let resp = client
+        .request(method, &url)
+        .header("x-amz-date", &now)
+        .header("x-amz-content-sha256", &payload_hash)
+        .header("authorization", authorization)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    let message = xml_values(&text, "Message").into_iter().next().unwrap_or(text);
+    Err(format!("S3 error {}: {}", status, message))
+}
+
+/// Text of every `<tag>` element in an S3 XML response.
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut values = Vec::new();
+    let mut inside = false;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => inside = e.local_name().as_ref() == tag.as_bytes(),
+            Ok(Event::Text(t)) if inside => {
+                if let Ok(text) = t.unescape() {
+                    values.push(text.into_owned());
+                }
+            }
+            Ok(Event::End(_)) => inside = false,
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+    values
+}
+
+/// Archives we uploaded under the configured prefix, oldest first.
+pub async fn list(client: &Client, cfg: &S3Config) -> Result<Vec<RemoteBackup>, String> {
+    let cfg = &cfg.resolved()?;
+    let prefix = format!("{}{}", cfg.prefix, ARCHIVE_PREFIX);
+    let mut backups = Vec::new();
+    let mut token: Option<String> = None;
+    loop {
+        let mut query = vec![("list-type", "2".to_string()), ("prefix", prefix.clone())];
+        if let Some(t) = &token {
+            query.push(("continuation-token", t.clone()));
+        }
+        let xml = send(client, cfg, Method::GET, "", &query, Vec::new()).await?.text().await.map_err(|e| e.to_string())?;
+        let keys = xml_values(&xml, "Key");
+        let sizes = xml_values(&xml, "Size");
+        let dates = xml_values(&xml, "LastModified");
+        for (i, key) in keys.into_iter().enumerate() {
+            backups.push(RemoteBackup {
+                key,
+                size: sizes.get(i).and_then(|s| s.parse().ok()).unwrap_or(0),
+                last_modified: dates.get(i).cloned(),
+            });
+        }
+        token = xml_values(&xml, "NextContinuationToken").into_iter().next();
+        if token.is_none() {
+            break;
+        }
+    }
+    // Keys embed the upload time, so name order is age order
+    backups.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(backups)
+}
+
+/// Zips collection.json and, optionally, the cover cache into `target`. Returns the image count.
+fn write_archive(target: &Path, collection: &[u8], images_dir: Option<&Path>) -> Result<usize, String> {
+    use zip::write::SimpleFileOptions;
+    use zip::CompressionMethod;
+    let file = std::fs::File::create(target).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated).large_file(true);
+    zip.start_file("collection.json", deflated).map_err(|e| e.to_string())?;
+    zip.write_all(collection).map_err(|e| e.to_string())?;
+
+    let mut images = 0;
+    if let Some(dir) = images_dir {
+        // Covers are already compressed; storing them keeps the backup fast
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !path.is_file() || name.ends_with(".tmp") {
+                continue;
+            }
+            let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+            zip.start_file(format!("covers/{}", name), stored).map_err(|e| e.to_string())?;
+            zip.write_all(&bytes).map_err(|e| e.to_string())?;
+            images += 1;
+        }
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(images)
+}
+
+async fn upload_file(client: &Client, cfg: &S3Config, key: &str, path: &Path, size: u64) -> Result<usize, String> {
+    if size <= PART_SIZE as u64 {
+        let body = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+        send(client, cfg, Method::PUT, key, &[], body).await?;
+        return Ok(1);
+    }
+    let xml = send(client, cfg, Method::POST, key, &[("uploads", String::new())], Vec::new())
+        .await?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    let upload_id = xml_values(&xml, "UploadId").into_iter().next().ok_or_else(|| "S3 did not return an upload id".to_string())?;
+    match upload_parts(client, cfg, key, path, &upload_id).await {
+        Ok(parts) => Ok(parts),
+        Err(e) => {
+            // Otherwise the uploaded parts keep costing storage
+            let _ = send(client, cfg, Method::DELETE, key, &[("uploadId", upload_id)], Vec::new()).await;
+            Err(e)
+        }
+    }
+}
+
+async fn upload_parts(client: &Client, cfg: &S3Config, key: &str, path: &Path, upload_id: &str) -> Result<usize, String> {
+    let mut file = tokio::fs::File::open(path).await.map_err(|e| e.to_string())?;
+    let mut etags = Vec::new();
+    loop {
+        let mut chunk = Vec::with_capacity(PART_SIZE);
+        (&mut file).take(PART_SIZE as u64).read_to_end(&mut chunk).await.map_err(|e| e.to_string())?;
+        if chunk.is_empty() {
+            break;
+        }
+        let number = etags.len() + 1;
+        let query = [("partNumber", number.to_string()), ("uploadId", upload_id.to_string())];
+        let resp = send(client, cfg, Method::PUT, key, &query, chunk).await?;
+        let etag = resp
+            .headers()
+            .get("etag")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| format!("S3 returned no ETag for part {}", number))?
+            .to_string();
+        etags.push(etag);
+    }
+    let parts: String = etags
+        .iter()
+        .enumerate()
+        .map(|(i, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", i + 1, etag))
+        .collect();
+    let body = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts);
+    let resp = send(client, cfg, Method::POST, key, &[("uploadId", upload_id.to_string())], body.into_bytes()).await?;
+    // S3 can report a failed completion inside a 200 response
+    let text = resp.text().await.unwrap_or_default();
+    if text.contains("<Error>") {
+        let message = xml_values(&text, "Message").into_iter().next().unwrap_or(text);
+        return Err(format!("S3 error: {}", message));
+    }
+    Ok(etags.len())
+}
+
+/// Builds an archive and uploads it, then applies the retention setting.
+pub async fn run(client: &Client, db: &Database, cfg: &S3Config, images_dir: &Path) -> Result<UploadedBackup, String> {
+    let cfg = &cfg.resolved()?;
+    let started = now_ms();
+    let key = format!("{}{}{}.zip", cfg.prefix, ARCHIVE_PREFIX, amz_date(started));
+    let archive: PathBuf = db.backup_dir()?.join(format!("cloud-{}.zip", started));
+    let collection = db.snapshot_json().await?;
+    let images_dir = cfg.include_images.then(|| images_dir.to_path_buf());
+    let target = archive.clone();
+    let images = tauri::async_runtime::spawn_blocking(move || write_archive(&target, &collection, images_dir.as_deref()))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r);
+
+    let result = async {
+        let images = images?;
+        let size = tokio::fs::metadata(&archive).await.map_err(|e| e.to_string())?.len();
+        let parts = upload_file(client, cfg, &key, &archive, size).await?;
+        Ok::<_, String>((size, parts, images))
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&archive).await;
+    let (size, parts, images) = result?;
+
+    let mut pruned = 0;
+    if cfg.keep > 0 {
+        let existing = list(client, cfg).await?;
+        let excess = existing.len().saturating_sub(cfg.keep);
+        for old in &existing[..excess] {
+            if send(client, cfg, Method::DELETE, &old.key, &[], Vec::new()).await.is_ok() {
+                pruned += 1;
+            }
+        }
+    }
+    Ok(UploadedBackup { key, size, parts, images, pruned })
+}
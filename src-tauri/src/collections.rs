@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use crate::models::{MediaItem, MediaType};
+
+/// A user-defined group of items. Collections can nest via `parent_id`, and
+/// `item_ids` holds the manual order of the members.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub cover_url: Option<String>,
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub item_ids: Vec<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Fields accepted by `create_collection` / `update_collection`.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionInput {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub cover_url: Option<String>,
+    pub parent_id: Option<String>,
+    pub item_ids: Option<Vec<String>>,
+}
+
+/// True if making `parent_id` the parent of `id` would create a cycle.
+pub fn creates_cycle(collections: &[Collection], id: &str, parent_id: Option<&str>) -> bool {
+    let mut cur = parent_id.map(|s| s.to_string());
+    let mut hops = 0;
+    while let Some(p) = cur {
+        if p == id || hops > collections.len() {
+            return true;
+        }
+        cur = collections.iter().find(|c| c.id == p).and_then(|c| c.parent_id.clone());
+        hops += 1;
+    }
+    false
+}
+
+/// Converts the legacy `is_collection` container items and `parent_collection_id`
+/// links into Collection records. A link to a container that doesn't exist yet
+/// (its save hasn't arrived) stays on the item until it does. Returns true if
+/// anything was migrated.
+pub fn absorb_legacy_flags(items: &mut Vec<MediaItem>, collections: &mut Vec<Collection>, now: i64) -> bool {
+    let mut changed = false;
+    let mut i = 0;
+    while i < items.len() {
+        if items[i].is_collection != Some(true) {
+            i += 1;
+            continue;
+        }
+        let container = items.remove(i);
+        changed = true;
+        let cover_url = container.custom_poster_url.clone().or(container.poster_url.clone());
+        let description = Some(container.description.clone()).filter(|d| !d.trim().is_empty());
+        match collections.iter_mut().find(|c| c.id == container.id) {
+            Some(c) => {
+                c.name = container.title.clone();
+                c.description = description;
+                c.cover_url = cover_url;
+                c.parent_id = container.parent_collection_id.clone();
+                c.updated_at = now;
+            }
+            None => collections.push(Collection {
+                id: container.id.clone(),
+                name: container.title.clone(),
+                description,
+                cover_url,
+                parent_id: container.parent_collection_id.clone(),
+                item_ids: Vec::new(),
+                created_at: container.saved_at.unwrap_or(now),
+                updated_at: now,
+            }),
+        }
+    }
+
+    for item in items.iter_mut() {
+        let Some(pid) = item.parent_collection_id.clone() else {
+            continue;
+        };
+        if !collections.iter().any(|c| c.id == pid) {
+            continue;
+        }
+        item.parent_collection_id = None;
+        changed = true;
+        // An item sits in at most one collection in the legacy model
+        leave_collections(collections, &item.id, now);
+        if let Some(c) = collections.iter_mut().find(|c| c.id == pid) {
+            c.item_ids.push(item.id.clone());
+            c.updated_at = now;
+        }
+    }
+    changed
+}
+
+/// Takes `item_id` out of every collection. Returns true if it was in one.
+pub fn leave_collections(collections: &mut [Collection], item_id: &str, now: i64) -> bool {
+    let mut changed = false;
+    for c in collections.iter_mut() {
+        let before = c.item_ids.len();
+        c.item_ids.retain(|id| id != item_id);
+        if c.item_ids.len() != before {
+            c.updated_at = now;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Renders collections back into the flag-based shape the item grid still consumes:
+/// one container item per collection, and `parent_collection_id` on members.
+pub fn project_legacy_flags(items: &[MediaItem], collections: &[Collection]) -> Vec<MediaItem> {
+    let mut out: Vec<MediaItem> = Vec::with_capacity(items.len() + collections.len());
+    for c in collections {
+        let first = c.item_ids.first().and_then(|id| items.iter().find(|i| i.id == *id));
+        out.push(MediaItem {
+            id: c.id.clone(),
+            title: c.name.clone(),
+            director_or_author: first.map(|i| i.director_or_author.clone()).unwrap_or_default(),
+            description: c.description.clone().unwrap_or_default(),
+            release_date: first.map(|i| i.release_date.clone()).unwrap_or_default(),
+            media_type: first.map(|i| i.media_type.clone()).unwrap_or(MediaType::Other),
+            category: first.and_then(|i| i.category.clone()),
+            saved_at: Some(c.created_at),
+            poster_url: c.cover_url.clone().or_else(|| first.and_then(|i| i.poster_url.clone())),
+            last_edited_at: Some(c.updated_at),
+            parent_collection_id: c.parent_id.clone(),
+            is_collection: Some(true),
+            ..Default::default()
+        });
+    }
+    for item in items {
+        let mut it = item.clone();
+        // Falls back to a link still waiting for its container
+        it.parent_collection_id = collections
+            .iter()
+            .find(|c| c.item_ids.contains(&item.id))
+            .map(|c| c.id.clone())
+            .or_else(|| item.parent_collection_id.clone());
+        out.push(it);
+    }
+    out
+}
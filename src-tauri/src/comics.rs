@@ -0,0 +1,100 @@
+// Volume and chapter progress for comics and manga. Each chapter read is kept
+// with when it was read, so re-reading or skipping around is recorded; the
+// current position is the highest chapter read, which is also written back to
+// the free-text `user_progress` the rest of the app shows.
+
+use serde::{Deserialize, Serialize};
+
+/// Ranges like "1-500" expand to at most this many chapters.
+const MAX_RANGE: u32 = 5000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterRead {
+    /// Normalized number: "12", "12.5".
+    pub chapter: String,
+    pub volume: Option<u32>,
+    pub read_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingProgress {
+    /// Position: volume and chapter of the highest chapter read.
+    pub volume: Option<u32>,
+    pub chapter: Option<String>,
+    /// Ordered by chapter number.
+    #[serde(default)]
+    pub read: Vec<ChapterRead>,
+}
+
+fn number(chapter: &str) -> f64 {
+    chapter.parse().unwrap_or(f64::MAX)
+}
+
+/// "Ch. 12", "chapter 12.5", "第12话" or "12" as "12" / "12.5".
+pub fn normalize_chapter(input: &str) -> Option<String> {
+    let start = input.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = input[start..].chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+    let n: f64 = digits.trim_end_matches('.').parse().ok()?;
+    Some(if n.fract() == 0.0 { format!("{}", n as u64) } else { format!("{}", n) })
+}
+
+/// Chapters named by `inputs`; an entry like "1-20" stands for every whole chapter in between.
+pub fn expand(inputs: &[String]) -> Result<Vec<String>, String> {
+    let mut out = Vec::new();
+    for input in inputs {
+        let parts: Vec<&str> = input.split('-').map(str::trim).collect();
+        match parts.as_slice() {
+            [from, to] if !from.is_empty() && !to.is_empty() => {
+                let from: u32 = normalize_chapter(from).and_then(|c| c.parse().ok()).ok_or_else(|| format!("Invalid chapter range: {}", input))?;
+                let to: u32 = normalize_chapter(to).and_then(|c| c.parse().ok()).ok_or_else(|| format!("Invalid chapter range: {}", input))?;
+                if to < from || to - from >= MAX_RANGE {
+                    return Err(format!("Invalid chapter range: {}", input));
+                }
+                out.extend((from..=to).map(|n| n.to_string()));
+            }
+            _ => out.push(normalize_chapter(input).ok_or_else(|| format!("Invalid chapter: {}", input))?),
+        }
+    }
+    Ok(out)
+}
+
+impl ReadingProgress {
+    /// Marks `chapters` read (or unread); returns how many changed.
+    pub fn mark(&mut self, chapters: &[String], volume: Option<u32>, read: bool, now: i64) -> usize {
+        let mut changed = 0;
+        for chapter in chapters {
+            let existing = self.read.iter().position(|r| &r.chapter == chapter);
+            match (existing, read) {
+                (None, true) => {
+                    self.read.push(ChapterRead { chapter: chapter.clone(), volume, read_at: now });
+                    changed += 1;
+                }
+                (Some(i), false) => {
+                    self.read.remove(i);
+                    changed += 1;
+                }
+                (Some(i), true) if volume.is_some() && self.read[i].volume != volume => {
+                    self.read[i].volume = volume;
+                    changed += 1;
+                }
+                _ => {}
+            }
+        }
+        self.read.sort_by(|a, b| number(&a.chapter).total_cmp(&number(&b.chapter)));
+        let last = self.read.last();
+        self.chapter = last.map(|r| r.chapter.clone());
+        // A chapter without a volume keeps the volume we were in
+        self.volume = last.and_then(|r| r.volume).or(if last.is_some() { self.volume } else { None });
+        changed
+    }
+
+    pub fn text(&self) -> Option<String> {
+        let chapter = self.chapter.as_ref()?;
+        Some(match self.volume {
+            Some(v) => format!("Vol. {} Ch. {}", v, chapter),
+            None => format!("Ch. {}", chapter),
+        })
+    }
+}
@@ -0,0 +1,90 @@
+// Age and content ratings: the certification TMDB reports (MPAA for films, US
+// TV ratings for shows), Bangumi's adult flag, or Douban-style tags on the item
+// when no provider says anything. Each rating is mapped to a minimum age so
+// smart lists can filter on it and a user can hide mature items altogether.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::models::MediaItem;
+
+/// Minimum age from which a rating counts as mature.
+pub const MATURE_AGE: u8 = 17;
+
+/// Tags (as Douban users write them) that imply an age rating.
+const MATURE_TAGS: &[(&str, u8)] = &[("情色", 18), ("18禁", 18), ("r18", 18), ("限制级", 17), ("r级", 17), ("cult", 16), ("血腥", 16)];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentRating {
+    /// "MPAA", "US-TV", "Bangumi", "AO3" or "Tags".
+    pub system: String,
+    /// As the system writes it: "PG-13", "TV-MA", "R18".
+    pub value: String,
+    pub min_age: Option<u8>,
+}
+
+impl ContentRating {
+    fn new(system: &str, value: &str) -> Self {
+        let min_age = min_age(value);
+        ContentRating { system: system.to_string(), value: value.to_string(), min_age }
+    }
+
+    pub fn is_mature(&self) -> bool {
+        self.min_age.map(|a| a >= MATURE_AGE).unwrap_or(false)
+    }
+}
+
+/// What a user has chosen not to see in their collection.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentFilter {
+    pub hide_mature: bool,
+}
+
+impl ContentFilter {
+    pub fn allows(&self, item: &MediaItem) -> bool {
+        !(self.hide_mature && item.content_rating.as_ref().map(ContentRating::is_mature).unwrap_or(false))
+    }
+}
+
+fn min_age(value: &str) -> Option<u8> {
+    let age = match value.trim().to_uppercase().as_str() {
+        "G" | "TV-Y" | "TV-G" => 0,
+        "TV-Y7" | "TV-Y7-FV" => 7,
+        "PG" | "TV-PG" => 10,
+        "PG-13" => 13,
+        "TV-14" => 14,
+        "R" | "TV-MA" => 17,
+        "NC-17" | "R18" | "X" => 18,
+        other => return other.trim_end_matches('+').parse().ok().filter(|a| *a <= 21),
+    };
+    Some(age)
+}
+
+/// US certification from a TMDB movie with `release_dates` appended.
+pub fn from_tmdb_movie(v: &Value) -> Option<ContentRating> {
+    let us = v["release_dates"]["results"].as_array()?.iter().find(|r| r["iso_3166_1"].as_str() == Some("US"))?;
+    let cert = us["release_dates"].as_array()?.iter().filter_map(|d| d["certification"].as_str()).find(|c| !c.trim().is_empty())?;
+    Some(ContentRating::new("MPAA", cert.trim()))
+}
+
+/// US rating from a TMDB show with `content_ratings` appended.
+pub fn from_tmdb_tv(v: &Value) -> Option<ContentRating> {
+    let us = v["content_ratings"]["results"].as_array()?.iter().find(|r| r["iso_3166_1"].as_str() == Some("US"))?;
+    let rating = us["rating"].as_str().map(str::trim).filter(|r| !r.is_empty())?;
+    Some(ContentRating::new("US-TV", rating))
+}
+
+/// Bangumi only flags adult subjects.
+pub fn from_bangumi(v: &Value) -> Option<ContentRating> {
+    (v["nsfw"].as_bool() == Some(true)).then(|| ContentRating::new("Bangumi", "R18"))
+}
+
+/// Strictest rating implied by the item's tags.
+pub fn from_tags(tags: &[String]) -> Option<ContentRating> {
+    let (tag, age) = tags
+        .iter()
+        .filter_map(|t| MATURE_TAGS.iter().find(|(m, _)| t.trim().to_lowercase() == *m).map(|(_, a)| (t.trim(), *a)))
+        .max_by_key(|(_, a)| *a)?;
+    Some(ContentRating { system: "Tags".to_string(), value: tag.to_string(), min_age: Some(age) })
+}
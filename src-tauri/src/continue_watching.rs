@@ -0,0 +1,66 @@
+// The "continue watching" queue: items in progress, most recently touched
+// first. Recency comes from the newest progress event, tracked session or
+// edit and decays with a two-week half-life; a snoozed item sinks below the
+// rest until its snooze runs out.
+
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::activity::{ActivityEvent, ActivityKind};
+use crate::models::MediaItem;
+use crate::statuses::{StatusDef, StatusKind};
+use crate::watch_time::TimeSession;
+
+const HALF_LIFE_DAYS: f64 = 14.0;
+/// Snooze length when the caller gives none.
+pub const DEFAULT_SNOOZE_DAYS: u32 = 7;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueEntry {
+    pub item: MediaItem,
+    pub last_activity_at: i64,
+    /// 1.0 for something touched just now, halving every two weeks; 0 while snoozed.
+    pub priority: f64,
+    pub snoozed_until: Option<i64>,
+}
+
+fn in_progress(item: &MediaItem, statuses: &[StatusDef]) -> bool {
+    let started = item.user_progress.as_deref().map(|p| !p.trim().is_empty()).unwrap_or(false);
+    match item.status.as_deref().and_then(|s| crate::statuses::find(statuses, s)) {
+        Some(s) => s.kind == StatusKind::InProgress || (s.kind == StatusKind::Planned && started),
+        None => item.category.is_none() && started,
+    }
+}
+
+pub fn queue(
+    items: &[MediaItem],
+    statuses: &[StatusDef],
+    activity: &[ActivityEvent],
+    sessions: &[TimeSession],
+    snoozes: &HashMap<String, i64>,
+    now: i64,
+) -> Vec<QueueEntry> {
+    let progress = activity
+        .iter()
+        .filter(|e| matches!(e.kind, ActivityKind::Started | ActivityKind::EpisodeWatched))
+        .map(|e| (e.item_id.as_str(), e.at));
+    let watched = sessions.iter().map(|s| (s.item_id.as_str(), s.ended_at.unwrap_or(now).max(s.started_at)));
+    let mut last: HashMap<&str, i64> = HashMap::new();
+    for (id, at) in progress.chain(watched) {
+        let e = last.entry(id).or_insert(at);
+        *e = (*e).max(at);
+    }
+    let mut entries: Vec<QueueEntry> = items
+        .iter()
+        .filter(|i| in_progress(i, statuses))
+        .map(|item| {
+            let last_activity_at = last.get(item.id.as_str()).copied().unwrap_or(0).max(item.last_edited_at.unwrap_or(0));
+            let snoozed_until = snoozes.get(&item.id).copied().filter(|until| *until > now);
+            let age_days = (now - last_activity_at).max(0) as f64 / crate::database::DAY_MS as f64;
+            let priority = if snoozed_until.is_some() { 0.0 } else { 0.5f64.powf(age_days / HALF_LIFE_DAYS) };
+            QueueEntry { item: item.clone(), last_activity_at, priority, snoozed_until }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.priority.total_cmp(&a.priority).then(b.last_activity_at.cmp(&a.last_activity_at)));
+    entries
+}
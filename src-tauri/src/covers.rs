@@ -0,0 +1,131 @@
+// Cover candidates for the "pick a cover" dialog. Every source is queried at the
+// same time; a source that fails or times out simply contributes nothing.
+
+use std::collections::HashSet;
+use reqwest::Client;
+use serde::Serialize;
+use crate::models::MediaType;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverCandidate {
+    pub url: String,
+    /// "douban", "wikipedia", "omdb", "bangumi" or the image search provider name.
+    pub source: String,
+    /// Page or title the image belongs to, when the source tells us.
+    pub label: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl CoverCandidate {
+    fn new(url: &str, source: &str, label: Option<&str>) -> Self {
+        CoverCandidate {
+            url: url.to_string(),
+            source: source.to_string(),
+            label: label.filter(|l| !l.is_empty()).map(str::to_string),
+            width: None,
+            height: None,
+        }
+    }
+}
+
+// Same image regardless of scheme, query-less CDN variants or trailing slash
+fn url_key(url: &str) -> String {
+    let url = url.split("://").nth(1).unwrap_or(url);
+    url.split(['?', '#']).next().unwrap_or(url).trim_end_matches('/').to_ascii_lowercase()
+}
+
+/// Drops duplicate URLs, keeping the first occurrence (sources are ordered by preference).
+pub fn dedupe(candidates: Vec<CoverCandidate>) -> Vec<CoverCandidate> {
+    let mut seen = HashSet::new();
+    candidates
+        .into_iter()
+        .filter(|c| c.url.starts_with("http") && seen.insert(url_key(&c.url)))
+        .collect()
+}
+
+pub async fn douban(client: &Client, title: &str) -> Vec<CoverCandidate> {
+    match crate::douban_lookup(client, title).await {
+        (page, Some(image)) => vec![CoverCandidate::new(&image, "douban", page.as_deref())],
+        _ => Vec::new(),
+    }
+}
+
+pub async fn wikipedia(client: &Client, title: &str, lang: &str) -> Vec<CoverCandidate> {
+    let url = format!(
+        "https://{}.wikipedia.org/w/api.php?action=query&prop=pageimages&piprop=thumbnail|original&pithumbsize=1024&format=json&redirects=1&titles={}",
+        lang,
+        urlencoding::encode(title)
+    );
+    let Ok(v) = crate::fetch_json(client, &url).await else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    for page in v["query"]["pages"].as_object().into_iter().flat_map(|p| p.values()) {
+        let image = if page["original"]["source"].is_string() { &page["original"] } else { &page["thumbnail"] };
+        if let Some(src) = image["source"].as_str() {
+            let mut c = CoverCandidate::new(src, "wikipedia", page["title"].as_str());
+            c.width = image["width"].as_u64().map(|w| w as u32);
+            c.height = image["height"].as_u64().map(|h| h as u32);
+            out.push(c);
+        }
+    }
+    out
+}
+
+pub async fn omdb(client: &Client, title: &str, media_type: &MediaType, api_key: &str) -> Vec<CoverCandidate> {
+    let kind = match media_type {
+        MediaType::Movie => "&type=movie",
+        MediaType::TvSeries => "&type=series",
+        _ => "",
+    };
+    let url = format!("https://www.omdbapi.com/?apikey={}&t={}{}", urlencoding::encode(api_key), urlencoding::encode(title), kind);
+    let Ok(v) = crate::fetch_json(client, &url).await else {
+        return Vec::new();
+    };
+    match v["Poster"].as_str().filter(|p| *p != "N/A") {
+        Some(poster) => vec![CoverCandidate::new(poster, "omdb", v["Title"].as_str())],
+        None => Vec::new(),
+    }
+}
+
+pub async fn bangumi(client: &Client, title: &str, media_type: &MediaType) -> Vec<CoverCandidate> {
+    let subject_type = match media_type {
+        MediaType::Book | MediaType::Comic => "&type=1",
+        MediaType::Music => "&type=3",
+        _ => "",
+    };
+    let url = format!(
+        "https://api.bgm.tv/search/subject/{}?responseGroup=small&max_results=5{}",
+        urlencoding::encode(title),
+        subject_type
+    );
+    let Ok(v) = crate::fetch_json(client, &url).await else {
+        return Vec::new();
+    };
+    v["list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| {
+            let image = s["images"]["large"].as_str().or(s["images"]["common"].as_str())?;
+            let label = s["name_cn"].as_str().filter(|n| !n.is_empty()).or(s["name"].as_str());
+            Some(CoverCandidate::new(&image.replace("http://", "https://"), "bangumi", label))
+        })
+        .collect()
+}
+
+/// Image results from the configured web search provider (Google or Serper with a key).
+pub async fn image_search(client: &Client, query: &str, provider: &str, api_key: &str, cx: Option<&str>) -> Vec<CoverCandidate> {
+    let results = match (provider, cx) {
+        ("google", Some(cx)) => crate::google_search(client, query, api_key, cx, Some("image")).await,
+        ("serper", _) => crate::serper_search(client, query, api_key, Some("image")).await,
+        _ => return Vec::new(),
+    };
+    results
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|r| Some(CoverCandidate::new(r.image.as_deref()?, provider, Some(&r.title))))
+        .collect()
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFieldKind {
+    Text,
+    Number,
+    Date,
+    Select,
+}
+
+/// One entry of a user's custom field schema, e.g. "shelf" (text) or "price" (number).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomFieldDef {
+    pub key: String,
+    pub label: String,
+    pub kind: CustomFieldKind,
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+pub fn validate_schema(fields: &[CustomFieldDef]) -> Result<(), String> {
+    let mut keys: Vec<&str> = Vec::new();
+    for f in fields {
+        let key = f.key.trim();
+        if key.is_empty() {
+            return Err("Custom field key cannot be empty".to_string());
+        }
+        if keys.contains(&key) {
+            return Err(format!("Duplicate custom field '{}'", key));
+        }
+        if f.kind == CustomFieldKind::Select && f.options.is_empty() {
+            return Err(format!("Select field '{}' needs at least one option", key));
+        }
+        keys.push(key);
+    }
+    Ok(())
+}
+
+fn is_iso_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+        return false;
+    }
+    match (parts[0].parse::<u32>(), parts[1].parse::<u32>(), parts[2].parse::<u32>()) {
+        (Ok(_), Ok(m), Ok(d)) => (1..=12).contains(&m) && (1..=31).contains(&d),
+        _ => false,
+    }
+}
+
+/// Checks values against the schema. Keys the schema no longer defines are dropped,
+/// and null values clear the field.
+pub fn validate_values(values: &mut HashMap<String, Value>, schema: &[CustomFieldDef]) -> Result<(), String> {
+    values.retain(|k, v| !v.is_null() && schema.iter().any(|f| f.key == *k));
+    for (key, value) in values.iter() {
+        let def = schema.iter().find(|f| f.key == *key).expect("retained keys are in schema");
+        let ok = match def.kind {
+            CustomFieldKind::Text => value.is_string(),
+            CustomFieldKind::Number => value.is_number(),
+            CustomFieldKind::Date => value.as_str().map(is_iso_date).unwrap_or(false),
+            CustomFieldKind::Select => value.as_str().map(|s| def.options.iter().any(|o| o == s)).unwrap_or(false),
+        };
+        if !ok {
+            return Err(format!("Invalid value for custom field '{}'", def.label));
+        }
+    }
+    Ok(())
+}
@@ -1,13 +1,232 @@
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 use tauri::Manager;
-use crate::models::{MediaItem, CollectionData, UserRecord};
-use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::models::{MediaItem, ChangeLog, CollectionCategory, CollectionData, Completion, ItemPatch, ItemRevision, Quote, Settings, SyncCursor, Tombstone, TrustedDevice, UserRecord};
+use crate::journal::{ItemChange, Journal, Operation, OperationKind, Replay};
+use crate::smart::SmartList;
+use crate::collections::{Collection, CollectionInput};
+use crate::custom_fields::CustomFieldDef;
+use crate::people::{Person, PersonDetail};
+use crate::feeds::{FeedEntry, FeedSubscription};
+use crate::metadata::{FieldChanges, ItemDetails};
+use crate::scheduler::{JobKind, JobRun};
+use crate::relations::{RelatedItem, Relation, RelationKind};
+use crate::webhooks::Webhook;
+use crate::activity::ActivityEvent;
+use crate::watch_time::TimeSession;
+use crate::goals::{Goal, GoalInput, GoalKind, GoalProgress};
+use crate::notes::Note;
+use crate::statuses::StatusDef;
+use crate::ordering::ViewOrder;
+use crate::sharing::{CollectionShare, CollectionShares, SharePermission, SharedCollection};
+use crate::at_rest::Key;
+use std::collections::{HashMap, HashSet};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+
+// Writes are coalesced: the file is flushed once no change arrived for FLUSH_DEBOUNCE,
+// but never later than FLUSH_MAX_DELAY after the first pending change.
+const FLUSH_DEBOUNCE: Duration = Duration::from_millis(500);
+const FLUSH_MAX_DELAY: Duration = Duration::from_secs(5);
+const SNAPSHOT_KEEP: usize = 10;
+pub const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const HISTORY_MAX_REVISIONS: usize = 50;
+// Long enough for a device that was offline for months to still learn about deletions
+const TOMBSTONE_RETENTION_DAYS: i64 = 180;
+const SYNC_HISTORY_MAX: usize = 200;
+const AUTH_LOG_MAX: usize = 500;
+
+pub fn new_id() -> String {
+    use rand_core::{OsRng, RngCore};
+    format!("{:x}{:08x}", now_ms(), OsRng.next_u32())
+}
+
+pub fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// What paired devices exchange: one user's items, never account data.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPayload {
+    pub username: String,
+    pub items: Vec<MediaItem>,
+    /// Items deleted on the sending device (trashed or purged).
+    #[serde(default)]
+    pub tombstones: Vec<Tombstone>,
+    /// Sender's clock when the payload was built; pass it as `since` next time.
+    #[serde(default)]
+    pub until: i64,
+    /// When the user's password or recovery codes last changed, if since `since`.
+    /// Only the time is sent; the hashes never leave the device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credentials_changed_at: Option<i64>,
+    /// Manual orders and pins of the views rearranged since `since`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub view_orders: HashMap<String, ViewOrder>,
+}
+
+/// How far ahead of our clock a peer's timestamp may be before it is ignored.
+const MAX_CLOCK_SKEW_MS: i64 = 5 * 60 * 1000;
+
+/// Outcome of merging another device's data into ours.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeSummary {
+    pub added: usize,
+    pub updated: usize,
+    /// Items that were identical or where our copy is newer.
+    pub unchanged: usize,
+    /// Items moved to trash because the other device deleted them.
+    pub deleted: usize,
+    pub conflicts: Vec<SyncConflict>,
+    /// The password was changed on the other device after it last changed here;
+    /// the user has to change it here too.
+    #[serde(default)]
+    pub credentials_changed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictKind {
+    /// Both copies changed with the same timestamp.
+    Edit,
+    /// One device deleted the item while the other edited it.
+    DeleteVsEdit,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictResolution {
+    KeptLocal,
+    TookRemote,
+}
+
+/// An item the merge could not settle cleanly. `remote` carries the other
+/// device's copy so the user can still pick it (by saving it with `save_item`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    pub username: String,
+    pub item_id: String,
+    pub title: String,
+    pub kind: ConflictKind,
+    pub resolution: ConflictResolution,
+    pub local_updated_at: Option<i64>,
+    pub remote_updated_at: Option<i64>,
+    pub remote: Option<MediaItem>,
+}
+
+/// Where an item ended up after a change, as broadcast to live-update clients.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ItemEventKind {
+    Saved,
+    Trashed,
+    Deleted,
+}
+
+/// One item change, published on `Database::subscribe` as it happens.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemEvent {
+    pub username: String,
+    pub item_id: String,
+    pub kind: ItemEventKind,
+    pub at: i64,
+}
+
+/// `items_by_user` (and every other per-user map) key of the local profile.
+/// Usernames can't start with `@`, so no account can claim it.
+pub const LOCAL_PROFILE: &str = "@local";
+
+// Slow subscribers past this many events miss some and resynchronize
+const ITEM_EVENT_CAPACITY: usize = 256;
+
+/// What `delete_account` erased.
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountErasure {
+    pub items: usize,
+    pub trashed_items: usize,
+    pub revisions: usize,
+    pub collections: usize,
+    pub smart_lists: usize,
+    pub tombstones: usize,
+    pub trusted_devices: usize,
+    pub webhooks: usize,
+    /// Cover files only this user's items used; the caller deletes them.
+    pub images: Vec<String>,
+    /// Backup files rewritten without the user.
+    pub backups: usize,
+}
+
+/// Which copy wins when both accounts have the same work in `merge_users`.
+/// The other copy is still folded in (tags, review, missing fields).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountMergeStrategy {
+    #[default]
+    PreferTarget,
+    PreferSource,
+    /// The copy edited last.
+    Newest,
+    /// Don't combine duplicates; the source's copy is added as a separate item.
+    KeepBoth,
+}
+
+/// What `merge_users` did.
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountMerge {
+    /// Source items added as new items.
+    pub added: usize,
+    /// Source items combined with a duplicate in the target.
+    pub merged: usize,
+    pub trash: usize,
+    pub collections: usize,
+    pub relations: usize,
+}
+
+/// Describes what happened when collection.json could not be loaded cleanly.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryReport {
+    pub recovered_from_backup: bool,
+    pub error: String,
+    pub corrupt_copy: Option<String>,
+    /// The files are encrypted and can't be opened with the keychain's key (if any).
+    /// Nothing was loaded and nothing is written until the key is put back with
+    /// `set_secret`.
+    #[serde(default)]
+    pub locked: bool,
+}
 
 pub struct Database {
     path: PathBuf,
-    cache: Mutex<CollectionData>,
+    cache: RwLock<CollectionData>,
+    recovery: std::sync::Mutex<Option<RecoveryReport>>,
+    /// See `RecoveryReport::locked`.
+    locked: AtomicBool,
+    dirty: AtomicBool,
+    flush_signal: Notify,
+    write_lock: Mutex<()>,
+    // Lock order: always acquire `cache` before `journals`
+    journals: Mutex<HashMap<String, Journal>>,
+    events: broadcast::Sender<ItemEvent>,
+    /// Set while collection.json is encrypted at rest.
+    key: std::sync::Mutex<Option<crate::at_rest::Key>>,
+    /// `Settings::close_to_tray`, readable from window events without the async lock.
+    close_to_tray: AtomicBool,
 }
 
 impl Database {
@@ -16,56 +235,352 @@ impl Database {
         if !app_dir.exists() {
             fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
         }
-        let path = app_dir.join("collection.json");
-        
-        let data = if path.exists() {
-            let content = fs::read_to_string(&path).unwrap_or_else(|_| "{}".to_string());
-            serde_json::from_str(&content).unwrap_or_default()
-        } else {
-            CollectionData::default()
-        };
+        Self::open(app_dir.join("collection.json"))
+    }
+
+    /// The database kept in `path` (collection.json).
+    pub(crate) fn open(path: PathBuf) -> Self {
+        let (mut data, recovery, key) = Self::load(&path);
+        if let Some(r) = &recovery {
+            eprintln!("Database recovery: {:?}", r);
+        }
+        let locked = recovery.as_ref().is_some_and(|r| r.locked);
+        let changed = Self::prepare(&mut data);
+        let close_to_tray = data.settings.close_to_tray;
 
         Database {
             path,
-            cache: Mutex::new(data),
+            cache: RwLock::new(data),
+            recovery: std::sync::Mutex::new(recovery),
+            locked: AtomicBool::new(locked),
+            dirty: AtomicBool::new(changed),
+            flush_signal: Notify::new(),
+            write_lock: Mutex::new(()),
+            journals: Mutex::new(HashMap::new()),
+            events: broadcast::channel(ITEM_EVENT_CAPACITY).0,
+            key: std::sync::Mutex::new(key),
+            close_to_tray: AtomicBool::new(close_to_tray),
+        }
+    }
+
+    /// Live feed of item changes for all users.
+    pub fn subscribe(&self) -> broadcast::Receiver<ItemEvent> {
+        self.events.subscribe()
+    }
+
+    /// Spawns the background task that persists pending changes after a short quiet period.
+    pub fn start_flusher(db: Arc<Database>) {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                db.flush_signal.notified().await;
+                let deadline = tokio::time::Instant::now() + FLUSH_MAX_DELAY;
+                while tokio::time::Instant::now() < deadline
+                    && tokio::time::timeout(FLUSH_DEBOUNCE, db.flush_signal.notified()).await.is_ok()
+                {}
+                if let Err(e) = db.flush().await {
+                    eprintln!("Failed to flush database: {}", e);
+                }
+            }
+        });
+    }
+
+    fn backup_path(path: &Path) -> PathBuf {
+        path.with_extension("json.bak")
+    }
+
+    fn read_data(path: &Path, key: Option<&Key>) -> Result<CollectionData, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        let content = crate::at_rest::decode(&bytes, key)?;
+        serde_json::from_slice(&content).map_err(|e| e.to_string())
+    }
+
+    // Trash purge and load-time migrations; true if anything changed.
+    fn prepare(data: &mut CollectionData) -> bool {
+        let purged = Self::purge_expired_trash(data);
+        let mut migrated = Self::absorb_all_legacy_collections(data);
+        migrated |= crate::scheduler::migrate_legacy_intervals(&mut data.settings);
+        migrated |= crate::secrets::migrate_tmdb_key(&mut data.settings);
+        migrated |= data.settings.s3_backup.as_mut().is_some_and(crate::cloud_backup::move_secret_to_keychain);
+        migrated |= crate::sync::migrate_peer_ids(data);
+        for items in data.items_by_user.values_mut() {
+            migrated |= crate::statuses::migrate(items);
+            migrated |= crate::dates::migrate(items);
+        }
+        purged > 0 || migrated
+    }
+
+    fn locked_report(error: &str) -> (CollectionData, Option<RecoveryReport>, Option<Key>) {
+        let error = format!("{}. Put the database key back with set_secret(\"{}\") to open the collection.", error, crate::at_rest::KEY_SECRET);
+        (CollectionData::default(), Some(RecoveryReport { recovered_from_backup: false, error, corrupt_copy: None, locked: true }), None)
+    }
+
+    // Loads the main file, falling back to the .bak copy if it is missing or corrupt.
+    // A corrupt main file is moved aside (never overwritten) so it can be inspected later.
+    // The keychain is only consulted when a file is encrypted; without a usable key
+    // the database comes up locked rather than empty, and the files are left as they are.
+    pub(crate) fn load(path: &Path) -> (CollectionData, Option<RecoveryReport>, Option<Key>) {
+        let backup = Self::backup_path(path);
+        let encrypted = crate::at_rest::file_is_encrypted(path) || crate::at_rest::file_is_encrypted(&backup);
+        let key = if encrypted {
+            match crate::at_rest::stored_key() {
+                Ok(Some(key)) => Some(key),
+                Ok(None) => return Self::locked_report("The collection is encrypted and its key is not in the keychain"),
+                Err(e) => return Self::locked_report(&e),
+            }
+        } else {
+            None
+        };
+        let error = if path.exists() {
+            match Self::read_data(path, key.as_ref()) {
+                Ok(data) => return (data, None, key),
+                Err(e) => e,
+            }
+        } else if backup.exists() {
+            "collection.json is missing".to_string()
+        } else {
+            return (CollectionData::default(), None, key);
+        };
+
+        let recovered = Self::read_data(&backup, key.as_ref());
+        if recovered.is_err() && encrypted {
+            // Neither copy opens with this key: more likely the wrong key than two damaged files
+            return Self::locked_report(&error);
+        }
+        let mut corrupt_copy = None;
+        if path.exists() {
+            let ts = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let aside = path.with_extension(format!("json.corrupt-{}", ts));
+            if fs::rename(path, &aside).is_ok() {
+                corrupt_copy = Some(aside.to_string_lossy().to_string());
+            }
+        }
+
+        match recovered {
+            Ok(data) => (data, Some(RecoveryReport { recovered_from_backup: true, error, corrupt_copy, locked: false }), key),
+            Err(_) => (CollectionData::default(), Some(RecoveryReport { recovered_from_backup: false, error, corrupt_copy, locked: false }), key),
         }
     }
 
-    pub fn save(&self) -> Result<(), String> {
-        let data = self.cache.lock().map_err(|e| e.to_string())?;
-        let content = serde_json::to_string_pretty(&*data).map_err(|e| e.to_string())?;
-        fs::write(&self.path, content).map_err(|e| e.to_string())?;
+    pub fn recovery_report(&self) -> Option<RecoveryReport> {
+        self.recovery.lock().ok().and_then(|r| r.clone())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::SeqCst)
+    }
+
+    fn ensure_unlocked(&self) -> Result<(), String> {
+        if self.is_locked() {
+            return Err("The collection is encrypted and locked until its key is restored".to_string());
+        }
+        Ok(())
+    }
+
+    /// Loads the collection again once the key is back in the keychain. Changes
+    /// made while locked were never written and are dropped.
+    pub async fn unlock(&self) -> Result<(), String> {
+        if !self.is_locked() {
+            return Ok(());
+        }
+        let _guard = self.write_lock.lock().await;
+        let (mut data, recovery, key) = Self::load(&self.path);
+        if let Some(r) = recovery.as_ref().filter(|r| r.locked) {
+            return Err(r.error.clone());
+        }
+        let changed = Self::prepare(&mut data);
+        self.close_to_tray.store(data.settings.close_to_tray, Ordering::SeqCst);
+        *self.cache.write().await = data;
+        *self.key.lock().map_err(|e| e.to_string())? = key;
+        *self.recovery.lock().map_err(|e| e.to_string())? = recovery;
+        self.locked.store(false, Ordering::SeqCst);
+        self.dirty.store(changed, Ordering::SeqCst);
+        if changed {
+            self.flush_signal.notify_one();
+        }
+        Ok(())
+    }
+
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+        self.flush_signal.notify_one();
+    }
+
+    /// Writes the cache to disk if anything changed since the last flush.
+    pub async fn flush(&self) -> Result<(), String> {
+        let _guard = self.write_lock.lock().await;
+        self.ensure_unlocked()?;
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        let content = self.snapshot_json().await?;
+        let path = self.path.clone();
+        let res = tauri::async_runtime::spawn_blocking(move || Self::write_atomic(&path, &content))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r);
+        if res.is_err() {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+        res
+    }
+
+    // Write to a temp file and rename over the original so a crash mid-write
+    // never leaves a truncated collection.json; the previous file is kept as .bak.
+    fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+        let tmp = path.with_extension("json.tmp");
+        {
+            let mut file = fs::File::create(&tmp).map_err(|e| e.to_string())?;
+            file.write_all(content).map_err(|e| e.to_string())?;
+            file.sync_all().map_err(|e| e.to_string())?;
+        }
+        if path.exists() {
+            fs::copy(path, Self::backup_path(path)).map_err(|e| e.to_string())?;
+        }
+        fs::rename(&tmp, path).map_err(|e| e.to_string())?;
         Ok(())
     }
 
     #[allow(dead_code)]
-    pub fn get_all(&self) -> Result<Vec<MediaItem>, String> {
-        let data = self.cache.lock().map_err(|e| e.to_string())?;
+    pub async fn get_all(&self) -> Result<Vec<MediaItem>, String> {
+        let data = self.cache.read().await;
         Ok(data.items.clone())
     }
 
-    pub fn get_all_for_user(&self, username: &str) -> Result<Vec<MediaItem>, String> {
-        let data = self.cache.lock().map_err(|e| e.to_string())?;
-        Ok(data.items_by_user.get(username).cloned().unwrap_or_default())
+    pub async fn get_all_for_user(&self, username: &str) -> Result<Vec<MediaItem>, String> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let collections = data.collections_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let mut all = crate::collections::project_legacy_flags(items, collections);
+        for (share, collection) in crate::sharing::incoming(&data, username) {
+            let Some(owned) = data.items_by_user.get(&share.owner) else {
+                continue;
+            };
+            for id in &collection.item_ids {
+                if all.iter().any(|i| &i.id == id) {
+                    continue;
+                }
+                if let Some(item) = owned.iter().find(|i| &i.id == id) {
+                    all.push(MediaItem { shared_from: Some(share.owner.clone()), ..item.clone() });
+                }
+            }
+        }
+        Ok(all)
+    }
+
+    /// See `sharing::owner_of`.
+    pub async fn item_owner(&self, username: &str, id: &str, write: bool) -> Result<String, String> {
+        let data = self.cache.read().await;
+        crate::sharing::owner_of(&data, username, id, write)
     }
 
-    pub fn add_item_for_user(&self, username: &str, item: MediaItem) -> Result<(), String> {
-        let mut data = self.cache.lock().map_err(|e| e.to_string())?;
+    pub async fn find_item(&self, username: &str, id: &str) -> Option<MediaItem> {
+        let data = self.cache.read().await;
+        data.items_by_user.get(username)?.iter().find(|i| i.id == id).cloned()
+    }
+
+    pub async fn add_item_for_user(&self, username: &str, mut item: MediaItem) -> Result<(), String> {
+        item.shared_from = None;
+        let mut data = self.cache.write().await;
+        let statuses = Self::statuses_in(&data, username);
+        if !item.custom_fields.is_empty() {
+            let schema = data.custom_fields_by_user.get(username).map(|s| s.as_slice()).unwrap_or(&[]);
+            crate::custom_fields::validate_values(&mut item.custom_fields, schema)?;
+        }
+        // Container items and parent links from the grid become collection records
+        if item.is_collection == Some(true) || item.parent_collection_id.is_some() {
+            let inner = &mut *data;
+            let collections = inner.collections_by_user.entry(username.to_string()).or_default();
+            let mut pending = vec![item];
+            crate::collections::absorb_legacy_flags(&mut pending, collections, now_ms());
+            match pending.pop() {
+                Some(stripped) => item = stripped,
+                None => {
+                    // A new container takes in the members saved before it
+                    if let Some(list) = inner.items_by_user.get_mut(username) {
+                        crate::collections::absorb_legacy_flags(list, collections, now_ms());
+                    }
+                    drop(data);
+                    self.mark_dirty();
+                    return Ok(());
+                }
+            }
+        }
         let list = data.items_by_user.entry(username.to_string()).or_default();
-        list.retain(|i| i.id != item.id);
-        list.insert(0, item);
+        let existing_idx = list.iter().position(|i| i.id == item.id);
+        let before = existing_idx.map(|idx| list.remove(idx));
+        crate::ratings::merge_into_item(&mut item, before.as_ref());
+        crate::statuses::reconcile(before.as_ref(), &mut item, &statuses);
+        crate::dates::normalize(&mut item);
+        // The cover cache is filled in the background; a stale frontend copy must not drop it
+        if item.poster_cache.is_none() {
+            item.poster_cache = before.as_ref().and_then(|b| b.poster_cache.clone());
+        }
+        // Only real edits move `updated_at`; re-saving an unchanged item keeps its sync version
+        item.updated_at = match &before {
+            Some(b) if !Self::changed_fields(b, &item).iter().any(|f| f != "posterCache" && f != "updatedAt") => b.updated_at,
+            _ => Some(now_ms()),
+        };
+        let changed = before.as_ref().map(|b| b.updated_at) != Some(item.updated_at);
+        list.insert(0, item.clone());
+        if changed {
+            self.log_change(&mut data, username, &item.id);
+        }
+        match &before {
+            Some(prev) => Self::record_revision(&mut data, username, prev, &item),
+            None => Self::record_activity(&mut data, username, None, &item),
+        }
+        let kind = if before.is_some() { OperationKind::Update } else { OperationKind::Add };
+        let change = ItemChange { item_id: item.id.clone(), index: existing_idx.or(Some(0)), before, after: Some(item) };
+        self.record(username, kind, vec![change]).await;
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    // Applies the patch to every listed item under a single lock and journal entry
+    pub async fn bulk_update_for_user(&self, username: &str, ids: &[String], patch: &ItemPatch) -> Result<usize, String> {
+        let mut data = self.cache.write().await;
+        let now = now_ms();
+        let mut updated = Vec::new();
+        let statuses = Self::statuses_in(&data, username);
+        if let Some(list) = data.items_by_user.get_mut(username) {
+            for (idx, item) in list.iter_mut().enumerate() {
+                if !ids.contains(&item.id) {
+                    continue;
+                }
+                let before = item.clone();
+                patch.apply(item);
+                crate::statuses::reconcile(Some(&before), item, &statuses);
+                crate::dates::normalize(item);
+                item.last_edited_at = Some(now);
+                item.updated_at = Some(now);
+                updated.push((idx, before, item.clone()));
+            }
+        }
+        let mut changes = Vec::new();
+        for (idx, before, after) in updated {
+            Self::record_revision(&mut data, username, &before, &after);
+            self.log_change(&mut data, username, &after.id);
+            changes.push(ItemChange { item_id: after.id.clone(), index: Some(idx), before: Some(before), after: Some(after) });
+        }
+        let count = changes.len();
+        self.record(username, OperationKind::BulkUpdate, changes).await;
         drop(data);
-        self.save()
+        self.mark_dirty();
+        Ok(count)
     }
 
-    pub fn reorder_items_for_user(&self, username: &str, new_order_ids: Vec<String>) -> Result<(), String> {
-        let mut data = self.cache.lock().map_err(|e| e.to_string())?;
+    pub async fn reorder_items_for_user(&self, username: &str, new_order_ids: Vec<String>) -> Result<(), String> {
+        let mut data = self.cache.write().await;
         if let Some(list) = data.items_by_user.get_mut(username) {
             let mut id_map: std::collections::HashMap<String, MediaItem> = list.drain(..).map(|item| (item.id.clone(), item)).collect();
             let mut new_list = Vec::new();
             
-            for id in new_order_ids {
-                if let Some(item) = id_map.remove(&id) {
+            for id in &new_order_ids {
+                if let Some(item) = id_map.remove(id) {
                     new_list.push(item);
                 }
             }
@@ -86,92 +601,2856 @@ impl Database {
             
             *list = new_list;
         }
+        // Container ids in the same order list position the collections
+        if let Some(collections) = data.collections_by_user.get_mut(username) {
+            collections.sort_by_key(|c| new_order_ids.iter().position(|id| *id == c.id).unwrap_or(usize::MAX));
+        }
         drop(data);
-        self.save()
+        self.mark_dirty();
+        Ok(())
     }
 
 
-    pub fn remove_item_for_user(&self, username: &str, id: &str) -> Result<(), String> {
-        let mut data = self.cache.lock().map_err(|e| e.to_string())?;
-        if let Some(list) = data.items_by_user.get_mut(username) {
-            list.retain(|i| i.id != id);
+    // Removed items are moved to the user's trash rather than dropped outright
+    pub async fn remove_item_for_user(&self, username: &str, id: &str) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        if Self::delete_collection_in(&mut data, username, id) {
+            drop(data);
+            self.mark_dirty();
+            return Ok(());
         }
+        let removed = data
+            .items_by_user
+            .get_mut(username)
+            .and_then(|list| list.iter().position(|i| i.id == id).map(|idx| (idx, list.remove(idx))));
+        if let Some((idx, mut item)) = removed {
+            let change = ItemChange { item_id: item.id.clone(), index: Some(idx), before: Some(item.clone()), after: None };
+            self.record(username, OperationKind::Remove, vec![change]).await;
+            item.deleted_at = Some(now_ms());
+            let trash = data.trash_by_user.entry(username.to_string()).or_default();
+            trash.retain(|i| i.id != item.id);
+            trash.insert(0, item);
+            self.log_change(&mut data, username, id);
+        }
+        Self::purge_expired_trash(&mut data);
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Applies a user edit to one item, recording a revision and the sync change.
+    async fn edit_item<T>(&self, username: &str, id: &str, edit: impl FnOnce(&mut MediaItem) -> Result<T, String>) -> Result<T, String> {
+        let mut data = self.cache.write().await;
+        let statuses = Self::statuses_in(&data, username);
+        let list = data.items_by_user.get_mut(username).ok_or_else(|| "Item not found".to_string())?;
+        let item = list.iter_mut().find(|i| i.id == id).ok_or_else(|| "Item not found".to_string())?;
+        let before = item.clone();
+        let result = edit(item)?;
+        crate::statuses::reconcile(Some(&before), item, &statuses);
+        crate::dates::normalize(item);
+        item.last_edited_at = Some(now_ms());
+        item.updated_at = item.last_edited_at;
+        let after = item.clone();
+        Self::record_revision(&mut data, username, &before, &after);
+        self.log_change(&mut data, username, id);
         drop(data);
-        self.save()
+        self.mark_dirty();
+        Ok(result)
+    }
+
+    /// Records another finish of the item and moves it to Watched.
+    pub async fn add_completion(&self, username: &str, id: &str, at: Option<i64>, rating: Option<f32>) -> Result<MediaItem, String> {
+        self.edit_item(username, id, |item| {
+            item.completions.push(Completion { at: at.unwrap_or_else(now_ms), rating });
+            item.completions.sort_by_key(|c| c.at);
+            item.category = Some(CollectionCategory::Watched);
+            if rating.is_some() {
+                item.user_rating = rating;
+            }
+            Ok(())
+        })
+        .await?;
+        self.find_item(username, id).await.ok_or_else(|| "Item not found".to_string())
+    }
+
+    pub async fn set_trailer(&self, username: &str, id: &str, url: &str) -> Result<(), String> {
+        self.edit_item(username, id, |item| {
+            item.trailer_url = Some(url.to_string());
+            Ok(())
+        })
+        .await
+    }
+
+    /// Adds titles in languages the item lacks; returns the item's titles by language.
+    pub async fn add_alt_titles(&self, username: &str, id: &str, found: HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+        self.edit_item(username, id, |item| {
+            crate::titles::merge(&mut item.alt_titles, found);
+            Ok(item.alt_titles.clone())
+        })
+        .await
     }
 
-    pub fn get_full_data(&self) -> Result<CollectionData, String> {
-        let data = self.cache.lock().map_err(|e| e.to_string())?;
-        Ok(data.clone())
+    pub async fn set_play_times(&self, username: &str, id: &str, times: crate::hltb::PlayTimes) -> Result<(), String> {
+        self.edit_item(username, id, |item| {
+            item.play_times = Some(times);
+            Ok(())
+        })
+        .await
     }
 
-    pub fn merge_full_data(&self, incoming: CollectionData) -> Result<(), String> {
-        let mut data = self.cache.lock().map_err(|e| e.to_string())?;
-        
-        // Merge Users
-        for user in incoming.users {
-            if !data.users.iter().any(|u| u.username == user.username) {
-                data.users.push(user);
+    /// Stores the platform title id under `key` and turns on update checks for the item.
+    pub async fn follow_update_source(&self, username: &str, id: &str, key: &str, title_id: &str) -> Result<MediaItem, String> {
+        self.edit_item(username, id, |item| {
+            item.provider_ids.get_or_insert_with(HashMap::new).insert(key.to_string(), title_id.to_string());
+            item.is_ongoing = true;
+            item.notification_enabled = Some(true);
+            Ok(())
+        })
+        .await?;
+        self.find_item(username, id).await.ok_or_else(|| "Item not found".to_string())
+    }
+
+    /// Marks chapters read or unread and moves `user_progress` to the highest chapter read.
+    pub async fn mark_chapters_read(&self, username: &str, id: &str, chapters: &[String], volume: Option<u32>, read: bool) -> Result<MediaItem, String> {
+        let chapters = crate::comics::expand(chapters)?;
+        self.edit_item(username, id, |item| {
+            let reading = item.reading.get_or_insert_with(Default::default);
+            reading.mark(&chapters, volume, read, now_ms());
+            if let Some(text) = reading.text() {
+                item.user_progress = Some(text);
+            }
+            Ok(())
+        })
+        .await?;
+        self.find_item(username, id).await.ok_or_else(|| "Item not found".to_string())
+    }
+
+    /// Moves progress on by one episode; reaching the last one adds a completion
+    /// and moves the item to Watched, as `add_completion` does.
+    pub async fn mark_next_episode(&self, username: &str, id: &str, counts: &crate::episodes::EpisodeCounts) -> Result<MediaItem, String> {
+        self.edit_item(username, id, |item| {
+            let next = crate::episodes::advance(item.user_progress.as_deref(), counts, item.is_ongoing);
+            item.user_progress = Some(next.progress);
+            if next.finished && item.category != Some(CollectionCategory::Watched) {
+                item.completions.push(Completion { at: now_ms(), rating: None });
+                item.category = Some(CollectionCategory::Watched);
             }
+            Ok(())
+        })
+        .await?;
+        self.find_item(username, id).await.ok_or_else(|| "Item not found".to_string())
+    }
+
+    /// Adds a quote, or updates the text and location of `quote_id`.
+    pub async fn save_quote(&self, username: &str, item_id: &str, quote_id: Option<&str>, text: &str, location: Option<String>) -> Result<Quote, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("Quote text is required".to_string());
         }
+        let location = location.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        self.edit_item(username, item_id, |item| {
+            let now = now_ms();
+            let quote = match quote_id {
+                Some(qid) => item.quotes.iter_mut().find(|q| q.id == qid).ok_or_else(|| "Quote not found".to_string())?,
+                None => {
+                    item.quotes.push(Quote { id: new_id(), text: String::new(), location: None, created_at: now, updated_at: None });
+                    item.quotes.last_mut().unwrap()
+                }
+            };
+            if quote_id.is_some() {
+                quote.updated_at = Some(now);
+            }
+            quote.text = text.to_string();
+            quote.location = location;
+            Ok(quote.clone())
+        })
+        .await
+    }
 
-        // Merge Items per User
-        for (username, incoming_items) in incoming.items_by_user {
-            let local_items = data.items_by_user.entry(username).or_default();
-            
-            for item in incoming_items {
-                if let Some(existing_idx) = local_items.iter().position(|i| i.id == item.id) {
-                    // Update if incoming is newer (naive check: always update or check timestamps if available)
-                    // Assuming last_edited_at exists
-                    let existing = &local_items[existing_idx];
-                    let incoming_ts = item.last_edited_at.unwrap_or(0);
-                    let existing_ts = existing.last_edited_at.unwrap_or(0);
-                    
-                    if incoming_ts > existing_ts {
-                        local_items[existing_idx] = item;
-                    }
-                } else {
-                    local_items.push(item);
+    /// Adds a note, or replaces the markdown of `note_id`.
+    pub async fn save_note(&self, username: &str, item_id: &str, note_id: Option<&str>, markdown: &str) -> Result<Note, String> {
+        self.edit_item(username, item_id, |item| {
+            let now = now_ms();
+            let note = match note_id {
+                Some(nid) => item.notes.iter_mut().find(|n| n.id == nid).ok_or_else(|| "Note not found".to_string())?,
+                None => {
+                    item.notes.push(Note { id: new_id(), created_at: now, ..Default::default() });
+                    item.notes.last_mut().unwrap()
                 }
+            };
+            if note_id.is_some() {
+                note.updated_at = Some(now);
+            }
+            note.markdown = markdown.to_string();
+            note.prune_attachments();
+            Ok(note.clone())
+        })
+        .await
+    }
+
+    pub async fn delete_note(&self, username: &str, item_id: &str, note_id: &str) -> Result<(), String> {
+        self.edit_item(username, item_id, |item| {
+            let before = item.notes.len();
+            item.notes.retain(|n| n.id != note_id);
+            if item.notes.len() == before {
+                return Err("Note not found".to_string());
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Records a stored attachment on the note; the markdown links to it separately.
+    pub async fn add_note_attachment(&self, username: &str, item_id: &str, note_id: &str, file: &str) -> Result<Note, String> {
+        self.edit_item(username, item_id, |item| {
+            let note = item.notes.iter_mut().find(|n| n.id == note_id).ok_or_else(|| "Note not found".to_string())?;
+            if !note.attachments.iter().any(|f| f == file) {
+                note.attachments.push(file.to_string());
+            }
+            Ok(note.clone())
+        })
+        .await
+    }
+
+    /// Attachment files any note of any account (trash and edit history included) refers to.
+    pub async fn referenced_attachments(&self) -> HashSet<String> {
+        let data = self.cache.read().await;
+        let revisions = data.history_by_user.values().flat_map(|h| h.values()).flatten().map(|r| &r.snapshot);
+        data.items_by_user
+            .values()
+            .chain(data.trash_by_user.values())
+            .flatten()
+            .chain(revisions)
+            .flat_map(|i| i.notes.iter())
+            .flat_map(|n| n.attachments.iter().cloned())
+            .collect()
+    }
+
+    pub async fn delete_quote(&self, username: &str, item_id: &str, quote_id: &str) -> Result<(), String> {
+        self.edit_item(username, item_id, |item| {
+            let before = item.quotes.len();
+            item.quotes.retain(|q| q.id != quote_id);
+            if item.quotes.len() == before {
+                return Err("Quote not found".to_string());
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    // --- Trash ---
+    pub async fn get_trash_for_user(&self, username: &str) -> Result<Vec<MediaItem>, String> {
+        let data = self.cache.read().await;
+        Ok(data.trash_by_user.get(username).cloned().unwrap_or_default())
+    }
+
+    pub async fn restore_item_for_user(&self, username: &str, id: &str) -> Result<MediaItem, String> {
+        let mut data = self.cache.write().await;
+        let trash = data.trash_by_user.get_mut(username).ok_or_else(|| "Item not in trash".to_string())?;
+        let idx = trash.iter().position(|i| i.id == id).ok_or_else(|| "Item not in trash".to_string())?;
+        let mut item = trash.remove(idx);
+        item.deleted_at = None;
+        // Newer than the deletion, so paired devices bring it back too
+        item.updated_at = Some(now_ms());
+        let list = data.items_by_user.entry(username.to_string()).or_default();
+        list.retain(|i| i.id != item.id);
+        list.insert(0, item.clone());
+        self.log_change(&mut data, username, id);
+        let change = ItemChange { item_id: item.id.clone(), index: Some(0), before: None, after: Some(item.clone()) };
+        self.record(username, OperationKind::Restore, vec![change]).await;
+        drop(data);
+        self.mark_dirty();
+        Ok(item)
+    }
+
+    pub async fn empty_trash_for_user(&self, username: &str) -> Result<usize, String> {
+        let mut data = self.cache.write().await;
+        let emptied = data.trash_by_user.remove(username).unwrap_or_default();
+        let count = emptied.len();
+        Self::add_tombstones(&mut data, username, &emptied);
+        let now = now_ms();
+        for item in &emptied {
+            let _ = self.events.send(ItemEvent { username: username.to_string(), item_id: item.id.clone(), kind: ItemEventKind::Deleted, at: now });
+        }
+        let inner = &mut *data;
+        if let Some(relations) = inner.relations_by_user.get_mut(username) {
+            let items = inner.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+            crate::relations::prune(relations, items);
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(count)
+    }
+
+    fn purge_expired_trash(data: &mut CollectionData) -> usize {
+        let cutoff = now_ms() - data.settings.trash_retention_days as i64 * DAY_MS;
+        let mut expired = Vec::new();
+        for (username, trash) in data.trash_by_user.iter_mut() {
+            let (keep, gone): (Vec<_>, Vec<_>) = trash.drain(..).partition(|i| i.deleted_at.unwrap_or(0) > cutoff);
+            *trash = keep;
+            if !gone.is_empty() {
+                expired.push((username.clone(), gone));
+            }
+        }
+        let mut purged = 0;
+        for (username, gone) in expired {
+            purged += gone.len();
+            Self::add_tombstones(data, &username, &gone);
+        }
+        let tombstone_cutoff = now_ms() - TOMBSTONE_RETENTION_DAYS * DAY_MS;
+        for tombstones in data.tombstones_by_user.values_mut() {
+            tombstones.retain(|t| t.deleted_at > tombstone_cutoff);
+        }
+        let inner = &mut *data;
+        for (username, log) in inner.change_log_by_user.iter_mut() {
+            let items = inner.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+            let before = log.changes.len();
+            log.changes.retain(|id, at| *at > tombstone_cutoff || items.iter().any(|i| &i.id == id));
+            if log.changes.len() < before {
+                // Deletions before the cutoff are forgotten, so older cursors need a full exchange
+                log.started_at = log.started_at.max(tombstone_cutoff);
             }
         }
+        purged
+    }
+
+    fn add_tombstones(data: &mut CollectionData, username: &str, items: &[MediaItem]) {
+        let tombstones = data.tombstones_by_user.entry(username.to_string()).or_default();
+        for item in items {
+            tombstones.retain(|t| t.item_id != item.id);
+            tombstones.push(Tombstone { item_id: item.id.clone(), deleted_at: item.deleted_at.unwrap_or_else(now_ms) });
+        }
+    }
+
+    // --- Duplicates ---
+    pub async fn find_duplicates_for_user(&self, username: &str) -> Result<Vec<crate::dedupe::DuplicateGroup>, String> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        Ok(crate::dedupe::find_duplicates(items))
+    }
+
+    // Folds the merged items into `keep_id` and moves them to trash, as one undoable operation
+    pub async fn merge_items_for_user(&self, username: &str, keep_id: &str, merge_ids: &[String]) -> Result<MediaItem, String> {
+        let mut data = self.cache.write().await;
+        let list = data.items_by_user.get(username).ok_or_else(|| "Item not found".to_string())?;
+        let keep_idx = list.iter().position(|i| i.id == keep_id).ok_or_else(|| "Item not found".to_string())?;
+        let before = list[keep_idx].clone();
+        let others: Vec<(usize, MediaItem)> = list
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.id != keep_id && merge_ids.contains(&i.id))
+            .map(|(idx, i)| (idx, i.clone()))
+            .collect();
+        if others.is_empty() {
+            return Err("No items to merge".to_string());
+        }
+
+        let mut merged = before.clone();
+        for (_, other) in &others {
+            crate::dedupe::merge_into(&mut merged, other);
+        }
+        merged.last_edited_at = Some(now_ms());
+        merged.updated_at = merged.last_edited_at;
 
+        Self::record_revision(&mut data, username, &before, &merged);
+        let written = self.apply_item_state(&mut data, username, keep_id, Some(&merged), Some(keep_idx));
+        let mut changes = vec![ItemChange { item_id: merged.id.clone(), index: Some(keep_idx), before: Some(before.clone()), after: written.clone() }];
+        for (idx, other) in others {
+            self.apply_item_state(&mut data, username, &other.id, None, None);
+            changes.push(ItemChange { item_id: other.id.clone(), index: Some(idx), before: Some(other), after: None });
+        }
+        self.record(username, OperationKind::Merge, changes).await;
         drop(data);
-        self.save()
+        self.mark_dirty();
+        Ok(written.unwrap_or(merged))
     }
-    
-    #[allow(dead_code)]
-    pub fn update_item(&self, _item: MediaItem) -> Result<(), String> {
-        Err("update_item deprecated; use per-user methods".to_string())
+
+    // --- Item history ---
+    fn changed_fields(before: &MediaItem, after: &MediaItem) -> Vec<String> {
+        let (Ok(Value::Object(a)), Ok(Value::Object(b))) = (serde_json::to_value(before), serde_json::to_value(after)) else {
+            return Vec::new();
+        };
+        let mut fields: Vec<String> = a
+            .iter()
+            .filter(|(k, v)| b.get(*k).unwrap_or(&Value::Null) != *v)
+            .map(|(k, _)| k.clone())
+            .collect();
+        fields.extend(b.keys().filter(|k| !a.contains_key(*k)).cloned());
+        fields
     }
-    
-    // Bulk import
-    pub fn import_for_user(&self, username: &str, items: Vec<MediaItem>) -> Result<(), String> {
-         let mut data = self.cache.lock().map_err(|e| e.to_string())?;
-         let list = data.items_by_user.entry(username.to_string()).or_default();
-         let existing_ids: Vec<String> = list.iter().map(|i| i.id.clone()).collect();
-         for item in items {
-             if !existing_ids.contains(&item.id) {
-                 list.push(item);
-             }
-         }
-         drop(data);
-         self.save()
+
+    fn record_revision(data: &mut CollectionData, username: &str, before: &MediaItem, after: &MediaItem) {
+        let changed_fields = Self::changed_fields(before, after);
+        if changed_fields.is_empty() {
+            return;
+        }
+        let revisions = data
+            .history_by_user
+            .entry(username.to_string())
+            .or_default()
+            .entry(before.id.clone())
+            .or_default();
+        let revision = revisions.last().map(|r| r.revision + 1).unwrap_or(1);
+        revisions.push(ItemRevision { revision, at: now_ms(), changed_fields, snapshot: before.clone() });
+        if revisions.len() > HISTORY_MAX_REVISIONS {
+            revisions.remove(0);
+        }
+        Self::record_activity(data, username, Some(before), after);
     }
 
-    // --- Auth helpers ---
-    pub fn find_user(&self, username: &str) -> Option<UserRecord> {
-        let data = self.cache.lock().ok()?;
-        data.users.iter().find(|u| u.username == username).cloned()
+    fn record_activity(data: &mut CollectionData, username: &str, before: Option<&MediaItem>, after: &MediaItem) {
+        let events = crate::activity::events_for(before, after, now_ms());
+        if events.is_empty() {
+            return;
+        }
+        let log = data.activity_by_user.entry(username.to_string()).or_default();
+        log.extend(events);
+        let excess = log.len().saturating_sub(crate::activity::ACTIVITY_MAX);
+        log.drain(..excess);
     }
 
-    pub fn add_user(&self, user: UserRecord) -> Result<(), String> {
-        let mut data = self.cache.lock().map_err(|e| e.to_string())?;
-        if data.users.iter().any(|u| u.username == user.username) {
-            return Err("User already exists".to_string());
+    pub async fn get_item_timeline(&self, username: &str, id: &str) -> Vec<ActivityEvent> {
+        let data = self.cache.read().await;
+        data.activity_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .filter(|e| e.item_id == id)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn start_time_session(&self, username: &str, item_id: &str) -> Result<TimeSession, String> {
+        let mut data = self.cache.write().await;
+        if !data.items_by_user.get(username).is_some_and(|l| l.iter().any(|i| i.id == item_id)) {
+            return Err("Item not found".to_string());
         }
-        data.users.push(user);
+        let list = data.time_sessions_by_user.entry(username.to_string()).or_default();
+        if let Some(running) = list.iter().find(|s| s.item_id == item_id && s.ended_at.is_none()) {
+            return Ok(running.clone());
+        }
+        let started = TimeSession { id: new_id(), item_id: item_id.to_string(), started_at: now_ms(), ended_at: None, seconds: 0 };
+        list.push(started.clone());
+        drop(data);
+        self.mark_dirty();
+        Ok(started)
+    }
+
+    pub async fn get_continue_watching(&self, username: &str) -> Vec<crate::continue_watching::QueueEntry> {
+        let data = self.cache.read().await;
+        crate::continue_watching::queue(
+            data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]),
+            &Self::statuses_in(&data, username),
+            data.activity_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]),
+            data.time_sessions_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]),
+            &data.snoozes_by_user.get(username).cloned().unwrap_or_default(),
+            now_ms(),
+        )
+    }
+
+    /// Snoozes `item_id` for `days` (0 wakes it up); returns when the snooze ends.
+    pub async fn snooze_item(&self, username: &str, item_id: &str, days: u32) -> Result<Option<i64>, String> {
+        let mut data = self.cache.write().await;
+        if !data.items_by_user.get(username).map(|l| l.iter().any(|i| i.id == item_id)).unwrap_or(false) {
+            return Err("Item not found".to_string());
+        }
+        let now = now_ms();
+        let snoozes = data.snoozes_by_user.entry(username.to_string()).or_default();
+        snoozes.retain(|_, until| *until > now);
+        let until = if days == 0 {
+            snoozes.remove(item_id);
+            None
+        } else {
+            let until = now + days as i64 * DAY_MS;
+            snoozes.insert(item_id.to_string(), until);
+            Some(until)
+        };
         drop(data);
-        self.save()
+        self.mark_dirty();
+        Ok(until)
+    }
+
+    pub async fn stop_time_session(&self, username: &str, item_id: &str) -> Result<TimeSession, String> {
+        let mut data = self.cache.write().await;
+        let running = data
+            .time_sessions_by_user
+            .get_mut(username)
+            .and_then(|l| l.iter_mut().find(|s| s.item_id == item_id && s.ended_at.is_none()))
+            .ok_or_else(|| "No session is running for this item".to_string())?;
+        running.stop(now_ms());
+        let stopped = running.clone();
+        drop(data);
+        self.mark_dirty();
+        Ok(stopped)
+    }
+
+    pub async fn get_time_sessions(&self, username: &str, item_id: Option<&str>) -> Vec<TimeSession> {
+        let data = self.cache.read().await;
+        data.time_sessions_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .rev()
+            .filter(|s| item_id.map(|id| s.item_id == id).unwrap_or(true))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn get_statistics(&self, username: &str, range: (Option<i64>, Option<i64>), utc_offset_minutes: i32) -> crate::stats::Statistics {
+        let mut data = self.cache.write().await;
+        let items = data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let no_history = HashMap::new();
+        let history = data.history_by_user.get(username).unwrap_or(&no_history);
+        let sessions = data.time_sessions_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let activity = data.activity_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let mut stats = crate::stats::compute(items, history, sessions, range);
+        stats.streaks = crate::stats::streaks(activity, sessions, now_ms(), utc_offset_minutes);
+        let record = data.streaks_by_user.entry(username.to_string()).or_default();
+        if stats.streaks.best > record.best {
+            record.best = stats.streaks.best;
+            record.best_ended_on = stats.streaks.best_ended_on.clone().unwrap_or_default();
+            drop(data);
+            self.mark_dirty();
+        } else if record.best > stats.streaks.best {
+            stats.streaks.best = record.best;
+            stats.streaks.best_ended_on = Some(record.best_ended_on.clone());
+        }
+        stats
+    }
+
+    pub async fn year_review(&self, username: &str, year: i64, utc_offset_minutes: i32) -> crate::review::YearReview {
+        // The year runs between the user's local midnights, not UTC ones
+        let offset = utc_offset_minutes as i64 * 60_000;
+        let range = (
+            Some(crate::smart::days_from_civil(year, 1, 1) * DAY_MS - offset),
+            Some(crate::smart::days_from_civil(year + 1, 1, 1) * DAY_MS - offset),
+        );
+        let stats = self.get_statistics(username, range, utc_offset_minutes).await;
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let activity = data.activity_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        crate::review::build(year, stats, items, activity, utc_offset_minutes)
+    }
+
+    pub async fn get_activity_heatmap(&self, username: &str, year: i64, utc_offset_minutes: i32) -> crate::stats::Heatmap {
+        let data = self.cache.read().await;
+        let activity = data.activity_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let sessions = data.time_sessions_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        crate::stats::heatmap(year, activity, sessions, utc_offset_minutes)
+    }
+
+    pub async fn get_activity_feed(&self, username: &str, from: i64, to: i64) -> Vec<ActivityEvent> {
+        let data = self.cache.read().await;
+        data.activity_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .rev()
+            .filter(|e| e.at >= from && e.at < to)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn get_item_history(&self, username: &str, id: &str) -> Result<Vec<ItemRevision>, String> {
+        let data = self.cache.read().await;
+        Ok(data
+            .history_by_user
+            .get(username)
+            .and_then(|h| h.get(id))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    // Reverting saves the snapshot as a new edit, so the revert itself can be reverted
+    pub async fn revert_item(&self, username: &str, id: &str, revision: u32) -> Result<MediaItem, String> {
+        let mut snapshot = {
+            let data = self.cache.read().await;
+            data.history_by_user
+                .get(username)
+                .and_then(|h| h.get(id))
+                .and_then(|revs| revs.iter().find(|r| r.revision == revision))
+                .map(|r| r.snapshot.clone())
+                .ok_or_else(|| "Revision not found".to_string())?
+        };
+        snapshot.last_edited_at = Some(now_ms());
+        self.add_item_for_user(username, snapshot.clone()).await?;
+        Ok(snapshot)
+    }
+
+    // --- Undo / redo journal ---
+    async fn record(&self, username: &str, kind: OperationKind, changes: Vec<ItemChange>) {
+        let mut journals = self.journals.lock().await;
+        journals.entry(username.to_string()).or_default().record(kind, now_ms(), changes);
+    }
+
+    // Puts the item back into the active list as `state`, or moves it to trash when `state` is None.
+    // Returns the item as written, with its new sync version.
+    fn apply_item_state(&self, data: &mut CollectionData, username: &str, id: &str, state: Option<&MediaItem>, index: Option<usize>) -> Option<MediaItem> {
+        let list = data.items_by_user.entry(username.to_string()).or_default();
+        let current_idx = list.iter().position(|i| i.id == id);
+        let current = current_idx.map(|idx| list.remove(idx));
+        match state {
+            Some(item) => {
+                let pos = index.or(current_idx).unwrap_or(0).min(list.len());
+                let mut item = item.clone();
+                // An undone edit is still a change as far as paired devices are concerned
+                item.updated_at = Some(now_ms());
+                list.insert(pos, item.clone());
+                if let Some(trash) = data.trash_by_user.get_mut(username) {
+                    trash.retain(|i| i.id != id);
+                }
+                self.log_change(data, username, id);
+                Some(item)
+            }
+            None => {
+                if let Some(mut item) = current {
+                    item.deleted_at = Some(now_ms());
+                    let trash = data.trash_by_user.entry(username.to_string()).or_default();
+                    trash.retain(|i| i.id != id);
+                    trash.insert(0, item);
+                }
+                self.log_change(data, username, id);
+                None
+            }
+        }
+    }
+
+    fn log_change(&self, data: &mut CollectionData, username: &str, id: &str) {
+        let now = now_ms();
+        let log = data
+            .change_log_by_user
+            .entry(username.to_string())
+            .or_insert_with(|| ChangeLog { started_at: now, changes: HashMap::new() });
+        log.changes.insert(id.to_string(), now);
+
+        let has = |lists: &HashMap<String, Vec<MediaItem>>| lists.get(username).is_some_and(|l| l.iter().any(|i| i.id == id));
+        let kind = if has(&data.items_by_user) {
+            ItemEventKind::Saved
+        } else if has(&data.trash_by_user) {
+            ItemEventKind::Trashed
+        } else {
+            ItemEventKind::Deleted
+        };
+        // No receivers is the normal case while the server is stopped
+        let _ = self.events.send(ItemEvent { username: username.to_string(), item_id: id.to_string(), kind, at: now });
+    }
+
+    pub async fn undo_last_operation(&self, username: &str) -> Result<Option<Replay>, String> {
+        self.replay(username, true).await
+    }
+
+    pub async fn redo_last_operation(&self, username: &str) -> Result<Option<Replay>, String> {
+        self.replay(username, false).await
+    }
+
+    // Steps one operation back (or forward). An item whose version no longer matches the
+    // state the operation left it in was edited since, by hand, a sync or a refresh, and
+    // is skipped rather than overwritten.
+    async fn replay(&self, username: &str, undo: bool) -> Result<Option<Replay>, String> {
+        let mut data = self.cache.write().await;
+        let mut journals = self.journals.lock().await;
+        let journal = journals.entry(username.to_string()).or_default();
+        let popped = if undo { journal.pop_undo() } else { journal.pop_redo() };
+        let Some(mut op) = popped else {
+            return Ok(None);
+        };
+        let mut changes = std::mem::take(&mut op.changes);
+        if undo {
+            changes.reverse();
+        }
+        let mut skipped = Vec::new();
+        for mut change in changes {
+            let (expected, target) = if undo { (&change.after, &change.before) } else { (&change.before, &change.after) };
+            let current = data.items_by_user.get(username).and_then(|l| l.iter().find(|i| i.id == change.item_id));
+            if current.map(|i| i.updated_at) != expected.as_ref().map(|i| i.updated_at) {
+                skipped.push(change.item_id);
+                continue;
+            }
+            let target = target.clone();
+            // Keep the written version so the opposite step can check against it
+            let written = self.apply_item_state(&mut data, username, &change.item_id, target.as_ref(), change.index);
+            if undo {
+                change.before = written;
+            } else {
+                change.after = written;
+            }
+            op.changes.push(change);
+        }
+        if undo {
+            op.changes.reverse();
+        }
+        let applied = !op.changes.is_empty();
+        if applied {
+            if undo {
+                journal.push_redo(op.clone());
+            } else {
+                journal.push_undo(op.clone());
+            }
+        }
+        drop(journals);
+        drop(data);
+        if applied {
+            self.mark_dirty();
+        }
+        Ok(Some(Replay { operation: op, skipped }))
+    }
+
+    pub async fn get_recent_operations(&self, username: &str, limit: usize) -> Vec<Operation> {
+        let journals = self.journals.lock().await;
+        journals.get(username).map(|j| j.recent(limit)).unwrap_or_default()
+    }
+
+    // --- Collections ---
+    fn absorb_all_legacy_collections(data: &mut CollectionData) -> bool {
+        let mut changed = false;
+        let now = now_ms();
+        for (username, items) in data.items_by_user.iter_mut() {
+            let collections = data.collections_by_user.entry(username.clone()).or_default();
+            changed |= crate::collections::absorb_legacy_flags(items, collections, now);
+        }
+        changed
+    }
+
+    fn delete_collection_in(data: &mut CollectionData, username: &str, id: &str) -> bool {
+        let Some(collections) = data.collections_by_user.get_mut(username) else {
+            return false;
+        };
+        let Some(idx) = collections.iter().position(|c| c.id == id) else {
+            return false;
+        };
+        let removed = collections.remove(idx);
+        // Nested collections move up to the deleted collection's parent
+        for c in collections.iter_mut() {
+            if c.parent_id.as_deref() == Some(id) {
+                c.parent_id = removed.parent_id.clone();
+            }
+        }
+        // Links still waiting for this container won't be resolved now
+        for item in data.items_by_user.get_mut(username).into_iter().flatten() {
+            if item.parent_collection_id.as_deref() == Some(id) {
+                item.parent_collection_id = None;
+            }
+        }
+        true
+    }
+
+    /// Takes an item out of whatever collection it is in.
+    pub async fn leave_collections(&self, username: &str, item_id: &str) {
+        let mut data = self.cache.write().await;
+        let Some(collections) = data.collections_by_user.get_mut(username) else {
+            return;
+        };
+        if crate::collections::leave_collections(collections, item_id, now_ms()) {
+            drop(data);
+            self.mark_dirty();
+        }
+    }
+
+    pub async fn get_collections(&self, username: &str) -> Vec<Collection> {
+        let data = self.cache.read().await;
+        data.collections_by_user.get(username).cloned().unwrap_or_default()
+    }
+
+    pub async fn save_collection(&self, username: &str, id: Option<String>, input: CollectionInput) -> Result<Collection, String> {
+        let mut data = self.cache.write().await;
+        let known_items: Vec<String> = data
+            .items_by_user
+            .get(username)
+            .map(|l| l.iter().map(|i| i.id.clone()).collect())
+            .unwrap_or_default();
+        let collections = data.collections_by_user.entry(username.to_string()).or_default();
+        let now = now_ms();
+        let id = id.unwrap_or_else(new_id);
+        if let Some(pid) = input.parent_id.as_deref() {
+            if !collections.iter().any(|c| c.id == pid) {
+                return Err("Parent collection not found".to_string());
+            }
+            if crate::collections::creates_cycle(collections, &id, Some(pid)) {
+                return Err("A collection cannot be nested inside itself".to_string());
+            }
+        }
+        let idx = match collections.iter().position(|c| c.id == id) {
+            Some(idx) => idx,
+            None => {
+                let name = input.name.clone().filter(|n| !n.trim().is_empty()).ok_or_else(|| "Collection name is required".to_string())?;
+                collections.push(Collection {
+                    id: id.clone(),
+                    name,
+                    description: None,
+                    cover_url: None,
+                    parent_id: None,
+                    item_ids: Vec::new(),
+                    created_at: now,
+                    updated_at: now,
+                });
+                collections.len() - 1
+            }
+        };
+        let c = &mut collections[idx];
+        if let Some(name) = input.name.filter(|n| !n.trim().is_empty()) {
+            c.name = name;
+        }
+        if input.description.is_some() {
+            c.description = input.description;
+        }
+        if input.cover_url.is_some() {
+            c.cover_url = input.cover_url;
+        }
+        if input.parent_id.is_some() {
+            c.parent_id = input.parent_id;
+        }
+        if let Some(ids) = input.item_ids {
+            let mut seen = Vec::new();
+            for item_id in ids {
+                if known_items.contains(&item_id) && !seen.contains(&item_id) {
+                    seen.push(item_id);
+                }
+            }
+            c.item_ids = seen;
+        }
+        c.updated_at = now;
+        let saved = c.clone();
+        drop(data);
+        self.mark_dirty();
+        Ok(saved)
+    }
+
+    pub async fn delete_collection(&self, username: &str, id: &str) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        if !Self::delete_collection_in(&mut data, username, id) {
+            return Err("Collection not found".to_string());
+        }
+        data.collection_shares.retain(|s| !(s.owner == username && s.collection_id == id));
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Shares the user's collection `id` with `grantee`, or changes the permission
+    /// of an existing share.
+    pub async fn share_collection(&self, owner: &str, id: &str, grantee: &str, permission: SharePermission) -> Result<CollectionShare, String> {
+        let mut data = self.cache.write().await;
+        if grantee == owner {
+            return Err("A collection cannot be shared with its owner".to_string());
+        }
+        if !data.users.iter().any(|u| u.username == grantee) {
+            return Err("User not found".to_string());
+        }
+        if !data.collections_by_user.get(owner).is_some_and(|l| l.iter().any(|c| c.id == id)) {
+            return Err("Collection not found".to_string());
+        }
+        let existing = data.collection_shares.iter_mut().find(|s| s.owner == owner && s.collection_id == id && s.grantee == grantee);
+        let share = match existing {
+            Some(share) => {
+                share.permission = permission;
+                share.clone()
+            }
+            None => {
+                let share = CollectionShare {
+                    owner: owner.to_string(),
+                    collection_id: id.to_string(),
+                    grantee: grantee.to_string(),
+                    permission,
+                    created_at: now_ms(),
+                };
+                data.collection_shares.push(share.clone());
+                share
+            }
+        };
+        drop(data);
+        self.mark_dirty();
+        Ok(share)
+    }
+
+    /// Stops sharing; also lets a grantee leave a collection shared with them.
+    pub async fn unshare_collection(&self, username: &str, owner: &str, id: &str, grantee: &str) -> Result<(), String> {
+        if username != owner && username != grantee {
+            return Err("Share not found".to_string());
+        }
+        let mut data = self.cache.write().await;
+        let before = data.collection_shares.len();
+        data.collection_shares.retain(|s| !(s.owner == owner && s.collection_id == id && s.grantee == grantee));
+        if data.collection_shares.len() == before {
+            return Err("Share not found".to_string());
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    pub async fn get_collection_shares(&self, username: &str) -> CollectionShares {
+        let data = self.cache.read().await;
+        CollectionShares {
+            outgoing: data.collection_shares.iter().filter(|s| s.owner == username).cloned().collect(),
+            incoming: crate::sharing::incoming(&data, username)
+                .into_iter()
+                .map(|(s, c)| SharedCollection { owner: s.owner.clone(), permission: s.permission, collection: c.clone() })
+                .collect(),
+        }
+    }
+
+    // --- Statuses ---
+    fn statuses_in(data: &CollectionData, username: &str) -> Vec<StatusDef> {
+        data.statuses_by_user.get(username).cloned().unwrap_or_else(crate::statuses::defaults)
+    }
+
+    pub async fn get_statuses(&self, username: &str) -> Vec<StatusDef> {
+        Self::statuses_in(&*self.cache.read().await, username)
+    }
+
+    /// Replaces the status list. `renames` (old name -> new name) carries items
+    /// over to renamed statuses; items whose status now counts differently get
+    /// the matching category. Returns how many items changed.
+    pub async fn set_statuses(&self, username: &str, statuses: Vec<StatusDef>, renames: &HashMap<String, String>) -> Result<usize, String> {
+        crate::statuses::validate(&statuses)?;
+        let mut data = self.cache.write().await;
+        let changed = match data.items_by_user.get_mut(username) {
+            Some(items) => crate::statuses::apply(items, &statuses, renames),
+            None => Vec::new(),
+        };
+        let now = now_ms();
+        for id in &changed {
+            if let Some(item) = data.items_by_user.get_mut(username).and_then(|l| l.iter_mut().find(|i| &i.id == id)) {
+                item.updated_at = Some(now);
+            }
+            self.log_change(&mut data, username, id);
+        }
+        data.statuses_by_user.insert(username.to_string(), statuses);
+        drop(data);
+        self.mark_dirty();
+        Ok(changed.len())
+    }
+
+    pub async fn pick_random(&self, username: &str, filters: &crate::picker::PickFilters) -> Option<crate::picker::Pick> {
+        use rand_core::{OsRng, RngCore};
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let roll = (OsRng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        crate::picker::pick(items, &Self::statuses_in(&data, username), filters, roll)
+    }
+
+    pub async fn get_content_filter(&self, username: &str) -> crate::content_rating::ContentFilter {
+        self.cache.read().await.content_filters_by_user.get(username).copied().unwrap_or_default()
+    }
+
+    pub async fn set_content_filter(&self, username: &str, filter: crate::content_rating::ContentFilter) {
+        let mut data = self.cache.write().await;
+        data.content_filters_by_user.insert(username.to_string(), filter);
+        drop(data);
+        self.mark_dirty();
+    }
+
+    // --- Last.fm ---
+    pub async fn get_lastfm_accounts(&self) -> Vec<(String, String)> {
+        self.cache.read().await.lastfm_accounts_by_user.iter().map(|(u, l)| (u.clone(), l.clone())).collect()
+    }
+
+    pub async fn set_lastfm_account(&self, username: &str, lastfm_user: &str) {
+        let mut data = self.cache.write().await;
+        data.lastfm_accounts_by_user.insert(username.to_string(), lastfm_user.to_string());
+        drop(data);
+        self.mark_dirty();
+    }
+
+    /// Sets play counts on albums already imported and, with `add_new`, imports the rest.
+    /// Play count changes are background updates: no revision or activity is recorded.
+    pub async fn apply_lastfm(&self, username: &str, albums: &[crate::lastfm::Album], add_new: bool) -> crate::lastfm::LastfmImport {
+        let mut summary = crate::lastfm::LastfmImport::default();
+        let mut data = self.cache.write().await;
+        let now = now_ms();
+        let list = data.items_by_user.entry(username.to_string()).or_default();
+        let mut changed = Vec::new();
+        let mut new_items: Vec<MediaItem> = Vec::new();
+        for album in albums {
+            let key = crate::lastfm::album_key(&album.url);
+            let is_album = |i: &MediaItem| i.provider_ids.as_ref().and_then(|ids| ids.get("lastfm")) == Some(&key);
+            match list.iter_mut().find(|i| is_album(i)) {
+                Some(item) if item.play_count != Some(album.play_count) => {
+                    item.play_count = Some(album.play_count);
+                    item.updated_at = Some(now);
+                    changed.push(item.id.clone());
+                }
+                None if add_new && !new_items.iter().any(is_album) => new_items.push(crate::lastfm::to_item(album)),
+                _ => {}
+            }
+        }
+        summary.updated = changed.len();
+        for id in &changed {
+            self.log_change(&mut data, username, id);
+        }
+        drop(data);
+        self.mark_dirty();
+        summary.added = new_items.len();
+        if !new_items.is_empty() {
+            let _ = self.import_for_user(username, new_items).await;
+        }
+        summary
+    }
+
+    // --- Plex ---
+    pub async fn get_plex_accounts(&self) -> Vec<(String, crate::plex::PlexAccount)> {
+        self.cache.read().await.plex_accounts_by_user.iter().map(|(u, a)| (u.clone(), a.clone())).collect()
+    }
+
+    /// Links `server_url`, or unlinks the user's server when `None`.
+    pub async fn set_plex_account(&self, username: &str, server_url: Option<&str>) {
+        let mut data = self.cache.write().await;
+        match server_url {
+            Some(url) => {
+                data.plex_accounts_by_user.insert(username.to_string(), crate::plex::PlexAccount { server_url: url.to_string(), last_synced_at: None });
+            }
+            None => {
+                data.plex_accounts_by_user.remove(username);
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+    }
+
+    // --- Jellyfin/Emby ---
+    pub async fn get_jellyfin_accounts(&self) -> Vec<(String, crate::jellyfin::JellyfinAccount)> {
+        self.cache.read().await.jellyfin_accounts_by_user.iter().map(|(u, a)| (u.clone(), a.clone())).collect()
+    }
+
+    /// Links a server user, or unlinks the user's server when `None`.
+    pub async fn set_jellyfin_account(&self, username: &str, account: Option<crate::jellyfin::JellyfinAccount>) {
+        let mut data = self.cache.write().await;
+        match account {
+            Some(account) => {
+                data.jellyfin_accounts_by_user.insert(username.to_string(), account);
+            }
+            None => {
+                data.jellyfin_accounts_by_user.remove(username);
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+    }
+
+    /// Copies watched state from a media server's library onto matching items;
+    /// `key` is the server's provider id key. Unlike Last.fm play counts these are
+    /// real history, so revisions and activity are recorded as for an edit.
+    pub async fn apply_server_items(&self, username: &str, key: &str, server_items: &[crate::media_server::ServerItem]) -> crate::media_server::SyncSummary {
+        let mut summary = crate::media_server::SyncSummary { scanned: server_items.len(), ..Default::default() };
+        let mut data = self.cache.write().await;
+        let statuses = Self::statuses_in(&data, username);
+        let now = now_ms();
+        let list = data.items_by_user.entry(username.to_string()).or_default();
+        let mut changed = Vec::new();
+        for server_item in server_items {
+            let Some(at) = crate::media_server::find_match(list, key, server_item) else {
+                continue;
+            };
+            summary.matched += 1;
+            let item = &mut list[at];
+            let before = item.clone();
+            if crate::media_server::apply(item, key, server_item) {
+                crate::statuses::reconcile(Some(&before), item, &statuses);
+                item.updated_at = Some(now);
+                changed.push((before, item.clone()));
+            }
+        }
+        summary.updated = changed.len();
+        for (before, after) in &changed {
+            Self::record_revision(&mut data, username, before, after);
+            self.log_change(&mut data, username, &after.id);
+        }
+        let synced_at = match key {
+            crate::plex::KEY => data.plex_accounts_by_user.get_mut(username).map(|a| &mut a.last_synced_at),
+            crate::jellyfin::KEY => data.jellyfin_accounts_by_user.get_mut(username).map(|a| &mut a.last_synced_at),
+            _ => None,
+        };
+        if let Some(synced_at) = synced_at {
+            *synced_at = Some(now);
+        }
+        drop(data);
+        self.mark_dirty();
+        summary
+    }
+
+    // --- Manual order ---
+    pub async fn get_view_order(&self, username: &str, view: &str) -> ViewOrder {
+        let data = self.cache.read().await;
+        data.view_orders_by_user.get(username).and_then(|o| o.get(view)).cloned().unwrap_or_default()
+    }
+
+    /// Stores `ids` as the manual order of `view`; ids of items the user doesn't have are dropped.
+    pub async fn set_item_order(&self, username: &str, view: &str, ids: Vec<String>) -> Result<ViewOrder, String> {
+        crate::ordering::valid_view(view)?;
+        let mut data = self.cache.write().await;
+        let items = data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let ids: Vec<String> = ids.into_iter().filter(|id| items.iter().any(|i| &i.id == id)).collect();
+        let order = data.view_orders_by_user.entry(username.to_string()).or_default().entry(view.to_string()).or_default();
+        order.set_order(&ids, now_ms());
+        let order = order.clone();
+        drop(data);
+        self.mark_dirty();
+        Ok(order)
+    }
+
+    pub async fn pin_item(&self, username: &str, view: &str, item_id: &str, pinned: bool) -> Result<ViewOrder, String> {
+        crate::ordering::valid_view(view)?;
+        let mut data = self.cache.write().await;
+        if pinned && !data.items_by_user.get(username).map(|l| l.iter().any(|i| i.id == item_id)).unwrap_or(false) {
+            return Err("Item not found".to_string());
+        }
+        let order = data.view_orders_by_user.entry(username.to_string()).or_default().entry(view.to_string()).or_default();
+        let changed = order.set_pinned(item_id, pinned, now_ms());
+        let order = order.clone();
+        drop(data);
+        if changed {
+            self.mark_dirty();
+        }
+        Ok(order)
+    }
+
+    // --- Goals ---
+    pub async fn get_goals(&self, username: &str) -> Vec<GoalProgress> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]);
+        let no_history = HashMap::new();
+        let history = data.history_by_user.get(username).unwrap_or(&no_history);
+        let now = now_ms();
+        data.goals_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .map(|g| crate::goals::progress(g, items, history, now))
+            .collect()
+    }
+
+    pub async fn save_goal(&self, username: &str, id: Option<String>, input: GoalInput) -> Result<Goal, String> {
+        let mut data = self.cache.write().await;
+        let now = now_ms();
+        let backlog = crate::goals::backlog(input.media_type.as_ref(), data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[]));
+        let goals = data.goals_by_user.entry(username.to_string()).or_default();
+        let existing = id.as_ref().and_then(|id| goals.iter().position(|g| &g.id == id));
+        let mut g = match existing {
+            Some(idx) => goals[idx].clone(),
+            None if id.is_some() => return Err("Goal not found".to_string()),
+            None => {
+                let title = input.title.clone().filter(|t| !t.trim().is_empty()).ok_or_else(|| "Goal title is required".to_string())?;
+                let kind = input.kind.unwrap_or(GoalKind::Finish);
+                let ends_at = input.ends_at.ok_or_else(|| "Goal end date is required".to_string())?;
+                // A backlog goal measures against the list as it is now
+                let target = match kind {
+                    GoalKind::ClearBacklog => backlog,
+                    GoalKind::Finish => input.target.ok_or_else(|| "Goal target is required".to_string())?,
+                };
+                Goal {
+                    id: new_id(),
+                    title,
+                    kind,
+                    media_type: input.media_type.clone(),
+                    target,
+                    starts_at: input.starts_at.unwrap_or(now),
+                    ends_at,
+                    created_at: now,
+                    updated_at: now,
+                    last_reminded_at: None,
+                }
+            }
+        };
+        if let Some(title) = input.title.filter(|t| !t.trim().is_empty()) {
+            g.title = title;
+        }
+        if input.media_type.is_some() {
+            g.media_type = input.media_type;
+        }
+        if let Some(target) = input.target.filter(|_| g.kind == GoalKind::Finish) {
+            g.target = target;
+        }
+        if let Some(at) = input.starts_at {
+            g.starts_at = at;
+        }
+        if let Some(at) = input.ends_at {
+            g.ends_at = at;
+        }
+        if g.ends_at <= g.starts_at {
+            return Err("A goal must end after it starts".to_string());
+        }
+        if g.target == 0 {
+            let reason = if g.kind == GoalKind::ClearBacklog { "The watchlist is already empty" } else { "Goal target must be at least 1" };
+            return Err(reason.to_string());
+        }
+        g.updated_at = now;
+        match existing {
+            Some(idx) => goals[idx] = g.clone(),
+            None => goals.push(g.clone()),
+        }
+        let saved = g;
+        drop(data);
+        self.mark_dirty();
+        Ok(saved)
+    }
+
+    pub async fn delete_goal(&self, username: &str, id: &str) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        let goals = data.goals_by_user.get_mut(username).ok_or_else(|| "Goal not found".to_string())?;
+        let before = goals.len();
+        goals.retain(|g| g.id != id);
+        if goals.len() == before {
+            return Err("Goal not found".to_string());
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Goals of every user that are due a behind-pace reminder, marked as reminded.
+    pub async fn take_goal_reminders(&self) -> Vec<(String, GoalProgress)> {
+        let users: Vec<String> = self.cache.read().await.goals_by_user.keys().cloned().collect();
+        let now = now_ms();
+        let mut due = Vec::new();
+        for username in users {
+            let progress = self.get_goals(&username).await;
+            for p in crate::goals::due_reminders(&progress, now) {
+                due.push((username.clone(), p.clone()));
+            }
+        }
+        if due.is_empty() {
+            return due;
+        }
+        let mut data = self.cache.write().await;
+        for (username, p) in &due {
+            if let Some(g) = data.goals_by_user.get_mut(username).and_then(|l| l.iter_mut().find(|g| g.id == p.goal.id)) {
+                g.last_reminded_at = Some(now);
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+        due
+    }
+
+    // --- Custom fields ---
+    pub async fn get_custom_field_schema(&self, username: &str) -> Vec<CustomFieldDef> {
+        let data = self.cache.read().await;
+        data.custom_fields_by_user.get(username).cloned().unwrap_or_default()
+    }
+
+    pub async fn set_custom_field_schema(&self, username: &str, fields: Vec<CustomFieldDef>) -> Result<(), String> {
+        crate::custom_fields::validate_schema(&fields)?;
+        let mut data = self.cache.write().await;
+        data.custom_fields_by_user.insert(username.to_string(), fields);
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    // --- People ---
+    pub async fn get_people(&self, username: &str) -> Vec<Person> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        let meta = data.people_by_user.get(username).cloned().unwrap_or_default();
+        crate::people::collect_people(items, &meta)
+    }
+
+    /// The person plus the items they are credited on.
+    pub async fn get_person(&self, username: &str, id: &str) -> Option<PersonDetail> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        let meta = data.people_by_user.get(username).cloned().unwrap_or_default();
+        let person = crate::people::collect_people(items, &meta).into_iter().find(|p| p.id == id)?;
+        let credited = items.iter().filter(|i| person.item_ids.contains(&i.id)).cloned().collect();
+        Some(PersonDetail { person, items: credited })
+    }
+
+    pub async fn set_person_provider_id(&self, username: &str, id: &str, provider: &str, provider_id: &str) {
+        let mut data = self.cache.write().await;
+        data.people_by_user
+            .entry(username.to_string())
+            .or_default()
+            .entry(id.to_string())
+            .or_default()
+            .provider_ids
+            .insert(provider.to_string(), provider_id.to_string());
+        drop(data);
+        self.mark_dirty();
+    }
+
+    /// Follows or unfollows a person; unfollowing drops their pending suggestions.
+    pub async fn follow_person(&self, username: &str, id: &str, follow: bool) -> Result<Person, String> {
+        let mut data = self.cache.write().await;
+        let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        let meta = data.people_by_user.get(username).cloned().unwrap_or_default();
+        let mut person = crate::people::collect_people(items, &meta).into_iter().find(|p| p.id == id).ok_or_else(|| "Person not found".to_string())?;
+        let state = &mut data.people_by_user.entry(username.to_string()).or_default().entry(id.to_string()).or_default().follow;
+        if !follow {
+            *state = None;
+            if let Some(suggestions) = data.creator_suggestions_by_user.get_mut(username) {
+                suggestions.retain(|s| s.person_id != id);
+            }
+        } else if state.is_none() {
+            *state = Some(crate::follows::FollowState { since: now_ms(), ..Default::default() });
+        }
+        person.followed = follow;
+        drop(data);
+        self.mark_dirty();
+        Ok(person)
+    }
+
+    /// Every followed person across users, for the `creatorWorks` job. People none
+    /// of whose items are left are skipped.
+    pub async fn get_followed_people(&self) -> Vec<(String, Person)> {
+        let data = self.cache.read().await;
+        let mut out = Vec::new();
+        for (username, meta) in &data.people_by_user {
+            if !meta.values().any(|m| m.follow.is_some()) {
+                continue;
+            }
+            let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+            out.extend(crate::people::collect_people(items, meta).into_iter().filter(|p| p.followed).map(|p| (username.clone(), p)));
+        }
+        out
+    }
+
+    /// Records a lookup of `person`'s works on `sources` and stores the new ones as
+    /// suggestions, which are returned.
+    pub async fn record_creator_works(&self, username: &str, person: &Person, sources: &[&str], works: Vec<crate::people::PersonWork>) -> Vec<crate::follows::CreatorSuggestion> {
+        let mut data = self.cache.write().await;
+        let dismissed = data.dismissed_works_by_user.get(username).cloned().unwrap_or_default();
+        // Unfollowed while the lookup ran
+        let Some(follow) = data.people_by_user.get_mut(username).and_then(|p| p.get_mut(&person.id)).and_then(|m| m.follow.as_mut()) else {
+            return Vec::new();
+        };
+        let now = now_ms();
+        let fresh = crate::follows::new_works(follow, sources, works, &dismissed, now);
+        let suggestions = data.creator_suggestions_by_user.entry(username.to_string()).or_default();
+        let found: Vec<crate::follows::CreatorSuggestion> = fresh
+            .into_iter()
+            .filter(|w| !suggestions.iter().any(|s| s.work.source == w.source && s.work.source_id == w.source_id))
+            .map(|work| crate::follows::CreatorSuggestion { person_id: person.id.clone(), person_name: person.name.clone(), work, found_at: now })
+            .collect();
+        suggestions.extend(found.iter().cloned());
+        drop(data);
+        self.mark_dirty();
+        found
+    }
+
+    pub async fn get_creator_suggestions(&self, username: &str) -> Vec<crate::follows::CreatorSuggestion> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        crate::follows::visible(data.creator_suggestions_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]), items)
+    }
+
+    /// Removes a suggestion and blocks the work from being suggested again.
+    pub async fn dismiss_creator_work(&self, username: &str, source: &str, source_id: &str) {
+        let mut data = self.cache.write().await;
+        if let Some(suggestions) = data.creator_suggestions_by_user.get_mut(username) {
+            suggestions.retain(|s| !(s.work.source == source && s.work.source_id == source_id));
+        }
+        data.dismissed_works_by_user.entry(username.to_string()).or_default().insert(crate::follows::work_key(source, source_id));
+        drop(data);
+        self.mark_dirty();
+    }
+
+    // --- Relations ---
+    pub async fn get_relations(&self, username: &str, id: &str) -> Vec<RelatedItem> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        let relations = data.relations_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        crate::relations::related_to(relations, items, id)
+    }
+
+    pub async fn get_all_relations(&self, username: &str) -> Vec<Relation> {
+        self.cache.read().await.relations_by_user.get(username).cloned().unwrap_or_default()
+    }
+
+    pub async fn get_franchise(&self, username: &str, id: &str) -> Vec<MediaItem> {
+        let data = self.cache.read().await;
+        let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        let relations = data.relations_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        crate::relations::franchise(relations, items, id)
+    }
+
+    pub async fn set_relation(&self, username: &str, from_id: &str, to_id: &str, kind: RelationKind) -> Result<(), String> {
+        if from_id == to_id {
+            return Err("An item cannot be related to itself".to_string());
+        }
+        let mut data = self.cache.write().await;
+        let items = data.items_by_user.get(username).map(|v| v.as_slice()).unwrap_or(&[]);
+        if !items.iter().any(|i| i.id == from_id) || !items.iter().any(|i| i.id == to_id) {
+            return Err("Item not found".to_string());
+        }
+        let relations = data.relations_by_user.entry(username.to_string()).or_default();
+        relations.retain(|r| !r.connects(from_id, to_id));
+        relations.push(Relation {
+            from_id: from_id.to_string(),
+            to_id: to_id.to_string(),
+            kind,
+            source: None,
+            created_at: now_ms(),
+        });
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    pub async fn remove_relation(&self, username: &str, from_id: &str, to_id: &str) -> Result<bool, String> {
+        let mut data = self.cache.write().await;
+        let Some(relations) = data.relations_by_user.get_mut(username) else {
+            return Ok(false);
+        };
+        let before = relations.len();
+        relations.retain(|r| !r.connects(from_id, to_id));
+        let removed = relations.len() != before;
+        drop(data);
+        if removed {
+            self.mark_dirty();
+        }
+        Ok(removed)
+    }
+
+    /// Merges provider-derived edges; returns how many were added or changed.
+    pub async fn add_auto_relations(&self, username: &str, found: Vec<Relation>) -> usize {
+        let mut data = self.cache.write().await;
+        let relations = data.relations_by_user.entry(username.to_string()).or_default();
+        let changed = found.into_iter().filter(|r| crate::relations::upsert(relations, r.clone())).count();
+        drop(data);
+        if changed > 0 {
+            self.mark_dirty();
+        }
+        changed
+    }
+
+    // --- Smart lists ---
+    pub async fn get_smart_lists(&self, username: &str) -> Vec<SmartList> {
+        let data = self.cache.read().await;
+        data.smart_lists_by_user.get(username).cloned().unwrap_or_default()
+    }
+
+    pub async fn save_smart_list(&self, username: &str, id: Option<String>, name: String, query: String) -> Result<SmartList, String> {
+        crate::smart::parse(&query)?;
+        let mut data = self.cache.write().await;
+        let lists = data.smart_lists_by_user.entry(username.to_string()).or_default();
+        let now = now_ms();
+        let saved = match id {
+            Some(id) => {
+                let list = lists.iter_mut().find(|l| l.id == id).ok_or_else(|| "Smart list not found".to_string())?;
+                list.name = name;
+                list.query = query;
+                list.updated_at = now;
+                list.clone()
+            }
+            None => {
+                let list = SmartList { id: new_id(), name, query, created_at: now, updated_at: now };
+                lists.push(list.clone());
+                list
+            }
+        };
+        drop(data);
+        self.mark_dirty();
+        Ok(saved)
+    }
+
+    pub async fn delete_smart_list(&self, username: &str, id: &str) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        if let Some(lists) = data.smart_lists_by_user.get_mut(username) {
+            lists.retain(|l| l.id != id);
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    pub async fn evaluate_query(&self, username: &str, query: &str) -> Result<Vec<MediaItem>, String> {
+        let q = crate::smart::parse(query)?;
+        let data = self.cache.read().await;
+        Ok(q.filter(data.items_by_user.get(username).map(|l| l.as_slice()).unwrap_or(&[])))
+    }
+
+    // --- Update checks ---
+    /// Ongoing items with notifications on whose last check is older than `min_age_ms`,
+    /// or whose next episode has aired since they were last checked.
+    pub async fn get_items_due_for_update_check(&self, min_age_ms: i64) -> Vec<(String, MediaItem)> {
+        let data = self.cache.read().await;
+        let now = now_ms();
+        let cutoff = now - min_age_ms;
+        let aired_since = |i: &MediaItem, t: i64| i.next_airing.as_ref().map(|a| a.at > t && a.at <= now).unwrap_or(false);
+        data.items_by_user
+            .iter()
+            .flat_map(|(user, items)| items.iter().map(move |i| (user, i)))
+            .filter(|(_, i)| i.is_ongoing && i.notification_enabled != Some(false))
+            .filter(|(_, i)| i.last_checked_at.map(|t| t <= cutoff || aired_since(i, t)).unwrap_or(true))
+            .map(|(user, i)| (user.clone(), i.clone()))
+            .collect()
+    }
+
+    /// Stamps `last_checked_at`, stores the next airing and records the latest release
+    /// if it differs from what the item already shows. Returns true when the item was
+    /// flagged as having a new update.
+    pub async fn apply_update_check(&self, username: &str, id: &str, check: &crate::updates::Check) -> bool {
+        let mut data = self.cache.write().await;
+        let Some(item) = data.items_by_user.get_mut(username).and_then(|l| l.iter_mut().find(|i| i.id == id)) else {
+            return false;
+        };
+        item.last_checked_at = Some(now_ms());
+        item.next_airing = check.next_airing.clone();
+        let is_new = check.latest.is_some() && check.latest != item.latest_update_info;
+        if is_new {
+            item.latest_update_info = check.latest.clone();
+            item.has_new_update = Some(true);
+        }
+        drop(data);
+        self.mark_dirty();
+        is_new
+    }
+
+    // --- Cover cache ---
+    pub async fn set_poster_cache(&self, username: &str, id: &str, cache: Option<crate::images::CachedImage>) -> Result<MediaItem, String> {
+        let mut data = self.cache.write().await;
+        let item = data
+            .items_by_user
+            .get_mut(username)
+            .and_then(|l| l.iter_mut().find(|i| i.id == id))
+            .ok_or_else(|| "Item not found".to_string())?;
+        item.poster_cache = cache;
+        let updated = item.clone();
+        drop(data);
+        self.mark_dirty();
+        Ok(updated)
+    }
+
+    // --- Scheduled jobs ---
+    pub async fn get_job_runs(&self) -> HashMap<JobKind, JobRun> {
+        self.cache.read().await.job_runs.clone()
+    }
+
+    pub async fn record_job_run(&self, kind: JobKind, run: JobRun) {
+        self.cache.write().await.job_runs.insert(kind, run);
+        self.mark_dirty();
+    }
+
+    pub fn backup_dir(&self) -> Result<PathBuf, String> {
+        let dir = self.path.parent().ok_or_else(|| "Invalid data path".to_string())?.join("backups");
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        Ok(dir)
+    }
+
+    /// The whole collection in the same format as collection.json (encrypted if it is).
+    pub async fn snapshot_json(&self) -> Result<Vec<u8>, String> {
+        self.ensure_unlocked()?;
+        let json = {
+            let data = self.cache.read().await;
+            serde_json::to_vec_pretty(&*data).map_err(|e| e.to_string())?
+        };
+        crate::at_rest::encode(json, self.current_key().as_ref())
+    }
+
+    /// Whether `key` decrypts collection.json (or its .bak when the main file is unreadable).
+    pub fn key_opens_files(&self, key: &Key) -> bool {
+        Self::read_data(&self.path, Some(key)).is_ok() || Self::read_data(&Self::backup_path(&self.path), Some(key)).is_ok()
+    }
+
+    fn current_key(&self) -> Option<Key> {
+        self.key.lock().ok().and_then(|k| *k)
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.current_key().is_some()
+    }
+
+    /// .bak and the snapshot backups, which hold the same data as collection.json.
+    fn local_copies(&self) -> Result<Vec<PathBuf>, String> {
+        let mut files = vec![Self::backup_path(&self.path)];
+        if let Ok(entries) = fs::read_dir(self.backup_dir()?) {
+            files.extend(
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("collection-") && n.ends_with(".json"))),
+            );
+        }
+        Ok(files.into_iter().filter(|f| f.exists()).collect())
+    }
+
+    /// Encrypts collection.json and its local copies with `key`, or decrypts them
+    /// with None. Copies that can't be read are left alone and counted as failed.
+    pub async fn set_encryption(&self, key: Option<Key>) -> Result<usize, String> {
+        let old = self.current_key();
+        *self.key.lock().map_err(|e| e.to_string())? = key;
+        self.dirty.store(true, Ordering::SeqCst);
+        if let Err(e) = self.flush().await {
+            *self.key.lock().map_err(|e| e.to_string())? = old;
+            return Err(e);
+        }
+        let _guard = self.write_lock.lock().await;
+        let mut failed = 0;
+        for file in self.local_copies()? {
+            let rewritten = Self::read_data(&file, old.as_ref())
+                .and_then(|data| serde_json::to_vec_pretty(&data).map_err(|e| e.to_string()))
+                .and_then(|json| crate::at_rest::encode(json, key.as_ref()))
+                .and_then(|bytes| fs::write(&file, bytes).map_err(|e| e.to_string()));
+            if rewritten.is_err() {
+                failed += 1;
+            }
+        }
+        Ok(failed)
+    }
+
+    /// Flushes and copies collection.json to `backups/collection-<ts>.json`, keeping
+    /// the newest `SNAPSHOT_KEEP` copies.
+    pub async fn write_snapshot_backup(&self) -> Result<PathBuf, String> {
+        self.dirty.store(true, Ordering::SeqCst);
+        self.flush().await?;
+        let _guard = self.write_lock.lock().await;
+        let dir = self.backup_dir()?;
+        let target = dir.join(format!("collection-{}.json", now_ms()));
+        fs::copy(&self.path, &target).map_err(|e| e.to_string())?;
+
+        let mut snapshots: Vec<PathBuf> = fs::read_dir(&dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with("collection-")).unwrap_or(false))
+            .collect();
+        // Timestamps have the same digit count, so name order is age order
+        snapshots.sort();
+        let excess = snapshots.len().saturating_sub(SNAPSHOT_KEEP);
+        for old in &snapshots[..excess] {
+            let _ = fs::remove_file(old);
+        }
+        Ok(target)
+    }
+
+    pub async fn get_all_items(&self) -> Vec<(String, MediaItem)> {
+        let data = self.cache.read().await;
+        data.items_by_user
+            .iter()
+            .flat_map(|(user, items)| items.iter().map(move |i| (user.clone(), i.clone())))
+            .collect()
+    }
+
+    pub async fn get_items_with_provider_ids(&self) -> Vec<(String, MediaItem)> {
+        let data = self.cache.read().await;
+        data.items_by_user
+            .iter()
+            .flat_map(|(user, items)| items.iter().map(move |i| (user, i)))
+            .filter(|(_, i)| i.provider_ids.as_ref().map(|ids| !ids.is_empty()).unwrap_or(false))
+            .map(|(user, i)| (user.clone(), i.clone()))
+            .collect()
+    }
+
+    /// Applies refreshed provider details, recording a revision when anything changed.
+    pub async fn apply_item_details(&self, username: &str, id: &str, details: ItemDetails) -> Result<FieldChanges, String> {
+        let mut data = self.cache.write().await;
+        let list = data.items_by_user.get_mut(username).ok_or_else(|| "Item not found".to_string())?;
+        let item = list.iter_mut().find(|i| i.id == id).ok_or_else(|| "Item not found".to_string())?;
+        let before = item.clone();
+        let changes = crate::metadata::apply_details(item, details);
+        if changes.updated.is_empty() {
+            return Ok(changes);
+        }
+        crate::dates::normalize(item);
+        item.last_edited_at = Some(now_ms());
+        item.updated_at = item.last_edited_at;
+        let after = item.clone();
+        Self::record_revision(&mut data, username, &before, &after);
+        self.log_change(&mut data, username, id);
+        drop(data);
+        self.mark_dirty();
+        Ok(changes)
+    }
+
+    // --- Feeds ---
+    /// Attaches (or with `None`, detaches) a feed URL. Changing the URL starts over.
+    pub async fn set_item_feed(&self, username: &str, item_id: &str, url: Option<String>) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        if !data.items_by_user.get(username).map(|l| l.iter().any(|i| i.id == item_id)).unwrap_or(false) {
+            return Err("Item not found".to_string());
+        }
+        let feeds = data.feeds_by_user.entry(username.to_string()).or_default();
+        let url = url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty());
+        match url {
+            Some(url) if !url.starts_with("http://") && !url.starts_with("https://") => {
+                return Err("Feed URL must start with http:// or https://".to_string());
+            }
+            Some(url) => match feeds.iter_mut().find(|f| f.item_id == item_id) {
+                Some(f) if f.url == url => {}
+                Some(f) => {
+                    f.url = url;
+                    f.last_polled_at = None;
+                    f.last_error = None;
+                    f.entries.clear();
+                }
+                None => feeds.push(FeedSubscription {
+                    item_id: item_id.to_string(),
+                    url,
+                    last_polled_at: None,
+                    last_error: None,
+                    entries: Vec::new(),
+                }),
+            },
+            None => feeds.retain(|f| f.item_id != item_id),
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Subscriptions with only their unread entries, optionally for a single item.
+    pub async fn get_item_updates(&self, username: &str, item_id: Option<&str>) -> Vec<FeedSubscription> {
+        let data = self.cache.read().await;
+        data.feeds_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .filter(|f| item_id.map(|id| f.item_id == id).unwrap_or(true))
+            .map(|f| {
+                let mut f = f.clone();
+                f.entries.retain(|e| !e.read);
+                f
+            })
+            .collect()
+    }
+
+    /// Marks the given entries (or all of the item's entries) read; clears the
+    /// item's new-update flag once nothing unread is left.
+    pub async fn mark_feed_entries_read(&self, username: &str, item_id: &str, entry_ids: Option<Vec<String>>) -> Result<usize, String> {
+        let mut data = self.cache.write().await;
+        let sub = data
+            .feeds_by_user
+            .get_mut(username)
+            .and_then(|l| l.iter_mut().find(|f| f.item_id == item_id))
+            .ok_or_else(|| "Feed not found".to_string())?;
+        let mut count = 0;
+        for e in sub.entries.iter_mut().filter(|e| !e.read) {
+            if entry_ids.as_ref().map(|ids| ids.contains(&e.id)).unwrap_or(true) {
+                e.read = true;
+                count += 1;
+            }
+        }
+        let all_read = sub.entries.iter().all(|e| e.read);
+        if all_read {
+            if let Some(item) = data.items_by_user.get_mut(username).and_then(|l| l.iter_mut().find(|i| i.id == item_id)) {
+                item.has_new_update = Some(false);
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(count)
+    }
+
+    pub(crate) async fn get_feeds_due(&self, min_age_ms: i64) -> Vec<(String, FeedSubscription)> {
+        let data = self.cache.read().await;
+        let cutoff = now_ms() - min_age_ms;
+        data.feeds_by_user
+            .iter()
+            .flat_map(|(user, feeds)| feeds.iter().map(move |f| (user, f)))
+            .filter(|(_, f)| f.last_polled_at.map(|t| t <= cutoff).unwrap_or(true))
+            .map(|(user, f)| (user.clone(), f.clone()))
+            .collect()
+    }
+
+    /// Stores a poll result and returns the entries that are new since the last poll.
+    pub(crate) async fn record_feed_poll(&self, username: &str, item_id: &str, fetched: Result<Vec<FeedEntry>, String>) -> Vec<FeedEntry> {
+        let mut data = self.cache.write().await;
+        let Some(sub) = data.feeds_by_user.get_mut(username).and_then(|l| l.iter_mut().find(|f| f.item_id == item_id)) else {
+            return Vec::new();
+        };
+        let fresh = match fetched {
+            Ok(entries) => {
+                sub.last_error = None;
+                crate::feeds::absorb_entries(sub, entries)
+            }
+            Err(e) => {
+                sub.last_error = Some(e);
+                Vec::new()
+            }
+        };
+        sub.last_polled_at = Some(now_ms());
+        if let Some(newest) = fresh.first() {
+            if let Some(item) = data.items_by_user.get_mut(username).and_then(|l| l.iter_mut().find(|i| i.id == item_id)) {
+                item.latest_update_info = Some(newest.title.clone());
+                item.has_new_update = Some(true);
+                item.last_checked_at = Some(now_ms());
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+        fresh
+    }
+
+    pub async fn get_settings(&self) -> Settings {
+        self.cache.read().await.settings.clone()
+    }
+
+    /// Whether closing the main window should only hide it; safe to call from
+    /// synchronous event handlers.
+    pub fn close_to_tray(&self) -> bool {
+        self.close_to_tray.load(Ordering::SeqCst)
+    }
+
+    pub async fn update_settings(&self, mut settings: Settings) -> Result<(), String> {
+        if let Some(s3) = settings.s3_backup.as_mut() {
+            crate::cloud_backup::move_secret_to_keychain(s3);
+        }
+        let mut data = self.cache.write().await;
+        self.close_to_tray.store(settings.close_to_tray, Ordering::SeqCst);
+        data.settings = settings;
+        Self::purge_expired_trash(&mut data);
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// What a paired device receives for `username`: the items changed here at or
+    /// after `since` (by our clock, as returned in a previous payload's `until`),
+    /// plus tombstones for the ones deleted. A `since` of 0, or one older than
+    /// the change log, gets the whole collection. Nothing account-related is included.
+    pub async fn get_sync_changes(&self, username: &str, since: i64) -> SyncPayload {
+        let data = self.cache.read().await;
+        let until = now_ms();
+        let log = data.change_log_by_user.get(username);
+        let changed: Option<Vec<&String>> = match log {
+            Some(log) if since > 0 && since >= log.started_at => {
+                Some(log.changes.iter().filter(|(_, at)| **at >= since).map(|(id, _)| id).collect())
+            }
+            _ => None,
+        };
+        let included = |id: &String| match &changed {
+            Some(ids) => ids.contains(&id),
+            None => true,
+        };
+        let trashed = data
+            .trash_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .filter(|i| included(&i.id))
+            .map(|i| Tombstone { item_id: i.id.clone(), deleted_at: i.deleted_at.unwrap_or(0) });
+        let purged = data.tombstones_by_user.get(username).into_iter().flatten().filter(|t| included(&t.item_id)).cloned();
+        let credentials_changed_at = data
+            .users
+            .iter()
+            .find(|u| u.username == username)
+            .and_then(|u| u.last_modified)
+            .filter(|at| changed.is_none() || *at >= since);
+        let view_orders = data
+            .view_orders_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .filter(|(_, o)| changed.is_none() || o.updated_at >= since)
+            .map(|(view, o)| (view.clone(), o.clone()))
+            .collect();
+        SyncPayload {
+            username: username.to_string(),
+            items: data.items_by_user.get(username).into_iter().flatten().filter(|i| included(&i.id)).cloned().collect(),
+            tombstones: trashed.chain(purged).collect(),
+            until,
+            credentials_changed_at,
+            view_orders,
+        }
+    }
+
+    pub async fn sync_cursor(&self, username: &str, peer: &str) -> SyncCursor {
+        let data = self.cache.read().await;
+        data.sync_cursors_by_user.get(username).and_then(|c| c.get(peer)).cloned().unwrap_or_default()
+    }
+
+    pub async fn set_sync_cursor(&self, username: &str, peer: &str, cursor: SyncCursor) {
+        let mut data = self.cache.write().await;
+        data.sync_cursors_by_user.entry(username.to_string()).or_default().insert(peer.to_string(), cursor);
+        drop(data);
+        self.mark_dirty();
+    }
+
+    /// Merges another device's items into `username`'s collection, last write
+    /// wins: new items are added, newer edits replace older ones, and newer
+    /// deletions move the local copy to trash. Items edited on both sides at the
+    /// same time, or edited on one side after being deleted on the other, are
+    /// reported as conflicts along with how they were resolved.
+    pub async fn merge_sync_payload(&self, username: &str, incoming: SyncPayload) -> Result<MergeSummary, String> {
+        let mut data = self.cache.write().await;
+        let mut summary = MergeSummary::default();
+        // Credentials stay on each device; a newer change elsewhere is only reported.
+        // Times from the peer's future are ignored rather than trusted.
+        if let Some(at) = incoming.credentials_changed_at.filter(|at| *at <= now_ms() + MAX_CLOCK_SKEW_MS) {
+            let local = data.users.iter().find(|u| u.username == username).and_then(|u| u.last_modified).unwrap_or(0);
+            summary.credentials_changed = at > local;
+        }
+        if !incoming.view_orders.is_empty() {
+            let orders = data.view_orders_by_user.entry(username.to_string()).or_default();
+            crate::ordering::merge(orders, incoming.view_orders.clone());
+        }
+        let conflict = |item: &MediaItem, kind, resolution, local: Option<i64>, remote: Option<&MediaItem>| SyncConflict {
+            username: username.to_string(),
+            item_id: item.id.clone(),
+            title: item.title.clone(),
+            kind,
+            resolution,
+            local_updated_at: local,
+            remote_updated_at: remote.map(|r| r.version()),
+            remote: remote.cloned(),
+        };
+        // Local deletion time per id, from trash and from purged-item tombstones
+        let mut local_deleted: HashMap<String, i64> = HashMap::new();
+        for t in data.tombstones_by_user.get(username).into_iter().flatten() {
+            local_deleted.insert(t.item_id.clone(), t.deleted_at);
+        }
+        for i in data.trash_by_user.get(username).into_iter().flatten() {
+            local_deleted.insert(i.id.clone(), i.deleted_at.unwrap_or(0));
+        }
+        let mut trashed = Vec::new();
+        let mut touched = Vec::new();
+
+        {
+            let local_items = data.items_by_user.entry(username.to_string()).or_default();
+
+            for tombstone in &incoming.tombstones {
+                let Some(idx) = local_items.iter().position(|i| i.id == tombstone.item_id) else {
+                    continue;
+                };
+                let local = &local_items[idx];
+                if tombstone.deleted_at >= local.version() {
+                    let mut item = local_items.remove(idx);
+                    item.deleted_at = Some(tombstone.deleted_at);
+                    touched.push(item.id.clone());
+                    trashed.push(item);
+                    summary.deleted += 1;
+                } else {
+                    summary.conflicts.push(conflict(local, ConflictKind::DeleteVsEdit, ConflictResolution::KeptLocal, Some(local.version()), None));
+                }
+            }
+
+            for mut item in incoming.items {
+                // Devices on an older version send items without the parsed date
+                crate::dates::normalize(&mut item);
+                if let Some(existing_idx) = local_items.iter().position(|i| i.id == item.id) {
+                    let existing = &local_items[existing_idx];
+                    if Self::changed_fields(existing, &item).iter().all(|f| f == "posterCache") {
+                        summary.unchanged += 1;
+                        continue;
+                    }
+                    let (incoming_ts, existing_ts) = (item.version(), existing.version());
+                    if incoming_ts > existing_ts {
+                        // The cover cache refers to files on the other device
+                        item.poster_cache = existing.poster_cache.clone();
+                        touched.push(item.id.clone());
+                        local_items[existing_idx] = item;
+                        summary.updated += 1;
+                    } else if incoming_ts == existing_ts {
+                        summary.conflicts.push(conflict(existing, ConflictKind::Edit, ConflictResolution::KeptLocal, Some(existing_ts), Some(&item)));
+                    } else {
+                        summary.unchanged += 1;
+                    }
+                    continue;
+                }
+                item.poster_cache = None;
+                match local_deleted.get(&item.id) {
+                    Some(&deleted_at) if deleted_at >= item.version() => {
+                        // Deleted here after the other device's last edit; their next sync drops it
+                        summary.unchanged += 1;
+                    }
+                    Some(&deleted_at) => {
+                        summary.conflicts.push(conflict(&item, ConflictKind::DeleteVsEdit, ConflictResolution::TookRemote, Some(deleted_at), Some(&item)));
+                        touched.push(item.id.clone());
+                        local_items.push(item);
+                        summary.added += 1;
+                    }
+                    None => {
+                        touched.push(item.id.clone());
+                        local_items.push(item);
+                        summary.added += 1;
+                    }
+                }
+            }
+        }
+
+        // Resurrected items leave the trash / tombstone list
+        let present: Vec<String> = data.items_by_user.get(username).into_iter().flatten().map(|i| i.id.clone()).collect();
+        if let Some(trash) = data.trash_by_user.get_mut(username) {
+            trash.retain(|i| !present.contains(&i.id));
+        }
+        if let Some(tombstones) = data.tombstones_by_user.get_mut(username) {
+            tombstones.retain(|t| !present.contains(&t.item_id));
+        }
+        if !trashed.is_empty() {
+            let trash = data.trash_by_user.entry(username.to_string()).or_default();
+            for item in trashed {
+                trash.retain(|i| i.id != item.id);
+                trash.insert(0, item);
+            }
+        }
+        for id in &touched {
+            self.log_change(&mut data, username, id);
+        }
+
+        drop(data);
+        self.mark_dirty();
+        Ok(summary)
+    }
+
+    // --- Trusted devices ---
+    /// Refuses to pair a device that reuses the name of another device already
+    /// paired for `username`; the user has to revoke the old one first.
+    pub async fn check_new_pairing(&self, username: &str, fingerprint: &str, name: &str) -> Result<(), String> {
+        let data = self.cache.read().await;
+        let devices = data.trusted_devices_by_user.get(username).map(|d| d.as_slice()).unwrap_or(&[]);
+        match devices.iter().any(|d| d.name == name && !d.fingerprint.is_empty() && d.fingerprint != fingerprint) {
+            true => Err(format!("A different device named {} is already paired. Remove it before pairing this one.", name)),
+            false => Ok(()),
+        }
+    }
+
+    /// Trusts a newly paired device, replacing an earlier pairing with the same
+    /// device (or a same-named one from before key pinning).
+    pub async fn add_trusted_device(&self, username: &str, fingerprint: &str, name: &str, token_hash: String) -> Result<TrustedDevice, String> {
+        self.check_new_pairing(username, fingerprint, name).await?;
+        let mut data = self.cache.write().await;
+        let device = TrustedDevice {
+            id: new_id(),
+            name: name.to_string(),
+            fingerprint: fingerprint.to_string(),
+            token_hash,
+            paired_at: now_ms(),
+            last_seen_at: None,
+            last_synced_at: None,
+        };
+        let devices = data.trusted_devices_by_user.entry(username.to_string()).or_default();
+        devices.retain(|d| d.fingerprint != fingerprint && !(d.fingerprint.is_empty() && d.name == name));
+        devices.push(device.clone());
+        drop(data);
+        self.mark_dirty();
+        Ok(TrustedDevice { token_hash: String::new(), ..device })
+    }
+
+    pub async fn get_trusted_devices(&self, username: &str) -> Vec<TrustedDevice> {
+        let data = self.cache.read().await;
+        data.trusted_devices_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .map(|d| TrustedDevice { token_hash: String::new(), ..d.clone() })
+            .collect()
+    }
+
+    /// Forgets the device and the token it gave us, so neither side can sync the other.
+    pub async fn revoke_trusted_device(&self, username: &str, id: &str) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        let devices = data.trusted_devices_by_user.get_mut(username).ok_or_else(|| "Device not found".to_string())?;
+        let idx = devices.iter().position(|d| d.id == id).ok_or_else(|| "Device not found".to_string())?;
+        let device = devices.remove(idx);
+        if let Some(peers) = data.peer_tokens_by_user.get_mut(username) {
+            peers.remove(&device.fingerprint);
+        }
+        if let Some(cursors) = data.sync_cursors_by_user.get_mut(username) {
+            cursors.remove(&device.fingerprint);
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    pub async fn mark_peer_synced(&self, username: &str, peer: &str, at: i64) {
+        let mut data = self.cache.write().await;
+        let Some(device) = data.trusted_devices_by_user.get_mut(username).and_then(|d| d.iter_mut().find(|d| d.fingerprint == peer)) else {
+            return;
+        };
+        device.last_synced_at = Some(at);
+        drop(data);
+        self.mark_dirty();
+    }
+
+    /// The user and device name a device token belongs to; also records that the device was seen.
+    pub async fn user_for_sync_token(&self, token_hash: &str) -> Option<(String, String)> {
+        let mut data = self.cache.write().await;
+        let (username, device) = data
+            .trusted_devices_by_user
+            .iter_mut()
+            .find_map(|(u, devices)| devices.iter_mut().find(|d| d.token_hash == token_hash).map(|d| (u.clone(), d)))?;
+        device.last_seen_at = Some(now_ms());
+        let name = device.name.clone();
+        drop(data);
+        self.mark_dirty();
+        Some((username, name))
+    }
+
+    // --- Webhooks ---
+    pub async fn get_webhooks(&self, username: &str) -> Vec<Webhook> {
+        let data = self.cache.read().await;
+        data.webhooks_by_user.get(username).cloned().unwrap_or_default()
+    }
+
+    /// Creates or replaces a webhook; a new one gets an id and, if none was given, a secret.
+    pub async fn save_webhook(&self, username: &str, mut hook: Webhook) -> Result<Webhook, String> {
+        let url = hook.url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err("Webhook URL must start with http:// or https://".to_string());
+        }
+        hook.url = url.to_string();
+        if hook.secret.trim().is_empty() {
+            hook.secret = crate::sync::new_token();
+        }
+        let mut data = self.cache.write().await;
+        let hooks = data.webhooks_by_user.entry(username.to_string()).or_default();
+        match hooks.iter_mut().find(|h| !hook.id.is_empty() && h.id == hook.id) {
+            Some(existing) => {
+                hook.last_delivery = existing.last_delivery.take();
+                *existing = hook.clone();
+            }
+            None => {
+                hook.id = new_id();
+                hooks.push(hook.clone());
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(hook)
+    }
+
+    pub async fn delete_webhook(&self, username: &str, id: &str) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        let hooks = data.webhooks_by_user.get_mut(username).ok_or_else(|| "Webhook not found".to_string())?;
+        let before = hooks.len();
+        hooks.retain(|h| h.id != id);
+        if hooks.len() == before {
+            return Err("Webhook not found".to_string());
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    pub async fn record_webhook_delivery(&self, username: &str, id: &str, delivery: crate::webhooks::Delivery) {
+        let mut data = self.cache.write().await;
+        let Some(hook) = data.webhooks_by_user.get_mut(username).and_then(|h| h.iter_mut().find(|h| h.id == id)) else {
+            return;
+        };
+        hook.last_delivery = Some(delivery);
+        drop(data);
+        self.mark_dirty();
+    }
+
+    // --- Sync history ---
+    pub async fn record_sync_session(&self, username: &str, session: crate::sync::SyncSession) {
+        let mut data = self.cache.write().await;
+        let history = data.sync_history_by_user.entry(username.to_string()).or_default();
+        history.insert(0, session);
+        history.truncate(SYNC_HISTORY_MAX);
+        drop(data);
+        self.mark_dirty();
+    }
+
+    pub async fn record_auth_event(&self, event: crate::auth::AuthEvent) {
+        let mut data = self.cache.write().await;
+        data.auth_log.insert(0, event);
+        data.auth_log.truncate(AUTH_LOG_MAX);
+        drop(data);
+        self.mark_dirty();
+    }
+
+    /// Newest first: events for `username` and LAN requests no account could be matched to.
+    pub async fn get_auth_log(&self, username: &str, limit: usize) -> Vec<crate::auth::AuthEvent> {
+        let data = self.cache.read().await;
+        data.auth_log
+            .iter()
+            .filter(|e| e.username.as_deref().map(|u| u == username).unwrap_or(true))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Newest first, optionally only sessions with one peer.
+    pub async fn get_sync_history(&self, username: &str, peer: Option<&str>, limit: usize) -> Vec<crate::sync::SyncSession> {
+        let data = self.cache.read().await;
+        data.sync_history_by_user
+            .get(username)
+            .into_iter()
+            .flatten()
+            .filter(|s| peer.map(|p| s.peer == p).unwrap_or(true))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// This device's sync encryption key, generated and stored the first time it is needed.
+    pub async fn device_key(&self) -> Result<crate::envelope::DeviceKey, String> {
+        let mut data = self.cache.write().await;
+        if let Some(stored) = &data.device_key {
+            return crate::envelope::DeviceKey::from_base64(stored);
+        }
+        let key = crate::envelope::DeviceKey::generate();
+        data.device_key = Some(key.to_base64());
+        drop(data);
+        self.mark_dirty();
+        Ok(key)
+    }
+
+    /// Pins `public_key` under its fingerprint. A pin is never replaced by a different key.
+    pub async fn set_peer_key(&self, public_key: &str) -> Result<String, String> {
+        let peer = crate::envelope::fingerprint(public_key);
+        let mut data = self.cache.write().await;
+        let pinned = data.peer_keys.entry(peer.clone()).or_insert_with(|| public_key.to_string());
+        if pinned != public_key {
+            return Err("A different key is already pinned for that device".to_string());
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(peer)
+    }
+
+    pub async fn peer_key(&self, peer: &str) -> Option<String> {
+        let data = self.cache.read().await;
+        data.peer_keys.get(peer).cloned()
+    }
+
+    /// Remembers the token a peer gave us for `username`, keyed by the peer's fingerprint.
+    pub async fn set_peer_token(&self, username: &str, peer: &str, token: &str) {
+        let mut data = self.cache.write().await;
+        data.peer_tokens_by_user
+            .entry(username.to_string())
+            .or_default()
+            .insert(peer.to_string(), token.to_string());
+        drop(data);
+        self.mark_dirty();
+    }
+
+    pub async fn peer_token(&self, username: &str, peer: &str) -> Option<String> {
+        let data = self.cache.read().await;
+        data.peer_tokens_by_user.get(username)?.get(peer).cloned()
+    }
+
+    /// Every local user paired with the device whose fingerprint is `peer`.
+    pub async fn users_paired_with(&self, peer: &str) -> Vec<String> {
+        let data = self.cache.read().await;
+        data.peer_tokens_by_user
+            .iter()
+            .filter(|(_, peers)| peers.contains_key(peer))
+            .map(|(user, _)| user.clone())
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub async fn update_item(&self, _item: MediaItem) -> Result<(), String> {
+        Err("update_item deprecated; use per-user methods".to_string())
+    }
+    
+    // Bulk import
+    pub async fn import_for_user(&self, username: &str, items: Vec<MediaItem>) -> Result<(), String> {
+         let mut data = self.cache.write().await;
+         let list = data.items_by_user.entry(username.to_string()).or_default();
+         let existing_ids: Vec<String> = list.iter().map(|i| i.id.clone()).collect();
+         let mut changes = Vec::new();
+         let mut imported = Vec::new();
+         for mut item in items {
+             if !existing_ids.contains(&item.id) {
+                 crate::dates::normalize(&mut item);
+                 item.updated_at.get_or_insert_with(now_ms);
+                 imported.push(item.id.clone());
+                 changes.push(ItemChange { item_id: item.id.clone(), index: Some(list.len()), before: None, after: Some(item.clone()) });
+                 list.push(item);
+             }
+         }
+         for id in &imported {
+             self.log_change(&mut data, username, id);
+         }
+         self.record(username, OperationKind::Import, changes).await;
+         let inner = &mut *data;
+         if let Some(list) = inner.items_by_user.get_mut(username) {
+             let collections = inner.collections_by_user.entry(username.to_string()).or_default();
+             crate::collections::absorb_legacy_flags(list, collections, now_ms());
+         }
+         drop(data);
+         self.mark_dirty();
+         Ok(())
+    }
+
+    pub async fn user_archive(&self, username: &str) -> crate::archive::UserArchive {
+        let data = self.cache.read().await;
+        crate::archive::UserArchive {
+            version: crate::archive::ARCHIVE_VERSION,
+            username: username.to_string(),
+            exported_at: now_ms(),
+            items: data.items_by_user.get(username).cloned().unwrap_or_default(),
+            trash: data.trash_by_user.get(username).cloned().unwrap_or_default(),
+            history: data.history_by_user.get(username).cloned().unwrap_or_default(),
+            collections: data.collections_by_user.get(username).cloned().unwrap_or_default(),
+            smart_lists: data.smart_lists_by_user.get(username).cloned().unwrap_or_default(),
+            custom_fields: data.custom_fields_by_user.get(username).cloned().unwrap_or_default(),
+            relations: data.relations_by_user.get(username).cloned().unwrap_or_default(),
+            people: data.people_by_user.get(username).cloned().unwrap_or_default(),
+            feeds: data.feeds_by_user.get(username).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Adds what the archive has and this account doesn't; existing items,
+    /// lists and settings are never overwritten.
+    pub async fn import_user_archive(&self, username: &str, archive: crate::archive::UserArchive) -> crate::archive::ArchiveImport {
+        let mut data = self.cache.write().await;
+        let mut summary = crate::archive::ArchiveImport::default();
+        let user = username.to_string();
+        let known: std::collections::HashSet<String> = data
+            .items_by_user
+            .get(username)
+            .into_iter()
+            .chain(data.trash_by_user.get(username))
+            .flatten()
+            .map(|i| i.id.clone())
+            .collect();
+
+        let mut changes = Vec::new();
+        let mut added = Vec::new();
+        let list = data.items_by_user.entry(user.clone()).or_default();
+        for mut item in archive.items {
+            if known.contains(&item.id) {
+                summary.skipped += 1;
+                continue;
+            }
+            crate::dates::normalize(&mut item);
+            item.updated_at.get_or_insert_with(now_ms);
+            changes.push(ItemChange { item_id: item.id.clone(), index: Some(list.len()), before: None, after: Some(item.clone()) });
+            added.push(item.id.clone());
+            list.push(item);
+        }
+        summary.items = added.len();
+        let trash = data.trash_by_user.entry(user.clone()).or_default();
+        for item in archive.trash {
+            if known.contains(&item.id) {
+                summary.skipped += 1;
+            } else {
+                trash.push(item);
+                summary.trash += 1;
+            }
+        }
+        let history = data.history_by_user.entry(user.clone()).or_default();
+        for (id, revisions) in archive.history {
+            if !known.contains(&id) {
+                history.entry(id).or_insert(revisions);
+            }
+        }
+
+        let collections = data.collections_by_user.entry(user.clone()).or_default();
+        for c in archive.collections {
+            if !collections.iter().any(|x| x.id == c.id) {
+                collections.push(c);
+                summary.collections += 1;
+            }
+        }
+        let smart_lists = data.smart_lists_by_user.entry(user.clone()).or_default();
+        for l in archive.smart_lists {
+            if !smart_lists.iter().any(|x| x.id == l.id) {
+                smart_lists.push(l);
+                summary.smart_lists += 1;
+            }
+        }
+        let fields = data.custom_fields_by_user.entry(user.clone()).or_default();
+        for f in archive.custom_fields {
+            if !fields.iter().any(|x| x.key == f.key) {
+                fields.push(f);
+                summary.custom_fields += 1;
+            }
+        }
+        let relations = data.relations_by_user.entry(user.clone()).or_default();
+        for r in archive.relations {
+            if !relations.iter().any(|x| x.connects(&r.from_id, &r.to_id)) {
+                relations.push(r);
+                summary.relations += 1;
+            }
+        }
+        let people = data.people_by_user.entry(user.clone()).or_default();
+        for (id, meta) in archive.people {
+            people.entry(id).or_insert(meta);
+        }
+        let feeds = data.feeds_by_user.entry(user).or_default();
+        for f in archive.feeds {
+            if !feeds.iter().any(|x| x.item_id == f.item_id && x.url == f.url) {
+                feeds.push(f);
+                summary.feeds += 1;
+            }
+        }
+
+        for id in &added {
+            self.log_change(&mut data, username, id);
+        }
+        self.record(username, OperationKind::Import, changes).await;
+        drop(data);
+        self.mark_dirty();
+        summary
+    }
+
+    // --- Auth helpers ---
+    pub async fn find_user(&self, username: &str) -> Option<UserRecord> {
+        let data = self.cache.read().await;
+        data.users.iter().find(|u| u.username == username).cloned()
+    }
+
+    pub async fn set_extension_token(&self, username: &str, token_hash: Option<String>) {
+        let mut data = self.cache.write().await;
+        match token_hash {
+            Some(hash) => {
+                data.extension_tokens_by_user.insert(username.to_string(), hash);
+            }
+            None => {
+                data.extension_tokens_by_user.remove(username);
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+    }
+
+    pub async fn user_for_extension_token(&self, token_hash: &str) -> Option<String> {
+        let data = self.cache.read().await;
+        data.extension_tokens_by_user.iter().find(|(_, h)| h.as_str() == token_hash).map(|(u, _)| u.clone())
+    }
+
+    pub async fn set_web_token(&self, username: &str, token_hash: Option<String>) {
+        let mut data = self.cache.write().await;
+        match token_hash {
+            Some(hash) => {
+                data.web_tokens_by_user.insert(username.to_string(), hash);
+            }
+            None => {
+                data.web_tokens_by_user.remove(username);
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+    }
+
+    pub async fn user_for_web_token(&self, token_hash: &str) -> Option<String> {
+        let data = self.cache.read().await;
+        data.web_tokens_by_user.iter().find(|(_, h)| h.as_str() == token_hash).map(|(u, _)| u.clone())
+    }
+
+    pub async fn set_feed_token(&self, username: &str, token_hash: Option<String>) {
+        let mut data = self.cache.write().await;
+        match token_hash {
+            Some(hash) => {
+                data.feed_tokens_by_user.insert(username.to_string(), hash);
+            }
+            None => {
+                data.feed_tokens_by_user.remove(username);
+            }
+        }
+        drop(data);
+        self.mark_dirty();
+    }
+
+    pub async fn user_for_feed_token(&self, token_hash: &str) -> Option<String> {
+        let data = self.cache.read().await;
+        data.feed_tokens_by_user.iter().find(|(_, h)| h.as_str() == token_hash).map(|(u, _)| u.clone())
+    }
+
+    /// Stores a new hash; `changed` is false when the same password was only
+    /// rehashed, which other devices don't need to hear about.
+    pub async fn set_password_hash(&self, username: &str, hash: String, changed: bool) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        let user = data.users.iter_mut().find(|u| u.username == username).ok_or_else(|| "User not found".to_string())?;
+        user.password_hash = hash;
+        if changed {
+            user.last_modified = Some(now_ms());
+        }
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Replaces the user's recovery codes with `hashes`.
+    pub async fn set_recovery_codes(&self, username: &str, hashes: Vec<String>) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        let user = data.users.iter_mut().find(|u| u.username == username).ok_or_else(|| "User not found".to_string())?;
+        user.recovery_codes = hashes;
+        user.last_modified = Some(now_ms());
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Uses up the recovery code with hash `code_hash` to set a new password hash.
+    /// Returns how many codes are left.
+    pub async fn redeem_recovery_code(&self, username: &str, code_hash: &str, password_hash: String) -> Result<usize, String> {
+        let mut data = self.cache.write().await;
+        let user = data
+            .users
+            .iter_mut()
+            .find(|u| u.username == username)
+            .filter(|u| u.recovery_codes.iter().any(|c| c == code_hash))
+            .ok_or_else(|| "INVALID_RECOVERY_CODE".to_string())?;
+        user.recovery_codes.retain(|c| c != code_hash);
+        user.password_hash = password_hash;
+        user.last_modified = Some(now_ms());
+        let left = user.recovery_codes.len();
+        drop(data);
+        self.mark_dirty();
+        Ok(left)
+    }
+
+    /// Moves everything `source` has into `target` and deletes the `source`
+    /// account. Items that are the same work (same id, or matched like
+    /// `find_duplicates` does) are combined according to `strategy`; ids are
+    /// remapped in collections, relations and feeds accordingly.
+    pub async fn merge_users(&self, source: &str, target: &str, strategy: AccountMergeStrategy) -> Result<AccountMerge, String> {
+        if source == target {
+            return Err("Cannot merge an account into itself".to_string());
+        }
+        let mut data = self.cache.write().await;
+        if !data.users.iter().any(|u| u.username == source) {
+            return Err("User not found".to_string());
+        }
+        let mut summary = AccountMerge::default();
+        let target_key = target.to_string();
+        let items = data.items_by_user.remove(source).unwrap_or_default();
+        let trash = data.trash_by_user.remove(source).unwrap_or_default();
+        let mut history = data.history_by_user.remove(source).unwrap_or_default();
+        // source item id -> id it has in the target
+        let mut ids: HashMap<String, String> = HashMap::new();
+        let mut touched = Vec::new();
+
+        for item in items {
+            let list = data.items_by_user.entry(target_key.clone()).or_default();
+            let duplicate = list
+                .iter()
+                .position(|t| t.id == item.id || crate::dedupe::match_score(t, &item).is_some())
+                .filter(|_| strategy != AccountMergeStrategy::KeepBoth);
+            let Some(idx) = duplicate else {
+                let mut item = item;
+                let taken = list.iter().any(|t| t.id == item.id)
+                    || data.trash_by_user.get(target).is_some_and(|t| t.iter().any(|t| t.id == item.id));
+                if taken {
+                    let new = new_id();
+                    ids.insert(item.id.clone(), new.clone());
+                    if let Some(revisions) = history.remove(&item.id) {
+                        history.insert(new.clone(), revisions);
+                    }
+                    item.id = new;
+                }
+                touched.push(item.id.clone());
+                data.items_by_user.entry(target_key.clone()).or_default().push(item);
+                summary.added += 1;
+                continue;
+            };
+            let existing = list[idx].clone();
+            let edited = |i: &MediaItem| i.last_edited_at.or(i.updated_at).unwrap_or(0);
+            let source_wins = match strategy {
+                AccountMergeStrategy::PreferSource => true,
+                AccountMergeStrategy::Newest => edited(&item) > edited(&existing),
+                _ => false,
+            };
+            let mut merged = if source_wins { item.clone() } else { existing.clone() };
+            crate::dedupe::merge_into(&mut merged, if source_wins { &existing } else { &item });
+            merged.id = existing.id.clone();
+            merged.updated_at = Some(now_ms());
+            list[idx] = merged.clone();
+            Self::record_revision(&mut data, target, &existing, &merged);
+            // The source's edits become part of the surviving item's history
+            if let Some(mut revisions) = history.remove(&item.id) {
+                let dest = data.history_by_user.entry(target_key.clone()).or_default().entry(existing.id.clone()).or_default();
+                dest.append(&mut revisions);
+                dest.sort_by_key(|r| r.at);
+                for (n, r) in dest.iter_mut().enumerate() {
+                    r.revision = n as u32 + 1;
+                }
+                let excess = dest.len().saturating_sub(HISTORY_MAX_REVISIONS);
+                dest.drain(..excess);
+            }
+            ids.insert(item.id.clone(), existing.id.clone());
+            touched.push(existing.id);
+            summary.merged += 1;
+        }
+        let remap = |id: &str| ids.get(id).cloned().unwrap_or_else(|| id.to_string());
+
+        let target_history = data.history_by_user.entry(target_key.clone()).or_default();
+        for (id, revisions) in history {
+            target_history.entry(id).or_insert(revisions);
+        }
+        for item in trash {
+            let list = data.trash_by_user.entry(target_key.clone()).or_default();
+            if !list.iter().any(|t| t.id == item.id) && !ids.contains_key(&item.id) {
+                list.push(item);
+                summary.trash += 1;
+            }
+        }
+
+        let mut collection_ids: HashMap<String, String> = HashMap::new();
+        let source_collections = data.collections_by_user.remove(source).unwrap_or_default();
+        let collections = data.collections_by_user.entry(target_key.clone()).or_default();
+        for mut c in source_collections {
+            c.item_ids = c.item_ids.iter().map(|id| remap(id)).collect();
+            // Same name: one collection with the members of both
+            if let Some(same) = collections.iter_mut().find(|t| t.name.trim().eq_ignore_ascii_case(c.name.trim())) {
+                collection_ids.insert(c.id.clone(), same.id.clone());
+                for id in c.item_ids {
+                    if !same.item_ids.contains(&id) {
+                        same.item_ids.push(id);
+                    }
+                }
+                continue;
+            }
+            if collections.iter().any(|t| t.id == c.id) {
+                let new = new_id();
+                collection_ids.insert(c.id.clone(), new.clone());
+                c.id = new;
+            }
+            collections.push(c);
+            summary.collections += 1;
+        }
+        for c in collections.iter_mut() {
+            if let Some(parent) = c.parent_id.as_ref().and_then(|p| collection_ids.get(p)) {
+                c.parent_id = Some(parent.clone());
+            }
+        }
+
+        let source_relations = data.relations_by_user.remove(source).unwrap_or_default();
+        let relations = data.relations_by_user.entry(target_key.clone()).or_default();
+        for mut r in source_relations {
+            r.from_id = remap(&r.from_id);
+            r.to_id = remap(&r.to_id);
+            if r.from_id != r.to_id && !relations.iter().any(|x| x.connects(&r.from_id, &r.to_id)) {
+                relations.push(r);
+                summary.relations += 1;
+            }
+        }
+        let source_feeds = data.feeds_by_user.remove(source).unwrap_or_default();
+        let feeds = data.feeds_by_user.entry(target_key.clone()).or_default();
+        for mut f in source_feeds {
+            f.item_id = remap(&f.item_id);
+            if !feeds.iter().any(|x| x.item_id == f.item_id && x.url == f.url) {
+                feeds.push(f);
+            }
+        }
+        let source_fields = data.custom_fields_by_user.remove(source).unwrap_or_default();
+        let fields = data.custom_fields_by_user.entry(target_key.clone()).or_default();
+        for f in source_fields {
+            if !fields.iter().any(|x| x.key == f.key) {
+                fields.push(f);
+            }
+        }
+        let source_lists = data.smart_lists_by_user.remove(source).unwrap_or_default();
+        let lists = data.smart_lists_by_user.entry(target_key.clone()).or_default();
+        for l in source_lists {
+            if !lists.iter().any(|x| x.id == l.id) {
+                lists.push(l);
+            }
+        }
+        let source_people = data.people_by_user.remove(source).unwrap_or_default();
+        let people = data.people_by_user.entry(target_key.clone()).or_default();
+        for (id, meta) in source_people {
+            people.entry(id).or_insert(meta);
+        }
+        let mut source_activity = data.activity_by_user.remove(source).unwrap_or_default();
+        for e in source_activity.iter_mut() {
+            e.item_id = remap(&e.item_id);
+        }
+        let activity = data.activity_by_user.entry(target_key.clone()).or_default();
+        activity.append(&mut source_activity);
+        activity.sort_by_key(|e| e.at);
+        let excess = activity.len().saturating_sub(crate::activity::ACTIVITY_MAX);
+        activity.drain(..excess);
+        let mut source_time = data.time_sessions_by_user.remove(source).unwrap_or_default();
+        for t in source_time.iter_mut() {
+            t.item_id = remap(&t.item_id);
+        }
+        data.time_sessions_by_user.entry(target_key.clone()).or_default().append(&mut source_time);
+        if let Some(source_statuses) = data.statuses_by_user.remove(source) {
+            let mut statuses = Self::statuses_in(&data, target);
+            for st in source_statuses {
+                if crate::statuses::find(&statuses, &st.name).is_none() {
+                    statuses.push(st);
+                }
+            }
+            data.statuses_by_user.insert(target_key.clone(), statuses);
+        }
+        for (view, mut order) in data.view_orders_by_user.remove(source).unwrap_or_default() {
+            order.positions = order.positions.into_iter().map(|(id, n)| (remap(&id), n)).collect();
+            order.pinned = order.pinned.iter().map(|id| remap(id)).collect();
+            data.view_orders_by_user.entry(target_key.clone()).or_default().entry(view).or_insert(order);
+        }
+        for (id, until) in data.snoozes_by_user.remove(source).unwrap_or_default() {
+            data.snoozes_by_user.entry(target_key.clone()).or_default().entry(remap(&id)).or_insert(until);
+        }
+        if let Some(filter) = data.content_filters_by_user.remove(source) {
+            data.content_filters_by_user.entry(target_key.clone()).or_insert(filter);
+        }
+        if let Some(account) = data.lastfm_accounts_by_user.remove(source) {
+            data.lastfm_accounts_by_user.entry(target_key.clone()).or_insert(account);
+        }
+        if let Some(account) = data.plex_accounts_by_user.remove(source) {
+            data.plex_accounts_by_user.entry(target_key.clone()).or_insert(account);
+        }
+        for suggestion in data.creator_suggestions_by_user.remove(source).unwrap_or_default() {
+            let suggestions = data.creator_suggestions_by_user.entry(target_key.clone()).or_default();
+            if !suggestions.iter().any(|s| s.work.source == suggestion.work.source && s.work.source_id == suggestion.work.source_id) {
+                suggestions.push(suggestion);
+            }
+        }
+        let dismissed = data.dismissed_works_by_user.remove(source).unwrap_or_default();
+        data.dismissed_works_by_user.entry(target_key.clone()).or_default().extend(dismissed);
+        if let Some(account) = data.jellyfin_accounts_by_user.remove(source) {
+            data.jellyfin_accounts_by_user.entry(target_key.clone()).or_insert(account);
+        }
+        let mut source_goals = data.goals_by_user.remove(source).unwrap_or_default();
+        data.goals_by_user.entry(target_key.clone()).or_default().append(&mut source_goals);
+        let mut source_hooks = data.webhooks_by_user.remove(source).unwrap_or_default();
+        data.webhooks_by_user.entry(target_key).or_default().append(&mut source_hooks);
+
+        // Tokens, paired devices and sync state belonged to the source login
+        Self::purge_user(&mut data, source);
+        for id in &touched {
+            self.log_change(&mut data, target, id);
+        }
+        drop(data);
+        self.journals.lock().await.remove(source);
+        self.mark_dirty();
+        Ok(summary)
+    }
+
+    /// Hands all data stored under `from` to `to`, which must not have any yet.
+    pub async fn move_user_data(&self, from: &str, to: &str) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        let has_items = |m: &HashMap<String, Vec<MediaItem>>| m.get(to).is_some_and(|l| !l.is_empty());
+        if has_items(&data.items_by_user) || has_items(&data.trash_by_user) {
+            return Err(format!("{} already has items", to));
+        }
+        fn take<V>(map: &mut HashMap<String, V>, from: &str, to: &str) {
+            if let Some(v) = map.remove(from) {
+                map.insert(to.to_string(), v);
+            }
+        }
+        take(&mut data.items_by_user, from, to);
+        take(&mut data.trash_by_user, from, to);
+        take(&mut data.history_by_user, from, to);
+        take(&mut data.smart_lists_by_user, from, to);
+        take(&mut data.collections_by_user, from, to);
+        take(&mut data.custom_fields_by_user, from, to);
+        take(&mut data.people_by_user, from, to);
+        take(&mut data.relations_by_user, from, to);
+        take(&mut data.feeds_by_user, from, to);
+        take(&mut data.extension_tokens_by_user, from, to);
+        take(&mut data.web_tokens_by_user, from, to);
+        take(&mut data.feed_tokens_by_user, from, to);
+        take(&mut data.trusted_devices_by_user, from, to);
+        take(&mut data.peer_tokens_by_user, from, to);
+        take(&mut data.tombstones_by_user, from, to);
+        take(&mut data.change_log_by_user, from, to);
+        take(&mut data.sync_cursors_by_user, from, to);
+        take(&mut data.webhooks_by_user, from, to);
+        take(&mut data.sync_history_by_user, from, to);
+        take(&mut data.activity_by_user, from, to);
+        take(&mut data.time_sessions_by_user, from, to);
+        take(&mut data.goals_by_user, from, to);
+        take(&mut data.streaks_by_user, from, to);
+        take(&mut data.statuses_by_user, from, to);
+        take(&mut data.view_orders_by_user, from, to);
+        take(&mut data.snoozes_by_user, from, to);
+        take(&mut data.content_filters_by_user, from, to);
+        take(&mut data.lastfm_accounts_by_user, from, to);
+        take(&mut data.plex_accounts_by_user, from, to);
+        take(&mut data.jellyfin_accounts_by_user, from, to);
+        take(&mut data.creator_suggestions_by_user, from, to);
+        take(&mut data.dismissed_works_by_user, from, to);
+        for share in data.collection_shares.iter_mut().filter(|s| s.owner == from) {
+            share.owner = to.to_string();
+        }
+        let mut journals = self.journals.lock().await;
+        take(&mut journals, from, to);
+        drop(journals);
+        drop(data);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Removes every trace of `username` from `data`. Returns None if there was none.
+    fn purge_user(data: &mut CollectionData, username: &str) -> Option<AccountErasure> {
+        let had_user = data.users.iter().any(|u| u.username == username);
+        data.users.retain(|u| u.username != username);
+        let items = data.items_by_user.remove(username).unwrap_or_default();
+        let trash = data.trash_by_user.remove(username).unwrap_or_default();
+        let mut erased = AccountErasure {
+            items: items.len(),
+            trashed_items: trash.len(),
+            revisions: data.history_by_user.remove(username).map(|h| h.values().map(Vec::len).sum()).unwrap_or(0),
+            collections: data.collections_by_user.remove(username).map(|c| c.len()).unwrap_or(0),
+            smart_lists: data.smart_lists_by_user.remove(username).map(|l| l.len()).unwrap_or(0),
+            tombstones: data.tombstones_by_user.remove(username).map(|t| t.len()).unwrap_or(0),
+            trusted_devices: data.trusted_devices_by_user.remove(username).map(|d| d.len()).unwrap_or(0),
+            webhooks: data.webhooks_by_user.remove(username).map(|w| w.len()).unwrap_or(0),
+            ..Default::default()
+        };
+        data.custom_fields_by_user.remove(username);
+        data.people_by_user.remove(username);
+        data.relations_by_user.remove(username);
+        data.feeds_by_user.remove(username);
+        data.extension_tokens_by_user.remove(username);
+        data.web_tokens_by_user.remove(username);
+        data.feed_tokens_by_user.remove(username);
+        data.peer_tokens_by_user.remove(username);
+        data.change_log_by_user.remove(username);
+        data.sync_cursors_by_user.remove(username);
+        data.sync_history_by_user.remove(username);
+        data.activity_by_user.remove(username);
+        data.time_sessions_by_user.remove(username);
+        data.goals_by_user.remove(username);
+        data.streaks_by_user.remove(username);
+        data.statuses_by_user.remove(username);
+        data.view_orders_by_user.remove(username);
+        data.snoozes_by_user.remove(username);
+        data.content_filters_by_user.remove(username);
+        data.lastfm_accounts_by_user.remove(username);
+        data.plex_accounts_by_user.remove(username);
+        data.jellyfin_accounts_by_user.remove(username);
+        data.creator_suggestions_by_user.remove(username);
+        data.dismissed_works_by_user.remove(username);
+        data.auth_log.retain(|e| e.username.as_deref() != Some(username));
+        data.collection_shares.retain(|s| s.owner != username && s.grantee != username);
+        if !had_user && items.is_empty() && trash.is_empty() && erased.revisions == 0 {
+            return None;
+        }
+
+        // Covers are content-addressed and may be shared with other accounts
+        let still_used: std::collections::HashSet<&str> = data
+            .items_by_user
+            .values()
+            .chain(data.trash_by_user.values())
+            .flatten()
+            .filter_map(|i| i.poster_cache.as_ref().map(|c| c.file.as_str()))
+            .collect();
+        let mut images: Vec<String> = items
+            .iter()
+            .chain(trash.iter())
+            .filter_map(|i| i.poster_cache.as_ref().map(|c| c.file.clone()))
+            .filter(|f| !still_used.contains(f.as_str()))
+            .collect();
+        images.sort();
+        images.dedup();
+        erased.images = images;
+        Some(erased)
+    }
+
+    /// Deletes the account and all its data, then rewrites collection.json.bak and
+    /// the snapshot backups without it. Cloud backups already uploaded are not touched.
+    pub async fn delete_user(&self, username: &str) -> Result<AccountErasure, String> {
+        let mut data = self.cache.write().await;
+        let mut erased = Self::purge_user(&mut data, username).ok_or_else(|| "User not found".to_string())?;
+        drop(data);
+        self.journals.lock().await.remove(username);
+        self.mark_dirty();
+        // Flushing first, so the .bak it leaves behind is scrubbed below
+        self.flush().await?;
+
+        let _guard = self.write_lock.lock().await;
+        let key = self.current_key();
+        for file in self.local_copies()? {
+            let Ok(mut old) = Self::read_data(&file, key.as_ref()) else {
+                continue;
+            };
+            if Self::purge_user(&mut old, username).is_none() {
+                continue;
+            }
+            let content = serde_json::to_vec_pretty(&old).map_err(|e| e.to_string())?;
+            fs::write(&file, crate::at_rest::encode(content, key.as_ref())?).map_err(|e| e.to_string())?;
+            erased.backups += 1;
+        }
+        Ok(erased)
+    }
+
+    pub async fn add_user(&self, user: UserRecord) -> Result<(), String> {
+        let mut data = self.cache.write().await;
+        if data.users.iter().any(|u| u.username == user.username) {
+            return Err("User already exists".to_string());
+        }
+        data.users.push(user);
+        drop(data);
+        self.mark_dirty();
+        Ok(())
     }
 }
@@ -0,0 +1,135 @@
+// Release dates. `release_date` stays whatever the provider or user wrote
+// ("2010", "Oct 2023", "2024-04-07", "2024年4月"); alongside it each item keeps
+// that date parsed to ISO form with its precision, so items sort and filter by
+// date whatever the source. The parsed form is refreshed whenever an item is
+// saved, and filled in for existing data at startup.
+
+use std::cmp::Ordering;
+use serde::{Deserialize, Serialize};
+use crate::models::MediaItem;
+
+const MONTHS: [&str; 12] = ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"];
+/// Old books have early dates; anything outside this is not a year.
+const YEARS: std::ops::RangeInclusive<u32> = 1000..=2999;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum DatePrecision {
+    Year,
+    Month,
+    Day,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialDate {
+    /// "2023", "2023-10" or "2023-10-07"; these sort correctly as strings.
+    pub iso: String,
+    pub precision: DatePrecision,
+}
+
+/// Whether the day exists, e.g. not February 30th.
+pub(crate) fn valid_day(y: u32, m: u32, d: u32) -> bool {
+    (1..=31).contains(&d) && crate::updates::civil_from_days(crate::smart::days_from_civil(y as i64, m, d)) == (y as i64, m, d)
+}
+
+fn date(y: u32, m: Option<u32>, d: Option<u32>) -> PartialDate {
+    match (m.filter(|m| (1..=12).contains(m)), d) {
+        (Some(m), Some(d)) if valid_day(y, m, d) => PartialDate { iso: format!("{:04}-{:02}-{:02}", y, m, d), precision: DatePrecision::Day },
+        (Some(m), _) => PartialDate { iso: format!("{:04}-{:02}", y, m), precision: DatePrecision::Month },
+        _ => PartialDate { iso: format!("{:04}", y), precision: DatePrecision::Year },
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    /// Value and digit count; "7th" and "07T10:00" read as 7.
+    Number(u32, usize),
+    Month(u32),
+    Word,
+}
+
+fn tokens(raw: &str) -> Vec<Token> {
+    // 年/月/日 end the parts of a Chinese or Japanese date
+    raw.split(|c: char| !c.is_alphanumeric() || matches!(c, '年' | '月' | '日' | '号'))
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            let digits: String = t.chars().take_while(|c| c.is_ascii_digit()).collect();
+            if !digits.is_empty() {
+                return digits.parse().map(|n| Token::Number(n, digits.len())).unwrap_or(Token::Word);
+            }
+            let lower = t.to_lowercase();
+            // "Oct", "Sept" or "October", but not "Marvel"
+            match MONTHS.iter().position(|m| lower.len() >= 3 && m.starts_with(lower.as_str())) {
+                Some(i) => Token::Month(i as u32 + 1),
+                None => Token::Word,
+            }
+        })
+        .collect()
+}
+
+/// Parses a freeform release date. Year-first dates ("2024-04-07", "2024/4",
+/// "2024年4月7日", ISO timestamps), month names ("Oct 2023", "7 October 2023",
+/// "Oct 7th, 2023") and slash dates with the year last ("04/07/2024", month
+/// first unless that can't be) are understood; anything else falls back to the
+/// first year in the text at year precision.
+pub fn parse(raw: &str) -> Option<PartialDate> {
+    let tokens = tokens(raw);
+    let number = |i: usize| match tokens.get(i) {
+        Some(Token::Number(n, _)) => Some(*n),
+        _ => None,
+    };
+    let year_at = tokens.iter().position(|t| matches!(t, Token::Number(n, 4) if YEARS.contains(n)))?;
+    let year = number(year_at)?;
+    if let Some(month_at) = tokens.iter().position(|t| matches!(t, Token::Month(_))) {
+        let Token::Month(month) = tokens[month_at] else {
+            return None;
+        };
+        // The day sits next to the month name: "7 Oct 2023", "Oct 7, 2023"
+        let day = [month_at + 1, month_at.wrapping_sub(1)]
+            .into_iter()
+            .filter(|i| *i != year_at)
+            .find_map(|i| match tokens.get(i) {
+                Some(Token::Number(d, 1..=2)) => Some(*d),
+                _ => None,
+            });
+        return Some(date(year, Some(month), day));
+    }
+    if year_at == 0 {
+        return Some(match (number(1), number(2)) {
+            (Some(m), d) if (1..=12).contains(&m) => date(year, Some(m), d),
+            _ => date(year, None, None),
+        });
+    }
+    match (year_at, number(0), number(1)) {
+        (2, Some(a), Some(b)) if a > 12 => Some(date(year, Some(b), Some(a))),
+        (2, Some(a), Some(b)) => Some(date(year, Some(a), Some(b))),
+        _ => Some(date(year, None, None)),
+    }
+}
+
+/// Brings `normalized_release_date` in line with `release_date`; returns whether it changed.
+pub fn normalize(item: &mut MediaItem) -> bool {
+    let parsed = parse(&item.release_date);
+    if item.normalized_release_date == parsed {
+        return false;
+    }
+    item.normalized_release_date = parsed;
+    true
+}
+
+/// Fills in parsed dates for items saved before they existed; returns whether any changed.
+pub fn migrate(items: &mut [MediaItem]) -> bool {
+    items.iter_mut().fold(false, |changed, item| normalize(item) | changed)
+}
+
+/// Oldest first, items without a date last.
+pub fn compare(a: &MediaItem, b: &MediaItem) -> Ordering {
+    let key = |i: &MediaItem| i.normalized_release_date.as_ref().map(|d| d.iso.clone()).or_else(|| parse(&i.release_date).map(|d| d.iso));
+    match (key(a), key(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
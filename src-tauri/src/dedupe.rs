@@ -0,0 +1,329 @@
+use serde::Serialize;
+use crate::models::MediaItem;
+
+const TITLE_SIMILARITY_THRESHOLD: f64 = 0.85;
+const YEAR_TOLERANCE: i32 = 1;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    pub item_ids: Vec<String>,
+    pub titles: Vec<String>,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportAction {
+    Add,
+    /// Shares a provider id with an item already there, or came twice in the import.
+    Skip,
+    /// Title (or a title in another language) and year match an existing item;
+    /// left for the user to confirm.
+    Review,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportDecision {
+    pub item: MediaItem,
+    pub action: ImportAction,
+    /// The existing item it matched, for Skip and Review.
+    pub existing_item_id: Option<String>,
+    pub existing_title: Option<String>,
+    /// 1 for a provider id match, else the title similarity.
+    pub score: f64,
+}
+
+/// Incoming items sorted into what to add, what is certainly there already and
+/// what probably is.
+#[derive(Debug, Clone, Default)]
+pub struct ImportPlan {
+    pub add: Vec<MediaItem>,
+    pub skipped: usize,
+    pub probable: Vec<ImportDecision>,
+}
+
+/// Lowercases and drops everything but letters/digits, so "Spirited Away!" and
+/// "spirited-away" compare equal. Full-width letters and digits ("ＡＢＣ１") count as
+/// their ASCII forms; CJK characters are kept as-is.
+pub fn normalize_title(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| match c {
+            '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+            _ => c,
+        })
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// First plausible 4-digit year in a freeform release date ("2010", "Oct 2023", "2024-04-07").
+pub fn extract_year(date: &str) -> Option<i32> {
+    let bytes = date.as_bytes();
+    for i in 0..bytes.len().saturating_sub(3) {
+        let window = &bytes[i..i + 4];
+        let boundary_before = i == 0 || !bytes[i - 1].is_ascii_digit();
+        let boundary_after = i + 4 == bytes.len() || !bytes[i + 4].is_ascii_digit();
+        if boundary_before && boundary_after && window.iter().all(|b| b.is_ascii_digit()) {
+            let y: i32 = date[i..i + 4].parse().ok()?;
+            if (1800..=2200).contains(&y) {
+                return Some(y);
+            }
+        }
+    }
+    None
+}
+
+fn bigrams(s: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = s.chars().collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+/// Sørensen–Dice coefficient over character bigrams of the normalized titles.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let (ba, mut bb) = (bigrams(&a), bigrams(&b));
+    if ba.is_empty() || bb.is_empty() {
+        return 0.0;
+    }
+    let total = (ba.len() + bb.len()) as f64;
+    let mut matches = 0usize;
+    for g in ba {
+        if let Some(pos) = bb.iter().position(|x| *x == g) {
+            bb.swap_remove(pos);
+            matches += 1;
+        }
+    }
+    2.0 * matches as f64 / total
+}
+
+fn years_compatible(a: &MediaItem, b: &MediaItem) -> bool {
+    match (extract_year(&a.release_date), extract_year(&b.release_date)) {
+        (Some(x), Some(y)) => (x - y).abs() <= YEAR_TOLERANCE,
+        _ => true,
+    }
+}
+
+/// Returns the similarity score if the two items look like the same work.
+pub fn match_score(a: &MediaItem, b: &MediaItem) -> Option<f64> {
+    if a.media_type != b.media_type || !years_compatible(a, b) {
+        return None;
+    }
+    // Best pair over the saved titles and the titles in other languages
+    let titles = |i: &'_ MediaItem| std::iter::once(i.title.clone()).chain(i.alt_titles.values().cloned()).collect::<Vec<_>>();
+    let (ta, tb) = (titles(a), titles(b));
+    let score = ta.iter().flat_map(|x| tb.iter().map(move |y| title_similarity(x, y))).fold(0.0, f64::max);
+    (score >= TITLE_SIMILARITY_THRESHOLD).then_some(score)
+}
+
+fn shares_provider_id(a: &MediaItem, b: &MediaItem) -> bool {
+    match (&a.provider_ids, &b.provider_ids) {
+        (Some(x), Some(y)) => x.iter().any(|(k, v)| y.get(k) == Some(v)),
+        _ => false,
+    }
+}
+
+/// Decides for each incoming item whether to add it: a provider id shared with an
+/// existing item (or an earlier incoming one) skips it, while a title and year
+/// match, across each side's titles in other languages, only marks it for review.
+pub fn classify_import(existing: &[MediaItem], incoming: Vec<MediaItem>) -> Vec<ImportDecision> {
+    let mut decisions: Vec<ImportDecision> = Vec::new();
+    for item in incoming {
+        let decision = |action, other: Option<&MediaItem>, score| ImportDecision {
+            existing_item_id: other.map(|o| o.id.clone()),
+            existing_title: other.map(|o| o.title.clone()),
+            item: item.clone(),
+            action,
+            score,
+        };
+        let added = || decisions.iter().filter(|d| d.action == ImportAction::Add).map(|d| &d.item);
+        let next = if let Some(other) = existing.iter().find(|e| e.id == item.id || shares_provider_id(e, &item)) {
+            decision(ImportAction::Skip, Some(other), 1.0)
+        } else if added().any(|a| a.id == item.id || same_work(a, &item)) {
+            decision(ImportAction::Skip, None, 1.0)
+        } else if let Some((score, other)) = existing.iter().filter_map(|e| Some((match_score(e, &item)?, e))).max_by(|a, b| a.0.total_cmp(&b.0)) {
+            decision(ImportAction::Review, Some(other), score)
+        } else {
+            decision(ImportAction::Add, None, 0.0)
+        };
+        decisions.push(next);
+    }
+    decisions
+}
+
+impl ImportPlan {
+    pub fn new(existing: &[MediaItem], incoming: Vec<MediaItem>) -> Self {
+        let mut plan = ImportPlan::default();
+        for d in classify_import(existing, incoming) {
+            match d.action {
+                ImportAction::Add => plan.add.push(d.item),
+                ImportAction::Skip => plan.skipped += 1,
+                ImportAction::Review => plan.probable.push(d),
+            }
+        }
+        plan
+    }
+}
+
+/// Whether two items are the same work: they share a provider id, or their
+/// titles and years match as in `match_score`.
+pub fn same_work(a: &MediaItem, b: &MediaItem) -> bool {
+    shares_provider_id(a, b) || match_score(a, b).is_some()
+}
+
+pub fn find_duplicates(items: &[MediaItem]) -> Vec<DuplicateGroup> {
+    // Union-find over all matching pairs
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut best = vec![0.0f64; items.len()];
+    for i in 0..items.len() {
+        for j in (i + 1)..items.len() {
+            if let Some(score) = match_score(&items[i], &items[j]) {
+                let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                if ri != rj {
+                    parent[rj] = ri;
+                }
+                best[i] = best[i].max(score);
+                best[j] = best[j].max(score);
+            }
+        }
+    }
+
+    let mut groups: Vec<(usize, DuplicateGroup)> = Vec::new();
+    for i in 0..items.len() {
+        let r = root(&mut parent, i);
+        match groups.iter_mut().find(|(gr, _)| *gr == r) {
+            Some((_, g)) => {
+                g.item_ids.push(items[i].id.clone());
+                g.titles.push(items[i].title.clone());
+                g.score = g.score.min(best[i]);
+            }
+            None => groups.push((r, DuplicateGroup {
+                item_ids: vec![items[i].id.clone()],
+                titles: vec![items[i].title.clone()],
+                score: best[i],
+            })),
+        }
+    }
+    groups.into_iter().map(|(_, g)| g).filter(|g| g.item_ids.len() > 1).collect()
+}
+
+fn union_into(target: &mut Option<Vec<String>>, other: &Option<Vec<String>>) {
+    if let Some(src) = other {
+        let dst = target.get_or_insert_with(Vec::new);
+        for v in src {
+            if !dst.contains(v) {
+                dst.push(v.clone());
+            }
+        }
+    }
+}
+
+fn fill_if_empty(target: &mut Option<String>, other: &Option<String>) {
+    if target.as_deref().map(|s| s.trim().is_empty()).unwrap_or(true) {
+        if let Some(v) = other {
+            if !v.trim().is_empty() {
+                *target = Some(v.clone());
+            }
+        }
+    }
+}
+
+/// Folds `other` into `keep`. The surviving record's own values win; reviews are
+/// concatenated so no hand-written text is lost.
+pub fn merge_into(keep: &mut MediaItem, other: &MediaItem) {
+    match (&keep.user_review, &other.user_review) {
+        (Some(a), Some(b)) if !b.trim().is_empty() && a.trim() != b.trim() => {
+            keep.user_review = Some(if a.trim().is_empty() { b.clone() } else { format!("{}\n\n---\n\n{}", a, b) });
+        }
+        (None, Some(b)) => keep.user_review = Some(b.clone()),
+        _ => {}
+    }
+    fill_if_empty(&mut keep.user_progress, &other.user_progress);
+    fill_if_empty(&mut keep.custom_poster_url, &other.custom_poster_url);
+    fill_if_empty(&mut keep.poster_url, &other.poster_url);
+    fill_if_empty(&mut keep.rating, &other.rating);
+    fill_if_empty(&mut keep.latest_update_info, &other.latest_update_info);
+    fill_if_empty(&mut keep.trailer_url, &other.trailer_url);
+    for (lang, title) in &other.alt_titles {
+        keep.alt_titles.entry(lang.clone()).or_insert_with(|| title.clone());
+    }
+    if let Some(other_reading) = &other.reading {
+        let reading = keep.reading.get_or_insert_with(Default::default);
+        for r in &other_reading.read {
+            if !reading.read.iter().any(|k| k.chapter == r.chapter) {
+                reading.mark(std::slice::from_ref(&r.chapter), r.volume, true, r.read_at);
+            }
+        }
+    }
+    if keep.narrators.is_empty() {
+        keep.narrators = other.narrators.clone();
+    }
+    if keep.duration_minutes.is_none() {
+        keep.duration_minutes = other.duration_minutes;
+    }
+    if keep.min_players.is_none() && keep.max_players.is_none() {
+        keep.min_players = other.min_players;
+        keep.max_players = other.max_players;
+    }
+    if keep.min_playtime.is_none() && keep.max_playtime.is_none() {
+        keep.min_playtime = other.min_playtime;
+        keep.max_playtime = other.max_playtime;
+    }
+    if keep.play_count.is_none() {
+        keep.play_count = other.play_count;
+    }
+    if keep.play_times.is_none() {
+        keep.play_times = other.play_times.clone();
+    }
+    if keep.content_rating.is_none() {
+        keep.content_rating = other.content_rating.clone();
+    }
+    if keep.user_rating.is_none() {
+        keep.user_rating = other.user_rating;
+    }
+    if keep.category.is_none() {
+        keep.category = other.category.clone();
+    }
+    if other.notification_enabled == Some(true) {
+        keep.notification_enabled = Some(true);
+    }
+    union_into(&mut keep.tags, &other.tags);
+    union_into(&mut keep.cast, &other.cast);
+    for c in &other.completions {
+        if !keep.completions.contains(c) {
+            keep.completions.push(c.clone());
+        }
+    }
+    keep.completions.sort_by_key(|c| c.at);
+    for n in &other.notes {
+        if !keep.notes.iter().any(|k| k.id == n.id) {
+            keep.notes.push(n.clone());
+        }
+    }
+    for q in &other.quotes {
+        if !keep.quotes.iter().any(|k| k.id == q.id || k.text == q.text) {
+            keep.quotes.push(q.clone());
+        }
+    }
+    if let Some(ids) = &other.provider_ids {
+        let dst = keep.provider_ids.get_or_insert_with(Default::default);
+        for (k, v) in ids {
+            dst.entry(k.clone()).or_insert_with(|| v.clone());
+        }
+    }
+}
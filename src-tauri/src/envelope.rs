@@ -0,0 +1,128 @@
+// Application-layer encryption for LAN sync. Every device has a static X25519
+// key whose public half peers pin when pairing. A request is sealed to the
+// peer's public key with a fresh ephemeral key (X25519 -> HKDF-SHA256 ->
+// ChaCha20-Poly1305) and the reply is sealed with the same shared secret, so
+// collection data and sync tokens never cross the network in the clear and
+// only the pinned device can read them.
+
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use rand_core::{OsRng, RngCore};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::hkdf::{Salt, HKDF_SHA256};
+use serde::{Deserialize, Serialize};
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+const REQUEST_INFO: &[u8] = b"mediatracker-sync request";
+const RESPONSE_INFO: &[u8] = b"mediatracker-sync response";
+
+/// This device's long-term key.
+#[derive(Clone)]
+pub struct DeviceKey(StaticSecret);
+
+impl DeviceKey {
+    pub fn generate() -> Self {
+        Self(StaticSecret::random_from_rng(OsRng))
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self, String> {
+        Ok(Self(StaticSecret::from(decode_32(s)?)))
+    }
+
+    pub fn to_base64(&self) -> String {
+        B64.encode(self.0.to_bytes())
+    }
+
+    pub fn public_base64(&self) -> String {
+        B64.encode(PublicKey::from(&self.0).as_bytes())
+    }
+}
+
+/// Stable id for the device holding `public_key`: the first 16 bytes of its
+/// SHA-256, in hex. Unlike the hostname a device reports, it can't be claimed
+/// by another device, so pins, tokens and sync cursors are keyed by it.
+pub fn fingerprint(public_key: &str) -> String {
+    let bytes = decode_32(public_key).map(Vec::from).unwrap_or_else(|_| public_key.as_bytes().to_vec());
+    ring::digest::digest(&ring::digest::SHA256, &bytes).as_ref()[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// An encrypted message. `epk` (the sender's ephemeral public key) is only set on requests.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Sealed {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub epk: String,
+    pub nonce: String,
+    pub data: String,
+}
+
+/// Shared secret of one request, used to open or seal its reply.
+pub struct Exchange {
+    shared: [u8; 32],
+    salt: Vec<u8>,
+}
+
+impl Exchange {
+    fn key(&self, info: &[u8]) -> Result<LessSafeKey, String> {
+        let prk = Salt::new(HKDF_SHA256, &self.salt).extract(&self.shared);
+        let info = [info];
+        let okm = prk.expand(&info, &CHACHA20_POLY1305).map_err(|_| "Key derivation failed".to_string())?;
+        Ok(LessSafeKey::new(UnboundKey::from(okm)))
+    }
+
+    pub fn seal_reply(&self, plaintext: &[u8]) -> Result<Sealed, String> {
+        seal_with(&self.key(RESPONSE_INFO)?, String::new(), plaintext)
+    }
+
+    pub fn open_reply(&self, sealed: &Sealed) -> Result<Vec<u8>, String> {
+        open_with(&self.key(RESPONSE_INFO)?, sealed)
+    }
+}
+
+/// Seals `plaintext` so that only the holder of `peer_public` can read it.
+pub fn seal_request(peer_public: &str, plaintext: &[u8]) -> Result<(Sealed, Exchange), String> {
+    let peer = PublicKey::from(decode_32(peer_public)?);
+    let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+    let epk = PublicKey::from(&ephemeral);
+    let shared = ephemeral.diffie_hellman(&peer);
+    let exchange = Exchange { shared: shared.to_bytes(), salt: [epk.as_bytes().as_slice(), peer.as_bytes()].concat() };
+    let sealed = seal_with(&exchange.key(REQUEST_INFO)?, B64.encode(epk.as_bytes()), plaintext)?;
+    Ok((sealed, exchange))
+}
+
+pub fn open_request(key: &DeviceKey, sealed: &Sealed) -> Result<(Vec<u8>, Exchange), String> {
+    let epk = PublicKey::from(decode_32(&sealed.epk)?);
+    let ours = PublicKey::from(&key.0);
+    let shared = key.0.diffie_hellman(&epk);
+    let exchange = Exchange { shared: shared.to_bytes(), salt: [epk.as_bytes().as_slice(), ours.as_bytes()].concat() };
+    let plaintext = open_with(&exchange.key(REQUEST_INFO)?, sealed)?;
+    Ok((plaintext, exchange))
+}
+
+fn seal_with(key: &LessSafeKey, epk: String, plaintext: &[u8]) -> Result<Sealed, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let mut data = plaintext.to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| "Encryption failed".to_string())?;
+    Ok(Sealed { epk, nonce: B64.encode(nonce), data: B64.encode(data) })
+}
+
+fn open_with(key: &LessSafeKey, sealed: &Sealed) -> Result<Vec<u8>, String> {
+    let nonce: [u8; NONCE_LEN] = B64
+        .decode(&sealed.nonce)
+        .ok()
+        .and_then(|n| n.try_into().ok())
+        .ok_or_else(|| "Invalid nonce".to_string())?;
+    let mut data = B64.decode(&sealed.data).map_err(|e| e.to_string())?;
+    let plaintext = key
+        .open_in_place(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| "Could not decrypt sync message".to_string())?;
+    Ok(plaintext.to_vec())
+}
+
+fn decode_32(s: &str) -> Result<[u8; 32], String> {
+    B64.decode(s.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| "Invalid key".to_string())
+}
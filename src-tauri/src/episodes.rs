@@ -0,0 +1,154 @@
+// "Watched the next episode": reads the free-text `user_progress` as a season
+// and episode ("S2E05", "Ep 7", "7/12", "12"), moves it on by one, rolls over
+// to the next season using the provider's episode counts, and reports when the
+// last episode of a finished show has been reached.
+
+use reqwest::Client;
+use crate::models::MediaItem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub season: Option<u32>,
+    pub episode: u32,
+    /// Episode count written in the progress itself, as in "7/12".
+    pub of: Option<u32>,
+}
+
+/// Episode counts from the item's provider.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EpisodeCounts {
+    /// (season number, episodes), specials (season 0) excluded.
+    pub seasons: Vec<(u32, u32)>,
+    /// For providers without seasons.
+    pub total: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advance {
+    pub progress: String,
+    /// The new progress is the last episode.
+    pub finished: bool,
+}
+
+fn digits_at(s: &str, start: usize) -> Option<(u32, usize)> {
+    let len = s[start..].bytes().take_while(u8::is_ascii_digit).count();
+    Some((s[start..start + len].parse().ok()?, start + len))
+}
+
+pub fn parse(progress: &str) -> Option<Progress> {
+    let s = progress.trim();
+    let lower = s.to_ascii_lowercase();
+    // S1E5 / S01E05 / s1 e5
+    let season_at = lower.char_indices().find(|&(i, c)| c == 's' && lower[i + 1..].starts_with(|c: char| c.is_ascii_digit()));
+    if let Some((i, _)) = season_at {
+        let (season, end) = digits_at(&lower, i + 1)?;
+        let rest = lower[end..].trim_start();
+        if let Some(rest) = rest.strip_prefix('e') {
+            let start = lower.len() - rest.len();
+            let (episode, _) = digits_at(&lower, start)?;
+            return Some(Progress { season: Some(season), episode, of: None });
+        }
+    }
+    // Otherwise the first number, with an optional "/total" after it
+    let start = lower.find(|c: char| c.is_ascii_digit())?;
+    let (episode, end) = digits_at(&lower, start)?;
+    let of = lower[end..]
+        .trim_start()
+        .strip_prefix('/')
+        .map(str::trim_start)
+        .and_then(|rest| digits_at(rest, 0))
+        .map(|(n, _)| n);
+    Some(Progress { season: None, episode, of })
+}
+
+/// Writes `p` back in the shape `original` had.
+fn format(original: &str, p: Progress) -> String {
+    if let Some(season) = p.season {
+        let lower = original.to_ascii_lowercase();
+        let padded = lower.contains("s0") || lower.contains("e0");
+        return if padded { format!("S{:02}E{:02}", season, p.episode) } else { format!("S{}E{}", season, p.episode) };
+    }
+    let Some(start) = original.find(|c: char| c.is_ascii_digit()) else {
+        return p.episode.to_string();
+    };
+    let len = original[start..].bytes().take_while(u8::is_ascii_digit).count();
+    format!("{}{}{}", &original[..start], p.episode, &original[start + len..])
+}
+
+/// Progress after watching one more episode. `ongoing` shows never count as finished.
+pub fn advance(progress: Option<&str>, counts: &EpisodeCounts, ongoing: bool) -> Advance {
+    let original = progress.map(str::trim).filter(|p| !p.is_empty());
+    let current = original.and_then(parse);
+    let mut next = match current {
+        Some(p) => Progress { episode: p.episode + 1, ..p },
+        // Nothing watched yet: the first episode, in seasons if the provider has them
+        None => Progress { season: counts.seasons.first().map(|(s, _)| *s), episode: 1, of: None },
+    };
+    let last_season = counts.seasons.iter().map(|(s, _)| *s).max();
+    if let Some(season) = next.season {
+        let in_season = counts.seasons.iter().find(|(s, _)| *s == season).map(|(_, n)| *n);
+        if let Some(n) = in_season.filter(|n| next.episode > *n) {
+            // Past the end of this season: first episode of the next one, if there is one
+            if let Some((s, _)) = counts.seasons.iter().filter(|(s, n)| *s > season && *n > 0).min_by_key(|(s, _)| *s) {
+                next = Progress { season: Some(*s), episode: 1, of: None };
+            } else {
+                next.episode = n;
+            }
+        }
+    } else if let Some(total) = next.of.or(counts.total).filter(|t| next.episode > *t) {
+        next.episode = total;
+    }
+    let finished = !ongoing
+        && match next.season {
+            Some(season) => {
+                Some(season) == last_season && counts.seasons.iter().any(|(s, n)| *s == season && next.episode >= *n)
+            }
+            None => next.of.or(counts.total).map(|t| next.episode >= t).unwrap_or(false),
+        };
+    let progress = match original {
+        Some(o) if current.is_some() => format(o, next),
+        _ => match next.season {
+            Some(s) => format!("S{}E{}", s, next.episode),
+            None => format!("Ep {}", next.episode),
+        },
+    };
+    Advance { progress, finished }
+}
+
+async fn tmdb_counts(client: &Client, tv_id: &str, api_key: &str) -> Result<EpisodeCounts, String> {
+    let url = format!(
+        "https://api.themoviedb.org/3/tv/{}?api_key={}",
+        urlencoding::encode(tv_id),
+        urlencoding::encode(api_key)
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    let seasons = v["seasons"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| Some((s["season_number"].as_u64()? as u32, s["episode_count"].as_u64()? as u32)))
+        .filter(|(s, _)| *s > 0)
+        .collect();
+    Ok(EpisodeCounts { seasons, total: v["number_of_episodes"].as_u64().map(|n| n as u32) })
+}
+
+async fn bangumi_counts(client: &Client, subject_id: &str) -> Result<EpisodeCounts, String> {
+    let url = format!("https://api.bgm.tv/v0/subjects/{}", urlencoding::encode(subject_id));
+    let v = crate::fetch_json(client, &url).await?;
+    let total = v["total_episodes"].as_u64().filter(|n| *n > 0).or(v["eps"].as_u64()).filter(|n| *n > 0);
+    Ok(EpisodeCounts { seasons: Vec::new(), total: total.map(|n| n as u32) })
+}
+
+/// Episode counts from TMDB (with a key) or Bangumi; empty when neither knows the item.
+pub async fn counts(client: &Client, item: &MediaItem, tmdb_api_key: Option<&str>) -> Result<EpisodeCounts, String> {
+    let Some(ids) = item.provider_ids.as_ref() else {
+        return Ok(EpisodeCounts::default());
+    };
+    if let (Some(id), Some(key)) = (ids.get("tmdbTv"), tmdb_api_key) {
+        return tmdb_counts(client, id, key).await;
+    }
+    if let Some(id) = ids.get("bangumi") {
+        return bangumi_counts(client, id).await;
+    }
+    Ok(EpisodeCounts::default())
+}
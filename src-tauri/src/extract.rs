@@ -0,0 +1,139 @@
+// Item drafts from free text. `extract_item_from_text` asks the configured AI
+// model to describe the work a pasted message is about as a JSON object
+// following SCHEMA; the reply is validated here, not trusted, and a malformed
+// reply is sent back to the model with the problem so it can correct itself.
+
+use serde_json::{Map, Value};
+use crate::database::{new_id, now_ms};
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+
+/// Attempts before giving up on a model that keeps replying badly.
+pub const MAX_ATTEMPTS: usize = 3;
+/// Longer pastes are cut; a recommendation doesn't need more.
+const MAX_TEXT_CHARS: usize = 4000;
+const MAX_LIST: usize = 20;
+
+const TYPES: &[&str] = &["Book", "Movie", "TV Series", "Comic", "Short Drama", "Music", "Fanfiction", "Podcast", "Audiobook", "Board Game", "Other"];
+
+const SCHEMA: &str = r#"{
+  "type": "object",
+  "additionalProperties": false,
+  "required": ["title", "type"],
+  "properties": {
+    "title": { "type": "string", "description": "Title of the work, empty if the text names none" },
+    "type": { "enum": ["Book", "Movie", "TV Series", "Comic", "Short Drama", "Music", "Fanfiction", "Podcast", "Audiobook", "Board Game", "Other"] },
+    "directorOrAuthor": { "type": ["string", "null"], "description": "Director, author, artist or studio" },
+    "releaseDate": { "type": ["string", "null"], "pattern": "^\\d{4}(-\\d{2}(-\\d{2})?)?$" },
+    "description": { "type": ["string", "null"], "description": "One or two sentences, in the language of the text" },
+    "cast": { "type": ["array", "null"], "items": { "type": "string" } },
+    "tags": { "type": ["array", "null"], "items": { "type": "string" }, "description": "Genres" }
+  }
+}"#;
+
+/// The opening messages for `text`.
+pub fn messages(text: &str) -> Vec<Value> {
+    let text: String = text.trim().chars().take(MAX_TEXT_CHARS).collect();
+    let system = format!(
+        "You extract the one book, film, show, album or other work that a message recommends or talks about. \
+         Reply with a single JSON object that validates against this JSON schema, and nothing else:\n{}\n\
+         Only state facts you are sure of; use null for anything else. If the message names no work, reply {{\"title\": \"\", \"type\": \"Other\"}}.",
+        SCHEMA
+    );
+    vec![serde_json::json!({ "role": "system", "content": system }), serde_json::json!({ "role": "user", "content": text })]
+}
+
+/// Follow-up asking the model to fix `reply`, which failed validation with `error`.
+pub fn retry_messages(reply: &str, error: &str) -> Vec<Value> {
+    vec![
+        serde_json::json!({ "role": "assistant", "content": reply }),
+        serde_json::json!({ "role": "user", "content": format!("That reply is invalid: {}. Reply again with only the corrected JSON object.", error) }),
+    ]
+}
+
+/// The JSON object in a reply, which models like to wrap in a code fence or a sentence.
+fn json_object(reply: &str) -> Result<Map<String, Value>, String> {
+    let (start, end) = match (reply.find('{'), reply.rfind('}')) {
+        (Some(start), Some(end)) if start < end => (start, end),
+        _ => return Err("no JSON object found".to_string()),
+    };
+    match serde_json::from_str::<Value>(&reply[start..=end]) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err("the reply is not a JSON object".to_string()),
+        Err(e) => Err(format!("invalid JSON ({})", e)),
+    }
+}
+
+fn optional_string(map: &Map<String, Value>, key: &str) -> Result<String, String> {
+    match map.get(key) {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(Value::String(s)) => Ok(s.trim().to_string()),
+        Some(_) => Err(format!("\"{}\" must be a string or null", key)),
+    }
+}
+
+fn string_list(map: &Map<String, Value>, key: &str) -> Result<Option<Vec<String>>, String> {
+    let values = match map.get(key) {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::Array(values)) => values,
+        Some(_) => return Err(format!("\"{}\" must be an array of strings or null", key)),
+    };
+    let mut out = Vec::new();
+    for v in values {
+        let s = v.as_str().ok_or_else(|| format!("\"{}\" must contain only strings", key))?.trim();
+        if !s.is_empty() && !out.iter().any(|o: &String| o.eq_ignore_ascii_case(s)) {
+            out.push(s.to_string());
+        }
+    }
+    out.truncate(MAX_LIST);
+    Ok((!out.is_empty()).then_some(out))
+}
+
+/// "2019", "2019-05" or "2019-05-24" with a plausible month and day.
+fn valid_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    let number = |s: &str, len: usize| if s.len() == len && s.chars().all(|c| c.is_ascii_digit()) { s.parse::<u32>().ok() } else { None };
+    match parts[..] {
+        [y] => number(y, 4).is_some(),
+        [y, m] => number(y, 4).is_some() && number(m, 2).is_some_and(|m| (1..=12).contains(&m)),
+        [y, m, d] => number(y, 4).is_some() && number(m, 2).is_some_and(|m| (1..=12).contains(&m)) && number(d, 2).is_some_and(|d| (1..=31).contains(&d)),
+        _ => false,
+    }
+}
+
+/// Validates a model reply against SCHEMA. `Ok(None)` when the model found no
+/// work in the text; `Err` describes what is wrong, for the retry.
+pub fn parse_reply(reply: &str) -> Result<Option<MediaItem>, String> {
+    let map = json_object(reply)?;
+    if let Some(key) = map.keys().find(|k| !["title", "type", "directorOrAuthor", "releaseDate", "description", "cast", "tags"].contains(&k.as_str())) {
+        return Err(format!("unexpected property \"{}\"", key));
+    }
+    let title = match map.get("title") {
+        Some(Value::String(s)) => s.trim().to_string(),
+        _ => return Err("\"title\" is required and must be a string".to_string()),
+    };
+    let type_name = map.get("type").and_then(Value::as_str).unwrap_or("");
+    if !TYPES.contains(&type_name) {
+        return Err(format!("\"type\" must be one of {}", TYPES.join(", ")));
+    }
+    if title.is_empty() {
+        return Ok(None);
+    }
+    let media_type: MediaType = serde_json::from_value(Value::String(type_name.to_string())).map_err(|e| e.to_string())?;
+    let release_date = optional_string(&map, "releaseDate")?;
+    if !release_date.is_empty() && !valid_date(&release_date) {
+        return Err("\"releaseDate\" must look like YYYY, YYYY-MM or YYYY-MM-DD".to_string());
+    }
+    Ok(Some(MediaItem {
+        id: new_id(),
+        title,
+        media_type,
+        director_or_author: optional_string(&map, "directorOrAuthor")?,
+        release_date,
+        description: optional_string(&map, "description")?,
+        cast: string_list(&map, "cast")?,
+        tags: string_list(&map, "tags")?,
+        category: Some(CollectionCategory::ToWatch),
+        saved_at: Some(now_ms()),
+        ..Default::default()
+    }))
+}
@@ -0,0 +1,215 @@
+// RSS/Atom subscriptions attached to items (e.g. a manga site's chapter feed).
+// The scheduler's `feedPoll` job fetches every subscription, keeps entries it has
+// not seen before as unread, and flags the item as having a new update.
+
+use std::time::Duration;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use crate::database::Database;
+
+pub const FEED_EVENT: &str = "feed-update";
+
+const MAX_ENTRIES: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedEntry {
+    pub id: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub published: Option<String>,
+    pub fetched_at: i64,
+    #[serde(default)]
+    pub read: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedSubscription {
+    pub item_id: String,
+    pub url: String,
+    pub last_polled_at: Option<i64>,
+    pub last_error: Option<String>,
+    /// Newest first, capped at `MAX_ENTRIES`.
+    #[serde(default)]
+    pub entries: Vec<FeedEntry>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedUpdate {
+    pub username: String,
+    pub item_id: String,
+    pub new_entries: Vec<FeedEntry>,
+}
+
+#[derive(Debug, Default)]
+struct Parsed {
+    id: String,
+    title: String,
+    link: String,
+    published: String,
+}
+
+fn local_name(e: &BytesStart) -> Vec<u8> {
+    e.local_name().as_ref().to_vec()
+}
+
+// Atom puts the URL in an attribute; prefer rel="alternate" (or no rel)
+fn atom_href(e: &BytesStart) -> Option<String> {
+    let mut href = None;
+    let mut rel = None;
+    for a in e.attributes().flatten() {
+        match a.key.as_ref() {
+            b"href" => href = a.unescape_value().ok().map(|v| v.to_string()),
+            b"rel" => rel = a.unescape_value().ok().map(|v| v.to_string()),
+            _ => {}
+        }
+    }
+    href.filter(|_| rel.as_deref().map(|r| r == "alternate").unwrap_or(true))
+}
+
+/// Entries of an RSS 2.0 or Atom document in document order.
+pub fn parse_feed(xml: &str) -> Result<Vec<FeedEntry>, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut out = Vec::new();
+    let mut cur: Option<Parsed> = None;
+    let mut field: Vec<u8> = Vec::new();
+    let now = crate::database::now_ms();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name = local_name(&e);
+                if name == b"item" || name == b"entry" {
+                    cur = Some(Parsed::default());
+                } else if let Some(p) = cur.as_mut() {
+                    if name == b"link" && p.link.is_empty() {
+                        if let Some(h) = atom_href(&e) {
+                            p.link = h;
+                        }
+                    }
+                    field = name;
+                }
+            }
+            Ok(Event::Empty(e)) => {
+                if let Some(p) = cur.as_mut() {
+                    if local_name(&e) == b"link" && p.link.is_empty() {
+                        if let Some(h) = atom_href(&e) {
+                            p.link = h;
+                        }
+                    }
+                }
+            }
+            Ok(Event::Text(t)) => {
+                if let Some(p) = cur.as_mut() {
+                    let val = t.unescape().unwrap_or_default().to_string();
+                    set_field(p, &field, val);
+                }
+            }
+            Ok(Event::CData(t)) => {
+                if let Some(p) = cur.as_mut() {
+                    let val = String::from_utf8_lossy(t.as_ref()).to_string();
+                    set_field(p, &field, val);
+                }
+            }
+            Ok(Event::End(e)) => {
+                let name = e.local_name().as_ref().to_vec();
+                if name == b"item" || name == b"entry" {
+                    if let Some(p) = cur.take() {
+                        let id = [&p.id, &p.link, &p.title].into_iter().find(|s| !s.is_empty()).cloned().unwrap_or_default();
+                        if !id.is_empty() {
+                            out.push(FeedEntry {
+                                id,
+                                title: p.title,
+                                link: Some(p.link).filter(|l| !l.is_empty()),
+                                published: Some(p.published).filter(|d| !d.is_empty()),
+                                fetched_at: now,
+                                read: false,
+                            });
+                        }
+                    }
+                }
+                field.clear();
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid feed: {}", e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(out)
+}
+
+fn set_field(p: &mut Parsed, field: &[u8], val: String) {
+    match field {
+        b"title" if p.title.is_empty() => p.title = val,
+        b"guid" | b"id" if p.id.is_empty() => p.id = val,
+        b"link" if p.link.is_empty() => p.link = val,
+        b"pubDate" | b"published" | b"updated" | b"date" if p.published.is_empty() => p.published = val,
+        _ => {}
+    }
+}
+
+/// Adds entries not seen before to the subscription (newest first) and returns them.
+/// On the first poll everything is recorded as already read so old chapters don't
+/// show up as new.
+pub fn absorb_entries(sub: &mut FeedSubscription, fetched: Vec<FeedEntry>) -> Vec<FeedEntry> {
+    let first_poll = sub.last_polled_at.is_none();
+    let mut fresh: Vec<FeedEntry> = fetched
+        .into_iter()
+        .filter(|e| !sub.entries.iter().any(|s| s.id == e.id))
+        .map(|mut e| {
+            e.read = first_poll;
+            e
+        })
+        .collect();
+    fresh.dedup_by(|a, b| a.id == b.id);
+    sub.entries.splice(0..0, fresh.iter().cloned());
+    sub.entries.truncate(MAX_ENTRIES);
+    if first_poll {
+        Vec::new()
+    } else {
+        fresh
+    }
+}
+
+pub async fn fetch_feed(client: &Client, url: &str) -> Result<Vec<FeedEntry>, String> {
+    let fut = client
+        .get(url)
+        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
+        .header("Accept", "application/rss+xml, application/atom+xml, application/xml, text/xml")
+        .send();
+    let resp = tokio::time::timeout(Duration::from_secs(20), fut)
+        .await
+        .map_err(|_| "Request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let body = resp.text().await.map_err(|e| e.to_string())?;
+    parse_feed(&body)
+}
+
+/// Polls every subscription not polled within `min_age_ms` and emits an event per
+/// item with new entries.
+pub async fn poll_all(app: &AppHandle, db: &Database, client: &Client, min_age_ms: i64) -> Vec<FeedUpdate> {
+    let mut updates = Vec::new();
+    for (username, sub) in db.get_feeds_due(min_age_ms).await {
+        let fetched = fetch_feed(client, &sub.url).await;
+        let new_entries = db.record_feed_poll(&username, &sub.item_id, fetched).await;
+        if let Some(newest) = new_entries.first() {
+            let title = db.find_item(&username, &sub.item_id).await.map(|i| i.title).unwrap_or_default();
+            crate::notify::item(app, db, &username, &sub.item_id, &title, &newest.title).await;
+            let update = FeedUpdate { username, item_id: sub.item_id.clone(), new_entries };
+            let _ = app.emit(FEED_EVENT, update.clone());
+            updates.push(update);
+        }
+    }
+    updates
+}
@@ -0,0 +1,145 @@
+// Followed creators. Following a person (director, author, studio staff...)
+// lets the `creatorWorks` job look up their works on the providers their items
+// came from and suggest the ones that are new since the last look. The first
+// look at a provider only records what is already listed, so following someone
+// doesn't flood the suggestions with their back catalogue. Dismissing a
+// suggestion blocks that work for good.
+
+use std::collections::HashSet;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use crate::database::{Database, DAY_MS};
+use crate::models::{MediaItem, MediaType};
+use crate::people::{Person, PersonWork};
+
+/// Event emitted to the frontend with the suggestions a run found.
+pub const CREATOR_WORKS_EVENT: &str = "creator-works";
+
+/// Credits dated further back than this are old works newly added to the provider.
+const MAX_AGE_DAYS: i64 = 365;
+
+/// Kept in `PersonMeta` while the person is followed.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowState {
+    pub since: i64,
+    /// `work_key`s seen so far.
+    #[serde(default)]
+    pub known_works: HashSet<String>,
+    /// Providers looked at at least once; works from the others aren't new yet.
+    #[serde(default)]
+    pub checked_sources: Vec<String>,
+    pub last_checked_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatorSuggestion {
+    pub person_id: String,
+    pub person_name: String,
+    pub work: PersonWork,
+    pub found_at: i64,
+}
+
+/// Identifies a work across runs and in the blocklist.
+pub fn work_key(source: &str, source_id: &str) -> String {
+    format!("{}:{}", source, source_id)
+}
+
+/// Works to suggest out of a fresh listing from `sources`, recording them all as
+/// seen: not listed before, not collected or dismissed, and not an old credit.
+pub fn new_works(follow: &mut FollowState, sources: &[&str], works: Vec<PersonWork>, dismissed: &HashSet<String>, now: i64) -> Vec<PersonWork> {
+    let (y, m, d) = crate::updates::civil_from_days((now - MAX_AGE_DAYS * DAY_MS).div_euclid(DAY_MS));
+    let cutoff = format!("{:04}-{:02}-{:02}", y, m, d);
+    let mut fresh: Vec<PersonWork> = Vec::new();
+    for work in works {
+        let key = work_key(&work.source, &work.source_id);
+        let baseline = !follow.checked_sources.contains(&work.source);
+        if !follow.known_works.insert(key.clone()) || baseline || work.in_collection || dismissed.contains(&key) {
+            continue;
+        }
+        let recent = crate::dates::parse(&work.release_date).map(|d| d.iso.as_str() >= &cutoff[..d.iso.len()]).unwrap_or(true);
+        if recent && !fresh.iter().any(|w| w.source == work.source && w.source_id == work.source_id) {
+            fresh.push(work);
+        }
+    }
+    for source in sources {
+        if !follow.checked_sources.iter().any(|s| s == source) {
+            follow.checked_sources.push(source.to_string());
+        }
+    }
+    follow.last_checked_at = Some(now);
+    fresh
+}
+
+/// Providers to ask about a person, going by where their items came from.
+fn providers(person: &Person, items: &[MediaItem], has_tmdb_key: bool) -> Vec<&'static str> {
+    let credited: Vec<&MediaItem> = items.iter().filter(|i| person.item_ids.contains(&i.id)).collect();
+    let has_id = |key: &str| credited.iter().any(|i| i.provider_ids.as_ref().map(|ids| ids.contains_key(key)).unwrap_or(false));
+    let mut out = Vec::new();
+    if person.provider_ids.contains_key("bangumi") || has_id("bangumi") {
+        out.push("bangumi");
+    }
+    let screen = credited.iter().any(|i| matches!(i.media_type, MediaType::Movie | MediaType::TvSeries | MediaType::ShortDrama));
+    if has_tmdb_key && (person.provider_ids.contains_key("tmdb") || has_id("tmdb") || has_id("tmdbTv") || (screen && out.is_empty())) {
+        out.push("tmdb");
+    }
+    if credited.iter().any(|i| matches!(i.media_type, MediaType::Book | MediaType::Audiobook)) {
+        out.push("googleBooks");
+    }
+    out
+}
+
+/// Looks up every followed person's works and records the new ones as suggestions,
+/// notifying once per person. Returns how many were found.
+pub async fn check_all(app: &AppHandle, db: &Database, client: &Client) -> Result<usize, String> {
+    let tmdb_key = crate::secrets::resolve(None, crate::secrets::TMDB);
+    let mut total = 0;
+    for (username, person) in db.get_followed_people().await {
+        let items = db.get_all_for_user(&username).await?;
+        let mut works = Vec::new();
+        let mut checked = Vec::new();
+        for provider in providers(&person, &items, tmdb_key.is_some()) {
+            let known_id = person.provider_ids.get(provider).map(String::as_str);
+            let found = match (provider, tmdb_key.as_deref()) {
+                ("tmdb", Some(key)) => crate::people::tmdb_person_works(client, &person.name, known_id, key, &items).await,
+                ("bangumi", _) => crate::people::bangumi_person_works(client, &person.name, known_id, &items).await,
+                _ => crate::people::google_books_person_works(client, &person.name, &items).await.map(|w| (String::new(), w)),
+            };
+            match found {
+                Ok((provider_id, found)) => {
+                    if !provider_id.is_empty() && known_id != Some(provider_id.as_str()) {
+                        db.set_person_provider_id(&username, &person.id, provider, &provider_id).await;
+                    }
+                    works.extend(found);
+                    checked.push(provider);
+                }
+                Err(e) => eprintln!("Works lookup on {} failed for {}: {}", provider, person.name, e),
+            }
+        }
+        if checked.is_empty() {
+            continue;
+        }
+        let found = db.record_creator_works(&username, &person, &checked, works).await;
+        if found.is_empty() {
+            continue;
+        }
+        let _ = app.emit(CREATOR_WORKS_EVENT, &found);
+        let titles: Vec<&str> = found.iter().map(|s| s.work.title.as_str()).collect();
+        crate::notify::general(app, db, &format!("New from {}", person.name), &titles.join(", ")).await;
+        total += found.len();
+    }
+    Ok(total)
+}
+
+/// Newest suggestions first, leaving out works added to the collection since.
+pub fn visible(suggestions: &[CreatorSuggestion], items: &[MediaItem]) -> Vec<CreatorSuggestion> {
+    let mut out: Vec<CreatorSuggestion> = suggestions
+        .iter()
+        .filter(|s| !items.iter().any(|i| crate::dedupe::title_similarity(&i.title, &s.work.title) >= 0.9))
+        .cloned()
+        .collect();
+    out.sort_by_key(|s| std::cmp::Reverse(s.found_at));
+    out
+}
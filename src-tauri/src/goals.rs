@@ -0,0 +1,98 @@
+// Goals and challenges: "finish 50 books in 2025" or "clear the watchlist by
+// June". Progress is computed from the collection whenever goals are listed;
+// the GoalReminder job notifies once a week about goals behind pace.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::models::{CollectionCategory, ItemRevision, MediaItem, MediaType};
+
+/// At most one reminder per goal in this span.
+pub const REMIND_EVERY_MS: i64 = 7 * crate::database::DAY_MS;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum GoalKind {
+    /// Finish `target` items between `starts_at` and `ends_at`.
+    Finish,
+    /// Empty the To Watch list by `ends_at`; `target` is its size when the goal was made.
+    ClearBacklog,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Goal {
+    pub id: String,
+    pub title: String,
+    pub kind: GoalKind,
+    /// Only items of this type count; None for all.
+    pub media_type: Option<MediaType>,
+    pub target: u32,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_reminded_at: Option<i64>,
+}
+
+/// Fields accepted by `create_goal` / `update_goal`.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalInput {
+    pub title: Option<String>,
+    pub kind: Option<GoalKind>,
+    pub media_type: Option<MediaType>,
+    pub target: Option<u32>,
+    pub starts_at: Option<i64>,
+    pub ends_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalProgress {
+    #[serde(flatten)]
+    pub goal: Goal,
+    pub current: u32,
+    /// Where the user should be by now to finish on time.
+    pub expected: f64,
+    pub done: bool,
+    pub behind: bool,
+}
+
+fn counts(goal: &Goal, item: &MediaItem) -> bool {
+    goal.media_type.as_ref().map(|t| *t == item.media_type).unwrap_or(true)
+}
+
+/// To Watch items the goal covers.
+pub fn backlog(goal_type: Option<&MediaType>, items: &[MediaItem]) -> u32 {
+    items
+        .iter()
+        .filter(|i| i.category == Some(CollectionCategory::ToWatch) && goal_type.map(|t| *t == i.media_type).unwrap_or(true))
+        .count() as u32
+}
+
+pub fn progress(goal: &Goal, items: &[MediaItem], history: &HashMap<String, Vec<ItemRevision>>, now: i64) -> GoalProgress {
+    let current = match goal.kind {
+        GoalKind::Finish => items
+            .iter()
+            .filter(|i| counts(goal, i))
+            .filter_map(|i| crate::atom::completed_at(i, history.get(&i.id).map(|h| h.as_slice()).unwrap_or(&[])))
+            .filter(|at| *at >= goal.starts_at && *at < goal.ends_at)
+            .count() as u32,
+        GoalKind::ClearBacklog => goal.target.saturating_sub(backlog(goal.media_type.as_ref(), items)),
+    };
+    let span = (goal.ends_at - goal.starts_at).max(1) as f64;
+    let elapsed = ((now - goal.starts_at) as f64 / span).clamp(0.0, 1.0);
+    let expected = goal.target as f64 * elapsed;
+    let done = current >= goal.target;
+    // Rounding down keeps a goal that's one item short early on from nagging
+    GoalProgress { goal: goal.clone(), current, expected, done, behind: !done && (current as f64) < expected.floor() }
+}
+
+/// Goals behind pace that are still running and weren't reminded about recently.
+pub fn due_reminders(goals: &[GoalProgress], now: i64) -> Vec<&GoalProgress> {
+    goals
+        .iter()
+        .filter(|p| p.behind && now < p.goal.ends_at)
+        .filter(|p| p.goal.last_reminded_at.map(|t| now - t >= REMIND_EVERY_MS).unwrap_or(true))
+        .collect()
+}
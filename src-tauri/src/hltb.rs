@@ -0,0 +1,166 @@
+// Completion times from HowLongToBeat. The site has no public API; its own search
+// posts to an endpoint whose path and key are baked into the site's app script and
+// change with each deploy, so both are read from the script and cached until a
+// search stops working. Times come back in seconds and are kept in minutes.
+
+use std::sync::Mutex;
+use std::time::Duration;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const SITE: &str = "https://howlongtobeat.com";
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// Search endpoint path including its key, e.g. "/api/search/4b4cbe570602c88660f7df8ea0cb6b6e".
+static ENDPOINT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Average times, in minutes, as stored on an item.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayTimes {
+    pub game_id: String,
+    pub main_minutes: Option<u32>,
+    pub extra_minutes: Option<u32>,
+    pub completionist_minutes: Option<u32>,
+    pub fetched_at: i64,
+}
+
+impl PlayTimes {
+    /// Best guess at how long finishing takes: the main story, else whatever is known.
+    pub fn estimate_minutes(&self) -> Option<u32> {
+        self.main_minutes.or(self.extra_minutes).or(self.completionist_minutes)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HltbGame {
+    pub name: String,
+    pub year: Option<i32>,
+    pub image_url: Option<String>,
+    pub url: String,
+    pub times: PlayTimes,
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+    tokio::time::timeout(Duration::from_secs(15), request.header("User-Agent", USER_AGENT).header("Referer", SITE).send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+async fn page(client: &Client, url: &str) -> Result<String, String> {
+    let resp = send(client.get(url)).await?;
+    if !resp.status().is_success() {
+        return Err(format!("HowLongToBeat Error: {}", resp.status()));
+    }
+    resp.text().await.map_err(|e| e.to_string())
+}
+
+/// The search path from the app script, which builds it as
+/// `fetch("/api/<name>/".concat("abc").concat("def"), ...)`.
+fn endpoint_from_script(script: &str) -> Option<String> {
+    for (at, _) in script.match_indices("fetch(\"/api/") {
+        let rest = &script[at + "fetch(\"".len()..];
+        let path = &rest[..rest.find('"')?];
+        let mut key = String::new();
+        let mut tail = &rest[path.len() + 1..];
+        while let Some(next) = tail.strip_prefix(".concat(\"") {
+            let end = next.find('"')?;
+            key.push_str(&next[..end]);
+            tail = next[end + 1..].strip_prefix(')')?;
+        }
+        if !key.is_empty() && tail.starts_with(',') {
+            return Some(format!("{}{}", path, key));
+        }
+    }
+    None
+}
+
+async fn discover_endpoint(client: &Client) -> Result<String, String> {
+    let home = page(client, SITE).await?;
+    let script = home
+        .split("<script src=\"")
+        .skip(1)
+        .filter_map(|s| s.split('"').next())
+        .find(|src| src.contains("/pages/_app-"))
+        .ok_or_else(|| "HowLongToBeat page layout changed".to_string())?;
+    let url = if script.starts_with("http") { script.to_string() } else { format!("{}{}", SITE, script) };
+    endpoint_from_script(&page(client, &url).await?).ok_or_else(|| "HowLongToBeat search endpoint not found".to_string())
+}
+
+fn minutes(v: &Value) -> Option<u32> {
+    v.as_u64().filter(|s| *s > 0).map(|s| ((s + 30) / 60) as u32)
+}
+
+fn parse_game(v: &Value, now: i64) -> Option<HltbGame> {
+    let id = v["game_id"].as_u64()?;
+    Some(HltbGame {
+        name: v["game_name"].as_str()?.to_string(),
+        year: v["release_world"].as_i64().filter(|y| *y > 0).map(|y| y as i32),
+        image_url: v["game_image"].as_str().filter(|i| !i.is_empty()).map(|i| format!("{}/games/{}", SITE, i)),
+        url: format!("{}/game/{}", SITE, id),
+        times: PlayTimes {
+            game_id: id.to_string(),
+            main_minutes: minutes(&v["comp_main"]),
+            extra_minutes: minutes(&v["comp_plus"]),
+            completionist_minutes: minutes(&v["comp_100"]),
+            fetched_at: now,
+        },
+    })
+}
+
+async fn post_search(client: &Client, endpoint: &str, title: &str) -> Result<reqwest::Response, String> {
+    let body = json!({
+        "searchType": "games",
+        "searchTerms": title.split_whitespace().collect::<Vec<_>>(),
+        "searchPage": 1,
+        "size": 20,
+        "searchOptions": {
+            "games": {
+                "userId": 0,
+                "platform": "",
+                "sortCategory": "popular",
+                "rangeCategory": "main",
+                "rangeTime": { "min": null, "max": null },
+                "gameplay": { "perspective": "", "flow": "", "genre": "", "difficulty": "" },
+                "rangeYear": { "min": "", "max": "" },
+                "modifier": "",
+            },
+            "users": { "sortCategory": "postcount" },
+            "lists": { "sortCategory": "follows" },
+            "filter": "",
+            "sort": 0,
+            "randomizer": 0,
+        },
+        "useCache": true,
+    });
+    send(client.post(format!("{}{}", SITE, endpoint)).header("Origin", SITE).json(&body)).await
+}
+
+/// Games matching `title`, closest name first.
+pub async fn search(client: &Client, title: &str, now: i64) -> Result<Vec<HltbGame>, String> {
+    let cached = ENDPOINT.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut endpoint = match cached {
+        Some(e) => e,
+        None => discover_endpoint(client).await?,
+    };
+    let mut resp = post_search(client, &endpoint, title).await?;
+    // A new deploy retires the old key; look it up again once
+    if matches!(resp.status().as_u16(), 403 | 404) {
+        endpoint = discover_endpoint(client).await?;
+        resp = post_search(client, &endpoint, title).await?;
+    }
+    if !resp.status().is_success() {
+        *ENDPOINT.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        return Err(format!("HowLongToBeat Error: {}", resp.status()));
+    }
+    *ENDPOINT.lock().unwrap_or_else(|e| e.into_inner()) = Some(endpoint);
+    let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+    let wanted = title.trim().to_lowercase();
+    let mut games: Vec<HltbGame> = v["data"].as_array().into_iter().flatten().filter_map(|g| parse_game(g, now)).collect();
+    // Exact name matches first; the site's own popularity order otherwise
+    games.sort_by_key(|g| g.name.to_lowercase() != wanted);
+    Ok(games)
+}
@@ -0,0 +1,261 @@
+// Local cover cache. Posters are downloaded into `<app data>/covers/<sha256>.<ext>`
+// and served to the webview through the `mtimg://` protocol, so covers keep
+// working after the original URL rots or starts hotlink-blocking.
+//
+// Each cover also gets JPEG thumbnails (`<sha256>-small.jpg`, `<sha256>-medium.jpg`)
+// so grids don't decode full-size posters; pick one with `?size=small|medium`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::http::{Response, StatusCode};
+
+pub const PROTOCOL: &str = "mtimg";
+pub const PROGRESS_EVENT: &str = "poster-cache-progress";
+
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedImage {
+    pub hash: String,
+    /// File name inside the cache dir; also the path used in `mtimg://localhost/<file>`.
+    pub file: String,
+    /// URL the file was downloaded from; a different poster URL means the cache is stale.
+    pub source_url: String,
+    pub content_type: String,
+    pub size: u64,
+    pub cached_at: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheProgress {
+    pub done: usize,
+    pub total: usize,
+    pub item_id: String,
+    pub error: Option<String>,
+}
+
+/// Result of `fetch_image`: either inline data or a cached file for the `mtimg` protocol.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchedImage {
+    pub content_type: String,
+    pub size: u64,
+    pub data_url: Option<String>,
+    pub cached: Option<CachedImage>,
+}
+
+pub struct ImageCache {
+    dir: PathBuf,
+}
+
+/// Hosts that refuse image requests without a matching Referer.
+pub fn referer_for(url: &str) -> Option<&'static str> {
+    let host = url.split("://").nth(1)?.split('/').next()?;
+    if host.ends_with("doubanio.com") || host.ends_with("douban.com") {
+        Some("https://movie.douban.com/")
+    } else {
+        None
+    }
+}
+
+/// Douban's image hosts are domestic and throttle proxied traffic.
+pub fn prefers_direct(url: &str) -> bool {
+    referer_for(url).is_some()
+}
+
+pub(crate) fn sniff(bytes: &[u8]) -> Option<(&'static str, &'static str)> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some(("image/jpeg", "jpg")),
+        [0x89, b'P', b'N', b'G', ..] => Some(("image/png", "png")),
+        [b'G', b'I', b'F', b'8', ..] => Some(("image/gif", "gif")),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some(("image/webp", "webp")),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageSize {
+    Small,
+    Medium,
+    Full,
+}
+
+impl ImageSize {
+    fn from_query(query: Option<&str>) -> ImageSize {
+        let size = query
+            .unwrap_or("")
+            .split('&')
+            .find_map(|kv| kv.strip_prefix("size="))
+            .unwrap_or("");
+        match size {
+            "small" => ImageSize::Small,
+            "medium" => ImageSize::Medium,
+            _ => ImageSize::Full,
+        }
+    }
+
+    // Bounding box (width, height) for thumbnails; posters are roughly 2:3
+    fn bounds(self) -> Option<(u32, u32, &'static str)> {
+        match self {
+            ImageSize::Small => Some((200, 300, "small")),
+            ImageSize::Medium => Some((400, 600, "medium")),
+            ImageSize::Full => None,
+        }
+    }
+}
+
+fn thumbnail_name(file: &str, suffix: &str) -> String {
+    let stem = file.split('.').next().unwrap_or(file);
+    format!("{}-{}.jpg", stem, suffix)
+}
+
+/// Writes the small and medium thumbnails for a cached file (skipping existing ones).
+/// Decoding is CPU-bound, so call this from a blocking task.
+pub fn generate_thumbnails(dir: &Path, file: &str) -> Result<(), String> {
+    let mut decoded = None;
+    for size in [ImageSize::Small, ImageSize::Medium] {
+        let (w, h, suffix) = size.bounds().expect("thumbnail sizes have bounds");
+        let target = dir.join(thumbnail_name(file, suffix));
+        if target.exists() {
+            continue;
+        }
+        if decoded.is_none() {
+            let bytes = fs::read(dir.join(file)).map_err(|e| e.to_string())?;
+            decoded = Some(image::load_from_memory(&bytes).map_err(|e| e.to_string())?);
+        }
+        let img = decoded.as_ref().expect("decoded above");
+        // Never upscale small covers
+        let thumb = if img.width() > w || img.height() > h { img.thumbnail(w, h) } else { img.clone() };
+        let mut out = Cursor::new(Vec::new());
+        thumb.to_rgb8().write_to(&mut out, image::ImageFormat::Jpeg).map_err(|e| e.to_string())?;
+        fs::write(&target, out.into_inner()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn content_type_for(file: &str) -> &'static str {
+    match file.rsplit('.').next() {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+/// Downloads an image, sending `referer` (or the host's known one) and any extra headers.
+pub async fn fetch(client: &Client, url: &str, referer: Option<&str>, headers: &HashMap<String, String>) -> Result<(Vec<u8>, &'static str, &'static str), String> {
+    let mut req = client.get(url).header("Accept", "image/avif,image/webp,image/png,image/jpeg,*/*");
+    if let Some(referer) = referer.or_else(|| referer_for(url)) {
+        req = req.header("Referer", referer);
+    }
+    for (k, v) in headers {
+        req = req.header(k.as_str(), v.as_str());
+    }
+    let resp = tokio::time::timeout(Duration::from_secs(30), req.send())
+        .await
+        .map_err(|_| "Request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err("Image is too large".to_string());
+    }
+    let (content_type, ext) = sniff(&bytes).ok_or_else(|| "Response is not an image".to_string())?;
+    Ok((bytes.to_vec(), content_type, ext))
+}
+
+impl ImageCache {
+    pub fn new(dir: PathBuf) -> Self {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Failed to create cover cache dir: {}", e);
+        }
+        ImageCache { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Resolves a cache file name, rejecting anything that is not `<hex>.<ext>`.
+    pub fn path_of(&self, file: &str) -> Option<PathBuf> {
+        let (stem, ext) = file.split_once('.')?;
+        let valid = !stem.is_empty()
+            && stem.chars().all(|c| c.is_ascii_hexdigit())
+            && !ext.is_empty()
+            && ext.chars().all(|c| c.is_ascii_alphanumeric());
+        valid.then(|| self.dir.join(file))
+    }
+
+    /// Writes bytes into the cache under their content hash.
+    pub fn store(&self, bytes: &[u8], content_type: &str, ext: &str, source_url: &str, now: i64) -> Result<CachedImage, String> {
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        let file = format!("{}.{}", hash, ext);
+        let path = self.dir.join(&file);
+        // Content-addressed: an existing file already has these exact bytes
+        if !path.exists() {
+            let tmp = path.with_extension("tmp");
+            fs::write(&tmp, bytes).map_err(|e| e.to_string())?;
+            fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+        }
+        Ok(CachedImage {
+            hash,
+            file,
+            source_url: source_url.to_string(),
+            content_type: content_type.to_string(),
+            size: bytes.len() as u64,
+            cached_at: now,
+        })
+    }
+
+    /// Deletes a cached file and its thumbnails; returns whether the file existed.
+    pub fn remove(&self, file: &str) -> bool {
+        let Some(path) = self.path_of(file) else {
+            return false;
+        };
+        for suffix in ["small", "medium"] {
+            let _ = fs::remove_file(self.dir.join(thumbnail_name(file, suffix)));
+        }
+        fs::remove_file(path).is_ok()
+    }
+
+    pub async fn download(&self, client: &Client, url: &str, now: i64) -> Result<CachedImage, String> {
+        let (bytes, content_type, ext) = fetch(client, url, None, &HashMap::new()).await?;
+        self.store(&bytes, content_type, ext, url, now)
+    }
+
+    /// Serves `mtimg://localhost/<file>?size=small|medium`. Missing thumbnails are
+    /// generated on first request; if that fails the full image is served.
+    pub fn respond(&self, uri_path: &str, query: Option<&str>) -> Response<Vec<u8>> {
+        let file = uri_path.trim_start_matches('/');
+        let Some(full) = self.path_of(file) else {
+            return Response::builder().status(StatusCode::NOT_FOUND).body(Vec::new()).unwrap();
+        };
+        let thumb = ImageSize::from_query(query).bounds().and_then(|(_, _, suffix)| {
+            let path = self.dir.join(thumbnail_name(file, suffix));
+            (path.exists() || generate_thumbnails(&self.dir, file).is_ok()).then_some(path)
+        });
+        let (path, content_type) = match thumb {
+            Some(p) => (p, "image/jpeg"),
+            None => (full, content_type_for(file)),
+        };
+        match fs::read(path) {
+            Ok(bytes) => Response::builder()
+                .header("Content-Type", content_type)
+                .header("Cache-Control", "max-age=31536000, immutable")
+                .header("Access-Control-Allow-Origin", "*")
+                .body(bytes)
+                .unwrap(),
+            Err(_) => Response::builder().status(StatusCode::NOT_FOUND).body(Vec::new()).unwrap(),
+        }
+    }
+}
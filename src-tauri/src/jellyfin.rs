@@ -0,0 +1,191 @@
+// Watched state from Jellyfin or Emby, which share the API used here. Movies and
+// series of one server user are read with an API key and matched to MediaItems as
+// described in `media_server`; the server's item id is kept as the "jellyfin"
+// provider id. The `jellyfinSync` job repeats the pass, and with `push_watched`
+// items finished in MediaTracker are marked played on the server too.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::error::RecvError;
+use crate::database::{Database, ItemEventKind};
+use crate::media_server::{external_key, ServerItem, SyncSummary};
+use crate::models::{CollectionCategory, MediaType};
+
+/// Provider id key of the server's item id.
+pub const KEY: &str = "jellyfin";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct JellyfinAccount {
+    /// e.g. "http://192.168.1.10:8096"
+    pub server_url: String,
+    pub user_id: String,
+    pub user_name: String,
+    /// Mark items played on the server when they are finished here.
+    #[serde(default)]
+    pub push_watched: bool,
+    pub last_synced_at: Option<i64>,
+}
+
+fn request(client: &Client, method: reqwest::Method, server_url: &str, api_key: &str, path: &str) -> RequestBuilder {
+    let url = format!("{}{}", server_url.trim().trim_end_matches('/'), path);
+    client
+        .request(method, url)
+        .header("Accept", "application/json")
+        // Understood by both Jellyfin and Emby
+        .header("X-Emby-Token", api_key)
+}
+
+async fn send(request: RequestBuilder) -> Result<reqwest::Response, String> {
+    let resp = tokio::time::timeout(Duration::from_secs(30), request.send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    match resp.status().as_u16() {
+        401 => Err("Jellyfin rejected the API key".to_string()),
+        _ if !resp.status().is_success() => Err(format!("Jellyfin Error: {}", resp.status())),
+        _ => Ok(resp),
+    }
+}
+
+async fn get(client: &Client, server_url: &str, api_key: &str, path: &str) -> Result<Value, String> {
+    send(request(client, reqwest::Method::GET, server_url, api_key, path))
+        .await?
+        .json::<Value>()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Id of the server user called `user_name` (case-insensitive).
+pub async fn user_id(client: &Client, server_url: &str, api_key: &str, user_name: &str) -> Result<String, String> {
+    let users = get(client, server_url, api_key, "/Users").await?;
+    users
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|u| u["Name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(user_name.trim())))
+        .and_then(|u| u["Id"].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| format!("No Jellyfin user named {}", user_name.trim()))
+}
+
+/// "2023-11-14T22:13:20.0000000Z" as epoch milliseconds; the server writes UTC.
+fn parse_date(s: &str) -> Option<i64> {
+    let (date, time) = s.split_once('T')?;
+    let mut d = date.splitn(3, '-').map(|p| p.parse::<u32>().ok());
+    let (y, m, day) = (d.next()??, d.next()??, d.next()??);
+    let mut t = time.trim_end_matches('Z').split(':').map(|p| p.split('.').next().and_then(|p| p.parse::<i64>().ok()));
+    let (h, min, sec) = (t.next()??, t.next()??, t.next().flatten().unwrap_or(0));
+    let days = crate::smart::days_from_civil(y as i64, m, day);
+    Some(days * crate::database::DAY_MS + (h * 3600 + min * 60 + sec) * 1000)
+}
+
+/// One entry of an items listing; movies and series only.
+pub fn parse_item(v: &Value) -> Option<ServerItem> {
+    let media_type = match v["Type"].as_str()? {
+        "Movie" => MediaType::Movie,
+        "Series" => MediaType::TvSeries,
+        _ => return None,
+    };
+    let external_ids = v["ProviderIds"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(scheme, id)| Some((external_key(scheme, &media_type)?.to_string(), id.as_str().filter(|s| !s.is_empty())?.to_string())))
+        .collect::<HashMap<_, _>>();
+    let data = &v["UserData"];
+    Some(ServerItem {
+        id: v["Id"].as_str()?.to_string(),
+        title: v["Name"].as_str()?.to_string(),
+        original_title: v["OriginalTitle"].as_str().filter(|t| !t.is_empty()).map(str::to_string),
+        year: v["ProductionYear"].as_i64().map(|y| y as i32),
+        external_ids,
+        media_type,
+        // Series count as played once every episode is
+        watched: data["Played"].as_bool().unwrap_or(false),
+        last_viewed_at: data["LastPlayedDate"].as_str().and_then(parse_date),
+    })
+}
+
+/// Every movie and series of the user, with their playback state.
+pub async fn library(client: &Client, account: &JellyfinAccount, api_key: &str) -> Result<Vec<ServerItem>, String> {
+    let path = format!(
+        "/Users/{}/Items?Recursive=true&IncludeItemTypes=Movie,Series&Fields=ProviderIds,ProductionYear,OriginalTitle&EnableUserData=true",
+        urlencoding::encode(&account.user_id)
+    );
+    let v = get(client, &account.server_url, api_key, &path).await?;
+    Ok(v["Items"].as_array().into_iter().flatten().filter_map(parse_item).collect())
+}
+
+pub async fn mark_played(client: &Client, account: &JellyfinAccount, api_key: &str, item_id: &str) -> Result<(), String> {
+    let path = format!("/Users/{}/PlayedItems/{}", urlencoding::encode(&account.user_id), urlencoding::encode(item_id));
+    send(request(client, reqwest::Method::POST, &account.server_url, api_key, &path)).await.map(|_| ())
+}
+
+/// Syncs watched state for every linked server.
+pub async fn reconcile(db: &Database, client: &Client, api_key: &str) -> Result<SyncSummary, String> {
+    let mut total = SyncSummary::default();
+    for (username, account) in db.get_jellyfin_accounts().await {
+        let items = library(client, &account, api_key).await?;
+        total.add(&db.apply_server_items(&username, KEY, &items).await);
+    }
+    Ok(total)
+}
+
+async fn watched_items(db: &Database) -> HashSet<(String, String)> {
+    db.get_all_items()
+        .await
+        .into_iter()
+        .filter(|(_, i)| i.category == Some(CollectionCategory::Watched))
+        .map(|(user, i)| (user, i.id))
+        .collect()
+}
+
+/// Spawns the task that marks items played on the server when they move to
+/// Watched here, for users who turned on `push_watched`.
+pub fn start(app: AppHandle, db: Arc<Database>) {
+    tauri::async_runtime::spawn(async move {
+        let mut events = db.subscribe();
+        let mut watched = watched_items(&db).await;
+        loop {
+            let event = match events.recv().await {
+                Ok(e) => e,
+                Err(RecvError::Lagged(_)) => {
+                    watched = watched_items(&db).await;
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let key = (event.username.clone(), event.item_id.clone());
+            let item = match event.kind {
+                ItemEventKind::Saved => db.find_item(&event.username, &event.item_id).await,
+                _ => None,
+            };
+            let Some(item) = item.filter(|i| i.category == Some(CollectionCategory::Watched)) else {
+                watched.remove(&key);
+                continue;
+            };
+            if !watched.insert(key) {
+                continue;
+            }
+            let Some(server_id) = item.provider_ids.as_ref().and_then(|ids| ids.get(KEY)) else {
+                continue;
+            };
+            let Some(account) = db.get_jellyfin_accounts().await.into_iter().find(|(u, a)| *u == event.username && a.push_watched).map(|(_, a)| a) else {
+                continue;
+            };
+            let Some(api_key) = crate::secrets::resolve(None, crate::secrets::JELLYFIN) else {
+                continue;
+            };
+            let client = app.state::<crate::AppState>().direct_client.clone();
+            if let Err(e) = mark_played(&client, &account, &api_key, server_id).await {
+                eprintln!("Jellyfin: could not mark {} played: {}", item.title, e);
+            }
+        }
+    });
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+use crate::models::MediaItem;
+
+const JOURNAL_MAX_ENTRIES: usize = 100;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationKind {
+    Add,
+    Update,
+    BulkUpdate,
+    Remove,
+    Merge,
+    Import,
+    Restore,
+}
+
+/// State of one item before and after an operation. `None` means the item was not
+/// in the active collection (never added, or moved to trash).
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemChange {
+    pub item_id: String,
+    pub index: Option<usize>,
+    pub before: Option<MediaItem>,
+    pub after: Option<MediaItem>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    pub id: u64,
+    pub kind: OperationKind,
+    pub at: i64,
+    pub changes: Vec<ItemChange>,
+}
+
+/// Result of an undo or redo. `operation` holds only the changes that were applied;
+/// `skipped` lists items left alone because they were edited after the operation.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Replay {
+    pub operation: Operation,
+    pub skipped: Vec<String>,
+}
+
+// Per-user undo/redo stacks. Kept in memory only; the journal does not survive a restart.
+#[derive(Default)]
+pub struct Journal {
+    undo: Vec<Operation>,
+    redo: Vec<Operation>,
+    next_id: u64,
+}
+
+impl Journal {
+    pub fn record(&mut self, kind: OperationKind, at: i64, changes: Vec<ItemChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        self.next_id += 1;
+        self.undo.push(Operation { id: self.next_id, kind, at, changes });
+        if self.undo.len() > JOURNAL_MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Operation> {
+        self.undo.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Operation> {
+        self.redo.pop()
+    }
+
+    pub fn push_undo(&mut self, op: Operation) {
+        self.undo.push(op);
+    }
+
+    pub fn push_redo(&mut self, op: Operation) {
+        self.redo.push(op);
+    }
+
+    /// Most recent operations first.
+    pub fn recent(&self, limit: usize) -> Vec<Operation> {
+        self.undo.iter().rev().take(limit).cloned().collect()
+    }
+}
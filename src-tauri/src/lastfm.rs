@@ -0,0 +1,187 @@
+// Music from Last.fm scrobbles. An import turns a user's top albums (and the
+// albums of their loved tracks) into Music items carrying the play count; the
+// scheduler's `lastfmRefresh` job later updates play counts on albums already
+// imported, without adding new ones. Albums are matched by their Last.fm URL,
+// stored as the item's "lastfm" provider id.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use crate::database::{new_id, now_ms, Database};
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+
+const API: &str = "https://ws.audioscrobbler.com/2.0/";
+/// Last.fm asks clients to stay under five requests a second.
+const REQUEST_GAP: Duration = Duration::from_millis(250);
+/// Largest page `user.getTopAlbums` returns.
+const MAX_PAGE: u32 = 1000;
+const LOVED_LIMIT: u32 = 50;
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Album {
+    pub artist: String,
+    pub title: String,
+    pub url: String,
+    pub image_url: Option<String>,
+    pub play_count: u64,
+    /// Has a track the user loved.
+    pub loved: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LastfmImport {
+    pub added: usize,
+    pub updated: usize,
+}
+
+/// The provider id albums are matched on.
+pub fn album_key(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_lowercase()
+}
+
+async fn call(client: &Client, key: &str, params: &[(&str, &str)]) -> Result<Value, String> {
+    tokio::time::sleep(REQUEST_GAP).await;
+    let query: String = params.iter().map(|(k, v)| format!("&{}={}", k, urlencoding::encode(v))).collect();
+    let url = format!("{}?format=json&api_key={}{}", API, urlencoding::encode(key), query);
+    let resp = tokio::time::timeout(Duration::from_secs(15), client.get(&url).header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)").send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+    // Errors come back as {"error": 6, "message": "User not found"}, sometimes with a 200
+    if let Some(message) = v["message"].as_str().filter(|_| v["error"].is_number()) {
+        return Err(format!("Last.fm Error: {}", message));
+    }
+    Ok(v)
+}
+
+/// Largest image Last.fm lists, skipping its blank placeholder.
+fn image(v: &Value) -> Option<String> {
+    v.as_array()?
+        .iter()
+        .rev()
+        .filter_map(|i| i["#text"].as_str())
+        .find(|u| !u.is_empty() && !u.contains("2a96cbd8b46e442fc41c2b86b821562f"))
+        .map(str::to_string)
+}
+
+fn count(v: &Value) -> u64 {
+    v.as_u64().or_else(|| v.as_str()?.parse().ok()).unwrap_or(0)
+}
+
+pub async fn top_albums(client: &Client, key: &str, user: &str, limit: u32) -> Result<Vec<Album>, String> {
+    let limit = limit.clamp(1, MAX_PAGE).to_string();
+    let v = call(client, key, &[("method", "user.gettopalbums"), ("user", user), ("period", "overall"), ("limit", &limit)]).await?;
+    Ok(v["topalbums"]["album"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|a| {
+            Some(Album {
+                artist: a["artist"]["name"].as_str()?.to_string(),
+                title: a["name"].as_str().filter(|n| !n.is_empty())?.to_string(),
+                url: a["url"].as_str()?.to_string(),
+                image_url: image(&a["image"]),
+                play_count: count(&a["playcount"]),
+                loved: false,
+            })
+        })
+        .collect())
+}
+
+/// The user's plays of one album, via `album.getInfo`.
+pub async fn album_play_count(client: &Client, key: &str, user: &str, artist: &str, title: &str) -> Result<u64, String> {
+    let v = call(client, key, &[("method", "album.getinfo"), ("artist", artist), ("album", title), ("username", user)]).await?;
+    Ok(count(&v["album"]["userplaycount"]))
+}
+
+/// Albums of the user's most recently loved tracks. Loved tracks don't name their
+/// album, so each one is looked up with `track.getInfo`.
+pub async fn loved_albums(client: &Client, key: &str, user: &str) -> Result<Vec<Album>, String> {
+    let limit = LOVED_LIMIT.to_string();
+    let v = call(client, key, &[("method", "user.getlovedtracks"), ("user", user), ("limit", &limit)]).await?;
+    let mut albums: Vec<Album> = Vec::new();
+    for t in v["lovedtracks"]["track"].as_array().into_iter().flatten() {
+        let (Some(artist), Some(track)) = (t["artist"]["name"].as_str(), t["name"].as_str()) else {
+            continue;
+        };
+        let Ok(info) = call(client, key, &[("method", "track.getinfo"), ("artist", artist), ("track", track), ("username", user)]).await else {
+            continue;
+        };
+        let album = &info["track"]["album"];
+        let (Some(title), Some(url)) = (album["title"].as_str(), album["url"].as_str()) else {
+            continue;
+        };
+        if albums.iter().any(|a| album_key(&a.url) == album_key(url)) {
+            continue;
+        }
+        let artist = album["artist"].as_str().unwrap_or(artist);
+        albums.push(Album {
+            play_count: album_play_count(client, key, user, artist, title).await.unwrap_or(0),
+            artist: artist.to_string(),
+            title: title.to_string(),
+            url: url.to_string(),
+            image_url: image(&album["image"]),
+            loved: true,
+        });
+    }
+    Ok(albums)
+}
+
+/// Top albums plus, with `include_loved`, albums of loved tracks not among them.
+pub async fn fetch(client: &Client, key: &str, user: &str, limit: u32, include_loved: bool) -> Result<Vec<Album>, String> {
+    let mut albums = top_albums(client, key, user, limit).await?;
+    if include_loved {
+        for loved in loved_albums(client, key, user).await? {
+            match albums.iter_mut().find(|a| album_key(&a.url) == album_key(&loved.url)) {
+                Some(a) => a.loved = true,
+                None => albums.push(loved),
+            }
+        }
+    }
+    Ok(albums)
+}
+
+pub fn to_item(album: &Album) -> MediaItem {
+    let now = now_ms();
+    MediaItem {
+        id: new_id(),
+        title: album.title.clone(),
+        director_or_author: album.artist.clone(),
+        media_type: MediaType::Music,
+        category: Some(CollectionCategory::Watched),
+        saved_at: Some(now),
+        poster_url: album.image_url.clone(),
+        play_count: Some(album.play_count),
+        tags: album.loved.then(|| vec!["loved".to_string()]),
+        provider_ids: Some(HashMap::from([("lastfm".to_string(), album_key(&album.url))])),
+        ..Default::default()
+    }
+}
+
+/// Updates play counts of imported albums for every linked account; returns how many changed.
+pub async fn refresh(db: &Database, client: &Client, key: &str) -> Result<usize, String> {
+    let mut updated = 0;
+    for (username, lastfm_user) in db.get_lastfm_accounts().await {
+        let mut albums = top_albums(client, key, &lastfm_user, MAX_PAGE).await?;
+        // Imported albums that dropped out of the top list are looked up one by one
+        let seen: HashSet<String> = albums.iter().map(|a| album_key(&a.url)).collect();
+        for item in db.get_all_for_user(&username).await? {
+            let Some(id) = item.provider_ids.as_ref().and_then(|ids| ids.get("lastfm")) else {
+                continue;
+            };
+            if seen.contains(id) {
+                continue;
+            }
+            if let Ok(play_count) = album_play_count(client, key, &lastfm_user, &item.director_or_author, &item.title).await {
+                albums.push(Album { url: id.clone(), play_count, ..Default::default() });
+            }
+        }
+        updated += db.apply_lastfm(&username, &albums, false).await.updated;
+    }
+    Ok(updated)
+}
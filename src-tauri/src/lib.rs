@@ -1,13 +1,12 @@
-use tauri::{command, State, Manager};
+use tauri::{command, AppHandle, Emitter, State, Manager};
 use std::sync::Arc;
-use crate::models::CollectionData;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use reqwest::Client;
 use std::collections::HashMap;
 use std::error::Error;
 use database::Database;
-use models::{MediaItem, UserPublic, UserRecord};
+use models::{ItemPatch, MediaItem, Settings, TrustedDevice, UserPublic, UserRecord};
 use quick_xml::events::Event;
 use quick_xml::Reader;
 use std::time::Duration;
@@ -16,8 +15,83 @@ use tokio::sync::RwLock;
 use winreg::{enums::HKEY_CURRENT_USER, RegKey};
 
 mod models;
+mod activity;
+mod airtime;
+mod anilist;
+mod ao3;
+mod api;
+mod archive;
+mod at_rest;
+mod atom;
+mod audiobooks;
+mod auth;
+mod bgg;
+mod bilibili;
+mod clipboard;
+mod cloud_backup;
+mod collections;
+mod comics;
+mod content_rating;
+mod continue_watching;
+mod covers;
+mod custom_fields;
 mod database;
+mod dates;
+mod dedupe;
+mod envelope;
+mod episodes;
+mod extract;
+mod feeds;
+mod follows;
+mod goals;
+mod hltb;
+mod images;
+mod journal;
+mod jellyfin;
+mod lastfm;
+mod mangadex;
+mod media_scan;
+mod media_server;
+mod metadata;
+mod nfo;
+mod notes;
+mod notify;
+mod novelupdates;
+mod ordering;
+mod people;
+mod picker;
+mod plex;
+mod podcasts;
+mod quick_add;
+mod rate_limit;
+mod ratings;
+mod relations;
+mod review;
+mod scheduler;
+mod scrape;
+mod secrets;
+mod session;
+mod sharing;
+mod smart;
+mod spotify;
+mod statuses;
+mod stats;
+mod streaming;
+mod titles;
+mod trailers;
+mod tray;
 mod sync;
+mod upcoming;
+mod updates;
+mod viewing_history;
+mod watch_time;
+mod web;
+mod webhooks;
+mod webtoons;
+mod wiki;
+mod wikidata;
+#[cfg(test)]
+mod tests;
 
 const SEARCH_CACHE_TTL_MS: u64 = 2 * 60 * 60 * 1000;
 const SEARCH_CACHE_MAX_ENTRIES: usize = 512;
@@ -45,7 +119,17 @@ struct SearchConfig {
     use_system_proxy: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl SearchConfig {
+    /// Fills in `api_key` from the keychain (stored under the provider's name)
+    /// when the frontend didn't send one.
+    fn with_stored_key(mut self) -> Self {
+        let sent = self.api_key.take().filter(|k| !matches!(k.trim(), "" | "undefined" | "null"));
+        self.api_key = secrets::resolve(sent, &self.provider);
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 struct AIChatConfig {
     model: Option<String>,
     #[serde(rename = "baseURL")]
@@ -623,9 +707,26 @@ fn resolve_url(base: &str, v: &str) -> String {
     s.to_string()
 }
 
-#[command]
-async fn douban_cover(title: String, _kind: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
-    let q = urlencoding::encode(&title);
+/// GET a JSON document with the app's user agent and a 15s timeout.
+pub(crate) async fn fetch_json(client: &Client, url: &str) -> Result<Value, String> {
+    let fut = client
+        .get(url)
+        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
+        .header("Accept", "application/json")
+        .send();
+    let resp = tokio::time::timeout(std::time::Duration::from_secs(15), fut)
+        .await
+        .map_err(|_| "Request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    resp.json::<Value>().await.map_err(|e| e.to_string())
+}
+
+/// Finds the first Douban subject page for `title` and its `og:image`, via the direct client.
+async fn douban_lookup(client: &Client, title: &str) -> (Option<String>, Option<String>) {
+    let q = urlencoding::encode(title);
     // Prefer movie search, then book
     let urls = vec![
         format!("https://movie.douban.com/subject_search?search_text={}&cat=1002", q),
@@ -661,11 +762,10 @@ async fn douban_cover(title: String, _kind: Option<String>, state: State<'_, App
         }
         None
     }
-    // 1) Fetch search page(s) using direct client (domestic)
+    // 1) Fetch search page(s) (domestic)
     let mut subject_url: Option<String> = None;
     for u in urls {
-        if subject_url.is_some() { break; }
-        let fut = state.direct_client.get(&u).send();
+        let fut = client.get(&u).send();
         let res = tokio::time::timeout(std::time::Duration::from_secs(8), fut).await;
         if let Ok(Ok(resp)) = res {
             if let Ok(text) = resp.text().await {
@@ -677,20 +777,27 @@ async fn douban_cover(title: String, _kind: Option<String>, state: State<'_, App
         }
     }
     // 2) Fetch subject page and extract og:image
-    if let Some(su) = subject_url {
-        let fut = state.direct_client.get(&su).send();
-        if let Ok(Ok(resp)) = tokio::time::timeout(std::time::Duration::from_secs(8), fut).await {
-            if let Ok(text) = resp.text().await {
-                if let Some(img) = find_og_image(&text) {
-                    let body = serde_json::json!({ "ok": true, "url": su, "image": img }).to_string();
-                    return Ok(body);
-                }
-            }
+    let Some(su) = subject_url else {
+        return (None, None);
+    };
+    let fut = client.get(&su).send();
+    let mut image = None;
+    if let Ok(Ok(resp)) = tokio::time::timeout(std::time::Duration::from_secs(8), fut).await {
+        if let Ok(text) = resp.text().await {
+            image = find_og_image(&text);
         }
-        let body = serde_json::json!({ "ok": false, "url": su }).to_string();
-        return Ok(body);
     }
-    Ok(serde_json::json!({ "ok": false }).to_string())
+    (Some(su), image)
+}
+
+#[command]
+async fn douban_cover(title: String, _kind: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let body = match douban_lookup(&state.direct_client, &title).await {
+        (Some(su), Some(img)) => serde_json::json!({ "ok": true, "url": su, "image": img }),
+        (Some(su), None) => serde_json::json!({ "ok": false, "url": su }),
+        _ => serde_json::json!({ "ok": false }),
+    };
+    Ok(body.to_string())
 }
 
 #[command]
@@ -737,8 +844,30 @@ async fn fetch_og_image(url: String, config: Option<FetchPageConfig>, state: Sta
 }
 
 
+/// Fetches any page and extracts OpenGraph / Twitter card / JSON-LD metadata into a MediaItem draft.
+#[command]
+async fn scrape_url(url: String, config: Option<FetchPageConfig>, state: State<'_, AppState>) -> Result<scrape::ScrapedPage, String> {
+    let target = url.trim().to_string();
+    if !target.starts_with("http://") && !target.starts_with("https://") {
+        return Err("Not a web URL".to_string());
+    }
+    let (proxy_url, use_system_proxy) = config
+        .as_ref()
+        .map(|c| (c.proxy_url.clone(), c.use_system_proxy))
+        .unwrap_or((None, None));
+    let local_client = client_with_proxy(proxy_url, use_system_proxy);
+    // Douban pages are domestic and throttle proxied traffic
+    let client = if images::prefers_direct(&target) {
+        &state.direct_client
+    } else {
+        local_client.as_ref().unwrap_or(&state.proxy_client)
+    };
+    scrape::fetch_page(client, &target).await
+}
+
 #[command]
 async fn web_search(query: String, config: SearchConfig, state: State<'_, AppState>) -> Result<String, String> {
+    let config = config.with_stored_key();
     println!("Rust web_search called. Provider: {}, Type: {:?}", config.provider, config.search_type);
     
     // Choose HTTP client
@@ -861,6 +990,7 @@ async fn web_search(query: String, config: SearchConfig, state: State<'_, AppSta
 
 #[command]
 async fn test_search_provider(config: SearchConfig, state: State<'_, AppState>) -> Result<String, String> {
+    let config = config.with_stored_key();
     let start = std::time::Instant::now();
     
     // Use dynamic client based on config (like web_search)
@@ -909,8 +1039,44 @@ async fn test_search_provider(config: SearchConfig, state: State<'_, AppState>)
     }
 }
 
+/// Stores an API key or token in the OS keychain; an empty value deletes it.
+#[command]
+async fn set_secret(name: String, value: String, db: State<'_, Arc<Database>>) -> Result<(), String> {
+    if name != at_rest::KEY_SECRET {
+        return secrets::set(&name, &value);
+    }
+    // Only for putting a lost key back, which opens a collection that came up locked;
+    // everything else about the key goes through set_database_encryption
+    if !db.is_locked() {
+        return Err("The database key can only be changed with set_database_encryption".to_string());
+    }
+    let key = at_rest::key_from_base64(&value).ok_or_else(|| "Invalid database key".to_string())?;
+    if !db.key_opens_files(&key) {
+        return Err("This key does not open the collection".to_string());
+    }
+    secrets::set(&name, &value)?;
+    db.unlock().await
+}
+
+#[command]
+async fn get_secret(name: String) -> Result<Option<String>, String> {
+    if name == at_rest::KEY_SECRET {
+        return Err("The database key can't be read".to_string());
+    }
+    secrets::get(&name)
+}
+
+#[command]
+async fn delete_secret(name: String) -> Result<(), String> {
+    if name == at_rest::KEY_SECRET {
+        return Err("The database key can only be removed with set_database_encryption".to_string());
+    }
+    secrets::delete(&name)
+}
+
 #[command]
-async fn test_omdb(api_key: String, state: State<'_, AppState>) -> Result<String, String> {
+async fn test_omdb(api_key: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let api_key = secrets::resolve(api_key, secrets::OMDB).ok_or("Missing OMDb API Key")?;
     let start = std::time::Instant::now();
     let url = format!("https://www.omdbapi.com/?t={}&y={}&apikey={}", urlencoding::encode("Inception"), urlencoding::encode("2010"), urlencoding::encode(&api_key));
     let resp = state.direct_client.get(&url).send().await.map_err(|e| e.to_string())?;
@@ -931,6 +1097,7 @@ async fn test_omdb(api_key: String, state: State<'_, AppState>) -> Result<String
     });
     Ok(body.to_string())
 }
+
 #[command]
 async fn wiki_pageimages(title: String, lang_zh: bool, state: State<'_, AppState>) -> Result<String, String> {
     let base = if lang_zh { "https://zh.wikipedia.org/w/api.php" } else { "https://en.wikipedia.org/w/api.php" };
@@ -954,10 +1121,184 @@ async fn wiki_pageimages(title: String, lang_zh: bool, state: State<'_, AppState
     Ok(body2)
 }
 
+/// Plain-text article and infobox fields from Wikipedia (`lang` defaults to "en").
+#[command]
+async fn wiki_extract(title: String, lang: Option<String>, state: State<'_, AppState>) -> Result<wiki::WikiArticle, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Missing title".to_string());
+    }
+    let lang = lang.map(|l| l.trim().to_lowercase()).filter(|l| !l.is_empty()).unwrap_or_else(|| "en".to_string());
+    if !wiki::valid_lang(&lang) {
+        return Err("Invalid language".to_string());
+    }
+    wiki::extract(&state.proxy_client, title, &lang).await
+}
+
+/// Wikidata entity for an item, found by its provider ids (IMDb, TMDB, Bangumi, Douban) or else
+/// by title; `entity_id` skips the search. None when nothing matches.
+#[command]
+async fn wikidata_lookup(
+    title: Option<String>,
+    provider_ids: Option<HashMap<String, String>>,
+    entity_id: Option<String>,
+    lang: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<wikidata::WikidataEntity>, String> {
+    let lang = lang.map(|l| l.trim().to_lowercase()).filter(|l| !l.is_empty()).unwrap_or_else(|| "en".to_string());
+    if !wiki::valid_lang(&lang) {
+        return Err("Invalid language".to_string());
+    }
+    let id = match entity_id.map(|i| i.trim().to_uppercase()).filter(|i| !i.is_empty()) {
+        Some(id) if id.starts_with('Q') && id[1..].chars().all(|c| c.is_ascii_digit()) => Some(id),
+        Some(_) => return Err("Invalid Wikidata id".to_string()),
+        None => wikidata::resolve(&state.proxy_client, &provider_ids.unwrap_or_default(), title.as_deref(), &lang).await?,
+    };
+    match id {
+        Some(id) => wikidata::lookup(&state.proxy_client, &id, &lang).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Looks up the item's Chinese, English and Japanese titles on TMDB, Bangumi and
+/// Wikidata by its provider ids and adds the ones it lacks to `alt_titles`.
+#[command]
+async fn fetch_localized_titles(
+    session: String,
+    item_id: String,
+    tmdb_api_key: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<HashMap<String, String>, String> {
+    let username = sessions.user(&session)?;
+    let item = db.find_item(&username, &item_id).await.ok_or_else(|| "Item not found".to_string())?;
+    let key = secrets::resolve(tmdb_api_key, secrets::TMDB);
+    let found = titles::fetch(&state.proxy_client, &item, key.as_deref()).await?;
+    db.add_alt_titles(&username, &item_id, found).await
+}
+
+/// Services streaming, renting or selling a title in `country` (ISO code, default "US"),
+/// from JustWatch. Answers are cached for a few hours; None when JustWatch doesn't know it.
+#[command]
+async fn streaming_availability(
+    title: String,
+    country: Option<String>,
+    year: Option<i32>,
+    media_type: Option<models::MediaType>,
+    cache: State<'_, streaming::AvailabilityCache>,
+    state: State<'_, AppState>,
+) -> Result<Option<streaming::Availability>, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Missing title".to_string());
+    }
+    let country = country.map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty()).unwrap_or_else(|| "US".to_string());
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err("Invalid country".to_string());
+    }
+    let now = database::now_ms();
+    let key = streaming::cache_key(title, year, &country);
+    if let Some(hit) = cache.get(&key, now).await {
+        return Ok(hit);
+    }
+    let found = streaming::lookup(&state.proxy_client, title, year, media_type.as_ref(), &country, now).await?;
+    cache.put(key, found.clone(), now).await;
+    Ok(found)
+}
+
+/// Best YouTube trailer for a title, from the YouTube Data API when a `youtube` key is stored,
+/// else from the configured web search restricted to youtube.com. With `item_id` the URL is
+/// also saved on the item.
+#[command]
+async fn find_trailer(
+    session: String,
+    title: String,
+    year: Option<i32>,
+    item_id: Option<String>,
+    search: Option<SearchConfig>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<trailers::Trailer>, String> {
+    let username = app.state::<session::Sessions>().user(&session)?;
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Missing title".to_string());
+    }
+    let query = trailers::query(title, year);
+    let candidates = match secrets::resolve(None, secrets::YOUTUBE) {
+        Some(key) => trailers::youtube(&state.proxy_client, &query, &key).await?,
+        None => {
+            let config = search.map(SearchConfig::with_stored_key);
+            let client = &state.proxy_client;
+            let query = format!("{} site:youtube.com", query);
+            let results = match config.as_ref().map(|c| (c.provider.as_str(), c.api_key.as_deref(), c.cx.as_deref())) {
+                Some(("google", Some(key), Some(cx))) => google_search(client, &query, key, cx, Some("text")).await,
+                Some(("serper", Some(key), _)) => serper_search(client, &query, key, Some("text")).await,
+                _ => duckduckgo_search(client, &query).await,
+            };
+            results.map_err(|e| e.to_string())?.iter().filter_map(|r| trailers::from_link(&r.link, &r.title)).collect()
+        }
+    };
+    let Some(trailer) = trailers::best(candidates, title, year) else {
+        return Ok(None);
+    };
+    if let Some(id) = item_id {
+        let db = app.state::<Arc<Database>>();
+        let owner = db.item_owner(&username, &id, true).await?;
+        db.set_trailer(&owner, &id, &trailer.url).await?;
+    }
+    Ok(Some(trailer))
+}
+
+#[command]
+async fn find_cover_candidates(
+    title: String,
+    media_type: models::MediaType,
+    omdb_api_key: Option<String>,
+    search: Option<SearchConfig>,
+    state: State<'_, AppState>,
+) -> Result<Vec<covers::CoverCandidate>, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Missing title".to_string());
+    }
+    let search = search.map(SearchConfig::with_stored_key);
+    let omdb_api_key = secrets::resolve(omdb_api_key, secrets::OMDB);
+    let search_client = search.as_ref().and_then(|c| client_with_proxy(c.proxy_url.clone(), c.use_system_proxy));
+    let search_client = search_client.as_ref().unwrap_or(&state.proxy_client);
+    let image_search = async {
+        let Some(config) = search.as_ref() else {
+            return Vec::new();
+        };
+        let Some(key) = config.api_key.as_deref().filter(|k| !k.trim().is_empty()) else {
+            return Vec::new();
+        };
+        let query = format!("{} poster", title);
+        covers::image_search(search_client, &query, &config.provider, key, config.cx.as_deref()).await
+    };
+    let omdb = async {
+        match omdb_api_key.as_deref().filter(|k| !k.trim().is_empty()) {
+            Some(key) => covers::omdb(&state.direct_client, title, &media_type, key).await,
+            None => Vec::new(),
+        }
+    };
+    let (douban, wiki_zh, wiki_en, omdb, bangumi, searched) = tokio::join!(
+        covers::douban(&state.direct_client, title),
+        covers::wikipedia(&state.proxy_client, title, "zh"),
+        covers::wikipedia(&state.proxy_client, title, "en"),
+        omdb,
+        covers::bangumi(&state.proxy_client, title, &media_type),
+        image_search,
+    );
+    let all = [douban, omdb, bangumi, wiki_zh, wiki_en, searched].concat();
+    Ok(covers::dedupe(all))
+}
+
 #[command]
 async fn ai_chat(messages: Vec<Value>, temperature: f32, tools: Option<Value>, config: AIChatConfig, state: State<'_, AppState>) -> Result<String, String> {
     let start = std::time::Instant::now();
-    let api_key = config.api_key.ok_or("Missing API Key")?;
+    let api_key = secrets::resolve(config.api_key, secrets::AI).ok_or("Missing API Key")?;
     let raw_base = config.base_url.unwrap_or("https://api.moonshot.cn/v1".to_string());
     let mut base_url = raw_base.trim().trim_end_matches(')').trim_matches('"').trim_matches('\'').to_string();
     if base_url.is_empty() { base_url = "https://api.moonshot.cn/v1".to_string(); }
@@ -1076,6 +1417,33 @@ async fn ai_chat(messages: Vec<Value>, temperature: f32, tools: Option<Value>, c
     Err("API Error: exceeded retries".to_string())
 }
 
+/// Turns pasted text (a recommendation, a review snippet) into an item draft using
+/// the configured AI model. The reply is validated against `extract`'s schema and
+/// the model is asked to correct an invalid one; nothing is saved.
+#[command]
+async fn extract_item_from_text(text: String, config: AIChatConfig, app: AppHandle) -> Result<MediaItem, String> {
+    if text.trim().is_empty() {
+        return Err("Nothing to extract from".to_string());
+    }
+    let mut messages = extract::messages(&text);
+    let mut last_error = String::new();
+    for _ in 0..extract::MAX_ATTEMPTS {
+        let raw = ai_chat(messages.clone(), 0.2, None, config.clone(), app.state::<AppState>()).await?;
+        let reply: Value = serde_json::from_str(&raw).unwrap_or(Value::Null);
+        let content = reply["choices"][0]["message"]["content"].as_str().unwrap_or_default().to_string();
+        match extract::parse_reply(&content) {
+            Ok(Some(item)) => return Ok(item),
+            Ok(None) => return Err("No title found in the text".to_string()),
+            Err(e) => {
+                println!("AI extraction reply rejected: {}", e);
+                messages.extend(extract::retry_messages(&content, &e));
+                last_error = e;
+            }
+        }
+    }
+    Err(format!("The AI model did not return a valid item: {}", last_error))
+}
+
 #[command]
 async fn test_proxy(config: ProxyTestConfig, state: State<'_, AppState>) -> Result<String, String> {
     let url = config
@@ -1111,220 +1479,2241 @@ async fn test_proxy(config: ProxyTestConfig, state: State<'_, AppState>) -> Resu
 // --- Database Commands ---
 
 #[command]
-fn get_collection(username: String, db: State<Arc<Database>>) -> Result<Vec<MediaItem>, String> {
-    db.get_all_for_user(&username)
+fn get_recovery_report(db: State<Arc<Database>>) -> Option<database::RecoveryReport> {
+    db.recovery_report()
 }
 
 #[command]
-fn save_item(username: String, item: MediaItem, db: State<Arc<Database>>) -> Result<(), String> {
-    db.add_item_for_user(&username, item)
+async fn flush_database(db: State<'_, Arc<Database>>) -> Result<(), String> {
+    db.flush().await
 }
 
 #[command]
-fn remove_item(username: String, id: String, db: State<Arc<Database>>) -> Result<(), String> {
-    db.remove_item_for_user(&username, &id)
+async fn get_collection(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<MediaItem>, String> {
+    let username = sessions.user(&session)?;
+    let filter = db.get_content_filter(&username).await;
+    let mut items = db.get_all_for_user(&username).await?;
+    items.retain(|i| filter.allows(i));
+    Ok(items)
 }
 
 #[command]
-fn import_collection(username: String, items: Vec<MediaItem>, db: State<Arc<Database>>) -> Result<(), String> {
-    db.import_for_user(&username, items)
+async fn get_content_filter(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<content_rating::ContentFilter, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_content_filter(&username).await)
 }
 
+/// With `hide_mature`, `get_collection` leaves out items rated for adults.
 #[command]
-fn reorder_collection(username: String, ids: Vec<String>, db: State<Arc<Database>>) -> Result<(), String> {
-    db.reorder_items_for_user(&username, ids)
+async fn set_content_filter(
+    session: String,
+    filter: content_rating::ContentFilter,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_content_filter(&username, filter).await;
+    Ok(())
 }
 
-
 #[command]
-fn export_collection(
-    username: String,
-    target_path: Option<String>,
-    redact_sensitive: Option<bool>,
-    db: State<Arc<Database>>,
-    app: tauri::AppHandle,
-) -> Result<String, String> {
-    let items = db.get_all_for_user(&username)?;
-    let redact = redact_sensitive.unwrap_or(true);
-    let mut export_items = Vec::new();
-    if redact {
-        for mut it in items.clone() {
-            it.user_review = None;
-            it.notification_enabled = None;
-            export_items.push(it);
-        }
-    } else {
-        export_items = items;
+async fn save_item(session: String, item: MediaItem, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item.id, true).await?;
+    // Grid items carry their membership, so a cleared link means it left its collection
+    if item.parent_collection_id.is_none() && item.is_collection != Some(true) {
+        db.leave_collections(&owner, &item.id).await;
     }
+    db.add_item_for_user(&owner, item).await
+}
 
-    let out_path = if let Some(path) = target_path {
-        std::path::PathBuf::from(path)
-    } else {
-        let base_dir = app.path()
-            .document_dir()
-            .map_err(|e| e.to_string())?;
-        let out_dir = base_dir.join("MediaTracker").join(&username);
-        std::fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
-        out_dir.join("collection.json")
-    };
+/// Logs a finish (or rewatch/reread); `at` defaults to now.
+#[command]
+async fn add_completion(
+    session: String,
+    id: String,
+    at: Option<i64>,
+    rating: Option<f32>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &id, true).await?;
+    db.add_completion(&owner, &id, at, rating).await
+}
 
-    if let Some(parent) = out_path.parent() {
-        if !parent.exists() {
-             std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-        }
-    }
+/// Advances progress by one episode, using TMDB (needs `tmdb_api_key` or a stored one) or
+/// Bangumi episode counts for season rollover; without them the count is just incremented.
+#[command]
+async fn mark_next_episode_watched(
+    session: String,
+    item_id: String,
+    tmdb_api_key: Option<String>,
+    db: State<'_, Arc<Database>>,
+    state: State<'_, AppState>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    let item = db.find_item(&owner, &item_id).await.ok_or_else(|| "Item not found".to_string())?;
+    let key = secrets::resolve(tmdb_api_key, secrets::TMDB);
+    let counts = episodes::counts(&state.proxy_client, &item, key.as_deref()).await.unwrap_or_default();
+    db.mark_next_episode(&owner, &item_id, &counts).await
+}
 
-    let content = serde_json::to_string_pretty(&export_items).map_err(|e| e.to_string())?;
-    std::fs::write(&out_path, content).map_err(|e| e.to_string())?;
+/// Chapters are numbers ("12", "12.5") or whole-chapter ranges ("1-20"); `read` defaults to true.
+#[command]
+async fn mark_chapters_read(
+    session: String,
+    item_id: String,
+    chapters: Vec<String>,
+    volume: Option<u32>,
+    read: Option<bool>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    db.mark_chapters_read(&owner, &item_id, &chapters, volume, read.unwrap_or(true)).await
+}
 
-    Ok(out_path.to_string_lossy().to_string())
+/// Follows a Webtoon or Bilibili Comics title by its URL; new episodes are picked up by the update check.
+#[command]
+async fn follow_webtoon(
+    session: String,
+    item_id: String,
+    url: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let (source, title_id) = webtoons::parse_url(&url).ok_or_else(|| "Unsupported webtoon URL".to_string())?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    db.follow_update_source(&owner, &item_id, source.key(), &title_id).await
 }
 
 #[command]
-async fn bangumi_search(query: String, subject_type: Option<u32>, token: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
-    let mut url = format!("https://api.bgm.tv/search/subject/{}?responseGroup=large", urlencoding::encode(&query));
-    if let Some(t) = subject_type {
-        url.push_str(&format!("&type={}", t));
-    }
+async fn add_quote(
+    session: String,
+    item_id: String,
+    text: String,
+    location: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<models::Quote, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    db.save_quote(&owner, &item_id, None, &text, location).await
+}
 
-    let mut builder = state.proxy_client.get(&url)
-        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
-        .header("Accept", "application/json");
+#[command]
+async fn update_quote(
+    session: String,
+    item_id: String,
+    quote_id: String,
+    text: String,
+    location: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<models::Quote, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    db.save_quote(&owner, &item_id, Some(&quote_id), &text, location).await
+}
 
-    if let Some(tok) = token {
-        if !tok.is_empty() {
-            builder = builder.header("Authorization", format!("Bearer {}", tok));
-        }
-    }
+#[command]
+async fn delete_quote(session: String, item_id: String, quote_id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    db.delete_quote(&owner, &item_id, &quote_id).await
+}
 
-    let resp = builder.send().await.map_err(|e| e.to_string())?;
-    
-    if !resp.status().is_success() {
-        return Err(format!("Bangumi Error: {}", resp.status()));
-    }
-    
-    let body = resp.text().await.map_err(|e| e.to_string())?;
-    Ok(body)
+#[command]
+async fn add_note(session: String, item_id: String, markdown: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<notes::Note, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    db.save_note(&owner, &item_id, None, &markdown).await
 }
 
+/// Attachments the new markdown no longer links to are dropped and their files collected.
 #[command]
-async fn bangumi_details(id: u64, token: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
-    let url = format!("https://api.bgm.tv/v0/subjects/{}", id);
-    let mut builder = state.proxy_client.get(&url)
-        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
-        .header("Accept", "application/json");
+async fn update_note(
+    session: String,
+    item_id: String,
+    note_id: String,
+    markdown: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    attachments: State<'_, notes::Attachments>,
+) -> Result<notes::Note, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    let note = db.save_note(&owner, &item_id, Some(&note_id), &markdown).await?;
+    notes::collect_garbage(&attachments, &db.referenced_attachments().await)?;
+    Ok(note)
+}
 
-    if let Some(tok) = token {
-        if !tok.is_empty() {
-            builder = builder.header("Authorization", format!("Bearer {}", tok));
-        }
-    }
+#[command]
+async fn delete_note(
+    session: String,
+    item_id: String,
+    note_id: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    attachments: State<'_, notes::Attachments>,
+) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    db.delete_note(&owner, &item_id, &note_id).await?;
+    notes::collect_garbage(&attachments, &db.referenced_attachments().await)?;
+    Ok(())
+}
 
-    let resp = builder.send().await.map_err(|e| e.to_string())?;
-    
-    if !resp.status().is_success() {
-        return Err(format!("Bangumi Error: {}", resp.status()));
-    }
-    
-    let body = resp.text().await.map_err(|e| e.to_string())?;
-    Ok(body)
+/// Stores an image (file path or `data:` URL) for a note. Returns the note; link the
+/// image in its markdown as `mtnote://localhost/<file>`.
+#[command]
+async fn attach_note_image(
+    session: String,
+    item_id: String,
+    note_id: String,
+    source: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    attachments: State<'_, notes::Attachments>,
+) -> Result<notes::Note, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    let file = notes::store(&attachments, &source, database::now_ms())?;
+    db.add_note_attachment(&owner, &item_id, &note_id, &file).await
 }
 
+/// Removes attachment files no note refers to; returns how many.
+#[command]
+async fn collect_note_attachments(db: State<'_, Arc<Database>>, attachments: State<'_, notes::Attachments>) -> Result<usize, String> {
+    notes::collect_garbage(&attachments, &db.referenced_attachments().await)
+}
 
 #[command]
-fn register_user(username: String, password: String, db: State<Arc<Database>>) -> Result<UserPublic, String> {
-    let u = username.trim();
-    if u.len() < 3 { return Err("Username too short".to_string()); }
-    if password.len() < 6 { return Err("Password too short".to_string()); }
-    if db.find_user(u).is_some() {
-        return Err("USER_EXISTS".to_string());
-    }
+async fn bulk_update_items(session: String, ids: Vec<String>, patch: ItemPatch, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<usize, String> {
+    let username = sessions.user(&session)?;
+    db.bulk_update_for_user(&username, &ids, &patch).await
+}
 
-    let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
-    let hash = argon2
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(|e| e.to_string())?
-        .to_string();
+#[command]
+async fn remove_item(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &id, true).await?;
+    db.remove_item_for_user(&owner, &id).await
+}
 
-    let created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
-        .as_secs() as i64;
+#[command]
+async fn get_trash(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<MediaItem>, String> {
+    let username = sessions.user(&session)?;
+    db.get_trash_for_user(&username).await
+}
 
-    let record = UserRecord { username: u.to_string(), password_hash: hash, created_at };
-    db.add_user(record)?;
-    Ok(UserPublic { username: u.to_string() })
+#[command]
+async fn restore_item(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    db.restore_item_for_user(&username, &id).await
 }
 
 #[command]
-fn login_user(username: String, password: String, db: State<Arc<Database>>) -> Result<UserPublic, String> {
-    let u = username.trim();
-    let record = db.find_user(u).ok_or_else(|| "INVALID_CREDENTIALS".to_string())?;
+async fn empty_trash(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<usize, String> {
+    let username = sessions.user(&session)?;
+    db.empty_trash_for_user(&username).await
+}
 
-    let parsed = PasswordHash::new(&record.password_hash).map_err(|e| e.to_string())?;
-    let argon2 = Argon2::default();
-    match argon2.verify_password(password.as_bytes(), &parsed) {
-        Ok(_) => Ok(UserPublic { username: u.to_string() }),
-        Err(_) => Err("INVALID_CREDENTIALS".to_string()),
-    }
+#[command]
+async fn find_duplicates(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<dedupe::DuplicateGroup>, String> {
+    let username = sessions.user(&session)?;
+    db.find_duplicates_for_user(&username).await
 }
 
-// --- Sync Commands ---
+#[command]
+async fn merge_items(session: String, keep_id: String, merge_ids: Vec<String>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    db.merge_items_for_user(&username, &keep_id, &merge_ids).await
+}
 
 #[command]
-async fn start_sync_server(sync: State<'_, sync::SyncService>, db: State<'_, Arc<Database>>) -> Result<(), String> {
-    let db = db.inner().clone(); 
-    let sync = sync.inner().clone();
-    tokio::spawn(async move {
-        sync.start_server(db).await;
-    });
-    Ok(())
+async fn get_item_history(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<models::ItemRevision>, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &id, false).await?;
+    db.get_item_history(&owner, &id).await
 }
 
 #[command]
-fn get_peers(sync: State<'_, sync::SyncService>) -> Result<Vec<sync::PeerInfo>, String> {
-    Ok(sync.get_known_peers())
+async fn revert_item(session: String, id: String, revision: u32, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &id, true).await?;
+    db.revert_item(&owner, &id, revision).await
 }
 
+/// `period` is `all` (default), a year (`2024`), a month (`2024-03`) or the last N days (`30d`).
+/// `utc_offset_minutes` (e.g. 480 for UTC+8) sets where days begin for streaks.
 #[command]
-async fn sync_with_peer(peer_ip: String, peer_port: u16, db: State<'_, Arc<Database>>) -> Result<(), String> {
-    let url = format!("http://{}:{}/sync/data", peer_ip, peer_port);
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .map_err(|e| e.to_string())?;
-        
-    let resp = client.get(&url).send().await.map_err(|e| e.to_string())?;
-    let data: CollectionData = resp.json().await.map_err(|e| e.to_string())?;
-    db.merge_full_data(data)?;
-    Ok(())
+async fn get_statistics(
+    session: String,
+    period: Option<String>,
+    utc_offset_minutes: Option<i32>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<stats::Statistics, String> {
+    let username = sessions.user(&session)?;
+    let range = stats::parse_period(period.as_deref().unwrap_or(""), database::now_ms())?;
+    Ok(db.get_statistics(&username, range, utc_offset_minutes.unwrap_or(0)).await)
 }
 
-// Password hashing (Argon2)
-use argon2::{Argon2, PasswordHasher};
-use argon2::password_hash::{PasswordHash, PasswordVerifier, SaltString};
-use rand_core::OsRng;
+/// With `ai`, the configured model also writes a narrative; if that fails the
+/// review is returned without one.
+#[command]
+async fn generate_year_review(
+    session: String,
+    year: i64,
+    ai: Option<AIChatConfig>,
+    utc_offset_minutes: Option<i32>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<review::YearReview, String> {
+    let username = sessions.user(&session)?;
+    let mut year_review = db.year_review(&username, year, utc_offset_minutes.unwrap_or(0)).await;
+    if let Some(config) = ai {
+        let messages = vec![serde_json::json!({ "role": "user", "content": review::prompt(&year_review) })];
+        match ai_chat(messages, 0.7, None, config, state).await {
+            Ok(raw) => {
+                let reply: Value = serde_json::from_str(&raw).unwrap_or(Value::Null);
+                year_review.narrative = reply["choices"][0]["message"]["content"].as_str().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+                year_review.markdown = review::markdown(&year_review);
+            }
+            Err(e) => println!("Year review narrative failed: {}", e),
+        }
+    }
+    Ok(year_review)
+}
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_dialog::init())
-        .setup(|app| {
-            let db = Arc::new(Database::new(app.handle()));
-            app.manage(db.clone());
-            
-            let sync_service = sync::SyncService::new();
-            app.manage(sync_service);
+#[command]
+async fn get_activity_heatmap(
+    session: String,
+    year: i64,
+    utc_offset_minutes: Option<i32>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<stats::Heatmap, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_activity_heatmap(&username, year, utc_offset_minutes.unwrap_or(0)).await)
+}
 
-            // 1. Proxy Client (System Proxy Enabled) - For Google, Serper, etc.
-            let proxy_client = Client::builder()
-                .tcp_nodelay(true)
-                .user_agent("MediaTracker/1.0")
-                .local_address(std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0)))
-                .connect_timeout(std::time::Duration::from_secs(10))
-                .timeout(std::time::Duration::from_secs(120))
+/// Oldest first.
+#[command]
+async fn get_item_timeline(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<activity::ActivityEvent>, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &id, false).await?;
+    Ok(db.get_item_timeline(&owner, &id).await)
+}
+
+/// Newest first; `from` / `to` are epoch milliseconds, `to` exclusive.
+#[command]
+async fn get_activity_feed(
+    session: String,
+    from: Option<i64>,
+    to: Option<i64>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Vec<activity::ActivityEvent>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_activity_feed(&username, from.unwrap_or(0), to.unwrap_or(i64::MAX)).await)
+}
+
+/// Starts timing the user watching/reading an item; a timer already running for it is returned as is.
+#[command]
+async fn start_session(session: String, item_id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<watch_time::TimeSession, String> {
+    let username = sessions.user(&session)?;
+    db.start_time_session(&username, &item_id).await
+}
+
+#[command]
+async fn get_continue_watching(
+    session: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Vec<continue_watching::QueueEntry>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_continue_watching(&username).await)
+}
+
+#[command]
+async fn snooze_item(
+    session: String,
+    item_id: String,
+    days: Option<u32>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Option<i64>, String> {
+    let username = sessions.user(&session)?;
+    db.snooze_item(&username, &item_id, days.unwrap_or(continue_watching::DEFAULT_SNOOZE_DAYS)).await
+}
+
+#[command]
+async fn stop_session(session: String, item_id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<watch_time::TimeSession, String> {
+    let username = sessions.user(&session)?;
+    db.stop_time_session(&username, &item_id).await
+}
+
+/// Sessions for one item, or for all items when `item_id` is omitted; newest first.
+#[command]
+async fn get_time_sessions(
+    session: String,
+    item_id: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Vec<watch_time::TimeSession>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_time_sessions(&username, item_id.as_deref()).await)
+}
+
+#[command]
+async fn undo_last_operation(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Option<journal::Replay>, String> {
+    let username = sessions.user(&session)?;
+    db.undo_last_operation(&username).await
+}
+
+#[command]
+async fn redo_last_operation(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Option<journal::Replay>, String> {
+    let username = sessions.user(&session)?;
+    db.redo_last_operation(&username).await
+}
+
+#[command]
+async fn get_recent_operations(session: String, limit: Option<usize>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<journal::Operation>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_recent_operations(&username, limit.unwrap_or(20)).await)
+}
+
+#[command]
+async fn list_collections(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<collections::Collection>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_collections(&username).await)
+}
+
+#[command]
+async fn create_collection(session: String, input: collections::CollectionInput, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<collections::Collection, String> {
+    let username = sessions.user(&session)?;
+    db.save_collection(&username, None, input).await
+}
+
+#[command]
+async fn update_collection(session: String, id: String, input: collections::CollectionInput, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<collections::Collection, String> {
+    let username = sessions.user(&session)?;
+    db.save_collection(&username, Some(id), input).await
+}
+
+#[command]
+async fn delete_collection(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.delete_collection(&username, &id).await
+}
+
+#[command]
+async fn share_collection(
+    session: String,
+    id: String,
+    grantee: String,
+    permission: sharing::SharePermission,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<sharing::CollectionShare, String> {
+    let username = sessions.user(&session)?;
+    db.share_collection(&username, &id, grantee.trim(), permission).await
+}
+
+/// Owners stop sharing with `grantee`; grantees pass themselves to leave.
+#[command]
+async fn unshare_collection(
+    session: String,
+    owner: String,
+    id: String,
+    grantee: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.unshare_collection(&username, &owner, &id, &grantee).await
+}
+
+#[command]
+async fn list_collection_shares(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<sharing::CollectionShares, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_collection_shares(&username).await)
+}
+
+#[command]
+async fn get_statuses(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<statuses::StatusDef>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_statuses(&username).await)
+}
+
+/// `renames` maps old status names to new ones so items follow a rename.
+#[command]
+async fn set_statuses(
+    session: String,
+    statuses: Vec<statuses::StatusDef>,
+    renames: Option<HashMap<String, String>>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<usize, String> {
+    let username = sessions.user(&session)?;
+    db.set_statuses(&username, statuses, &renames.unwrap_or_default()).await
+}
+
+#[command]
+async fn pick_random(
+    session: String,
+    filters: Option<picker::PickFilters>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Option<picker::Pick>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.pick_random(&username, &filters.unwrap_or_default()).await)
+}
+
+#[command]
+async fn get_view_order(session: String, view: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<ordering::ViewOrder, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_view_order(&username, &view).await)
+}
+
+#[command]
+async fn set_item_order(
+    session: String,
+    view: String,
+    ids: Vec<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<ordering::ViewOrder, String> {
+    let username = sessions.user(&session)?;
+    db.set_item_order(&username, &view, ids).await
+}
+
+#[command]
+async fn pin_item(
+    session: String,
+    view: String,
+    item_id: String,
+    pinned: bool,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<ordering::ViewOrder, String> {
+    let username = sessions.user(&session)?;
+    db.pin_item(&username, &view, &item_id, pinned).await
+}
+
+#[command]
+async fn list_goals(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<goals::GoalProgress>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_goals(&username).await)
+}
+
+#[command]
+async fn create_goal(session: String, input: goals::GoalInput, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<goals::Goal, String> {
+    let username = sessions.user(&session)?;
+    db.save_goal(&username, None, input).await
+}
+
+#[command]
+async fn update_goal(session: String, id: String, input: goals::GoalInput, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<goals::Goal, String> {
+    let username = sessions.user(&session)?;
+    db.save_goal(&username, Some(id), input).await
+}
+
+#[command]
+async fn delete_goal(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.delete_goal(&username, &id).await
+}
+
+#[command]
+async fn get_custom_field_schema(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<custom_fields::CustomFieldDef>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_custom_field_schema(&username).await)
+}
+
+#[command]
+async fn set_custom_field_schema(session: String, fields: Vec<custom_fields::CustomFieldDef>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_custom_field_schema(&username, fields).await
+}
+
+/// Runs the ongoing-item update check for the session's user immediately, ignoring the interval.
+#[command]
+async fn check_updates_now(
+    session: String,
+    app: AppHandle,
+    db: State<'_, Arc<Database>>,
+    state: State<'_, AppState>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Vec<updates::UpdateFound>, String> {
+    let username = sessions.user(&session)?;
+    Ok(updates::run_check(&app, &db, &state.proxy_client, 0, Some(&username)).await)
+}
+
+// The poster the item displays: a user-chosen cover wins over the provider one
+fn display_poster(item: &MediaItem) -> Option<String> {
+    item.custom_poster_url
+        .clone()
+        .or_else(|| item.poster_url.clone())
+        .filter(|u| u.starts_with("http://") || u.starts_with("https://"))
+}
+
+async fn cache_item_poster(item: &MediaItem, force: bool, username: &str, db: &Database, cache: &images::ImageCache, state: &AppState) -> Result<Option<MediaItem>, String> {
+    let Some(url) = display_poster(item) else {
+        return Ok(None);
+    };
+    if !force && item.poster_cache.as_ref().map(|c| c.source_url == url).unwrap_or(false) {
+        return Ok(None);
+    }
+    let client = if images::prefers_direct(&url) { &state.direct_client } else { &state.proxy_client };
+    let cached = cache.download(client, &url, database::now_ms()).await?;
+    let (dir, file) = (cache.dir().to_path_buf(), cached.file.clone());
+    // Thumbnails are an optimisation; the protocol falls back to the full image
+    if let Ok(Err(e)) = tauri::async_runtime::spawn_blocking(move || images::generate_thumbnails(&dir, &file)).await {
+        eprintln!("Failed to generate thumbnails: {}", e);
+    }
+    db.set_poster_cache(username, &item.id, Some(cached)).await.map(Some)
+}
+
+/// Downloads an image the webview cannot load itself (e.g. Douban covers, which need
+/// a Referer). Returns a base64 data URL, or with `cache: true` a file in the cover
+/// cache that can be shown through the `mtimg` protocol.
+#[command]
+async fn fetch_image(
+    url: String,
+    referer: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    direct: Option<bool>,
+    cache: Option<bool>,
+    images: State<'_, images::ImageCache>,
+    state: State<'_, AppState>,
+) -> Result<images::FetchedImage, String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("Only http(s) URLs are supported".to_string());
+    }
+    let client = if direct.unwrap_or_else(|| images::prefers_direct(&url)) { &state.direct_client } else { &state.proxy_client };
+    let (bytes, content_type, ext) = images::fetch(client, &url, referer.as_deref(), &headers.unwrap_or_default()).await?;
+    let size = bytes.len() as u64;
+    if cache.unwrap_or(false) {
+        let cached = images.store(&bytes, content_type, ext, &url, database::now_ms())?;
+        return Ok(images::FetchedImage { content_type: content_type.to_string(), size, data_url: None, cached: Some(cached) });
+    }
+    use base64::Engine;
+    let data_url = format!("data:{};base64,{}", content_type, base64::engine::general_purpose::STANDARD.encode(&bytes));
+    Ok(images::FetchedImage { content_type: content_type.to_string(), size, data_url: Some(data_url), cached: None })
+}
+
+#[command]
+async fn cache_poster(session: String, id: String, db: State<'_, Arc<Database>>, cache: State<'_, images::ImageCache>, state: State<'_, AppState>, sessions: State<'_, session::Sessions>) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let item = db.find_item(&username, &id).await.ok_or_else(|| "Item not found".to_string())?;
+    let updated = cache_item_poster(&item, true, &username, &db, &cache, &state).await?;
+    updated.ok_or_else(|| "Item has no poster URL".to_string())
+}
+
+/// Downloads every poster that is not cached yet (or all of them with `force`),
+/// emitting a progress event per item. Returns the number of posters downloaded.
+#[command]
+async fn cache_all_posters(session: String, force: Option<bool>, app: AppHandle, db: State<'_, Arc<Database>>, cache: State<'_, images::ImageCache>, state: State<'_, AppState>, sessions: State<'_, session::Sessions>) -> Result<usize, String> {
+    let username = sessions.user(&session)?;
+    let items: Vec<MediaItem> = db.get_all_for_user(&username).await?.into_iter().filter(|i| i.is_collection != Some(true)).collect();
+    let total = items.len();
+    let mut downloaded = 0;
+    for (idx, item) in items.iter().enumerate() {
+        let result = cache_item_poster(item, force.unwrap_or(false), &username, &db, &cache, &state).await;
+        if let Ok(Some(_)) = result {
+            downloaded += 1;
+        }
+        let _ = app.emit(images::PROGRESS_EVENT, images::CacheProgress {
+            done: idx + 1,
+            total,
+            item_id: item.id.clone(),
+            error: result.err(),
+        });
+    }
+    Ok(downloaded)
+}
+
+/// Re-fetches provider details for `ids` (or every item when omitted), optionally
+/// restricted to one provider. Progress is reported through `metadata::REFRESH_EVENT`.
+#[command]
+async fn refresh_metadata(
+    session: String,
+    ids: Option<Vec<String>>,
+    provider: Option<String>,
+    app: AppHandle,
+    db: State<'_, Arc<Database>>,
+    control: State<'_, metadata::RefreshControl>,
+    state: State<'_, AppState>,
+) -> Result<metadata::RefreshSummary, String> {
+    let username = app.state::<session::Sessions>().user(&session)?;
+    let provider = provider.filter(|p| !p.is_empty() && p != "auto");
+    if let Some(p) = provider.as_deref() {
+        if p != "bangumi" && p != "tmdb" {
+            return Err(format!("Unsupported provider: {}", p));
+        }
+    }
+    metadata::refresh(&app, db.inner(), &control, &state.proxy_client, &username, ids, provider).await
+}
+
+/// Stops a running `refresh_metadata` after the requests in flight; returns false if none was running.
+#[command]
+async fn cancel_metadata_refresh(control: State<'_, metadata::RefreshControl>) -> Result<bool, String> {
+    Ok(control.cancel())
+}
+
+#[command]
+async fn list_jobs(app: AppHandle, db: State<'_, Arc<Database>>) -> Result<Vec<scheduler::JobStatus>, String> {
+    Ok(scheduler::status(&app, &db).await)
+}
+
+#[command]
+async fn run_job_now(kind: scheduler::JobKind, app: AppHandle, db: State<'_, Arc<Database>>) -> Result<scheduler::JobRun, String> {
+    scheduler::run_job(&app, db.inner(), kind).await
+}
+
+/// Lists uploaded archives; pass an unsaved `config` to test the connection before saving it.
+#[command]
+async fn list_cloud_backups(config: Option<cloud_backup::S3Config>, db: State<'_, Arc<Database>>, state: State<'_, AppState>) -> Result<Vec<cloud_backup::RemoteBackup>, String> {
+    let config = match config {
+        Some(c) => c,
+        None => db.get_settings().await.s3_backup.ok_or_else(|| "Cloud backup is not configured".to_string())?,
+    };
+    cloud_backup::list(&state.proxy_client, &config).await
+}
+
+#[command]
+async fn set_job_schedule(kind: scheduler::JobKind, enabled: Option<bool>, interval_minutes: Option<u32>, app: AppHandle, db: State<'_, Arc<Database>>) -> Result<Vec<scheduler::JobStatus>, String> {
+    let mut settings = db.get_settings().await;
+    let mut schedule = settings.job(kind);
+    if let Some(e) = enabled {
+        schedule.enabled = e;
+    }
+    if let Some(m) = interval_minutes {
+        if m == 0 {
+            return Err("Interval must be at least one minute".to_string());
+        }
+        schedule.interval_minutes = m;
+    }
+    settings.jobs.insert(kind, schedule);
+    db.update_settings(settings).await?;
+    Ok(scheduler::status(&app, &db).await)
+}
+
+#[command]
+async fn set_item_feed(session: String, item_id: String, url: Option<String>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_item_feed(&username, &item_id, url).await
+}
+
+#[command]
+async fn get_item_updates(session: String, item_id: Option<String>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<feeds::FeedSubscription>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_item_updates(&username, item_id.as_deref()).await)
+}
+
+#[command]
+async fn mark_feed_entries_read(session: String, item_id: String, entry_ids: Option<Vec<String>>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<usize, String> {
+    let username = sessions.user(&session)?;
+    db.mark_feed_entries_read(&username, &item_id, entry_ids).await
+}
+
+#[command]
+async fn poll_feeds_now(app: AppHandle, db: State<'_, Arc<Database>>, state: State<'_, AppState>) -> Result<Vec<feeds::FeedUpdate>, String> {
+    Ok(feeds::poll_all(&app, &db, &state.proxy_client, 0).await)
+}
+
+#[command]
+async fn get_relations(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<relations::RelatedItem>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_relations(&username, &id).await)
+}
+
+#[command]
+async fn get_franchise(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<MediaItem>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_franchise(&username, &id).await)
+}
+
+#[command]
+async fn set_relation(session: String, from_id: String, to_id: String, kind: relations::RelationKind, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_relation(&username, &from_id, &to_id, kind).await
+}
+
+#[command]
+async fn remove_relation(session: String, from_id: String, to_id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<bool, String> {
+    let username = sessions.user(&session)?;
+    db.remove_relation(&username, &from_id, &to_id).await
+}
+
+/// Populates the relation graph from TMDB collections (when a key is given) and
+/// Bangumi subject relations. Manual edges are left alone.
+#[command]
+async fn auto_link_relations(session: String, tmdb_api_key: Option<String>, db: State<'_, Arc<Database>>, state: State<'_, AppState>, sessions: State<'_, session::Sessions>) -> Result<usize, String> {
+    let username = sessions.user(&session)?;
+    let items = db.get_all_for_user(&username).await?;
+    let now = database::now_ms();
+    let mut found = relations::bangumi_links(&state.proxy_client, &items, now).await;
+    if let Some(key) = secrets::resolve(tmdb_api_key, secrets::TMDB) {
+        found.extend(relations::tmdb_collection_links(&state.proxy_client, &items, &key, now).await);
+    }
+    Ok(db.add_auto_relations(&username, found).await)
+}
+
+/// The "Upcoming" feed for the next `window_days` (default 90): next episodes of
+/// tracked shows, new books by followed authors and sequels of completed items,
+/// soonest first. `utc_offset_minutes` defaults to the saved setting.
+#[command]
+async fn get_upcoming(
+    session: String,
+    window_days: Option<u32>,
+    utc_offset_minutes: Option<i32>,
+    tmdb_api_key: Option<String>,
+    db: State<'_, Arc<Database>>,
+    state: State<'_, AppState>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Vec<upcoming::UpcomingEntry>, String> {
+    let username = sessions.user(&session)?;
+    let items = db.get_all_for_user(&username).await?;
+    let offset = match utc_offset_minutes {
+        Some(offset) => offset,
+        None => db.get_settings().await.utc_offset_minutes,
+    };
+    let span = upcoming::window(database::now_ms(), offset, window_days.unwrap_or(upcoming::DEFAULT_WINDOW_DAYS));
+    let mut entries = upcoming::from_collection(&items, &db.get_all_relations(&username).await, span, offset);
+    entries.extend(upcoming::books(&state.proxy_client, &items, &db.get_people(&username).await, span).await);
+    let key = secrets::resolve(tmdb_api_key, secrets::TMDB);
+    entries.extend(upcoming::sequels(&state.proxy_client, &items, key.as_deref(), span).await);
+    entries.sort_by_key(|e| e.at);
+    Ok(entries)
+}
+
+#[command]
+async fn list_people(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<people::Person>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_people(&username).await)
+}
+
+#[command]
+async fn get_person(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<people::PersonDetail, String> {
+    let username = sessions.user(&session)?;
+    db.get_person(&username, &id).await.ok_or_else(|| "Person not found".to_string())
+}
+
+/// Other works by a person from TMDB (needs `api_key` or a stored one) or Bangumi, each flagged
+/// with whether it is already in the collection and finished.
+#[command]
+async fn fetch_person_works(
+    session: String,
+    id: String,
+    provider: Option<String>,
+    api_key: Option<String>,
+    db: State<'_, Arc<Database>>,
+    state: State<'_, AppState>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<Vec<people::PersonWork>, String> {
+    let username = sessions.user(&session)?;
+    let detail = db.get_person(&username, &id).await.ok_or_else(|| "Person not found".to_string())?;
+    let items = db.get_all_for_user(&username).await?;
+    let api_key = secrets::resolve(api_key, secrets::TMDB);
+    let provider = provider.unwrap_or_else(|| if api_key.is_some() { "tmdb".to_string() } else { "bangumi".to_string() });
+    let known_id = detail.person.provider_ids.get(&provider).map(|s| s.as_str());
+    let (provider_id, works) = match provider.as_str() {
+        "tmdb" => {
+            let key = api_key.ok_or_else(|| "TMDB API key is required".to_string())?;
+            people::tmdb_person_works(&state.proxy_client, &detail.person.name, known_id, &key, &items).await?
+        }
+        "bangumi" => people::bangumi_person_works(&state.proxy_client, &detail.person.name, known_id, &items).await?,
+        other => return Err(format!("Unsupported provider '{}'", other)),
+    };
+    if known_id != Some(provider_id.as_str()) {
+        db.set_person_provider_id(&username, &id, &provider, &provider_id).await;
+    }
+    Ok(works)
+}
+
+/// Follows (or with `follow: false`, unfollows) a person so the `creatorWorks` job
+/// looks for their new works.
+#[command]
+async fn follow_person(session: String, id: String, follow: bool, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<people::Person, String> {
+    let username = sessions.user(&session)?;
+    db.follow_person(&username, &id, follow).await
+}
+
+/// New works by followed people, newest first.
+#[command]
+async fn list_creator_suggestions(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<follows::CreatorSuggestion>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_creator_suggestions(&username).await)
+}
+
+/// Drops a suggestion; the work is never suggested again.
+#[command]
+async fn dismiss_creator_suggestion(session: String, source: String, source_id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.dismiss_creator_work(&username, &source, &source_id).await;
+    Ok(())
+}
+
+#[command]
+async fn list_smart_lists(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<smart::SmartList>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_smart_lists(&username).await)
+}
+
+#[command]
+async fn create_smart_list(session: String, name: String, query: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<smart::SmartList, String> {
+    let username = sessions.user(&session)?;
+    db.save_smart_list(&username, None, name, query).await
+}
+
+#[command]
+async fn update_smart_list(session: String, id: String, name: String, query: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<smart::SmartList, String> {
+    let username = sessions.user(&session)?;
+    db.save_smart_list(&username, Some(id), name, query).await
+}
+
+#[command]
+async fn delete_smart_list(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.delete_smart_list(&username, &id).await
+}
+
+#[command]
+async fn evaluate_smart_list(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<MediaItem>, String> {
+    let username = sessions.user(&session)?;
+    let list = db
+        .get_smart_lists(&username)
+        .await
+        .into_iter()
+        .find(|l| l.id == id)
+        .ok_or_else(|| "Smart list not found".to_string())?;
+    db.evaluate_query(&username, &list.query).await
+}
+
+// Lets the editor preview results before saving
+#[command]
+async fn evaluate_smart_query(session: String, query: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<MediaItem>, String> {
+    let username = sessions.user(&session)?;
+    db.evaluate_query(&username, &query).await
+}
+
+#[command]
+async fn get_settings(db: State<'_, Arc<Database>>) -> Result<Settings, String> {
+    let mut settings = db.get_settings().await;
+    // Keys the keychain couldn't take stay with the backend
+    settings.tmdb_api_key = None;
+    if let Some(s3) = settings.s3_backup.as_mut() {
+        s3.secret_access_key.clear();
+    }
+    Ok(settings)
+}
+
+#[command]
+async fn update_settings(settings: Settings, app: AppHandle, db: State<'_, Arc<Database>>) -> Result<(), String> {
+    // Rebind first so a shortcut the system rejects isn't saved
+    if settings.quick_add_shortcut != db.get_settings().await.quick_add_shortcut {
+        quick_add::register(&app, &settings.quick_add_shortcut)?;
+    }
+    db.update_settings(settings).await
+}
+
+/// How `import_collection` would treat each item: added, skipped as already
+/// there, or held as a probable duplicate. Confirmed items can then be imported.
+#[command]
+async fn preview_import(session: String, items: Vec<MediaItem>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<dedupe::ImportDecision>, String> {
+    let username = sessions.user(&session)?;
+    let existing = db.get_all_for_user(&username).await?;
+    Ok(dedupe::classify_import(&existing, items))
+}
+
+#[command]
+async fn import_collection(session: String, items: Vec<MediaItem>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.import_for_user(&username, items).await
+}
+
+/// Imports `lastfm_user`'s top albums (up to `limit`, default 200) and, unless
+/// `include_loved` is false, the albums of their loved tracks as Music items.
+/// The account is remembered so the refresh job keeps play counts current.
+#[command]
+async fn import_lastfm(
+    session: String,
+    lastfm_user: String,
+    limit: Option<u32>,
+    include_loved: Option<bool>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<lastfm::LastfmImport, String> {
+    let username = sessions.user(&session)?;
+    let lastfm_user = lastfm_user.trim();
+    if lastfm_user.is_empty() {
+        return Err("Missing Last.fm username".to_string());
+    }
+    let key = secrets::resolve(None, secrets::LASTFM).ok_or_else(|| "Last.fm API key is not set".to_string())?;
+    let albums = lastfm::fetch(&state.proxy_client, &key, lastfm_user, limit.unwrap_or(200), include_loved.unwrap_or(true)).await?;
+    db.set_lastfm_account(&username, lastfm_user).await;
+    Ok(db.apply_lastfm(&username, &albums, true).await)
+}
+
+/// Links a Plex server and syncs its watched state right away; the `plexSync`
+/// job repeats the sync. A `token` given here replaces the stored one.
+#[command]
+async fn link_plex(
+    session: String,
+    server_url: String,
+    token: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<media_server::SyncSummary, String> {
+    let username = sessions.user(&session)?;
+    let server_url = server_url.trim().trim_end_matches('/');
+    if !server_url.starts_with("http://") && !server_url.starts_with("https://") {
+        return Err("Plex server URL must start with http:// or https://".to_string());
+    }
+    if let Some(token) = token.as_deref().filter(|t| !t.trim().is_empty()) {
+        secrets::set(secrets::PLEX, token)?;
+    }
+    let token = secrets::resolve(None, secrets::PLEX).ok_or_else(|| "Plex token is not set".to_string())?;
+    let items = plex::library(&state.direct_client, server_url, &token).await?;
+    db.set_plex_account(&username, Some(server_url)).await;
+    Ok(db.apply_server_items(&username, plex::KEY, &items).await)
+}
+
+#[command]
+async fn unlink_plex(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_plex_account(&username, None).await;
+    Ok(())
+}
+
+/// Links a Jellyfin or Emby server user and syncs their watched state right away;
+/// the `jellyfinSync` job repeats the sync. With `push_watched`, items finished
+/// here are marked played on the server. An `api_key` given here replaces the stored one.
+#[command]
+async fn link_jellyfin(
+    session: String,
+    server_url: String,
+    user_name: String,
+    api_key: Option<String>,
+    push_watched: Option<bool>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<media_server::SyncSummary, String> {
+    let db = app.state::<Arc<Database>>();
+    let username = app.state::<session::Sessions>().user(&session)?;
+    let server_url = server_url.trim().trim_end_matches('/');
+    if !server_url.starts_with("http://") && !server_url.starts_with("https://") {
+        return Err("Jellyfin server URL must start with http:// or https://".to_string());
+    }
+    if let Some(key) = api_key.as_deref().filter(|k| !k.trim().is_empty()) {
+        secrets::set(secrets::JELLYFIN, key)?;
+    }
+    let api_key = secrets::resolve(None, secrets::JELLYFIN).ok_or_else(|| "Jellyfin API key is not set".to_string())?;
+    let user_id = jellyfin::user_id(&state.direct_client, server_url, &api_key, &user_name).await?;
+    let account = jellyfin::JellyfinAccount {
+        server_url: server_url.to_string(),
+        user_id,
+        user_name: user_name.trim().to_string(),
+        push_watched: push_watched.unwrap_or(false),
+        last_synced_at: None,
+    };
+    let items = jellyfin::library(&state.direct_client, &account, &api_key).await?;
+    db.set_jellyfin_account(&username, Some(account)).await;
+    Ok(db.apply_server_items(&username, jellyfin::KEY, &items).await)
+}
+
+#[command]
+async fn unlink_jellyfin(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_jellyfin_account(&username, None).await;
+    Ok(())
+}
+
+/// Imports the movie and tvshow NFOs found under `path`. Items already in the
+/// collection by provider id are skipped; ones whose title only looks like an
+/// existing item come back as probable duplicates for the user to confirm.
+#[command]
+async fn import_nfo_folder(
+    session: String,
+    path: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<nfo::NfoImport, String> {
+    let username = sessions.user(&session)?;
+    let (found, failed) = tauri::async_runtime::spawn_blocking(move || nfo::scan(std::path::Path::new(&path)))
+        .await
+        .map_err(|e| e.to_string())??;
+    let existing = db.get_all_for_user(&username).await?;
+    let total = found.len();
+    let plan = dedupe::ImportPlan::new(&existing, found);
+    let summary = nfo::NfoImport { found: total, added: plan.add.len(), skipped: plan.skipped, failed, probable_duplicates: plan.probable };
+    db.import_for_user(&username, plan.add).await?;
+    Ok(summary)
+}
+
+/// Writes a movie or TV series as a Kodi NFO to `path` (a file, or a folder to
+/// get `movie.nfo`/`tvshow.nfo`); returns the file written.
+#[command]
+async fn export_nfo(
+    session: String,
+    item_id: String,
+    path: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<String, String> {
+    let username = sessions.user(&session)?;
+    let item = db.find_item(&username, &item_id).await.ok_or_else(|| "Item not found".to_string())?;
+    let written = nfo::export(&item, std::path::Path::new(&path))?;
+    Ok(written.to_string_lossy().to_string())
+}
+
+/// Finds the video files under `path` and proposes an item for each movie or show,
+/// matched on TMDB (with a key) or Bangumi, with a confidence score. Nothing is added.
+#[command]
+async fn scan_media_folder(
+    session: String,
+    path: String,
+    tmdb_api_key: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<media_scan::MediaScan, String> {
+    let username = sessions.user(&session)?;
+    let existing = db.get_all_for_user(&username).await?;
+    let key = secrets::resolve(tmdb_api_key, secrets::TMDB);
+    media_scan::scan(&state.proxy_client, std::path::Path::new(&path), key.as_deref(), &existing).await
+}
+
+/// Imports a viewing-history CSV (Netflix and similar services): episodes become
+/// one series item with progress, other rows watched movies. Titles that look
+/// like items already in the collection come back as probable duplicates.
+#[command]
+async fn import_viewing_history(session: String, path: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<viewing_history::HistoryImport, String> {
+    let username = sessions.user(&session)?;
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    let (found, mut summary) = viewing_history::parse(&String::from_utf8_lossy(&bytes))?;
+    let existing = db.get_all_for_user(&username).await?;
+    let plan = dedupe::ImportPlan::new(&existing, found);
+    summary.added = plan.add.len();
+    summary.skipped = plan.skipped;
+    summary.probable_duplicates = plan.probable;
+    db.import_for_user(&username, plan.add).await?;
+    Ok(summary)
+}
+
+/// Imports the anime seasons followed on Bilibili (追番); seasons already in the
+/// collection are skipped and probable duplicates returned for confirmation. A
+/// `cookie` (SESSDATA) given here replaces the stored one.
+#[command]
+async fn import_bilibili(
+    session: String,
+    cookie: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<bilibili::BilibiliImport, String> {
+    let username = sessions.user(&session)?;
+    if let Some(cookie) = cookie.as_deref().filter(|c| !c.trim().is_empty()) {
+        secrets::set(secrets::BILIBILI, cookie.trim())?;
+    }
+    let cookie = secrets::resolve(None, secrets::BILIBILI).ok_or_else(|| "Bilibili cookie is not set".to_string())?;
+    let found = bilibili::follow_list(&state.direct_client, &cookie).await?;
+    let existing = db.get_all_for_user(&username).await?;
+    let total = found.len();
+    let plan = dedupe::ImportPlan::new(&existing, found);
+    let summary = bilibili::BilibiliImport { found: total, added: plan.add.len(), skipped: plan.skipped, probable_duplicates: plan.probable };
+    db.import_for_user(&username, plan.add).await?;
+    Ok(summary)
+}
+
+/// A season's anime from AniList, most popular first, each flagged if it is already
+/// in the collection. Seasons are cached for a few hours.
+#[command]
+async fn seasonal_anime(
+    session: String,
+    year: i32,
+    season: anilist::Season,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+    cache: State<'_, anilist::SeasonCache>,
+) -> Result<Vec<anilist::SeasonalShow>, String> {
+    let username = sessions.user(&session)?;
+    let now = database::now_ms();
+    let mut shows = match cache.get(year, season, now).await {
+        Some(shows) => shows,
+        None => {
+            let shows = anilist::season(&state.proxy_client, year, season).await?;
+            cache.put(year, season, shows.clone(), now).await;
+            shows
+        }
+    };
+    anilist::mark_collected(&mut shows, &db.get_all_for_user(&username).await?);
+    Ok(shows)
+}
+
+/// Adds a show from the seasonal chart to To Watch, unless it looks like one already
+/// in the collection.
+#[command]
+async fn add_seasonal_anime(session: String, show: anilist::SeasonalShow, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let item = anilist::to_item(&show, database::now_ms());
+    let plan = dedupe::ImportPlan::new(&db.get_all_for_user(&username).await?, vec![item]);
+    if let Some(title) = plan.probable.first().and_then(|d| d.existing_title.as_deref()) {
+        return Err(format!("Already in your collection as \"{}\"", title));
+    }
+    let item = plan.add.into_iter().next().ok_or_else(|| "Already in your collection".to_string())?;
+    db.add_item_for_user(&username, item.clone()).await?;
+    Ok(item)
+}
+
+/// Provider results for the quick-add overlay, which gets the session from the main window.
+#[command]
+async fn quick_add_search(session: String, query: String, state: State<'_, AppState>, sessions: State<'_, session::Sessions>) -> Result<Vec<quick_add::Hit>, String> {
+    sessions.user(&session)?;
+    let key = secrets::resolve(None, secrets::TMDB);
+    Ok(quick_add::search(&state.proxy_client, &state.direct_client, &query, key.as_deref()).await)
+}
+
+/// Saves the result picked in the overlay and hides it; the open main window
+/// reloads on the `quick-add` event, as for the browser extension.
+#[command]
+async fn quick_add_save(session: String, hit: quick_add::Hit, app: AppHandle, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<MediaItem, String> {
+    let username = sessions.user(&session)?;
+    let item = quick_add::to_item(&hit, database::now_ms());
+    let plan = dedupe::ImportPlan::new(&db.get_all_for_user(&username).await?, vec![item]);
+    if let Some(title) = plan.probable.first().and_then(|d| d.existing_title.as_deref()) {
+        return Err(format!("Already in your collection as \"{}\"", title));
+    }
+    let item = plan.add.into_iter().next().ok_or_else(|| "Already in your collection".to_string())?;
+    db.add_item_for_user(&username, item.clone()).await?;
+    quick_add::hide(&app);
+    let _ = app.emit(sync::QUICK_ADD_EVENT, sync::QuickAdded { username, item: item.clone(), existing: false });
+    Ok(item)
+}
+
+#[command]
+async fn close_quick_add(app: AppHandle) -> Result<(), String> {
+    quick_add::hide(&app);
+    Ok(())
+}
+
+/// Starts Spotify sign-in and returns the authorization URL to open; the outcome
+/// arrives as a `spotify-auth` event. `client_id` is the user's Spotify app, whose
+/// redirect URI must be http://127.0.0.1:<port>/callback.
+#[command]
+async fn spotify_authorize(client_id: String, port: Option<u16>, app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    let client_id = client_id.trim();
+    if client_id.is_empty() {
+        return Err("Missing Spotify client id".to_string());
+    }
+    spotify::authorize(app, state.proxy_client.clone(), client_id.to_string(), port.unwrap_or(spotify::DEFAULT_PORT)).await
+}
+
+#[command]
+async fn spotify_connected() -> Result<bool, String> {
+    Ok(spotify::is_connected())
+}
+
+#[command]
+async fn spotify_disconnect() -> Result<(), String> {
+    secrets::delete(secrets::SPOTIFY_REFRESH_TOKEN)?;
+    secrets::delete(secrets::SPOTIFY_CLIENT_ID)
+}
+
+/// Imports saved albums (and playlists unless `include_playlists` is false) as Music items.
+#[command]
+async fn import_spotify(
+    session: String,
+    include_playlists: Option<bool>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<spotify::SpotifyImport, String> {
+    let username = sessions.user(&session)?;
+    let existing: Vec<String> = db
+        .get_all_for_user(&username)
+        .await?
+        .into_iter()
+        .filter_map(|i| i.provider_ids.and_then(|mut ids| ids.remove("spotify")))
+        .collect();
+    let (items, summary) = spotify::fetch_items(&state.proxy_client, include_playlists.unwrap_or(true), &existing).await?;
+    db.import_for_user(&username, items).await?;
+    Ok(summary)
+}
+
+#[command]
+async fn reorder_collection(session: String, ids: Vec<String>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.reorder_items_for_user(&username, ids).await
+}
+
+
+#[command]
+async fn export_collection(
+    session: String,
+    target_path: Option<String>,
+    redact_sensitive: Option<bool>,
+    db: State<'_, Arc<Database>>,
+    app: tauri::AppHandle,
+    sessions: State<'_, session::Sessions>,
+) -> Result<String, String> {
+    let username = sessions.user(&session)?;
+    let items = db.get_all_for_user(&username).await?;
+    let redact = redact_sensitive.unwrap_or(true);
+    let mut export_items = Vec::new();
+    if redact {
+        for mut it in items.clone() {
+            it.user_review = None;
+            it.notification_enabled = None;
+            export_items.push(it);
+        }
+    } else {
+        export_items = items;
+    }
+
+    let out_path = if let Some(path) = target_path {
+        std::path::PathBuf::from(path)
+    } else {
+        let base_dir = app.path()
+            .document_dir()
+            .map_err(|e| e.to_string())?;
+        let out_dir = base_dir.join("MediaTracker").join(&username);
+        std::fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
+        out_dir.join("collection.json")
+    };
+
+    if let Some(parent) = out_path.parent() {
+        if !parent.exists() {
+             std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let content = serde_json::to_string_pretty(&export_items).map_err(|e| e.to_string())?;
+    std::fs::write(&out_path, content).map_err(|e| e.to_string())?;
+
+    Ok(out_path.to_string_lossy().to_string())
+}
+
+/// Writes the next airing of every tracked show as an .ics file and returns its path.
+/// `utc_offset_minutes` places date-only airings on the user's local day; defaults to
+/// the saved setting.
+#[command]
+async fn export_calendar(
+    session: String,
+    target_path: Option<String>,
+    utc_offset_minutes: Option<i32>,
+    db: State<'_, Arc<Database>>,
+    app: tauri::AppHandle,
+    sessions: State<'_, session::Sessions>,
+) -> Result<String, String> {
+    let username = sessions.user(&session)?;
+    let items = db.get_all_for_user(&username).await?;
+    let offset = match utc_offset_minutes {
+        Some(offset) => offset,
+        None => db.get_settings().await.utc_offset_minutes,
+    };
+
+    let out_path = if let Some(path) = target_path {
+        std::path::PathBuf::from(path)
+    } else {
+        let base_dir = app.path()
+            .document_dir()
+            .map_err(|e| e.to_string())?;
+        base_dir.join("MediaTracker").join(&username).join("airing.ics")
+    };
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    std::fs::write(&out_path, airtime::calendar(&items, offset, database::now_ms())).map_err(|e| e.to_string())?;
+    Ok(out_path.to_string_lossy().to_string())
+}
+
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveExport {
+    path: String,
+    items: usize,
+    covers: usize,
+}
+
+/// Writes the user's whole collection and its cached covers to one ZIP (see `archive`).
+#[command]
+async fn export_user_archive(
+    session: String,
+    target_path: Option<String>,
+    db: State<'_, Arc<Database>>,
+    app: tauri::AppHandle,
+    sessions: State<'_, session::Sessions>,
+) -> Result<ArchiveExport, String> {
+    let username = sessions.user(&session)?;
+    let out_path = match target_path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            let out_dir = app.path().document_dir().map_err(|e| e.to_string())?.join("MediaTracker").join(&username);
+            out_dir.join(format!("{}-archive-{}.zip", username, database::now_ms()))
+        }
+    };
+    if let Some(parent) = out_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let archive = db.user_archive(&username).await;
+    let items = archive.items.len();
+    let target = out_path.clone();
+    let covers = tauri::async_runtime::spawn_blocking(move || archive::write(&target, &archive, &app.state::<images::ImageCache>()))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(ArchiveExport { path: out_path.to_string_lossy().to_string(), items, covers })
+}
+
+/// Merges an archive from `export_user_archive` into the signed-in account,
+/// which may have a different name than the one that exported it.
+#[command]
+async fn import_user_archive(
+    session: String,
+    path: String,
+    db: State<'_, Arc<Database>>,
+    app: tauri::AppHandle,
+    sessions: State<'_, session::Sessions>,
+) -> Result<archive::ArchiveImport, String> {
+    let username = sessions.user(&session)?;
+    let (archive, covers) = tauri::async_runtime::spawn_blocking(move || archive::read(std::path::Path::new(&path), &app.state::<images::ImageCache>()))
+        .await
+        .map_err(|e| e.to_string())??;
+    let mut summary = db.import_user_archive(&username, archive).await;
+    summary.covers = covers;
+    Ok(summary)
+}
+
+#[command]
+async fn bangumi_search(query: String, subject_type: Option<u32>, token: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let mut url = format!("https://api.bgm.tv/search/subject/{}?responseGroup=large", urlencoding::encode(&query));
+    if let Some(t) = subject_type {
+        url.push_str(&format!("&type={}", t));
+    }
+
+    let mut builder = state.proxy_client.get(&url)
+        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
+        .header("Accept", "application/json");
+
+    if let Some(tok) = token {
+        if !tok.is_empty() {
+            builder = builder.header("Authorization", format!("Bearer {}", tok));
+        }
+    }
+
+    let resp = builder.send().await.map_err(|e| e.to_string())?;
+    
+    if !resp.status().is_success() {
+        return Err(format!("Bangumi Error: {}", resp.status()));
+    }
+    
+    let body = resp.text().await.map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+/// MangaDex titles matching `query` with chapters in `languages` (default: the
+/// `mangadexLanguages` setting).
+#[command]
+async fn mangadex_search(
+    query: String,
+    languages: Option<Vec<String>>,
+    db: State<'_, Arc<Database>>,
+    state: State<'_, AppState>,
+) -> Result<Vec<mangadex::Manga>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("Missing query".to_string());
+    }
+    let languages = match languages {
+        Some(l) => l,
+        None => db.get_settings().await.mangadex_languages,
+    };
+    mangadex::search(&state.proxy_client, query, &languages).await
+}
+
+#[command]
+async fn mangadex_details(id: String, state: State<'_, AppState>) -> Result<mangadex::Manga, String> {
+    mangadex::details(&state.proxy_client, id.trim()).await
+}
+
+#[command]
+async fn novelupdates_search(query: String, state: State<'_, AppState>) -> Result<Vec<novelupdates::Novel>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("Missing query".to_string());
+    }
+    novelupdates::search(&state.proxy_client, query).await
+}
+
+/// `id` is a series slug or series page URL.
+#[command]
+async fn novelupdates_details(id: String, state: State<'_, AppState>) -> Result<novelupdates::Novel, String> {
+    novelupdates::details(&state.proxy_client, &id).await
+}
+
+/// `id` is a work id or a work/chapter URL.
+#[command]
+async fn ao3_work(id: String, state: State<'_, AppState>) -> Result<ao3::Work, String> {
+    ao3::work(&state.proxy_client, &id).await
+}
+
+fn podcastindex_credentials() -> Option<podcasts::Credentials> {
+    let key = secrets::resolve(None, secrets::PODCASTINDEX_KEY)?;
+    let secret = secrets::resolve(None, secrets::PODCASTINDEX_SECRET)?;
+    Some(podcasts::Credentials { key, secret })
+}
+
+/// Searches PodcastIndex when its key and secret are stored, iTunes otherwise.
+#[command]
+async fn podcast_search(query: String, state: State<'_, AppState>) -> Result<Vec<podcasts::Podcast>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("Missing query".to_string());
+    }
+    podcasts::search(&state.proxy_client, query, podcastindex_credentials().as_ref()).await
+}
+
+/// `source` is "itunes" (default) or "podcastIndex".
+#[command]
+async fn podcast_details(source: Option<String>, id: String, state: State<'_, AppState>) -> Result<podcasts::Podcast, String> {
+    let source = source.unwrap_or_else(|| "itunes".to_string());
+    podcasts::details(&state.proxy_client, &source, id.trim(), podcastindex_credentials().as_ref()).await
+}
+
+/// Attaches a podcast's RSS feed to the item so the feed poller reports new episodes.
+/// Without `feed_url` the feed is looked up from the item's iTunes or PodcastIndex id.
+#[command]
+async fn follow_podcast(
+    session: String,
+    item_id: String,
+    feed_url: Option<String>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let username = sessions.user(&session)?;
+    let owner = db.item_owner(&username, &item_id, true).await?;
+    let feed_url = match feed_url.filter(|u| !u.trim().is_empty()) {
+        Some(url) => url.trim().to_string(),
+        None => {
+            let item = db.find_item(&owner, &item_id).await.ok_or_else(|| "Item not found".to_string())?;
+            let ids = item.provider_ids.unwrap_or_default();
+            let (source, id) = ["itunes", "podcastIndex"]
+                .iter()
+                .find_map(|s| ids.get(*s).map(|id| (*s, id.clone())))
+                .ok_or_else(|| "Item has no podcast id; pass the feed URL".to_string())?;
+            podcasts::details(&state.proxy_client, source, &id, podcastindex_credentials().as_ref())
+                .await?
+                .feed_url
+                .ok_or_else(|| "Podcast has no public feed".to_string())?
+        }
+    };
+    db.set_item_feed(&owner, &item_id, Some(feed_url.clone())).await?;
+    Ok(feed_url)
+}
+
+/// `region` is an Audible store ("us", "uk", "de", ...); the US store by default.
+#[command]
+async fn audiobook_search(query: String, region: Option<String>, state: State<'_, AppState>) -> Result<Vec<audiobooks::Audiobook>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("Missing query".to_string());
+    }
+    audiobooks::search(&state.proxy_client, query, region.as_deref().unwrap_or("us")).await
+}
+
+#[command]
+async fn audiobook_details(asin: String, region: Option<String>, state: State<'_, AppState>) -> Result<audiobooks::Audiobook, String> {
+    audiobooks::details(&state.proxy_client, &asin, region.as_deref().unwrap_or("us")).await
+}
+
+#[command]
+async fn bgg_search(query: String, state: State<'_, AppState>) -> Result<Vec<bgg::BoardGame>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("Missing query".to_string());
+    }
+    let token = secrets::resolve(None, secrets::BGG);
+    bgg::search(&state.proxy_client, query, token.as_deref()).await
+}
+
+#[command]
+async fn bgg_details(id: String, state: State<'_, AppState>) -> Result<bgg::BoardGame, String> {
+    let token = secrets::resolve(None, secrets::BGG);
+    bgg::thing(&state.proxy_client, id.trim(), token.as_deref()).await
+}
+
+/// Best HowLongToBeat match for `title` (same release year first when given);
+/// with `item_id` its times are stored on the item.
+#[command]
+async fn hltb_lookup(
+    session: String,
+    title: String,
+    year: Option<i32>,
+    item_id: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<hltb::HltbGame>, String> {
+    let username = app.state::<session::Sessions>().user(&session)?;
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Missing title".to_string());
+    }
+    let games = hltb::search(&state.proxy_client, title, database::now_ms()).await?;
+    let Some(game) = games.iter().find(|g| year.is_some() && g.year == year).or(games.first()).cloned() else {
+        return Ok(None);
+    };
+    if let Some(item_id) = item_id {
+        let db = app.state::<Arc<Database>>();
+        let owner = db.item_owner(&username, &item_id, true).await?;
+        db.set_play_times(&owner, &item_id, game.times.clone()).await?;
+    }
+    Ok(Some(game))
+}
+
+#[command]
+async fn bangumi_details(id: u64, token: Option<String>, state: State<'_, AppState>) -> Result<String, String> {
+    let url = format!("https://api.bgm.tv/v0/subjects/{}", id);
+    let mut builder = state.proxy_client.get(&url)
+        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
+        .header("Accept", "application/json");
+
+    if let Some(tok) = token {
+        if !tok.is_empty() {
+            builder = builder.header("Authorization", format!("Bearer {}", tok));
+        }
+    }
+
+    let resp = builder.send().await.map_err(|e| e.to_string())?;
+    
+    if !resp.status().is_success() {
+        return Err(format!("Bangumi Error: {}", resp.status()));
+    }
+    
+    let body = resp.text().await.map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+
+/// Validates and stores a new user; returns its recovery codes.
+async fn create_account(db: &Database, username: &str, password: &str) -> Result<Vec<String>, String> {
+    if username.len() < 3 { return Err("Username too short".to_string()); }
+    // Reserved for profiles that aren't accounts, like the local one
+    if username.starts_with('@') { return Err("Username cannot start with @".to_string()); }
+    if password.len() < 6 { return Err("Password too short".to_string()); }
+    if db.find_user(username).await.is_some() {
+        return Err("USER_EXISTS".to_string());
+    }
+
+    let hash = hash_password(password)?;
+
+    let created_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs() as i64;
+
+    let (recovery_codes, recovery_hashes) = auth::new_recovery_codes();
+    let record = UserRecord { username: username.to_string(), password_hash: hash, created_at, last_modified: Some(database::now_ms()), recovery_codes: recovery_hashes };
+    db.add_user(record).await?;
+    Ok(recovery_codes)
+}
+
+#[command]
+async fn register_user(username: String, password: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<session::SessionInfo, String> {
+    let u = username.trim();
+    let recovery_codes = create_account(&db, u, &password).await?;
+    Ok(session::SessionInfo { recovery_codes, ..sessions.create(u) })
+}
+
+/// Signs in to the built-in local profile, which needs no account. Its data is
+/// kept under `database::LOCAL_PROFILE` until `promote_to_account`.
+#[command]
+async fn start_local_session(sessions: State<'_, session::Sessions>) -> Result<session::SessionInfo, String> {
+    Ok(sessions.create(database::LOCAL_PROFILE))
+}
+
+/// Registers a new account and moves everything from the local profile into it.
+#[command]
+async fn promote_to_account(
+    session: String,
+    username: String,
+    password: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<session::SessionInfo, String> {
+    if sessions.user(&session)? != database::LOCAL_PROFILE {
+        return Err("Only the local profile can be turned into an account".to_string());
+    }
+    let u = username.trim();
+    let recovery_codes = create_account(&db, u, &password).await?;
+    db.move_user_data(database::LOCAL_PROFILE, u).await?;
+    sessions.end_all(database::LOCAL_PROFILE);
+    Ok(session::SessionInfo { recovery_codes, ..sessions.create(u) })
+}
+
+/// Logins are throttled per username after repeated failures (see `auth`).
+#[command]
+async fn login_user(
+    username: String,
+    password: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    throttle: State<'_, auth::LoginThrottle>,
+) -> Result<session::SessionInfo, String> {
+    use auth::{AuthEvent, AuthOutcome};
+    let u = username.trim();
+    if let Err(e) = throttle.check(u) {
+        db.record_auth_event(AuthEvent::login(Some(u), AuthOutcome::Throttled, None)).await;
+        return Err(e);
+    }
+    let record = db.find_user(u).await;
+    let valid = match &record {
+        Some(r) => verify_password(&password, &r.password_hash)?,
+        None => false,
+    };
+    let known = record.is_some();
+    let Some(record) = record.filter(|_| valid) else {
+        let outcome = if throttle.failed(u) { AuthOutcome::LockedOut } else { AuthOutcome::Failure };
+        // Unknown names are logged without a username, so every account sees them
+        let event = if known {
+            AuthEvent::login(Some(u), outcome, None)
+        } else {
+            AuthEvent::login(None, outcome, Some(format!("Unknown user \"{}\"", u)))
+        };
+        db.record_auth_event(event).await;
+        return Err("INVALID_CREDENTIALS".to_string());
+    };
+    throttle.succeeded(u);
+    db.record_auth_event(AuthEvent::login(Some(u), AuthOutcome::Success, None)).await;
+    // Hashes made with older Argon2 settings are upgraded while we have the password
+    if needs_rehash(&record.password_hash) {
+        if let Ok(hash) = hash_password(&password) {
+            db.set_password_hash(u, hash, false).await?;
+        }
+    }
+    Ok(sessions.create(u))
+}
+
+/// Verifies `old_password` and replaces it; other sessions of the user are ended.
+#[command]
+async fn change_password(
+    session: String,
+    old_password: String,
+    new_password: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    let record = db.find_user(&username).await.ok_or_else(|| "User not found".to_string())?;
+    if !verify_password(&old_password, &record.password_hash)? {
+        return Err("INVALID_CREDENTIALS".to_string());
+    }
+    if new_password.len() < 6 {
+        return Err("Password too short".to_string());
+    }
+    db.set_password_hash(&username, hash_password(&new_password)?, true).await?;
+    sessions.end_others(&username, &session);
+    Ok(())
+}
+
+/// Deletes the signed-in account with everything stored for it, including the
+/// copies in local backups and covers no other account uses, and signs it out.
+#[command]
+async fn delete_account(
+    session: String,
+    password: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    cache: State<'_, images::ImageCache>,
+) -> Result<database::AccountErasure, String> {
+    let username = sessions.user(&session)?;
+    let record = db.find_user(&username).await.ok_or_else(|| "User not found".to_string())?;
+    if !verify_password(&password, &record.password_hash)? {
+        return Err("INVALID_CREDENTIALS".to_string());
+    }
+    let mut erased = db.delete_user(&username).await?;
+    erased.images.retain(|file| cache.remove(file));
+    sessions.end_all(&username);
+    Ok(erased)
+}
+
+/// Folds the `source` account into the signed-in one and deletes it. Needs the
+/// source's password, which is throttled like a login.
+#[command]
+async fn merge_users(
+    session: String,
+    source: String,
+    source_password: String,
+    strategy: Option<database::AccountMergeStrategy>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    throttle: State<'_, auth::LoginThrottle>,
+) -> Result<database::AccountMerge, String> {
+    let target = sessions.user(&session)?;
+    let source = source.trim();
+    if source == target {
+        return Err("Cannot merge an account into itself".to_string());
+    }
+    throttle.check(source)?;
+    let record = db.find_user(source).await.ok_or_else(|| "INVALID_CREDENTIALS".to_string())?;
+    if !verify_password(&source_password, &record.password_hash)? {
+        throttle.failed(source);
+        return Err("INVALID_CREDENTIALS".to_string());
+    }
+    throttle.succeeded(source);
+    let merged = db.merge_users(source, &target, strategy.unwrap_or_default()).await?;
+    sessions.end_all(source);
+    Ok(merged)
+}
+
+/// Sets a new password with one of the user's recovery codes, which is used up,
+/// and signs in. Attempts are throttled like logins.
+#[command]
+async fn reset_password_with_recovery_code(
+    username: String,
+    code: String,
+    new_password: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+    throttle: State<'_, auth::LoginThrottle>,
+) -> Result<session::SessionInfo, String> {
+    use auth::{AuthEvent, AuthOutcome};
+    let u = username.trim();
+    let detail = || Some("Recovery code".to_string());
+    if let Err(e) = throttle.check(u) {
+        db.record_auth_event(AuthEvent::login(Some(u), AuthOutcome::Throttled, detail())).await;
+        return Err(e);
+    }
+    if new_password.len() < 6 {
+        return Err("Password too short".to_string());
+    }
+    let hash = hash_password(&new_password)?;
+    if let Err(e) = db.redeem_recovery_code(u, &auth::recovery_code_hash(&code), hash).await {
+        let outcome = if throttle.failed(u) { AuthOutcome::LockedOut } else { AuthOutcome::Failure };
+        let known = db.find_user(u).await.is_some();
+        db.record_auth_event(AuthEvent::login(Some(u).filter(|_| known), outcome, detail())).await;
+        return Err(e);
+    }
+    throttle.succeeded(u);
+    db.record_auth_event(AuthEvent::login(Some(u), AuthOutcome::Success, detail())).await;
+    sessions.end_all(u);
+    Ok(sessions.create(u))
+}
+
+/// Replaces the user's recovery codes with new ones, returned once.
+#[command]
+async fn regenerate_recovery_codes(session: String, password: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<String>, String> {
+    let username = sessions.user(&session)?;
+    let record = db.find_user(&username).await.ok_or_else(|| "User not found".to_string())?;
+    if !verify_password(&password, &record.password_hash)? {
+        return Err("INVALID_CREDENTIALS".to_string());
+    }
+    let (codes, hashes) = auth::new_recovery_codes();
+    db.set_recovery_codes(&username, hashes).await?;
+    Ok(codes)
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DatabaseEncryption {
+    enabled: bool,
+    /// The new key (base64), only returned when encryption is turned on or the key
+    /// is rotated; keep it somewhere safe in case the keychain entry is lost.
+    key: Option<String>,
+    /// Backup copies that could not be rewritten.
+    failed_copies: usize,
+}
+
+#[command]
+async fn get_database_encryption(db: State<'_, Arc<Database>>) -> Result<bool, String> {
+    Ok(db.is_encrypted())
+}
+
+/// Turns encryption of collection.json and its local backups on or off, or with
+/// `rotate` re-encrypts them under a new key.
+#[command]
+async fn set_database_encryption(
+    session: String,
+    password: String,
+    enabled: bool,
+    rotate: Option<bool>,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<DatabaseEncryption, String> {
+    let username = sessions.user(&session)?;
+    let record = db.find_user(&username).await.ok_or_else(|| "User not found".to_string())?;
+    if !verify_password(&password, &record.password_hash)? {
+        return Err("INVALID_CREDENTIALS".to_string());
+    }
+    let rotate = enabled && db.is_encrypted() && rotate.unwrap_or(false);
+    if enabled == db.is_encrypted() && !rotate {
+        return Ok(DatabaseEncryption { enabled, key: None, failed_copies: 0 });
+    }
+    if !enabled {
+        let failed_copies = db.set_encryption(None).await?;
+        // Copies still encrypted need the key to stay readable
+        if failed_copies == 0 {
+            secrets::delete(at_rest::KEY_SECRET)?;
+        }
+        return Ok(DatabaseEncryption { enabled, key: None, failed_copies });
+    }
+    let previous = secrets::get(at_rest::KEY_SECRET)?.filter(|_| rotate);
+    let key = at_rest::new_key();
+    // Stored first: a file encrypted with a key we failed to keep would be lost
+    secrets::set(at_rest::KEY_SECRET, &at_rest::key_to_base64(&key))?;
+    match db.set_encryption(Some(key)).await {
+        Ok(failed_copies) => Ok(DatabaseEncryption { enabled, key: Some(at_rest::key_to_base64(&key)), failed_copies }),
+        Err(e) => {
+            let _ = match previous {
+                Some(old) => secrets::set(at_rest::KEY_SECRET, &old),
+                None => secrets::delete(at_rest::KEY_SECRET),
+            };
+            Err(e)
+        }
+    }
+}
+
+/// Recent logins and rejected LAN requests, newest first.
+#[command]
+async fn get_auth_log(session: String, limit: Option<usize>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<auth::AuthEvent>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_auth_log(&username, limit.unwrap_or(100)).await)
+}
+
+#[command]
+async fn logout_user(session: String, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    sessions.end(&session);
+    Ok(())
+}
+
+/// Who a stored session belongs to; fails with `SESSION_EXPIRED` once it is no longer valid.
+#[command]
+async fn check_session(session: String, sessions: State<'_, session::Sessions>) -> Result<UserPublic, String> {
+    Ok(UserPublic { username: sessions.user(&session)? })
+}
+
+// --- Sync Commands ---
+
+#[command]
+async fn start_sync_server(app: AppHandle, sync: State<'_, sync::SyncService>, db: State<'_, Arc<Database>>) -> Result<(), String> {
+    let port = db.get_settings().await.sync_port;
+    sync.start(db.inner().clone(), app.clone(), port).await?;
+    tray::set_sync_checked(&app, true);
+    Ok(())
+}
+
+/// Starts LAN sync (optionally on a new port) and remembers it for the next launch.
+#[command]
+async fn start_sync(port: Option<u16>, app: AppHandle, sync: State<'_, sync::SyncService>, db: State<'_, Arc<Database>>) -> Result<sync::SyncStatus, String> {
+    let mut settings = db.get_settings().await;
+    if let Some(p) = port {
+        if p < 1024 {
+            return Err("Port must be between 1024 and 65535".to_string());
+        }
+        // Moving to another port needs a fresh listener
+        if sync.status().await.port.map(|current| current != p).unwrap_or(false) {
+            sync.stop().await;
+        }
+        settings.sync_port = p;
+    }
+    let status = sync.start(db.inner().clone(), app.clone(), settings.sync_port).await?;
+    tray::set_sync_checked(&app, true);
+    settings.sync_enabled = true;
+    db.update_settings(settings).await?;
+    Ok(status)
+}
+
+#[command]
+async fn stop_sync(app: AppHandle, sync: State<'_, sync::SyncService>, db: State<'_, Arc<Database>>) -> Result<sync::SyncStatus, String> {
+    sync.stop().await;
+    tray::set_sync_checked(&app, false);
+    let mut settings = db.get_settings().await;
+    settings.sync_enabled = false;
+    db.update_settings(settings).await?;
+    Ok(sync.status().await)
+}
+
+#[command]
+async fn sync_status(sync: State<'_, sync::SyncService>) -> Result<sync::SyncStatus, String> {
+    Ok(sync.status().await)
+}
+
+/// Issues a new API token (browser extension, scripts) for `/api/*`, replacing any previous one.
+#[command]
+async fn create_extension_token(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<String, String> {
+    let username = sessions.user(&session)?;
+    if db.find_user(&username).await.is_none() {
+        return Err("User not found".to_string());
+    }
+    let token = sync::new_token();
+    db.set_extension_token(&username, Some(sync::token_hash(&token))).await;
+    Ok(token)
+}
+
+#[command]
+async fn revoke_extension_token(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_extension_token(&username, None).await;
+    Ok(())
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct WebAccess {
+    token: String,
+    /// Link to open with the token; None while the sync server is stopped.
+    url: Option<String>,
+}
+
+/// Issues a token for the read-only web view at `/web`, replacing any previous one.
+#[command]
+async fn create_web_token(session: String, db: State<'_, Arc<Database>>, sync: State<'_, sync::SyncService>, sessions: State<'_, session::Sessions>) -> Result<WebAccess, String> {
+    let username = sessions.user(&session)?;
+    if db.find_user(&username).await.is_none() {
+        return Err("User not found".to_string());
+    }
+    let token = sync::new_token();
+    db.set_web_token(&username, Some(sync::token_hash(&token))).await;
+    let url = sync.status().await.address.map(|addr| format!("http://{}/web?token={}", addr, token));
+    Ok(WebAccess { token, url })
+}
+
+#[command]
+async fn revoke_web_token(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_web_token(&username, None).await;
+    Ok(())
+}
+
+/// Issues a token for the `/feed.xml` Atom feed, replacing any previous one.
+/// The returned link is what friends paste into their feed reader.
+#[command]
+async fn create_feed_token(session: String, db: State<'_, Arc<Database>>, sync: State<'_, sync::SyncService>, sessions: State<'_, session::Sessions>) -> Result<WebAccess, String> {
+    let username = sessions.user(&session)?;
+    if db.find_user(&username).await.is_none() {
+        return Err("User not found".to_string());
+    }
+    let token = sync::new_token();
+    db.set_feed_token(&username, Some(sync::token_hash(&token))).await;
+    let url = sync.status().await.address.map(|addr| format!("http://{}/feed.xml?token={}", addr, token));
+    Ok(WebAccess { token, url })
+}
+
+#[command]
+async fn revoke_feed_token(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.set_feed_token(&username, None).await;
+    Ok(())
+}
+
+#[command]
+async fn list_webhooks(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<webhooks::Webhook>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_webhooks(&username).await)
+}
+
+#[command]
+async fn save_webhook(session: String, webhook: webhooks::Webhook, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<webhooks::Webhook, String> {
+    let username = sessions.user(&session)?;
+    db.save_webhook(&username, webhook).await
+}
+
+#[command]
+async fn delete_webhook(session: String, id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.delete_webhook(&username, &id).await
+}
+
+/// Sends a `test` event to the webhook right away and reports how it went.
+#[command]
+async fn test_webhook(session: String, id: String, db: State<'_, Arc<Database>>, state: State<'_, AppState>, sessions: State<'_, session::Sessions>) -> Result<webhooks::Delivery, String> {
+    let username = sessions.user(&session)?;
+    let hook = db.get_webhooks(&username).await.into_iter().find(|h| h.id == id).ok_or_else(|| "Webhook not found".to_string())?;
+    let delivery = webhooks::deliver(&state.direct_client, &hook, webhooks::WebhookEvent::Test, &username, None).await;
+    db.record_webhook_delivery(&username, &id, delivery.clone()).await;
+    Ok(delivery)
+}
+
+#[command]
+fn list_peers(sync: State<'_, sync::SyncService>) -> Result<Vec<sync::PeerInfo>, String> {
+    Ok(sync.get_known_peers())
+}
+
+/// Syncs `username` with one peer; `direction` defaults to a two-way merge. Fails with
+/// `NOT_PAIRED` until the devices have been paired with `pair_with_peer`.
+#[command]
+async fn sync_with_peer(
+    session: String,
+    peer_ip: String,
+    peer_port: u16,
+    direction: Option<sync::SyncDirection>,
+    app: AppHandle,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<sync::PeerSyncSummary, String> {
+    let username = sessions.user(&session)?;
+    sync::sync_with_peer(&app, &db, &username, &peer_ip, peer_port, direction.unwrap_or_default()).await
+}
+
+/// Recorded sync sessions, newest first; `peer` narrows it to one device.
+#[command]
+async fn get_sync_history(session: String, peer: Option<String>, limit: Option<usize>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<sync::SyncSession>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_sync_history(&username, peer.as_deref(), limit.unwrap_or(50)).await)
+}
+
+/// Shows a PIN that another device enters (via `pair_with_peer`) to pair with `username`.
+#[command]
+async fn start_pairing(session: String, sync: State<'_, sync::SyncService>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<sync::PairingCode, String> {
+    let username = sessions.user(&session)?;
+    if db.find_user(&username).await.is_none() {
+        return Err("User not found".to_string());
+    }
+    sync.start_pairing(&username).await
+}
+
+#[command]
+async fn cancel_pairing(sync: State<'_, sync::SyncService>) -> Result<(), String> {
+    sync.cancel_pairing().await;
+    Ok(())
+}
+
+#[command]
+async fn pair_with_peer(
+    session: String,
+    peer_ip: String,
+    peer_port: u16,
+    pin: String,
+    db: State<'_, Arc<Database>>,
+    sessions: State<'_, session::Sessions>,
+) -> Result<TrustedDevice, String> {
+    let username = sessions.user(&session)?;
+    sync::pair_with_peer(&db, &username, &peer_ip, peer_port, &pin, None).await
+}
+
+/// A QR code (and its text) that another device scans to pair with `username`.
+#[command]
+async fn get_pairing_qr(session: String, sync: State<'_, sync::SyncService>, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<sync::PairingQr, String> {
+    let username = sessions.user(&session)?;
+    if db.find_user(&username).await.is_none() {
+        return Err("User not found".to_string());
+    }
+    sync.pairing_qr(&db, &username).await
+}
+
+/// Pairs using the text of another device's pairing QR code.
+#[command]
+async fn pair_with_qr(session: String, payload: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<TrustedDevice, String> {
+    let username = sessions.user(&session)?;
+    let uri: sync::PairingUri = payload.parse()?;
+    sync::pair_with_peer(&db, &username, &uri.host, uri.port, &uri.pin, Some(&uri.key)).await
+}
+
+#[command]
+async fn list_trusted_devices(session: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<Vec<TrustedDevice>, String> {
+    let username = sessions.user(&session)?;
+    Ok(db.get_trusted_devices(&username).await)
+}
+
+#[command]
+async fn revoke_trusted_device(session: String, device_id: String, db: State<'_, Arc<Database>>, sessions: State<'_, session::Sessions>) -> Result<(), String> {
+    let username = sessions.user(&session)?;
+    db.revoke_trusted_device(&username, &device_id).await
+}
+
+// Password hashing (Argon2)
+use argon2::{Argon2, PasswordHasher};
+use argon2::password_hash::{PasswordHash, PasswordVerifier, SaltString};
+use rand_core::OsRng;
+
+fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn verify_password(password: &str, hash: &str) -> Result<bool, String> {
+    let parsed = PasswordHash::new(hash).map_err(|e| e.to_string())?;
+    Ok(Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+}
+
+/// True when `hash` wasn't made with the algorithm, version and cost `Argon2::default()` uses now.
+fn needs_rehash(hash: &str) -> bool {
+    let Ok(parsed) = PasswordHash::new(hash) else {
+        return false;
+    };
+    let current = argon2::Params::default();
+    let same_params = argon2::Params::try_from(&parsed)
+        .map(|p| p.m_cost() == current.m_cost() && p.t_cost() == current.t_cost() && p.p_cost() == current.p_cost())
+        .unwrap_or(false);
+    parsed.algorithm != argon2::Algorithm::default().ident() || parsed.version != Some(argon2::Version::default().into()) || !same_params
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .setup(|app| {
+            let db = Arc::new(Database::new(app.handle()));
+            Database::start_flusher(db.clone());
+            app.manage(db.clone());
+            let covers_dir = app.path().app_data_dir().expect("Failed to get app data dir").join("covers");
+            app.manage(images::ImageCache::new(covers_dir));
+            let attachments_dir = app.path().app_data_dir().expect("Failed to get app data dir").join("attachments");
+            app.manage(notes::Attachments(images::ImageCache::new(attachments_dir)));
+            
+            let sync_service = sync::SyncService::new();
+            app.manage(sync_service.clone());
+            tray::build(app.handle())?;
+            {
+                let (db, app) = (db.clone(), app.handle().clone());
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = quick_add::register(&app, &db.get_settings().await.quick_add_shortcut) {
+                        eprintln!("Failed to register quick-add shortcut: {}", e);
+                    }
+                });
+            }
+            {
+                let (db, app) = (db.clone(), app.handle().clone());
+                tauri::async_runtime::spawn(async move {
+                    let settings = db.get_settings().await;
+                    if settings.sync_enabled {
+                        match sync_service.start(db, app.clone(), settings.sync_port).await {
+                            Ok(_) => tray::set_sync_checked(&app, true),
+                            Err(e) => eprintln!("Failed to start sync server: {}", e),
+                        }
+                    }
+                });
+            }
+
+            // 1. Proxy Client (System Proxy Enabled) - For Google, Serper, etc.
+            let proxy_client = Client::builder()
+                .tcp_nodelay(true)
+                .user_agent("MediaTracker/1.0")
+                .local_address(std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0)))
+                .connect_timeout(std::time::Duration::from_secs(10))
+                .timeout(std::time::Duration::from_secs(120))
                 .build()
                 .unwrap_or_else(|_| Client::new());
 
@@ -1340,6 +3729,16 @@ pub fn run() {
                 .unwrap_or_else(|_| Client::new());
             
             app.manage(AppState { proxy_client, direct_client, search_cache: RwLock::new(HashMap::new()) });
+            app.manage(scheduler::Scheduler::default());
+            app.manage(metadata::RefreshControl::default());
+            app.manage(streaming::AvailabilityCache::default());
+            app.manage(anilist::SeasonCache::default());
+            app.manage(session::Sessions::default());
+            app.manage(auth::LoginThrottle::default());
+            scheduler::start(app.handle().clone(), db.clone());
+            clipboard::start(app.handle().clone(), db.clone());
+            webhooks::start(app.handle().clone(), db.clone());
+            jellyfin::start(app.handle().clone(), db.clone());
             
             #[cfg(debug_assertions)]
             if let Some(w) = app.get_webview_window("main") {
@@ -1348,29 +3747,239 @@ pub fn run() {
 
             Ok(())
         })
+        .register_asynchronous_uri_scheme_protocol(images::PROTOCOL, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            let path = request.uri().path().to_string();
+            let query = request.uri().query().map(|q| q.to_string());
+            tauri::async_runtime::spawn_blocking(move || {
+                responder.respond(app.state::<images::ImageCache>().respond(&path, query.as_deref()));
+            });
+        })
+        .register_asynchronous_uri_scheme_protocol(notes::PROTOCOL, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            let path = request.uri().path().to_string();
+            let query = request.uri().query().map(|q| q.to_string());
+            tauri::async_runtime::spawn_blocking(move || {
+                responder.respond(app.state::<notes::Attachments>().0.respond(&path, query.as_deref()));
+            });
+        })
+        .on_window_event(|window, event| {
+            if window.label() != "main" {
+                return;
+            }
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                // Keep running in the tray; the tray menu brings the window back or quits
+                if window.state::<Arc<Database>>().close_to_tray() {
+                    api.prevent_close();
+                    let _ = window.hide();
+                } else if let Some(overlay) = window.get_webview_window(quick_add::LABEL) {
+                    // A hidden overlay would otherwise keep the app running
+                    let _ = overlay.close();
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             web_search, 
             bangumi_search,
             bangumi_details,
+            mangadex_search,
+            mangadex_details,
+            novelupdates_search,
+            novelupdates_details,
+            ao3_work,
+            podcast_search,
+            podcast_details,
+            follow_podcast,
+            audiobook_search,
+            audiobook_details,
+            bgg_search,
+            bgg_details,
+            hltb_lookup,
             ai_chat,
+            extract_item_from_text,
             wiki_pageimages,
+            wiki_extract,
+            wikidata_lookup,
+            fetch_localized_titles,
+            find_trailer,
+            streaming_availability,
+            find_cover_candidates,
             douban_cover,
             fetch_og_image,
+            scrape_url,
             test_proxy,
             test_search_provider,
+            set_secret,
+            get_secret,
+            delete_secret,
             test_omdb,
+            get_recovery_report,
+            flush_database,
             get_collection,
+            get_content_filter,
+            set_content_filter,
             save_item,
+            add_completion,
+            mark_next_episode_watched,
+            mark_chapters_read,
+            follow_webtoon,
+            add_quote,
+            update_quote,
+            delete_quote,
+            add_note,
+            update_note,
+            delete_note,
+            attach_note_image,
+            collect_note_attachments,
+            bulk_update_items,
             remove_item,
+            get_trash,
+            restore_item,
+            empty_trash,
+            find_duplicates,
+            merge_items,
+            get_item_history,
+            revert_item,
+            get_item_timeline,
+            get_activity_feed,
+            get_statistics,
+            get_activity_heatmap,
+            generate_year_review,
+            start_session,
+            stop_session,
+            get_continue_watching,
+            snooze_item,
+            get_time_sessions,
+            undo_last_operation,
+            redo_last_operation,
+            get_recent_operations,
+            list_collections,
+            create_collection,
+            update_collection,
+            delete_collection,
+            share_collection,
+            unshare_collection,
+            list_collection_shares,
+            get_statuses,
+            set_statuses,
+            pick_random,
+            get_view_order,
+            set_item_order,
+            pin_item,
+            list_goals,
+            create_goal,
+            update_goal,
+            delete_goal,
+            get_custom_field_schema,
+            set_custom_field_schema,
+            list_people,
+            fetch_image,
+            cache_poster,
+            cache_all_posters,
+            refresh_metadata,
+            cancel_metadata_refresh,
+            list_jobs,
+            run_job_now,
+            set_job_schedule,
+            list_cloud_backups,
+            check_updates_now,
+            set_item_feed,
+            get_item_updates,
+            mark_feed_entries_read,
+            poll_feeds_now,
+            get_relations,
+            get_franchise,
+            set_relation,
+            remove_relation,
+            auto_link_relations,
+            get_upcoming,
+            get_person,
+            fetch_person_works,
+            follow_person,
+            list_creator_suggestions,
+            dismiss_creator_suggestion,
+            list_smart_lists,
+            create_smart_list,
+            update_smart_list,
+            delete_smart_list,
+            evaluate_smart_list,
+            evaluate_smart_query,
+            get_settings,
+            update_settings,
+            preview_import,
             import_collection,
+            import_lastfm,
+            link_plex,
+            unlink_plex,
+            link_jellyfin,
+            unlink_jellyfin,
+            import_nfo_folder,
+            export_nfo,
+            scan_media_folder,
+            import_viewing_history,
+            import_bilibili,
+            seasonal_anime,
+            add_seasonal_anime,
+            quick_add_search,
+            quick_add_save,
+            close_quick_add,
+            spotify_authorize,
+            spotify_connected,
+            spotify_disconnect,
+            import_spotify,
             reorder_collection,
             export_collection,
+            export_calendar,
+            export_user_archive,
+            import_user_archive,
             register_user,
+            start_local_session,
+            promote_to_account,
+            merge_users,
             login_user,
+            logout_user,
+            check_session,
+            change_password,
+            delete_account,
+            get_auth_log,
+            get_database_encryption,
+            set_database_encryption,
+            reset_password_with_recovery_code,
+            regenerate_recovery_codes,
             start_sync_server,
-            get_peers,
+            start_sync,
+            stop_sync,
+            sync_status,
+            create_extension_token,
+            revoke_extension_token,
+            create_web_token,
+            revoke_web_token,
+            create_feed_token,
+            revoke_feed_token,
+            list_webhooks,
+            save_webhook,
+            delete_webhook,
+            test_webhook,
+            list_peers,
+            start_pairing,
+            cancel_pairing,
+            pair_with_peer,
+            get_pairing_qr,
+            get_sync_history,
+            pair_with_qr,
+            list_trusted_devices,
+            revoke_trusted_device,
             sync_with_peer
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Persist any debounced writes before the process goes away
+            if let tauri::RunEvent::Exit = event {
+                let db = app.state::<Arc<Database>>().inner().clone();
+                if let Err(e) = tauri::async_runtime::block_on(db.flush()) {
+                    eprintln!("Failed to flush database on exit: {}", e);
+                }
+            }
+        });
 }
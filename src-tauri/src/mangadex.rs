@@ -0,0 +1,142 @@
+// MangaDex (api.mangadex.org): search, details, and the newest chapter for the
+// update checker. MangaDex allows about five requests a second per client, so
+// every request here waits its turn behind the previous one, and a 429 is
+// reported with the wait the server asked for rather than retried in a loop.
+
+use std::time::Duration;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use crate::rate_limit::RateLimiter;
+
+const API: &str = "https://api.mangadex.org";
+const COVERS: &str = "https://uploads.mangadex.org/covers";
+/// All ratings; MangaDex hides erotica and pornographic titles unless asked.
+const CONTENT_RATINGS: &str = "contentRating[]=safe&contentRating[]=suggestive&contentRating[]=erotica&contentRating[]=pornographic";
+
+static LIMITER: RateLimiter = RateLimiter::new(Duration::from_millis(250));
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Manga {
+    pub id: String,
+    pub title: String,
+    pub alt_titles: Vec<String>,
+    pub description: Option<String>,
+    /// "ongoing", "completed", "hiatus" or "cancelled".
+    pub status: Option<String>,
+    pub year: Option<i32>,
+    pub authors: Vec<String>,
+    pub cover_url: Option<String>,
+    /// "safe", "suggestive", "erotica" or "pornographic".
+    pub content_rating: Option<String>,
+    pub last_chapter: Option<String>,
+    pub url: String,
+}
+
+async fn get(client: &Client, path: &str) -> Result<Value, String> {
+    LIMITER.wait().await;
+    let fut = client
+        .get(format!("{}{}", API, path))
+        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
+        .send();
+    let resp = tokio::time::timeout(Duration::from_secs(12), fut)
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if resp.status().as_u16() == 429 {
+        let retry = resp.headers().get("X-RateLimit-Retry-After").and_then(|v| v.to_str().ok()).unwrap_or("?").to_string();
+        return Err(format!("MangaDex rate limit reached; retry after {}", retry));
+    }
+    if !resp.status().is_success() {
+        return Err(format!("MangaDex Error: {}", resp.status()));
+    }
+    resp.json::<Value>().await.map_err(|e| e.to_string())
+}
+
+/// A localized string map, preferring English, then the original, then anything.
+fn localized(v: &Value, original: Option<&str>) -> Option<String> {
+    let map = v.as_object()?;
+    ["en", original.unwrap_or("ja"), "ja-ro"]
+        .iter()
+        .find_map(|l| map.get(*l).and_then(Value::as_str))
+        .or_else(|| map.values().find_map(Value::as_str))
+        .map(str::to_string)
+}
+
+fn parse_manga(v: &Value) -> Option<Manga> {
+    let id = v["id"].as_str()?.to_string();
+    let a = &v["attributes"];
+    let original = a["originalLanguage"].as_str();
+    let related = v["relationships"].as_array().map(|r| r.as_slice()).unwrap_or(&[]);
+    let mut authors: Vec<String> = Vec::new();
+    for r in related.iter().filter(|r| matches!(r["type"].as_str(), Some("author" | "artist"))) {
+        if let Some(name) = r["attributes"]["name"].as_str().filter(|n| !authors.iter().any(|a| a == n)) {
+            authors.push(name.to_string());
+        }
+    }
+    let cover_url = related
+        .iter()
+        .find(|r| r["type"].as_str() == Some("cover_art"))
+        .and_then(|r| r["attributes"]["fileName"].as_str())
+        .map(|f| format!("{}/{}/{}.512.jpg", COVERS, id, f));
+    Some(Manga {
+        title: localized(&a["title"], original).unwrap_or_default(),
+        alt_titles: a["altTitles"].as_array().into_iter().flatten().filter_map(|t| localized(t, original)).collect(),
+        description: localized(&a["description"], original).filter(|d| !d.trim().is_empty()),
+        status: a["status"].as_str().map(str::to_string),
+        year: a["year"].as_i64().map(|y| y as i32),
+        authors,
+        cover_url,
+        content_rating: a["contentRating"].as_str().map(str::to_string),
+        last_chapter: a["lastChapter"].as_str().filter(|c| !c.is_empty()).map(str::to_string),
+        url: format!("https://mangadex.org/title/{}", id),
+        id,
+    })
+}
+
+/// `&{param}[]=` for each language; languages are short codes like "en" or "pt-br".
+fn language_params(param: &str, languages: &[String]) -> String {
+    languages
+        .iter()
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty() && l.len() <= 8 && l.chars().all(|c| c.is_ascii_alphabetic() || c == '-'))
+        .map(|l| format!("&{}[]={}", param, l))
+        .collect()
+}
+
+pub async fn search(client: &Client, query: &str, languages: &[String]) -> Result<Vec<Manga>, String> {
+    let path = format!(
+        "/manga?title={}&limit=20&includes[]=cover_art&includes[]=author&includes[]=artist&{}{}",
+        urlencoding::encode(query),
+        CONTENT_RATINGS,
+        language_params("availableTranslatedLanguage", languages)
+    );
+    let v = get(client, &path).await?;
+    Ok(v["data"].as_array().into_iter().flatten().filter_map(parse_manga).collect())
+}
+
+pub async fn details(client: &Client, id: &str) -> Result<Manga, String> {
+    let path = format!("/manga/{}?includes[]=cover_art&includes[]=author&includes[]=artist", urlencoding::encode(id));
+    let v = get(client, &path).await?;
+    parse_manga(&v["data"]).ok_or_else(|| "Manga not found".to_string())
+}
+
+/// Newest chapter in one of `languages`, as "Vol. 3 Ch. 21" / "Ch. 21".
+pub async fn latest_chapter(client: &Client, id: &str, languages: &[String]) -> Result<Option<String>, String> {
+    let path = format!(
+        "/manga/{}/feed?limit=1&order[readableAt]=desc&order[chapter]=desc&{}{}",
+        urlencoding::encode(id),
+        CONTENT_RATINGS,
+        language_params("translatedLanguage", languages)
+    );
+    let v = get(client, &path).await?;
+    let a = &v["data"][0]["attributes"];
+    let Some(chapter) = a["chapter"].as_str().filter(|c| !c.is_empty()) else {
+        return Ok(None);
+    };
+    Ok(Some(match a["volume"].as_str().filter(|v| !v.is_empty()) {
+        Some(volume) => format!("Vol. {} Ch. {}", volume, chapter),
+        None => format!("Ch. {}", chapter),
+    }))
+}
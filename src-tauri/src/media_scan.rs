@@ -0,0 +1,344 @@
+// Local media folder scanner. Video files are found under a folder and their
+// names parsed ("The.Matrix.1999.1080p.BluRay.mkv", "Dark.S01E02.WEB.mkv",
+// "[Group] Show - 05 [1080p].mkv") into a title, year and episode; episodes of one
+// show are grouped. Each group is looked up on TMDB (or Bangumi without a TMDB
+// key) and returned as a draft item with a confidence score. Nothing is saved:
+// the user picks which proposals to add.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use crate::dedupe::{extract_year, normalize_title, title_similarity};
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+
+const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "m4v", "avi", "mov", "wmv", "webm", "ts", "m2ts", "mpg", "mpeg", "flv"];
+/// Release tags that end the title part of a file name.
+const RELEASE_TAGS: &[&str] = &[
+    "480p", "576p", "720p", "1080p", "1080i", "2160p", "4k", "uhd", "bluray", "bdrip", "brrip", "bdremux", "remux", "webrip", "webdl", "web",
+    "hdtv", "dvdrip", "dvd", "hdrip", "x264", "x265", "h264", "h265", "hevc", "avc", "xvid", "hdr", "hdr10", "dv", "aac", "ac3", "dts", "ddp",
+    "atmos", "proper", "repack", "extended", "unrated", "remastered", "imax", "10bit", "multi", "subbed", "dubbed",
+];
+/// Folders and files that are not the feature itself.
+const EXTRAS: &[&str] = &["sample", "samples", "trailer", "trailers", "extras", "featurettes", "behind the scenes", "deleted scenes"];
+/// Media folders can be deep (Shows/Name/Season 1/...) but not this deep.
+const MAX_DEPTH: usize = 8;
+/// Provider lookups per scan; the rest are returned unmatched.
+const MAX_LOOKUPS: usize = 200;
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedName {
+    pub title: String,
+    pub year: Option<i32>,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Proposal {
+    /// Draft to add; filled from the provider when matched.
+    pub item: MediaItem,
+    pub files: Vec<String>,
+    /// Distinct episodes found, for TV series.
+    pub episodes: usize,
+    /// "tmdb" or "bangumi" when matched.
+    pub provider: Option<String>,
+    /// 0 to 1; how sure the match is, 0 when unmatched.
+    pub confidence: f64,
+    /// Item already in the collection that this looks like.
+    pub existing_item_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaScan {
+    pub files: usize,
+    pub proposals: Vec<Proposal>,
+}
+
+/// Files under `root` with one of `extensions` (lowercase), skipping hidden folders.
+pub(crate) fn files_under(root: &Path, extensions: &[&str]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let wanted = || path.extension().is_some_and(|e| extensions.contains(&e.to_string_lossy().to_lowercase().as_str()));
+            match entry.file_type() {
+                Ok(t) if t.is_dir() && !hidden && depth < MAX_DEPTH => dirs.push((path, depth + 1)),
+                Ok(t) if t.is_file() && wanted() => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// "S01E02", "s1e2" or "1x02".
+fn episode_token(token: &str) -> Option<(u32, u32)> {
+    let lower = token.to_ascii_lowercase();
+    let (season, episode) = match lower.strip_prefix('s') {
+        Some(rest) => rest.split_once('e')?,
+        None => lower.split_once('x')?,
+    };
+    // "S01E01E02" counts as its first episode
+    let episode: String = episode.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let valid = !season.is_empty() && season.len() <= 2 && season.chars().all(|c| c.is_ascii_digit()) && (1..=3).contains(&episode.len());
+    valid.then(|| Some((season.parse().ok()?, episode.parse().ok()?)))?
+}
+
+fn year_token(token: &str) -> Option<i32> {
+    let digits = token.trim_matches(|c| c == '(' || c == ')');
+    (digits.len() == 4).then(|| digits.parse().ok()).flatten().filter(|y| (1900..=2099).contains(y))
+}
+
+/// Title, year and episode from a file or folder name, without its extension.
+pub fn parse_name(stem: &str) -> ParsedName {
+    // Bracketed parts are release groups, hashes and quality tags
+    let mut cleaned = String::new();
+    let mut depth = 0;
+    for c in stem.chars() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = (depth - 1).max(0),
+            _ if depth > 0 => {}
+            '.' | '_' => cleaned.push(' '),
+            _ => cleaned.push(c),
+        }
+    }
+    let tokens: Vec<&str> = cleaned.split_whitespace().collect();
+    let mut parsed = ParsedName::default();
+    // The title runs up to the episode or the first release tag...
+    let mut stop = tokens.len();
+    for (i, token) in tokens.iter().enumerate() {
+        let tag: String = token.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect();
+        if let Some((season, episode)) = episode_token(token) {
+            parsed.season = Some(season);
+            parsed.episode = Some(episode);
+        } else if short_number(token) && ((i > 0 && tokens[i - 1] == "-") || (i == 0 && tokens.get(1) == Some(&"-"))) {
+            // "Show - 05", the usual anime release naming, or "05 - Episode Title" in a show's folder
+            parsed.episode = token.parse().ok();
+        } else if !(i > 0 && RELEASE_TAGS.contains(&tag.as_str())) {
+            continue;
+        }
+        stop = i;
+        break;
+    }
+    // ...or the last year before that, so "Blade Runner 2049 (2017)" keeps its number
+    let year_at = (1..stop).rev().find(|i| year_token(tokens[*i]).is_some());
+    parsed.year = year_at.and_then(|i| year_token(tokens[i]));
+    let title_end = year_at.unwrap_or(stop);
+    parsed.title = tokens[..title_end].join(" ").trim_end_matches([' ', '-']).trim().to_string();
+    parsed
+}
+
+fn short_number(token: &str) -> bool {
+    (1..=3).contains(&token.len()) && token.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_extra(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    EXTRAS.iter().any(|e| lower == *e || lower.split(|c: char| !c.is_alphanumeric()).any(|w| w == *e))
+}
+
+/// "Season 1", "S01", "Specials": folders that don't name the show.
+fn is_season_folder(name: &str) -> bool {
+    let lower = name.trim().to_lowercase();
+    let number = lower.strip_prefix("season").or_else(|| lower.strip_prefix('s')).map(str::trim);
+    lower == "specials" || number.is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// The parsed name of a video file, taking the show or movie title from its
+/// folders when the file name has none (or only an episode number).
+fn parse_file(root: &Path, file: &Path) -> Option<ParsedName> {
+    let mut parsed = parse_name(&file.file_stem()?.to_string_lossy());
+    let folder = file
+        .parent()?
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root) && *dir != root)
+        .filter_map(|dir| dir.file_name().map(|n| n.to_string_lossy().to_string()))
+        .find(|name| !is_season_folder(name))
+        .map(|name| parse_name(&name));
+    if let Some(folder) = folder {
+        let same_title = normalize_title(&folder.title) == normalize_title(&parsed.title);
+        if parsed.title.is_empty() {
+            parsed.title = folder.title;
+            parsed.year = parsed.year.or(folder.year);
+        } else if same_title {
+            parsed.year = parsed.year.or(folder.year);
+        }
+    }
+    (!parsed.title.is_empty()).then_some(parsed)
+}
+
+/// How well a provider result fits the parsed title and year.
+pub fn confidence(title: &str, year: Option<i32>, titles: &[&str], found_year: Option<i32>) -> f64 {
+    let similarity = titles.iter().map(|t| title_similarity(title, t)).fold(0.0, f64::max);
+    let year_factor = match (year, found_year) {
+        (Some(a), Some(b)) if a == b => 1.0,
+        (Some(a), Some(b)) if (a - b).abs() == 1 => 0.85,
+        (Some(_), Some(_)) => 0.4,
+        // Without a year in the name, a title match alone is less certain
+        (None, _) => 0.8,
+        (Some(_), None) => 0.9,
+    };
+    (similarity * year_factor * 100.0).round() / 100.0
+}
+
+/// Files of one movie or show.
+#[derive(Default)]
+struct Group {
+    parsed: ParsedName,
+    files: Vec<String>,
+    episodes: HashSet<(u32, u32)>,
+}
+
+struct Candidate {
+    provider: &'static str,
+    id: String,
+    title: String,
+    original_title: Option<String>,
+    release_date: String,
+    description: String,
+    poster_url: Option<String>,
+}
+
+fn text(v: &Value) -> Option<String> {
+    v.as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+async fn tmdb_candidates(client: &Client, api_key: &str, parsed: &ParsedName, tv: bool) -> Result<Vec<Candidate>, String> {
+    let (kind, year_param) = if tv { ("tv", "first_air_date_year") } else { ("movie", "year") };
+    let mut url = format!(
+        "https://api.themoviedb.org/3/search/{}?api_key={}&query={}",
+        kind,
+        urlencoding::encode(api_key),
+        urlencoding::encode(&parsed.title)
+    );
+    if let Some(year) = parsed.year {
+        url.push_str(&format!("&{}={}", year_param, year));
+    }
+    let v = crate::fetch_json(client, &url).await?;
+    let (title, original, date) = if tv { ("name", "original_name", "first_air_date") } else { ("title", "original_title", "release_date") };
+    Ok(v["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .take(5)
+        .filter_map(|r| {
+            Some(Candidate {
+                provider: "tmdb",
+                id: r["id"].as_u64()?.to_string(),
+                title: text(&r[title])?,
+                original_title: text(&r[original]),
+                release_date: text(&r[date]).unwrap_or_default(),
+                description: text(&r["overview"]).unwrap_or_default(),
+                poster_url: text(&r["poster_path"]).map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
+            })
+        })
+        .collect())
+}
+
+async fn bangumi_candidates(client: &Client, parsed: &ParsedName) -> Result<Vec<Candidate>, String> {
+    let url = format!("https://api.bgm.tv/search/subject/{}?type=2&responseGroup=medium&max_results=5", urlencoding::encode(&parsed.title));
+    let v = crate::fetch_json(client, &url).await?;
+    Ok(v["list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| {
+            let name = text(&s["name"])?;
+            Some(Candidate {
+                provider: "bangumi",
+                id: s["id"].as_u64()?.to_string(),
+                title: text(&s["name_cn"]).unwrap_or_else(|| name.clone()),
+                original_title: Some(name),
+                release_date: text(&s["air_date"]).unwrap_or_default(),
+                description: text(&s["summary"]).unwrap_or_default(),
+                poster_url: text(&s["images"]["large"]).map(|u| u.replace("http://", "https://")),
+            })
+        })
+        .collect())
+}
+
+/// Finds the media under `root` and proposes an item for each movie or show,
+/// matched on TMDB when `tmdb_api_key` is set and on Bangumi otherwise.
+pub async fn scan(client: &Client, root: &Path, tmdb_api_key: Option<&str>, existing: &[MediaItem]) -> Result<MediaScan, String> {
+    if !root.is_dir() {
+        return Err(format!("Not a folder: {}", root.display()));
+    }
+    let walk_root = root.to_path_buf();
+    let files = tauri::async_runtime::spawn_blocking(move || files_under(&walk_root, VIDEO_EXTENSIONS))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Keyed by normalized title, year and whether it is a show
+    let mut groups: BTreeMap<(String, Option<i32>, bool), Group> = BTreeMap::new();
+    for file in &files {
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        if relative.iter().any(|part| is_extra(&part.to_string_lossy())) {
+            continue;
+        }
+        let Some(parsed) = parse_file(root, file) else {
+            continue;
+        };
+        let tv = parsed.episode.is_some();
+        let group = groups.entry((normalize_title(&parsed.title), parsed.year, tv)).or_insert_with(|| Group { parsed: parsed.clone(), ..Default::default() });
+        group.files.push(file.to_string_lossy().to_string());
+        if let Some(episode) = parsed.episode {
+            group.episodes.insert((parsed.season.unwrap_or(1), episode));
+        }
+    }
+
+    let mut proposals = Vec::new();
+    for (lookups, ((_, _, tv), Group { parsed, files, episodes })) in groups.into_iter().enumerate() {
+        let candidates = match (lookups < MAX_LOOKUPS, tmdb_api_key) {
+            (false, _) => Ok(Vec::new()),
+            (true, Some(key)) => tmdb_candidates(client, key, &parsed, tv).await,
+            (true, None) => bangumi_candidates(client, &parsed).await,
+        };
+        let best = candidates
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| {
+                let titles: Vec<&str> = std::iter::once(c.title.as_str()).chain(c.original_title.as_deref()).collect();
+                (confidence(&parsed.title, parsed.year, &titles, extract_year(&c.release_date)), c)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        let mut item = MediaItem {
+            id: crate::database::new_id(),
+            title: parsed.title.clone(),
+            release_date: parsed.year.map(|y| y.to_string()).unwrap_or_default(),
+            media_type: if tv { MediaType::TvSeries } else { MediaType::Movie },
+            category: Some(CollectionCategory::ToWatch),
+            ..Default::default()
+        };
+        let (provider, confidence) = match best {
+            Some((score, c)) => {
+                let key = match (c.provider, tv) {
+                    ("tmdb", true) => "tmdbTv",
+                    (provider, _) => provider,
+                };
+                item.title = c.title;
+                item.release_date = if c.release_date.is_empty() { item.release_date } else { c.release_date };
+                item.description = c.description;
+                item.poster_url = c.poster_url;
+                item.provider_ids = Some([(key.to_string(), c.id)].into_iter().collect());
+                (Some(c.provider.to_string()), score)
+            }
+            None => (None, 0.0),
+        };
+        let existing_item_id = existing.iter().find(|e| crate::dedupe::same_work(e, &item)).map(|e| e.id.clone());
+        proposals.push(Proposal { item, files, episodes: episodes.len(), provider, confidence, existing_item_id });
+    }
+    proposals.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then_with(|| a.item.title.cmp(&b.item.title)));
+    Ok(MediaScan { files: files.len(), proposals })
+}
@@ -0,0 +1,109 @@
+// Watched state shared by the media server integrations (Plex, Jellyfin/Emby).
+// Each server's library is read into `ServerItem`s, which are matched to
+// MediaItems by the server's own id (kept as a provider id under the server's
+// key), an external id (IMDb, TMDB, TVDB) or title and year. Matched items are
+// marked Watched when the server says so and get a completion at the last view
+// date; a server never un-watches anything.
+
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::dedupe::{extract_year, normalize_title};
+use crate::models::{CollectionCategory, Completion, MediaItem, MediaType};
+
+/// A completion this close before the last view is taken to be the same viewing.
+const SAME_VIEWING_MS: i64 = crate::database::DAY_MS;
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerItem {
+    /// The server's id for the item, stored as the item's provider id.
+    pub id: String,
+    pub title: String,
+    pub original_title: Option<String>,
+    pub year: Option<i32>,
+    pub media_type: MediaType,
+    /// Keyed like our provider ids: "imdb", "tmdb"/"tmdbTv", "tvdb".
+    pub external_ids: HashMap<String, String>,
+    pub watched: bool,
+    pub last_viewed_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSummary {
+    pub scanned: usize,
+    pub matched: usize,
+    pub updated: usize,
+}
+
+impl SyncSummary {
+    pub fn add(&mut self, other: &SyncSummary) {
+        self.scanned += other.scanned;
+        self.matched += other.matched;
+        self.updated += other.updated;
+    }
+}
+
+/// Our key for an external id scheme as servers name it ("imdb", "Tmdb", ...).
+pub fn external_key(scheme: &str, media_type: &MediaType) -> Option<&'static str> {
+    match (scheme.to_ascii_lowercase().as_str(), media_type) {
+        ("imdb", _) => Some("imdb"),
+        ("tmdb", MediaType::TvSeries) => Some("tmdbTv"),
+        ("tmdb", _) => Some("tmdb"),
+        ("tvdb", _) => Some("tvdb"),
+        _ => None,
+    }
+}
+
+/// Index of the item `server_item` is, preferring ids over title and year.
+/// `key` is the provider id key the server's ids are stored under.
+pub fn find_match(items: &[MediaItem], key: &str, server_item: &ServerItem) -> Option<usize> {
+    let ids = |i: &MediaItem| i.provider_ids.clone().unwrap_or_default();
+    if !server_item.id.is_empty() {
+        if let Some(at) = items.iter().position(|i| ids(i).get(key) == Some(&server_item.id)) {
+            return Some(at);
+        }
+    }
+    if let Some(at) = items.iter().position(|i| {
+        let ids = ids(i);
+        server_item.external_ids.iter().any(|(k, v)| ids.get(k) == Some(v))
+    }) {
+        return Some(at);
+    }
+    let titles: Vec<String> = std::iter::once(&server_item.title).chain(server_item.original_title.as_ref()).map(|t| normalize_title(t)).collect();
+    items.iter().position(|i| {
+        let year_ok = match (extract_year(&i.release_date), server_item.year) {
+            (Some(a), Some(b)) => (a - b).abs() <= 1,
+            _ => true,
+        };
+        let known = std::iter::once(&i.title).chain(i.alt_titles.values());
+        i.media_type == server_item.media_type && year_ok && known.map(|t| normalize_title(t)).any(|t| titles.contains(&t))
+    })
+}
+
+/// Copies the server's watched state onto `item`; returns whether anything changed.
+pub fn apply(item: &mut MediaItem, key: &str, server_item: &ServerItem) -> bool {
+    let mut changed = false;
+    if !server_item.id.is_empty() {
+        let ids = item.provider_ids.get_or_insert_with(HashMap::new);
+        if ids.get(key) != Some(&server_item.id) {
+            ids.insert(key.to_string(), server_item.id.clone());
+            changed = true;
+        }
+    }
+    if !server_item.watched {
+        return changed;
+    }
+    if item.category != Some(CollectionCategory::Watched) {
+        item.category = Some(CollectionCategory::Watched);
+        changed = true;
+    }
+    if let Some(at) = server_item.last_viewed_at {
+        if !item.completions.iter().any(|c| c.at >= at - SAME_VIEWING_MS) {
+            item.completions.push(Completion { at, rating: None });
+            item.completions.sort_by_key(|c| c.at);
+            changed = true;
+        }
+    }
+    changed
+}
@@ -0,0 +1,411 @@
+// Re-fetching provider details for items that already carry provider ids. Used by
+// the `refresh_metadata` command and the scheduler's `metadataRefresh` job.
+//
+// A refresh only fills provider-owned fields; the title, the user's review and a
+// custom poster are never replaced. Besides the main provider's score, a full
+// refresh also fills the OMDb and Douban rating slots (see `ratings::fetch_extra`).
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::task::JoinSet;
+use crate::database::Database;
+use crate::models::MediaItem;
+use crate::ratings::SourceRating;
+use crate::content_rating::ContentRating;
+
+pub const REFRESH_EVENT: &str = "metadata-refresh-progress";
+
+const REFRESH_CONCURRENCY: usize = 4;
+const TMDB_IMAGE_BASE: &str = "https://image.tmdb.org/t/p/w500";
+
+/// Provider-owned fields as currently reported by the provider. `None` means the
+/// provider had nothing, which leaves the stored value alone.
+#[derive(Debug, Clone, Default)]
+pub struct ItemDetails {
+    pub description: Option<String>,
+    pub release_date: Option<String>,
+    pub director_or_author: Option<String>,
+    pub cast: Option<Vec<String>>,
+    pub poster_url: Option<String>,
+    pub is_ongoing: Option<bool>,
+    pub ratings: HashMap<String, SourceRating>,
+    pub content_rating: Option<ContentRating>,
+    pub narrators: Option<Vec<String>>,
+    pub duration_minutes: Option<u32>,
+    /// Board games: (min, max) players and play time in minutes.
+    pub players: Option<(u32, u32)>,
+    pub playtime: Option<(u32, u32)>,
+}
+
+/// Which camelCase fields a refresh changed, and which user-owned ones it left alone.
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChanges {
+    pub updated: Vec<String>,
+    pub preserved: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshProgress {
+    pub done: usize,
+    pub total: usize,
+    pub item_id: String,
+    pub title: String,
+    #[serde(flatten)]
+    pub changes: FieldChanges,
+    pub error: Option<String>,
+    /// Set on the extra last event sent when a cancel left items undone.
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshSummary {
+    pub total: usize,
+    pub refreshed: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub cancelled: bool,
+}
+
+/// One bulk refresh at a time; `cancel` stops handing out new items.
+#[derive(Default)]
+pub struct RefreshControl {
+    running: AtomicBool,
+    cancel: AtomicBool,
+}
+
+impl RefreshControl {
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) -> bool {
+        let running = self.running.load(Ordering::SeqCst);
+        if running {
+            self.cancel.store(true, Ordering::SeqCst);
+        }
+        running
+    }
+}
+
+fn non_empty(v: &Value) -> Option<String> {
+    v.as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+fn names(v: &Value, limit: usize) -> Option<Vec<String>> {
+    let out: Vec<String> = v.as_array()?.iter().filter_map(|p| non_empty(&p["name"])).take(limit).collect();
+    (!out.is_empty()).then_some(out)
+}
+
+// Bangumi infobox values are either a string or a list of {v}
+fn infobox_value(v: &Value, keys: &[&str]) -> Option<String> {
+    let entry = v["infobox"].as_array()?.iter().find(|e| e["key"].as_str().map(|k| keys.contains(&k)).unwrap_or(false))?;
+    match &entry["value"] {
+        Value::Array(parts) => {
+            let joined: Vec<String> = parts.iter().filter_map(|p| non_empty(&p["v"])).collect();
+            (!joined.is_empty()).then(|| joined.join(", "))
+        }
+        other => non_empty(other),
+    }
+}
+
+async fn bangumi_details(client: &Client, id: &str, now: i64) -> Result<ItemDetails, String> {
+    let v = crate::fetch_json(client, &format!("https://api.bgm.tv/v0/subjects/{}", urlencoding::encode(id))).await?;
+    let mut ratings = HashMap::new();
+    if let Some(score) = v["rating"]["score"].as_f64().filter(|s| *s > 0.0) {
+        ratings.insert("bangumi".to_string(), SourceRating { score, scale: 10.0, votes: v["rating"]["total"].as_u64(), updated_at: Some(now) });
+    }
+    Ok(ItemDetails {
+        description: non_empty(&v["summary"]),
+        release_date: non_empty(&v["date"]),
+        director_or_author: infobox_value(&v, &["导演", "作者", "原作", "艺术家"]),
+        cast: None,
+        poster_url: non_empty(&v["images"]["large"]).map(|u| u.replace("http://", "https://")),
+        is_ongoing: None,
+        ratings,
+        content_rating: crate::content_rating::from_bangumi(&v),
+        narrators: None,
+        duration_minutes: None,
+        players: None,
+        playtime: None,
+    })
+}
+
+async fn tmdb_details(client: &Client, path: &str, api_key: &str, now: i64) -> Result<ItemDetails, String> {
+    let is_tv = path.starts_with("tv/");
+    let extra = if is_tv { "content_ratings" } else { "release_dates" };
+    let url = format!("https://api.themoviedb.org/3/{}?api_key={}&append_to_response=credits,{}", path, urlencoding::encode(api_key), extra);
+    let v = crate::fetch_json(client, &url).await?;
+    let mut ratings = HashMap::new();
+    if let Some(score) = v["vote_average"].as_f64().filter(|s| *s > 0.0) {
+        ratings.insert("tmdb".to_string(), SourceRating { score, scale: 10.0, votes: v["vote_count"].as_u64(), updated_at: Some(now) });
+    }
+    let director_or_author = if is_tv {
+        names(&v["created_by"], 3).map(|n| n.join(", "))
+    } else {
+        let directors: Vec<String> = v["credits"]["crew"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|c| c["job"].as_str() == Some("Director"))
+            .filter_map(|c| non_empty(&c["name"]))
+            .collect();
+        (!directors.is_empty()).then(|| directors.join(", "))
+    };
+    Ok(ItemDetails {
+        description: non_empty(&v["overview"]),
+        release_date: non_empty(if is_tv { &v["first_air_date"] } else { &v["release_date"] }),
+        director_or_author,
+        cast: names(&v["credits"]["cast"], 10),
+        poster_url: non_empty(&v["poster_path"]).map(|p| format!("{}{}", TMDB_IMAGE_BASE, p)),
+        is_ongoing: if is_tv { v["in_production"].as_bool() } else { None },
+        ratings,
+        content_rating: if is_tv { crate::content_rating::from_tmdb_tv(&v) } else { crate::content_rating::from_tmdb_movie(&v) },
+        narrators: None,
+        duration_minutes: None,
+        players: None,
+        playtime: None,
+    })
+}
+
+/// Current details for an item from `provider` ("bangumi", "tmdb", "audible" or "bgg"), or from the
+/// first provider it has an id for. `Ok(None)` means there is nothing to look up.
+pub async fn fetch_details(client: &Client, item: &MediaItem, provider: Option<&str>, tmdb_api_key: Option<&str>, now: i64) -> Result<Option<ItemDetails>, String> {
+    let Some(ids) = item.provider_ids.as_ref() else {
+        return Ok(None);
+    };
+    let wants = |p: &str| provider.map(|want| want == p).unwrap_or(true);
+    if wants("bangumi") {
+        if let Some(id) = ids.get("bangumi") {
+            return bangumi_details(client, id, now).await.map(Some);
+        }
+    }
+    if wants("tmdb") {
+        let path = ids
+            .get("tmdb")
+            .map(|id| format!("movie/{}", urlencoding::encode(id)))
+            .or_else(|| ids.get("tmdbTv").map(|id| format!("tv/{}", urlencoding::encode(id))));
+        match (path, tmdb_api_key) {
+            (Some(path), Some(key)) => return tmdb_details(client, &path, key, now).await.map(Some),
+            (Some(_), None) => return Err("TMDB API key is not set".to_string()),
+            _ => {}
+        }
+    }
+    if wants("audible") {
+        if let Some(asin) = ids.get("audible") {
+            return crate::audiobooks::details(client, asin, "us").await.map(|b| Some(b.details()));
+        }
+    }
+    if wants("bgg") {
+        if let Some(id) = ids.get("bgg") {
+            let token = crate::secrets::resolve(None, crate::secrets::BGG);
+            return crate::bgg::thing(client, id, token.as_deref()).await.map(|g| Some(g.details(now)));
+        }
+    }
+    Ok(None)
+}
+
+fn same_rating(a: &SourceRating, b: &SourceRating) -> bool {
+    a.score == b.score && a.scale == b.scale && a.votes == b.votes
+}
+
+/// Copies fetched details onto the item and reports what changed.
+pub fn apply_details(item: &mut MediaItem, details: ItemDetails) -> FieldChanges {
+    let mut changes = FieldChanges::default();
+    let mut set = |field: &str, slot: &mut String, value: Option<String>| {
+        if let Some(v) = value.filter(|v| v != slot) {
+            *slot = v;
+            changes.updated.push(field.to_string());
+        }
+    };
+    set("description", &mut item.description, details.description);
+    set("releaseDate", &mut item.release_date, details.release_date);
+    set("directorOrAuthor", &mut item.director_or_author, details.director_or_author);
+    if let Some(cast) = details.cast.filter(|c| item.cast.as_ref() != Some(c)) {
+        item.cast = Some(cast);
+        changes.updated.push("cast".to_string());
+    }
+    if let Some(url) = details.poster_url.filter(|u| item.poster_url.as_ref() != Some(u)) {
+        item.poster_url = Some(url);
+        changes.updated.push("posterUrl".to_string());
+    }
+    if let Some(narrators) = details.narrators.filter(|n| *n != item.narrators) {
+        item.narrators = narrators;
+        changes.updated.push("narrators".to_string());
+    }
+    if let Some(minutes) = details.duration_minutes.filter(|m| item.duration_minutes != Some(*m)) {
+        item.duration_minutes = Some(minutes);
+        changes.updated.push("durationMinutes".to_string());
+    }
+    if let Some((min, max)) = details.players.filter(|p| (item.min_players, item.max_players) != (Some(p.0), Some(p.1))) {
+        item.min_players = Some(min);
+        item.max_players = Some(max);
+        changes.updated.push("players".to_string());
+    }
+    if let Some((min, max)) = details.playtime.filter(|p| (item.min_playtime, item.max_playtime) != (Some(p.0), Some(p.1))) {
+        item.min_playtime = Some(min);
+        item.max_playtime = Some(max);
+        changes.updated.push("playtime".to_string());
+    }
+    if let Some(ongoing) = details.is_ongoing.filter(|o| *o != item.is_ongoing) {
+        item.is_ongoing = ongoing;
+        changes.updated.push("isOngoing".to_string());
+    }
+    // Tags only decide when no provider has ever given a rating
+    let tagged = || match &item.content_rating {
+        Some(r) if r.system != "Tags" => None,
+        _ => crate::content_rating::from_tags(item.tags.as_deref().unwrap_or(&[])),
+    };
+    if let Some(rating) = details.content_rating.or_else(tagged).filter(|r| item.content_rating.as_ref() != Some(r)) {
+        item.content_rating = Some(rating);
+        changes.updated.push("contentRating".to_string());
+    }
+    let slots = item.ratings.get_or_insert_with(HashMap::new);
+    let mut ratings_changed = false;
+    for (source, rating) in details.ratings {
+        if slots.get(&source).map(|r| !same_rating(r, &rating)).unwrap_or(true) {
+            slots.insert(source, rating);
+            ratings_changed = true;
+        }
+    }
+    if ratings_changed {
+        crate::ratings::merge_into_item(item, None);
+        changes.updated.push("ratings".to_string());
+    }
+    if item.user_review.as_deref().map(|r| !r.is_empty()).unwrap_or(false) {
+        changes.preserved.push("userReview".to_string());
+    }
+    if item.custom_poster_url.as_deref().map(|u| !u.is_empty()).unwrap_or(false) {
+        changes.preserved.push("customPosterUrl".to_string());
+    }
+    changes
+}
+
+/// Refreshes the given items (or all of the user's items) with up to
+/// REFRESH_CONCURRENCY requests in flight, emitting REFRESH_EVENT per item.
+pub async fn refresh(
+    app: &AppHandle,
+    db: &Arc<Database>,
+    control: &RefreshControl,
+    client: &Client,
+    username: &str,
+    ids: Option<Vec<String>>,
+    provider: Option<String>,
+) -> Result<RefreshSummary, String> {
+    if control.running.swap(true, Ordering::SeqCst) {
+        return Err("A metadata refresh is already running".to_string());
+    }
+    control.cancel.store(false, Ordering::SeqCst);
+    let result = run_refresh(app, db, control, client, username, ids, provider).await;
+    control.running.store(false, Ordering::SeqCst);
+    result
+}
+
+async fn run_refresh(
+    app: &AppHandle,
+    db: &Arc<Database>,
+    control: &RefreshControl,
+    client: &Client,
+    username: &str,
+    ids: Option<Vec<String>>,
+    provider: Option<String>,
+) -> Result<RefreshSummary, String> {
+    let tmdb_key = crate::secrets::resolve(None, crate::secrets::TMDB);
+    let direct = app.state::<crate::AppState>().direct_client.clone();
+    let items: Vec<MediaItem> = db
+        .get_all_for_user(username)
+        .await?
+        .into_iter()
+        .filter(|i| ids.as_ref().map(|ids| ids.contains(&i.id)).unwrap_or(true))
+        .collect();
+    let mut summary = RefreshSummary { total: items.len(), ..Default::default() };
+    let mut workers = JoinSet::new();
+    let mut pending = items.into_iter();
+    let mut done = 0;
+    loop {
+        // Top up the pool; after a cancel the in-flight requests are just drained
+        while workers.len() < REFRESH_CONCURRENCY && !control.cancel.load(Ordering::SeqCst) {
+            let Some(item) = pending.next() else {
+                break;
+            };
+            let (client, direct, tmdb_key, provider) = (client.clone(), direct.clone(), tmdb_key.clone(), provider.clone());
+            workers.spawn(async move {
+                let now = crate::database::now_ms();
+                let mut fetched = fetch_details(&client, &item, provider.as_deref(), tmdb_key.as_deref(), now).await;
+                // Other sites' scores go into their own slots next to the provider's
+                if provider.is_none() {
+                    let extra = crate::ratings::fetch_extra(&direct, &item, now).await;
+                    if !extra.is_empty() {
+                        match &mut fetched {
+                            Ok(Some(details)) => details.ratings.extend(extra),
+                            Ok(None) => fetched = Ok(Some(ItemDetails { ratings: extra, ..Default::default() })),
+                            Err(_) => {}
+                        }
+                    }
+                }
+                (item, fetched)
+            });
+        }
+        let Some(joined) = workers.join_next().await else {
+            break;
+        };
+        let (item, fetched) = joined.map_err(|e| e.to_string())?;
+        done += 1;
+        let mut progress = RefreshProgress {
+            done,
+            total: summary.total,
+            item_id: item.id.clone(),
+            title: item.title.clone(),
+            changes: FieldChanges::default(),
+            error: None,
+            cancelled: false,
+        };
+        match fetched {
+            Ok(Some(details)) => match db.apply_item_details(username, &item.id, details).await {
+                Ok(changes) => {
+                    if changes.updated.is_empty() {
+                        summary.unchanged += 1;
+                    } else {
+                        summary.refreshed += 1;
+                    }
+                    progress.changes = changes;
+                }
+                Err(e) => {
+                    summary.failed += 1;
+                    progress.error = Some(e);
+                }
+            },
+            Ok(None) => summary.skipped += 1,
+            Err(e) => {
+                summary.failed += 1;
+                progress.error = Some(e);
+            }
+        }
+        let _ = app.emit(REFRESH_EVENT, progress);
+    }
+    summary.cancelled = control.cancel.load(Ordering::SeqCst) && done < summary.total;
+    if summary.cancelled {
+        // done never reaches total, so listeners learn here that it is over
+        let _ = app.emit(
+            REFRESH_EVENT,
+            RefreshProgress {
+                done,
+                total: summary.total,
+                item_id: String::new(),
+                title: String::new(),
+                changes: FieldChanges::default(),
+                error: None,
+                cancelled: true,
+            },
+        );
+    }
+    Ok(summary)
+}
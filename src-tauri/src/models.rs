@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 pub enum MediaType {
     #[serde(rename = "Book")]
     Book,
@@ -15,7 +15,16 @@ pub enum MediaType {
     ShortDrama,
     #[serde(rename = "Music")]
     Music,
+    #[serde(rename = "Fanfiction")]
+    Fanfiction,
+    #[serde(rename = "Podcast")]
+    Podcast,
+    #[serde(rename = "Audiobook")]
+    Audiobook,
+    #[serde(rename = "Board Game")]
+    BoardGame,
     #[serde(rename = "Other")]
+    #[default]
     Other,
 }
 
@@ -29,7 +38,7 @@ pub enum CollectionCategory {
     Watched,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct MediaItem {
     pub id: String,
@@ -37,6 +46,9 @@ pub struct MediaItem {
     pub director_or_author: String,
     pub description: String,
     pub release_date: String,
+    // `release_date` parsed to ISO form with its precision; refreshed on save
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized_release_date: Option<crate::dates::PartialDate>,
     #[serde(rename = "type")]
     pub media_type: MediaType,
     pub is_ongoing: bool,
@@ -44,7 +56,9 @@ pub struct MediaItem {
     pub category: Option<CollectionCategory>,
     pub saved_at: Option<i64>,
     pub poster_url: Option<String>,
-    pub rating: Option<String>,
+    pub rating: Option<String>, // Display string; derived from `ratings` when any source is set
+    pub ratings: Option<HashMap<String, crate::ratings::SourceRating>>, // keyed by source: "douban", "imdb", "bangumi", "metacritic", ...
+    pub aggregate_rating: Option<f64>,
     pub cast: Option<Vec<String>>,
     pub user_progress: Option<String>,
     pub notification_enabled: Option<bool>,
@@ -52,12 +66,185 @@ pub struct MediaItem {
     pub has_new_update: Option<bool>, // New field
     pub user_review: Option<String>,
     pub custom_poster_url: Option<String>,
+    pub poster_cache: Option<crate::images::CachedImage>, // Local copy of the displayed poster
     pub last_edited_at: Option<i64>,
+    pub updated_at: Option<i64>, // Any content change, including background ones; drives sync
     pub status: Option<String>, // 'To Watch' etc, seems redundant with category but present in some parts
     pub added_at: Option<String>,
     pub user_rating: Option<f32>,
+    // Legacy collection flags: accepted on input and rendered on output, but the
+    // stored source of truth is `CollectionData::collections_by_user`
     pub parent_collection_id: Option<String>,
     pub is_collection: Option<bool>,
+    pub deleted_at: Option<i64>,
+    pub tags: Option<Vec<String>>,
+    pub provider_ids: Option<HashMap<String, String>>, // e.g. "tmdb" -> "27205" (movie), "tmdbTv" -> "1396", "bangumi" -> "253"
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub custom_fields: HashMap<String, serde_json::Value>,
+    // Every time the item was finished, oldest first; more than one is a rewatch/reread
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub completions: Vec<Completion>,
+    // Highlights and quotes, mostly for books
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quotes: Vec<Quote>,
+    // Markdown notes; `user_review` stays the single review
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<crate::notes::Note>,
+    // Volume/chapter position and chapters read, for comics and manga
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<crate::comics::ReadingProgress>,
+    // Age rating from the provider, or implied by tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_rating: Option<crate::content_rating::ContentRating>,
+    // Audiobook narrators and listening length
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub narrators: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_minutes: Option<u32>,
+    // Board game player count and play time in minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_players: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_players: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_playtime: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_playtime: Option<u32>,
+    // Average completion times from HowLongToBeat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub play_times: Option<crate::hltb::PlayTimes>,
+    // Scrobbles of an album imported from Last.fm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub play_count: Option<u64>,
+    // Best trailer found by `find_trailer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trailer_url: Option<String>,
+    // Titles in other languages, keyed by language code ("zh", "en", "ja")
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub alt_titles: HashMap<String, String>,
+    // Next episode's air time from the update checker, in UTC with the broadcaster's zone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_airing: Option<crate::airtime::Airing>,
+    // Owner of an item another account shared with the user; never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_from: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Completion {
+    pub at: i64,
+    /// Rating given that time, if it differed.
+    pub rating: Option<f32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Quote {
+    pub id: String,
+    pub text: String,
+    /// Page, chapter or timestamp, as the user wrote it.
+    pub location: Option<String>,
+    pub created_at: i64,
+    pub updated_at: Option<i64>,
+}
+
+impl MediaItem {
+    /// Timestamp sync compares to decide which copy of an item is newer.
+    pub fn version(&self) -> i64 {
+        self.updated_at.or(self.last_edited_at).unwrap_or(0)
+    }
+}
+
+/// Record of an item permanently deleted, kept so paired devices delete it too
+/// instead of sending it back.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tombstone {
+    pub item_id: String,
+    pub deleted_at: i64,
+}
+
+/// A device paired by PIN to sync one user's collection. Revoking it removes the entry.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedDevice {
+    pub id: String,
+    pub name: String,
+    /// `envelope::fingerprint` of the device's key; empty for pairings older than key pinning.
+    #[serde(default)]
+    pub fingerprint: String,
+    /// SHA-256 of the token the device presents; empty in what the frontend receives.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub token_hash: String,
+    pub paired_at: i64,
+    /// Last time the device synced with us.
+    pub last_seen_at: Option<i64>,
+    /// Last time we synced with the device successfully.
+    pub last_synced_at: Option<i64>,
+}
+
+/// When each item last changed on this device (by local clock), so peers can
+/// ask for only what changed since their last sync.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeLog {
+    /// Changes before this are not recorded; older cursors get a full exchange.
+    pub started_at: i64,
+    pub changes: HashMap<String, i64>,
+}
+
+/// How far delta sync with one peer got in each direction.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncCursor {
+    /// The peer's `until` from the last pull, in the peer's clock.
+    pub pulled_until: i64,
+    /// Our `until` from the last successful push, in our clock.
+    pub pushed_until: i64,
+}
+
+/// Partial update applied to many items at once by `bulk_update_items`.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemPatch {
+    pub category: Option<CollectionCategory>,
+    #[serde(rename = "type")]
+    pub media_type: Option<MediaType>,
+    pub status: Option<String>,
+    pub notification_enabled: Option<bool>,
+    pub add_tags: Option<Vec<String>>,
+    pub remove_tags: Option<Vec<String>>,
+}
+
+impl ItemPatch {
+    pub fn apply(&self, item: &mut MediaItem) {
+        if let Some(c) = &self.category {
+            item.category = Some(c.clone());
+        }
+        if let Some(t) = &self.media_type {
+            item.media_type = t.clone();
+        }
+        if let Some(s) = &self.status {
+            item.status = Some(s.clone());
+        }
+        if let Some(n) = self.notification_enabled {
+            item.notification_enabled = Some(n);
+        }
+        if self.add_tags.is_some() || self.remove_tags.is_some() {
+            let mut tags = item.tags.take().unwrap_or_default();
+            for t in self.add_tags.iter().flatten() {
+                let t = t.trim();
+                if !t.is_empty() && !tags.iter().any(|x| x == t) {
+                    tags.push(t.to_string());
+                }
+            }
+            if let Some(remove) = &self.remove_tags {
+                tags.retain(|t| !remove.contains(t));
+            }
+            item.tags = Some(tags);
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -68,6 +255,177 @@ pub struct CollectionData {
     pub users: Vec<UserRecord>,
     #[serde(default)]
     pub items_by_user: HashMap<String, Vec<MediaItem>>, 
+    #[serde(default)]
+    pub trash_by_user: HashMap<String, Vec<MediaItem>>,
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub history_by_user: HashMap<String, HashMap<String, Vec<ItemRevision>>>,
+    #[serde(default)]
+    pub smart_lists_by_user: HashMap<String, Vec<crate::smart::SmartList>>,
+    #[serde(default)]
+    pub collections_by_user: HashMap<String, Vec<crate::collections::Collection>>,
+    #[serde(default)]
+    pub custom_fields_by_user: HashMap<String, Vec<crate::custom_fields::CustomFieldDef>>,
+    #[serde(default)]
+    pub people_by_user: HashMap<String, HashMap<String, crate::people::PersonMeta>>,
+    #[serde(default)]
+    pub relations_by_user: HashMap<String, Vec<crate::relations::Relation>>,
+    #[serde(default)]
+    pub feeds_by_user: HashMap<String, Vec<crate::feeds::FeedSubscription>>,
+    #[serde(default)]
+    pub job_runs: HashMap<crate::scheduler::JobKind, crate::scheduler::JobRun>,
+    /// SHA-256 of each user's browser-extension token (the token itself is only shown once).
+    #[serde(default)]
+    pub extension_tokens_by_user: HashMap<String, String>,
+    /// SHA-256 of each user's token for the read-only web view.
+    #[serde(default)]
+    pub web_tokens_by_user: HashMap<String, String>,
+    /// SHA-256 of each user's token for the Atom feed.
+    #[serde(default)]
+    pub feed_tokens_by_user: HashMap<String, String>,
+    /// Devices paired (by PIN) to sync each user.
+    #[serde(default)]
+    pub trusted_devices_by_user: HashMap<String, Vec<TrustedDevice>>,
+    /// Tokens peers issued to us: username -> peer fingerprint -> token.
+    #[serde(default)]
+    pub peer_tokens_by_user: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    pub tombstones_by_user: HashMap<String, Vec<Tombstone>>,
+    #[serde(default)]
+    pub change_log_by_user: HashMap<String, ChangeLog>,
+    /// username -> peer fingerprint -> delta sync position.
+    #[serde(default)]
+    pub sync_cursors_by_user: HashMap<String, HashMap<String, SyncCursor>>,
+    /// This device's X25519 secret for sync encryption (base64), created on first use.
+    #[serde(default)]
+    pub device_key: Option<String>,
+    /// Public keys pinned when pairing: peer fingerprint -> base64 key.
+    #[serde(default)]
+    pub peer_keys: HashMap<String, String>,
+    #[serde(default)]
+    pub webhooks_by_user: HashMap<String, Vec<crate::webhooks::Webhook>>,
+    /// Most recent sync sessions per user, newest first.
+    #[serde(default)]
+    pub sync_history_by_user: HashMap<String, Vec<crate::sync::SyncSession>>,
+    /// Logins and rejected LAN requests, newest first.
+    #[serde(default)]
+    pub auth_log: Vec<crate::auth::AuthEvent>,
+    /// Collections shared between accounts on this computer.
+    #[serde(default)]
+    pub collection_shares: Vec<crate::sharing::CollectionShare>,
+    /// Item lifecycle events, oldest first.
+    #[serde(default)]
+    pub activity_by_user: HashMap<String, Vec<crate::activity::ActivityEvent>>,
+    #[serde(default)]
+    pub time_sessions_by_user: HashMap<String, Vec<crate::watch_time::TimeSession>>,
+    #[serde(default)]
+    pub goals_by_user: HashMap<String, Vec<crate::goals::Goal>>,
+    #[serde(default)]
+    pub streaks_by_user: HashMap<String, crate::stats::StreakRecord>,
+    /// Ordered status lists; users without one get `statuses::defaults`.
+    #[serde(default)]
+    pub statuses_by_user: HashMap<String, Vec<crate::statuses::StatusDef>>,
+    /// Manual order and pins, by view name.
+    #[serde(default)]
+    pub view_orders_by_user: HashMap<String, HashMap<String, crate::ordering::ViewOrder>>,
+    /// Item id -> snoozed until, for the continue-watching queue.
+    #[serde(default)]
+    pub snoozes_by_user: HashMap<String, HashMap<String, i64>>,
+    #[serde(default)]
+    pub content_filters_by_user: HashMap<String, crate::content_rating::ContentFilter>,
+    /// Last.fm username whose play counts the `lastfmRefresh` job keeps current.
+    #[serde(default)]
+    pub lastfm_accounts_by_user: HashMap<String, String>,
+    /// Plex server whose watched state the `plexSync` job reconciles.
+    #[serde(default)]
+    pub plex_accounts_by_user: HashMap<String, crate::plex::PlexAccount>,
+    /// Jellyfin/Emby server user whose watched state the `jellyfinSync` job reconciles.
+    #[serde(default)]
+    pub jellyfin_accounts_by_user: HashMap<String, crate::jellyfin::JellyfinAccount>,
+    /// New works by followed people, waiting to be added or dismissed.
+    #[serde(default)]
+    pub creator_suggestions_by_user: HashMap<String, Vec<crate::follows::CreatorSuggestion>>,
+    /// `follows::work_key`s of dismissed suggestions, never suggested again.
+    #[serde(default)]
+    pub dismissed_works_by_user: HashMap<String, std::collections::HashSet<String>>,
+}
+
+/// Snapshot of an item taken just before `save_item` overwrote it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemRevision {
+    pub revision: u32,
+    pub at: i64,
+    pub changed_fields: Vec<String>,
+    pub snapshot: MediaItem,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub trash_retention_days: u32,
+    /// From before the TMDB key lived in the keychain (`secrets::TMDB`); moved there
+    /// at load and only kept here while the keychain can't take it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmdb_api_key: Option<String>,
+    pub notifications_enabled: bool,
+    /// Offer to add items when a Douban/IMDb/Bangumi/TMDB link is copied.
+    pub clipboard_watch_enabled: bool,
+    /// Start the LAN sync server at launch.
+    pub sync_enabled: bool,
+    pub sync_port: u16,
+    /// Per-job overrides; jobs not listed use `JobKind::default_schedule`.
+    pub jobs: HashMap<crate::scheduler::JobKind, crate::scheduler::JobSchedule>,
+    /// Intervals from before `jobs` (0 turned the job off); only read, and moved
+    /// into `jobs` at load by `scheduler::migrate_legacy_intervals`.
+    #[serde(skip_serializing)]
+    pub update_check_interval_minutes: Option<u32>,
+    #[serde(skip_serializing)]
+    pub feed_poll_interval_minutes: Option<u32>,
+    /// Off-site target for the `cloudBackup` job.
+    pub s3_backup: Option<crate::cloud_backup::S3Config>,
+    /// Chapter languages MangaDex searches and update checks look at.
+    pub mangadex_languages: Vec<String>,
+    /// Language whose title to show ("zh", "en", "ja") when an item has one in `alt_titles`.
+    pub title_language: Option<String>,
+    /// The user's offset from UTC (480 for UTC+8), kept current by the frontend;
+    /// background notifications show air times in it.
+    pub utc_offset_minutes: i32,
+    /// Closing the main window hides it to the tray instead of quitting, so
+    /// update checks and the sync server keep running.
+    pub close_to_tray: bool,
+    /// Global shortcut that opens the quick-add overlay ("CommandOrControl+Shift+M");
+    /// empty for none.
+    pub quick_add_shortcut: String,
+}
+
+impl Settings {
+    pub fn job(&self, kind: crate::scheduler::JobKind) -> crate::scheduler::JobSchedule {
+        self.jobs.get(&kind).copied().unwrap_or_else(|| kind.default_schedule())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            trash_retention_days: 30,
+            tmdb_api_key: None,
+            notifications_enabled: true,
+            clipboard_watch_enabled: false,
+            sync_enabled: false,
+            sync_port: crate::sync::DEFAULT_PORT,
+            jobs: HashMap::new(),
+            update_check_interval_minutes: None,
+            feed_poll_interval_minutes: None,
+            s3_backup: None,
+            mangadex_languages: vec!["en".to_string()],
+            title_language: None,
+            utc_offset_minutes: 0,
+            close_to_tray: false,
+            quick_add_shortcut: crate::quick_add::DEFAULT_SHORTCUT.to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -76,6 +434,12 @@ pub struct UserRecord {
     pub username: String,
     pub password_hash: String,
     pub created_at: i64,
+    /// When the credentials last changed (ms); the newer copy wins when syncing.
+    #[serde(default)]
+    pub last_modified: Option<i64>,
+    /// SHA-256 of each unused recovery code.
+    #[serde(default)]
+    pub recovery_codes: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
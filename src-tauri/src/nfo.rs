@@ -0,0 +1,231 @@
+// Kodi-style .nfo files. Importing walks a media folder for movie and tvshow
+// NFOs (`movie.nfo`, `<file name>.nfo`, `tvshow.nfo`) and turns each into an
+// item; episode and other NFOs are skipped. Exporting writes an item back as a
+// `<movie>` or `<tvshow>` document Kodi, Jellyfin and Emby all read.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Serialize;
+use crate::database::{new_id, now_ms};
+use crate::dedupe::ImportDecision;
+use crate::media_server::external_key;
+use crate::models::{CollectionCategory, Completion, MediaItem, MediaType};
+
+/// NFOs are small; anything bigger is not one.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NfoImport {
+    pub found: usize,
+    pub added: usize,
+    /// Already in the collection by provider id, or found twice.
+    pub skipped: usize,
+    /// Files that could not be read or parsed.
+    pub failed: Vec<String>,
+    /// Not added: titles that look like items already in the collection.
+    pub probable_duplicates: Vec<ImportDecision>,
+}
+
+fn attr(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.trim().to_string()))
+        .filter(|v| !v.is_empty())
+}
+
+/// "2019-05-24 21:03:11" or "2019-05-24" as epoch milliseconds (UTC).
+fn parse_date(s: &str) -> Option<i64> {
+    let (date, time) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+    let mut d = date.splitn(3, '-').map(|p| p.parse::<u32>().ok());
+    let (y, m, day) = (d.next()??, d.next()??, d.next()??);
+    let mut t = time.split(':').map(|p| p.parse::<i64>().unwrap_or(0));
+    let secs = t.next().unwrap_or(0) * 3600 + t.next().unwrap_or(0) * 60 + t.next().unwrap_or(0);
+    Some(crate::smart::days_from_civil(y as i64, m, day) * crate::database::DAY_MS + secs * 1000)
+}
+
+/// A movie or tvshow NFO as a new item; `None` for any other root element.
+pub fn parse(xml: &str) -> Result<Option<MediaItem>, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut item = MediaItem { id: new_id(), saved_at: Some(now_ms()), ..Default::default() };
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    // Type of the last uniqueid opened
+    let mut unique_type: Option<String> = None;
+    let mut poster = false;
+    let (mut year, mut premiered, mut last_played, mut play_count) = (None, None, None, 0u64);
+    let (mut directors, mut cast, mut tags, mut ids) = (Vec::new(), Vec::new(), Vec::new(), HashMap::new());
+    let mut watched = false;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                let name = e.local_name().as_ref().to_vec();
+                if path.is_empty() {
+                    item.media_type = match name.as_slice() {
+                        b"movie" => MediaType::Movie,
+                        b"tvshow" => MediaType::TvSeries,
+                        _ => return Ok(None),
+                    };
+                }
+                match name.as_slice() {
+                    b"uniqueid" => unique_type = Some(attr(&e, b"type").unwrap_or_else(|| "imdb".to_string())),
+                    b"thumb" => poster = matches!(attr(&e, b"aspect").as_deref(), None | Some("poster")),
+                    _ => {}
+                }
+                path.push(name);
+            }
+            Ok(Event::Text(t)) => {
+                let val = t.unescape().unwrap_or_default().trim().to_string();
+                // Only direct children of the root, plus actor names
+                match (path.len(), path.last().map(|n| n.as_slice())) {
+                    _ if val.is_empty() => {}
+                    (2, Some(b"title")) => item.title = val,
+                    (2, Some(b"plot")) => item.description = val,
+                    (2, Some(b"outline")) if item.description.is_empty() => item.description = val,
+                    (2, Some(b"year")) => year = Some(val),
+                    (2, Some(b"premiered")) | (2, Some(b"aired")) => premiered = Some(val),
+                    (2, Some(b"director")) => directors.push(val),
+                    (2, Some(b"studio")) if item.media_type == MediaType::TvSeries && directors.is_empty() => directors.push(val),
+                    (2, Some(b"tag")) => tags.push(val),
+                    (2, Some(b"userrating")) => item.user_rating = val.parse::<f32>().ok().filter(|r| *r > 0.0),
+                    (2, Some(b"playcount")) => play_count = val.parse().unwrap_or(0),
+                    (2, Some(b"watched")) => watched = val.eq_ignore_ascii_case("true"),
+                    (2, Some(b"lastplayed")) => last_played = parse_date(&val),
+                    (2, Some(b"thumb")) if poster && item.poster_url.is_none() && val.starts_with("http") => item.poster_url = Some(val),
+                    (2, Some(b"uniqueid")) => {
+                        if let Some(key) = unique_type.as_deref().and_then(|t| external_key(t, &item.media_type)) {
+                            ids.insert(key.to_string(), val);
+                        }
+                    }
+                    (3, Some(b"name")) if path[1] == b"actor" => cast.push(val),
+                    _ => {}
+                }
+            }
+            Ok(Event::End(_)) => {
+                path.pop();
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(format!("Invalid NFO: {}", e)),
+            _ => {}
+        }
+        buf.clear();
+    }
+    if item.title.is_empty() {
+        return Err("NFO has no title".to_string());
+    }
+    item.release_date = premiered.filter(|p| !p.is_empty()).or(year).unwrap_or_default();
+    item.director_or_author = directors.join(", ");
+    item.cast = (!cast.is_empty()).then_some(cast);
+    item.tags = (!tags.is_empty()).then_some(tags);
+    item.provider_ids = (!ids.is_empty()).then_some(ids);
+    if play_count > 0 || watched {
+        item.category = Some(CollectionCategory::Watched);
+        item.completions = last_played.map(|at| Completion { at, rating: None }).into_iter().collect();
+    } else {
+        item.category = Some(CollectionCategory::ToWatch);
+    }
+    Ok(Some(item))
+}
+
+/// Items for the movie and tvshow NFOs under `root`, with the files that failed.
+pub fn scan(root: &Path) -> Result<(Vec<MediaItem>, Vec<String>), String> {
+    if !root.is_dir() {
+        return Err(format!("Not a folder: {}", root.display()));
+    }
+    let (mut items, mut failed) = (Vec::new(), Vec::new());
+    for file in crate::media_scan::files_under(root, &["nfo"]) {
+        let parsed = match std::fs::metadata(&file) {
+            Ok(m) if m.len() > MAX_FILE_BYTES => Err("File too large".to_string()),
+            _ => std::fs::read(&file).map_err(|e| e.to_string()).and_then(|bytes| parse(&String::from_utf8_lossy(&bytes))),
+        };
+        match parsed {
+            Ok(Some(item)) => items.push(item),
+            Ok(None) => {}
+            Err(e) => failed.push(format!("{}: {}", file.display(), e)),
+        }
+    }
+    Ok((items, failed))
+}
+
+fn element(out: &mut String, name: &str, value: &str) {
+    if !value.trim().is_empty() {
+        out.push_str(&format!("  <{0}>{1}</{0}>\n", name, escape(value.trim())));
+    }
+}
+
+/// The item as a Kodi NFO document; only movies and TV series have one.
+pub fn to_nfo(item: &MediaItem) -> Result<String, String> {
+    let root = match item.media_type {
+        MediaType::Movie => "movie",
+        MediaType::TvSeries => "tvshow",
+        _ => return Err("Only movies and TV series can be exported as NFO".to_string()),
+    };
+    let mut out = format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\" ?>\n<{}>\n", root);
+    element(&mut out, "title", &item.title);
+    element(&mut out, "plot", &item.description);
+    if let Some(year) = crate::dedupe::extract_year(&item.release_date) {
+        element(&mut out, "year", &year.to_string());
+    }
+    if parse_date(&item.release_date).is_some() {
+        element(&mut out, if root == "movie" { "premiered" } else { "aired" }, &item.release_date);
+    }
+    let creator_tag = if root == "movie" { "director" } else { "studio" };
+    for name in item.director_or_author.split(',') {
+        element(&mut out, creator_tag, name);
+    }
+    let mut ids: Vec<_> = item.provider_ids.iter().flatten().collect();
+    ids.sort();
+    for (key, id) in ids {
+        let kodi = match key.as_str() {
+            "imdb" => "imdb",
+            "tmdb" | "tmdbTv" => "tmdb",
+            "tvdb" => "tvdb",
+            _ => continue,
+        };
+        let default = if kodi == "imdb" || (kodi == "tvdb" && root == "tvshow") { " default=\"true\"" } else { "" };
+        out.push_str(&format!("  <uniqueid type=\"{}\"{}>{}</uniqueid>\n", kodi, default, escape(id)));
+    }
+    if let Some(poster) = item.custom_poster_url.as_ref().or(item.poster_url.as_ref()).filter(|u| u.starts_with("http")) {
+        out.push_str(&format!("  <thumb aspect=\"poster\">{}</thumb>\n", escape(poster)));
+    }
+    if let Some(rating) = item.user_rating {
+        element(&mut out, "userrating", &(rating.round() as i64).to_string());
+    }
+    let watched = item.category == Some(CollectionCategory::Watched);
+    element(&mut out, "playcount", &(if watched { item.completions.len().max(1) } else { 0 }).to_string());
+    if let Some(last) = item.completions.last().filter(|_| watched) {
+        let (y, m, d) = crate::updates::civil_from_days(last.at.div_euclid(crate::database::DAY_MS));
+        let secs = last.at.rem_euclid(crate::database::DAY_MS) / 1000;
+        element(&mut out, "lastplayed", &format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, m, d, secs / 3600, secs / 60 % 60, secs % 60));
+    }
+    for tag in item.tags.iter().flatten() {
+        element(&mut out, "tag", tag);
+    }
+    for name in item.cast.iter().flatten() {
+        out.push_str(&format!("  <actor>\n    <name>{}</name>\n  </actor>\n", escape(name)));
+    }
+    out.push_str(&format!("</{}>\n", root));
+    Ok(out)
+}
+
+/// Writes the item's NFO to `path`: the file itself, or `movie.nfo`/`tvshow.nfo`
+/// when `path` is a folder. Returns the file written.
+pub fn export(item: &MediaItem, path: &Path) -> Result<PathBuf, String> {
+    let content = to_nfo(item)?;
+    let target = if path.is_dir() {
+        path.join(if item.media_type == MediaType::Movie { "movie.nfo" } else { "tvshow.nfo" })
+    } else {
+        path.to_path_buf()
+    };
+    if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&target, content).map_err(|e| e.to_string())?;
+    Ok(target)
+}
@@ -0,0 +1,82 @@
+// Markdown notes on items, several per item. Images pasted or picked into a
+// note are stored content-addressed in `<app data>/attachments` and shown via
+// `mtnote://localhost/<file>`. A note keeps only the attachments its markdown
+// still mentions; files no note refers to any more are garbage collected.
+
+use std::collections::HashSet;
+use std::io::Read;
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde::{Deserialize, Serialize};
+use crate::images::ImageCache;
+
+pub const PROTOCOL: &str = "mtnote";
+const MAX_ATTACHMENT_BYTES: usize = 20 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Note {
+    pub id: String,
+    pub markdown: String,
+    /// Attachment file names, e.g. `<sha256>.png`.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    pub created_at: i64,
+    pub updated_at: Option<i64>,
+}
+
+impl Note {
+    /// Drops attachments the markdown no longer links to.
+    pub fn prune_attachments(&mut self) {
+        let markdown = &self.markdown;
+        self.attachments.retain(|f| markdown.contains(f.as_str()));
+    }
+}
+
+/// Attachment files; a separate store from the cover cache so the two are collected independently.
+pub struct Attachments(pub ImageCache);
+
+/// Reads an image from a file path or a `data:` URL and stores it; returns the file name.
+pub fn store(attachments: &Attachments, source: &str, now: i64) -> Result<String, String> {
+    let too_large = || "Image is too large".to_string();
+    let bytes = match source.strip_prefix("data:") {
+        Some(rest) => {
+            let (_, data) = rest.split_once(";base64,").ok_or_else(|| "Only base64 data URLs are supported".to_string())?;
+            if data.trim().len() / 4 * 3 > MAX_ATTACHMENT_BYTES + 2 {
+                return Err(too_large());
+            }
+            B64.decode(data.trim()).map_err(|e| e.to_string())?
+        }
+        None => {
+            // Never read more than the limit, whatever the file claims to be
+            let file = std::fs::File::open(source).map_err(|e| e.to_string())?;
+            if file.metadata().map_err(|e| e.to_string())?.len() > MAX_ATTACHMENT_BYTES as u64 {
+                return Err(too_large());
+            }
+            let mut bytes = Vec::new();
+            file.take(MAX_ATTACHMENT_BYTES as u64 + 1).read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+            bytes
+        }
+    };
+    if bytes.len() > MAX_ATTACHMENT_BYTES {
+        return Err(too_large());
+    }
+    let (content_type, ext) = crate::images::sniff(&bytes).ok_or_else(|| "Not an image".to_string())?;
+    // Only the file name is kept, not where the image came from
+    Ok(attachments.0.store(&bytes, content_type, ext, "", now)?.file)
+}
+
+/// Deletes attachment files not in `referenced`; returns how many were removed.
+pub fn collect_garbage(attachments: &Attachments, referenced: &HashSet<String>) -> Result<usize, String> {
+    let mut removed = 0;
+    for entry in std::fs::read_dir(attachments.0.dir()).map_err(|e| e.to_string())?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Thumbnails and temp files fail `path_of` and go with their image
+        if attachments.0.path_of(&name).is_none() || name.ends_with(".tmp") || referenced.contains(&name) {
+            continue;
+        }
+        if attachments.0.remove(&name) {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,32 @@
+// System notifications. A notification about an item carries the item's id and
+// owner as extras; when the user clicks it (or one of its actions) the plugin
+// reports `actionPerformed` to the frontend, which opens the item. Platforms
+// without a click callback simply don't navigate.
+
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+use crate::database::Database;
+
+fn show(app: &AppHandle, title: &str, body: &str, target: Option<(&str, &str)>) {
+    let mut builder = app.notification().builder().title(title).body(body);
+    if let Some((username, item_id)) = target {
+        builder = builder.extra("username", username).extra("itemId", item_id);
+    }
+    if let Err(e) = builder.show() {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
+
+/// Notification about one item (new episode, chapter or feed entry).
+pub async fn item(app: &AppHandle, db: &Database, username: &str, item_id: &str, title: &str, body: &str) {
+    if db.get_settings().await.notifications_enabled {
+        show(app, title, body, Some((username, item_id)));
+    }
+}
+
+/// Notification that is not tied to an item.
+pub async fn general(app: &AppHandle, db: &Database, title: &str, body: &str) {
+    if db.get_settings().await.notifications_enabled {
+        show(app, title, body, None);
+    }
+}
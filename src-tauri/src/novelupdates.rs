@@ -0,0 +1,131 @@
+// Light novels from NovelUpdates (novelupdates.com), which has no API: search
+// results and series pages are scraped, and the newest translated release on a
+// series page feeds the update checker the same way a new episode does. The
+// site is behind aggressive bot protection, so requests are spaced out.
+
+use std::time::Duration;
+use reqwest::Client;
+use serde::Serialize;
+use crate::rate_limit::RateLimiter;
+use crate::scrape::{attr_after, element, html_text, link_texts};
+
+const SITE: &str = "https://www.novelupdates.com";
+
+static LIMITER: RateLimiter = RateLimiter::new(Duration::from_secs(2));
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Novel {
+    /// Series slug, e.g. "omniscient-readers-viewpoint".
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub cover_url: Option<String>,
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    pub genres: Vec<String>,
+    pub year: Option<i32>,
+    /// Status in the country of origin, e.g. "551 Chapters (Completed)".
+    pub status: Option<String>,
+    /// Newest translated release, e.g. "c551" or "v3c12".
+    pub latest_release: Option<String>,
+}
+
+async fn get(client: &Client, url: &str) -> Result<String, String> {
+    LIMITER.wait().await;
+    let resp = tokio::time::timeout(Duration::from_secs(15), client.get(url).send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if matches!(resp.status().as_u16(), 403 | 503) {
+        return Err("NovelUpdates refused the request (bot protection); try again later or via a proxy".to_string());
+    }
+    if !resp.status().is_success() {
+        return Err(format!("NovelUpdates Error: {}", resp.status()));
+    }
+    resp.text().await.map_err(|e| e.to_string())
+}
+
+/// Slug from a series URL (`/series/<slug>/`) or a bare slug.
+pub fn series_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let slug = match input.split_once("/series/") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or(""),
+        None => input,
+    };
+    (!slug.is_empty() && slug.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')).then(|| slug.to_lowercase())
+}
+
+fn non_empty(s: String) -> Option<String> {
+    (!s.is_empty()).then_some(s)
+}
+
+pub async fn search(client: &Client, query: &str) -> Result<Vec<Novel>, String> {
+    let url = format!("{}/?s={}&post_type=seriesplans", SITE, urlencoding::encode(query));
+    let html = get(client, &url).await?;
+    let mut out = Vec::new();
+    for block in html.split("class=\"search_main_box_nu\"").skip(1) {
+        let Some(title_html) = element(block, "class=\"search_title\"") else {
+            continue;
+        };
+        let Some(href) = attr_after(title_html, "<a ", "href") else {
+            continue;
+        };
+        let Some(id) = series_id(&href) else {
+            continue;
+        };
+        let title = link_texts(title_html).into_iter().next().unwrap_or_default();
+        out.push(Novel {
+            url: format!("{}/series/{}/", SITE, id),
+            id,
+            title,
+            cover_url: attr_after(block, "<img", "src").filter(|s| s.starts_with("http") && !s.contains("noimagemid")),
+            description: element(block, "class=\"search_body_nu\"").map(|b| {
+                // The body repeats the title and genres before the blurb
+                let text = b.rsplit("</div>").next().unwrap_or(b);
+                html_text(text)
+            }).and_then(non_empty),
+            genres: element(block, "class=\"search_genre\"").map(link_texts).unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+    Ok(out)
+}
+
+/// Newest release in the series page's release table, e.g. "c551".
+fn latest_release(html: &str) -> Option<String> {
+    let at = html.find("class=\"chp-release\"")?;
+    let rest = &html[at..];
+    let text = html_text(rest[rest.find('>')? + 1..].split("</a>").next()?);
+    non_empty(text).or_else(|| attr_after(rest, "class=", "title"))
+}
+
+pub fn parse_series(id: &str, html: &str) -> Novel {
+    Novel {
+        id: id.to_string(),
+        title: element(html, "class=\"seriestitlenu\"").map(html_text).unwrap_or_default(),
+        url: format!("{}/series/{}/", SITE, id),
+        cover_url: element(html, "class=\"seriesimg\"").and_then(|b| attr_after(b, "<img", "src")).filter(|s| !s.contains("noimagefound")),
+        description: element(html, "id=\"editdescription\"").map(html_text).and_then(non_empty),
+        authors: element(html, "id=\"showauthors\"").map(link_texts).unwrap_or_default(),
+        genres: element(html, "id=\"seriesgenre\"").map(link_texts).unwrap_or_default(),
+        year: element(html, "id=\"edityear\"").and_then(|y| html_text(y).get(..4).and_then(|y| y.parse().ok())),
+        status: element(html, "id=\"editstatus\"").map(html_text).and_then(non_empty),
+        latest_release: latest_release(html),
+    }
+}
+
+pub async fn details(client: &Client, id: &str) -> Result<Novel, String> {
+    let id = series_id(id).ok_or_else(|| "Invalid NovelUpdates series".to_string())?;
+    let html = get(client, &format!("{}/series/{}/", SITE, id)).await?;
+    let novel = parse_series(&id, &html);
+    if novel.title.is_empty() {
+        return Err("Series not found".to_string());
+    }
+    Ok(novel)
+}
+
+/// Newest translated release, for the update checker.
+pub async fn latest(client: &Client, id: &str) -> Result<Option<String>, String> {
+    Ok(details(client, id).await?.latest_release)
+}
@@ -0,0 +1,53 @@
+// Manual order and pinned items per view ("all", a collection id, a smart
+// list id, ... as the frontend names them). Each view is one record with its
+// own timestamp, so paired devices exchange and merge whole views, newest wins.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewOrder {
+    /// Item id -> position; items not listed go after the ordered ones.
+    #[serde(default)]
+    pub positions: HashMap<String, u32>,
+    /// Pinned items, shown first in this order.
+    #[serde(default)]
+    pub pinned: Vec<String>,
+    pub updated_at: i64,
+}
+
+impl ViewOrder {
+    pub fn set_order(&mut self, ids: &[String], now: i64) {
+        self.positions = ids.iter().enumerate().map(|(n, id)| (id.clone(), n as u32)).collect();
+        self.updated_at = now;
+    }
+
+    /// Returns whether anything changed.
+    pub fn set_pinned(&mut self, id: &str, pinned: bool, now: i64) -> bool {
+        let was = self.pinned.iter().any(|p| p == id);
+        match (was, pinned) {
+            (false, true) => self.pinned.push(id.to_string()),
+            (true, false) => self.pinned.retain(|p| p != id),
+            _ => return false,
+        }
+        self.updated_at = now;
+        true
+    }
+}
+
+pub fn valid_view(view: &str) -> Result<(), String> {
+    if view.trim().is_empty() || view.len() > 200 {
+        return Err("Invalid view".to_string());
+    }
+    Ok(())
+}
+
+/// Takes the views `incoming` has newer copies of.
+pub fn merge(local: &mut HashMap<String, ViewOrder>, incoming: HashMap<String, ViewOrder>) {
+    for (view, order) in incoming {
+        if local.get(&view).map(|l| order.updated_at > l.updated_at).unwrap_or(true) {
+            local.insert(view, order);
+        }
+    }
+}
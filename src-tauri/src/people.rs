@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::dedupe::{normalize_title, title_similarity};
+use crate::models::{CollectionCategory, MediaItem};
+
+/// Provider details remembered for a person between lookups.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonMeta {
+    #[serde(default)]
+    pub provider_ids: HashMap<String, String>,
+    pub image_url: Option<String>,
+    /// Set while the user follows this person.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow: Option<crate::follows::FollowState>,
+}
+
+/// A director/author/actor, derived from the items' creator and cast fields.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Person {
+    pub id: String,
+    pub name: String,
+    pub roles: Vec<String>,
+    pub item_ids: Vec<String>,
+    pub provider_ids: HashMap<String, String>,
+    pub image_url: Option<String>,
+    pub followed: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonDetail {
+    pub person: Person,
+    pub items: Vec<MediaItem>,
+}
+
+/// One credit from the provider's filmography/bibliography, flagged against the collection.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonWork {
+    pub title: String,
+    pub release_date: String,
+    pub media_type: Option<String>,
+    pub role: Option<String>,
+    pub poster_url: Option<String>,
+    pub source: String,
+    pub source_id: String,
+    pub in_collection: bool,
+    pub completed: bool,
+}
+
+pub fn person_id(name: &str) -> String {
+    normalize_title(name)
+}
+
+fn split_names(s: &str) -> Vec<String> {
+    s.split([',', '/', '、', '&', ';', '，'])
+        .map(|n| n.trim())
+        .filter(|n| !n.is_empty())
+        .map(|n| n.to_string())
+        .collect()
+}
+
+pub fn collect_people(items: &[MediaItem], meta: &HashMap<String, PersonMeta>) -> Vec<Person> {
+    let mut people: Vec<Person> = Vec::new();
+    let mut add = |name: String, role: &str, item_id: &str| {
+        let id = person_id(&name);
+        if id.is_empty() {
+            return;
+        }
+        let p = match people.iter_mut().position(|p| p.id == id) {
+            Some(idx) => &mut people[idx],
+            None => {
+                let m = meta.get(&id).cloned().unwrap_or_default();
+                let followed = m.follow.is_some();
+                people.push(Person { id, name, roles: Vec::new(), item_ids: Vec::new(), provider_ids: m.provider_ids, image_url: m.image_url, followed });
+                people.last_mut().unwrap()
+            }
+        };
+        if !p.roles.iter().any(|r| r == role) {
+            p.roles.push(role.to_string());
+        }
+        if !p.item_ids.iter().any(|i| i == item_id) {
+            p.item_ids.push(item_id.to_string());
+        }
+    };
+    for item in items {
+        for name in split_names(&item.director_or_author) {
+            add(name, "creator", &item.id);
+        }
+        for name in item.cast.iter().flatten() {
+            add(name.trim().to_string(), "cast", &item.id);
+        }
+    }
+    people
+}
+
+// Marks works already in the collection (and finished) by fuzzy title match
+fn annotate(mut work: PersonWork, items: &[MediaItem]) -> PersonWork {
+    if let Some(item) = items.iter().find(|i| title_similarity(&i.title, &work.title) >= 0.9) {
+        work.in_collection = true;
+        work.completed = item.category == Some(CollectionCategory::Watched);
+    }
+    work
+}
+
+pub async fn tmdb_person_works(
+    client: &Client,
+    name: &str,
+    known_id: Option<&str>,
+    api_key: &str,
+    items: &[MediaItem],
+) -> Result<(String, Vec<PersonWork>), String> {
+    let tmdb_id = match known_id {
+        Some(id) => id.to_string(),
+        None => {
+            let url = format!(
+                "https://api.themoviedb.org/3/search/person?api_key={}&query={}",
+                urlencoding::encode(api_key),
+                urlencoding::encode(name)
+            );
+            let v = crate::fetch_json(client, &url).await?;
+            v["results"][0]["id"].as_u64().ok_or("Person not found on TMDB")?.to_string()
+        }
+    };
+    let url = format!(
+        "https://api.themoviedb.org/3/person/{}/combined_credits?api_key={}",
+        tmdb_id,
+        urlencoding::encode(api_key)
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    let mut works: Vec<PersonWork> = Vec::new();
+    for (list, role_key) in [("crew", "job"), ("cast", "character")] {
+        for c in v[list].as_array().into_iter().flatten() {
+            let source_id = c["id"].as_u64().map(|i| i.to_string()).unwrap_or_default();
+            if source_id.is_empty() || works.iter().any(|w| w.source_id == source_id) {
+                continue;
+            }
+            let work = PersonWork {
+                title: c["title"].as_str().or(c["name"].as_str()).unwrap_or("").to_string(),
+                release_date: c["release_date"].as_str().or(c["first_air_date"].as_str()).unwrap_or("").to_string(),
+                media_type: c["media_type"].as_str().map(|s| s.to_string()),
+                role: c[role_key].as_str().filter(|s| !s.is_empty()).map(|s| s.to_string()),
+                poster_url: c["poster_path"].as_str().map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
+                source: "tmdb".to_string(),
+                source_id,
+                in_collection: false,
+                completed: false,
+            };
+            works.push(annotate(work, items));
+        }
+    }
+    works.sort_by(|a, b| b.release_date.cmp(&a.release_date));
+    Ok((tmdb_id, works))
+}
+
+pub async fn bangumi_person_works(
+    client: &Client,
+    name: &str,
+    known_id: Option<&str>,
+    items: &[MediaItem],
+) -> Result<(String, Vec<PersonWork>), String> {
+    let bgm_id = match known_id {
+        Some(id) => id.to_string(),
+        None => {
+            let fut = client
+                .post("https://api.bgm.tv/v0/search/persons")
+                .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
+                .json(&serde_json::json!({ "keyword": name }))
+                .send();
+            let resp = tokio::time::timeout(std::time::Duration::from_secs(15), fut)
+                .await
+                .map_err(|_| "Request timed out".to_string())?
+                .map_err(|e| e.to_string())?;
+            let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+            v["data"][0]["id"].as_u64().ok_or("Person not found on Bangumi")?.to_string()
+        }
+    };
+    let url = format!("https://api.bgm.tv/v0/persons/{}/subjects", bgm_id);
+    let v = crate::fetch_json(client, &url).await?;
+    let works = v
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|s| {
+            let title = s["name_cn"].as_str().filter(|t| !t.is_empty()).or(s["name"].as_str()).unwrap_or("");
+            let work = PersonWork {
+                title: title.to_string(),
+                release_date: String::new(),
+                media_type: s["type"].as_u64().map(|t| t.to_string()),
+                role: s["staff"].as_str().map(|r| r.to_string()),
+                poster_url: s["image"].as_str().filter(|u| !u.is_empty()).map(|u| u.to_string()),
+                source: "bangumi".to_string(),
+                source_id: s["id"].as_u64().map(|i| i.to_string()).unwrap_or_default(),
+                in_collection: false,
+                completed: false,
+            };
+            annotate(work, items)
+        })
+        .collect();
+    Ok((bgm_id, works))
+}
+
+/// An author's books on Google Books, newest first.
+pub async fn google_books_person_works(client: &Client, name: &str, items: &[MediaItem]) -> Result<Vec<PersonWork>, String> {
+    let url = format!(
+        "https://www.googleapis.com/books/v1/volumes?q={}&orderBy=newest&maxResults=20&printType=books",
+        urlencoding::encode(&format!("inauthor:\"{}\"", name))
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    let works = v["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|volume| {
+            let info = &volume["volumeInfo"];
+            let work = PersonWork {
+                title: info["title"].as_str().filter(|t| !t.trim().is_empty())?.trim().to_string(),
+                release_date: info["publishedDate"].as_str().unwrap_or("").to_string(),
+                media_type: Some("book".to_string()),
+                role: None,
+                poster_url: info["imageLinks"]["thumbnail"].as_str().map(|u| u.replacen("http://", "https://", 1)),
+                source: "googleBooks".to_string(),
+                source_id: volume["id"].as_str()?.to_string(),
+                in_collection: false,
+                completed: false,
+            };
+            Some(annotate(work, items))
+        })
+        .collect();
+    Ok(works)
+}
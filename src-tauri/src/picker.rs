@@ -0,0 +1,79 @@
+// "What should I watch tonight": a weighted random pick among the items the
+// user hasn't finished, optionally narrowed by type, tag or runtime and
+// tilted toward the better-rated ones.
+
+use serde::{Deserialize, Serialize};
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+use crate::statuses::{StatusDef, StatusKind};
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PickFilters {
+    pub media_type: Option<MediaType>,
+    /// Item must have at least one of these tags; empty for any.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Minutes, from the `runtime` custom field; items without one still qualify.
+    pub max_runtime: Option<f64>,
+    /// Higher-rated items are picked more often (up to ten times as often).
+    #[serde(default)]
+    pub prefer_rated: bool,
+    /// Also consider items already started.
+    #[serde(default)]
+    pub include_in_progress: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Pick {
+    pub item: MediaItem,
+    /// How many items matched the filters.
+    pub candidates: usize,
+}
+
+fn unwatched(item: &MediaItem, statuses: &[StatusDef], include_in_progress: bool) -> bool {
+    match item.status.as_deref().and_then(|s| crate::statuses::find(statuses, s)) {
+        Some(s) => s.kind == StatusKind::Planned || (include_in_progress && s.kind == StatusKind::InProgress),
+        None => item.category == Some(CollectionCategory::ToWatch),
+    }
+}
+
+fn weight(item: &MediaItem, prefer_rated: bool) -> f64 {
+    if !prefer_rated {
+        return 1.0;
+    }
+    let score = item.aggregate_rating.or(item.user_rating.map(f64::from)).unwrap_or(0.0).clamp(0.0, 10.0) / 10.0;
+    1.0 + 9.0 * score * score
+}
+
+fn matches(item: &MediaItem, statuses: &[StatusDef], filters: &PickFilters) -> bool {
+    if item.is_collection == Some(true) || !unwatched(item, statuses, filters.include_in_progress) {
+        return false;
+    }
+    if filters.media_type.as_ref().map(|t| &item.media_type != t).unwrap_or(false) {
+        return false;
+    }
+    if !filters.tags.is_empty() {
+        let tags = item.tags.as_deref().unwrap_or(&[]);
+        if !filters.tags.iter().any(|f| tags.iter().any(|t| t.eq_ignore_ascii_case(f.trim()))) {
+            return false;
+        }
+    }
+    let runtime = crate::stats::number(item, crate::stats::RUNTIME_FIELD);
+    !matches!(filters.max_runtime, Some(max) if runtime > max)
+}
+
+/// Picks one item; `roll` is uniform in `[0, 1)`.
+pub fn pick(items: &[MediaItem], statuses: &[StatusDef], filters: &PickFilters, roll: f64) -> Option<Pick> {
+    let candidates: Vec<(&MediaItem, f64)> =
+        items.iter().filter(|i| matches(i, statuses, filters)).map(|i| (i, weight(i, filters.prefer_rated))).collect();
+    let total: f64 = candidates.iter().map(|(_, w)| w).sum();
+    let mut target = roll.clamp(0.0, 1.0) * total;
+    for (item, w) in &candidates {
+        if target < *w {
+            return Some(Pick { item: (*item).clone(), candidates: candidates.len() });
+        }
+        target -= w;
+    }
+    candidates.last().map(|(item, _)| Pick { item: (*item).clone(), candidates: candidates.len() })
+}
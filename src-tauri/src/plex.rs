@@ -0,0 +1,109 @@
+// Watched state from a Plex Media Server. The movie and show libraries are read
+// with the account token (X-Plex-Token) and matched to MediaItems as described in
+// `media_server`; the Plex GUID is kept as the "plex" provider id. The `plexSync`
+// job repeats the pass.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::media_server::{external_key, ServerItem, SyncSummary};
+use crate::models::MediaType;
+
+/// Provider id key of the Plex GUID.
+pub const KEY: &str = "plex";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PlexAccount {
+    /// e.g. "http://192.168.1.10:32400"
+    pub server_url: String,
+    pub last_synced_at: Option<i64>,
+}
+
+async fn get(client: &Client, server_url: &str, token: &str, path: &str) -> Result<Value, String> {
+    let url = format!("{}{}", server_url.trim().trim_end_matches('/'), path);
+    let request = client
+        .get(&url)
+        .header("Accept", "application/json")
+        .header("X-Plex-Token", token)
+        .header("X-Plex-Product", "MediaTracker")
+        .header("X-Plex-Client-Identifier", "mediatracker-desktop");
+    let resp = tokio::time::timeout(Duration::from_secs(30), request.send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    match resp.status().as_u16() {
+        401 => return Err("Plex rejected the token".to_string()),
+        _ if !resp.status().is_success() => return Err(format!("Plex Error: {}", resp.status())),
+        _ => {}
+    }
+    resp.json::<Value>().await.map_err(|e| e.to_string())
+}
+
+/// Plex "Guid" entries ("imdb://tt0133093", "tmdb://603", "tvdb://81189") under our keys.
+fn external_ids(v: &Value, media_type: &MediaType) -> HashMap<String, String> {
+    v["Guid"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|g| g["id"].as_str()?.split_once("://"))
+        .filter_map(|(scheme, id)| Some((external_key(scheme, media_type)?.to_string(), id.to_string())))
+        .collect()
+}
+
+fn count(v: &Value) -> u64 {
+    v.as_u64().or_else(|| v.as_str()?.parse().ok()).unwrap_or(0)
+}
+
+/// One entry of a library listing; movies and shows only.
+pub fn parse_item(v: &Value) -> Option<ServerItem> {
+    let media_type = match v["type"].as_str()? {
+        "movie" => MediaType::Movie,
+        "show" => MediaType::TvSeries,
+        _ => return None,
+    };
+    // Shows are watched once every episode is
+    let watched = match media_type {
+        MediaType::TvSeries => count(&v["leafCount"]) > 0 && count(&v["viewedLeafCount"]) >= count(&v["leafCount"]),
+        _ => count(&v["viewCount"]) > 0,
+    };
+    Some(ServerItem {
+        id: v["guid"].as_str().unwrap_or_default().to_string(),
+        title: v["title"].as_str()?.to_string(),
+        original_title: v["originalTitle"].as_str().filter(|t| !t.is_empty()).map(str::to_string),
+        year: v["year"].as_i64().map(|y| y as i32),
+        external_ids: external_ids(v, &media_type),
+        media_type,
+        watched,
+        last_viewed_at: v["lastViewedAt"].as_i64().filter(|t| *t > 0).map(|t| t * 1000),
+    })
+}
+
+/// Every movie and show in the server's movie and show libraries.
+pub async fn library(client: &Client, server_url: &str, token: &str) -> Result<Vec<ServerItem>, String> {
+    let sections = get(client, server_url, token, "/library/sections").await?;
+    let mut items = Vec::new();
+    for section in sections["MediaContainer"]["Directory"].as_array().into_iter().flatten() {
+        if !matches!(section["type"].as_str(), Some("movie") | Some("show")) {
+            continue;
+        }
+        let Some(key) = section["key"].as_str() else {
+            continue;
+        };
+        let listing = get(client, server_url, token, &format!("/library/sections/{}/all?includeGuids=1", urlencoding::encode(key))).await?;
+        items.extend(listing["MediaContainer"]["Metadata"].as_array().into_iter().flatten().filter_map(parse_item));
+    }
+    Ok(items)
+}
+
+/// Syncs watched state for every linked server.
+pub async fn reconcile(db: &crate::database::Database, client: &Client, token: &str) -> Result<SyncSummary, String> {
+    let mut total = SyncSummary::default();
+    for (username, account) in db.get_plex_accounts().await {
+        let items = library(client, &account.server_url, token).await?;
+        total.add(&db.apply_server_items(&username, KEY, &items).await);
+    }
+    Ok(total)
+}
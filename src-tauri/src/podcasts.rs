@@ -0,0 +1,141 @@
+// Podcast show metadata and artwork from PodcastIndex (when an API key and
+// secret are stored) or the keyless iTunes Search API. New episodes aren't
+// checked here: a followed show's RSS feed is attached to the item and the
+// feed poller picks up episodes like any other subscription.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::Client;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use serde::Serialize;
+use serde_json::Value;
+
+const PODCASTINDEX_API: &str = "https://api.podcastindex.org/api/1.0";
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Podcast {
+    /// "podcastIndex" or "itunes".
+    pub source: String,
+    pub id: String,
+    pub title: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    pub artwork_url: Option<String>,
+    /// RSS feed to poll for new episodes.
+    pub feed_url: Option<String>,
+    pub url: Option<String>,
+    pub genres: Vec<String>,
+    pub language: Option<String>,
+    pub episode_count: Option<u32>,
+    /// Ids to store on the item: "itunes" and/or "podcastIndex".
+    pub provider_ids: HashMap<String, String>,
+}
+
+/// PodcastIndex credentials: the API key and secret issued together.
+pub struct Credentials {
+    pub key: String,
+    pub secret: String,
+}
+
+fn text(v: &Value) -> Option<String> {
+    v.as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+fn id_text(v: &Value) -> Option<String> {
+    v.as_u64().filter(|id| *id > 0).map(|id| id.to_string()).or_else(|| text(v))
+}
+
+async fn send(request: reqwest::RequestBuilder, service: &str) -> Result<Value, String> {
+    let resp = tokio::time::timeout(Duration::from_secs(12), request.send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("{} Error: {}", service, resp.status()));
+    }
+    resp.json::<Value>().await.map_err(|e| e.to_string())
+}
+
+fn from_itunes(v: &Value) -> Option<Podcast> {
+    let id = id_text(&v["collectionId"])?;
+    Some(Podcast {
+        source: "itunes".to_string(),
+        title: text(&v["collectionName"])?,
+        author: text(&v["artistName"]),
+        description: None,
+        artwork_url: text(&v["artworkUrl600"]).or_else(|| text(&v["artworkUrl100"])),
+        feed_url: text(&v["feedUrl"]),
+        url: text(&v["collectionViewUrl"]),
+        genres: v["genres"].as_array().into_iter().flatten().filter_map(text).filter(|g| g != "Podcasts").collect(),
+        language: None,
+        episode_count: v["trackCount"].as_u64().map(|n| n as u32),
+        provider_ids: HashMap::from([("itunes".to_string(), id.clone())]),
+        id,
+    })
+}
+
+fn from_podcastindex(v: &Value) -> Option<Podcast> {
+    let id = id_text(&v["id"])?;
+    let mut provider_ids = HashMap::from([("podcastIndex".to_string(), id.clone())]);
+    if let Some(itunes) = id_text(&v["itunesId"]) {
+        provider_ids.insert("itunes".to_string(), itunes);
+    }
+    Some(Podcast {
+        source: "podcastIndex".to_string(),
+        title: text(&v["title"])?,
+        author: text(&v["author"]).or_else(|| text(&v["ownerName"])),
+        description: text(&v["description"]),
+        artwork_url: text(&v["artwork"]).or_else(|| text(&v["image"])),
+        feed_url: text(&v["url"]),
+        url: text(&v["link"]),
+        genres: v["categories"].as_object().map(|c| c.values().filter_map(text).collect()).unwrap_or_default(),
+        language: text(&v["language"]),
+        episode_count: v["episodeCount"].as_u64().map(|n| n as u32),
+        provider_ids,
+        id,
+    })
+}
+
+/// PodcastIndex signs each request with sha1(key + secret + unix time).
+async fn podcastindex(client: &Client, path: &str, credentials: &Credentials) -> Result<Value, String> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|e| e.to_string())?.as_secs().to_string();
+    let signature: String = digest(&SHA1_FOR_LEGACY_USE_ONLY, format!("{}{}{}", credentials.key, credentials.secret, now).as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let request = client
+        .get(format!("{}{}", PODCASTINDEX_API, path))
+        .header("User-Agent", "MediaTracker-Rust/1.0 (https://github.com/yourrepo)")
+        .header("X-Auth-Key", &credentials.key)
+        .header("X-Auth-Date", &now)
+        .header("Authorization", signature);
+    send(request, "PodcastIndex").await
+}
+
+pub async fn search(client: &Client, query: &str, credentials: Option<&Credentials>) -> Result<Vec<Podcast>, String> {
+    if let Some(credentials) = credentials {
+        let v = podcastindex(client, &format!("/search/byterm?q={}&max=20", urlencoding::encode(query)), credentials).await?;
+        return Ok(v["feeds"].as_array().into_iter().flatten().filter_map(from_podcastindex).collect());
+    }
+    let url = format!("https://itunes.apple.com/search?media=podcast&entity=podcast&limit=20&term={}", urlencoding::encode(query));
+    let v = send(client.get(&url), "iTunes").await?;
+    Ok(v["results"].as_array().into_iter().flatten().filter_map(from_itunes).collect())
+}
+
+/// A show by iTunes id, or by PodcastIndex feed id when `source` is "podcastIndex".
+pub async fn details(client: &Client, source: &str, id: &str, credentials: Option<&Credentials>) -> Result<Podcast, String> {
+    if !id.chars().all(|c| c.is_ascii_digit()) || id.is_empty() {
+        return Err("Invalid podcast id".to_string());
+    }
+    let found = match (source, credentials) {
+        ("podcastIndex", Some(credentials)) => from_podcastindex(&podcastindex(client, &format!("/podcasts/byfeedid?id={}", id), credentials).await?["feed"]),
+        ("podcastIndex", None) => return Err("PodcastIndex API key and secret are not set".to_string()),
+        _ => {
+            let v = send(client.get(format!("https://itunes.apple.com/lookup?entity=podcast&id={}", id)), "iTunes").await?;
+            v["results"].as_array().into_iter().flatten().find_map(from_itunes)
+        }
+    };
+    found.ok_or_else(|| "Podcast not found".to_string())
+}
@@ -0,0 +1,212 @@
+// Quick-add overlay. A global shortcut (`Settings::quick_add_shortcut`) pops a
+// small always-on-top window where a title is typed; TMDB and Bangumi are asked
+// at the same time with a short timeout, and the picked result is saved as a To
+// Watch item without bringing up the main window. The overlay page
+// (src/pages/QuickAddPage.tsx) asks the main window for the session to use.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use crate::database::new_id;
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+
+pub const LABEL: &str = "quick-add";
+
+pub const DEFAULT_SHORTCUT: &str = "CommandOrControl+Shift+M";
+
+/// Slower providers are left out rather than waited for.
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(4);
+const MAX_HITS: usize = 8;
+
+/// A search result the overlay lists, and sends back when one is picked.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Hit {
+    /// Provider id key: "tmdb", "tmdbTv" or "bangumi".
+    pub source: String,
+    pub source_id: String,
+    pub title: String,
+    pub original_title: Option<String>,
+    pub release_date: String,
+    pub media_type: MediaType,
+    pub poster_url: Option<String>,
+    pub description: String,
+}
+
+/// Binds `shortcut` to the overlay, replacing the previous binding; an empty
+/// shortcut turns it off.
+pub fn register(app: &AppHandle, shortcut: &str) -> Result<(), String> {
+    let shortcuts = app.global_shortcut();
+    shortcuts.unregister_all().map_err(|e| e.to_string())?;
+    if shortcut.trim().is_empty() {
+        return Ok(());
+    }
+    shortcuts
+        .on_shortcut(shortcut.trim(), |app, _, event| {
+            if event.state() == ShortcutState::Pressed {
+                toggle(app);
+            }
+        })
+        .map_err(|e| format!("Invalid shortcut '{}': {}", shortcut, e))
+}
+
+/// Shows the overlay, creating it the first time.
+pub fn show(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+    let built = WebviewWindowBuilder::new(app, LABEL, WebviewUrl::App("index.html#/quick-add".into()))
+        .title("Quick Add")
+        .inner_size(560.0, 380.0)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .center()
+        .focused(true)
+        .build();
+    if let Err(e) = built {
+        eprintln!("Failed to open quick-add window: {}", e);
+    }
+}
+
+pub fn hide(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        let _ = window.hide();
+    }
+}
+
+fn toggle(app: &AppHandle) {
+    let visible = app.get_webview_window(LABEL).and_then(|w| w.is_visible().ok()).unwrap_or(false);
+    if visible {
+        hide(app);
+    } else {
+        show(app);
+    }
+}
+
+fn text(v: &Value) -> Option<String> {
+    v.as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+pub fn parse_tmdb(v: &Value) -> Vec<Hit> {
+    v["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|r| {
+            let (source, media_type, title, original, date) = match r["media_type"].as_str()? {
+                "movie" => ("tmdb", MediaType::Movie, "title", "original_title", "release_date"),
+                "tv" => ("tmdbTv", MediaType::TvSeries, "name", "original_name", "first_air_date"),
+                _ => return None,
+            };
+            let title = text(&r[title])?;
+            Some(Hit {
+                source: source.to_string(),
+                source_id: r["id"].as_u64()?.to_string(),
+                original_title: text(&r[original]).filter(|o| *o != title),
+                title,
+                release_date: text(&r[date]).unwrap_or_default(),
+                media_type,
+                poster_url: text(&r["poster_path"]).map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
+                description: text(&r["overview"]).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+pub fn parse_bangumi(v: &Value) -> Vec<Hit> {
+    v["list"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| {
+            let media_type = match s["type"].as_u64()? {
+                1 => MediaType::Book,
+                2 | 6 => MediaType::TvSeries,
+                3 => MediaType::Music,
+                _ => return None,
+            };
+            let name = text(&s["name"])?;
+            let title = text(&s["name_cn"]).unwrap_or_else(|| name.clone());
+            Some(Hit {
+                source: "bangumi".to_string(),
+                source_id: s["id"].as_u64()?.to_string(),
+                original_title: Some(name).filter(|n| *n != title),
+                title,
+                release_date: text(&s["air_date"]).unwrap_or_default(),
+                media_type,
+                poster_url: text(&s["images"]["large"]).map(|u| u.replace("http://", "https://")),
+                description: text(&s["summary"]).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+async fn within_timeout(client: &Client, url: &str) -> Option<Value> {
+    match tokio::time::timeout(PROVIDER_TIMEOUT, crate::fetch_json(client, url)).await {
+        Ok(Ok(v)) => Some(v),
+        Ok(Err(e)) => {
+            eprintln!("Quick-add lookup failed: {}", e);
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// TMDB (when there is a key) and Bangumi results, taking turns so both show up
+/// near the top.
+pub async fn search(proxy: &Client, direct: &Client, query: &str, tmdb_api_key: Option<&str>) -> Vec<Hit> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let tmdb = async {
+        let key = tmdb_api_key?;
+        let url = format!(
+            "https://api.themoviedb.org/3/search/multi?api_key={}&query={}",
+            urlencoding::encode(key),
+            urlencoding::encode(query)
+        );
+        within_timeout(proxy, &url).await.map(|v| parse_tmdb(&v))
+    };
+    let bangumi = async {
+        let url = format!("https://api.bgm.tv/search/subject/{}?responseGroup=medium&max_results=10", urlencoding::encode(query));
+        within_timeout(direct, &url).await.map(|v| parse_bangumi(&v))
+    };
+    let (tmdb, bangumi) = tokio::join!(tmdb, bangumi);
+    let (mut tmdb, mut bangumi) = (tmdb.unwrap_or_default().into_iter(), bangumi.unwrap_or_default().into_iter());
+    let mut hits = Vec::new();
+    while hits.len() < MAX_HITS {
+        let (a, b) = (tmdb.next(), bangumi.next());
+        if a.is_none() && b.is_none() {
+            break;
+        }
+        hits.extend(a.into_iter().chain(b));
+    }
+    hits.truncate(MAX_HITS);
+    hits
+}
+
+/// The picked result as a new To Watch item.
+pub fn to_item(hit: &Hit, now: i64) -> MediaItem {
+    MediaItem {
+        id: new_id(),
+        title: hit.title.clone(),
+        description: hit.description.clone(),
+        release_date: hit.release_date.clone(),
+        media_type: hit.media_type.clone(),
+        category: Some(CollectionCategory::ToWatch),
+        status: Some("To Watch".to_string()),
+        saved_at: Some(now),
+        poster_url: hit.poster_url.clone(),
+        provider_ids: Some(HashMap::from([(hit.source.clone(), hit.source_id.clone())])),
+        ..Default::default()
+    }
+}
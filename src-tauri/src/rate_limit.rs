@@ -0,0 +1,31 @@
+// Request spacing for providers that throttle or block clients that ask too
+// often. Each provider keeps a static limiter; callers wait for their slot
+// before sending, so concurrent lookups queue up instead of bursting.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct RateLimiter {
+    min_gap: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub const fn new(min_gap: Duration) -> Self {
+        RateLimiter { min_gap, next: Mutex::new(None) }
+    }
+
+    /// Waits until the next request slot and books the one after it.
+    pub async fn wait(&self) {
+        let wait = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let slot = next.filter(|t| *t > now).unwrap_or(now);
+            *next = Some(slot + self.min_gap);
+            slot - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::models::{MediaItem, MediaType};
+use crate::scrape::{element, html_text};
+
+/// A score reported by one source, on that source's own scale (e.g. 86 of 100).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRating {
+    pub score: f64,
+    pub scale: f64,
+    pub votes: Option<u64>,
+    pub updated_at: Option<i64>,
+}
+
+impl SourceRating {
+    pub fn out_of_ten(&self) -> Option<f64> {
+        (self.scale > 0.0).then(|| self.score / self.scale * 10.0)
+    }
+}
+
+/// Mean of all source scores normalized to a 0-10 scale.
+pub fn aggregate(ratings: &HashMap<String, SourceRating>) -> Option<f64> {
+    let scores: Vec<f64> = ratings.values().filter_map(|r| r.out_of_ten()).collect();
+    if scores.is_empty() {
+        return None;
+    }
+    let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+    Some((mean * 10.0).round() / 10.0)
+}
+
+/// Keeps slots filled by other providers when an update only carries some sources,
+/// then recomputes the aggregate and the display string.
+pub fn merge_into_item(item: &mut MediaItem, previous: Option<&MediaItem>) {
+    if let Some(prev) = previous.and_then(|p| p.ratings.as_ref()) {
+        let slots = item.ratings.get_or_insert_with(HashMap::new);
+        for (source, r) in prev {
+            slots.entry(source.clone()).or_insert_with(|| r.clone());
+        }
+    }
+    let Some(slots) = item.ratings.as_ref().filter(|r| !r.is_empty()) else {
+        return;
+    };
+    item.aggregate_rating = aggregate(slots);
+    if let Some(agg) = item.aggregate_rating {
+        item.rating = Some(format!("{:.1}/10", agg));
+    }
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    s.trim().trim_end_matches('%').replace(',', "").parse::<f64>().ok()
+}
+
+/// Per-source ratings from an OMDb title response (IMDb, Rotten Tomatoes, Metacritic).
+pub fn from_omdb(v: &Value, now: i64) -> HashMap<String, SourceRating> {
+    let mut out = HashMap::new();
+    if let Some(score) = v["imdbRating"].as_str().and_then(parse_number) {
+        let votes = v["imdbVotes"].as_str().and_then(parse_number).map(|n| n as u64);
+        out.insert("imdb".to_string(), SourceRating { score, scale: 10.0, votes, updated_at: Some(now) });
+    }
+    for r in v["Ratings"].as_array().into_iter().flatten() {
+        let source = r["Source"].as_str().unwrap_or("");
+        let value = r["Value"].as_str().unwrap_or("");
+        let (key, parsed) = match source {
+            "Rotten Tomatoes" => ("rottenTomatoes", parse_number(value).map(|s| (s, 100.0))),
+            "Metacritic" => ("metacritic", value.split('/').next().and_then(parse_number).map(|s| (s, 100.0))),
+            _ => continue,
+        };
+        if let Some((score, scale)) = parsed {
+            out.insert(key.to_string(), SourceRating { score, scale, votes: None, updated_at: Some(now) });
+        }
+    }
+    out
+}
+
+/// IMDb, Rotten Tomatoes and Metacritic scores for an IMDb id, through OMDb.
+pub async fn omdb(client: &Client, imdb_id: &str, api_key: &str, now: i64) -> Result<HashMap<String, SourceRating>, String> {
+    let url = format!("https://www.omdbapi.com/?apikey={}&i={}", urlencoding::encode(api_key), urlencoding::encode(imdb_id.trim()));
+    let resp = tokio::time::timeout(Duration::from_secs(12), client.get(&url).send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("OMDb Error: {}", resp.status()));
+    }
+    let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+    if v["Response"].as_str() == Some("False") {
+        return Err(format!("OMDb Error: {}", v["Error"].as_str().unwrap_or("not found")));
+    }
+    Ok(from_omdb(&v, now))
+}
+
+/// The score on a Douban subject page; unrated subjects show none.
+pub fn from_douban_page(html: &str, now: i64) -> Option<SourceRating> {
+    let score = parse_number(&html_text(element(html, "property=\"v:average\"")?))?;
+    let votes = element(html, "property=\"v:votes\"").and_then(|v| parse_number(&html_text(v))).map(|n| n as u64);
+    Some(SourceRating { score, scale: 10.0, votes, updated_at: Some(now) })
+}
+
+/// Score of Douban subject `id`; books live on a different host than films and shows.
+pub async fn douban(client: &Client, id: &str, media_type: &MediaType, now: i64) -> Result<Option<SourceRating>, String> {
+    let host = if *media_type == MediaType::Book { "book" } else { "movie" };
+    let url = format!("https://{}.douban.com/subject/{}/", host, urlencoding::encode(id.trim()));
+    let resp = tokio::time::timeout(Duration::from_secs(12), client.get(&url).send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("Douban Error: {}", resp.status()));
+    }
+    let body = resp.text().await.map_err(|e| e.to_string())?;
+    Ok(from_douban_page(&body, now))
+}
+
+/// Slots the item's main metadata provider doesn't fill: OMDb (needs an IMDb id and
+/// a stored OMDb key) and Douban. `client` should be the direct one, as Douban
+/// throttles proxied traffic. Failures only leave their slots as they were.
+pub async fn fetch_extra(client: &Client, item: &MediaItem, now: i64) -> HashMap<String, SourceRating> {
+    let mut out = HashMap::new();
+    let Some(ids) = item.provider_ids.as_ref() else {
+        return out;
+    };
+    if let (Some(imdb_id), Some(key)) = (ids.get("imdb"), crate::secrets::resolve(None, crate::secrets::OMDB)) {
+        match omdb(client, imdb_id, &key, now).await {
+            Ok(found) => out.extend(found),
+            Err(e) => eprintln!("OMDb ratings failed for {}: {}", item.title, e),
+        }
+    }
+    if let Some(id) = ids.get("douban") {
+        match douban(client, id, &item.media_type, now).await {
+            Ok(Some(rating)) => {
+                out.insert("douban".to_string(), rating);
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Douban rating failed for {}: {}", item.title, e),
+        }
+    }
+    out
+}
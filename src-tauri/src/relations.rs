@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use crate::models::MediaItem;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RelationKind {
+    Sequel,
+    Prequel,
+    Remake,
+    Adaptation,
+    /// The inverse of `Remake` / `Adaptation`: the work the other one is based on.
+    Original,
+    SameFranchise,
+}
+
+impl RelationKind {
+    pub fn inverse(self) -> RelationKind {
+        match self {
+            RelationKind::Sequel => RelationKind::Prequel,
+            RelationKind::Prequel => RelationKind::Sequel,
+            RelationKind::Remake | RelationKind::Adaptation => RelationKind::Original,
+            RelationKind::Original => RelationKind::Adaptation,
+            RelationKind::SameFranchise => RelationKind::SameFranchise,
+        }
+    }
+}
+
+/// A typed edge read as "`from_id` is the `kind` of `to_id`" (e.g. A is the sequel of B).
+/// Only one edge is kept per pair of items.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Relation {
+    pub from_id: String,
+    pub to_id: String,
+    pub kind: RelationKind,
+    /// Provider that created the edge during auto-linking; None for manual edges.
+    pub source: Option<String>,
+    pub created_at: i64,
+}
+
+impl Relation {
+    pub fn connects(&self, a: &str, b: &str) -> bool {
+        (self.from_id == a && self.to_id == b) || (self.from_id == b && self.to_id == a)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedItem {
+    /// What `item` is relative to the queried item.
+    pub kind: RelationKind,
+    pub item: MediaItem,
+}
+
+/// Inserts or replaces the edge between the two items. Manual edges are not
+/// overwritten by auto-linked ones. Returns true if the graph changed.
+pub fn upsert(relations: &mut Vec<Relation>, rel: Relation) -> bool {
+    if let Some(existing) = relations.iter_mut().find(|r| r.connects(&rel.from_id, &rel.to_id)) {
+        if rel.source.is_some() && existing.source.is_none() {
+            return false;
+        }
+        let same = if existing.from_id == rel.from_id { existing.kind == rel.kind } else { existing.kind.inverse() == rel.kind };
+        if same {
+            return false;
+        }
+        *existing = rel;
+        return true;
+    }
+    relations.push(rel);
+    true
+}
+
+pub fn related_to(relations: &[Relation], items: &[MediaItem], id: &str) -> Vec<RelatedItem> {
+    relations
+        .iter()
+        .filter_map(|r| {
+            let (other, kind) = if r.to_id == id {
+                (&r.from_id, r.kind)
+            } else if r.from_id == id {
+                (&r.to_id, r.kind.inverse())
+            } else {
+                return None;
+            };
+            let item = items.iter().find(|i| i.id == *other)?.clone();
+            Some(RelatedItem { kind, item })
+        })
+        .collect()
+}
+
+/// Every item reachable from `id` through any relation, in release order.
+pub fn franchise(relations: &[Relation], items: &[MediaItem], id: &str) -> Vec<MediaItem> {
+    let mut seen: HashSet<&str> = HashSet::from([id]);
+    let mut queue: VecDeque<&str> = VecDeque::from([id]);
+    while let Some(cur) = queue.pop_front() {
+        for r in relations {
+            let next = if r.from_id == cur {
+                r.to_id.as_str()
+            } else if r.to_id == cur {
+                r.from_id.as_str()
+            } else {
+                continue;
+            };
+            if seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    let mut out: Vec<MediaItem> = items.iter().filter(|i| seen.contains(i.id.as_str())).cloned().collect();
+    out.sort_by(crate::dates::compare);
+    out
+}
+
+/// Drops edges whose endpoints no longer exist.
+pub fn prune(relations: &mut Vec<Relation>, items: &[MediaItem]) -> bool {
+    let before = relations.len();
+    relations.retain(|r| items.iter().any(|i| i.id == r.from_id) && items.iter().any(|i| i.id == r.to_id));
+    relations.len() != before
+}
+
+fn provider_id<'a>(item: &'a MediaItem, provider: &str) -> Option<&'a str> {
+    item.provider_ids.as_ref()?.get(provider).map(|s| s.as_str())
+}
+
+/// Links movies that belong to the same TMDB collection as `SameFranchise`.
+pub async fn tmdb_collection_links(client: &Client, items: &[MediaItem], api_key: &str, now: i64) -> Vec<Relation> {
+    let mut by_collection: HashMap<u64, Vec<&MediaItem>> = HashMap::new();
+    for item in items {
+        let Some(tmdb_id) = provider_id(item, "tmdb") else {
+            continue;
+        };
+        let url = format!(
+            "https://api.themoviedb.org/3/movie/{}?api_key={}",
+            urlencoding::encode(tmdb_id),
+            urlencoding::encode(api_key)
+        );
+        // A failed lookup only means this item stays unlinked
+        if let Ok(v) = crate::fetch_json(client, &url).await {
+            if let Some(cid) = v["belongs_to_collection"]["id"].as_u64() {
+                by_collection.entry(cid).or_default().push(item);
+            }
+        }
+    }
+    let mut out = Vec::new();
+    for members in by_collection.values() {
+        for pair in members.windows(2) {
+            out.push(Relation {
+                from_id: pair[1].id.clone(),
+                to_id: pair[0].id.clone(),
+                kind: RelationKind::SameFranchise,
+                source: Some("tmdb".to_string()),
+                created_at: now,
+            });
+        }
+    }
+    out
+}
+
+// Bangumi labels describe the related subject relative to the queried one,
+// e.g. "续集" means the related subject is the sequel.
+fn bangumi_kind(label: &str) -> Option<RelationKind> {
+    match label {
+        "续集" => Some(RelationKind::Sequel),
+        "前传" => Some(RelationKind::Prequel),
+        "改编" => Some(RelationKind::Adaptation),
+        "原作" => Some(RelationKind::Original),
+        "不同演绎" => Some(RelationKind::Remake),
+        "系列" | "主线故事" | "番外篇" | "相同世界观" | "衍生" => Some(RelationKind::SameFranchise),
+        _ => None,
+    }
+}
+
+/// Uses Bangumi subject relations between items that are both in the collection.
+pub async fn bangumi_links(client: &Client, items: &[MediaItem], now: i64) -> Vec<Relation> {
+    let by_bgm: HashMap<&str, &MediaItem> = items
+        .iter()
+        .filter_map(|i| provider_id(i, "bangumi").map(|b| (b, i)))
+        .collect();
+    let mut out = Vec::new();
+    for (bgm_id, item) in &by_bgm {
+        let url = format!("https://api.bgm.tv/v0/subjects/{}/subjects", bgm_id);
+        let Ok(v) = crate::fetch_json(client, &url).await else {
+            continue;
+        };
+        for rel in v.as_array().into_iter().flatten() {
+            let other_id = rel["id"].as_u64().map(|i| i.to_string()).unwrap_or_default();
+            let (Some(other), Some(kind)) = (by_bgm.get(other_id.as_str()), rel["relation"].as_str().and_then(bangumi_kind)) else {
+                continue;
+            };
+            out.push(Relation {
+                from_id: other.id.clone(),
+                to_id: item.id.clone(),
+                kind,
+                source: Some("bangumi".to_string()),
+                created_at: now,
+            });
+        }
+    }
+    out
+}
@@ -0,0 +1,140 @@
+// Yearly wrap-up: the year's statistics plus a few highlights, rendered as
+// markdown. `generate_year_review` can additionally have the configured AI
+// model write a short narrative from the same figures.
+
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::activity::{ActivityEvent, ActivityKind};
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+use crate::stats::Statistics;
+
+const TOP_RATED: usize = 5;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Highlight {
+    pub id: String,
+    pub title: String,
+    pub rating: Option<f32>,
+    /// Progress updates during the year, for `longest_series`.
+    pub episodes: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BusiestMonth {
+    /// `YYYY-MM`
+    pub month: String,
+    /// Episodes watched and items finished.
+    pub events: usize,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct YearReview {
+    pub year: i64,
+    pub stats: Statistics,
+    /// Items finished this year, best rated first.
+    pub highest_rated: Vec<Highlight>,
+    /// The series with the most episodes watched this year.
+    pub longest_series: Option<Highlight>,
+    pub most_binged_month: Option<BusiestMonth>,
+    /// Written by the AI model when one was configured.
+    pub narrative: Option<String>,
+    pub markdown: String,
+}
+
+fn highlight(item: &MediaItem, episodes: usize) -> Highlight {
+    Highlight { id: item.id.clone(), title: item.title.clone(), rating: item.user_rating, episodes }
+}
+
+pub fn build(year: i64, stats: Statistics, items: &[MediaItem], activity: &[ActivityEvent], utc_offset_minutes: i32) -> YearReview {
+    let (from, to) = (stats.from.unwrap_or(i64::MIN), stats.to.unwrap_or(i64::MAX));
+    let offset = utc_offset_minutes as i64 * 60_000;
+    let in_year: Vec<&ActivityEvent> = activity.iter().filter(|e| e.at >= from && e.at < to).collect();
+
+    let finished: Vec<&str> = in_year.iter().filter(|e| e.kind == ActivityKind::Finished).map(|e| e.item_id.as_str()).collect();
+    let mut rated: Vec<&MediaItem> = items
+        .iter()
+        .filter(|i| i.user_rating.is_some() && i.category == Some(CollectionCategory::Watched) && finished.contains(&i.id.as_str()))
+        .collect();
+    rated.sort_by(|a, b| b.user_rating.unwrap_or(0.0).total_cmp(&a.user_rating.unwrap_or(0.0)));
+
+    let mut episodes: HashMap<&str, usize> = HashMap::new();
+    let mut months: HashMap<String, usize> = HashMap::new();
+    for e in &in_year {
+        let watched = matches!(e.kind, ActivityKind::Started | ActivityKind::EpisodeWatched);
+        if watched {
+            *episodes.entry(e.item_id.as_str()).or_insert(0) += 1;
+        }
+        if watched || e.kind == ActivityKind::Finished {
+            let (y, m, _) = crate::updates::civil_from_days((e.at + offset).div_euclid(crate::database::DAY_MS));
+            *months.entry(format!("{:04}-{:02}", y, m)).or_insert(0) += 1;
+        }
+    }
+    let longest_series = items
+        .iter()
+        .filter(|i| matches!(i.media_type, MediaType::TvSeries | MediaType::ShortDrama | MediaType::Comic))
+        .filter_map(|i| episodes.get(i.id.as_str()).map(|n| (i, *n)))
+        .max_by_key(|(_, n)| *n)
+        .map(|(i, n)| highlight(i, n));
+    let most_binged_month = months
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        .map(|(month, events)| BusiestMonth { month, events });
+
+    let mut review = YearReview {
+        year,
+        stats,
+        highest_rated: rated.into_iter().take(TOP_RATED).map(|i| highlight(i, 0)).collect(),
+        longest_series,
+        most_binged_month,
+        ..Default::default()
+    };
+    review.markdown = markdown(&review);
+    review
+}
+
+/// The figures the AI narrative is written from.
+pub fn prompt(review: &YearReview) -> String {
+    format!(
+        "Write a warm, concise (under 200 words) year-in-review paragraph for someone's {} media diary. \
+         Use only these facts and don't invent titles:\n\n{}",
+        review.year, review.markdown
+    )
+}
+
+pub fn markdown(review: &YearReview) -> String {
+    let s = &review.stats;
+    let mut md = format!("# {} in review\n\n", review.year);
+    if let Some(narrative) = &review.narrative {
+        md.push_str(&format!("{}\n\n", narrative.trim()));
+    }
+    md.push_str(&format!("- Items: {}\n- Finished: {} ({:.0}%)\n", s.total_items, s.completed, s.completion_rate * 100.0));
+    if let Some(avg) = s.average_rating {
+        md.push_str(&format!("- Average rating: {:.1}\n", avg));
+    }
+    if s.seconds_tracked > 0 {
+        md.push_str(&format!("- Time tracked: {:.1} h\n", s.seconds_tracked as f64 / 3600.0));
+    }
+    for c in &s.by_type {
+        md.push_str(&format!("- {}: {}\n", c.key, c.count));
+    }
+    if !review.highest_rated.is_empty() {
+        md.push_str("\n## Highest rated\n\n");
+        for h in &review.highest_rated {
+            md.push_str(&format!("1. {} ({})\n", h.title, h.rating.unwrap_or(0.0)));
+        }
+    }
+    if let Some(h) = &review.longest_series {
+        md.push_str(&format!("\n## Longest series\n\n{} with {} episodes watched\n", h.title, h.episodes));
+    }
+    if let Some(m) = &review.most_binged_month {
+        md.push_str(&format!("\n## Most binged month\n\n{} ({} episodes and finishes)\n", m.month, m.events));
+    }
+    if !s.top_creators.is_empty() {
+        let names: Vec<&str> = s.top_creators.iter().take(5).map(|c| c.key.as_str()).collect();
+        md.push_str(&format!("\n## Favourite creators\n\n{}\n", names.join(", ")));
+    }
+    md
+}
@@ -0,0 +1,294 @@
+// Periodic background jobs. Schedules (enabled + interval) live in `Settings::jobs`;
+// the outcome of the last run of each job is persisted so restarts don't re-run
+// everything at once.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use crate::database::{now_ms, Database};
+
+const TICK: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    Backup,
+    UpdateCheck,
+    FeedPoll,
+    MetadataRefresh,
+    AutoSync,
+    CloudBackup,
+    GoalReminder,
+    LastfmRefresh,
+    PlexSync,
+    JellyfinSync,
+    CreatorWorks,
+}
+
+pub const ALL_JOBS: [JobKind; 11] = [
+    JobKind::Backup,
+    JobKind::UpdateCheck,
+    JobKind::FeedPoll,
+    JobKind::MetadataRefresh,
+    JobKind::AutoSync,
+    JobKind::CloudBackup,
+    JobKind::GoalReminder,
+    JobKind::LastfmRefresh,
+    JobKind::PlexSync,
+    JobKind::JellyfinSync,
+    JobKind::CreatorWorks,
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSchedule {
+    pub enabled: bool,
+    pub interval_minutes: u32,
+}
+
+impl JobKind {
+    pub fn default_schedule(self) -> JobSchedule {
+        let (enabled, interval_minutes) = match self {
+            JobKind::Backup => (true, 24 * 60),
+            JobKind::UpdateCheck => (true, 6 * 60),
+            JobKind::FeedPoll => (true, 60),
+            JobKind::MetadataRefresh => (false, 7 * 24 * 60),
+            JobKind::AutoSync => (false, 15),
+            JobKind::CloudBackup => (false, 24 * 60),
+            JobKind::GoalReminder => (true, 24 * 60),
+            JobKind::LastfmRefresh => (true, 24 * 60),
+            JobKind::PlexSync => (true, 6 * 60),
+            JobKind::JellyfinSync => (true, 6 * 60),
+            JobKind::CreatorWorks => (true, 24 * 60),
+        };
+        JobSchedule { enabled, interval_minutes }
+    }
+}
+
+/// Moves the interval settings the update checker and feed poller had before the
+/// scheduler into their job schedules; a schedule already set is kept. Returns
+/// true if anything was moved.
+pub fn migrate_legacy_intervals(settings: &mut crate::models::Settings) -> bool {
+    let legacy = [
+        (JobKind::UpdateCheck, settings.update_check_interval_minutes.take()),
+        (JobKind::FeedPoll, settings.feed_poll_interval_minutes.take()),
+    ];
+    let mut migrated = false;
+    for (kind, minutes) in legacy {
+        let Some(minutes) = minutes else {
+            continue;
+        };
+        let schedule = if minutes == 0 {
+            JobSchedule { enabled: false, ..kind.default_schedule() }
+        } else {
+            JobSchedule { enabled: true, interval_minutes: minutes }
+        };
+        settings.jobs.entry(kind).or_insert(schedule);
+        migrated = true;
+    }
+    migrated
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRun {
+    pub last_run_at: Option<i64>,
+    pub last_duration_ms: Option<i64>,
+    pub last_error: Option<String>,
+    pub last_result: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatus {
+    pub kind: JobKind,
+    #[serde(flatten)]
+    pub schedule: JobSchedule,
+    #[serde(flatten)]
+    pub run: JobRun,
+    pub running: bool,
+    pub next_run_at: Option<i64>,
+}
+
+/// Jobs currently executing, so a slow run is never started twice.
+#[derive(Default)]
+pub struct Scheduler {
+    running: Mutex<HashSet<JobKind>>,
+}
+
+impl Scheduler {
+    fn try_begin(&self, kind: JobKind) -> bool {
+        self.running.lock().unwrap().insert(kind)
+    }
+
+    fn finish(&self, kind: JobKind) {
+        self.running.lock().unwrap().remove(&kind);
+    }
+
+    fn is_running(&self, kind: JobKind) -> bool {
+        self.running.lock().unwrap().contains(&kind)
+    }
+}
+
+fn next_run(schedule: &JobSchedule, run: &JobRun) -> Option<i64> {
+    if !schedule.enabled || schedule.interval_minutes == 0 {
+        return None;
+    }
+    Some(run.last_run_at.map(|t| t + schedule.interval_minutes as i64 * 60_000).unwrap_or_else(now_ms))
+}
+
+pub async fn status(app: &AppHandle, db: &Database) -> Vec<JobStatus> {
+    let settings = db.get_settings().await;
+    let runs = db.get_job_runs().await;
+    let scheduler = app.state::<Scheduler>();
+    ALL_JOBS
+        .iter()
+        .map(|&kind| {
+            let schedule = settings.job(kind);
+            let run = runs.get(&kind).cloned().unwrap_or_default();
+            JobStatus { kind, next_run_at: next_run(&schedule, &run), schedule, run, running: scheduler.is_running(kind) }
+        })
+        .collect()
+}
+
+// Returns a short human-readable summary of what the job did
+async fn execute(app: &AppHandle, db: &Arc<Database>, kind: JobKind) -> Result<String, String> {
+    let client = app.state::<crate::AppState>().proxy_client.clone();
+    let interval_ms = db.get_settings().await.job(kind).interval_minutes as i64 * 60_000;
+    match kind {
+        JobKind::Backup => {
+            let path = db.write_snapshot_backup().await?;
+            Ok(format!("Saved {}", path.display()))
+        }
+        JobKind::UpdateCheck => {
+            // Skip items checked recently (e.g. by the frontend's own check)
+            let found = crate::updates::run_check(app, db, &client, interval_ms / 2, None).await;
+            Ok(format!("{} item(s) updated", found.len()))
+        }
+        JobKind::FeedPoll => {
+            let updates = crate::feeds::poll_all(app, db, &client, interval_ms / 2).await;
+            Ok(format!("{} feed(s) with new entries", updates.len()))
+        }
+        JobKind::MetadataRefresh => {
+            let mut ids_by_user: HashMap<String, Vec<String>> = HashMap::new();
+            for (username, item) in db.get_items_with_provider_ids().await {
+                ids_by_user.entry(username).or_default().push(item.id);
+            }
+            let control = app.state::<crate::metadata::RefreshControl>();
+            let (mut refreshed, mut failed) = (0, 0);
+            for (username, ids) in ids_by_user {
+                // A manual refresh has the control; the next run picks the rest up
+                if control.is_running() {
+                    return Ok(format!("{} item(s) refreshed, {} failed; skipped the rest while a manual refresh runs", refreshed, failed));
+                }
+                let summary = crate::metadata::refresh(app, db, &control, &client, &username, Some(ids), None).await?;
+                refreshed += summary.refreshed;
+                failed += summary.failed;
+            }
+            Ok(format!("{} item(s) refreshed, {} failed", refreshed, failed))
+        }
+        JobKind::AutoSync => {
+            let sync = app.state::<crate::sync::SyncService>();
+            let peers = sync.get_known_peers();
+            let (mut synced, mut attempted) = (0, 0);
+            for peer in &peers {
+                let (tried, ok) = crate::sync::auto_sync_peer(app, db, peer).await;
+                attempted += tried;
+                synced += ok;
+            }
+            Ok(format!("{} of {} paired sync(s) succeeded across {} peer(s)", synced, attempted, peers.len()))
+        }
+        JobKind::CloudBackup => {
+            let config = db.get_settings().await.s3_backup.ok_or_else(|| "Cloud backup is not configured".to_string())?;
+            let images = app.state::<crate::images::ImageCache>();
+            let uploaded = crate::cloud_backup::run(&client, db, &config, images.dir()).await?;
+            Ok(format!(
+                "Uploaded {} ({:.1} MB, {} part(s), {} cover(s)); removed {} old backup(s)",
+                uploaded.key,
+                uploaded.size as f64 / (1024.0 * 1024.0),
+                uploaded.parts,
+                uploaded.images,
+                uploaded.pruned
+            ))
+        }
+        JobKind::GoalReminder => {
+            let due = db.take_goal_reminders().await;
+            for (_, p) in &due {
+                let body = format!("{}: {} of {} so far, about {:.0} expected by now", p.goal.title, p.current, p.goal.target, p.expected);
+                crate::notify::general(app, db, "Goal behind pace", &body).await;
+            }
+            Ok(format!("{} goal(s) behind pace", due.len()))
+        }
+        JobKind::LastfmRefresh => {
+            if db.get_lastfm_accounts().await.is_empty() {
+                return Ok("No Last.fm accounts linked".to_string());
+            }
+            let key = crate::secrets::resolve(None, crate::secrets::LASTFM).ok_or_else(|| "Last.fm API key is not set".to_string())?;
+            let updated = crate::lastfm::refresh(db, &client, &key).await?;
+            Ok(format!("{} play count(s) updated", updated))
+        }
+        JobKind::PlexSync => {
+            if db.get_plex_accounts().await.is_empty() {
+                return Ok("No Plex server linked".to_string());
+            }
+            let token = crate::secrets::resolve(None, crate::secrets::PLEX).ok_or_else(|| "Plex token is not set".to_string())?;
+            // Plex servers are usually on the local network, so skip the proxy
+            let direct = app.state::<crate::AppState>().direct_client.clone();
+            let sync = crate::plex::reconcile(db, &direct, &token).await?;
+            Ok(format!("{} of {} Plex item(s) matched, {} updated", sync.matched, sync.scanned, sync.updated))
+        }
+        JobKind::JellyfinSync => {
+            if db.get_jellyfin_accounts().await.is_empty() {
+                return Ok("No Jellyfin server linked".to_string());
+            }
+            let api_key = crate::secrets::resolve(None, crate::secrets::JELLYFIN).ok_or_else(|| "Jellyfin API key is not set".to_string())?;
+            let direct = app.state::<crate::AppState>().direct_client.clone();
+            let sync = crate::jellyfin::reconcile(db, &direct, &api_key).await?;
+            Ok(format!("{} of {} Jellyfin item(s) matched, {} updated", sync.matched, sync.scanned, sync.updated))
+        }
+        JobKind::CreatorWorks => {
+            let found = crate::follows::check_all(app, db, &client).await?;
+            Ok(format!("{} new work(s) by followed people", found))
+        }
+    }
+}
+
+/// Runs a job now (unless it is already running) and records the outcome.
+pub async fn run_job(app: &AppHandle, db: &Arc<Database>, kind: JobKind) -> Result<JobRun, String> {
+    let scheduler = app.state::<Scheduler>();
+    if !scheduler.try_begin(kind) {
+        return Err("Job is already running".to_string());
+    }
+    let started = now_ms();
+    let result = execute(app, db, kind).await;
+    scheduler.finish(kind);
+    let run = JobRun {
+        last_run_at: Some(started),
+        last_duration_ms: Some(now_ms() - started),
+        last_error: result.as_ref().err().cloned(),
+        last_result: result.ok(),
+    };
+    db.record_job_run(kind, run.clone()).await;
+    Ok(run)
+}
+
+/// Spawns the scheduler loop that starts due jobs in the background.
+pub fn start(app: AppHandle, db: Arc<Database>) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(TICK).await;
+            let now = now_ms();
+            for job in status(&app, &db).await {
+                if job.running || job.next_run_at.map(|t| t > now).unwrap_or(true) {
+                    continue;
+                }
+                let (app, db) = (app.clone(), db.clone());
+                tauri::async_runtime::spawn(async move {
+                    let _ = run_job(&app, &db, job.kind).await;
+                });
+            }
+        }
+    });
+}
@@ -0,0 +1,395 @@
+// Turns an arbitrary web page into a MediaItem draft using the metadata most sites
+// already publish for link previews: OpenGraph / Twitter card <meta> tags and
+// schema.org JSON-LD. JSON-LD wins where both are present since it is typed.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use crate::database::now_ms;
+use crate::models::{MediaItem, MediaType};
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrapedPage {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    /// Raw og:type or JSON-LD @type, e.g. "video.movie" or "TVSeries".
+    pub kind: Option<String>,
+    pub release_date: Option<String>,
+    pub creator: Option<String>,
+    pub site_name: Option<String>,
+    /// Ready to edit and save; its id is fresh.
+    pub draft: MediaItem,
+}
+
+pub(crate) fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        // Entities are short; look no further than 10 characters for the ';'
+        let limit = rest.char_indices().nth(10).map(|(i, _)| i).unwrap_or(rest.len());
+        let Some(end) = rest[..limit].find(';') else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Visible text of an HTML fragment: tags dropped, entities decoded, whitespace collapsed.
+pub(crate) fn html_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                out.push(' ');
+            }
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    decode_entities(&out).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Inner HTML of the first element whose opening tag contains `marker`.
+pub(crate) fn element<'a>(html: &'a str, marker: &str) -> Option<&'a str> {
+    let at = html.find(marker)?;
+    let open_end = html[at..].find('>')? + at + 1;
+    let name_start = html[..at].rfind('<')? + 1;
+    let name: String = html[name_start..].chars().take_while(|c| c.is_ascii_alphanumeric()).collect();
+    let (open, close) = (format!("<{}", name), format!("</{}", name));
+    // Walk to the matching close tag so nested elements of the same kind are kept
+    let mut depth = 1;
+    let mut i = open_end;
+    loop {
+        let next_close = html[i..].find(&close)? + i;
+        match html[i..next_close].find(&open) {
+            Some(o) => {
+                depth += 1;
+                i += o + open.len();
+            }
+            None => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&html[open_end..next_close]);
+                }
+                i = next_close + close.len();
+            }
+        }
+    }
+}
+
+/// Text of every link in `html`.
+pub(crate) fn link_texts(html: &str) -> Vec<String> {
+    html.split("<a ").skip(1).filter_map(|a| a.split_once('>')).map(|(_, rest)| html_text(rest.split("</a>").next().unwrap_or(""))).filter(|t| !t.is_empty()).collect()
+}
+
+/// Quoted attribute `name` of the first tag starting at `marker`.
+pub(crate) fn attr_after(html: &str, marker: &str, name: &str) -> Option<String> {
+    let tag = &html[html.find(marker)?..];
+    let tag = &tag[..tag.find('>')?];
+    let key = format!("{}=\"", name);
+    let start = tag.find(&key)? + key.len();
+    Some(decode_entities(&tag[start..start + tag[start..].find('"')?]))
+}
+
+// Value of `name=` inside a single tag, quoted or bare. `tag_lower` is the
+// ASCII-lowercased tag, so byte offsets are shared.
+fn attr(tag: &str, tag_lower: &str, name: &str) -> Option<String> {
+    let skip_ws = |j: usize| j + tag_lower[j..].len() - tag_lower[j..].trim_start().len();
+    let mut from = 0;
+    while let Some(pos) = tag_lower[from..].find(name) {
+        let i = from + pos;
+        from = i + name.len();
+        let mut j = skip_ws(from);
+        if !tag_lower[..i].ends_with(char::is_whitespace) || !tag_lower[j..].starts_with('=') {
+            continue;
+        }
+        j = skip_ws(j + 1);
+        let raw = match tag_lower[j..].chars().next() {
+            Some(q @ ('"' | '\'')) => {
+                let end = tag_lower[j + 1..].find(q).map(|e| j + 1 + e).unwrap_or(tag.len());
+                &tag[j + 1..end]
+            }
+            _ => {
+                let end = tag_lower[j..].find(|c: char| c.is_whitespace() || c == '>').map(|e| j + e).unwrap_or(tag.len());
+                &tag[j..end]
+            }
+        };
+        return Some(decode_entities(raw));
+    }
+    None
+}
+
+/// `og:*`, `twitter:*` and other named <meta> tags, lowercased keys; first one wins.
+pub fn meta_tags(body: &str) -> HashMap<String, String> {
+    let lower = body.to_ascii_lowercase();
+    let mut out = HashMap::new();
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find("<meta") {
+        let start = from + pos;
+        let end = lower[start..].find('>').map(|e| start + e).unwrap_or(lower.len());
+        from = end;
+        let (tag, tag_lower) = (&body[start..end], &lower[start..end]);
+        let key = attr(tag, tag_lower, "property").or_else(|| attr(tag, tag_lower, "name"));
+        if let (Some(key), Some(content)) = (key, attr(tag, tag_lower, "content")) {
+            let content = content.trim().to_string();
+            if !content.is_empty() {
+                out.entry(key.to_ascii_lowercase()).or_insert(content);
+            }
+        }
+    }
+    out
+}
+
+fn html_title(body: &str) -> Option<String> {
+    let lower = body.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = decode_entities(body[start..end].trim());
+    (!title.is_empty()).then_some(title)
+}
+
+/// Every JSON object found in `<script type="application/ld+json">` blocks,
+/// with top-level arrays and `@graph` flattened.
+pub fn json_ld(body: &str) -> Vec<Value> {
+    let lower = body.to_ascii_lowercase();
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(pos) = lower[from..].find("application/ld+json") {
+        let at = from + pos;
+        let Some(start) = lower[at..].find('>').map(|e| at + e + 1) else {
+            break;
+        };
+        let Some(end) = lower[start..].find("</script").map(|e| start + e) else {
+            break;
+        };
+        from = end;
+        let Ok(v) = serde_json::from_str::<Value>(body[start..end].trim()) else {
+            continue;
+        };
+        let mut stack = vec![v];
+        while let Some(v) = stack.pop() {
+            match v {
+                Value::Array(items) => stack.extend(items.into_iter().rev()),
+                Value::Object(mut obj) => {
+                    if let Some(graph) = obj.remove("@graph") {
+                        stack.push(graph);
+                    }
+                    if obj.contains_key("@type") {
+                        out.push(Value::Object(obj));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+fn ld_type(v: &Value) -> Option<String> {
+    match &v["@type"] {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(types) => types.iter().find_map(|t| t.as_str().map(str::to_string)),
+        _ => None,
+    }
+}
+
+fn media_type_for(kind: &str) -> Option<MediaType> {
+    match kind.to_ascii_lowercase().as_str() {
+        "movie" | "video.movie" => Some(MediaType::Movie),
+        "tvseries" | "tvseason" | "tvepisode" | "video.tv_show" | "video.episode" => Some(MediaType::TvSeries),
+        "book" | "bookseries" => Some(MediaType::Book),
+        "comicseries" | "comicstory" | "comicissue" => Some(MediaType::Comic),
+        "musicalbum" | "musicrecording" | "musicplaylist" | "music.album" | "music.song" | "music.playlist" => Some(MediaType::Music),
+        _ => None,
+    }
+}
+
+// Strings, {name}/{url} objects, or arrays of either
+fn ld_text(v: &Value, key: &str) -> Option<String> {
+    let parts: Vec<String> = match v {
+        Value::String(s) => vec![s.clone()],
+        Value::Object(o) => o.get(key).and_then(Value::as_str).map(str::to_string).into_iter().collect(),
+        Value::Array(items) => items.iter().filter_map(|i| ld_text(i, key)).collect(),
+        _ => Vec::new(),
+    };
+    let parts: Vec<String> = parts.into_iter().map(|p| decode_entities(p.trim())).filter(|p| !p.is_empty()).collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+fn ld_first(v: &Value, key: &str) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Object(o) => o.get(key).and_then(|inner| ld_first(inner, key)),
+        Value::Array(items) => items.iter().find_map(|i| ld_first(i, key)),
+        _ => None,
+    }
+}
+
+fn ld_names(v: &Value) -> Option<Vec<String>> {
+    let names: Vec<String> = match v {
+        Value::Array(items) => items.iter().filter_map(|i| ld_text(i, "name")).collect(),
+        other => ld_text(other, "name").into_iter().collect(),
+    };
+    (!names.is_empty()).then_some(names)
+}
+
+/// Extracts page metadata from already fetched HTML. `url` resolves relative images.
+pub fn parse_page(url: &str, body: &str) -> ScrapedPage {
+    let meta = meta_tags(body);
+    let get = |keys: &[&str]| keys.iter().find_map(|k| meta.get(*k).cloned());
+    // Prefer a typed media entity over WebSite/Organization/BreadcrumbList noise
+    let entities = json_ld(body);
+    let ld = entities
+        .iter()
+        .find(|e| ld_type(e).and_then(|t| media_type_for(&t)).is_some())
+        .or_else(|| entities.iter().find(|e| ld_type(e).map(|t| t.ends_with("Work") || t == "Article" || t == "VideoObject").unwrap_or(false)));
+    let ld_get = |key: &str, inner: &str| ld.and_then(|e| ld_text(&e[key], inner));
+
+    let kind = ld.and_then(ld_type).or_else(|| get(&["og:type"]));
+    let title = ld_get("name", "name").or_else(|| get(&["og:title", "twitter:title"])).or_else(|| html_title(body));
+    let description = ld_get("description", "text").or_else(|| get(&["og:description", "twitter:description", "description"]));
+    let image = ld
+        .and_then(|e| ld_first(&e["image"], "url"))
+        .or_else(|| get(&["og:image", "og:image:url", "og:image:secure_url", "twitter:image", "twitter:image:src"]))
+        .map(|i| crate::resolve_url(url, &i))
+        .filter(|i| !i.is_empty());
+    let release_date = ["datePublished", "dateCreated", "startDate", "releasedEvent"]
+        .iter()
+        .find_map(|k| ld.and_then(|e| ld_first(&e[*k], "startDate")))
+        .or_else(|| get(&["video:release_date", "book:release_date", "music:release_date", "article:published_time"]))
+        .map(|d| d.chars().take(10).collect());
+    let creator = ["director", "author", "creator", "byArtist"]
+        .iter()
+        .find_map(|k| ld_get(k, "name"))
+        .or_else(|| get(&["book:author", "music:musician", "video:director"]));
+    let cast = ld.and_then(|e| ld_names(&e["actor"]));
+    let site_name = get(&["og:site_name", "application-name"]);
+
+    let draft = MediaItem {
+        id: crate::database::new_id(),
+        title: title.clone().unwrap_or_default(),
+        director_or_author: creator.clone().unwrap_or_default(),
+        description: description.clone().unwrap_or_default(),
+        release_date: release_date.clone().unwrap_or_default(),
+        media_type: kind.as_deref().and_then(media_type_for).unwrap_or_default(),
+        poster_url: image.clone(),
+        cast,
+        provider_ids: Some(provider_ids_from_url(url)).filter(|ids| !ids.is_empty()),
+        ..Default::default()
+    };
+    ScrapedPage { url: url.to_string(), title, description, image, kind, release_date, creator, site_name, draft }
+}
+
+/// Fetches a page and parses it. Redirects (short links, mobile pages) are followed
+/// and relative URLs resolve against the final page.
+pub async fn fetch_page(client: &Client, url: &str) -> Result<ScrapedPage, String> {
+    let fut = client.get(url).header("Accept", "text/html,application/xhtml+xml").send();
+    let resp = tokio::time::timeout(Duration::from_secs(15), fut)
+        .await
+        .map_err(|_| "Request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let final_url = resp.url().to_string();
+    let body = resp.text().await.map_err(|e| e.to_string())?;
+    Ok(parse_page(&final_url, &body))
+}
+
+/// Scrapes a link with the right client and, when the URL carries a Bangumi or
+/// TMDB id, fills the draft from the provider API (cleaner than page markup).
+pub async fn draft_for(app: &AppHandle, url: &str) -> Result<ScrapedPage, String> {
+    let state = app.state::<crate::AppState>();
+    let client = if crate::images::prefers_direct(url) { &state.direct_client } else { &state.proxy_client };
+    let mut page = fetch_page(client, url).await?;
+    let tmdb_key = crate::secrets::resolve(None, crate::secrets::TMDB);
+    if let Ok(Some(details)) = crate::metadata::fetch_details(&state.proxy_client, &page.draft, None, tmdb_key.as_deref(), now_ms()).await {
+        crate::metadata::apply_details(&mut page.draft, details);
+    }
+    Ok(page)
+}
+
+/// Provider ids implied by well-known detail page URLs (TMDB, Bangumi, IMDb, Douban, NovelUpdates, AO3).
+pub fn provider_ids_from_url(url: &str) -> HashMap<String, String> {
+    let mut ids = HashMap::new();
+    let after_scheme = url.split("://").nth(1).unwrap_or(url);
+    let (host, path) = after_scheme.split_once('/').unwrap_or((after_scheme, ""));
+    let host = host.to_ascii_lowercase();
+    let segments: Vec<&str> = path.split(['/', '?', '#']).filter(|s| !s.is_empty()).collect();
+    let id_after = |marker: &str| {
+        segments
+            .iter()
+            .position(|s| *s == marker)
+            .and_then(|i| segments.get(i + 1))
+            .map(|s| s.split('-').next().unwrap_or(s).to_string())
+            .filter(|s| !s.is_empty())
+    };
+    if host.ends_with("themoviedb.org") {
+        if let Some(id) = id_after("movie") {
+            ids.insert("tmdb".to_string(), id);
+        } else if let Some(id) = id_after("tv") {
+            ids.insert("tmdbTv".to_string(), id);
+        }
+    } else if host.ends_with("bgm.tv") || host.ends_with("bangumi.tv") || host.ends_with("chii.in") {
+        if let Some(id) = id_after("subject") {
+            ids.insert("bangumi".to_string(), id);
+        }
+    } else if host.ends_with("imdb.com") {
+        if let Some(id) = id_after("title").filter(|id| id.starts_with("tt")) {
+            ids.insert("imdb".to_string(), id);
+        }
+    } else if host.ends_with("douban.com") {
+        if let Some(id) = id_after("subject") {
+            ids.insert("douban".to_string(), id);
+        }
+    } else if host.ends_with("novelupdates.com") {
+        if let Some(id) = crate::novelupdates::series_id(url).filter(|_| segments.first() == Some(&"series")) {
+            ids.insert("novelUpdates".to_string(), id);
+        }
+    } else if host.ends_with("archiveofourown.org") {
+        if let Some(id) = id_after("works") {
+            ids.insert("ao3".to_string(), id);
+        }
+    }
+    ids
+}
@@ -0,0 +1,90 @@
+// API keys and tokens kept in the OS credential store (Keychain, Windows
+// Credential Manager, Secret Service) instead of the webview's localStorage.
+// Secrets are stored by name under the `MediaTracker` service; commands that
+// call a keyed service look the key up here when the frontend doesn't pass one.
+
+use keyring::Entry;
+
+const SERVICE: &str = "MediaTracker";
+
+/// Key for the AI chat provider.
+pub const AI: &str = "ai";
+/// BoardGameGeek application token.
+pub const BGG: &str = "bgg";
+/// Bilibili SESSDATA cookie (or the whole cookie string).
+pub const BILIBILI: &str = "bilibili";
+/// Jellyfin or Emby API key.
+pub const JELLYFIN: &str = "jellyfin";
+pub const LASTFM: &str = "lastfm";
+pub const OMDB: &str = "omdb";
+/// Plex account token (X-Plex-Token).
+pub const PLEX: &str = "plex";
+/// PodcastIndex issues a key and a secret together.
+pub const PODCASTINDEX_KEY: &str = "podcastindex-key";
+pub const PODCASTINDEX_SECRET: &str = "podcastindex-secret";
+/// Secret access key for `Settings::s3_backup`.
+pub const S3_SECRET: &str = "s3-secret";
+pub const SPOTIFY_CLIENT_ID: &str = "spotify-client-id";
+pub const SPOTIFY_REFRESH_TOKEN: &str = "spotify-refresh-token";
+pub const TMDB: &str = "tmdb";
+pub const YOUTUBE: &str = "youtube";
+
+fn entry(name: &str) -> Result<Entry, String> {
+    let valid = !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if !valid {
+        return Err(format!("Invalid secret name: {}", name));
+    }
+    Entry::new(SERVICE, name).map_err(|e| e.to_string())
+}
+
+pub fn set(name: &str, value: &str) -> Result<(), String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return delete(name);
+    }
+    entry(name)?.set_password(value).map_err(|e| e.to_string())
+}
+
+pub fn get(name: &str) -> Result<Option<String>, String> {
+    match entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Deleting a secret that isn't stored is not an error.
+pub fn delete(name: &str) -> Result<(), String> {
+    match entry(name)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `explicit` if non-empty, else the stored secret `name`. Lookup failures
+/// count as no key, so a missing keychain only disables the keyed service.
+/// Moves a TMDB key still in the settings file into the keychain, unless the
+/// keychain already has one. Returns true if the settings changed.
+pub fn migrate_tmdb_key(settings: &mut crate::models::Settings) -> bool {
+    let Some(key) = settings.tmdb_api_key.take() else {
+        return false;
+    };
+    let stored = match get(TMDB) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) if key.trim().is_empty() => Ok(()),
+        Ok(None) => set(TMDB, &key),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = stored {
+        eprintln!("Could not move the TMDB key to the keychain: {}", e);
+        settings.tmdb_api_key = Some(key);
+        return false;
+    }
+    true
+}
+
+pub fn resolve(explicit: Option<String>, name: &str) -> Option<String> {
+    explicit
+        .filter(|k| !k.trim().is_empty())
+        .or_else(|| get(name).ok().flatten())
+}
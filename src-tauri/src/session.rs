@@ -0,0 +1,116 @@
+// Login sessions. `login_user` issues a token that every user-scoped command
+// takes instead of trusting a `username` argument. Tokens are `<nonce>.<mac>`,
+// the MAC binding the nonce to the username under a key generated at launch, so
+// they can't be forged or moved to another account, and all sessions end when
+// the app restarts. Sessions also expire after `SESSION_TTL_MS` without use.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use rand_core::{OsRng, RngCore};
+use ring::hmac;
+use serde::Serialize;
+use crate::database::now_ms;
+
+const SESSION_TTL_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Error every command returns for a missing, forged or expired session.
+pub const SESSION_EXPIRED: &str = "SESSION_EXPIRED";
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub username: String,
+    pub session: String,
+    pub expires_at: i64,
+    /// Only set by `register_user`; shown to the user once.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recovery_codes: Vec<String>,
+}
+
+struct Session {
+    username: String,
+    expires_at: i64,
+}
+
+pub struct Sessions {
+    key: hmac::Key,
+    active: Mutex<HashMap<String, Session>>,
+}
+
+impl Default for Sessions {
+    fn default() -> Self {
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        Sessions { key: hmac::Key::new(hmac::HMAC_SHA256, &secret), active: Mutex::new(HashMap::new()) }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => Some((char::from(*hi).to_digit(16)? * 16 + char::from(*lo).to_digit(16)?) as u8),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Sessions {
+    fn signed(&self, nonce: &str, username: &str) -> hmac::Tag {
+        hmac::sign(&self.key, format!("{}\n{}", nonce, username).as_bytes())
+    }
+
+    pub fn create(&self, username: &str) -> SessionInfo {
+        let mut bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut bytes);
+        let nonce = hex(&bytes);
+        let token = format!("{}.{}", nonce, hex(self.signed(&nonce, username).as_ref()));
+        let expires_at = now_ms() + SESSION_TTL_MS;
+        if let Ok(mut active) = self.active.lock() {
+            let now = now_ms();
+            active.retain(|_, s| s.expires_at > now);
+            active.insert(token.clone(), Session { username: username.to_string(), expires_at });
+        }
+        SessionInfo { username: username.to_string(), session: token, expires_at, recovery_codes: Vec::new() }
+    }
+
+    /// The user a session belongs to; using a session extends it.
+    pub fn user(&self, token: &str) -> Result<String, String> {
+        let expired = || SESSION_EXPIRED.to_string();
+        let (nonce, mac) = token.split_once('.').ok_or_else(expired)?;
+        let mac = unhex(mac).ok_or_else(expired)?;
+        let mut active = self.active.lock().map_err(|_| expired())?;
+        let session = active.get_mut(token).ok_or_else(expired)?;
+        hmac::verify(&self.key, format!("{}\n{}", nonce, session.username).as_bytes(), &mac).map_err(|_| expired())?;
+        let now = now_ms();
+        if session.expires_at <= now {
+            active.remove(token);
+            return Err(expired());
+        }
+        session.expires_at = now + SESSION_TTL_MS;
+        Ok(session.username.clone())
+    }
+
+    pub fn end(&self, token: &str) {
+        if let Ok(mut active) = self.active.lock() {
+            active.remove(token);
+        }
+    }
+
+    /// Ends every session of `username` except `keep`, e.g. after a password change.
+    pub fn end_others(&self, username: &str, keep: &str) {
+        if let Ok(mut active) = self.active.lock() {
+            active.retain(|token, s| s.username != username || token == keep);
+        }
+    }
+
+    pub fn end_all(&self, username: &str) {
+        if let Ok(mut active) = self.active.lock() {
+            active.retain(|_, s| s.username != username);
+        }
+    }
+}
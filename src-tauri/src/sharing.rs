@@ -0,0 +1,79 @@
+// Collections shared with other accounts on this computer. A share lets the
+// grantee see the collection's items (in `get_collection`, marked with
+// `sharedFrom`) and, with `Write`, edit them; the items stay in the owner's
+// library. Only direct members are shared, not nested collections.
+
+use serde::{Deserialize, Serialize};
+use crate::collections::Collection;
+use crate::models::CollectionData;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SharePermission {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionShare {
+    pub owner: String,
+    pub collection_id: String,
+    pub grantee: String,
+    pub permission: SharePermission,
+    pub created_at: i64,
+}
+
+/// A collection someone else shared with the user.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedCollection {
+    pub owner: String,
+    pub permission: SharePermission,
+    pub collection: Collection,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionShares {
+    /// Shares the user made.
+    pub outgoing: Vec<CollectionShare>,
+    pub incoming: Vec<SharedCollection>,
+}
+
+/// Error for an edit to an item the user may only read.
+pub const READ_ONLY_SHARE: &str = "READ_ONLY_SHARE";
+
+/// Shares granted to `grantee` whose collection still exists, with the collection.
+pub fn incoming<'a>(data: &'a CollectionData, grantee: &str) -> Vec<(&'a CollectionShare, &'a Collection)> {
+    data.collection_shares
+        .iter()
+        .filter(|s| s.grantee == grantee)
+        .filter_map(|s| {
+            let collection = data.collections_by_user.get(&s.owner)?.iter().find(|c| c.id == s.collection_id)?;
+            Some((s, collection))
+        })
+        .collect()
+}
+
+/// Whose library the item `id` the user is acting on lives in: the user's own
+/// when they have it (or nobody has it), otherwise the owner of a collection
+/// shared with them that contains it. Fails for edits through a read-only share.
+pub fn owner_of(data: &CollectionData, username: &str, id: &str, write: bool) -> Result<String, String> {
+    if data.items_by_user.get(username).is_some_and(|l| l.iter().any(|i| i.id == id)) {
+        return Ok(username.to_string());
+    }
+    let shares: Vec<&CollectionShare> = incoming(data, username)
+        .into_iter()
+        .filter(|(_, c)| c.item_ids.iter().any(|m| m == id))
+        .map(|(s, _)| s)
+        .collect();
+    if let Some(share) = shares.iter().find(|s| s.permission == SharePermission::Write) {
+        return Ok(share.owner.clone());
+    }
+    match shares.first() {
+        Some(_) if write => Err(READ_ONLY_SHARE.to_string()),
+        Some(share) => Ok(share.owner.clone()),
+        None => Ok(username.to_string()),
+    }
+}
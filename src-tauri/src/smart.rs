@@ -0,0 +1,271 @@
+// Saved-filter DSL for smart lists.
+//
+// A query is a whitespace-separated list of terms that must all match:
+//   type:movie tag:sci-fi -category:watched rating>8 year>=2000 added>=2024-01-01 age<=13 "free text"
+// Values containing spaces are quoted (status:"On Hold"); a leading '-' negates a term.
+// `age` compares the minimum age of the content rating, `mature:true` matches
+// items rated for adults. Bare words match against the title and creator, and so
+// does anything quoted or whose prefix isn't a filter key ("Re:Zero", Re:Zero).
+// Dates cover their whole day, month or year: added:2024-03 is all of March.
+
+use serde::{Deserialize, Serialize};
+use crate::models::MediaItem;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartList {
+    pub id: String,
+    pub name: String,
+    pub query: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn test(self, a: f64, b: f64) -> bool {
+        match self {
+            Cmp::Eq => (a - b).abs() < f64::EPSILON,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    Type(String),
+    Tag(String),
+    Status(String),
+    Ongoing(bool),
+    Rating(Cmp, f64),
+    Year(Cmp, f64),
+    Added(Cmp, DateRange),
+    Edited(Cmp, DateRange),
+    Age(Cmp, f64),
+    Mature(bool),
+    Text(String),
+}
+
+/// `[start, end)` in epoch milliseconds.
+#[derive(Debug, Clone, Copy)]
+struct DateRange(f64, f64);
+
+impl DateRange {
+    fn test(self, cmp: Cmp, t: f64) -> bool {
+        match cmp {
+            Cmp::Eq => self.0 <= t && t < self.1,
+            Cmp::Lt => t < self.0,
+            Cmp::Le => t < self.1,
+            Cmp::Gt => t >= self.1,
+            Cmp::Ge => t >= self.0,
+        }
+    }
+}
+
+const KEYS: &[&str] = &["type", "tag", "status", "category", "ongoing", "rating", "year", "added", "saved", "edited", "age", "mature"];
+
+#[derive(Debug, Clone)]
+struct Term {
+    negated: bool,
+    predicate: Predicate,
+}
+
+#[derive(Debug, Clone)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+/// A term with its quotes removed, and where the first quoted part began.
+struct Token {
+    text: String,
+    quoted_from: Option<usize>,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut cur = String::new();
+    let mut quoted_from = None;
+    let mut in_quotes = false;
+    for ch in input.chars() {
+        match ch {
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted_from.get_or_insert(cur.len());
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                let quoted_from = quoted_from.take();
+                if !cur.is_empty() {
+                    tokens.push(Token { text: std::mem::take(&mut cur), quoted_from });
+                }
+            }
+            c => cur.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quote in query".to_string());
+    }
+    if !cur.is_empty() {
+        tokens.push(Token { text: cur, quoted_from });
+    }
+    Ok(tokens)
+}
+
+fn norm(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace() && *c != '_' && *c != '-').flat_map(|c| c.to_lowercase()).collect()
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+pub fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = m as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// "2024", "2024-03" or "2024-03-15" as the year, month or day it covers (UTC)
+fn parse_date_range(v: &str) -> Option<DateRange> {
+    let parts: Vec<&str> = v.split('-').collect();
+    let y: i64 = parts[0].parse().ok()?;
+    let m: u32 = parts.get(1).map(|p| p.parse().ok()).unwrap_or(Some(1))?;
+    let d: u32 = parts.get(2).map(|p| p.parse().ok()).unwrap_or(Some(1))?;
+    if parts.len() > 3 || !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    let start = days_from_civil(y, m, d);
+    let end = match parts.len() {
+        1 => days_from_civil(y + 1, 1, 1),
+        2 if m == 12 => days_from_civil(y + 1, 1, 1),
+        2 => days_from_civil(y, m + 1, 1),
+        _ => start + 1,
+    };
+    Some(DateRange((start * 86_400_000) as f64, (end * 86_400_000) as f64))
+}
+
+// Splits at the first operator character so values may themselves contain ':' or
+// '>'. Only the unquoted start of the token is searched, and only a known key splits.
+fn split_comparison(token: &str, quoted_from: Option<usize>) -> Option<(&str, Cmp, &str)> {
+    let i = token[..quoted_from.unwrap_or(token.len())].find([':', '=', '<', '>'])?;
+    if !KEYS.contains(&token[..i].to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    let rest = &token[i..];
+    let (cmp, len) = if rest.starts_with(">=") {
+        (Cmp::Ge, 2)
+    } else if rest.starts_with("<=") {
+        (Cmp::Le, 2)
+    } else if rest.starts_with('>') {
+        (Cmp::Gt, 1)
+    } else if rest.starts_with('<') {
+        (Cmp::Lt, 1)
+    } else {
+        (Cmp::Eq, 1)
+    };
+    Some((&token[..i], cmp, &token[i + len..]))
+}
+
+pub fn parse(input: &str) -> Result<Query, String> {
+    let mut terms = Vec::new();
+    for raw in tokenize(input)? {
+        let (negated, token, quoted_from) = match raw.text.strip_prefix('-') {
+            Some(rest) if !rest.is_empty() && raw.quoted_from != Some(0) => (true, rest, raw.quoted_from.map(|q| q - 1)),
+            _ => (false, raw.text.as_str(), raw.quoted_from),
+        };
+        let predicate = match split_comparison(token, quoted_from) {
+            Some((key, cmp, value)) => {
+                let number = |v: &str| v.parse::<f64>().map_err(|_| format!("Expected a number for '{}'", key));
+                let date = |v: &str| parse_date_range(v).ok_or_else(|| format!("Expected a date (YYYY-MM-DD) for '{}'", key));
+                match key.to_ascii_lowercase().as_str() {
+                    "type" => Predicate::Type(norm(value)),
+                    "tag" => Predicate::Tag(value.to_lowercase()),
+                    "status" | "category" => Predicate::Status(norm(value)),
+                    "ongoing" => Predicate::Ongoing(matches!(value, "true" | "yes" | "1")),
+                    "rating" => Predicate::Rating(cmp, number(value)?),
+                    "year" => Predicate::Year(cmp, number(value)?),
+                    "added" | "saved" => Predicate::Added(cmp, date(value)?),
+                    "edited" => Predicate::Edited(cmp, date(value)?),
+                    "age" => Predicate::Age(cmp, number(value)?),
+                    "mature" => Predicate::Mature(matches!(value, "true" | "yes" | "1")),
+                    other => return Err(format!("Unknown filter key '{}'", other)),
+                }
+            }
+            _ => Predicate::Text(token.to_lowercase()),
+        };
+        terms.push(Term { negated, predicate });
+    }
+    Ok(Query { terms })
+}
+
+/// User rating wins; otherwise the provider rating string ("8.5/10", "8.5") is parsed.
+pub fn effective_rating(item: &MediaItem) -> Option<f64> {
+    if let Some(r) = item.user_rating {
+        return Some(r as f64);
+    }
+    let s = item.rating.as_deref()?.trim();
+    let head = s.split('/').next()?.trim();
+    head.parse::<f64>().ok()
+}
+
+fn type_name(item: &MediaItem) -> String {
+    serde_json::to_value(&item.media_type)
+        .ok()
+        .and_then(|v| v.as_str().map(norm))
+        .unwrap_or_default()
+}
+
+fn category_name(item: &MediaItem) -> Option<String> {
+    item.category
+        .as_ref()
+        .and_then(|c| serde_json::to_value(c).ok())
+        .and_then(|v| v.as_str().map(norm))
+}
+
+impl Predicate {
+    fn matches(&self, item: &MediaItem) -> bool {
+        match self {
+            Predicate::Type(t) => type_name(item) == *t,
+            Predicate::Tag(t) => item.tags.iter().flatten().any(|x| x.to_lowercase() == *t),
+            Predicate::Status(s) => {
+                category_name(item).as_deref() == Some(s.as_str())
+                    || item.status.as_deref().map(norm).as_deref() == Some(s.as_str())
+            }
+            Predicate::Ongoing(b) => item.is_ongoing == *b,
+            Predicate::Rating(cmp, v) => effective_rating(item).map(|r| cmp.test(r, *v)).unwrap_or(false),
+            Predicate::Year(cmp, v) => crate::dedupe::extract_year(&item.release_date)
+                .map(|y| cmp.test(y as f64, *v))
+                .unwrap_or(false),
+            Predicate::Added(cmp, range) => item.saved_at.map(|t| range.test(*cmp, t as f64)).unwrap_or(false),
+            Predicate::Edited(cmp, range) => item.last_edited_at.map(|t| range.test(*cmp, t as f64)).unwrap_or(false),
+            Predicate::Age(cmp, v) => {
+                item.content_rating.as_ref().and_then(|r| r.min_age).map(|a| cmp.test(a as f64, *v)).unwrap_or(false)
+            }
+            Predicate::Mature(b) => item.content_rating.as_ref().map(|r| r.is_mature()).unwrap_or(false) == *b,
+            Predicate::Text(t) => {
+                item.title.to_lowercase().contains(t.as_str()) || item.director_or_author.to_lowercase().contains(t.as_str())
+            }
+        }
+    }
+}
+
+impl Query {
+    pub fn matches(&self, item: &MediaItem) -> bool {
+        self.terms.iter().all(|t| t.predicate.matches(item) != t.negated)
+    }
+
+    pub fn filter(&self, items: &[MediaItem]) -> Vec<MediaItem> {
+        items.iter().filter(|i| self.matches(i)).cloned().collect()
+    }
+}
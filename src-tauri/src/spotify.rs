@@ -0,0 +1,273 @@
+// Spotify saved albums and playlists. Sign-in is OAuth with PKCE, so no client
+// secret is needed: `authorize` binds a one-shot listener on 127.0.0.1 for the
+// redirect, hands back the URL for the frontend to open, and finishes in the
+// background when Spotify redirects, emitting AUTH_EVENT. The refresh token
+// is kept in the OS credential store; each import trades it for an access token.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as B64URL, Engine};
+use rand_core::{OsRng, RngCore};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use crate::database::{new_id, now_ms};
+use crate::models::{MediaItem, MediaType};
+
+pub const AUTH_EVENT: &str = "spotify-auth";
+pub const DEFAULT_PORT: u16 = 8898;
+
+const AUTHORIZE_URL: &str = "https://accounts.spotify.com/authorize";
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const API: &str = "https://api.spotify.com/v1";
+const SCOPES: &str = "user-library-read playlist-read-private playlist-read-collaborative";
+/// How long the listener waits for the browser to come back.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthResult {
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotifyImport {
+    pub albums: usize,
+    pub playlists: usize,
+    /// Already in the collection.
+    pub skipped: usize,
+}
+
+fn random_token(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut buf);
+    B64URL.encode(buf)
+}
+
+fn redirect_uri(port: u16) -> String {
+    format!("http://127.0.0.1:{}/callback", port)
+}
+
+/// Query parameters of the request line "GET /callback?code=...&state=... HTTP/1.1".
+fn callback_params(request: &str) -> HashMap<String, String> {
+    let target = request.split_whitespace().nth(1).unwrap_or("");
+    let query = target.split_once('?').map(|(_, q)| q).unwrap_or("");
+    query
+        .split('&')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_string(), urlencoding::decode(v).map(|v| v.into_owned()).unwrap_or_default()))
+        .collect()
+}
+
+/// Waits for the redirect and returns the authorization code.
+async fn await_code(listener: TcpListener, state: &str) -> Result<String, String> {
+    loop {
+        let (mut socket, _) = listener.accept().await.map_err(|e| e.to_string())?;
+        let mut buf = vec![0u8; 8192];
+        let n = socket.read(&mut buf).await.map_err(|e| e.to_string())?;
+        let request = String::from_utf8_lossy(&buf[..n]).to_string();
+        // Browsers also ask for /favicon.ico; only the callback counts
+        if !request.starts_with("GET /callback") {
+            let _ = socket.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+            continue;
+        }
+        let params = callback_params(&request);
+        let result = match (params.get("code"), params.get("error")) {
+            _ if params.get("state").map(String::as_str) != Some(state) => Err("Spotify sign-in state mismatch".to_string()),
+            (Some(code), _) => Ok(code.clone()),
+            (None, Some(error)) => Err(format!("Spotify sign-in failed: {}", error)),
+            (None, None) => Err("Spotify sign-in returned no code".to_string()),
+        };
+        let message = if result.is_ok() { "Signed in to Spotify. You can close this tab." } else { "Spotify sign-in failed. You can close this tab." };
+        let body = format!("<!doctype html><meta charset=\"utf-8\"><title>MediaTracker</title><p>{}</p>", message);
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        let _ = socket.write_all(response.as_bytes()).await;
+        return result;
+    }
+}
+
+async fn token_request(client: &Client, form: &[(&str, &str)]) -> Result<Value, String> {
+    let resp = tokio::time::timeout(Duration::from_secs(15), client.post(TOKEN_URL).form(form).send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+    if !status.is_success() {
+        return Err(format!("Spotify Error: {}", v["error_description"].as_str().or(v["error"].as_str()).unwrap_or(status.as_str())));
+    }
+    Ok(v)
+}
+
+fn store_tokens(client_id: &str, v: &Value) -> Result<(), String> {
+    crate::secrets::set(crate::secrets::SPOTIFY_CLIENT_ID, client_id)?;
+    if let Some(refresh) = v["refresh_token"].as_str() {
+        crate::secrets::set(crate::secrets::SPOTIFY_REFRESH_TOKEN, refresh)?;
+    }
+    Ok(())
+}
+
+/// Starts sign-in and returns the URL to open in the browser.
+pub async fn authorize(app: AppHandle, client: Client, client_id: String, port: u16) -> Result<String, String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to listen on port {} for the Spotify redirect: {}", port, e))?;
+    let verifier = random_token(48);
+    let challenge = B64URL.encode(Sha256::digest(verifier.as_bytes()));
+    let state = random_token(16);
+    let url = format!(
+        "{}?response_type=code&client_id={}&scope={}&redirect_uri={}&state={}&code_challenge_method=S256&code_challenge={}",
+        AUTHORIZE_URL,
+        urlencoding::encode(&client_id),
+        urlencoding::encode(SCOPES),
+        urlencoding::encode(&redirect_uri(port)),
+        state,
+        challenge
+    );
+    tauri::async_runtime::spawn(async move {
+        let result = async {
+            let code = tokio::time::timeout(AUTH_TIMEOUT, await_code(listener, &state))
+                .await
+                .map_err(|_| "Spotify sign-in timed out".to_string())??;
+            let redirect = redirect_uri(port);
+            let form = [
+                ("grant_type", "authorization_code"),
+                ("code", code.as_str()),
+                ("redirect_uri", redirect.as_str()),
+                ("client_id", client_id.as_str()),
+                ("code_verifier", verifier.as_str()),
+            ];
+            let v = token_request(&client, &form).await?;
+            store_tokens(&client_id, &v)
+        }
+        .await;
+        let _ = app.emit(AUTH_EVENT, AuthResult { ok: result.is_ok(), error: result.err() });
+    });
+    Ok(url)
+}
+
+pub fn is_connected() -> bool {
+    crate::secrets::resolve(None, crate::secrets::SPOTIFY_REFRESH_TOKEN).is_some()
+}
+
+/// A fresh access token from the stored refresh token. Spotify may rotate the
+/// refresh token, so a new one replaces the stored one.
+async fn access_token(client: &Client) -> Result<String, String> {
+    let not_connected = || "Spotify is not connected".to_string();
+    let client_id = crate::secrets::resolve(None, crate::secrets::SPOTIFY_CLIENT_ID).ok_or_else(not_connected)?;
+    let refresh = crate::secrets::resolve(None, crate::secrets::SPOTIFY_REFRESH_TOKEN).ok_or_else(not_connected)?;
+    let v = token_request(client, &[("grant_type", "refresh_token"), ("refresh_token", refresh.as_str()), ("client_id", client_id.as_str())]).await?;
+    store_tokens(&client_id, &v)?;
+    v["access_token"].as_str().map(str::to_string).ok_or_else(|| "Spotify returned no access token".to_string())
+}
+
+/// Every page of a paged endpoint, following `next`.
+async fn pages(client: &Client, token: &str, first: String) -> Result<Vec<Value>, String> {
+    let mut out = Vec::new();
+    let mut next = Some(first);
+    while let Some(url) = next.take() {
+        let resp = tokio::time::timeout(Duration::from_secs(15), client.get(&url).bearer_auth(token).send())
+            .await
+            .map_err(|_| "Timeout".to_string())?
+            .map_err(|e| e.to_string())?;
+        if resp.status().as_u16() == 429 {
+            let wait = resp.headers().get("Retry-After").and_then(|v| v.to_str().ok()?.parse().ok()).unwrap_or(5u64);
+            tokio::time::sleep(Duration::from_secs(wait.min(60))).await;
+            next = Some(url);
+            continue;
+        }
+        if !resp.status().is_success() {
+            return Err(format!("Spotify Error: {}", resp.status()));
+        }
+        let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+        next = v["next"].as_str().map(str::to_string);
+        out.extend(v["items"].as_array().cloned().unwrap_or_default());
+    }
+    Ok(out)
+}
+
+/// Largest image; Spotify lists them biggest first but doesn't promise it.
+fn cover(images: &Value) -> Option<String> {
+    images
+        .as_array()?
+        .iter()
+        .max_by_key(|i| i["width"].as_u64().unwrap_or(0))
+        .and_then(|i| i["url"].as_str())
+        .map(str::to_string)
+}
+
+fn album_item(album: &Value, now: i64) -> Option<MediaItem> {
+    let id = album["id"].as_str()?;
+    let artists: Vec<&str> = album["artists"].as_array().into_iter().flatten().filter_map(|a| a["name"].as_str()).collect();
+    let genres: Vec<String> = album["genres"].as_array().into_iter().flatten().filter_map(|g| g.as_str().map(str::to_string)).collect();
+    Some(MediaItem {
+        id: new_id(),
+        title: album["name"].as_str()?.to_string(),
+        director_or_author: artists.join(", "),
+        release_date: album["release_date"].as_str().unwrap_or_default().to_string(),
+        media_type: MediaType::Music,
+        saved_at: Some(now),
+        poster_url: cover(&album["images"]),
+        tags: (!genres.is_empty()).then_some(genres),
+        provider_ids: Some(HashMap::from([("spotify".to_string(), format!("album:{}", id))])),
+        ..Default::default()
+    })
+}
+
+fn playlist_item(playlist: &Value, now: i64) -> Option<MediaItem> {
+    let id = playlist["id"].as_str()?;
+    Some(MediaItem {
+        id: new_id(),
+        title: playlist["name"].as_str()?.to_string(),
+        director_or_author: playlist["owner"]["display_name"].as_str().unwrap_or_default().to_string(),
+        description: crate::scrape::html_text(playlist["description"].as_str().unwrap_or_default()),
+        media_type: MediaType::Music,
+        saved_at: Some(now),
+        poster_url: cover(&playlist["images"]),
+        provider_ids: Some(HashMap::from([("spotify".to_string(), format!("playlist:{}", id))])),
+        ..Default::default()
+    })
+}
+
+/// Saved albums and, with `include_playlists`, the user's playlists as new Music
+/// items; anything whose "spotify" id is in `existing` is skipped.
+pub async fn fetch_items(client: &Client, include_playlists: bool, existing: &[String]) -> Result<(Vec<MediaItem>, SpotifyImport), String> {
+    let token = access_token(client).await?;
+    let now = now_ms();
+    let mut summary = SpotifyImport::default();
+    let mut items: Vec<MediaItem> = Vec::new();
+    let spotify_id = |i: &MediaItem| i.provider_ids.as_ref().and_then(|ids| ids.get("spotify")).cloned().unwrap_or_default();
+    let mut add = |item: MediaItem, items: &mut Vec<MediaItem>| {
+        let id = spotify_id(&item);
+        if existing.contains(&id) || items.iter().any(|i| spotify_id(i) == id) {
+            summary.skipped += 1;
+            false
+        } else {
+            items.push(item);
+            true
+        }
+    };
+    let mut albums = 0;
+    for saved in pages(client, &token, format!("{}/me/albums?limit=50", API)).await? {
+        if let Some(item) = album_item(&saved["album"], now) {
+            albums += add(item, &mut items) as usize;
+        }
+    }
+    let mut playlists = 0;
+    if include_playlists {
+        for playlist in pages(client, &token, format!("{}/me/playlists?limit=50", API)).await? {
+            if let Some(item) = playlist_item(&playlist, now) {
+                playlists += add(item, &mut items) as usize;
+            }
+        }
+    }
+    summary.albums = albums;
+    summary.playlists = playlists;
+    Ok((items, summary))
+}
@@ -0,0 +1,316 @@
+// Aggregates for the statistics view, computed here so the frontend doesn't
+// need every item. A period limits the figures to items added or finished in
+// it; the monthly series and the time tracked follow the same range.
+
+use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::models::{CollectionCategory, ItemRevision, MediaItem};
+use crate::activity::{ActivityEvent, ActivityKind};
+use crate::watch_time::TimeSession;
+
+const TOP_N: usize = 10;
+/// Custom fields summed for items finished in the period.
+pub(crate) const RUNTIME_FIELD: &str = "runtime";
+const PAGES_FIELD: &str = "pages";
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Count {
+    pub key: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MonthCount {
+    /// `YYYY-MM`
+    pub month: String,
+    pub added: usize,
+    pub completed: usize,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Statistics {
+    /// Epoch milliseconds; None for all time.
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub total_items: usize,
+    pub by_type: Vec<Count>,
+    /// By status name; items without one count under their category.
+    pub by_status: Vec<Count>,
+    pub completed: usize,
+    /// Finishes logged with `add_completion` in the period, rewatches included.
+    pub completions: usize,
+    /// Those that weren't the item's first.
+    pub rewatches: usize,
+    /// Titles finished more than once, by number of finishes.
+    pub most_rewatched: Vec<Count>,
+    /// Share of the counted items that were finished, 0..1.
+    pub completion_rate: f64,
+    /// Keyed by the rating rounded down to a half star.
+    pub ratings: Vec<Count>,
+    pub average_rating: Option<f64>,
+    /// Tags stand in for genres.
+    pub top_tags: Vec<Count>,
+    pub top_creators: Vec<Count>,
+    pub by_month: Vec<MonthCount>,
+    /// From `start_session` / `stop_session`.
+    pub seconds_tracked: i64,
+    /// Sum of the `runtime` custom field (minutes) of finished items.
+    pub runtime_minutes: f64,
+    pub pages: f64,
+    /// Comic and manga chapters marked read in the period.
+    pub chapters_read: usize,
+    /// Chapters read by month (`YYYY-MM`), oldest first.
+    pub chapters_by_month: Vec<Count>,
+    /// Estimated minutes to finish everything still To Watch, over all time: the
+    /// HowLongToBeat main story for games, else the runtime or listening length.
+    pub backlog_minutes: f64,
+    /// To Watch items with no estimate, left out of `backlog_minutes`.
+    pub backlog_unestimated: usize,
+    /// Always over all time, not the period.
+    pub streaks: Streaks,
+}
+
+/// Consecutive days with a finish, a progress update or a timed session, by the
+/// user's local day.
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Streaks {
+    /// Runs up to today, or yesterday while today has no activity yet.
+    pub current: u32,
+    pub best: u32,
+    /// `YYYY-MM-DD`
+    pub best_ended_on: Option<String>,
+    pub last_active_on: Option<String>,
+}
+
+/// Best streak seen, kept because old activity is eventually dropped.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StreakRecord {
+    pub best: u32,
+    pub best_ended_on: String,
+}
+
+/// Per-day counts for one year; index 0 is January 1st (UTC).
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Heatmap {
+    pub year: i64,
+    pub completions: Vec<u32>,
+    /// Progress updates (first progress included).
+    pub episodes: Vec<u32>,
+    /// Timed sessions, by the day they started.
+    pub sessions: Vec<u32>,
+}
+
+/// `all` (or empty), `2024`, `2024-03` or `30d` (the last 30 days) as a
+/// half-open range of epoch milliseconds.
+pub fn parse_period(period: &str, now: i64) -> Result<(Option<i64>, Option<i64>), String> {
+    let p = period.trim().to_lowercase();
+    let invalid = || format!("Unknown period: {}", period);
+    if p.is_empty() || p == "all" {
+        return Ok((None, None));
+    }
+    if let Some(days) = p.strip_suffix('d') {
+        let days: i64 = days.parse().map_err(|_| invalid())?;
+        return Ok((Some(now - days * crate::database::DAY_MS), None));
+    }
+    let mut parts = p.split('-');
+    let year: i64 = parts.next().and_then(|y| y.parse().ok()).ok_or_else(invalid)?;
+    let day_ms = |y: i64, m: u32| crate::smart::days_from_civil(y, m, 1) * crate::database::DAY_MS;
+    match parts.next() {
+        None => Ok((Some(day_ms(year, 1)), Some(day_ms(year + 1, 1)))),
+        Some(m) => {
+            let month: u32 = m.parse().ok().filter(|m| (1..=12).contains(m)).ok_or_else(invalid)?;
+            let end = if month == 12 { day_ms(year + 1, 1) } else { day_ms(year, month + 1) };
+            Ok((Some(day_ms(year, month)), Some(end)))
+        }
+    }
+}
+
+fn month_of(ms: i64) -> String {
+    let (y, m, _) = crate::updates::civil_from_days(ms.div_euclid(crate::database::DAY_MS));
+    format!("{:04}-{:02}", y, m)
+}
+
+fn top(counts: HashMap<String, usize>, limit: usize) -> Vec<Count> {
+    let mut list: Vec<Count> = counts.into_iter().map(|(key, count)| Count { key, count }).collect();
+    list.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    list.truncate(limit);
+    list
+}
+
+pub(crate) fn number(item: &MediaItem, field: &str) -> f64 {
+    item.custom_fields.get(field).and_then(|v| v.as_f64().or_else(|| v.as_str()?.trim().parse().ok())).unwrap_or(0.0)
+}
+
+pub fn compute(
+    items: &[MediaItem],
+    history: &HashMap<String, Vec<ItemRevision>>,
+    sessions: &[TimeSession],
+    range: (Option<i64>, Option<i64>),
+) -> Statistics {
+    let (from, to) = range;
+    let within = |at: i64| from.map(|f| at >= f).unwrap_or(true) && to.map(|t| at < t).unwrap_or(true);
+    let mut stats = Statistics { from, to, ..Default::default() };
+    let mut by_type = HashMap::new();
+    let mut by_status = HashMap::new();
+    let mut ratings = HashMap::new();
+    let mut tags = HashMap::new();
+    let mut creators = HashMap::new();
+    let mut months: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut rewatched = HashMap::new();
+    let mut chapter_months = HashMap::new();
+    let mut rating_sum = 0.0;
+    let mut rated = 0;
+
+    for item in items {
+        for (n, _) in item.completions.iter().enumerate().filter(|(_, c)| within(c.at)) {
+            stats.completions += 1;
+            if n > 0 {
+                stats.rewatches += 1;
+            }
+        }
+        if item.completions.len() > 1 {
+            rewatched.insert(item.title.clone(), item.completions.len());
+        }
+        for r in item.reading.iter().flat_map(|p| &p.read).filter(|r| within(r.read_at)) {
+            stats.chapters_read += 1;
+            *chapter_months.entry(month_of(r.read_at)).or_insert(0) += 1;
+        }
+        if item.category == Some(CollectionCategory::ToWatch) {
+            let runtime = number(item, RUNTIME_FIELD);
+            let estimate = item
+                .play_times
+                .as_ref()
+                .and_then(|t| t.estimate_minutes())
+                .or(item.duration_minutes)
+                .map(|m| m as f64)
+                .or((runtime > 0.0).then_some(runtime));
+            match estimate {
+                Some(minutes) => stats.backlog_minutes += minutes,
+                None => stats.backlog_unestimated += 1,
+            }
+        }
+        let added = item.saved_at.filter(|at| within(*at));
+        let completed = crate::atom::completed_at(item, history.get(&item.id).map(|h| h.as_slice()).unwrap_or(&[])).filter(|at| within(*at));
+        let all_time = from.is_none() && to.is_none();
+        if !all_time && added.is_none() && completed.is_none() {
+            continue;
+        }
+        stats.total_items += 1;
+        let kind = serde_json::to_value(&item.media_type).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+        *by_type.entry(kind).or_insert(0) += 1;
+        let status = item.status.clone().or_else(|| item.category.as_ref().map(|c| crate::statuses::category_name(c).to_string()));
+        if let Some(status) = status {
+            *by_status.entry(status).or_insert(0) += 1;
+        }
+        if let Some(at) = added {
+            months.entry(month_of(at)).or_default().0 += 1;
+        }
+        let finished = if all_time { item.category == Some(CollectionCategory::Watched) } else { completed.is_some() };
+        if finished {
+            stats.completed += 1;
+            // Audiobooks without a runtime field count their listening length
+            let runtime = number(item, RUNTIME_FIELD);
+            stats.runtime_minutes += if runtime > 0.0 { runtime } else { item.duration_minutes.unwrap_or(0) as f64 };
+            stats.pages += number(item, PAGES_FIELD);
+        }
+        if let Some(at) = completed {
+            months.entry(month_of(at)).or_default().1 += 1;
+        }
+        if let Some(r) = item.user_rating {
+            *ratings.entry(format!("{:.1}", (r * 2.0).floor() / 2.0)).or_insert(0) += 1;
+            rating_sum += r as f64;
+            rated += 1;
+        }
+        for tag in item.tags.iter().flatten() {
+            *tags.entry(tag.clone()).or_insert(0) += 1;
+        }
+        let creator = item.director_or_author.trim();
+        if !creator.is_empty() {
+            *creators.entry(creator.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    stats.by_type = top(by_type, usize::MAX);
+    stats.by_status = top(by_status, usize::MAX);
+    stats.completion_rate = if stats.total_items > 0 { stats.completed as f64 / stats.total_items as f64 } else { 0.0 };
+    stats.ratings = ratings.into_iter().map(|(key, count)| Count { key, count }).collect();
+    stats.ratings.sort_by(|a, b| a.key.parse::<f64>().unwrap_or(0.0).total_cmp(&b.key.parse::<f64>().unwrap_or(0.0)));
+    stats.average_rating = (rated > 0).then(|| rating_sum / rated as f64);
+    stats.top_tags = top(tags, TOP_N);
+    stats.top_creators = top(creators, TOP_N);
+    stats.most_rewatched = top(rewatched, TOP_N);
+    stats.by_month = months.into_iter().map(|(month, (added, completed))| MonthCount { month, added, completed }).collect();
+    stats.by_month.sort_by(|a, b| a.month.cmp(&b.month));
+    stats.chapters_by_month = chapter_months.into_iter().map(|(key, count)| Count { key, count }).collect();
+    stats.chapters_by_month.sort_by(|a, b| a.key.cmp(&b.key));
+    stats.seconds_tracked = sessions
+        .iter()
+        .filter(|s| s.ended_at.is_some_and(within))
+        .map(|s| s.seconds)
+        .sum();
+    stats
+}
+
+/// Buckets by the user's local day, as `streaks` does.
+pub fn heatmap(year: i64, activity: &[ActivityEvent], sessions: &[TimeSession], utc_offset_minutes: i32) -> Heatmap {
+    let first = crate::smart::days_from_civil(year, 1, 1);
+    let days = (crate::smart::days_from_civil(year + 1, 1, 1) - first) as usize;
+    let mut map = Heatmap { year, completions: vec![0; days], episodes: vec![0; days], sessions: vec![0; days] };
+    let offset = utc_offset_minutes as i64 * 60_000;
+    let day = |at: i64| usize::try_from((at + offset).div_euclid(crate::database::DAY_MS) - first).ok().filter(|d| *d < days);
+    for e in activity {
+        let Some(d) = day(e.at) else {
+            continue;
+        };
+        match e.kind {
+            ActivityKind::Finished => map.completions[d] += 1,
+            ActivityKind::Started | ActivityKind::EpisodeWatched => map.episodes[d] += 1,
+            _ => {}
+        }
+    }
+    for d in sessions.iter().filter_map(|s| day(s.started_at)) {
+        map.sessions[d] += 1;
+    }
+    map
+}
+
+fn date_of(day: i64) -> String {
+    let (y, m, d) = crate::updates::civil_from_days(day);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// `utc_offset_minutes` shifts day boundaries to the user's local midnight.
+pub fn streaks(activity: &[ActivityEvent], sessions: &[TimeSession], now: i64, utc_offset_minutes: i32) -> Streaks {
+    let offset = utc_offset_minutes as i64 * 60_000;
+    let day = |at: i64| (at + offset).div_euclid(crate::database::DAY_MS);
+    let days: BTreeSet<i64> = activity
+        .iter()
+        .filter(|e| matches!(e.kind, ActivityKind::Finished | ActivityKind::Started | ActivityKind::EpisodeWatched))
+        .map(|e| day(e.at))
+        .chain(sessions.iter().map(|s| day(s.started_at)))
+        .collect();
+    let mut streaks = Streaks { last_active_on: days.last().map(|d| date_of(*d)), ..Default::default() };
+    let mut run: Option<(i64, u32)> = None;
+    for &d in &days {
+        let len = match run {
+            Some((prev, len)) if prev + 1 == d => len + 1,
+            _ => 1,
+        };
+        run = Some((d, len));
+        if len > streaks.best {
+            streaks.best = len;
+            streaks.best_ended_on = Some(date_of(d));
+        }
+    }
+    let today = day(now);
+    if let Some((_, len)) = run.filter(|(last, _)| *last >= today - 1) {
+        streaks.current = len;
+    }
+    streaks
+}
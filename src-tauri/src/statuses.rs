@@ -0,0 +1,147 @@
+// User-configurable statuses ("On Hold", "Dropped", "Rewatching", ...). An
+// item's `status` holds the status name; each status says what it counts as,
+// which keeps the old three-value `category` in step for the parts of the app
+// (and older synced devices) that still look at it.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::models::{CollectionCategory, MediaItem};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StatusKind {
+    Planned,
+    InProgress,
+    OnHold,
+    Dropped,
+    Completed,
+    Favorite,
+}
+
+impl StatusKind {
+    pub fn category(self) -> Option<CollectionCategory> {
+        match self {
+            StatusKind::Planned => Some(CollectionCategory::ToWatch),
+            StatusKind::Completed => Some(CollectionCategory::Watched),
+            StatusKind::Favorite => Some(CollectionCategory::Favorites),
+            StatusKind::InProgress | StatusKind::OnHold | StatusKind::Dropped => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusDef {
+    pub name: String,
+    pub kind: StatusKind,
+    pub color: Option<String>,
+}
+
+fn def(name: &str, kind: StatusKind) -> StatusDef {
+    StatusDef { name: name.to_string(), kind, color: None }
+}
+
+/// The list a user starts with, in display order. The first three are the old categories.
+pub fn defaults() -> Vec<StatusDef> {
+    vec![
+        def("To Watch", StatusKind::Planned),
+        def("Watched", StatusKind::Completed),
+        def("Favorites", StatusKind::Favorite),
+        def("Watching", StatusKind::InProgress),
+        def("Rewatching", StatusKind::InProgress),
+        def("On Hold", StatusKind::OnHold),
+        def("Dropped", StatusKind::Dropped),
+    ]
+}
+
+/// Name of the old category, which is also the default status for it.
+pub fn category_name(category: &CollectionCategory) -> &'static str {
+    match category {
+        CollectionCategory::ToWatch => "To Watch",
+        CollectionCategory::Watched => "Watched",
+        CollectionCategory::Favorites => "Favorites",
+    }
+}
+
+pub fn validate(statuses: &[StatusDef]) -> Result<(), String> {
+    if statuses.is_empty() {
+        return Err("At least one status is required".to_string());
+    }
+    let mut seen = Vec::new();
+    for s in statuses {
+        let name = s.name.trim().to_lowercase();
+        if name.is_empty() {
+            return Err("Status name is required".to_string());
+        }
+        if seen.contains(&name) {
+            return Err(format!("Duplicate status: {}", s.name.trim()));
+        }
+        seen.push(name);
+    }
+    Ok(())
+}
+
+pub fn find<'a>(statuses: &'a [StatusDef], name: &str) -> Option<&'a StatusDef> {
+    statuses.iter().find(|s| s.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Gives items that only have a category the matching status; returns whether any changed.
+pub fn migrate(items: &mut [MediaItem]) -> bool {
+    let mut changed = false;
+    for item in items.iter_mut().filter(|i| i.status.is_none()) {
+        if let Some(c) = &item.category {
+            item.status = Some(category_name(c).to_string());
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Keeps `status` and `category` agreeing after an edit: whichever of the two the
+/// edit changed wins, and a status with no category equivalent clears the category.
+pub fn reconcile(before: Option<&MediaItem>, item: &mut MediaItem, statuses: &[StatusDef]) {
+    let status_changed = before.map(|b| b.status != item.status).unwrap_or(true);
+    let category_changed = before.map(|b| b.category != item.category).unwrap_or(true);
+    if category_changed && !status_changed {
+        if let Some(c) = &item.category {
+            item.status = Some(category_name(c).to_string());
+        }
+        return;
+    }
+    match item.status.as_deref().and_then(|s| find(statuses, s)) {
+        Some(status) => item.category = status.kind.category(),
+        None if item.status.is_none() => {
+            if let Some(c) = &item.category {
+                item.status = Some(category_name(c).to_string());
+            }
+        }
+        None => {}
+    }
+}
+
+/// After the list changed: renames statuses on items (`old name -> new name`)
+/// and moves items whose status now counts as something else to the matching
+/// category. Returns the ids of the items that changed.
+pub fn apply(items: &mut [MediaItem], statuses: &[StatusDef], renames: &HashMap<String, String>) -> Vec<String> {
+    let mut changed = Vec::new();
+    for item in items.iter_mut() {
+        let Some(current) = item.status.clone() else {
+            continue;
+        };
+        let renamed = renames
+            .iter()
+            .find(|(old, _)| old.eq_ignore_ascii_case(&current))
+            .map(|(_, new)| new.trim().to_string());
+        let before = (item.status.clone(), item.category.clone());
+        if let Some(new) = renamed {
+            item.status = Some(new);
+        }
+        if let Some(status) = item.status.as_deref().and_then(|s| find(statuses, s)) {
+            item.category = status.kind.category();
+        }
+        if (item.status.clone(), item.category.clone()) != before {
+            changed.push(item.id.clone());
+        }
+    }
+    changed
+}
@@ -0,0 +1,169 @@
+// Where a title can be watched, from JustWatch's public GraphQL API. Results are
+// kept in memory per title and country for a few hours, so a list of To Watch
+// items can show "on Netflix" badges without a request per item per render.
+
+use std::collections::HashMap;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use crate::models::MediaType;
+
+const API: &str = "https://apis.justwatch.com/graphql";
+const CACHE_TTL_MS: i64 = 6 * 60 * 60 * 1000;
+const CACHE_MAX_ENTRIES: usize = 1024;
+
+const QUERY: &str = "query GetSearchTitles($country: Country!, $language: Language!, $first: Int!, $filter: TitleFilter) {
+  popularTitles(country: $country, first: $first, filter: $filter) {
+    edges { node {
+      objectType
+      content(country: $country, language: $language) { title originalReleaseYear fullPath }
+      offers(country: $country, platform: WEB) {
+        monetizationType presentationType standardWebURL retailPrice(language: $language)
+        package { clearName technicalName }
+      }
+    } }
+  }
+}";
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Offer {
+    /// Display name, e.g. "Netflix".
+    pub service: String,
+    /// Stable id, e.g. "netflix".
+    pub service_id: String,
+    /// "flatrate" (subscription), "free", "ads", "rent" or "buy".
+    pub kind: String,
+    /// "SD", "HD", "_4K".
+    pub quality: Option<String>,
+    pub url: Option<String>,
+    /// As JustWatch formats it, e.g. "$3.99".
+    pub price: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Availability {
+    pub title: String,
+    pub year: Option<i32>,
+    pub country: String,
+    pub url: Option<String>,
+    /// One per service and kind, best quality kept.
+    pub offers: Vec<Offer>,
+    pub fetched_at: i64,
+}
+
+/// Lookups by `cache_key`, with when they were made; titles JustWatch doesn't know are cached too.
+#[derive(Default)]
+pub struct AvailabilityCache(RwLock<HashMap<String, (i64, Option<Availability>)>>);
+
+impl AvailabilityCache {
+    pub async fn get(&self, key: &str, now: i64) -> Option<Option<Availability>> {
+        let cache = self.0.read().await;
+        cache.get(key).filter(|(at, _)| now - at <= CACHE_TTL_MS).map(|(_, hit)| hit.clone())
+    }
+
+    pub async fn put(&self, key: String, value: Option<Availability>, now: i64) {
+        let mut cache = self.0.write().await;
+        if cache.len() >= CACHE_MAX_ENTRIES {
+            cache.retain(|_, (at, _)| now - *at <= CACHE_TTL_MS);
+        }
+        cache.insert(key, (now, value));
+    }
+}
+
+pub fn cache_key(title: &str, year: Option<i32>, country: &str) -> String {
+    format!("{}|{}|{}", country, year.map(|y| y.to_string()).unwrap_or_default(), title.trim().to_lowercase())
+}
+
+/// JustWatch's language for a country; English where we don't know better.
+fn language(country: &str) -> &'static str {
+    match country {
+        "CN" | "TW" | "HK" => "zh",
+        "JP" => "ja",
+        "KR" => "ko",
+        "DE" | "AT" => "de",
+        "FR" => "fr",
+        "ES" | "MX" | "AR" => "es",
+        "IT" => "it",
+        "BR" | "PT" => "pt",
+        _ => "en",
+    }
+}
+
+fn quality_rank(q: Option<&str>) -> u8 {
+    match q {
+        Some("_4K") => 3,
+        Some("HD") => 2,
+        Some("SD") => 1,
+        _ => 0,
+    }
+}
+
+fn offers(node: &Value) -> Vec<Offer> {
+    let mut out: Vec<Offer> = Vec::new();
+    for o in node["offers"].as_array().into_iter().flatten() {
+        let Some(service_id) = o["package"]["technicalName"].as_str() else {
+            continue;
+        };
+        let offer = Offer {
+            service: o["package"]["clearName"].as_str().unwrap_or(service_id).to_string(),
+            service_id: service_id.to_string(),
+            kind: o["monetizationType"].as_str().unwrap_or_default().to_lowercase(),
+            quality: o["presentationType"].as_str().map(str::to_string),
+            url: o["standardWebURL"].as_str().map(str::to_string),
+            price: o["retailPrice"].as_str().map(str::to_string),
+        };
+        match out.iter_mut().find(|e| e.service_id == offer.service_id && e.kind == offer.kind) {
+            Some(e) if quality_rank(offer.quality.as_deref()) > quality_rank(e.quality.as_deref()) => *e = offer,
+            Some(_) => {}
+            None => out.push(offer),
+        }
+    }
+    out
+}
+
+pub async fn lookup(client: &Client, title: &str, year: Option<i32>, media_type: Option<&MediaType>, country: &str, now: i64) -> Result<Option<Availability>, String> {
+    let object_types = match media_type {
+        Some(MediaType::Movie) => json!(["MOVIE"]),
+        Some(MediaType::TvSeries) | Some(MediaType::ShortDrama) => json!(["SHOW"]),
+        _ => json!(["MOVIE", "SHOW"]),
+    };
+    let body = json!({
+        "operationName": "GetSearchTitles",
+        "query": QUERY,
+        "variables": {
+            "country": country,
+            "language": language(country),
+            "first": 5,
+            "filter": { "searchQuery": title, "objectTypes": object_types },
+        },
+    });
+    let fut = client.post(API).json(&body).send();
+    let resp = tokio::time::timeout(std::time::Duration::from_secs(12), fut)
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("JustWatch Error: {}", resp.status()));
+    }
+    let v = resp.json::<Value>().await.map_err(|e| e.to_string())?;
+    let nodes: Vec<&Value> = v["data"]["popularTitles"]["edges"].as_array().into_iter().flatten().map(|e| &e["node"]).collect();
+    let wanted = title.trim().to_lowercase();
+    let year_of = |n: &Value| n["content"]["originalReleaseYear"].as_i64().map(|y| y as i32);
+    // Same title and year first, then same title, then whatever JustWatch ranked first
+    let node = nodes
+        .iter()
+        .find(|n| n["content"]["title"].as_str().map(|t| t.to_lowercase() == wanted).unwrap_or(false) && (year.is_none() || year_of(n) == year))
+        .or_else(|| nodes.iter().find(|n| n["content"]["title"].as_str().map(|t| t.to_lowercase() == wanted).unwrap_or(false)))
+        .or_else(|| nodes.first());
+    Ok(node.map(|n| Availability {
+        title: n["content"]["title"].as_str().unwrap_or(title).to_string(),
+        year: year_of(n),
+        country: country.to_string(),
+        url: n["content"]["fullPath"].as_str().map(|p| format!("https://www.justwatch.com{}", p)),
+        offers: offers(n),
+        fetched_at: now,
+    }))
+}
@@ -1,18 +1,43 @@
-use axum::{routing::get, Router, Json, extract::State};
+use axum::{routing::{get, post}, Router, Json, extract::State};
+use axum::http::{HeaderMap, StatusCode};
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
-use std::collections::HashMap;
-use crate::database::Database;
-use crate::models::CollectionData;
+use std::collections::{HashMap, HashSet};
+use crate::database::{Database, MergeSummary, SyncPayload};
+use crate::envelope::{self, DeviceKey, Sealed};
+use tauri::AppHandle;
+use crate::models::{CollectionCategory, MediaItem, TrustedDevice};
 use mdns_sd::{ServiceDaemon, ServiceInfo, ServiceEvent};
 use local_ip_address::local_ip;
 use serde::{Deserialize, Serialize};
-
-use std::sync::atomic::{AtomicBool, Ordering};
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, Manager};
 
 #[derive(Clone)]
 pub struct SyncState {
     pub db: Arc<Database>,
+    pub app: AppHandle,
+    key: DeviceKey,
+    pairing: Arc<tokio::sync::Mutex<Option<PendingPairing>>>,
+}
+
+/// PIN offered by `start_pairing`, valid for one pairing until it expires.
+struct PendingPairing {
+    username: String,
+    pin: String,
+    expires_at: i64,
+    attempts: u32,
+}
+
+const PAIRING_TTL_MS: i64 = 5 * 60 * 1000;
+const PAIRING_MAX_ATTEMPTS: u32 = 5;
+
+/// What `start_pairing` shows the user to type on the other device.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingCode {
+    pub pin: String,
+    pub expires_at: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -23,12 +48,41 @@ pub struct PeerInfo {
     pub last_seen: u64,
 }
 
+pub const DEFAULT_PORT: u16 = 14567;
+const SERVICE_TYPE: &str = "_mediatracker._tcp.local.";
+/// How often known peers are checked while the server runs.
+const LIVENESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Peers not heard from (mDNS or `/sync/ping`) for this long are dropped.
+const PEER_STALE_SECS: u64 = 3 * 60;
+
+/// What `sync_status` reports about the LAN sync server.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub address: Option<String>,
+    pub started_at: Option<i64>,
+    pub peer_count: usize,
+}
+
+struct RunningServer {
+    addr: SocketAddr,
+    started_at: i64,
+    mdns_fullname: Option<String>,
+    shutdown: tokio::sync::oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+    liveness: tokio::task::JoinHandle<()>,
+}
+
 #[derive(Clone)]
 pub struct SyncService {
     mdns: ServiceDaemon,
-    port: u16,
     peers: Arc<RwLock<HashMap<String, PeerInfo>>>,
-    running: Arc<AtomicBool>,
+    server: Arc<tokio::sync::Mutex<Option<RunningServer>>>,
+    pairing: Arc<tokio::sync::Mutex<Option<PendingPairing>>>,
+    /// Peers a sync is in progress with, so auto-sync never overlaps a manual one.
+    syncing: Arc<std::sync::Mutex<HashSet<String>>>,
 }
 
 impl SyncService {
@@ -36,94 +90,216 @@ impl SyncService {
         let mdns = ServiceDaemon::new().expect("Failed to create mdns daemon");
         Self {
             mdns,
-            port: 14567,
             peers: Arc::new(RwLock::new(HashMap::new())),
-            running: Arc::new(AtomicBool::new(false)),
+            server: Arc::new(tokio::sync::Mutex::new(None)),
+            pairing: Arc::new(tokio::sync::Mutex::new(None)),
+            syncing: Arc::new(std::sync::Mutex::new(HashSet::new())),
         }
     }
 
-    pub async fn start_server(&self, db: Arc<Database>) {
-        if self.running.load(Ordering::SeqCst) {
-            println!("Sync server already running");
-            return;
+    /// Binds the HTTP server on `port`, announces it over mDNS and starts peer
+    /// discovery. Starting an already running server just reports its status.
+    pub async fn start(&self, db: Arc<Database>, app: AppHandle, port: u16) -> Result<SyncStatus, String> {
+        let mut server = self.server.lock().await;
+        if server.as_ref().map(|s| !s.task.is_finished()).unwrap_or(false) {
+            drop(server);
+            return Ok(self.status().await);
         }
-        
-        let state = SyncState { db };
+
+        let key = db.device_key().await?;
+        let state = SyncState { db: db.clone(), app: app.clone(), key, pairing: self.pairing.clone() };
         
         // Enable CORS
         use tower_http::cors::CorsLayer;
         let cors = CorsLayer::permissive();
 
-        let app = Router::new()
-            .route("/sync/data", get(get_data).post(receive_data))
+        let router = Router::new()
+            .route("/sync/info", get(get_info))
+            .route("/sync/ping", get(ping))
+            .route("/sync/secure", post(secure))
+            .route("/sync/pair", post(accept_pairing))
+            .route("/api/quick-add", post(quick_add))
+            .merge(crate::api::routes())
+            .merge(crate::atom::routes())
+            .merge(crate::web::routes())
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::auth::log_rejections))
             .layer(cors)
             .with_state(state);
 
         let ip = local_ip().unwrap_or("0.0.0.0".parse().unwrap());
-        let addr = SocketAddr::from((ip, self.port));
-        
+        let addr = SocketAddr::from((ip, port));
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| format!("Failed to bind sync port {}: {}", port, e))?;
         println!("Starting Sync Server on {}", addr);
-        
+
         // Announce via mDNS
         let hostname = get_hostname();
-        let service_type = "_mediatracker._tcp.local.";
         let instance_name = format!("MediaTracker_{}", hostname);
-        let host_ipv4 = ip.to_string();
-
-        let service_info = ServiceInfo::new(
-            service_type,
+        let mdns_fullname = match ServiceInfo::new(
+            SERVICE_TYPE,
             &instance_name,
             &format!("{}.local.", hostname),
-            &host_ipv4,
-            self.port,
+            ip.to_string().as_str(),
+            port,
             [("version", "1")].as_slice()
-        ).expect("Valid service info");
-        
-        if let Err(e) = self.mdns.register(service_info) {
-            eprintln!("Failed to register mDNS: {}", e);
-        }
+        ) {
+            Ok(info) => {
+                let fullname = info.get_fullname().to_string();
+                match self.mdns.register(info) {
+                    Ok(()) => Some(fullname),
+                    Err(e) => {
+                        eprintln!("Failed to register mDNS: {}", e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Invalid mDNS service info: {}", e);
+                None
+            }
+        };
 
         // Start Discovery in background
-        self.start_discovery();
-
-        // Run server
-        match tokio::net::TcpListener::bind(addr).await {
-            Ok(listener) => {
-                self.running.store(true, Ordering::SeqCst);
-                if let Err(e) = axum::serve(listener, app).await {
-                    eprintln!("Server error: {}", e);
-                }
-                self.running.store(false, Ordering::SeqCst);
+        self.start_discovery(app, db, mdns_fullname.clone(), addr);
+        let liveness = self.start_liveness_checks();
+
+        let (shutdown, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            let graceful = async {
+                let _ = shutdown_rx.await;
+            };
+            if let Err(e) = axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).with_graceful_shutdown(graceful).await {
+                eprintln!("Server error: {}", e);
+            }
+        });
+        *server = Some(RunningServer { addr, started_at: crate::database::now_ms(), mdns_fullname, shutdown, task, liveness });
+        drop(server);
+        Ok(self.status().await)
+    }
+
+    /// Stops accepting connections, lets in-flight requests finish, withdraws the
+    /// mDNS announcement and stops discovery. Returns false if nothing was running.
+    pub async fn stop(&self) -> bool {
+        let Some(running) = self.server.lock().await.take() else {
+            return false;
+        };
+        if let Some(fullname) = &running.mdns_fullname {
+            if let Err(e) = self.mdns.unregister(fullname) {
+                eprintln!("Failed to unregister mDNS: {}", e);
+            }
+        }
+        let _ = self.mdns.stop_browse(SERVICE_TYPE);
+        running.liveness.abort();
+        if let Ok(mut guard) = self.peers.write() {
+            guard.clear();
+        }
+        *self.pairing.lock().await = None;
+        let _ = running.shutdown.send(());
+        let mut task = running.task;
+        if tokio::time::timeout(std::time::Duration::from_secs(5), &mut task).await.is_err() {
+            // A client is holding a connection open; don't let it block shutdown
+            task.abort();
+        }
+        true
+    }
+
+    pub async fn status(&self) -> SyncStatus {
+        let server = self.server.lock().await;
+        match server.as_ref().filter(|s| !s.task.is_finished()) {
+            Some(s) => SyncStatus {
+                running: true,
+                port: Some(s.addr.port()),
+                address: Some(s.addr.to_string()),
+                started_at: Some(s.started_at),
+                peer_count: self.get_known_peers().len(),
             },
-            Err(e) => eprintln!("Failed to bind sync port: {}", e),
+            None => SyncStatus::default(),
         }
     }
 
-    fn start_discovery(&self) {
+    /// Offers a fresh 6-digit PIN for pairing another device with `username`,
+    /// replacing any PIN still pending.
+    pub async fn start_pairing(&self, username: &str) -> Result<PairingCode, String> {
+        use rand_core::{OsRng, RngCore};
+        let pin = format!("{:06}", OsRng.next_u32() % 1_000_000);
+        self.offer_pairing(username, pin).await
+    }
+
+    async fn offer_pairing(&self, username: &str, pin: String) -> Result<PairingCode, String> {
+        if !self.status().await.running {
+            return Err("Start sync before pairing a device".to_string());
+        }
+        let expires_at = crate::database::now_ms() + PAIRING_TTL_MS;
+        *self.pairing.lock().await = Some(PendingPairing { username: username.to_string(), pin: pin.clone(), expires_at, attempts: 0 });
+        Ok(PairingCode { pin, expires_at })
+    }
+
+    /// Like `start_pairing`, but with a long one-time secret instead of a PIN,
+    /// packed with this device's address and key into a QR code for scanning.
+    pub async fn pairing_qr(&self, db: &Database, username: &str) -> Result<PairingQr, String> {
+        let code = self.offer_pairing(username, new_token()).await?;
+        let addr = self.server.lock().await.as_ref().map(|s| s.addr).ok_or_else(|| "Sync is not running".to_string())?;
+        let payload = PairingUri {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            pin: code.pin,
+            key: db.device_key().await?.public_base64(),
+            name: get_hostname(),
+        }
+        .to_string();
+        let png = qr_png(&payload)?;
+        use base64::Engine;
+        let data_url = format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(&png));
+        Ok(PairingQr { payload, data_url, expires_at: code.expires_at })
+    }
+
+    pub async fn cancel_pairing(&self) {
+        *self.pairing.lock().await = None;
+    }
+
+    /// Browses for other instances. Our own announcement (by service name, or by
+    /// address when registration failed) is never recorded as a peer.
+    fn start_discovery(&self, app: AppHandle, db: Arc<Database>, own_fullname: Option<String>, own_addr: SocketAddr) {
         let mdns = self.mdns.clone();
         let peers = self.peers.clone();
-        let service_type = "_mediatracker._tcp.local.";
 
         std::thread::spawn(move || {
-            let receiver = mdns.browse(service_type).expect("Failed to browse");
+            let receiver = match mdns.browse(SERVICE_TYPE) {
+                Ok(r) => r,
+                Err(e) => {
+                    eprintln!("Failed to browse mDNS: {}", e);
+                    return;
+                }
+            };
             while let Ok(event) = receiver.recv() {
                 match event {
                     ServiceEvent::ServiceResolved(info) => {
                          let fullname = info.get_fullname().to_string();
-                         // Ignore self if possible, but IP check is easier later
                          let ip = info.get_addresses().iter().next().map(|ip| ip.to_string()).unwrap_or_default();
                          let port = info.get_port();
                          let hostname = info.get_hostname().to_string();
-                         
-                         if !ip.is_empty() {
+                         let is_self = own_fullname.as_deref() == Some(fullname.as_str())
+                             || (port == own_addr.port() && info.get_addresses().contains(&own_addr.ip()));
+
+                         if !ip.is_empty() && !is_self {
                              let p = PeerInfo { 
                                  name: hostname, 
                                  ip, 
                                  port,
-                                 last_seen: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+                                 last_seen: now_secs(),
+                             };
+                             let appeared = match peers.write() {
+                                 Ok(mut guard) => guard.insert(fullname, p.clone()).is_none(),
+                                 Err(_) => false,
                              };
-                             if let Ok(mut guard) = peers.write() {
-                                 guard.insert(fullname, p);
+                             if appeared {
+                                 let (app, db) = (app.clone(), db.clone());
+                                 tauri::async_runtime::spawn(async move {
+                                     if db.get_settings().await.job(crate::scheduler::JobKind::AutoSync).enabled {
+                                         auto_sync_peer(&app, &db, &p).await;
+                                     }
+                                 });
                              }
                          }
                     },
@@ -138,6 +314,48 @@ impl SyncService {
         });
     }
 
+    /// Periodically pings peers mDNS hasn't refreshed lately and forgets those
+    /// that stay silent past `PEER_STALE_SECS` (mDNS goodbyes are easily lost).
+    fn start_liveness_checks(&self) -> tokio::task::JoinHandle<()> {
+        let peers = self.peers.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(LIVENESS_INTERVAL).await;
+                let quiet: Vec<(String, PeerInfo)> = match peers.read() {
+                    Ok(guard) => guard
+                        .iter()
+                        .filter(|(_, p)| now_secs().saturating_sub(p.last_seen) >= LIVENESS_INTERVAL.as_secs())
+                        .map(|(k, p)| (k.clone(), p.clone()))
+                        .collect(),
+                    Err(_) => continue,
+                };
+                for (key, peer) in quiet {
+                    let alive = ping_peer(&peer.ip, peer.port).await.is_ok();
+                    let Ok(mut guard) = peers.write() else {
+                        continue;
+                    };
+                    match guard.get_mut(&key) {
+                        Some(p) if alive => p.last_seen = now_secs(),
+                        Some(p) if now_secs().saturating_sub(p.last_seen) > PEER_STALE_SECS => {
+                            guard.remove(&key);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        })
+    }
+
+    fn begin_sync(&self, peer: &str) -> bool {
+        self.syncing.lock().map(|mut s| s.insert(peer.to_string())).unwrap_or(false)
+    }
+
+    fn end_sync(&self, peer: &str) {
+        if let Ok(mut s) = self.syncing.lock() {
+            s.remove(peer);
+        }
+    }
+
     pub fn get_known_peers(&self) -> Vec<PeerInfo> {
         if let Ok(guard) = self.peers.read() {
             guard.values().cloned().collect()
@@ -147,18 +365,624 @@ impl SyncService {
     }
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncDirection {
+    /// Take the peer's changes only.
+    Pull,
+    /// Send our changes to the peer only.
+    Push,
+    /// Pull, then push, so both devices end up with everything.
+    #[default]
+    Merge,
+}
+
+/// What a sync with one peer did on each side.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerSyncSummary {
+    pub direction: SyncDirection,
+    /// Changes applied to this device.
+    pub pulled: Option<MergeSummary>,
+    /// Changes the peer applied.
+    pub pushed: Option<MergeSummary>,
+}
+
+/// One sync exchange as kept in the history, seen from this device.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSession {
+    pub id: String,
+    pub peer: String,
+    pub direction: SyncDirection,
+    /// False when the peer connected to us.
+    pub initiated_here: bool,
+    pub started_at: i64,
+    pub duration_ms: i64,
+    /// Items plus deletions sent to / received from the peer.
+    pub sent: usize,
+    pub received: usize,
+    /// What merging changed on this device.
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// What merging changed on the peer.
+    pub peer_added: usize,
+    pub peer_updated: usize,
+    pub peer_deleted: usize,
+    pub conflicts: usize,
+    pub error: Option<String>,
+}
+
+impl SyncSession {
+    fn begin(peer: &str, direction: SyncDirection, initiated_here: bool) -> Self {
+        Self {
+            id: crate::database::new_id(),
+            peer: peer.to_string(),
+            direction,
+            initiated_here,
+            started_at: crate::database::now_ms(),
+            ..Default::default()
+        }
+    }
+
+    fn applied_here(&mut self, summary: &MergeSummary) {
+        self.added += summary.added;
+        self.updated += summary.updated;
+        self.deleted += summary.deleted;
+        self.conflicts += summary.conflicts.len();
+    }
+
+    fn applied_there(&mut self, summary: &MergeSummary) {
+        self.peer_added += summary.added;
+        self.peer_updated += summary.updated;
+        self.peer_deleted += summary.deleted;
+        self.conflicts += summary.conflicts.len();
+    }
+
+    fn finish(mut self, error: Option<String>) -> Self {
+        self.duration_ms = crate::database::now_ms() - self.started_at;
+        self.error = error;
+        self
+    }
+}
+
+/// Unauthenticated self-description, used to identify a peer before syncing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerHello {
+    pub name: String,
+    pub version: u32,
+    /// X25519 key requests to this device are sealed to; pinned when pairing.
+    #[serde(default)]
+    pub public_key: String,
+}
+
+const PROTOCOL_VERSION: u32 = 5;
+
+/// What travels inside a sealed `/sync/secure` request.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SecureRequest {
+    token: String,
+    #[serde(flatten)]
+    op: SecureOp,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum SecureOp {
+    /// Items changed since `since` (the `until` of the caller's previous pull).
+    Changes { since: i64 },
+    /// Merge the caller's changes.
+    Push { payload: SyncPayload },
+}
+
+fn peer_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+pub async fn peer_hello(ip: &str, port: u16) -> Result<PeerHello, String> {
+    let url = format!("http://{}:{}/sync/info", ip, port);
+    let resp = peer_client()?.get(&url).send().await.map_err(|e| e.to_string())?;
+    let hello: PeerHello = resp.json().await.map_err(|_| "Peer does not support authenticated sync".to_string())?;
+    if hello.version < PROTOCOL_VERSION {
+        return Err("Peer runs an older MediaTracker; update it to sync".to_string());
+    }
+    Ok(hello)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Pong {
+    pub name: String,
+    pub version: u32,
+}
+
+/// Cheap liveness probe used to prune peers that disappeared without an mDNS goodbye.
+pub async fn ping_peer(ip: &str, port: u16) -> Result<Pong, String> {
+    let url = format!("http://{}:{}/sync/ping", ip, port);
+    let resp = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(3))
+        .build()
+        .map_err(|e| e.to_string())?
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    resp.json().await.map_err(|e| e.to_string())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn check_status(resp: &reqwest::Response) -> Result<(), String> {
+    match resp.status() {
+        s if s.is_success() => Ok(()),
+        StatusCode::UNAUTHORIZED => Err("NOT_PAIRED".to_string()),
+        s => Err(format!("Peer rejected sync: HTTP {}", s)),
+    }
+}
+
+/// Sends one request sealed to the key pinned under the fingerprint `peer`,
+/// authenticated with the token it gave `username`, and opens the sealed reply.
+async fn secure_call<T: serde::de::DeserializeOwned>(db: &Database, username: &str, peer: &str, ip: &str, port: u16, op: SecureOp) -> Result<T, String> {
+    let not_paired = || "NOT_PAIRED".to_string();
+    let token = db.peer_token(username, peer).await.ok_or_else(not_paired)?;
+    let key = db.peer_key(peer).await.ok_or_else(not_paired)?;
+    let body = serde_json::to_vec(&SecureRequest { token, op }).map_err(|e| e.to_string())?;
+    let (sealed, exchange) = envelope::seal_request(&key, &body)?;
+    let url = format!("http://{}:{}/sync/secure", ip, port);
+    let resp = peer_client()?.post(&url).json(&sealed).send().await.map_err(|e| e.to_string())?;
+    check_status(&resp)?;
+    let reply: Sealed = resp.json().await.map_err(|e| e.to_string())?;
+    serde_json::from_slice(&exchange.open_reply(&reply)?).map_err(|e| e.to_string())
+}
+
+fn payload_len(payload: &SyncPayload) -> usize {
+    payload.items.len() + payload.tombstones.len()
+}
+
+/// Fetches what changed on `peer` since the last pull and merges it into `username`.
+async fn pull_from_peer(db: &Database, username: &str, peer: &str, ip: &str, port: u16, session: &mut SyncSession) -> Result<MergeSummary, String> {
+    let mut cursor = db.sync_cursor(username, peer).await;
+    let payload: SyncPayload = secure_call(db, username, peer, ip, port, SecureOp::Changes { since: cursor.pulled_until }).await?;
+    let until = payload.until;
+    session.received += payload_len(&payload);
+    let summary = db.merge_sync_payload(username, payload).await?;
+    session.applied_here(&summary);
+    cursor.pulled_until = until;
+    db.set_sync_cursor(username, peer, cursor).await;
+    Ok(summary)
+}
+
+/// Sends what changed here since the last push to `peer`, which merges it and reports what changed.
+async fn push_to_peer(db: &Database, username: &str, peer: &str, ip: &str, port: u16, session: &mut SyncSession) -> Result<MergeSummary, String> {
+    let mut cursor = db.sync_cursor(username, peer).await;
+    let payload = db.get_sync_changes(username, cursor.pushed_until).await;
+    let until = payload.until;
+    session.sent += payload_len(&payload);
+    let summary = secure_call(db, username, peer, ip, port, SecureOp::Push { payload }).await?;
+    session.applied_there(&summary);
+    cursor.pushed_until = until;
+    db.set_sync_cursor(username, peer, cursor).await;
+    Ok(summary)
+}
+
+/// Emitted after every sync with a peer (manual or automatic), successful or not.
+pub const PEER_SYNCED_EVENT: &str = "peer-synced";
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerSynced {
+    pub username: String,
+    pub peer: String,
+    pub at: i64,
+    pub summary: Option<PeerSyncSummary>,
+    pub error: Option<String>,
+}
+
+/// Syncs `username` with a paired peer. Fails with `NOT_PAIRED` if the devices
+/// were never paired, the peer revoked us, or its key no longer matches.
+pub async fn sync_with_peer(app: &AppHandle, db: &Database, username: &str, ip: &str, port: u16, direction: SyncDirection) -> Result<PeerSyncSummary, String> {
+    let hello = peer_hello(ip, port).await?;
+    sync_paired(app, db, username, &hello, ip, port, direction).await
+}
+
+/// Runs one sync with the peer that answered `hello`, identified by its key's
+/// fingerprint; records when it succeeded and emits `PEER_SYNCED_EVENT` either way.
+async fn sync_paired(app: &AppHandle, db: &Database, username: &str, hello: &PeerHello, ip: &str, port: u16, direction: SyncDirection) -> Result<PeerSyncSummary, String> {
+    let peer = envelope::fingerprint(&hello.public_key);
+    let peer = peer.as_str();
+    let service = app.state::<SyncService>();
+    if !service.begin_sync(peer) {
+        return Err(format!("Already syncing with {}", hello.name));
+    }
+    let mut session = SyncSession::begin(&hello.name, direction, true);
+    let result = async {
+        let pulled = match direction {
+            SyncDirection::Pull | SyncDirection::Merge => Some(pull_from_peer(db, username, peer, ip, port, &mut session).await?),
+            SyncDirection::Push => None,
+        };
+        let pushed = match direction {
+            SyncDirection::Push | SyncDirection::Merge => Some(push_to_peer(db, username, peer, ip, port, &mut session).await?),
+            SyncDirection::Pull => None,
+        };
+        Ok::<_, String>(PeerSyncSummary { direction, pulled, pushed })
+    }
+    .await;
+    service.end_sync(peer);
+    db.record_sync_session(username, session.finish(result.as_ref().err().cloned())).await;
+
+    let at = crate::database::now_ms();
+    if result.is_ok() {
+        db.mark_peer_synced(username, peer, at).await;
+    }
+    if let Ok(PeerSyncSummary { pulled: Some(pulled), .. }) = &result {
+        if pulled.credentials_changed {
+            notify_credentials_changed(app, db, &hello.name).await;
+        }
+    }
+    let event = PeerSynced {
+        username: username.to_string(),
+        peer: hello.name.clone(),
+        at,
+        summary: result.as_ref().ok().cloned(),
+        error: result.as_ref().err().cloned(),
+    };
+    let _ = app.emit(PEER_SYNCED_EVENT, event);
+    result
+}
+
+/// Passwords aren't synced, so a change on another device has to be repeated here.
+async fn notify_credentials_changed(app: &AppHandle, db: &Database, peer: &str) {
+    let body = format!("Your password was changed on {}. Change it on this device too.", peer);
+    crate::notify::general(app, db, "MediaTracker", &body).await;
+}
+
+/// Delta-syncs every local user paired with `peer`. Returns (attempted, succeeded).
+pub async fn auto_sync_peer(app: &AppHandle, db: &Database, peer: &PeerInfo) -> (usize, usize) {
+    let Ok(hello) = peer_hello(&peer.ip, peer.port).await else {
+        return (0, 0);
+    };
+    let users = db.users_paired_with(&envelope::fingerprint(&hello.public_key)).await;
+    let mut synced = 0;
+    for username in &users {
+        if sync_paired(app, db, username, &hello, &peer.ip, peer.port, SyncDirection::Merge).await.is_ok() {
+            synced += 1;
+        }
+    }
+    (users.len(), synced)
+}
+
+/// What `get_pairing_qr` returns: the pairing URI and the same URI as a PNG QR code.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingQr {
+    pub payload: String,
+    pub data_url: String,
+    pub expires_at: i64,
+}
+
+/// `mediatracker://pair?host=..&port=..&pin=..&key=..&name=..`, the connection
+/// info a second device needs to pair without typing anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairingUri {
+    pub host: String,
+    pub port: u16,
+    pub pin: String,
+    /// The offering device's public key, checked against what it announces.
+    pub key: String,
+    pub name: String,
+}
+
+impl std::fmt::Display for PairingUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mediatracker://pair?host={}&port={}&pin={}&key={}&name={}",
+            urlencoding::encode(&self.host),
+            self.port,
+            urlencoding::encode(&self.pin),
+            urlencoding::encode(&self.key),
+            urlencoding::encode(&self.name)
+        )
+    }
+}
+
+impl std::str::FromStr for PairingUri {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let query = s.trim().strip_prefix("mediatracker://pair?").ok_or_else(|| "Not a MediaTracker pairing code".to_string())?;
+        let mut params = HashMap::new();
+        for pair in query.split('&') {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            let v = urlencoding::decode(v).map_err(|e| e.to_string())?;
+            params.insert(k, v.into_owned());
+        }
+        let mut take = |k: &str| params.remove(k).filter(|v| !v.is_empty()).ok_or_else(|| format!("Pairing code is missing {}", k));
+        Ok(PairingUri {
+            host: take("host")?,
+            port: take("port")?.parse().map_err(|_| "Invalid port in pairing code".to_string())?,
+            pin: take("pin")?,
+            key: take("key")?,
+            name: take("name").unwrap_or_default(),
+        })
+    }
+}
+
+/// Renders `text` as a black-on-white QR code PNG, 8 px per module with the standard quiet zone.
+fn qr_png(text: &str) -> Result<Vec<u8>, String> {
+    const SCALE: u32 = 8;
+    const QUIET: u32 = 4;
+    let code = qrcode::QrCode::new(text.as_bytes()).map_err(|e| e.to_string())?;
+    let width = code.width() as u32;
+    let colors = code.to_colors();
+    let size = (width + 2 * QUIET) * SCALE;
+    let img = image::GrayImage::from_fn(size, size, |x, y| {
+        let (mx, my) = ((x / SCALE).wrapping_sub(QUIET), (y / SCALE).wrapping_sub(QUIET));
+        let dark = mx < width && my < width && colors[(my * width + mx) as usize] == qrcode::Color::Dark;
+        image::Luma([if dark { 0 } else { 255 }])
+    });
+    let mut out = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut out, image::ImageFormat::Png).map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PairRequest {
+    pin: String,
+    /// Name of the device asking to pair.
+    name: String,
+    /// Token the asking device issued for us, so pairing works in both directions.
+    token: String,
+    /// The asking device's key, pinned for requests we send it.
+    public_key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PairResponse {
+    name: String,
+    token: String,
+}
+
+/// Pairs `username` with the peer showing `pin`. Both devices end up trusting
+/// each other with a token of their own and pin each other's key; returns the
+/// peer as now trusted here. The request is already sealed to the key the peer
+/// announces, so the PIN and tokens are never sent in the clear. With
+/// `expected_key` (from a scanned QR code) a peer announcing any other key is refused.
+pub async fn pair_with_peer(db: &Database, username: &str, ip: &str, port: u16, pin: &str, expected_key: Option<&str>) -> Result<TrustedDevice, String> {
+    let hello = peer_hello(ip, port).await?;
+    if expected_key.is_some_and(|k| k != hello.public_key) {
+        return Err("The device at that address is not the one that showed the QR code".to_string());
+    }
+    let peer = envelope::fingerprint(&hello.public_key);
+    db.check_new_pairing(username, &peer, &hello.name).await?;
+    let ours = new_token();
+    let req = PairRequest {
+        pin: pin.trim().to_string(),
+        name: get_hostname(),
+        token: ours.clone(),
+        public_key: db.device_key().await?.public_base64(),
+    };
+    let body = serde_json::to_vec(&req).map_err(|e| e.to_string())?;
+    let (sealed, exchange) = envelope::seal_request(&hello.public_key, &body)?;
+    let url = format!("http://{}:{}/sync/pair", ip, port);
+    let resp = peer_client()?.post(&url).json(&sealed).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        let msg = resp.text().await.unwrap_or_default();
+        return Err(if msg.is_empty() { "Pairing failed".to_string() } else { msg });
+    }
+    let reply: Sealed = resp.json().await.map_err(|e| e.to_string())?;
+    let paired: PairResponse = serde_json::from_slice(&exchange.open_reply(&reply)?).map_err(|e| e.to_string())?;
+    db.set_peer_key(&hello.public_key).await?;
+    db.set_peer_token(username, &peer, &paired.token).await;
+    db.add_trusted_device(username, &peer, &hello.name, token_hash(&ours)).await
+}
+
+async fn accept_pairing(State(state): State<SyncState>, Json(sealed): Json<Sealed>) -> Result<Json<Sealed>, (StatusCode, String)> {
+    let (body, exchange) = envelope::open_request(&state.key, &sealed).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let req: PairRequest = serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let mut pairing = state.pairing.lock().await;
+    let Some(pending) = pairing.as_mut().filter(|p| p.expires_at > crate::database::now_ms()) else {
+        *pairing = None;
+        return Err((StatusCode::FORBIDDEN, "No pairing in progress on that device".to_string()));
+    };
+    if pending.pin != req.pin.trim() {
+        pending.attempts += 1;
+        if pending.attempts >= PAIRING_MAX_ATTEMPTS {
+            // Too many guesses; the user has to start over with a new PIN
+            *pairing = None;
+        }
+        return Err((StatusCode::FORBIDDEN, "Wrong PIN".to_string()));
+    }
+    let username = pending.username.clone();
+    *pairing = None;
+    drop(pairing);
+
+    let conflict = |e: String| (StatusCode::CONFLICT, e);
+    let token = new_token();
+    let peer = state.db.set_peer_key(&req.public_key).await.map_err(conflict)?;
+    state.db.add_trusted_device(&username, &peer, &req.name, token_hash(&token)).await.map_err(conflict)?;
+    state.db.set_peer_token(&username, &peer, &req.token).await;
+    let body = format!("{} can now sync with your collection", req.name);
+    crate::notify::general(&state.app, &state.db, "Device paired", &body).await;
+    let reply = serde_json::to_vec(&PairResponse { name: get_hostname(), token }).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    exchange.seal_reply(&reply).map(Json).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Re-keys pins, peer tokens and sync cursors saved under the name a device
+/// reported to its key's fingerprint, and records the fingerprint on the
+/// trusted device. Pairings without a pinned key are left for the user to redo.
+pub fn migrate_peer_ids(data: &mut crate::models::CollectionData) -> bool {
+    let legacy: HashMap<String, String> = data
+        .peer_keys
+        .iter()
+        .filter(|(peer, key)| **peer != envelope::fingerprint(key))
+        .map(|(name, key)| (name.clone(), envelope::fingerprint(key)))
+        .collect();
+    if legacy.is_empty() {
+        return false;
+    }
+    for name in legacy.keys() {
+        if let Some(key) = data.peer_keys.remove(name) {
+            data.peer_keys.insert(envelope::fingerprint(&key), key);
+        }
+    }
+    for (username, devices) in data.trusted_devices_by_user.iter_mut() {
+        for device in devices.iter_mut().filter(|d| d.fingerprint.is_empty()) {
+            let Some(peer) = legacy.get(&device.name) else {
+                continue;
+            };
+            device.fingerprint = peer.clone();
+            if let Some(tokens) = data.peer_tokens_by_user.get_mut(username) {
+                if let Some(token) = tokens.remove(&device.name) {
+                    tokens.insert(peer.clone(), token);
+                }
+            }
+            if let Some(cursors) = data.sync_cursors_by_user.get_mut(username) {
+                if let Some(cursor) = cursors.remove(&device.name) {
+                    cursors.insert(peer.clone(), cursor);
+                }
+            }
+        }
+    }
+    true
+}
+
 fn get_hostname() -> String {
     hostname::get()
         .map(|h| h.to_string_lossy().to_string())
         .unwrap_or_else(|_| "Unknown".to_string())
 }
 
-async fn get_data(State(state): State<SyncState>) -> Json<CollectionData> {
-    let data = state.db.get_full_data().unwrap_or_default();
-    Json(data)
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers.get("authorization")?.to_str().ok()?.strip_prefix("Bearer ")
+}
+
+async fn ping() -> Json<Pong> {
+    Json(Pong { name: get_hostname(), version: PROTOCOL_VERSION })
 }
 
-async fn receive_data(State(state): State<SyncState>, Json(payload): Json<CollectionData>) -> Json<serde_json::Value> {
-    state.db.merge_full_data(payload).unwrap();
-    Json(serde_json::json!({"ok": true}))
+async fn get_info(State(state): State<SyncState>) -> Json<PeerHello> {
+    Json(PeerHello { name: get_hostname(), version: PROTOCOL_VERSION, public_key: state.key.public_base64() })
+}
+
+/// The only data endpoint: a request sealed to our key carrying a device token.
+/// Anything that fails to open or authenticate is answered with 401, which the
+/// caller reports as `NOT_PAIRED`.
+async fn secure(State(state): State<SyncState>, Json(sealed): Json<Sealed>) -> Result<Json<Sealed>, (StatusCode, String)> {
+    let unauthorized = || (StatusCode::UNAUTHORIZED, "Not paired".to_string());
+    let internal = |e: String| (StatusCode::INTERNAL_SERVER_ERROR, e);
+    let (body, exchange) = envelope::open_request(&state.key, &sealed).map_err(|_| unauthorized())?;
+    let req: SecureRequest = serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let (username, device) = state.db.user_for_sync_token(&token_hash(&req.token)).await.ok_or_else(unauthorized)?;
+    let reply = match req.op {
+        SecureOp::Changes { since } => {
+            // The peer pulling from us is a push from our side
+            let mut session = SyncSession::begin(&device, SyncDirection::Push, false);
+            let payload = state.db.get_sync_changes(&username, since).await;
+            session.sent = payload_len(&payload);
+            state.db.record_sync_session(&username, session.finish(None)).await;
+            serde_json::to_vec(&payload)
+        }
+        SecureOp::Push { payload } => {
+            let mut session = SyncSession::begin(&device, SyncDirection::Pull, false);
+            session.received = payload_len(&payload);
+            let result = state.db.merge_sync_payload(&username, payload).await;
+            if let Ok(summary) = &result {
+                session.applied_here(summary);
+            }
+            state.db.record_sync_session(&username, session.finish(result.as_ref().err().cloned())).await;
+            let summary = result.map_err(internal)?;
+            if summary.added + summary.updated + summary.deleted > 0 {
+                let body = format!(
+                    "{} added, {} updated, {} deleted from a device on your network",
+                    summary.added, summary.updated, summary.deleted
+                );
+                crate::notify::general(&state.app, &state.db, "MediaTracker", &body).await;
+            }
+            if summary.credentials_changed {
+                notify_credentials_changed(&state.app, &state.db, &device).await;
+            }
+            serde_json::to_vec(&summary)
+        }
+    }
+    .map_err(|e| internal(e.to_string()))?;
+    exchange.seal_reply(&reply).map(Json).map_err(internal)
+}
+
+/// Emitted after the browser extension added an item, so the open window can reload.
+pub const QUICK_ADD_EVENT: &str = "quick-add";
+
+/// Random bearer token (browser extension, sync); only its hash is stored.
+pub fn new_token() -> String {
+    use rand_core::{OsRng, RngCore};
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+pub(crate) async fn user_from_bearer(db: &Database, headers: &HeaderMap) -> Option<String> {
+    db.user_for_extension_token(&token_hash(bearer(headers)?)).await
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct QuickAddRequest {
+    url: Option<String>,
+    /// Prefilled by the extension; takes precedence over scraping `url`.
+    item: Option<MediaItem>,
+    category: Option<CollectionCategory>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickAdded {
+    pub username: String,
+    pub item: MediaItem,
+    /// True when the item was already in the collection and nothing was added.
+    pub existing: bool,
+}
+
+async fn quick_add(State(state): State<SyncState>, headers: HeaderMap, Json(req): Json<QuickAddRequest>) -> Result<Json<QuickAdded>, (StatusCode, String)> {
+    let username = user_from_bearer(&state.db, &headers)
+        .await
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid or missing token".to_string()))?;
+    let mut item = match (req.item, req.url.as_deref().map(str::trim).filter(|u| !u.is_empty())) {
+        (Some(item), _) => item,
+        (None, Some(url)) => crate::scrape::draft_for(&state.app, url)
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, e))?
+            .draft,
+        (None, None) => return Err((StatusCode::BAD_REQUEST, "Either url or item is required".to_string())),
+    };
+    if item.title.trim().is_empty() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, "Could not determine a title".to_string()));
+    }
+    let items = state.db.get_all_for_user(&username).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // Same rule as the frontend's addToCollection
+    if let Some(existing) = items.into_iter().find(|i| i.title == item.title && i.media_type == item.media_type) {
+        return Ok(Json(QuickAdded { username, item: existing, existing: true }));
+    }
+    if item.id.trim().is_empty() {
+        item.id = crate::database::new_id();
+    }
+    item.category = Some(req.category.unwrap_or(CollectionCategory::ToWatch));
+    item.saved_at = Some(crate::database::now_ms());
+    state.db.add_item_for_user(&username, item.clone()).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let added = QuickAdded { username, item, existing: false };
+    let _ = state.app.emit(QUICK_ADD_EVENT, added.clone());
+    Ok(Json(added))
 }
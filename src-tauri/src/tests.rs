@@ -1,39 +1,1064 @@
-#[cfg(test)]
-mod tests {
-    // Removed unused imports and tempfile dependency
+fn sample_item(id: &str, title: &str, release_date: &str) -> crate::models::MediaItem {
+    crate::models::MediaItem {
+        id: id.to_string(),
+        title: title.to_string(),
+        director_or_author: "Director".to_string(),
+        description: "Desc".to_string(),
+        release_date: release_date.to_string(),
+        media_type: crate::models::MediaType::Movie,
+        ..Default::default()
+    }
 }
 
 #[test]
 fn test_media_item_serialization() {
-    let item = crate::models::MediaItem {
-        id: "123".to_string(),
-        title: "Test Movie".to_string(),
-        director_or_author: "Director".to_string(),
-        description: "Desc".to_string(),
-        release_date: "2024".to_string(),
-        media_type: crate::models::MediaType::Movie,
-        is_ongoing: false,
-        latest_update_info: None,
-        category: None,
-        saved_at: None,
-        poster_url: None,
-        rating: None,
-        cast: None,
-        user_progress: None,
-        notification_enabled: None,
-        last_checked_at: None,
-        has_new_update: None,
-        user_review: None,
-        custom_poster_url: None,
-        last_edited_at: None,
-        status: None,
-        added_at: None,
-        user_rating: None,
-        parent_collection_id: None,
-        is_collection: None,
-    };
+    let item = sample_item("123", "Test Movie", "2024");
 
     let json = serde_json::to_string(&item).unwrap();
     assert!(json.contains("\"title\":\"Test Movie\""));
     assert!(json.contains("\"type\":\"Movie\""));
 }
+
+#[test]
+fn test_find_duplicates_normalizes_titles_and_years() {
+    let items = vec![
+        sample_item("a", "Spirited Away", "2001"),
+        sample_item("b", "spirited-away!", "2002-07-20"),
+        sample_item("c", "Spirited Away", "1985"),
+        sample_item("d", "Inception", "2010"),
+    ];
+    let groups = crate::dedupe::find_duplicates(&items);
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0].item_ids, vec!["a".to_string(), "b".to_string()]);
+}
+
+#[test]
+fn test_smart_query_filters_items() {
+    let mut a = sample_item("a", "Dune", "2021");
+    a.tags = Some(vec!["Sci-Fi".to_string()]);
+    a.rating = Some("8.2/10".to_string());
+    a.category = Some(crate::models::CollectionCategory::ToWatch);
+    let mut b = a.clone();
+    b.id = "b".to_string();
+    b.category = Some(crate::models::CollectionCategory::Watched);
+    let mut c = a.clone();
+    c.id = "c".to_string();
+    c.rating = Some("6.9".to_string());
+
+    let q = crate::smart::parse("type:movie tag:sci-fi -category:watched rating>8").unwrap();
+    let ids: Vec<String> = q.filter(&[a, b, c]).into_iter().map(|i| i.id).collect();
+    assert_eq!(ids, vec!["a".to_string()]);
+    assert!(crate::smart::parse("rating>high").is_err());
+}
+
+#[test]
+fn test_smart_query_text_with_colons_and_whole_day_dates() {
+    let day = crate::database::DAY_MS;
+    let mut a = sample_item("a", "Re:Zero", "2016");
+    a.saved_at = Some(crate::smart::days_from_civil(2024, 3, 15) * day + 15 * 3_600_000);
+    let mut b = sample_item("b", "Steins;Gate", "2011");
+    b.saved_at = Some(crate::smart::days_from_civil(2024, 3, 16) * day);
+
+    let ids = |q: &str| -> Vec<String> {
+        crate::smart::parse(q).unwrap().filter(&[a.clone(), b.clone()]).into_iter().map(|i| i.id).collect()
+    };
+    // Quoted or not a filter key: plain text
+    assert_eq!(ids("\"re:zero\""), vec!["a".to_string()]);
+    assert_eq!(ids("re:zero"), vec!["a".to_string()]);
+    assert_eq!(ids("-\"re:zero\""), vec!["b".to_string()]);
+    // A date matches the whole day (or month) it names
+    assert_eq!(ids("added:2024-03-15"), vec!["a".to_string()]);
+    assert_eq!(ids("added:2024-03").len(), 2);
+    assert_eq!(ids("added>2024-03-15"), vec!["b".to_string()]);
+    assert_eq!(ids("added<=2024-03-15"), vec!["a".to_string()]);
+}
+
+#[test]
+fn test_parse_rss_and_atom_feeds() {
+    let rss = r#"<?xml version="1.0"?><rss><channel><title>Site</title>
+        <item><title><![CDATA[Chapter 105]]></title><link>https://example.com/105</link><guid>c105</guid></item>
+        <item><title>Chapter 104</title><link>https://example.com/104</link></item>
+        </channel></rss>"#;
+    let entries = crate::feeds::parse_feed(rss).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].title, "Chapter 105");
+    assert_eq!(entries[0].id, "c105");
+    assert_eq!(entries[1].id, "https://example.com/104");
+
+    let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Site</title>
+        <entry><id>tag:1</id><title>Ep 3</title><link rel="alternate" href="https://example.com/3"/><updated>2024-05-01</updated></entry>
+        </feed>"#;
+    let entries = crate::feeds::parse_feed(atom).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].link.as_deref(), Some("https://example.com/3"));
+    assert_eq!(entries[0].published.as_deref(), Some("2024-05-01"));
+}
+
+#[test]
+fn test_metadata_refresh_keeps_user_fields() {
+    let mut item = sample_item("a", "Dune", "2021");
+    item.user_review = Some("Loved it".to_string());
+    item.custom_poster_url = Some("https://example.com/mine.jpg".to_string());
+    let details = crate::metadata::ItemDetails {
+        description: Some("Paul Atreides...".to_string()),
+        release_date: Some("2021".to_string()),
+        poster_url: Some("https://image.tmdb.org/t/p/w500/d.jpg".to_string()),
+        ..Default::default()
+    };
+    let changes = crate::metadata::apply_details(&mut item, details);
+    assert_eq!(changes.updated, vec!["description".to_string(), "posterUrl".to_string()]);
+    assert_eq!(changes.preserved, vec!["userReview".to_string(), "customPosterUrl".to_string()]);
+    assert_eq!(item.user_review.as_deref(), Some("Loved it"));
+    assert_eq!(item.custom_poster_url.as_deref(), Some("https://example.com/mine.jpg"));
+}
+
+#[test]
+fn test_scrape_page_prefers_json_ld() {
+    let html = r#"<html><head><title>Fallback</title>
+        <meta property="og:title" content="Dune &amp; More">
+        <meta property="og:image" content="/img/dune.jpg">
+        <meta name="description" content='Desert planet'>
+        <script type="application/ld+json">{"@context":"https://schema.org","@graph":[
+            {"@type":"WebSite","name":"Site"},
+            {"@type":"Movie","name":"Dune","datePublished":"2021-10-22T00:00:00Z",
+             "director":[{"@type":"Person","name":"Denis Villeneuve"}],
+             "actor":[{"name":"Timothée Chalamet"},{"name":"Zendaya"}]}]}</script>
+        </head></html>"#;
+    let page = crate::scrape::parse_page("https://www.themoviedb.org/movie/438631-dune", html);
+    assert_eq!(page.title.as_deref(), Some("Dune"));
+    assert_eq!(page.release_date.as_deref(), Some("2021-10-22"));
+    assert_eq!(page.creator.as_deref(), Some("Denis Villeneuve"));
+    assert_eq!(page.image.as_deref(), Some("https://www.themoviedb.org/img/dune.jpg"));
+    assert_eq!(page.description.as_deref(), Some("Desert planet"));
+    assert_eq!(page.draft.media_type, crate::models::MediaType::Movie);
+    assert_eq!(page.draft.cast.as_ref().map(|c| c.len()), Some(2));
+    assert_eq!(page.draft.provider_ids.as_ref().and_then(|ids| ids.get("tmdb")).map(String::as_str), Some("438631"));
+}
+
+#[test]
+fn test_decode_entities_multibyte() {
+    use crate::scrape::decode_entities;
+    // A bare '&' followed by CJK text must not slice inside a character
+    assert_eq!(decode_entities("& 中文字幕"), "& 中文字幕");
+    assert_eq!(decode_entities("豆瓣&中文字幕字幕&amp;"), "豆瓣&中文字幕字幕&");
+    assert_eq!(decode_entities("&#x4e2d;&#25991;"), "中文");
+}
+
+#[test]
+fn test_sync_envelope_round_trip() {
+    use crate::envelope::{open_request, seal_request, DeviceKey};
+    let server = DeviceKey::generate();
+    let (sealed, client) = seal_request(&server.public_base64(), b"hello").unwrap();
+    assert!(!sealed.data.contains("hello"));
+    let (body, exchange) = open_request(&server, &sealed).unwrap();
+    assert_eq!(body, b"hello");
+    let reply = exchange.seal_reply(b"world").unwrap();
+    assert_eq!(client.open_reply(&reply).unwrap(), b"world");
+    // Only the key the request was sealed to can open it
+    assert!(open_request(&DeviceKey::generate(), &sealed).is_err());
+}
+
+#[test]
+fn test_pairing_uri_round_trip() {
+    let uri = crate::sync::PairingUri {
+        host: "192.168.1.20".to_string(),
+        port: 14567,
+        pin: "abc123".to_string(),
+        key: "k+y/=".to_string(),
+        name: "Living Room PC".to_string(),
+    };
+    let text = uri.to_string();
+    assert!(text.starts_with("mediatracker://pair?host=192.168.1.20&port=14567"));
+    assert_eq!(text.parse::<crate::sync::PairingUri>().unwrap(), uri);
+    assert!("https://example.com".parse::<crate::sync::PairingUri>().is_err());
+}
+
+#[test]
+fn test_login_throttle_backs_off_and_resets() {
+    let throttle = crate::auth::LoginThrottle::default();
+    for _ in 0..2 {
+        assert!(!throttle.failed("alice"));
+        assert!(throttle.check("alice").is_ok());
+    }
+    assert!(!throttle.failed("Alice"));
+    let err = throttle.check("alice").unwrap_err();
+    assert!(err.starts_with(crate::auth::TOO_MANY_ATTEMPTS));
+    assert!(throttle.check("bob").is_ok());
+    throttle.succeeded("alice");
+    assert!(throttle.check("alice").is_ok());
+}
+
+#[test]
+fn test_at_rest_encryption_round_trip() {
+    use crate::at_rest::{decode, encode, is_encrypted, new_key};
+    let key = new_key();
+    let json = br#"{"users":[]}"#.to_vec();
+    let sealed = encode(json.clone(), Some(&key)).unwrap();
+    assert!(is_encrypted(&sealed));
+    assert_eq!(decode(&sealed, Some(&key)).unwrap().as_ref(), json.as_slice());
+    assert!(decode(&sealed, Some(&new_key())).is_err());
+    assert!(decode(&sealed, None).is_err());
+    // Plaintext passes through both ways
+    assert_eq!(encode(json.clone(), None).unwrap(), json);
+    assert_eq!(decode(&json, Some(&key)).unwrap().as_ref(), json.as_slice());
+}
+
+#[test]
+fn test_activity_events_follow_progress() {
+    use crate::activity::{events_for, ActivityKind};
+    let kinds = |before: Option<&crate::models::MediaItem>, after: &crate::models::MediaItem| {
+        events_for(before, after, 0).into_iter().map(|e| e.kind).collect::<Vec<_>>()
+    };
+    let added = sample_item("1", "Show", "2024");
+    assert_eq!(kinds(None, &added), vec![ActivityKind::Added]);
+    let started = crate::models::MediaItem { user_progress: Some("S1E1".to_string()), ..added.clone() };
+    assert_eq!(kinds(Some(&added), &started), vec![ActivityKind::Started]);
+    let finished = crate::models::MediaItem {
+        user_progress: Some("S1E8".to_string()),
+        category: Some(crate::models::CollectionCategory::Watched),
+        user_rating: Some(4.5),
+        ..started.clone()
+    };
+    assert_eq!(kinds(Some(&started), &finished), vec![ActivityKind::EpisodeWatched, ActivityKind::Finished, ActivityKind::Rated]);
+    assert!(kinds(Some(&finished), &finished).is_empty());
+}
+
+#[test]
+fn test_streaks_use_local_days() {
+    use crate::activity::{ActivityEvent, ActivityKind};
+    const DAY: i64 = crate::database::DAY_MS;
+    let event = |at: i64| ActivityEvent { at, item_id: "1".to_string(), title: "Show".to_string(), kind: ActivityKind::EpisodeWatched, detail: None };
+    // 23:00 UTC on day 10 is already day 11 at UTC+8
+    let activity = vec![event(10 * DAY + 23 * 3_600_000), event(12 * DAY + 3_600_000), event(20 * DAY)];
+    let utc = crate::stats::streaks(&activity, &[], 21 * DAY, 0);
+    assert_eq!((utc.best, utc.current), (1, 1));
+    let local = crate::stats::streaks(&activity, &[], 21 * DAY, 480);
+    assert_eq!((local.best, local.current), (2, 1));
+    assert_eq!(local.best_ended_on.as_deref(), Some("1970-01-13"));
+}
+
+#[test]
+fn test_heatmap_uses_local_days() {
+    use crate::activity::{ActivityEvent, ActivityKind};
+    let new_year = crate::smart::days_from_civil(2024, 1, 1) * crate::database::DAY_MS;
+    let event = |at: i64| ActivityEvent { at, item_id: "1".to_string(), title: "Show".to_string(), kind: ActivityKind::Finished, detail: None };
+    // 23:30 UTC on Jan 1 is Jan 2 at UTC+1; 00:30 UTC on Jan 1 is still 2023 at UTC-1
+    let activity = vec![event(new_year + 1_800_000), event(new_year + 23 * 3_600_000 + 1_800_000)];
+    let utc = crate::stats::heatmap(2024, &activity, &[], 0);
+    assert_eq!(&utc.completions[..2], &[2, 0]);
+    let east = crate::stats::heatmap(2024, &activity, &[], 60);
+    assert_eq!(&east.completions[..2], &[1, 1]);
+    let west = crate::stats::heatmap(2024, &activity, &[], -60);
+    assert_eq!(&west.completions[..2], &[1, 0]);
+}
+
+#[tokio::test]
+async fn test_year_review_uses_local_year_and_months() {
+    use crate::activity::{ActivityEvent, ActivityKind};
+    const HOUR: i64 = 3_600_000;
+    let new_year = crate::smart::days_from_civil(2024, 1, 1) * crate::database::DAY_MS;
+    let event = |at: i64| ActivityEvent { at, item_id: "1".to_string(), title: "Show".to_string(), kind: ActivityKind::EpisodeWatched, detail: None };
+    // 02:00 UTC on New Year's Day is still 2023 at UTC-5; 20:00 UTC on Jan 31 is February at UTC+8
+    let activity = vec![event(new_year + 2 * HOUR), event(new_year + 30 * 24 * HOUR + 20 * HOUR), event(new_year + 40 * 24 * HOUR)];
+    let stats = |offset: i64| crate::stats::Statistics { from: Some(new_year - offset), to: Some(new_year + 366 * 24 * HOUR - offset), ..Default::default() };
+
+    let west = crate::review::build(2024, stats(-5 * HOUR), &[], &activity[..1], -300);
+    assert!(west.most_binged_month.is_none());
+    let east = crate::review::build(2024, stats(8 * HOUR), &[], &activity, 480);
+    assert_eq!(east.most_binged_month.map(|m| (m.month, m.events)), Some(("2024-02".to_string(), 2)));
+
+    let (db, dir) = temp_db();
+    let review = db.year_review("alice", 2024, -300).await;
+    let expected = stats(-5 * HOUR);
+    assert_eq!((review.stats.from, review.stats.to), (expected.from, expected.to));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_status_and_category_stay_in_step() {
+    use crate::models::CollectionCategory;
+    use crate::statuses::{defaults, reconcile};
+    let statuses = defaults();
+    let before = crate::models::MediaItem { status: Some("To Watch".to_string()), category: Some(CollectionCategory::ToWatch), ..sample_item("1", "Show", "2024") };
+    // Picking a custom status clears a category it has no equivalent for
+    let mut on_hold = crate::models::MediaItem { status: Some("On Hold".to_string()), ..before.clone() };
+    reconcile(Some(&before), &mut on_hold, &statuses);
+    assert_eq!(on_hold.category, None);
+    // An older client that only moves the category moves the status too
+    let mut watched = crate::models::MediaItem { category: Some(CollectionCategory::Watched), ..before.clone() };
+    reconcile(Some(&before), &mut watched, &statuses);
+    assert_eq!(watched.status.as_deref(), Some("Watched"));
+}
+
+#[test]
+fn test_next_episode_rolls_over_seasons() {
+    use crate::episodes::{advance, EpisodeCounts};
+    let counts = EpisodeCounts { seasons: vec![(1, 10), (2, 8)], total: Some(18) };
+    assert_eq!(advance(Some("S01E09"), &counts, false).progress, "S01E10");
+    let rollover = advance(Some("S01E10"), &counts, false);
+    assert_eq!((rollover.progress.as_str(), rollover.finished), ("S02E01", false));
+    assert!(advance(Some("S2E7"), &counts, false).finished);
+    assert!(!advance(Some("S2E7"), &counts, true).finished);
+    let plain = advance(Some("Ep 11/12"), &EpisodeCounts::default(), false);
+    assert_eq!((plain.progress.as_str(), plain.finished), ("Ep 12/12", true));
+}
+
+#[test]
+fn test_infobox_fields_are_cleaned() {
+    let wikitext = "{{Short description|Film}}\n{{Infobox film\n| name = Inception\n| director = [[Christopher Nolan]]<ref>{{cite web|url=x}}</ref>\n| released = {{Film date|2010|07|16|US}}\n| starring = {{Plainlist|\n* [[Leonardo DiCaprio]]\n* [[Ken Watanabe|Watanabe]]\n}}\n}}\n'''Inception''' is a film.";
+    let infobox = crate::wiki::parse_infobox(wikitext);
+    assert_eq!(infobox.get("director").map(String::as_str), Some("Christopher Nolan"));
+    assert_eq!(infobox.get("released").map(String::as_str), Some("2010-07-16"));
+    assert_eq!(infobox.get("starring").map(String::as_str), Some("Leonardo DiCaprio, Watanabe"));
+}
+
+#[test]
+fn test_novelupdates_series_page() {
+    let html = r#"<div class="seriestitlenu">Omniscient Reader&#8217;s Viewpoint</div>
+<div class="seriesimg"><img src="https://cdn.novelupdates.com/images/2018/11/orv.jpg"></div>
+<div id="showauthors"><a class="genre" id="authtag" href="/nauthor/sing-shong/">Sing-Shong</a></div>
+<div id="seriesgenre" class="genre"><a class="genre" href="/genre/action/">Action</a> <a class="genre" href="/genre/fantasy/">Fantasy</a></div>
+<div id="edityear">2018</div>
+<div id="editstatus">551 Chapters (Completed)</div>
+<div id="editdescription"><p>Only I know the <b>end</b> of this world.</p></div>
+<table id="myTable"><tr><td><a class="chp-release" title="c551 (end)" href="//x/">c551 (end)</a></td></tr>
+<tr><td><a class="chp-release" title="c550" href="//y/">c550</a></td></tr></table>"#;
+    let novel = crate::novelupdates::parse_series("omniscient-readers-viewpoint", html);
+    assert_eq!(novel.title, "Omniscient Reader\u{2019}s Viewpoint");
+    assert_eq!(novel.authors, vec!["Sing-Shong"]);
+    assert_eq!(novel.genres, vec!["Action", "Fantasy"]);
+    assert_eq!(novel.year, Some(2018));
+    assert_eq!(novel.description.as_deref(), Some("Only I know the end of this world."));
+    assert_eq!(novel.latest_release.as_deref(), Some("c551 (end)"));
+    assert_eq!(crate::novelupdates::series_id("https://www.novelupdates.com/series/omniscient-readers-viewpoint/").as_deref(), Some("omniscient-readers-viewpoint"));
+}
+
+#[test]
+fn test_ao3_work_stats() {
+    let html = r#"<dl class="work meta group"><dt class="rating tags">Rating:</dt><dd class="rating tags"><ul><li><a class="tag" href="/tags/Mature">Mature</a></li></ul></dd>
+<dt class="fandom tags">Fandom:</dt><dd class="fandom tags"><ul><li><a class="tag">Harry Potter - J. K. Rowling</a></li></ul></dd>
+<dd class="stats"><dl class="stats"><dt class="published">Published:</dt><dd class="published">2020-01-05</dd><dt class="status">Updated:</dt><dd class="status">2024-03-01</dd>
+<dt class="words">Words:</dt><dd class="words">123,456</dd><dt class="chapters">Chapters:</dt><dd class="chapters"><a href="/works/42/chapters/9">12</a>/?</dd></dl></dd></dl>
+<h2 class="title heading">A Long Fic</h2><h3 class="byline heading"><a rel="author" href="/users/someone">someone</a></h3>"#;
+    let work = crate::ao3::parse_work("42", html);
+    assert_eq!(work.title, "A Long Fic");
+    assert_eq!(work.authors, vec!["someone"]);
+    assert_eq!((work.chapters_posted, work.chapters_total, work.complete), (12, None, false));
+    assert_eq!(work.words, Some(123456));
+    assert_eq!(work.updated.as_deref(), Some("2024-03-01"));
+    assert_eq!(work.progress(), "Ch. 12/?");
+    assert!(work.content_rating.map(|r| r.is_mature()).unwrap_or(false));
+    assert_eq!(crate::ao3::work_id("https://archiveofourown.org/works/42/chapters/9").as_deref(), Some("42"));
+}
+
+#[test]
+fn test_bgg_thing_parsing() {
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?><items termsofuse="https://boardgamegeek.com/xmlapi/termsofuse">
+<item type="boardgame" id="13"><thumbnail>https://cf.geekdo-images.com/t.jpg</thumbnail><image>https://cf.geekdo-images.com/i.jpg</image>
+<name type="primary" sortindex="1" value="CATAN" /><name type="alternate" sortindex="1" value="Die Siedler von Catan" />
+<description>Trade &amp;amp; build.&amp;#10;Settle the island.</description><yearpublished value="1995" />
+<minplayers value="3" /><maxplayers value="4" /><playingtime value="120" /><minplaytime value="60" /><maxplaytime value="120" /><minage value="10" />
+<link type="boardgamedesigner" id="11" value="Klaus Teuber" /><link type="boardgamemechanic" id="2072" value="Dice Rolling" />
+<statistics page="1"><ratings><usersrated value="123456" /><average value="7.1" /><averageweight value="2.29" /></ratings></statistics></item></items>"#;
+    let game = crate::bgg::parse_items(xml).unwrap().remove(0);
+    assert_eq!((game.id.as_str(), game.name.as_str(), game.year), ("13", "CATAN", Some(1995)));
+    assert_eq!(game.alt_names, vec!["Die Siedler von Catan"]);
+    assert_eq!(game.description.as_deref(), Some("Trade & build.\nSettle the island."));
+    assert_eq!((game.min_players, game.max_players, game.min_playtime, game.max_playtime), (Some(3), Some(4), Some(60), Some(120)));
+    assert_eq!(game.designers, vec!["Klaus Teuber"]);
+    assert_eq!((game.rating, game.users_rated, game.weight), (Some(7.1), Some(123456), Some(2.29)));
+}
+
+#[test]
+fn test_plex_watched_state_matching() {
+    let show = serde_json::json!({
+        "ratingKey": "42", "type": "show", "guid": "plex://show/5d9c086c46115600200aa2fe", "title": "Dark", "year": 2017,
+        "leafCount": 26, "viewedLeafCount": 26, "lastViewedAt": 1700000000,
+        "Guid": [{ "id": "imdb://tt5753856" }, { "id": "tmdb://70523" }]
+    });
+    let plex = crate::plex::parse_item(&show).unwrap();
+    let key = crate::plex::KEY;
+    assert!(plex.watched);
+    assert_eq!(plex.external_ids.get("tmdbTv").map(String::as_str), Some("70523"));
+
+    let by_title = crate::models::MediaItem { media_type: crate::models::MediaType::TvSeries, ..sample_item("1", "DARK", "2017-12-01") };
+    let mut by_id = sample_item("2", "Dark (German series)", "");
+    by_id.provider_ids = Some(std::collections::HashMap::from([("imdb".to_string(), "tt5753856".to_string())]));
+    let mut items = vec![by_title, by_id];
+    assert_eq!(crate::media_server::find_match(&items, key, &plex), Some(1));
+    items.remove(1);
+    assert_eq!(crate::media_server::find_match(&items, key, &plex), Some(0));
+
+    assert!(crate::media_server::apply(&mut items[0], key, &plex));
+    assert_eq!(items[0].category, Some(crate::models::CollectionCategory::Watched));
+    assert_eq!(items[0].completions.len(), 1);
+    // A second pass with nothing new changes nothing
+    assert!(!crate::media_server::apply(&mut items[0], key, &plex));
+}
+
+#[test]
+fn test_jellyfin_item_parsing() {
+    let movie = serde_json::json!({
+        "Id": "f27caa37e5142225cceded48f6553502", "Name": "The Matrix", "Type": "Movie", "ProductionYear": 1999,
+        "ProviderIds": { "Imdb": "tt0133093", "Tmdb": "603", "Tvdb": "" },
+        "UserData": { "Played": true, "PlayCount": 2, "LastPlayedDate": "2023-11-14T22:13:20.1234567Z" }
+    });
+    let item = crate::jellyfin::parse_item(&movie).unwrap();
+    assert!(item.watched);
+    assert_eq!(item.last_viewed_at, Some(1_700_000_000_000));
+    assert_eq!(item.external_ids.get("tmdb").map(String::as_str), Some("603"));
+    assert!(!item.external_ids.contains_key("tvdb"));
+}
+
+#[test]
+fn test_nfo_round_trip() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<movie>
+  <title>Spirited Away</title><originaltitle>千と千尋の神隠し</originaltitle>
+  <plot>A girl &amp; a bathhouse.</plot><year>2001</year><premiered>2001-07-20</premiered>
+  <director>Hayao Miyazaki</director>
+  <uniqueid type="imdb" default="true">tt0245429</uniqueid><uniqueid type="tmdb">129</uniqueid>
+  <thumb aspect="landscape">https://example.com/fanart.jpg</thumb><thumb aspect="poster">https://example.com/poster.jpg</thumb>
+  <userrating>9</userrating><playcount>1</playcount><lastplayed>2023-11-14 22:13:20</lastplayed>
+  <actor><name>Rumi Hiiragi</name><role>Chihiro</role></actor>
+</movie>"#;
+    let item = crate::nfo::parse(xml).unwrap().unwrap();
+    assert_eq!((item.title.as_str(), item.release_date.as_str(), item.director_or_author.as_str()), ("Spirited Away", "2001-07-20", "Hayao Miyazaki"));
+    assert_eq!(item.description, "A girl & a bathhouse.");
+    assert_eq!(item.poster_url.as_deref(), Some("https://example.com/poster.jpg"));
+    assert_eq!(item.provider_ids.as_ref().and_then(|ids| ids.get("tmdb")).map(String::as_str), Some("129"));
+    assert_eq!(item.category, Some(crate::models::CollectionCategory::Watched));
+    assert_eq!(item.completions[0].at, 1_700_000_000_000);
+    assert_eq!(item.cast, Some(vec!["Rumi Hiiragi".to_string()]));
+
+    let again = crate::nfo::parse(&crate::nfo::to_nfo(&item).unwrap()).unwrap().unwrap();
+    assert_eq!((again.title, again.release_date, again.user_rating), (item.title, item.release_date, item.user_rating));
+    assert_eq!((again.provider_ids, again.completions), (item.provider_ids, item.completions));
+    assert!(crate::nfo::parse("<episodedetails><title>Pilot</title></episodedetails>").unwrap().is_none());
+}
+
+#[test]
+fn test_media_file_name_parsing() {
+    use crate::media_scan::{parse_name, ParsedName};
+    let parsed = |title: &str, year: Option<i32>, season: Option<u32>, episode: Option<u32>| ParsedName { title: title.to_string(), year, season, episode };
+    assert_eq!(parse_name("The.Matrix.1999.1080p.BluRay.x264-GROUP"), parsed("The Matrix", Some(1999), None, None));
+    assert_eq!(parse_name("Blade Runner 2049 (2017) [2160p]"), parsed("Blade Runner 2049", Some(2017), None, None));
+    assert_eq!(parse_name("Dark.S01E02.Lies.WEB"), parsed("Dark", None, Some(1), Some(2)));
+    assert_eq!(parse_name("Doctor Who 2005 1x03"), parsed("Doctor Who", Some(2005), Some(1), Some(3)));
+    assert_eq!(parse_name("[SubsPlease] Frieren - 05 [1080p]"), parsed("Frieren", None, None, Some(5)));
+    assert_eq!(parse_name("05 - The Pilot").title, "");
+    assert!((crate::media_scan::confidence("The Matrix", Some(1999), &["The Matrix"], Some(1999)) - 1.0).abs() < 1e-9);
+    assert!(crate::media_scan::confidence("The Matrix", Some(1999), &["The Matrix"], Some(2021)) < 0.5);
+}
+
+#[test]
+fn test_viewing_history_csv() {
+    let csv = "\u{feff}Title,Date\r\n\
+\"Dark: Season 1: Secrets\",\"23/01/2024\"\r\n\
+\"Dark: Season 1: Lies\",\"24/01/2024\"\r\n\
+\"Dark: Season 2: Beginnings and Endings\",\"25/01/2024\"\r\n\
+\"Stranger Things: Stranger Things 4: Chapter One: The Hellfire Club\",\"26/01/2024\"\r\n\
+\"Star Wars: The Last Jedi\",\"02/02/2024\"\r\n\
+\"Glass Onion: A Knives Out Mystery\",\"03/02/2024\"\r\n";
+    let (items, summary) = crate::viewing_history::parse(csv).unwrap();
+    assert_eq!((summary.rows, summary.series, summary.movies, summary.episodes), (6, 2, 2, 4));
+    let dark = items.iter().find(|i| i.title == "Dark").unwrap();
+    assert_eq!(dark.user_progress.as_deref(), Some("S2E1"));
+    let jedi = items.iter().find(|i| i.title == "Star Wars: The Last Jedi").unwrap();
+    assert_eq!(jedi.media_type, crate::models::MediaType::Movie);
+    assert_eq!(jedi.completions[0].at, crate::smart::days_from_civil(2024, 2, 2) * crate::database::DAY_MS);
+    let title = crate::viewing_history::split_title("Stranger Things: Stranger Things 4: Chapter One: The Hellfire Club");
+    assert_eq!((title.series.as_str(), title.season, title.episode.as_deref()), ("Stranger Things", Some(4), Some("Chapter One: The Hellfire Club")));
+}
+
+#[test]
+fn test_bilibili_follow_parsing() {
+    let entry = serde_json::json!({
+        "season_id": 45969, "title": "葬送的芙莉莲", "cover": "http://i0.hdslb.com/bfs/bangumi/image/a.png",
+        "evaluate": "勇者一行人打倒魔王之后……", "styles": ["奇幻", "冒险"], "is_finish": 0, "follow_status": 2,
+        "progress": "看到第7话 21:03", "new_ep": { "index_show": "更新至第10话" },
+        "publish": { "pub_time": "2023-09-29 23:00:00" }, "rating": { "score": 9.7, "count": 120345 }
+    });
+    let item = crate::bilibili::parse_entry(&entry, 0).unwrap();
+    assert_eq!((item.title.as_str(), item.release_date.as_str()), ("葬送的芙莉莲", "2023-09-29"));
+    assert_eq!((item.status.as_deref(), item.category), (Some("Watching"), None));
+    assert_eq!(item.user_progress.as_deref(), Some("7"));
+    assert_eq!(item.poster_url.as_deref(), Some("https://i0.hdslb.com/bfs/bangumi/image/a.png"));
+    assert_eq!(item.tags.as_deref(), Some(&["Anime".to_string(), "奇幻".to_string(), "冒险".to_string()][..]));
+    assert!(item.is_ongoing);
+
+    let wanted = serde_json::json!({ "season_id": 1, "title": "PV only", "follow_status": 1, "progress": "看到PV1" });
+    let item = crate::bilibili::parse_entry(&wanted, 0).unwrap();
+    assert_eq!(item.category, Some(crate::models::CollectionCategory::ToWatch));
+    assert_eq!(item.user_progress, None);
+}
+
+#[test]
+fn test_ai_extraction_reply_validation() {
+    let reply = "Sure! Here it is:\n```json\n{\"title\": \"Arrival\", \"type\": \"Movie\", \"directorOrAuthor\": \"Denis Villeneuve\", \"releaseDate\": \"2016\", \"tags\": [\"Sci-Fi\", \"sci-fi\", \"Drama\"], \"cast\": null}\n```";
+    let item = crate::extract::parse_reply(reply).unwrap().unwrap();
+    assert_eq!((item.title.as_str(), item.media_type.clone(), item.release_date.as_str()), ("Arrival", crate::models::MediaType::Movie, "2016"));
+    assert_eq!(item.tags, Some(vec!["Sci-Fi".to_string(), "Drama".to_string()]));
+
+    assert!(crate::extract::parse_reply(r#"{"title": "", "type": "Other"}"#).unwrap().is_none());
+    assert!(crate::extract::parse_reply(r#"{"title": "Arrival", "type": "Film"}"#).is_err());
+    assert!(crate::extract::parse_reply(r#"{"title": "Arrival", "type": "Movie", "releaseDate": "Nov 2016"}"#).is_err());
+    assert!(crate::extract::parse_reply(r#"{"title": "Arrival", "type": "Movie", "rating": 9}"#).is_err());
+    assert!(crate::extract::parse_reply("I think it's Arrival.").is_err());
+}
+
+#[test]
+fn test_localized_titles() {
+    let tmdb = serde_json::json!({
+        "title": "Spirited Away", "original_title": "千と千尋の神隠し", "original_language": "ja",
+        "translations": { "translations": [
+            { "iso_639_1": "zh", "iso_3166_1": "TW", "data": { "title": "神隱少女" } },
+            { "iso_639_1": "zh", "iso_3166_1": "CN", "data": { "title": "千与千寻" } },
+            { "iso_639_1": "en", "iso_3166_1": "US", "data": { "title": "Spirited Away" } },
+            { "iso_639_1": "ja", "iso_3166_1": "JP", "data": { "title": "" } }
+        ] }
+    });
+    let found = crate::titles::from_tmdb(&tmdb);
+    assert_eq!(found.get("zh").map(String::as_str), Some("千与千寻"));
+    assert_eq!(found.get("ja").map(String::as_str), Some("千と千尋の神隠し"));
+
+    let mut saved = sample_item("1", "Spirited Away", "2001");
+    saved.alt_titles = found;
+    let imported = sample_item("2", "千与千寻", "2001-07-20");
+    assert!(crate::dedupe::same_work(&saved, &imported));
+    assert_eq!(crate::titles::display_title(&saved, Some("zh")), "千与千寻");
+    assert_eq!(crate::titles::display_title(&saved, Some("fr")), "Spirited Away");
+}
+
+#[test]
+fn test_import_duplicate_decisions() {
+    use crate::dedupe::ImportAction;
+    let mut saved = sample_item("1", "Spirited Away", "2001");
+    saved.provider_ids = Some([("tmdb".to_string(), "129".to_string())].into_iter().collect());
+    saved.alt_titles = [("zh".to_string(), "千与千寻".to_string())].into_iter().collect();
+    let existing = vec![saved, sample_item("2", "Ｐｅｒｆｅｃｔ　Ｂｌｕｅ", "1997")];
+
+    let mut by_id = sample_item("a", "Sen to Chihiro", "");
+    by_id.provider_ids = Some([("tmdb".to_string(), "129".to_string())].into_iter().collect());
+    let incoming = vec![
+        by_id,
+        sample_item("b", "千与千寻", "2002"),
+        sample_item("c", "Perfect Blue", "1997"),
+        sample_item("d", "Paprika", "2006"),
+        sample_item("e", "Paprika!", "2006"),
+        sample_item("f", "Perfect Blue", "2010"),
+    ];
+    let decisions = crate::dedupe::classify_import(&existing, incoming);
+    let actions: Vec<ImportAction> = decisions.iter().map(|d| d.action).collect();
+    assert_eq!(actions, [ImportAction::Skip, ImportAction::Review, ImportAction::Review, ImportAction::Add, ImportAction::Skip, ImportAction::Add]);
+    assert_eq!(decisions[1].existing_item_id.as_deref(), Some("1"));
+    assert_eq!(decisions[2].existing_title.as_deref(), Some("Ｐｅｒｆｅｃｔ　Ｂｌｕｅ"));
+}
+
+#[test]
+fn test_release_date_normalization() {
+    use crate::dates::{parse, DatePrecision};
+    let iso = |raw: &str| parse(raw).map(|d| d.iso);
+    assert_eq!(iso("2024-04-07"), Some("2024-04-07".to_string()));
+    assert_eq!(iso("2010-07-16T00:00:00Z"), Some("2010-07-16".to_string()));
+    assert_eq!(iso("Oct 2023"), Some("2023-10".to_string()));
+    assert_eq!(iso("October 7th, 2023"), Some("2023-10-07".to_string()));
+    assert_eq!(iso("7 Sept 2023"), Some("2023-09-07".to_string()));
+    assert_eq!(iso("2024年4月"), Some("2024-04".to_string()));
+    assert_eq!(iso("23.01.2024"), Some("2024-01-23".to_string()));
+    assert_eq!(iso("2023-02-30"), Some("2023-02".to_string()));
+    assert_eq!(iso("2010–2015"), Some("2010".to_string()));
+    assert_eq!(iso("Marvel Phase 2 (2013)"), Some("2013".to_string()));
+    assert_eq!(parse("2010").map(|d| d.precision), Some(DatePrecision::Year));
+    assert_eq!(parse("TBA"), None);
+
+    let mut items = vec![sample_item("1", "B", "Mar 2021"), sample_item("2", "A", "2020-12-31"), sample_item("3", "C", "")];
+    assert!(crate::dates::migrate(&mut items));
+    assert!(!crate::dates::migrate(&mut items));
+    items.sort_by(crate::dates::compare);
+    assert_eq!(items.iter().map(|i| i.id.as_str()).collect::<Vec<_>>(), ["2", "1", "3"]);
+}
+
+#[test]
+fn test_air_time_conversion() {
+    use crate::airtime::{self, Airing, JST};
+    let hour = 3_600_000;
+    let midnight = crate::smart::days_from_civil(2024, 1, 5) * crate::database::DAY_MS;
+    assert_eq!(airtime::to_utc("2024-01-05", Some("23:30"), JST), Some(midnight + 14 * hour + hour / 2));
+    // Late-night listings run past 24:00
+    assert_eq!(airtime::to_utc("2024-01-05", Some("25:30"), JST), Some(midnight + 16 * hour + hour / 2));
+    assert_eq!(airtime::to_utc("2024-01-05", None, JST), Some(midnight - 9 * hour));
+    assert_eq!(airtime::to_utc("2024-02-30", None, JST), None);
+    assert_eq!(airtime::country_zone("kr"), "Asia/Seoul");
+
+    let timed = Airing { episode: "Ep 6".to_string(), at: midnight + 14 * hour + hour / 2, source_timezone: JST.to_string(), time_known: true };
+    let dated = Airing { at: midnight - 9 * hour, time_known: false, ..timed.clone() };
+    assert_eq!(airtime::local_time(&timed, 480), "2024-01-05 22:30");
+    assert_eq!(airtime::local_time(&dated, -300), "2024-01-04");
+
+    let mut a = sample_item("a", "Frieren", "2023");
+    a.next_airing = Some(timed);
+    let mut b = sample_item("b", "Dungeon Meshi", "2024");
+    b.next_airing = Some(dated);
+    let ics = airtime::calendar(&[a, b, sample_item("c", "No airing", "2024")], -300, midnight);
+    assert!(ics.contains("DTSTART:20240105T143000Z\r\n"));
+    assert!(ics.contains("DTSTART;VALUE=DATE:20240104\r\n"));
+    assert!(ics.contains("SUMMARY:Frieren Ep 6\r\n"));
+    assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+}
+
+#[test]
+fn test_upcoming_feed() {
+    use crate::models::{CollectionCategory, MediaType};
+    use crate::upcoming::{self, UpcomingKind};
+    let day = crate::database::DAY_MS;
+    // 2025-03-01 10:00 UTC, seen from UTC+8
+    let now = crate::smart::days_from_civil(2025, 3, 1) * day + 10 * 3_600_000;
+    let span = upcoming::window(now, 480, 30);
+    assert_eq!(span.0, crate::smart::days_from_civil(2025, 3, 1) * day - 8 * 3_600_000);
+
+    let mut show = sample_item("show", "Frieren", "2023");
+    show.next_airing = crate::airtime::to_utc("2025-03-07", None, crate::airtime::JST).map(|at| crate::airtime::Airing {
+        episode: "Ep 9".to_string(),
+        at,
+        source_timezone: crate::airtime::JST.to_string(),
+        time_known: false,
+    });
+    let mut first = sample_item("first", "Dune", "2021");
+    first.category = Some(CollectionCategory::Watched);
+    let second = sample_item("second", "Dune: Part Three", "2025-03-20");
+    let late = sample_item("late", "Dune: Messiah", "2027-01-01");
+    let relations: Vec<crate::relations::Relation> = [("second", "first"), ("late", "second")]
+        .into_iter()
+        .map(|(from, to)| crate::relations::Relation { from_id: from.to_string(), to_id: to.to_string(), kind: crate::relations::RelationKind::Sequel, source: None, created_at: 0 })
+        .collect();
+    let mut book = sample_item("book", "Piranesi", "2020");
+    book.media_type = MediaType::Book;
+    book.director_or_author = "Susanna Clarke, Someone Else".to_string();
+    book.category = Some(CollectionCategory::Favorites);
+    let items = vec![show, first, second, late, book];
+
+    let entries = upcoming::from_collection(&items, &relations, span, 480);
+    let found: Vec<_> = entries.iter().map(|e| (e.kind, e.title.as_str(), e.date.as_str())).collect();
+    // The second sequel's prequel isn't finished yet
+    assert_eq!(found, [(UpcomingKind::Episode, "Frieren", "2025-03-06"), (UpcomingKind::Sequel, "Dune: Part Three", "2025-03-20")]);
+
+    assert_eq!(upcoming::followed_authors(&items, &[]), ["Susanna Clarke"]);
+    let volumes = serde_json::json!({ "items": [
+        { "id": "a", "volumeInfo": { "title": "The Wood at Midwinter", "publishedDate": "2025-03-11" } },
+        { "id": "b", "volumeInfo": { "title": "The Wood at Midwinter", "publishedDate": "2025-03-12" } },
+        { "id": "c", "volumeInfo": { "title": "Piranesi", "publishedDate": "2025-03-15" } },
+        { "id": "d", "volumeInfo": { "title": "Someday", "publishedDate": "2026" } }
+    ]});
+    let books = upcoming::google_books_entries(&volumes, "Susanna Clarke", &items, span);
+    assert_eq!(books.iter().map(|b| b.source_id.as_deref()).collect::<Vec<_>>(), [Some("a")]);
+}
+
+#[test]
+fn test_followed_creator_new_works() {
+    use crate::follows::{new_works, work_key, FollowState};
+    let work = |id: &str, date: &str| crate::people::PersonWork {
+        title: format!("Work {}", id),
+        release_date: date.to_string(),
+        media_type: Some("movie".to_string()),
+        role: Some("Director".to_string()),
+        poster_url: None,
+        source: "tmdb".to_string(),
+        source_id: id.to_string(),
+        in_collection: false,
+        completed: false,
+    };
+    let now = crate::smart::days_from_civil(2025, 6, 1) * crate::database::DAY_MS;
+    let mut follow = FollowState { since: now, ..Default::default() };
+    let dismissed = std::collections::HashSet::from([work_key("tmdb", "4")]);
+    // The first look only records the back catalogue
+    assert!(new_works(&mut follow, &["tmdb"], vec![work("1", "2019-05-01"), work("2", "")], &dismissed, now).is_empty());
+    assert_eq!(follow.checked_sources, ["tmdb"]);
+
+    let mut collected = work("5", "2025-09-01");
+    collected.in_collection = true;
+    let listing = vec![work("1", "2019-05-01"), work("3", "2026"), work("4", "2025-08-01"), collected, work("6", "2001-01-01"), work("7", "")];
+    let fresh = new_works(&mut follow, &["tmdb"], listing.clone(), &dismissed, now);
+    // Dismissed, collected and old credits are left out
+    assert_eq!(fresh.iter().map(|w| w.source_id.as_str()).collect::<Vec<_>>(), ["3", "7"]);
+    assert!(new_works(&mut follow, &["tmdb"], listing, &dismissed, now).is_empty());
+}
+
+#[test]
+fn test_anilist_seasonal_show() {
+    let media = serde_json::json!({
+        "id": 154587, "format": "TV", "status": "RELEASING", "episodes": 28, "averageScore": 90,
+        "genres": ["Adventure", "Fantasy"],
+        "title": { "romaji": "Sousou no Frieren", "english": "Frieren: Beyond Journey's End", "native": "葬送のフリーレン" },
+        "coverImage": { "large": "https://img.anili.st/frieren.jpg" },
+        "startDate": { "year": 2023, "month": 9, "day": 29 },
+        "studios": { "nodes": [{ "name": "Madhouse" }] },
+        "nextAiringEpisode": { "airingAt": 1704465000, "episode": 18 }
+    });
+    let show = crate::anilist::parse_show(&media).unwrap();
+    assert_eq!(show.title, "Sousou no Frieren");
+    assert_eq!(show.alt_titles.get("ja").map(String::as_str), Some("葬送のフリーレン"));
+    assert_eq!(show.studios, ["Madhouse"]);
+    assert_eq!(show.start_date, "2023-09-29");
+    let next = show.next_airing.clone().unwrap();
+    assert!(next.time_known);
+    // 2024-01-05 23:30 JST
+    assert_eq!(Some(next.at), crate::airtime::to_utc("2024-01-05", Some("23:30"), crate::airtime::JST));
+
+    let mut existing = sample_item("1", "Frieren: Beyond Journey's End", "2023");
+    existing.media_type = crate::models::MediaType::TvSeries;
+    let mut shows = vec![show.clone()];
+    crate::anilist::mark_collected(&mut shows, &[existing]);
+    assert!(shows[0].in_collection);
+
+    let item = crate::anilist::to_item(&show, 0);
+    assert_eq!(item.category, Some(crate::models::CollectionCategory::ToWatch));
+    assert!(item.is_ongoing);
+    assert_eq!(item.director_or_author, "Madhouse");
+    assert_eq!(item.provider_ids.as_ref().and_then(|ids| ids.get("anilist")).map(String::as_str), Some("154587"));
+}
+
+#[test]
+fn test_quick_add_hits() {
+    let tmdb = serde_json::json!({ "results": [
+        { "media_type": "tv", "id": 1396, "name": "Breaking Bad", "original_name": "Breaking Bad", "first_air_date": "2008-01-20", "poster_path": "/bb.jpg" },
+        { "media_type": "person", "id": 17419, "name": "Bryan Cranston" },
+        { "media_type": "movie", "id": 559969, "title": "El Camino", "release_date": "2019-10-11" }
+    ]});
+    let hits = crate::quick_add::parse_tmdb(&tmdb);
+    assert_eq!(hits.iter().map(|h| h.source.as_str()).collect::<Vec<_>>(), ["tmdbTv", "tmdb"]);
+    assert_eq!(hits[0].original_title, None);
+    assert_eq!(hits[0].poster_url.as_deref(), Some("https://image.tmdb.org/t/p/w500/bb.jpg"));
+
+    let bangumi = serde_json::json!({ "list": [
+        { "id": 253, "type": 2, "name": "カウボーイビバップ", "name_cn": "星际牛仔", "air_date": "1998-10-23" },
+        { "id": 9, "type": 4, "name": "Some Game" }
+    ]});
+    let hits = crate::quick_add::parse_bangumi(&bangumi);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].title, "星际牛仔");
+    assert_eq!(hits[0].original_title.as_deref(), Some("カウボーイビバップ"));
+
+    let item = crate::quick_add::to_item(&hits[0], 0);
+    assert_eq!(item.media_type, crate::models::MediaType::TvSeries);
+    assert_eq!(item.category, Some(crate::models::CollectionCategory::ToWatch));
+    assert_eq!(item.provider_ids.as_ref().and_then(|ids| ids.get("bangumi")).map(String::as_str), Some("253"));
+}
+
+#[test]
+fn test_encrypted_collection_without_key_stays_locked() {
+    let dir = std::env::temp_dir().join(format!("mt-locked-{}", crate::database::new_id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("collection.json");
+    // Sealed with a key the keychain doesn't have
+    let real_key = crate::at_rest::new_key();
+    let sealed = crate::at_rest::encode(b"{}".to_vec(), Some(&real_key)).unwrap();
+    std::fs::write(&path, &sealed).unwrap();
+    let (data, report, key) = crate::database::Database::load(&path);
+    let report = report.unwrap();
+    assert!(report.locked && !report.recovered_from_backup);
+    assert!(key.is_none() && data.users.is_empty());
+    // Left in place rather than moved aside as corrupt
+    assert_eq!(std::fs::read(&path).unwrap(), sealed);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    // Only the key that sealed it is taken back
+    let db = crate::database::Database::open(path);
+    assert!(db.is_locked());
+    assert!(db.key_opens_files(&real_key));
+    assert!(!db.key_opens_files(&crate::at_rest::new_key()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn temp_db() -> (crate::database::Database, std::path::PathBuf) {
+    let dir = std::env::temp_dir().join(format!("mt-db-{}", crate::database::new_id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    (crate::database::Database::open(dir.join("collection.json")), dir)
+}
+
+#[tokio::test]
+async fn test_sync_never_sends_or_takes_credentials() {
+    let (db, dir) = temp_db();
+    let user = crate::models::UserRecord {
+        username: "alice".to_string(),
+        password_hash: "$argon2id$local-hash".to_string(),
+        created_at: 0,
+        last_modified: Some(1_000),
+        recovery_codes: vec!["code-hash".to_string()],
+    };
+    db.add_user(user).await.unwrap();
+
+    let outgoing = db.get_sync_changes("alice", 0).await;
+    assert_eq!(outgoing.credentials_changed_at, Some(1_000));
+    let json = serde_json::to_string(&outgoing).unwrap();
+    assert!(!json.contains("local-hash") && !json.contains("code-hash"));
+
+    // A timestamp from the future can't pin anything
+    let incoming = crate::database::SyncPayload { credentials_changed_at: Some(i64::MAX), ..Default::default() };
+    assert!(!db.merge_sync_payload("alice", incoming).await.unwrap().credentials_changed);
+    let incoming = crate::database::SyncPayload { credentials_changed_at: Some(2_000), ..Default::default() };
+    assert!(db.merge_sync_payload("alice", incoming).await.unwrap().credentials_changed);
+    assert_eq!(db.find_user("alice").await.unwrap().password_hash, "$argon2id$local-hash");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_child_saved_before_its_container_joins_it() {
+    let (db, dir) = temp_db();
+    let child = crate::models::MediaItem { id: "child".into(), title: "Part 1".into(), parent_collection_id: Some("box".into()), ..Default::default() };
+    db.add_item_for_user("alice", child.clone()).await.unwrap();
+    // Still linked while the container is on its way
+    let items = db.get_all_for_user("alice").await.unwrap();
+    assert_eq!(items[0].parent_collection_id.as_deref(), Some("box"));
+
+    let container = crate::models::MediaItem { id: "box".into(), title: "Box Set".into(), is_collection: Some(true), ..Default::default() };
+    db.add_item_for_user("alice", container).await.unwrap();
+    let collections = db.get_collections("alice").await;
+    assert_eq!(collections.len(), 1);
+    assert_eq!(collections[0].item_ids, vec!["child".to_string()]);
+
+    // Clearing the link takes it back out
+    db.leave_collections("alice", "child").await;
+    db.add_item_for_user("alice", crate::models::MediaItem { parent_collection_id: None, ..child }).await.unwrap();
+    assert!(db.get_collections("alice").await[0].item_ids.is_empty());
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_douban_rating_from_subject_page() {
+    let html = r#"<div class="rating_self"><strong class="ll rating_num" property="v:average"> 9.2 </strong>
+        <div class="rating_sum"><a href="collections"><span property="v:votes">3,021,456</span>人评价</a></div></div>"#;
+    let rating = crate::ratings::from_douban_page(html, 1).unwrap();
+    assert_eq!((rating.score, rating.scale, rating.votes), (9.2, 10.0, Some(3_021_456)));
+    // Too few ratings: the score element is empty
+    assert!(crate::ratings::from_douban_page(r#"<strong class="ll rating_num" property="v:average"></strong>"#, 1).is_none());
+}
+
+#[test]
+fn test_legacy_intervals_move_into_job_schedules() {
+    use crate::scheduler::{JobKind, JobSchedule};
+    let mut settings: crate::models::Settings =
+        serde_json::from_str(r#"{"updateCheckIntervalMinutes": 120, "feedPollIntervalMinutes": 0}"#).unwrap();
+    assert!(crate::scheduler::migrate_legacy_intervals(&mut settings));
+    assert_eq!(settings.job(JobKind::UpdateCheck), JobSchedule { enabled: true, interval_minutes: 120 });
+    assert!(!settings.job(JobKind::FeedPoll).enabled);
+    // Moved once, and not written back out
+    assert!(!crate::scheduler::migrate_legacy_intervals(&mut settings));
+    assert!(!serde_json::to_string(&settings).unwrap().contains("IntervalMinutes"));
+}
+
+async fn stored(db: &crate::database::Database, id: &str) -> crate::models::MediaItem {
+    db.get_all_for_user("alice").await.unwrap().into_iter().find(|i| i.id == id).unwrap()
+}
+
+#[tokio::test]
+async fn test_sync_merge_keeps_the_newer_edit() {
+    let (db, dir) = temp_db();
+    db.add_item_for_user("alice", sample_item("a", "Local", "2020")).await.unwrap();
+    let local = stored(&db, "a").await;
+
+    let older = crate::models::MediaItem { title: "Older".into(), updated_at: Some(local.version() - 1), ..local.clone() };
+    let summary = db.merge_sync_payload("alice", crate::database::SyncPayload { items: vec![older], ..Default::default() }).await.unwrap();
+    assert_eq!((summary.updated, summary.unchanged), (0, 1));
+    assert_eq!(stored(&db, "a").await.title, "Local");
+
+    // Same version, different content: ours stays and the user gets to pick
+    let tie = crate::models::MediaItem { title: "Tie".into(), ..local.clone() };
+    let summary = db.merge_sync_payload("alice", crate::database::SyncPayload { items: vec![tie], ..Default::default() }).await.unwrap();
+    assert!(matches!(summary.conflicts[0].kind, crate::database::ConflictKind::Edit));
+    assert_eq!(stored(&db, "a").await.title, "Local");
+
+    let newer = crate::models::MediaItem { title: "Newer".into(), updated_at: Some(local.version() + 1), ..local };
+    let summary = db.merge_sync_payload("alice", crate::database::SyncPayload { items: vec![newer], ..Default::default() }).await.unwrap();
+    assert_eq!(summary.updated, 1);
+    assert_eq!(stored(&db, "a").await.title, "Newer");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_sync_merge_delete_vs_edit() {
+    let (db, dir) = temp_db();
+    db.add_item_for_user("alice", sample_item("a", "Edited here", "2020")).await.unwrap();
+    db.add_item_for_user("alice", sample_item("b", "Untouched", "2020")).await.unwrap();
+    let edited = stored(&db, "a").await;
+    let untouched = stored(&db, "b").await;
+
+    // Deleted there before our last edit: the edit wins; deleted after: it goes to trash
+    let tombstones = vec![
+        crate::models::Tombstone { item_id: "a".into(), deleted_at: edited.version() - 1 },
+        crate::models::Tombstone { item_id: "b".into(), deleted_at: untouched.version() + 1 },
+    ];
+    let summary = db.merge_sync_payload("alice", crate::database::SyncPayload { tombstones, ..Default::default() }).await.unwrap();
+    assert_eq!(summary.deleted, 1);
+    assert!(matches!(summary.conflicts[0].kind, crate::database::ConflictKind::DeleteVsEdit));
+    assert!(matches!(summary.conflicts[0].resolution, crate::database::ConflictResolution::KeptLocal));
+    let ids: Vec<String> = db.get_all_for_user("alice").await.unwrap().into_iter().map(|i| i.id).collect();
+    assert_eq!(ids, vec!["a".to_string()]);
+    assert_eq!(db.get_trash_for_user("alice").await.unwrap()[0].id, "b");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_sync_merge_resurrects_only_items_edited_after_deletion() {
+    let (db, dir) = temp_db();
+    db.add_item_for_user("alice", sample_item("a", "Gone", "2020")).await.unwrap();
+    let copy = stored(&db, "a").await;
+    db.remove_item_for_user("alice", "a").await.unwrap();
+    let deleted_at = db.get_trash_for_user("alice").await.unwrap()[0].deleted_at.unwrap();
+
+    // The other device's copy predates the deletion: stays deleted
+    let stale = crate::models::MediaItem { updated_at: Some(deleted_at - 1), ..copy.clone() };
+    let summary = db.merge_sync_payload("alice", crate::database::SyncPayload { items: vec![stale], ..Default::default() }).await.unwrap();
+    assert_eq!((summary.added, summary.unchanged), (0, 1));
+    assert!(db.get_all_for_user("alice").await.unwrap().is_empty());
+
+    // Edited there after the deletion: it comes back, flagged as a conflict
+    let revived = crate::models::MediaItem { title: "Back".into(), updated_at: Some(deleted_at + 1), ..copy };
+    let summary = db.merge_sync_payload("alice", crate::database::SyncPayload { items: vec![revived], ..Default::default() }).await.unwrap();
+    assert_eq!(summary.added, 1);
+    assert!(matches!(summary.conflicts[0].resolution, crate::database::ConflictResolution::TookRemote));
+    assert_eq!(stored(&db, "a").await.title, "Back");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Edits outside the journal land in a later millisecond than the journaled ones
+fn next_ms() {
+    std::thread::sleep(std::time::Duration::from_millis(2));
+}
+
+#[tokio::test]
+async fn test_undo_redo_skips_items_edited_since() {
+    let (db, dir) = temp_db();
+    db.add_item_for_user("alice", sample_item("a", "Old", "2020")).await.unwrap();
+    next_ms();
+    let renamed = crate::models::MediaItem { title: "New".into(), ..stored(&db, "a").await };
+    db.add_item_for_user("alice", renamed).await.unwrap();
+
+    let undone = db.undo_last_operation("alice").await.unwrap().unwrap();
+    assert!(undone.skipped.is_empty());
+    assert_eq!(stored(&db, "a").await.title, "Old");
+    let redone = db.redo_last_operation("alice").await.unwrap().unwrap();
+    assert!(redone.skipped.is_empty());
+    assert_eq!(stored(&db, "a").await.title, "New");
+    db.undo_last_operation("alice").await.unwrap().unwrap();
+
+    // A completion is not journaled; redoing the rename must not wipe it
+    next_ms();
+    db.add_completion("alice", "a", None, None).await.unwrap();
+    let redone = db.redo_last_operation("alice").await.unwrap().unwrap();
+    assert_eq!(redone.skipped, vec!["a".to_string()]);
+    assert!(redone.operation.changes.is_empty());
+    let item = stored(&db, "a").await;
+    assert_eq!((item.title.as_str(), item.completions.len()), ("Old", 1));
+    assert!(db.redo_last_operation("alice").await.unwrap().is_none());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_bulk_update_undo_leaves_later_edits() {
+    let (db, dir) = temp_db();
+    db.add_item_for_user("alice", sample_item("a", "A", "2020")).await.unwrap();
+    db.add_item_for_user("alice", sample_item("b", "B", "2020")).await.unwrap();
+    next_ms();
+    let patch = crate::models::ItemPatch { add_tags: Some(vec!["queued".into()]), ..Default::default() };
+    db.bulk_update_for_user("alice", &["a".to_string(), "b".to_string()], &patch).await.unwrap();
+    next_ms();
+    db.add_completion("alice", "b", None, None).await.unwrap();
+
+    let undone = db.undo_last_operation("alice").await.unwrap().unwrap();
+    assert_eq!(undone.skipped, vec!["b".to_string()]);
+    assert_eq!(undone.operation.changes.len(), 1);
+    assert!(stored(&db, "a").await.tags.unwrap_or_default().is_empty());
+    let b = stored(&db, "b").await;
+    assert_eq!(b.tags, Some(vec!["queued".to_string()]));
+    assert_eq!(b.completions.len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_merge_undo_and_redo() {
+    let (db, dir) = temp_db();
+    db.add_item_for_user("alice", sample_item("a", "Keep", "2020")).await.unwrap();
+    db.add_item_for_user("alice", sample_item("b", "Dupe", "2020")).await.unwrap();
+    next_ms();
+    db.merge_items_for_user("alice", "a", &["b".to_string()]).await.unwrap();
+    assert!(db.find_item("alice", "b").await.is_none());
+
+    let undone = db.undo_last_operation("alice").await.unwrap().unwrap();
+    assert!(undone.skipped.is_empty());
+    assert_eq!(stored(&db, "b").await.title, "Dupe");
+    assert!(db.get_trash_for_user("alice").await.unwrap().iter().all(|i| i.id != "b"));
+
+    let redone = db.redo_last_operation("alice").await.unwrap().unwrap();
+    assert!(redone.skipped.is_empty());
+    assert!(db.find_item("alice", "b").await.is_none());
+    assert!(db.get_trash_for_user("alice").await.unwrap().iter().any(|i| i.id == "b"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn test_peers_are_pinned_by_key_fingerprint() {
+    let (db, dir) = temp_db();
+    let laptop = crate::envelope::DeviceKey::generate().public_base64();
+    let impostor = crate::envelope::DeviceKey::generate().public_base64();
+    let peer = db.set_peer_key(&laptop).await.unwrap();
+    assert_eq!(peer, crate::envelope::fingerprint(&laptop));
+    db.add_trusted_device("alice", &peer, "laptop", "hash".into()).await.unwrap();
+    db.set_peer_token("alice", &peer, "token").await;
+    assert_eq!(db.users_paired_with(&peer).await, vec!["alice".to_string()]);
+
+    // Another device reporting the same hostname gets its own pin and can't take over the pairing
+    let other = db.set_peer_key(&impostor).await.unwrap();
+    assert_ne!(other, peer);
+    assert!(db.check_new_pairing("alice", &other, "laptop").await.is_err());
+    assert!(db.add_trusted_device("alice", &other, "laptop", "hash2".into()).await.is_err());
+    assert!(db.users_paired_with("laptop").await.is_empty());
+    assert_eq!(db.peer_key(&peer).await.as_deref(), Some(laptop.as_str()));
+
+    // Re-pairing the same device replaces its entry
+    db.add_trusted_device("alice", &peer, "laptop", "hash3".into()).await.unwrap();
+    assert_eq!(db.get_trusted_devices("alice").await.len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_legacy_peer_pins_move_to_fingerprints() {
+    let key = crate::envelope::DeviceKey::generate().public_base64();
+    let peer = crate::envelope::fingerprint(&key);
+    let mut data = crate::models::CollectionData::default();
+    data.peer_keys.insert("laptop".into(), key.clone());
+    data.peer_tokens_by_user.entry("alice".into()).or_default().insert("laptop".into(), "token".into());
+    data.sync_cursors_by_user.entry("alice".into()).or_default().insert("laptop".into(), Default::default());
+    data.trusted_devices_by_user.insert(
+        "alice".into(),
+        vec![crate::models::TrustedDevice {
+            id: "d1".into(),
+            name: "laptop".into(),
+            fingerprint: String::new(),
+            token_hash: "hash".into(),
+            paired_at: 0,
+            last_seen_at: None,
+            last_synced_at: None,
+        }],
+    );
+
+    assert!(crate::sync::migrate_peer_ids(&mut data));
+    assert_eq!(data.peer_keys.get(&peer), Some(&key));
+    assert!(!data.peer_keys.contains_key("laptop"));
+    assert_eq!(data.peer_tokens_by_user["alice"].get(&peer).map(String::as_str), Some("token"));
+    assert!(data.sync_cursors_by_user["alice"].contains_key(&peer));
+    assert_eq!(data.trusted_devices_by_user["alice"][0].fingerprint, peer);
+    assert!(!crate::sync::migrate_peer_ids(&mut data));
+}
+
+#[test]
+fn test_oversized_attachments_are_refused_before_reading() {
+    let dir = std::env::temp_dir().join(format!("mt-notes-{}", crate::database::new_id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let attachments = crate::notes::Attachments(crate::images::ImageCache::new(dir.join("attachments")));
+    let big = dir.join("big.png");
+    std::fs::File::create(&big).unwrap().set_len(20 * 1024 * 1024 + 1).unwrap();
+    assert_eq!(crate::notes::store(&attachments, big.to_str().unwrap(), 0), Err("Image is too large".to_string()));
+    let data_url = format!("data:image/png;base64,{}", "A".repeat(28 * 1024 * 1024));
+    assert_eq!(crate::notes::store(&attachments, &data_url, 0), Err("Image is too large".to_string()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,133 @@
+// Localized titles. An item's Chinese, English and Japanese titles are looked up
+// by its provider ids on TMDB (translations and original title), Bangumi (name
+// and name_cn) and Wikidata (labels), and kept in `MediaItem::alt_titles`, which
+// dedupe matches against and the UI can show instead of the saved title.
+
+use std::collections::HashMap;
+use reqwest::Client;
+use serde_json::Value;
+use crate::models::MediaItem;
+
+pub const LANGUAGES: [&str; 3] = ["zh", "en", "ja"];
+/// Chinese variants, most preferred first; TMDB names them by region.
+const CHINESE_REGIONS: [&str; 4] = ["CN", "SG", "TW", "HK"];
+
+fn non_empty(v: &Value) -> Option<String> {
+    v.as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+fn has_kana(s: &str) -> bool {
+    s.chars().any(|c| ('\u{3040}'..='\u{30ff}').contains(&c))
+}
+
+/// Language codes as providers write them ("zh-hans", "zh-CN", "en-US") reduced to ours.
+fn language(code: &str) -> Option<&'static str> {
+    let base = code.split(['-', '_']).next()?.to_ascii_lowercase();
+    LANGUAGES.iter().copied().find(|l| *l == base)
+}
+
+/// Titles from a TMDB movie or TV details response with `append_to_response=translations`.
+pub fn from_tmdb(v: &Value) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let title_key = if v.get("name").is_some() { "name" } else { "title" };
+    let mut translations: Vec<&Value> = v["translations"]["translations"].as_array().into_iter().flatten().collect();
+    // Mainland Chinese first, so it wins over the other regions
+    translations.sort_by_key(|t| CHINESE_REGIONS.iter().position(|r| t["iso_3166_1"].as_str() == Some(*r)).unwrap_or(CHINESE_REGIONS.len()));
+    for t in translations {
+        let (Some(lang), Some(title)) = (t["iso_639_1"].as_str().and_then(language), non_empty(&t["data"][title_key])) else {
+            continue;
+        };
+        out.entry(lang.to_string()).or_insert(title);
+    }
+    // Translations in the original language are often blank; the original title isn't
+    let original = non_empty(&v[if title_key == "name" { "original_name" } else { "original_title" }]);
+    if let (Some(lang), Some(title)) = (v["original_language"].as_str().and_then(language), original) {
+        out.entry(lang.to_string()).or_insert(title);
+    }
+    out
+}
+
+/// Titles from a Bangumi subject: `name_cn` is Chinese, `name` the original,
+/// which is taken as Japanese when it has kana.
+pub fn from_bangumi(v: &Value) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    if let Some(zh) = non_empty(&v["name_cn"]) {
+        out.insert("zh".to_string(), zh);
+    }
+    if let Some(name) = non_empty(&v["name"]).filter(|n| has_kana(n)) {
+        out.insert("ja".to_string(), name);
+    }
+    out
+}
+
+/// Adds the languages `found` has and `titles` lacks; titles already set are kept.
+pub fn merge(titles: &mut HashMap<String, String>, found: HashMap<String, String>) -> bool {
+    let mut changed = false;
+    for (lang, title) in found {
+        if !title.trim().is_empty() && !titles.contains_key(&lang) {
+            titles.insert(lang, title.trim().to_string());
+            changed = true;
+        }
+    }
+    changed
+}
+
+async fn tmdb(client: &Client, ids: &HashMap<String, String>, api_key: &str) -> Result<HashMap<String, String>, String> {
+    let path = match (ids.get("tmdb"), ids.get("tmdbTv")) {
+        (Some(id), _) => format!("movie/{}", urlencoding::encode(id)),
+        (None, Some(id)) => format!("tv/{}", urlencoding::encode(id)),
+        _ => return Ok(HashMap::new()),
+    };
+    let url = format!("https://api.themoviedb.org/3/{}?api_key={}&append_to_response=translations", path, urlencoding::encode(api_key));
+    Ok(from_tmdb(&crate::fetch_json(client, &url).await?))
+}
+
+async fn bangumi(client: &Client, id: &str) -> Result<HashMap<String, String>, String> {
+    let v = crate::fetch_json(client, &format!("https://api.bgm.tv/v0/subjects/{}", urlencoding::encode(id))).await?;
+    Ok(from_bangumi(&v))
+}
+
+async fn wikidata(client: &Client, ids: &HashMap<String, String>) -> Result<HashMap<String, String>, String> {
+    // Only by provider id: a title search could land on another work
+    let Some(entity) = crate::wikidata::resolve(client, ids, None, "en").await? else {
+        return Ok(HashMap::new());
+    };
+    let labels = crate::wikidata::labels(client, &entity, &["zh-cn", "zh-hans", "zh", "en", "ja"]).await?;
+    let mut out = HashMap::new();
+    for code in ["zh-cn", "zh-hans", "zh", "en", "ja"] {
+        if let (Some(lang), Some(label)) = (language(code), labels.get(code)) {
+            out.entry(lang.to_string()).or_insert_with(|| label.clone());
+        }
+    }
+    Ok(out)
+}
+
+/// Titles for the item from every source it has an id for, TMDB first, then
+/// Bangumi, then Wikidata. A failing source is skipped unless all of them fail.
+pub async fn fetch(client: &Client, item: &MediaItem, tmdb_api_key: Option<&str>) -> Result<HashMap<String, String>, String> {
+    let ids = item.provider_ids.clone().unwrap_or_default();
+    if ids.is_empty() {
+        return Err("The item has no provider ids to look titles up by".to_string());
+    }
+    let mut results = Vec::new();
+    if let Some(key) = tmdb_api_key.filter(|_| ids.contains_key("tmdb") || ids.contains_key("tmdbTv")) {
+        results.push(tmdb(client, &ids, key).await);
+    }
+    if let Some(id) = ids.get("bangumi") {
+        results.push(bangumi(client, id).await);
+    }
+    results.push(wikidata(client, &ids).await);
+    if results.iter().all(Result::is_err) {
+        return Err(results.pop().and_then(Result::err).unwrap_or_default());
+    }
+    let mut titles = HashMap::new();
+    for found in results.into_iter().flatten() {
+        merge(&mut titles, found);
+    }
+    Ok(titles)
+}
+
+/// The item's title in `lang`, or its saved title.
+pub fn display_title<'a>(item: &'a MediaItem, lang: Option<&str>) -> &'a str {
+    lang.and_then(|l| item.alt_titles.get(l)).map(String::as_str).unwrap_or(&item.title)
+}
@@ -0,0 +1,109 @@
+// Trailer lookup. With a YouTube Data API key (stored as the `youtube` secret)
+// the API is searched directly; otherwise the configured web search is asked
+// for youtube.com results. Candidates are scored on their titles so official
+// trailers beat reactions and reviews.
+
+use reqwest::Client;
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Trailer {
+    pub url: String,
+    pub video_id: String,
+    pub title: String,
+    pub channel: Option<String>,
+}
+
+const NOT_TRAILERS: &[&str] = &["reaction", "review", "fan made", "fanmade", "parody", "breakdown", "explained", "recap", "reacts"];
+
+/// YouTube video id from a watch, short or embed URL.
+pub fn video_id(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    let rest = rest.strip_prefix("www.").or_else(|| rest.strip_prefix("m.")).unwrap_or(rest);
+    let id = if let Some(r) = rest.strip_prefix("youtu.be/") {
+        r
+    } else if let Some(r) = rest.strip_prefix("youtube.com/embed/").or_else(|| rest.strip_prefix("youtube.com/shorts/")) {
+        r
+    } else if let Some(query) = rest.strip_prefix("youtube.com/watch?") {
+        query.split('&').find_map(|p| p.strip_prefix("v="))?
+    } else {
+        return None;
+    };
+    let id: String = id.chars().take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_').collect();
+    (id.len() == 11).then_some(id)
+}
+
+pub fn from_link(link: &str, title: &str) -> Option<Trailer> {
+    let id = video_id(link)?;
+    Some(Trailer { url: format!("https://www.youtube.com/watch?v={}", id), video_id: id, title: title.to_string(), channel: None })
+}
+
+fn score(t: &Trailer, title: &str, year: Option<i32>) -> i32 {
+    let name = t.title.to_lowercase();
+    let mut score = 0;
+    if name.contains("trailer") || name.contains("预告") {
+        score += 10;
+    }
+    if name.contains("official") || name.contains("官方") {
+        score += 5;
+    }
+    if name.contains(&title.trim().to_lowercase()) {
+        score += 8;
+    }
+    if year.map(|y| name.contains(&y.to_string())).unwrap_or(false) {
+        score += 3;
+    }
+    if name.contains("teaser") {
+        score -= 2;
+    }
+    if NOT_TRAILERS.iter().any(|w| name.contains(w)) {
+        score -= 20;
+    }
+    score
+}
+
+/// The most trailer-like candidate, if any looks like a trailer at all.
+pub fn best(candidates: Vec<Trailer>, title: &str, year: Option<i32>) -> Option<Trailer> {
+    candidates
+        .into_iter()
+        .enumerate()
+        .map(|(n, t)| (score(&t, title, year), n, t))
+        .filter(|(s, _, _)| *s > 0)
+        // Ties go to the provider's ranking
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)))
+        .map(|(_, _, t)| t)
+}
+
+pub fn query(title: &str, year: Option<i32>) -> String {
+    match year {
+        Some(y) => format!("{} {} trailer", title.trim(), y),
+        None => format!("{} trailer", title.trim()),
+    }
+}
+
+pub async fn youtube(client: &Client, query: &str, api_key: &str) -> Result<Vec<Trailer>, String> {
+    let url = format!(
+        "https://www.googleapis.com/youtube/v3/search?part=snippet&type=video&maxResults=10&q={}&key={}",
+        urlencoding::encode(query),
+        urlencoding::encode(api_key)
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    if let Some(message) = v["error"]["message"].as_str() {
+        return Err(format!("YouTube Error: {}", message));
+    }
+    Ok(v["items"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|i| {
+            let id = i["id"]["videoId"].as_str()?;
+            Some(Trailer {
+                url: format!("https://www.youtube.com/watch?v={}", id),
+                video_id: id.to_string(),
+                title: i["snippet"]["title"].as_str().unwrap_or_default().to_string(),
+                channel: i["snippet"]["channelTitle"].as_str().map(str::to_string),
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,117 @@
+// Tray icon. Its menu reaches the things worth doing without the window: open
+// the app, quick-add, switch the LAN sync server on or off and run the update
+// check. With `Settings::close_to_tray` on, closing the main window only hides it,
+// so the scheduler (update checks) and the sync server keep running.
+
+use std::sync::Arc;
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager, Wry};
+use crate::database::Database;
+
+const OPEN: &str = "open";
+const QUICK_ADD: &str = "quick-add";
+const TOGGLE_SYNC: &str = "toggle-sync";
+const CHECK_UPDATES: &str = "check-updates";
+const QUIT: &str = "quit";
+
+/// The sync entry, kept so its check mark can follow the server.
+pub struct SyncToggle(CheckMenuItem<Wry>);
+
+pub fn build(app: &AppHandle) -> tauri::Result<()> {
+    let sync = CheckMenuItem::with_id(app, TOGGLE_SYNC, "LAN Sync Server", true, false, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(app, OPEN, "Open MediaTracker", true, None::<&str>)?,
+            &MenuItem::with_id(app, QUICK_ADD, "Quick Add...", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &sync,
+            &MenuItem::with_id(app, CHECK_UPDATES, "Check for Updates Now", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, QUIT, "Quit", true, None::<&str>)?,
+        ],
+    )?;
+    let mut tray = TrayIconBuilder::with_id("main")
+        .tooltip("MediaTracker")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                show_main(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+    app.manage(SyncToggle(sync));
+    Ok(())
+}
+
+/// Brings the main window back, hidden or minimized.
+pub fn show_main(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+/// Ticks or unticks the sync entry; called whenever the server starts or stops.
+pub fn set_sync_checked(app: &AppHandle, running: bool) {
+    if let Some(toggle) = app.try_state::<SyncToggle>() {
+        let _ = toggle.0.set_checked(running);
+    }
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        OPEN => show_main(app),
+        QUICK_ADD => crate::quick_add::show(app),
+        TOGGLE_SYNC => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = toggle_sync(&app).await {
+                    eprintln!("Failed to toggle sync server: {}", e);
+                    crate::notify::general(&app, &app.state::<Arc<Database>>(), "LAN sync", &e).await;
+                }
+            });
+        }
+        CHECK_UPDATES => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let db = app.state::<Arc<Database>>().inner().clone();
+                let client = app.state::<crate::AppState>().proxy_client.clone();
+                // Found updates are notified per item by the check itself
+                if crate::updates::run_check(&app, &db, &client, 0, None).await.is_empty() {
+                    crate::notify::general(&app, &db, "Update check", "No new updates").await;
+                }
+            });
+        }
+        QUIT => app.exit(0),
+        _ => {}
+    }
+}
+
+/// Starts or stops the sync server and remembers the choice, as `start_sync`/`stop_sync` do.
+async fn toggle_sync(app: &AppHandle) -> Result<(), String> {
+    let db = app.state::<Arc<Database>>().inner().clone();
+    let sync = app.state::<crate::sync::SyncService>().inner().clone();
+    let mut settings = db.get_settings().await;
+    let running = if sync.status().await.running {
+        sync.stop().await;
+        false
+    } else {
+        if let Err(e) = sync.start(db.clone(), app.clone(), settings.sync_port).await {
+            // The menu ticks the entry itself on click
+            set_sync_checked(app, false);
+            return Err(e);
+        }
+        true
+    };
+    set_sync_checked(app, running);
+    settings.sync_enabled = running;
+    db.update_settings(settings).await
+}
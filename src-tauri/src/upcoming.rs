@@ -0,0 +1,301 @@
+// The "Upcoming" feed: everything with a release date ahead of the user,
+// oldest first. Three kinds of entries are merged into it: the next episode of
+// tracked shows (from `next_airing`), new books by followed authors (Google
+// Books), and sequels of completed items. Sequels come from the relation graph
+// when both items are in the collection, and otherwise from TMDB collections,
+// new TMDB seasons and Bangumi 续集 relations. A provider that fails only
+// leaves its entries out.
+
+use std::collections::HashSet;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use crate::airtime;
+use crate::dates::DatePrecision;
+use crate::dedupe::title_similarity;
+use crate::models::{CollectionCategory, MediaItem, MediaType};
+use crate::people::Person;
+use crate::relations::{Relation, RelationKind};
+
+/// Window when the caller gives none.
+pub const DEFAULT_WINDOW_DAYS: u32 = 90;
+/// Authors whose new books are looked up per refresh.
+const MAX_AUTHORS: usize = 20;
+/// Completed items, most recent first, whose sequels are looked up per refresh.
+const MAX_SEQUEL_LOOKUPS: usize = 30;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpcomingKind {
+    Episode,
+    Book,
+    Sequel,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpcomingEntry {
+    pub kind: UpcomingKind,
+    pub title: String,
+    /// The episode ("Ep 6", "S2E1"), the author, or the title it follows.
+    pub detail: String,
+    /// UTC milliseconds; the start of the release day when the time is unknown.
+    pub at: i64,
+    pub time_known: bool,
+    /// Day and time in the user's zone for episodes; "2025-03-04" or "2025-03" otherwise.
+    pub date: String,
+    /// The show, the collected sequel, or the completed item a new sequel follows.
+    pub item_id: Option<String>,
+    pub poster_url: Option<String>,
+    /// Where an entry not yet in the collection came from, e.g. ("tmdb", "12345").
+    pub source: Option<String>,
+    pub source_id: Option<String>,
+}
+
+/// The UTC span from the start of the user's today to `window_days` later.
+pub fn window(now: i64, utc_offset_minutes: i32, window_days: u32) -> (i64, i64) {
+    let offset = utc_offset_minutes as i64 * 60_000;
+    let start = (now + offset).div_euclid(crate::database::DAY_MS) * crate::database::DAY_MS - offset;
+    (start, start + window_days as i64 * crate::database::DAY_MS)
+}
+
+/// A provider release date as (start of that day in UTC, ISO date); month-only
+/// dates count from the first of the month, year-only dates are too vague.
+fn release(raw: &str) -> Option<(i64, String)> {
+    let date = crate::dates::parse(raw)?;
+    let day = match date.precision {
+        DatePrecision::Day => date.iso.clone(),
+        DatePrecision::Month => format!("{}-01", date.iso),
+        DatePrecision::Year => return None,
+    };
+    Some((airtime::to_utc(&day, None, "UTC")?, date.iso))
+}
+
+fn in_collection(items: &[MediaItem], title: &str) -> bool {
+    items.iter().any(|i| title_similarity(&i.title, title) >= 0.9 || i.alt_titles.values().any(|t| title_similarity(t, title) >= 0.9))
+}
+
+fn completed(item: &MediaItem) -> bool {
+    item.category == Some(CollectionCategory::Watched)
+}
+
+fn provider_id<'a>(item: &'a MediaItem, key: &str) -> Option<&'a str> {
+    item.provider_ids.as_ref()?.get(key).map(|s| s.as_str())
+}
+
+/// Entries that need no lookups: next episodes of tracked shows and sequels already
+/// in the collection whose prequel is completed.
+pub fn from_collection(items: &[MediaItem], relations: &[Relation], span: (i64, i64), utc_offset_minutes: i32) -> Vec<UpcomingEntry> {
+    let in_span = |at: i64| at >= span.0 && at < span.1;
+    let mut out: Vec<UpcomingEntry> = items
+        .iter()
+        .filter_map(|item| {
+            let airing = item.next_airing.as_ref().filter(|a| in_span(a.at))?;
+            Some(UpcomingEntry {
+                kind: UpcomingKind::Episode,
+                title: item.title.clone(),
+                detail: airing.episode.clone(),
+                at: airing.at,
+                time_known: airing.time_known,
+                date: airtime::local_time(airing, utc_offset_minutes),
+                item_id: Some(item.id.clone()),
+                poster_url: item.poster_url.clone(),
+                source: None,
+                source_id: None,
+            })
+        })
+        .collect();
+    for r in relations {
+        // Normalize to "sequel follows prequel"
+        let (sequel_id, prequel_id) = match r.kind {
+            RelationKind::Sequel => (&r.from_id, &r.to_id),
+            RelationKind::Prequel => (&r.to_id, &r.from_id),
+            _ => continue,
+        };
+        let (Some(sequel), Some(prequel)) = (items.iter().find(|i| i.id == *sequel_id), items.iter().find(|i| i.id == *prequel_id)) else {
+            continue;
+        };
+        if !completed(prequel) || completed(sequel) {
+            continue;
+        }
+        let Some((at, date)) = release(&sequel.release_date).filter(|(at, _)| in_span(*at)) else {
+            continue;
+        };
+        out.push(UpcomingEntry {
+            kind: UpcomingKind::Sequel,
+            title: sequel.title.clone(),
+            detail: prequel.title.clone(),
+            at,
+            time_known: false,
+            date,
+            item_id: Some(sequel.id.clone()),
+            poster_url: sequel.poster_url.clone(),
+            source: None,
+            source_id: None,
+        });
+    }
+    out
+}
+
+/// Followed people credited on a book, then authors with a book in Favorites.
+pub fn followed_authors(items: &[MediaItem], people: &[Person]) -> Vec<String> {
+    let is_book = |i: &MediaItem| matches!(i.media_type, MediaType::Book | MediaType::Audiobook);
+    let mut authors: Vec<String> = people
+        .iter()
+        .filter(|p| p.followed && items.iter().any(|i| is_book(i) && p.item_ids.contains(&i.id)))
+        .map(|p| p.name.clone())
+        .collect();
+    for item in items.iter().filter(|i| is_book(i) && i.category == Some(CollectionCategory::Favorites)) {
+        let name = item.director_or_author.split([',', '/', '、', '&', ';', '，']).next().unwrap_or("").trim();
+        if !name.is_empty() && !authors.iter().any(|a| a == name) {
+            authors.push(name.to_string());
+        }
+    }
+    authors.truncate(MAX_AUTHORS);
+    authors
+}
+
+/// Books in a Google Books `volumes` response released inside `span` and not yet collected.
+pub fn google_books_entries(v: &Value, author: &str, items: &[MediaItem], span: (i64, i64)) -> Vec<UpcomingEntry> {
+    let mut out: Vec<UpcomingEntry> = Vec::new();
+    for volume in v["items"].as_array().into_iter().flatten() {
+        let info = &volume["volumeInfo"];
+        let Some(title) = info["title"].as_str().map(str::trim).filter(|t| !t.is_empty()) else {
+            continue;
+        };
+        let Some((at, date)) = info["publishedDate"].as_str().and_then(release).filter(|(at, _)| *at >= span.0 && *at < span.1) else {
+            continue;
+        };
+        // Editions of one book share a title
+        if in_collection(items, title) || out.iter().any(|e| title_similarity(&e.title, title) >= 0.9) {
+            continue;
+        }
+        out.push(UpcomingEntry {
+            kind: UpcomingKind::Book,
+            title: title.to_string(),
+            detail: author.to_string(),
+            at,
+            time_known: false,
+            date,
+            item_id: None,
+            poster_url: info["imageLinks"]["thumbnail"].as_str().map(|u| u.replacen("http://", "https://", 1)),
+            source: Some("googleBooks".to_string()),
+            source_id: volume["id"].as_str().map(str::to_string),
+        });
+    }
+    out
+}
+
+pub async fn books(client: &Client, items: &[MediaItem], people: &[Person], span: (i64, i64)) -> Vec<UpcomingEntry> {
+    let mut out = Vec::new();
+    for author in followed_authors(items, people) {
+        let url = format!(
+            "https://www.googleapis.com/books/v1/volumes?q={}&orderBy=newest&maxResults=20&printType=books",
+            urlencoding::encode(&format!("inauthor:\"{}\"", author))
+        );
+        match crate::fetch_json(client, &url).await {
+            Ok(v) => out.extend(google_books_entries(&v, &author, items, span)),
+            Err(e) => eprintln!("Upcoming books lookup failed for {}: {}", author, e),
+        }
+    }
+    out
+}
+
+fn sequel_entry(prequel: &MediaItem, title: &str, release_date: &str, poster_url: Option<String>, source: &str, source_id: String) -> Option<UpcomingEntry> {
+    let (at, date) = release(release_date)?;
+    Some(UpcomingEntry {
+        kind: UpcomingKind::Sequel,
+        title: title.to_string(),
+        detail: prequel.title.clone(),
+        at,
+        time_known: false,
+        date,
+        item_id: Some(prequel.id.clone()),
+        poster_url,
+        source: Some(source.to_string()),
+        source_id: Some(source_id),
+    })
+}
+
+/// Later films in a completed movie's TMDB collection, or the next season of a completed show.
+async fn tmdb_sequels(client: &Client, item: &MediaItem, items: &[MediaItem], api_key: &str, seen_collections: &mut HashSet<u64>) -> Result<Vec<UpcomingEntry>, String> {
+    let key = urlencoding::encode(api_key);
+    if let Some(id) = provider_id(item, "tmdbTv") {
+        let v = crate::fetch_json(client, &format!("https://api.themoviedb.org/3/tv/{}?api_key={}", urlencoding::encode(id), key)).await?;
+        let next = &v["next_episode_to_air"];
+        if next["episode_number"].as_u64() != Some(1) {
+            return Ok(Vec::new());
+        }
+        let title = format!("{} Season {}", item.title, next["season_number"].as_u64().unwrap_or(0));
+        let poster = v["poster_path"].as_str().map(|p| format!("https://image.tmdb.org/t/p/w500{}", p));
+        return Ok(sequel_entry(item, &title, next["air_date"].as_str().unwrap_or(""), poster, "tmdbTv", id.to_string()).into_iter().collect());
+    }
+    let Some(id) = provider_id(item, "tmdb") else {
+        return Ok(Vec::new());
+    };
+    let v = crate::fetch_json(client, &format!("https://api.themoviedb.org/3/movie/{}?api_key={}", urlencoding::encode(id), key)).await?;
+    let Some(collection_id) = v["belongs_to_collection"]["id"].as_u64().filter(|c| seen_collections.insert(*c)) else {
+        return Ok(Vec::new());
+    };
+    let v = crate::fetch_json(client, &format!("https://api.themoviedb.org/3/collection/{}?api_key={}", collection_id, key)).await?;
+    Ok(v["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|part| {
+            let title = part["title"].as_str().filter(|t| !in_collection(items, t))?;
+            let poster = part["poster_path"].as_str().map(|p| format!("https://image.tmdb.org/t/p/w500{}", p));
+            sequel_entry(item, title, part["release_date"].as_str()?, poster, "tmdb", part["id"].as_u64()?.to_string())
+        })
+        .collect())
+}
+
+/// Subjects Bangumi lists as the sequel (续集) of a completed item.
+async fn bangumi_sequels(client: &Client, item: &MediaItem, items: &[MediaItem]) -> Result<Vec<UpcomingEntry>, String> {
+    let Some(id) = provider_id(item, "bangumi") else {
+        return Ok(Vec::new());
+    };
+    let related = crate::fetch_json(client, &format!("https://api.bgm.tv/v0/subjects/{}/subjects", urlencoding::encode(id))).await?;
+    let mut out = Vec::new();
+    for rel in related.as_array().into_iter().flatten().filter(|r| r["relation"].as_str() == Some("续集")) {
+        let Some(sequel_id) = rel["id"].as_u64() else {
+            continue;
+        };
+        if items.iter().any(|i| provider_id(i, "bangumi") == Some(sequel_id.to_string().as_str())) {
+            continue;
+        }
+        // The relation list has no dates
+        let v = crate::fetch_json(client, &format!("https://api.bgm.tv/v0/subjects/{}", sequel_id)).await?;
+        let title = v["name_cn"].as_str().filter(|t| !t.is_empty()).or(v["name"].as_str()).unwrap_or("");
+        let poster = v["images"]["large"].as_str().filter(|u| !u.is_empty()).map(str::to_string);
+        out.extend(sequel_entry(item, title, v["date"].as_str().unwrap_or(""), poster, "bangumi", sequel_id.to_string()));
+    }
+    Ok(out)
+}
+
+/// Sequels of recently completed items that are not in the collection yet. Items
+/// with a Bangumi id are looked up there, others on TMDB when there is a key.
+pub async fn sequels(client: &Client, items: &[MediaItem], tmdb_api_key: Option<&str>, span: (i64, i64)) -> Vec<UpcomingEntry> {
+    let mut done: Vec<&MediaItem> = items.iter().filter(|i| completed(i)).collect();
+    done.sort_by_key(|i| std::cmp::Reverse(i.last_edited_at.or(i.saved_at).unwrap_or(0)));
+    let mut seen_collections = HashSet::new();
+    let mut out: Vec<UpcomingEntry> = Vec::new();
+    for item in done.into_iter().take(MAX_SEQUEL_LOOKUPS) {
+        let found = match (provider_id(item, "bangumi"), tmdb_api_key) {
+            (Some(_), _) => bangumi_sequels(client, item, items).await,
+            (None, Some(key)) => tmdb_sequels(client, item, items, key, &mut seen_collections).await,
+            (None, None) => continue,
+        };
+        match found {
+            Ok(entries) => {
+                for e in entries {
+                    if e.at >= span.0 && e.at < span.1 && !out.iter().any(|o| o.source_id == e.source_id) {
+                        out.push(e);
+                    }
+                }
+            }
+            Err(e) => eprintln!("Upcoming sequel lookup failed for {}: {}", item.title, e),
+        }
+    }
+    out
+}
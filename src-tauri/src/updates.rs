@@ -0,0 +1,179 @@
+// Update checker for ongoing items, run by the scheduler's `updateCheck` job. It
+// looks at items marked `is_ongoing` that are due for a check, asks the provider
+// they came from for the latest aired episode (or MangaDex chapter), and flags
+// `has_new_update` when that changed. Air dates are compared as instants in the
+// broadcaster's zone (see `airtime`), and the next episode's air time is kept on
+// the item so it is checked again soon after it airs.
+
+use reqwest::Client;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use crate::airtime::{self, Airing};
+use crate::database::{now_ms, Database};
+use crate::models::{MediaItem, Settings};
+
+/// Event emitted to the frontend for every item with something new.
+pub const UPDATE_EVENT: &str = "media-update";
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateFound {
+    pub username: String,
+    pub item_id: String,
+    pub title: String,
+    pub latest_update_info: String,
+    pub next_airing: Option<Airing>,
+}
+
+/// What a provider reports for one item.
+#[derive(Debug, Clone, Default)]
+pub struct Check {
+    pub latest: Option<String>,
+    pub next_airing: Option<Airing>,
+}
+
+impl From<Option<String>> for Check {
+    fn from(latest: Option<String>) -> Self {
+        Check { latest, next_airing: None }
+    }
+}
+
+// Inverse of smart::days_from_civil
+pub(crate) fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+async fn bangumi_check(client: &Client, subject_id: &str) -> Result<Check, String> {
+    let base = format!("https://api.bgm.tv/v0/episodes?subject_id={}&type=0&limit=200", urlencoding::encode(subject_id));
+    let mut v = crate::fetch_json(client, &base).await?;
+    // Long-running shows: the newest episodes are on the last page
+    let total = v["total"].as_u64().unwrap_or(0);
+    if total > 200 {
+        v = crate::fetch_json(client, &format!("{}&offset={}", base, total - 200)).await?;
+    }
+    // Air dates are Japan dates; an episode counts as aired once its day has begun there
+    let now = now_ms();
+    let mut latest: Option<f64> = None;
+    let mut next: Option<(i64, f64)> = None;
+    for e in v["data"].as_array().into_iter().flatten() {
+        let Some(at) = e["airdate"].as_str().and_then(|d| airtime::to_utc(d, None, airtime::JST)) else {
+            continue;
+        };
+        let Some(n) = e["sort"].as_f64().or(e["ep"].as_f64()) else {
+            continue;
+        };
+        if at <= now {
+            latest = Some(latest.map_or(n, |l| l.max(n)));
+        } else if next.map(|(next_at, _)| at < next_at).unwrap_or(true) {
+            next = Some((at, n));
+        }
+    }
+    Ok(Check {
+        latest: latest.map(|n| format!("Ep {}", n)),
+        next_airing: next.map(|(at, n)| Airing {
+            episode: format!("Ep {}", n),
+            at,
+            source_timezone: airtime::JST.to_string(),
+            time_known: false,
+        }),
+    })
+}
+
+async fn tmdb_tv_check(client: &Client, tv_id: &str, api_key: &str) -> Result<Check, String> {
+    let url = format!(
+        "https://api.themoviedb.org/3/tv/{}?api_key={}",
+        urlencoding::encode(tv_id),
+        urlencoding::encode(api_key)
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    let episode = |ep: &serde_json::Value| match (ep["season_number"].as_u64(), ep["episode_number"].as_u64()) {
+        (Some(s), Some(e)) => Some(format!("S{}E{}", s, e)),
+        _ => None,
+    };
+    // TMDB air dates are local to where the show is made
+    let zone = v["origin_country"].as_array().and_then(|c| c.first()).and_then(|c| c.as_str()).map(airtime::country_zone).unwrap_or("UTC");
+    let next = &v["next_episode_to_air"];
+    let next_airing = episode(next).zip(next["air_date"].as_str().and_then(|d| airtime::to_utc(d, None, zone))).map(|(episode, at)| Airing {
+        episode,
+        at,
+        source_timezone: zone.to_string(),
+        time_known: false,
+    });
+    Ok(Check { latest: episode(&v["last_episode_to_air"]), next_airing })
+}
+
+/// Latest release info and next airing for one item; empty if no provider knows about it.
+pub async fn check_item(client: &Client, item: &MediaItem, settings: &Settings, tmdb_api_key: Option<&str>) -> Result<Check, String> {
+    let Some(ids) = item.provider_ids.as_ref() else {
+        return Ok(Check::default());
+    };
+    for source in crate::webtoons::ALL_SOURCES {
+        if let Some(id) = ids.get(source.key()) {
+            return source.latest(client, id).await.map(Check::from);
+        }
+    }
+    if let Some(id) = ids.get("mangadex") {
+        return crate::mangadex::latest_chapter(client, id, &settings.mangadex_languages).await.map(Check::from);
+    }
+    if let Some(id) = ids.get("ao3") {
+        return crate::ao3::latest(client, id).await.map(Check::from);
+    }
+    if let Some(id) = ids.get("novelUpdates") {
+        return crate::novelupdates::latest(client, id).await.map(Check::from);
+    }
+    if let Some(id) = ids.get(crate::anilist::KEY) {
+        return crate::anilist::airing(client, id).await;
+    }
+    if let Some(id) = ids.get("bangumi") {
+        return bangumi_check(client, id).await;
+    }
+    if let (Some(id), Some(key)) = (ids.get("tmdbTv"), tmdb_api_key) {
+        return tmdb_tv_check(client, id, key).await;
+    }
+    Ok(Check::default())
+}
+
+/// Checks every ongoing item not checked within `min_age_ms` (only `user`'s when
+/// given) and emits an event for each one that has something new.
+pub async fn run_check(app: &AppHandle, db: &Database, client: &Client, min_age_ms: i64, user: Option<&str>) -> Vec<UpdateFound> {
+    let settings = db.get_settings().await;
+    let tmdb_api_key = crate::secrets::resolve(None, crate::secrets::TMDB);
+    let mut found = Vec::new();
+    let due = db.get_items_due_for_update_check(min_age_ms).await;
+    for (username, item) in due.into_iter().filter(|(u, _)| user.is_none() || user == Some(u.as_str())) {
+        let check = match check_item(client, &item, &settings, tmdb_api_key.as_deref()).await {
+            Ok(check) => check,
+            Err(e) => {
+                eprintln!("Update check failed for {}: {}", item.title, e);
+                // Keep the known schedule until the provider answers again
+                Check { latest: None, next_airing: item.next_airing.clone() }
+            }
+        };
+        if db.apply_update_check(&username, &item.id, &check).await {
+            let update = UpdateFound {
+                username,
+                item_id: item.id.clone(),
+                title: crate::titles::display_title(&item, settings.title_language.as_deref()).to_string(),
+                latest_update_info: check.latest.unwrap_or_default(),
+                next_airing: check.next_airing,
+            };
+            let _ = app.emit(UPDATE_EVENT, update.clone());
+            let body = match update.next_airing.as_ref() {
+                Some(next) => format!("{} · Next: {} on {}", update.latest_update_info, next.episode, airtime::local_time(next, settings.utc_offset_minutes)),
+                None => update.latest_update_info.clone(),
+            };
+            crate::notify::item(app, db, &update.username, &update.item_id, &update.title, &body).await;
+            found.push(update);
+        }
+    }
+    found
+}
@@ -0,0 +1,217 @@
+// Viewing-history CSV importers: Netflix's "Title,Date" download, its full
+// ViewingActivity.csv, and the similar exports of other services, recognised by
+// their header. Episode rows ("Dark: Season 1: Secrets") are folded into one
+// series item whose progress is the last season watched and how many of its
+// episodes were seen; every other row is a watched movie.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde::Serialize;
+use crate::database::{new_id, now_ms};
+use crate::dedupe::normalize_title;
+use crate::models::{CollectionCategory, Completion, MediaItem, MediaType};
+
+/// Header names of the title and date columns, lowercase.
+const TITLE_COLUMNS: &[&str] = &["title", "name", "video title", "program", "show", "content"];
+const DATE_COLUMNS: &[&str] = &["date", "start time", "watched at", "date watched", "viewed at", "viewed on", "last watched"];
+/// ViewingActivity.csv lists trailers and previews with this column set.
+const SUPPLEMENTAL_COLUMN: &str = "supplemental video type";
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryImport {
+    pub rows: usize,
+    pub movies: usize,
+    pub series: usize,
+    pub episodes: usize,
+    pub added: usize,
+    /// Already in the collection.
+    pub skipped: usize,
+    /// Not added: titles that look like items already in the collection.
+    pub probable_duplicates: Vec<crate::dedupe::ImportDecision>,
+}
+
+/// A history title split into series, season and episode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Title {
+    pub series: String,
+    pub season: Option<u32>,
+    /// Episode name, or the whole title for a movie.
+    pub episode: Option<String>,
+}
+
+/// RFC 4180 rows: quoted fields may hold commas, doubled quotes and newlines.
+pub fn csv_rows(text: &str) -> Vec<Vec<String>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let (mut rows, mut row, mut field) = (Vec::new(), Vec::new(), String::new());
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|r| r.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+/// "Season 2", "Series 2", "Part 2", "Volume 2", "Book 2" or "Limited Series".
+fn season_marker(segment: &str) -> Option<Option<u32>> {
+    let lower = segment.trim().to_lowercase();
+    if lower == "limited series" || lower == "miniseries" {
+        return Some(None);
+    }
+    let (word, number) = lower.rsplit_once(' ')?;
+    let number: u32 = number.parse().ok()?;
+    ["season", "series", "part", "volume", "book", "collection"].contains(&word).then_some(Some(number))
+}
+
+/// Splits "Show: Season 1: Episode" (and "Stranger Things: Stranger Things 4: Chapter One")
+/// into its parts; titles without a season marker are left whole.
+pub fn split_title(raw: &str) -> Title {
+    let raw = raw.trim();
+    let parts: Vec<&str> = raw.split(": ").collect();
+    for i in 1..parts.len().saturating_sub(1) {
+        let series = parts[..i].join(": ");
+        let season = season_marker(parts[i]).or_else(|| {
+            // The season named after the show, with its number
+            let rest = parts[i].trim().strip_prefix(series.as_str())?.trim();
+            rest.parse::<u32>().ok().map(Some)
+        });
+        if let Some(season) = season {
+            return Title { series, season, episode: Some(parts[i + 1..].join(": ").trim_matches('"').to_string()) };
+        }
+    }
+    Title { series: raw.to_string(), season: None, episode: None }
+}
+
+/// Day-first when any date's first number can't be a month ("23/01/2024").
+fn day_first(dates: &[&str]) -> bool {
+    dates.iter().any(|d| {
+        d.split(['/', '.']).next().and_then(|n| n.trim().parse::<u32>().ok()).is_some_and(|n| n > 12)
+    })
+}
+
+/// "2024-01-23 21:13:44", "2024-01-23", "1/23/24" or "23.01.2024" as epoch milliseconds (UTC).
+pub fn parse_date(s: &str, day_first: bool) -> Option<i64> {
+    let s = s.trim();
+    let (date, time) = s.split_once([' ', 'T']).unwrap_or((s, ""));
+    let nums: Vec<u32> = date.split(['-', '/', '.']).map(|p| p.trim().parse().ok()).collect::<Option<_>>()?;
+    let [a, b, c] = nums[..] else {
+        return None;
+    };
+    let (y, m, d) = if date.contains('-') {
+        (a, b, c)
+    } else if day_first {
+        (c, b, a)
+    } else {
+        (c, a, b)
+    };
+    // Two-digit years are this century
+    let y = if y < 100 { 2000 + y } else { y };
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    let mut t = time.split(':').map(|p| p.trim().split('.').next().unwrap_or("").parse::<i64>().unwrap_or(0));
+    let secs = t.next().unwrap_or(0) * 3600 + t.next().unwrap_or(0) * 60 + t.next().unwrap_or(0);
+    Some(crate::smart::days_from_civil(y as i64, m, d) * crate::database::DAY_MS + secs * 1000)
+}
+
+/// One series being assembled from its episode rows.
+#[derive(Default)]
+struct Series {
+    title: String,
+    /// Season (0 when unnumbered) -> episode names.
+    episodes: BTreeMap<u32, BTreeSet<String>>,
+    last_at: Option<i64>,
+}
+
+/// Items for a viewing-history CSV, with counts; `added` and `skipped` are left to the caller.
+pub fn parse(text: &str) -> Result<(Vec<MediaItem>, HistoryImport), String> {
+    let rows = csv_rows(text);
+    let header: Vec<String> = rows.first().ok_or_else(|| "The file is empty".to_string())?.iter().map(|h| h.trim().to_lowercase()).collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+    let title_at = column(TITLE_COLUMNS).ok_or_else(|| "No title column found in the CSV header".to_string())?;
+    let date_at = column(DATE_COLUMNS);
+    let supplemental_at = column(&[SUPPLEMENTAL_COLUMN]);
+    let body: Vec<&Vec<String>> = rows[1..]
+        .iter()
+        .filter(|r| match supplemental_at.and_then(|i| r.get(i)) {
+            Some(kind) => kind.trim().is_empty(),
+            None => true,
+        })
+        .collect();
+    let dates: Vec<&str> = body.iter().filter_map(|r| r.get(date_at?)).map(String::as_str).collect();
+    let day_first = day_first(&dates);
+
+    let mut summary = HistoryImport { rows: body.len(), ..Default::default() };
+    let mut series: HashMap<String, Series> = HashMap::new();
+    // Movie title -> views
+    let mut movies: HashMap<String, (String, Option<i64>)> = HashMap::new();
+    for row in body {
+        let Some(raw) = row.get(title_at).map(|t| t.trim()).filter(|t| !t.is_empty()) else {
+            continue;
+        };
+        let at = date_at.and_then(|i| row.get(i)).and_then(|d| parse_date(d, day_first));
+        let title = split_title(raw);
+        match title.episode {
+            Some(episode) => {
+                let entry = series.entry(normalize_title(&title.series)).or_insert_with(|| Series { title: title.series.clone(), ..Default::default() });
+                entry.episodes.entry(title.season.unwrap_or(0)).or_default().insert(episode);
+                entry.last_at = entry.last_at.max(at);
+            }
+            None => {
+                let entry = movies.entry(normalize_title(&title.series)).or_insert_with(|| (title.series.clone(), None));
+                entry.1 = entry.1.max(at);
+            }
+        }
+    }
+
+    let now = now_ms();
+    let mut items = Vec::new();
+    for s in series.into_values() {
+        summary.series += 1;
+        summary.episodes += s.episodes.values().map(BTreeSet::len).sum::<usize>();
+        let progress = s.episodes.iter().next_back().map(|(season, eps)| match season {
+            0 => eps.len().to_string(),
+            n => format!("S{}E{}", n, eps.len()),
+        });
+        items.push(MediaItem {
+            id: new_id(),
+            title: s.title,
+            media_type: MediaType::TvSeries,
+            status: Some("Watching".to_string()),
+            user_progress: progress,
+            saved_at: Some(s.last_at.unwrap_or(now)),
+            ..Default::default()
+        });
+    }
+    for (title, at) in movies.into_values() {
+        summary.movies += 1;
+        items.push(MediaItem {
+            id: new_id(),
+            title,
+            media_type: MediaType::Movie,
+            category: Some(CollectionCategory::Watched),
+            completions: at.map(|at| Completion { at, rating: None }).into_iter().collect(),
+            saved_at: Some(at.unwrap_or(now)),
+            ..Default::default()
+        });
+    }
+    items.sort_by_key(|i| i.saved_at);
+    Ok((items, summary))
+}
@@ -0,0 +1,27 @@
+// Time actually spent on items, from `start_session` / `stop_session`. A running
+// timer is stored like a finished one without `ended_at`, so it survives the
+// app being closed and is stopped on the next `stop_session`.
+
+use serde::{Deserialize, Serialize};
+
+/// A timer left running longer than this counts only this much.
+pub const MAX_SESSION_SECS: i64 = 12 * 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSession {
+    pub id: String,
+    pub item_id: String,
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    /// Set when the session is stopped.
+    #[serde(default)]
+    pub seconds: i64,
+}
+
+impl TimeSession {
+    pub fn stop(&mut self, now: i64) {
+        self.ended_at = Some(now);
+        self.seconds = ((now - self.started_at) / 1000).clamp(0, MAX_SESSION_SECS);
+    }
+}
@@ -0,0 +1,241 @@
+// Read-only HTML view of a collection for browsers on the LAN (e.g. a phone),
+// served by the sync server under `/web`. Access needs a web token from
+// `create_web_token`: opening `/web?token=...` once stores it in a cookie, so
+// the address bar and cover requests don't carry it afterwards. The page
+// reloads itself when `/ws` reports a change to the collection.
+
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::Router;
+use serde::Deserialize;
+use tauri::Manager;
+use crate::atom::{feed_user, TokenQuery};
+use crate::models::{CollectionCategory, MediaItem};
+use crate::sync::{token_hash, SyncState};
+
+const COOKIE: &str = "mt_web";
+
+pub fn routes() -> Router<SyncState> {
+    Router::new()
+        .route("/web", get(index))
+        .route("/web/covers/{file}", get(cover))
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct IndexQuery {
+    token: Option<String>,
+    category: Option<String>,
+    q: Option<String>,
+}
+
+fn cookie_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|c| c.trim().strip_prefix(COOKIE)?.strip_prefix('='))
+}
+
+/// User signed in to the web view by cookie (also accepted by `/ws`).
+pub(crate) async fn viewer(state: &SyncState, headers: &HeaderMap) -> Option<String> {
+    state.db.user_for_web_token(&token_hash(cookie_token(headers)?)).await
+}
+
+async fn index(State(state): State<SyncState>, headers: HeaderMap, Query(query): Query<IndexQuery>) -> Response {
+    if let Some(token) = query.token.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+        if state.db.user_for_web_token(&token_hash(token)).await.is_none() {
+            return (StatusCode::UNAUTHORIZED, Html(login_page(Some("That token is not valid.")))).into_response();
+        }
+        let cookie = format!("{}={}; Path=/; Max-Age=31536000; HttpOnly; SameSite=Strict", COOKIE, token);
+        return ([(header::SET_COOKIE, cookie)], Redirect::to("/web")).into_response();
+    }
+    let Some(username) = viewer(&state, &headers).await else {
+        return (StatusCode::UNAUTHORIZED, Html(login_page(None))).into_response();
+    };
+    let items = match state.db.get_all_for_user(&username).await {
+        Ok(items) => items,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    Html(collection_page(&username, items, &query)).into_response()
+}
+
+/// Covers for the web view (cookie) and for `/feed.xml` entries (`?token=` with the feed token).
+async fn cover(State(state): State<SyncState>, headers: HeaderMap, Path(file): Path<String>, Query(query): Query<TokenQuery>) -> Response {
+    if viewer(&state, &headers).await.is_none() && feed_user(&state, query.token.as_deref()).await.is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let app = state.app.clone();
+    let served = tauri::async_runtime::spawn_blocking(move || app.state::<crate::images::ImageCache>().respond(&file, Some("size=medium"))).await;
+    match served {
+        Ok(resp) => {
+            let (parts, bytes) = resp.into_parts();
+            Response::from_parts(parts, Body::from(bytes))
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+const STYLE: &str = "
+body{font-family:system-ui,sans-serif;margin:0;background:#f4f4f5;color:#18181b}
+header{position:sticky;top:0;background:#fff;padding:12px 16px;box-shadow:0 1px 3px #0002}
+h1{font-size:18px;margin:0 0 8px}
+nav a{margin-right:12px;color:#71717a;text-decoration:none}nav a.on{color:#2563eb;font-weight:600}
+form{margin-top:8px}input{width:100%;box-sizing:border-box;padding:8px;border:1px solid #d4d4d8;border-radius:6px;font-size:16px}
+main{display:grid;grid-template-columns:repeat(auto-fill,minmax(150px,1fr));gap:12px;padding:16px}
+.card{background:#fff;border-radius:8px;overflow:hidden;box-shadow:0 1px 2px #0002}
+.card img,.card .ph{width:100%;aspect-ratio:2/3;object-fit:cover;background:#e4e4e7;display:block}
+.card div{padding:8px;font-size:13px}.card b{display:block;font-size:14px}.meta{color:#71717a}
+details{margin-top:4px}summary{cursor:pointer;color:#2563eb}
+.empty{padding:32px;text-align:center;color:#71717a}
+@media (prefers-color-scheme:dark){body{background:#18181b;color:#f4f4f5}header,.card{background:#27272a}input{background:#18181b;color:#f4f4f5;border-color:#3f3f46}}
+";
+
+// Reloads on the first change after a short quiet period, so a bulk edit causes one reload
+const LIVE_SCRIPT: &str = "
+(function(){var t;var ws=new WebSocket((location.protocol=='https:'?'wss://':'ws://')+location.host+'/ws');
+ws.onmessage=function(){clearTimeout(t);t=setTimeout(function(){location.reload()},1000)};})();
+";
+
+fn page(title: &str, body: &str, live: bool) -> String {
+    let script = if live { format!("<script>{}</script>", LIVE_SCRIPT) } else { String::new() };
+    format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width,initial-scale=1\">\
+         <title>{}</title><style>{}</style></head><body>{}{}</body></html>",
+        escape(title),
+        STYLE,
+        body,
+        script
+    )
+}
+
+fn login_page(error: Option<&str>) -> String {
+    let error = error.map(|e| format!("<p>{}</p>", escape(e))).unwrap_or_default();
+    page(
+        "MediaTracker",
+        &format!(
+            "<header><h1>MediaTracker</h1>{}<form method=\"get\" action=\"/web\">\
+             <input name=\"token\" placeholder=\"Web access token\" autocomplete=\"off\"></form></header>",
+            error
+        ),
+        false,
+    )
+}
+
+fn category_label(category: &CollectionCategory) -> &'static str {
+    match category {
+        CollectionCategory::Favorites => "Favorites",
+        CollectionCategory::ToWatch => "To Watch",
+        CollectionCategory::Watched => "Watched",
+    }
+}
+
+fn poster_src(item: &MediaItem) -> Option<String> {
+    if let Some(cached) = &item.poster_cache {
+        return Some(format!("/web/covers/{}", urlencoding::encode(&cached.file)));
+    }
+    // No local copy: let the browser try the original
+    item.custom_poster_url
+        .as_deref()
+        .or(item.poster_url.as_deref())
+        .filter(|u| u.starts_with("https://") || u.starts_with("http://"))
+        .map(str::to_string)
+}
+
+fn card(item: &MediaItem) -> String {
+    let poster = match poster_src(item) {
+        Some(src) => format!("<img src=\"{}\" loading=\"lazy\" referrerpolicy=\"no-referrer\" alt=\"\">", escape(&src)),
+        None => "<span class=\"ph\"></span>".to_string(),
+    };
+    let media_type = serde_json::to_value(&item.media_type).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+    let year = item.release_date.get(..4).unwrap_or("");
+    let mut meta = vec![media_type];
+    if !year.is_empty() {
+        meta.push(year.to_string());
+    }
+    if let Some(r) = item.user_rating {
+        meta.push(format!("★ {}", r));
+    }
+    let mut extra = String::new();
+    if let Some(p) = item.user_progress.as_deref().filter(|p| !p.is_empty()) {
+        extra.push_str(&format!("<span class=\"meta\">{}</span>", escape(p)));
+    }
+    let notes = [item.description.as_str(), item.user_review.as_deref().unwrap_or("")]
+        .iter()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| format!("<p>{}</p>", escape(s)))
+        .collect::<String>();
+    if !notes.is_empty() {
+        extra.push_str(&format!("<details><summary>More</summary>{}</details>", notes));
+    }
+    format!(
+        "<article class=\"card\">{}<div><b>{}</b><span class=\"meta\">{}</span><br>{}</div></article>",
+        poster,
+        escape(&item.title),
+        escape(&meta.join(" · ")),
+        extra
+    )
+}
+
+fn collection_page(username: &str, mut items: Vec<MediaItem>, query: &IndexQuery) -> String {
+    let category = query.category.as_deref().filter(|c| !c.is_empty());
+    let search = query.q.as_deref().map(str::trim).unwrap_or("").to_lowercase();
+    items.retain(|i| i.is_collection != Some(true));
+    items.retain(|i| match category {
+        Some(c) => i.category.as_ref().map(category_label) == Some(c),
+        None => true,
+    });
+    if !search.is_empty() {
+        items.retain(|i| i.title.to_lowercase().contains(&search) || i.director_or_author.to_lowercase().contains(&search));
+    }
+    items.sort_by_key(|i| std::cmp::Reverse(i.saved_at.unwrap_or(0)));
+
+    let tabs: String = [None, Some("To Watch"), Some("Watched"), Some("Favorites")]
+        .iter()
+        .map(|tab| {
+            let href = match tab {
+                Some(t) => format!("/web?category={}", urlencoding::encode(t)),
+                None => "/web".to_string(),
+            };
+            let class = if *tab == category { " class=\"on\"" } else { "" };
+            format!("<a href=\"{}\"{}>{}</a>", escape(&href), class, tab.unwrap_or("All"))
+        })
+        .collect();
+    let hidden = category
+        .map(|c| format!("<input type=\"hidden\" name=\"category\" value=\"{}\">", escape(c)))
+        .unwrap_or_default();
+    let cards: String = items.iter().map(card).collect();
+    let main = if cards.is_empty() { "<p class=\"empty\">Nothing here.</p>".to_string() } else { format!("<main>{}</main>", cards) };
+    page(
+        &format!("{}'s collection", username),
+        &format!(
+            "<header><h1>{}'s collection ({})</h1><nav>{}</nav>\
+             <form method=\"get\" action=\"/web\">{}<input name=\"q\" type=\"search\" placeholder=\"Search\" value=\"{}\"></form></header>{}",
+            escape(username),
+            items.len(),
+            tabs,
+            hidden,
+            escape(query.q.as_deref().unwrap_or("")),
+            main
+        ),
+        true,
+    )
+}
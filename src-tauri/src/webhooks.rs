@@ -0,0 +1,206 @@
+// Outgoing webhooks (Home Assistant, n8n, ...). A dispatcher follows the
+// database's item events and compares each saved item with what it saw before
+// to tell additions, completions and new ratings apart, then POSTs a JSON
+// payload to every enabled hook that subscribed to that event.
+//
+// Each request carries `X-MediaTracker-Event`, `X-MediaTracker-Timestamp` and
+// `X-MediaTracker-Signature: sha256=<hex>`, the HMAC-SHA256 of
+// `"<timestamp>.<body>"` keyed with the hook's secret.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::Client;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tokio::sync::broadcast::error::RecvError;
+use crate::database::{now_ms, Database, ItemEventKind};
+use crate::models::{CollectionCategory, MediaItem};
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookEvent {
+    Added,
+    Completed,
+    Rated,
+    /// Only sent by `test_webhook`.
+    Test,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub at: i64,
+    pub event: Option<WebhookEvent>,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    #[serde(default)]
+    pub id: String,
+    pub url: String,
+    /// Generated when saved empty.
+    #[serde(default)]
+    pub secret: String,
+    /// Events to send; empty sends all of them.
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub last_delivery: Option<Delivery>,
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+impl Webhook {
+    fn wants(&self, event: WebhookEvent) -> bool {
+        self.enabled && (event == WebhookEvent::Test || self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Payload<'a> {
+    event: WebhookEvent,
+    username: &'a str,
+    at: i64,
+    item: Option<&'a MediaItem>,
+}
+
+/// What the dispatcher remembers about an item to detect transitions.
+#[derive(Clone, PartialEq)]
+struct Seen {
+    category: Option<CollectionCategory>,
+    rating: Option<f32>,
+}
+
+impl Seen {
+    fn of(item: &MediaItem) -> Self {
+        Seen { category: item.category.clone(), rating: item.user_rating }
+    }
+}
+
+/// Events an item change amounts to. A new item is only `added`, so imports don't
+/// also report every watched or rated item in them.
+fn classify(before: Option<&Seen>, after: &Seen) -> Vec<WebhookEvent> {
+    let Some(before) = before else {
+        return vec![WebhookEvent::Added];
+    };
+    let mut events = Vec::new();
+    let watched = Some(CollectionCategory::Watched);
+    if after.category == watched && before.category != watched {
+        events.push(WebhookEvent::Completed);
+    }
+    if after.rating.is_some() && after.rating != before.rating {
+        events.push(WebhookEvent::Rated);
+    }
+    events
+}
+
+fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let mut ctx = hmac::Context::with_key(&key);
+    ctx.update(format!("{}.", timestamp).as_bytes());
+    ctx.update(body);
+    ctx.sign().as_ref().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Sends one event to one hook; the outcome is returned for `last_delivery`.
+pub async fn deliver(client: &Client, hook: &Webhook, event: WebhookEvent, username: &str, item: Option<&MediaItem>) -> Delivery {
+    let at = now_ms();
+    let mut delivery = Delivery { at, event: Some(event), status: None, error: None };
+    let body = match serde_json::to_vec(&Payload { event, username, at, item }) {
+        Ok(b) => b,
+        Err(e) => {
+            delivery.error = Some(e.to_string());
+            return delivery;
+        }
+    };
+    let event_name = serde_json::to_value(event).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+    let result = client
+        .post(&hook.url)
+        .timeout(DELIVERY_TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("X-MediaTracker-Event", event_name)
+        .header("X-MediaTracker-Timestamp", at.to_string())
+        .header("X-MediaTracker-Signature", format!("sha256={}", sign(&hook.secret, at, &body)))
+        .body(body)
+        .send()
+        .await;
+    match result {
+        Ok(resp) => {
+            delivery.status = Some(resp.status().as_u16());
+            if !resp.status().is_success() {
+                delivery.error = Some(format!("HTTP {}", resp.status()));
+            }
+        }
+        Err(e) => delivery.error = Some(e.to_string()),
+    }
+    delivery
+}
+
+async fn snapshot(db: &Database) -> HashMap<(String, String), Seen> {
+    db.get_all_items()
+        .await
+        .into_iter()
+        .map(|(user, item)| ((user, item.id.clone()), Seen::of(&item)))
+        .collect()
+}
+
+/// Spawns the dispatcher; it costs nothing while no user has a webhook.
+pub fn start(app: AppHandle, db: Arc<Database>) {
+    tauri::async_runtime::spawn(async move {
+        let mut events = db.subscribe();
+        let mut seen = snapshot(&db).await;
+        loop {
+            let event = match events.recv().await {
+                Ok(e) => e,
+                // Missed changes can't be classified; start over from the current state
+                Err(RecvError::Lagged(_)) => {
+                    seen = snapshot(&db).await;
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let key = (event.username.clone(), event.item_id.clone());
+            match event.kind {
+                ItemEventKind::Saved => {}
+                // Kept while trashed, so restoring an item doesn't count as adding it
+                ItemEventKind::Trashed => continue,
+                ItemEventKind::Deleted => {
+                    seen.remove(&key);
+                    continue;
+                }
+            }
+            let Some(item) = db.find_item(&event.username, &event.item_id).await else {
+                continue;
+            };
+            let now = Seen::of(&item);
+            let kinds = classify(seen.get(&key), &now);
+            seen.insert(key, now);
+            if kinds.is_empty() {
+                continue;
+            }
+            let hooks = db.get_webhooks(&event.username).await;
+            if hooks.is_empty() {
+                continue;
+            }
+            let client = app.state::<crate::AppState>().direct_client.clone();
+            for kind in kinds {
+                for hook in hooks.iter().filter(|h| h.wants(kind)) {
+                    let delivery = deliver(&client, hook, kind, &event.username, Some(&item)).await;
+                    db.record_webhook_delivery(&event.username, &hook.id, delivery).await;
+                }
+            }
+        }
+    });
+}
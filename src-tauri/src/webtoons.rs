@@ -0,0 +1,138 @@
+// Update sources for webtoon platforms that have no public API. Each source
+// knows how to recognize its title URLs, which provider id key it stores the
+// title under, and how to read the newest episode off the platform; the
+// update checker calls `latest` for followed items on its normal schedule.
+// Requests to one platform are spaced by that platform's `limiter`.
+//
+// A new platform is a new `UpdateSource` variant plus its arms below.
+
+use std::time::Duration;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::json;
+use crate::rate_limit::RateLimiter;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateSource {
+    Webtoon,
+    BilibiliComics,
+}
+
+pub const ALL_SOURCES: [UpdateSource; 2] = [UpdateSource::Webtoon, UpdateSource::BilibiliComics];
+
+static WEBTOON_LIMITER: RateLimiter = RateLimiter::new(Duration::from_secs(3));
+static BILIBILI_LIMITER: RateLimiter = RateLimiter::new(Duration::from_secs(2));
+
+impl UpdateSource {
+    /// Key in `MediaItem::provider_ids`.
+    pub fn key(self) -> &'static str {
+        match self {
+            UpdateSource::Webtoon => "webtoon",
+            UpdateSource::BilibiliComics => "bilibiliComics",
+        }
+    }
+
+    /// Spaces requests to the platform.
+    fn limiter(self) -> &'static RateLimiter {
+        match self {
+            UpdateSource::Webtoon => &WEBTOON_LIMITER,
+            UpdateSource::BilibiliComics => &BILIBILI_LIMITER,
+        }
+    }
+
+    /// Title id from a title or episode URL.
+    pub fn parse_url(self, url: &str) -> Option<String> {
+        let url = url.trim();
+        match self {
+            UpdateSource::Webtoon => {
+                if !url.contains("webtoons.com/") {
+                    return None;
+                }
+                let query = url.split_once('?')?.1;
+                let id = query.split('&').find_map(|p| p.strip_prefix("title_no=").or_else(|| p.strip_prefix("titleNo=")))?;
+                digits(id)
+            }
+            UpdateSource::BilibiliComics => {
+                if !url.contains("manga.bilibili.com/") && !url.contains("bilibilicomics.com/") {
+                    return None;
+                }
+                let (_, rest) = url.split_once("/mc")?;
+                digits(rest)
+            }
+        }
+    }
+
+    /// Newest episode of title `id`, e.g. "Ep. 612" or "第120话 标题".
+    pub async fn latest(self, client: &Client, id: &str) -> Result<Option<String>, String> {
+        self.limiter().wait().await;
+        match self {
+            UpdateSource::Webtoon => webtoon_latest(client, id).await,
+            UpdateSource::BilibiliComics => bilibili_latest(client, id).await,
+        }
+    }
+}
+
+fn digits(s: &str) -> Option<String> {
+    let id: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    (!id.is_empty()).then_some(id)
+}
+
+/// The source and title id for a URL from any supported platform.
+pub fn parse_url(url: &str) -> Option<(UpdateSource, String)> {
+    ALL_SOURCES.iter().find_map(|s| s.parse_url(url).map(|id| (*s, id)))
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+    let resp = tokio::time::timeout(Duration::from_secs(12), request.send())
+        .await
+        .map_err(|_| "Timeout".to_string())?
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    Ok(resp)
+}
+
+/// Text between `start` and the next `end` after `from`.
+fn between<'a>(html: &'a str, from: usize, start: &str, end: &str) -> Option<&'a str> {
+    let s = html[from..].find(start)? + from + start.len();
+    let e = html[s..].find(end)? + s;
+    Some(&html[s..e])
+}
+
+// The episode list page starts with the newest episode: <li class="_episodeItem" data-episode-no="612"> ... <span class="subj"><span>Episode 612</span>
+async fn webtoon_latest(client: &Client, id: &str) -> Result<Option<String>, String> {
+    let url = format!("https://www.webtoons.com/episodeList?titleNo={}", id);
+    // The desktop page needs a language cookie to skip the age/region gate
+    let html = send(client.get(&url).header("Cookie", "locale=en; needGDPR=false; needCCPA=false; needCOPPA=false"))
+        .await?
+        .text()
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(item) = html.find("data-episode-no=\"") else {
+        return Ok(None);
+    };
+    let number = between(&html, item, "data-episode-no=\"", "\"").and_then(digits);
+    let subject = between(&html, item, "<span class=\"subj\"><span>", "</span>").map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    Ok(subject.or(number.map(|n| format!("Ep. {}", n))))
+}
+
+async fn bilibili_latest(client: &Client, id: &str) -> Result<Option<String>, String> {
+    let comic_id: u64 = id.parse().map_err(|_| "Invalid Bilibili Comics id".to_string())?;
+    let request = client
+        .post("https://manga.bilibili.com/twirp/comic.v1.Comic/ComicDetail?device=pc&platform=web")
+        .json(&json!({ "comic_id": comic_id }));
+    let v = send(request).await?.json::<serde_json::Value>().await.map_err(|e| e.to_string())?;
+    if v["code"].as_i64().unwrap_or(0) != 0 {
+        return Err(format!("Bilibili Comics Error: {}", v["msg"].as_str().unwrap_or("unknown")));
+    }
+    // `ep_list` is newest first
+    let ep = &v["data"]["ep_list"][0];
+    let Some(short) = ep["short_title"].as_str().filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+    let title = ep["title"].as_str().map(str::trim).unwrap_or("");
+    let number = if short.chars().all(|c| c.is_ascii_digit()) { format!("第{}话", short) } else { short.to_string() };
+    Ok(Some(if title.is_empty() { number } else { format!("{} {}", number, title) }))
+}
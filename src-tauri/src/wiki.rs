@@ -0,0 +1,225 @@
+// Wikipedia as a metadata source: the plain-text article from the TextExtracts
+// API plus the fields of the lead infobox, read out of the wikitext of the
+// first section. Director, release date and episode count are picked from the
+// infobox so the frontend can prefill an item with them.
+
+use std::collections::HashMap;
+use reqwest::Client;
+use serde::Serialize;
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WikiArticle {
+    pub title: String,
+    pub lang: String,
+    pub url: String,
+    /// Whole article as plain text.
+    pub extract: String,
+    /// Lead paragraph, for the item description.
+    pub description: Option<String>,
+    /// Infobox fields with links, references and formatting removed.
+    pub infobox: HashMap<String, String>,
+    pub director_or_author: Option<String>,
+    pub release_date: Option<String>,
+    pub episodes: Option<u32>,
+}
+
+const INFOBOX_NAMES: &[&str] = &["infobox", "信息框", "电影信息框", "电视节目信息框", "動畫"];
+const CREATOR_KEYS: &[&str] = &["director", "directed_by", "author", "creator", "created_by", "artist", "导演", "作者", "原作", "监督"];
+const DATE_KEYS: &[&str] = &["released", "release_date", "first_aired", "published", "pub_date", "premiere", "上映日期", "首播", "首播日期", "出版日期", "发行日期"];
+const EPISODE_KEYS: &[&str] = &["num_episodes", "episodes", "集数", "话数"];
+
+pub fn valid_lang(lang: &str) -> bool {
+    !lang.is_empty() && lang.len() <= 12 && lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Index just past the `}}` closing the template opened at `start`.
+fn template_end(text: &str, start: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0usize;
+    let mut i = start;
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"{{" => {
+                depth += 1;
+                i += 2;
+            }
+            b"}}" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Splits template arguments on `|` outside nested templates and links.
+fn split_args(body: &str) -> Vec<&str> {
+    let bytes = body.as_bytes();
+    let (mut depth, mut from, mut out) = (0i32, 0, Vec::new());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' | b'[' if bytes.get(i + 1) == Some(&bytes[i]) => {
+                depth += 1;
+                i += 1;
+            }
+            b'}' | b']' if bytes.get(i + 1) == Some(&bytes[i]) => {
+                depth -= 1;
+                i += 1;
+            }
+            b'|' if depth == 0 => {
+                out.push(&body[from..i]);
+                from = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    out.push(&body[from..]);
+    out
+}
+
+/// Removes `<tag ...>...</tag>` blocks (references) and comments.
+fn strip_block(text: &str, open: &str, close: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find(open) {
+        out.push_str(&rest[..i]);
+        let after = &rest[i..];
+        // Self-closing `<ref name="x" />`
+        let tag_end = after.find('>').map(|e| e + 1).unwrap_or(after.len());
+        if after[..tag_end].ends_with("/>") {
+            rest = &after[tag_end..];
+            continue;
+        }
+        rest = match after.find(close) {
+            Some(e) => &after[e + close.len()..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Date templates such as `{{Film date|2010|7|16}}` or `{{Start date|2010|07|16}}`.
+fn template_date(args: &[&str]) -> Option<String> {
+    let nums: Vec<&str> = args.iter().map(|a| a.trim()).filter(|a| !a.contains('=')).take(3).collect();
+    let year: u32 = nums.first()?.parse().ok()?;
+    match (nums.get(1).and_then(|m| m.parse::<u32>().ok()), nums.get(2).and_then(|d| d.parse::<u32>().ok())) {
+        (Some(m), Some(d)) => Some(format!("{:04}-{:02}-{:02}", year, m, d)),
+        (Some(m), None) => Some(format!("{:04}-{:02}", year, m)),
+        _ => Some(format!("{:04}", year)),
+    }
+}
+
+/// Wikitext value to plain text: links to their label, templates to their
+/// content (dates as `YYYY-MM-DD`, lists joined with commas).
+pub fn clean(value: &str) -> String {
+    let text = strip_block(&strip_block(value, "<!--", "-->"), "<ref", "</ref>");
+    let mut out = String::new();
+    let mut rest = text.as_str();
+    loop {
+        let link = rest.find("[[");
+        let template = rest.find("{{");
+        match (link, template) {
+            (Some(l), t) if t.map(|t| l < t).unwrap_or(true) => {
+                out.push_str(&rest[..l]);
+                let Some(end) = rest[l..].find("]]") else {
+                    break;
+                };
+                let inner = &rest[l + 2..l + end];
+                out.push_str(inner.rsplit('|').next().unwrap_or(inner));
+                rest = &rest[l + end + 2..];
+            }
+            (_, Some(t)) => {
+                out.push_str(&rest[..t]);
+                let Some(end) = template_end(rest, t) else {
+                    break;
+                };
+                let args = split_args(&rest[t + 2..end - 2]);
+                let name = args[0].trim().to_lowercase();
+                if name.contains("date") || name.contains("日期") {
+                    out.push_str(&template_date(&args[1..]).unwrap_or_default());
+                } else if name.contains("list") || name == "ubl" {
+                    let items: Vec<String> = args[1..]
+                        .iter()
+                        .flat_map(|a| a.split('\n'))
+                        .map(|l| clean(l.trim().trim_start_matches('*')).trim().to_string())
+                        .filter(|l| !l.is_empty() && !l.contains('='))
+                        .collect();
+                    out.push_str(&items.join(", "));
+                } else if let Some(last) = args[1..].iter().rev().find(|a| !a.contains('=')) {
+                    out.push_str(&clean(last));
+                }
+                rest = &rest[end..];
+            }
+            _ => break,
+        }
+    }
+    out.push_str(rest);
+    let out = out.replace("<br />", ", ").replace("<br/>", ", ").replace("<br>", ", ").replace("'''", "").replace("''", "");
+    let out = strip_block(&out, "<small", "</small>");
+    out.split_whitespace().collect::<Vec<_>>().join(" ").trim_matches(|c: char| c == ',' || c.is_whitespace()).to_string()
+}
+
+/// Fields of the first infobox in `wikitext`, keyed by lowercase name.
+pub fn parse_infobox(wikitext: &str) -> HashMap<String, String> {
+    let start = wikitext.match_indices("{{").map(|(i, _)| i).find(|&i| {
+        let name: String = wikitext[i + 2..].trim_start().chars().take(10).collect::<String>().to_lowercase();
+        INFOBOX_NAMES.iter().any(|n| name.starts_with(n))
+    });
+    let Some(start) = start else {
+        return HashMap::new();
+    };
+    let Some(end) = template_end(wikitext, start) else {
+        return HashMap::new();
+    };
+    split_args(&wikitext[start + 2..end - 2])
+        .into_iter()
+        .skip(1)
+        .filter_map(|arg| {
+            let (key, value) = arg.split_once('=')?;
+            let value = clean(value);
+            (!value.is_empty()).then(|| (key.trim().to_lowercase().replace(' ', "_"), value))
+        })
+        .collect()
+}
+
+fn first_of(infobox: &HashMap<String, String>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|k| infobox.get(*k)).cloned()
+}
+
+pub async fn extract(client: &Client, title: &str, lang: &str) -> Result<WikiArticle, String> {
+    let url = format!(
+        "https://{}.wikipedia.org/w/api.php?action=query&prop=extracts|revisions|info&explaintext=1&exsectionformat=plain\
+         &rvprop=content&rvslots=main&rvsection=0&inprop=url&redirects=1&format=json&formatversion=2&titles={}",
+        lang,
+        urlencoding::encode(title)
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    let page = &v["query"]["pages"][0];
+    if page.is_null() || page["missing"].as_bool() == Some(true) {
+        return Err("Article not found".to_string());
+    }
+    let extract = page["extract"].as_str().unwrap_or_default().trim().to_string();
+    let wikitext = page["revisions"][0]["slots"]["main"]["content"].as_str().unwrap_or_default();
+    let infobox = parse_infobox(wikitext);
+    let episodes = first_of(&infobox, EPISODE_KEYS)
+        .and_then(|e| e.split(|c: char| !c.is_ascii_digit()).find(|s| !s.is_empty()).and_then(|n| n.parse().ok()));
+    Ok(WikiArticle {
+        title: page["title"].as_str().unwrap_or(title).to_string(),
+        lang: lang.to_string(),
+        url: page["fullurl"].as_str().unwrap_or_default().to_string(),
+        description: extract.split('\n').map(str::trim).find(|p| !p.is_empty()).map(str::to_string),
+        director_or_author: first_of(&infobox, CREATOR_KEYS),
+        release_date: first_of(&infobox, DATE_KEYS),
+        episodes,
+        extract,
+        infobox,
+    })
+}
@@ -0,0 +1,205 @@
+// Wikidata as a provider-neutral metadata source. An item is resolved to an
+// entity by one of its provider ids (through the matching Wikidata property) or
+// else by title, and the claims the app cares about are read out typed:
+// dates, people, series position, duration and website.
+
+use std::collections::HashMap;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+
+const API: &str = "https://www.wikidata.org/w/api.php";
+
+/// Our provider id keys and the Wikidata properties holding them.
+pub const EXTERNAL_IDS: &[(&str, &str)] = &[
+    ("imdb", "P345"),
+    ("tmdb", "P4947"),
+    ("tmdbTv", "P4983"),
+    ("bangumi", "P5732"),
+    ("douban", "P4529"),
+];
+
+const PUBLICATION_DATE: &str = "P577";
+const AUTHOR: &str = "P50";
+const DIRECTOR: &str = "P57";
+const PART_OF_SERIES: &str = "P179";
+const SERIES_ORDINAL: &str = "P1545";
+const DURATION: &str = "P2047";
+const OFFICIAL_WEBSITE: &str = "P856";
+const MINUTE: &str = "Q7727";
+const HOUR: &str = "Q25235";
+const SECOND: &str = "Q11574";
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WikidataEntity {
+    /// e.g. `Q25188`
+    pub id: String,
+    pub url: String,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, as precise as Wikidata has it; the earliest if several.
+    pub publication_date: Option<String>,
+    pub authors: Vec<String>,
+    pub directors: Vec<String>,
+    pub series: Option<String>,
+    /// Position in `series`, as written ("2", "1.5").
+    pub series_ordinal: Option<String>,
+    pub duration_minutes: Option<f64>,
+    pub official_website: Option<String>,
+    /// Provider ids found on the entity, keyed like `MediaItem::provider_ids`.
+    pub provider_ids: HashMap<String, String>,
+}
+
+fn statements<'a>(entity: &'a Value, property: &str) -> impl Iterator<Item = &'a Value> {
+    entity["claims"][property]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|c| c["rank"].as_str() != Some("deprecated"))
+}
+
+fn values<'a>(entity: &'a Value, property: &str) -> impl Iterator<Item = &'a Value> {
+    statements(entity, property).map(|c| &c["mainsnak"]["datavalue"]["value"]).filter(|v| !v.is_null())
+}
+
+fn entity_ids(entity: &Value, property: &str) -> Vec<String> {
+    values(entity, property).filter_map(|v| v["id"].as_str().map(str::to_string)).collect()
+}
+
+/// `+2010-07-16T00:00:00Z` at day (11), month (10) or year (9) precision.
+fn date(value: &Value) -> Option<String> {
+    let time = value["time"].as_str()?.trim_start_matches('+');
+    let day = time.split('T').next()?;
+    let keep = match value["precision"].as_u64()? {
+        11.. => 10,
+        10 => 7,
+        9 => 4,
+        _ => return None,
+    };
+    day.get(..keep).map(str::to_string)
+}
+
+fn minutes(value: &Value) -> Option<f64> {
+    let amount: f64 = value["amount"].as_str()?.trim_start_matches('+').parse().ok()?;
+    let unit = value["unit"].as_str()?.rsplit('/').next()?;
+    match unit {
+        MINUTE => Some(amount),
+        HOUR => Some(amount * 60.0),
+        SECOND => Some(amount / 60.0),
+        _ => None,
+    }
+}
+
+fn label(entity: &Value, langs: &[&str]) -> Option<String> {
+    langs.iter().find_map(|l| entity["labels"][*l]["value"].as_str()).map(str::to_string)
+}
+
+async fn get_entities(client: &Client, ids: &[String], props: &str, langs: &[&str]) -> Result<Value, String> {
+    let url = format!(
+        "{}?action=wbgetentities&format=json&ids={}&props={}&languages={}",
+        API,
+        urlencoding::encode(&ids.join("|")),
+        props,
+        urlencoding::encode(&langs.join("|"))
+    );
+    Ok(crate::fetch_json(client, &url).await?["entities"].clone())
+}
+
+/// Entity id holding `property` = `value`, via the search index.
+async fn find_by_statement(client: &Client, property: &str, value: &str) -> Result<Option<String>, String> {
+    let query = format!("haswbstatement:\"{}={}\"", property, value.replace('"', ""));
+    let url = format!("{}?action=query&list=search&format=json&srlimit=1&srsearch={}", API, urlencoding::encode(&query));
+    let v = crate::fetch_json(client, &url).await?;
+    Ok(v["query"]["search"][0]["title"].as_str().map(str::to_string))
+}
+
+async fn find_by_title(client: &Client, title: &str, lang: &str) -> Result<Option<String>, String> {
+    let url = format!(
+        "{}?action=wbsearchentities&format=json&type=item&limit=1&language={}&uselang={}&search={}",
+        API,
+        lang,
+        lang,
+        urlencoding::encode(title)
+    );
+    let v = crate::fetch_json(client, &url).await?;
+    Ok(v["search"][0]["id"].as_str().map(str::to_string))
+}
+
+/// Resolves by the first provider id Wikidata knows, falling back to `title`.
+pub async fn resolve(client: &Client, provider_ids: &HashMap<String, String>, title: Option<&str>, lang: &str) -> Result<Option<String>, String> {
+    for (key, property) in EXTERNAL_IDS {
+        if let Some(value) = provider_ids.get(*key).map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            if let Some(id) = find_by_statement(client, property, value).await? {
+                return Ok(Some(id));
+            }
+        }
+    }
+    match title.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(title) => find_by_title(client, title, lang).await,
+        None => Ok(None),
+    }
+}
+
+/// The entity's labels in `langs`, keyed by language code; languages without one are left out.
+pub async fn labels(client: &Client, id: &str, langs: &[&str]) -> Result<HashMap<String, String>, String> {
+    let entities = get_entities(client, &[id.to_string()], "labels", langs).await?;
+    Ok(langs
+        .iter()
+        .filter_map(|l| Some((l.to_string(), entities[id]["labels"][*l]["value"].as_str()?.to_string())))
+        .collect())
+}
+
+pub async fn lookup(client: &Client, id: &str, lang: &str) -> Result<WikidataEntity, String> {
+    let langs = [lang, "en"];
+    let entities = get_entities(client, &[id.to_string()], "labels|descriptions|claims", &langs).await?;
+    let entity = &entities[id];
+    if entity.is_null() || entity.get("missing").is_some() {
+        return Err("Wikidata entity not found".to_string());
+    }
+    let authors = entity_ids(entity, AUTHOR);
+    let directors = entity_ids(entity, DIRECTOR);
+    let series_statement = statements(entity, PART_OF_SERIES).next();
+    let series_id = series_statement.and_then(|s| s["mainsnak"]["datavalue"]["value"]["id"].as_str()).map(str::to_string);
+    // The ordinal is usually a qualifier of "part of the series"
+    let series_ordinal = series_statement
+        .and_then(|s| s["qualifiers"][SERIES_ORDINAL][0]["datavalue"]["value"].as_str())
+        .or_else(|| values(entity, SERIES_ORDINAL).find_map(Value::as_str))
+        .map(str::to_string);
+    let mut people: Vec<String> = authors.iter().chain(&directors).chain(&series_id).cloned().collect();
+    people.sort();
+    people.dedup();
+    let mut names = HashMap::new();
+    // wbgetentities takes at most 50 ids at a time
+    for chunk in people.chunks(50) {
+        let found = get_entities(client, chunk, "labels", &langs).await?;
+        for id in chunk {
+            if let Some(name) = label(&found[id], &langs) {
+                names.insert(id.clone(), name);
+            }
+        }
+    }
+    let named = |ids: &[String]| ids.iter().map(|id| names.get(id).cloned().unwrap_or_else(|| id.clone())).collect::<Vec<_>>();
+    let mut dates: Vec<String> = values(entity, PUBLICATION_DATE).filter_map(date).collect();
+    dates.sort();
+    let provider_ids = EXTERNAL_IDS
+        .iter()
+        .filter_map(|(key, property)| Some((key.to_string(), values(entity, property).find_map(Value::as_str)?.to_string())))
+        .collect();
+    let duration_minutes = values(entity, DURATION).find_map(minutes);
+    let official_website = values(entity, OFFICIAL_WEBSITE).find_map(Value::as_str).map(str::to_string);
+    Ok(WikidataEntity {
+        id: id.to_string(),
+        url: format!("https://www.wikidata.org/wiki/{}", id),
+        label: label(entity, &langs),
+        description: langs.iter().find_map(|l| entity["descriptions"][*l]["value"].as_str()).map(str::to_string),
+        publication_date: dates.into_iter().next(),
+        authors: named(&authors),
+        directors: named(&directors),
+        series: series_id.map(|s| names.get(&s).cloned().unwrap_or(s)),
+        series_ordinal,
+        duration_minutes,
+        official_website,
+        provider_ids,
+    })
+}
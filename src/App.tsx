@@ -1,4 +1,4 @@
-import React, { useEffect } from 'react';
+import React, { useEffect, useState } from 'react';
 import { HashRouter, Routes, Route, Navigate } from 'react-router-dom';
 import { Layout } from './components/Layout';
 import { SearchPage } from './pages/SearchPage';
@@ -11,6 +11,9 @@ import { useAuthStore } from './store/useAuthStore';
 import { useCollectionStore } from './store/useCollectionStore';
 import { checkUpdates } from './services/aiService';
 import { useTranslation } from 'react-i18next';
+import { emit, listen } from '@tauri-apps/api/event';
+import { addPluginListener, invoke } from '@tauri-apps/api/core';
+import { CollectionCategory, RecoveryReport, ScrapedPage } from './types/types';
 
 // Protected Route Wrapper
 const ProtectedRoute: React.FC<{ children: React.ReactNode }> = ({ children }) => {
@@ -21,15 +24,164 @@ const ProtectedRoute: React.FC<{ children: React.ReactNode }> = ({ children }) =
   return <>{children}</>;
 };
 
+// Shown in the locked-collection toast; putting the key back opens the collection
+const RestoreKey: React.FC<{ report: RecoveryReport; onRestored: () => void }> = ({ report, onRestored }) => {
+  const { t } = useTranslation();
+  const [key, setKey] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const restore = async () => {
+    try {
+      await invoke('set_secret', { name: 'database-key', value: key.trim() });
+      onRestored();
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+  return (
+    <div onClick={(e) => e.stopPropagation()}>
+      <div>{t('app.collection_locked', 'Your collection is encrypted and its key is missing. Nothing will be saved until the key is restored.')}</div>
+      <small>{error || report.error}</small>
+      <div className="flex gap-2 mt-2">
+        <input
+          type="password"
+          value={key}
+          onChange={(e) => setKey(e.target.value)}
+          placeholder={t('app.database_key', 'Database key')}
+          className="flex-1 px-2 py-1 rounded bg-theme-surface text-theme-text border border-theme-border"
+        />
+        <button onClick={restore} disabled={!key.trim()} className="px-2 py-1 rounded bg-theme-accent text-white disabled:opacity-50">
+          {t('app.restore_key', 'Restore')}
+        </button>
+      </div>
+    </div>
+  );
+};
+
 const App: React.FC = () => {
   const { t } = useTranslation();
   const { initialize } = useCollectionStore();
 
-  // Initialize store on mount
+  // Initialize store on mount, once a persisted session is known to still be valid
   useEffect(() => {
-    initialize();
+    useAuthStore.getState().verifySession().then(() => initialize());
   }, [initialize]);
 
+  // Tell the user if the collection file had to be recovered, or can't be opened at all
+  useEffect(() => {
+    if (!('__TAURI_INTERNALS__' in window)) return;
+    invoke<RecoveryReport | null>('get_recovery_report').then((report) => {
+      if (!report) return;
+      if (report.locked) {
+        const id = toast.error(
+          <RestoreKey report={report} onRestored={() => {
+            toast.dismiss(id);
+            toast.success(t('app.collection_unlocked', 'Collection unlocked'));
+            useCollectionStore.getState().refreshForUser();
+          }} />,
+          { autoClose: false, closeOnClick: false }
+        );
+      } else if (report.recoveredFromBackup) {
+        toast.warning(t('app.collection_recovered', 'Your collection file was damaged and has been restored from the backup.'), { autoClose: false });
+      } else {
+        toast.error(
+          <div>
+            <div>{t('app.collection_reset', 'Your collection file was damaged and no backup could be read. A copy was kept at:')}</div>
+            <small>{report.corruptCopy || report.error}</small>
+          </div>,
+          { autoClose: false }
+        );
+      }
+    }).catch(console.error);
+  }, [t]);
+
+  // Updates found by the background checker in the Rust backend
+  useEffect(() => {
+    const unlisten = listen<{ username: string; itemId: string; title: string; latestUpdateInfo: string }>('media-update', (event) => {
+      const { user } = useAuthStore.getState();
+      if (!user || user.username !== event.payload.username) return;
+      useCollectionStore.getState().updateItem(event.payload.itemId, {
+        latestUpdateInfo: event.payload.latestUpdateInfo,
+        hasNewUpdate: true,
+        lastCheckedAt: Date.now()
+      });
+      toast.info(`${event.payload.title}: ${event.payload.latestUpdateInfo}`);
+    });
+    const unlistenFeeds = listen<{ username: string; itemId: string; newEntries: { title: string }[] }>('feed-update', (event) => {
+      const { user } = useAuthStore.getState();
+      const newest = event.payload.newEntries[0];
+      if (!user || user.username !== event.payload.username || !newest) return;
+      const { collection, updateItem } = useCollectionStore.getState();
+      updateItem(event.payload.itemId, { latestUpdateInfo: newest.title, hasNewUpdate: true, lastCheckedAt: Date.now() });
+      const item = collection.find(i => i.id === event.payload.itemId);
+      toast.info(item ? `${item.title}: ${newest.title}` : newest.title);
+    });
+    // A notification about an item (or one of its actions) was clicked
+    const notificationClicks = '__TAURI_INTERNALS__' in window
+      ? addPluginListener<{ notification: { extra?: { username?: string; itemId?: string } } }>('notification', 'actionPerformed', (action) => {
+          const extra = action.notification.extra;
+          const { user } = useAuthStore.getState();
+          if (!extra?.itemId || !user || user.username !== extra.username) return;
+          window.location.hash = `#/collection?item=${encodeURIComponent(extra.itemId)}`;
+        }).catch(() => null)
+      : Promise.resolve(null);
+    // A supported link was copied while the clipboard watcher is on
+    const unlistenClipboard = listen<{ url: string; page?: ScrapedPage; error?: string }>('clipboard-link', (event) => {
+      const page = event.payload.page;
+      if (!page || !useAuthStore.getState().user) return;
+      const add = () => useCollectionStore.getState().addToCollection(page.draft, CollectionCategory.TO_WATCH);
+      toast.info(
+        <div onClick={add} style={{ cursor: 'pointer' }}>
+          <div>{page.title || event.payload.url}</div>
+          <small>{t('clipboard_add_hint', 'Click to add to your collection')}</small>
+        </div>,
+        { autoClose: 10000 }
+      );
+    });
+    // Added from the browser extension through the local server
+    const unlistenQuickAdd = listen<{ username: string; item: { title: string } }>('quick-add', (event) => {
+      const { user } = useAuthStore.getState();
+      if (!user || user.username !== event.payload.username) return;
+      useCollectionStore.getState().refreshForUser();
+      toast.success(event.payload.item.title);
+    });
+    // The quick-add overlay was shown and needs the logged-in session
+    const unlistenQuickAddReady = listen('quick-add-ready', () => {
+      emit('quick-add-session', { session: useAuthStore.getState().session });
+    });
+    // A script or shortcut changed an item through the REST API
+    const unlistenApi = listen<{ username: string; itemId: string; action: string }>('api-change', (event) => {
+      const { user } = useAuthStore.getState();
+      if (!user || user.username !== event.payload.username) return;
+      useCollectionStore.getState().refreshForUser();
+    });
+    // A bulk metadata refresh rewrote items in the backend; reload once it is done or cancelled
+    const unlistenRefresh = listen<{ done: number; total: number; cancelled: boolean }>('metadata-refresh-progress', (event) => {
+      if (event.payload.done === event.payload.total || event.payload.cancelled) {
+        useCollectionStore.getState().refreshForUser();
+      }
+    });
+    // A sync with a paired device finished (manual or auto-sync); reload if it brought changes
+    const unlistenPeerSync = listen<{ username: string; summary?: { pulled?: { added: number; updated: number; deleted: number } } }>('peer-synced', (event) => {
+      const { user } = useAuthStore.getState();
+      const pulled = event.payload.summary?.pulled;
+      if (!user || user.username !== event.payload.username || !pulled) return;
+      if (pulled.added + pulled.updated + pulled.deleted > 0) {
+        useCollectionStore.getState().refreshForUser();
+      }
+    });
+    return () => {
+      unlisten.then(f => f());
+      unlistenPeerSync.then(f => f());
+      unlistenFeeds.then(f => f());
+      notificationClicks.then(l => l?.unregister());
+      unlistenRefresh.then(f => f());
+      unlistenClipboard.then(f => f());
+      unlistenQuickAdd.then(f => f());
+      unlistenQuickAddReady.then(f => f());
+      unlistenApi.then(f => f());
+    };
+  }, [t]);
+
   // Auto-refresh logic on app mount
   useEffect(() => {
     const refreshUpdates = async () => {
@@ -40,7 +40,7 @@ export const AddMediaModal: React.FC<AddMediaModalProps> = ({ onClose }) => {
             posterUrl: posterUrl.trim() || `https://placehold.co/600x900/1a1a1a/FFF?text=${encodeURIComponent(type)}`,
             status: 'To Watch',
             addedAt: new Date().toISOString(),
-            isOngoing: type === MediaType.TV_SERIES || type === MediaType.COMIC || type === MediaType.SHORT_DRAMA,
+            isOngoing: type === MediaType.TV_SERIES || type === MediaType.COMIC || type === MediaType.SHORT_DRAMA || type === MediaType.FANFICTION,
             rating: '',
             cast: [],
             userRating: 0,
@@ -9,6 +9,7 @@ import { EditMediaModal } from './EditMediaModal';
 import { useTranslation } from 'react-i18next';
 import ReactMarkdown from 'react-markdown';
 import { toast } from 'react-toastify';
+import { convertFileSrc } from '@tauri-apps/api/core';
 import { checkUpdates, repairMediaItem } from '../services/aiService';
 
 const smartIncrement = (str: string): string => {
@@ -35,6 +36,16 @@ const smartDecrement = (str: string): string => {
     return str.substring(0, index) + nextNumStr + str.substring(index + numStr.length);
 };
 
+// Display names for the keys of `MediaItem.ratings`
+const RATING_SOURCES: Record<string, string> = {
+  douban: 'Douban',
+  imdb: 'IMDb',
+  tmdb: 'TMDB',
+  bangumi: 'Bangumi',
+  rottenTomatoes: 'RT',
+  metacritic: 'Metacritic',
+};
+
 interface MediaCardProps {
   item: MediaItem;
   onAction?: (item: MediaItem, category: CollectionCategory) => void;
@@ -136,15 +147,29 @@ export const MediaCard: React.FC<MediaCardProps> = ({
   };
 
   const fallbackPoster = 'https://placehold.co/600x400/1a1a1a/FFF?text=No+Image';
-  const [imgSrc, setImgSrc] = useState(normalizeImgSrc(item.customPosterUrl || item.posterUrl) || fallbackPoster);
+  // Prefer the locally cached copy while it still matches the displayed poster URL
+  const posterSrc = () => {
+    const remote = item.customPosterUrl || item.posterUrl;
+    if (item.posterCache && item.posterCache.sourceUrl === remote) {
+      return `${convertFileSrc(item.posterCache.file, 'mtimg')}?size=medium`;
+    }
+    return normalizeImgSrc(remote) || fallbackPoster;
+  };
+  const [imgSrc, setImgSrc] = useState(posterSrc);
 
   useEffect(() => {
-    setImgSrc(normalizeImgSrc(item.customPosterUrl || item.posterUrl) || fallbackPoster);
+    setImgSrc(posterSrc());
     setImgLoading(true);
     setImgFailed(false);
-  }, [item.customPosterUrl, item.posterUrl]);
+  }, [item.customPosterUrl, item.posterUrl, item.posterCache?.file]);
 
   const handleImageError = () => {
+      const remote = normalizeImgSrc(item.customPosterUrl || item.posterUrl);
+      const fromCache = imgSrc.startsWith('mtimg:') || imgSrc.includes('mtimg.localhost');
+      if (remote && fromCache) {
+          setImgSrc(remote);
+          return;
+      }
       setImgSrc('https://placehold.co/600x400/1a1a1a/FFF?text=Image+Error');
       setImgFailed(true);
       setImgLoading(false);
@@ -416,6 +441,17 @@ export const MediaCard: React.FC<MediaCardProps> = ({
                      </span>
                    </div>
                  )}
+
+                 {item.ratings && Object.keys(item.ratings).length > 0 && (
+                   <div className="flex items-start gap-2">
+                     <Star className="w-3.5 h-3.5 mt-0.5 flex-shrink-0 opacity-70" />
+                     <span className="line-clamp-2 leading-tight">
+                       {Object.entries(item.ratings)
+                         .map(([source, r]) => `${RATING_SOURCES[source] || source} ${r.scale === 10 ? r.score.toFixed(1) : `${Math.round(r.score)}/${r.scale}`}`)
+                         .join(' · ')}
+                     </span>
+                   </div>
+                 )}
                </div>
 
                <div className="w-full h-px mb-3 bg-theme-border" />
@@ -1,5 +1,7 @@
 import React, { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { useAuthStore } from '../store/useAuthStore';
 
 interface PeerInfo {
     name: string;
@@ -8,6 +10,58 @@ interface PeerInfo {
     last_seen: number;
 }
 
+interface MergeSummary {
+    added: number;
+    updated: number;
+    unchanged: number;
+    deleted: number;
+    conflicts: {
+        username: string;
+        itemId: string;
+        title: string;
+        kind: 'edit' | 'deleteVsEdit';
+        resolution: 'keptLocal' | 'tookRemote';
+    }[];
+}
+
+interface SyncSummary {
+    direction: 'pull' | 'push' | 'merge';
+    pulled?: MergeSummary;
+    pushed?: MergeSummary;
+}
+
+interface TrustedDevice {
+    id: string;
+    name: string;
+    /** Fingerprint of the device's key; tells apart devices with the same name. */
+    fingerprint: string;
+    pairedAt: number;
+    lastSeenAt?: number;
+    lastSyncedAt?: number;
+}
+
+interface SyncSession {
+    id: string;
+    peer: string;
+    direction: 'pull' | 'push' | 'merge';
+    initiatedHere: boolean;
+    startedAt: number;
+    durationMs: number;
+    sent: number;
+    received: number;
+    conflicts: number;
+    error?: string;
+}
+
+const sinceLabel = (at?: number) => {
+    if (!at) return 'never synced';
+    const minutes = Math.round((Date.now() - at) / 60000);
+    if (minutes < 1) return 'synced just now';
+    if (minutes < 60) return `synced ${minutes} min ago`;
+    const hours = Math.round(minutes / 60);
+    return hours < 24 ? `synced ${hours} h ago` : `synced ${Math.round(hours / 24)} d ago`;
+};
+
 interface SyncModalProps {
     isOpen: boolean;
     onClose: () => void;
@@ -17,12 +71,23 @@ export const SyncModal: React.FC<SyncModalProps> = ({ isOpen, onClose }) => {
     const [peers, setPeers] = useState<PeerInfo[]>([]);
     const [status, setStatus] = useState<string>('Idle');
     const [isSyncing, setIsSyncing] = useState(false);
+    const [pin, setPin] = useState<string | null>(null);
+    const [qr, setQr] = useState<{ payload: string; dataUrl: string } | null>(null);
+    const [devices, setDevices] = useState<TrustedDevice[]>([]);
+    const [history, setHistory] = useState<SyncSession[] | null>(null);
+    const session = useAuthStore(state => state.session) || '';
 
     useEffect(() => {
         if (isOpen) {
             startServer();
+            fetchDevices();
             const interval = setInterval(fetchPeers, 3000);
-            return () => clearInterval(interval);
+            // Auto-sync runs in the background; keep the "last synced" labels current
+            const unlistenSynced = listen('peer-synced', () => fetchDevices());
+            return () => {
+                clearInterval(interval);
+                unlistenSynced.then(f => f());
+            };
         }
     }, [isOpen]);
 
@@ -30,7 +95,8 @@ export const SyncModal: React.FC<SyncModalProps> = ({ isOpen, onClose }) => {
         try {
             setStatus('Starting Sync Service...');
             await invoke('start_sync_server');
-            setStatus('Scanning for devices...');
+            const info = await invoke<{ running: boolean; address?: string }>('sync_status');
+            setStatus(info.address ? `Listening on ${info.address}. Scanning for devices...` : 'Scanning for devices...');
         } catch (e) {
             setStatus('Error starting service: ' + String(e));
         }
@@ -38,7 +104,7 @@ export const SyncModal: React.FC<SyncModalProps> = ({ isOpen, onClose }) => {
 
     const fetchPeers = async () => {
         try {
-            const res = await invoke<PeerInfo[]>('get_peers');
+            const res = await invoke<PeerInfo[]>('list_peers');
             // Deduplicate by IP
             const unique = res.filter((v, i, a) => a.findIndex(t => t.ip === v.ip) === i);
             setPeers(unique);
@@ -47,22 +113,107 @@ export const SyncModal: React.FC<SyncModalProps> = ({ isOpen, onClose }) => {
         }
     };
 
-    const handleSync = async (peer: PeerInfo) => {
+    const fetchDevices = async () => {
+        try {
+            setDevices(await invoke<TrustedDevice[]>('list_trusted_devices', { session }));
+        } catch (e) {
+            console.error(e);
+        }
+    };
+
+    const handleSync = async (peer: PeerInfo, paired = false) => {
         setIsSyncing(true);
         setStatus(`Syncing with ${peer.name}...`);
         try {
-            await invoke('sync_with_peer', { peerIp: peer.ip, peerPort: peer.port });
-            setStatus('Sync Completed!');
-            setTimeout(() => {
-                window.location.reload(); 
-            }, 1000);
+            const summary = await invoke<SyncSummary>('sync_with_peer', { session, peerIp: peer.ip, peerPort: peer.port, direction: 'merge' });
+            const pulled = summary.pulled;
+            const conflicts = (pulled?.conflicts.length ?? 0) + (summary.pushed?.conflicts.length ?? 0);
+            setStatus(`Sync Completed! ${pulled?.added ?? 0} added, ${pulled?.updated ?? 0} updated, ${pulled?.deleted ?? 0} deleted` + (conflicts ? `, ${conflicts} conflict(s)` : ''));
         } catch (e) {
+            if (String(e) === 'NOT_PAIRED' && !paired) {
+                setIsSyncing(false);
+                // The other device shows its PIN under "Pair a device"
+                const entered = window.prompt(`Enter the PIN shown on ${peer.name}`);
+                if (!entered) {
+                    setStatus('Sync cancelled: device not paired');
+                    return;
+                }
+                try {
+                    await invoke('pair_with_peer', { session, peerIp: peer.ip, peerPort: peer.port, pin: entered });
+                } catch (err) {
+                    setStatus('Pairing failed: ' + String(err));
+                    return;
+                }
+                fetchDevices();
+                return handleSync(peer, true);
+            }
             setStatus('Sync Failed: ' + String(e));
         } finally {
             setIsSyncing(false);
         }
     };
 
+    const showPin = async () => {
+        try {
+            const code = await invoke<{ pin: string; expiresAt: number }>('start_pairing', { session });
+            setQr(null);
+            setPin(code.pin);
+            setTimeout(() => setPin(null), code.expiresAt - Date.now());
+        } catch (e) {
+            setStatus('Could not start pairing: ' + String(e));
+        }
+    };
+
+    const showQr = async () => {
+        try {
+            const code = await invoke<{ payload: string; dataUrl: string; expiresAt: number }>('get_pairing_qr', { session });
+            setPin(null);
+            setQr(code);
+            setTimeout(() => setQr(null), code.expiresAt - Date.now());
+        } catch (e) {
+            setStatus('Could not create pairing code: ' + String(e));
+        }
+    };
+
+    // For a pairing code copied from another device's QR screen
+    const pairFromCode = async () => {
+        const payload = window.prompt('Paste the pairing code from the other device');
+        if (!payload) return;
+        try {
+            const device = await invoke<TrustedDevice>('pair_with_qr', { session, payload });
+            setStatus(`Paired with ${device.name}`);
+            fetchDevices();
+        } catch (e) {
+            setStatus('Pairing failed: ' + String(e));
+        }
+    };
+
+    const toggleHistory = async () => {
+        if (history) return setHistory(null);
+        try {
+            setHistory(await invoke<SyncSession[]>('get_sync_history', { session, limit: 20 }));
+        } catch (e) {
+            setStatus('Could not load sync history: ' + String(e));
+        }
+    };
+
+    const revokeDevice = async (device: TrustedDevice) => {
+        if (!window.confirm(`Stop syncing with ${device.name}?`)) return;
+        try {
+            await invoke('revoke_trusted_device', { session, deviceId: device.id });
+            fetchDevices();
+        } catch (e) {
+            setStatus('Could not revoke device: ' + String(e));
+        }
+    };
+
+    const handleClose = () => {
+        if (pin || qr) invoke('cancel_pairing').catch(console.error);
+        setPin(null);
+        setQr(null);
+        onClose();
+    };
+
     if (!isOpen) return null;
 
     return (
@@ -101,9 +252,75 @@ export const SyncModal: React.FC<SyncModalProps> = ({ isOpen, onClose }) => {
                     )}
                 </div>
 
-                <div className="flex justify-end">
+                {pin && (
+                    <div className="mb-4 p-2 rounded bg-gray-100 dark:bg-gray-900 text-center dark:text-gray-200">
+                        <div className="text-xs text-gray-500">Enter this PIN on the other device</div>
+                        <div className="text-2xl font-mono tracking-widest">{pin}</div>
+                    </div>
+                )}
+
+                {history && (
+                    <div className="mb-4 max-h-40 overflow-y-auto text-xs dark:text-gray-300">
+                        {history.length === 0 && <div className="text-gray-400">No syncs yet</div>}
+                        {history.map(session => (
+                            <div key={session.id} className={`py-0.5 ${session.error ? 'text-red-500' : ''}`} title={session.error}>
+                                {new Date(session.startedAt).toLocaleString()} · {session.initiatedHere ? '→' : '←'} {session.peer} · ↑{session.sent} ↓{session.received}
+                                {session.conflicts > 0 && ` · ${session.conflicts} conflict(s)`} · {session.durationMs} ms
+                            </div>
+                        ))}
+                    </div>
+                )}
+
+                {qr && (
+                    <div className="mb-4 flex flex-col items-center">
+                        <img src={qr.dataUrl} alt="Pairing QR code" className="w-48 h-48" />
+                        <div className="text-xs text-gray-500 break-all select-all mt-1">{qr.payload}</div>
+                    </div>
+                )}
+
+                {devices.length > 0 && (
+                    <div className="mb-4">
+                        <div className="text-xs font-semibold text-gray-500 mb-1">Trusted devices</div>
+                        {devices.map(device => (
+                            <div key={device.id} className="flex justify-between items-center text-xs py-1 dark:text-gray-300">
+                                <span title={device.lastSeenAt ? `Last seen ${new Date(device.lastSeenAt).toLocaleString()}` : undefined}>
+                                    {device.name}
+                                    {device.fingerprint && <span className="text-gray-400 font-mono"> {device.fingerprint.slice(0, 8)}</span>}
+                                    <span className="text-gray-400"> · {sinceLabel(device.lastSyncedAt)}</span>
+                                </span>
+                                <button onClick={() => revokeDevice(device)} className="text-red-500 hover:underline">Revoke</button>
+                            </div>
+                        ))}
+                    </div>
+                )}
+
+                <div className="flex justify-between">
+                    <button
+                        onClick={showPin}
+                        className="px-4 py-2 text-sm text-blue-600 dark:text-blue-400 hover:bg-gray-100 dark:hover:bg-gray-700 rounded transition-colors"
+                    >
+                        Pair a device
+                    </button>
+                    <button
+                        onClick={showQr}
+                        className="px-2 py-2 text-sm text-blue-600 dark:text-blue-400 hover:bg-gray-100 dark:hover:bg-gray-700 rounded transition-colors"
+                    >
+                        QR
+                    </button>
+                    <button
+                        onClick={pairFromCode}
+                        className="px-2 py-2 text-sm text-blue-600 dark:text-blue-400 hover:bg-gray-100 dark:hover:bg-gray-700 rounded transition-colors"
+                    >
+                        Enter code
+                    </button>
+                    <button
+                        onClick={toggleHistory}
+                        className="px-2 py-2 text-sm text-blue-600 dark:text-blue-400 hover:bg-gray-100 dark:hover:bg-gray-700 rounded transition-colors"
+                    >
+                        History
+                    </button>
                     <button 
-                        onClick={onClose} 
+                        onClick={handleClose} 
                         className="px-4 py-2 text-sm text-gray-600 dark:text-gray-300 hover:bg-gray-100 dark:hover:bg-gray-700 rounded transition-colors"
                     >
                         Close
@@ -2,6 +2,7 @@ import React from 'react';
 import ReactDOM from 'react-dom/client';
 import './i18n';
 import App from './App';
+import { QuickAddPage } from './pages/QuickAddPage';
 import './index.css';
 
 const rootElement = document.getElementById('root');
@@ -9,9 +10,12 @@ if (!rootElement) {
   throw new Error("Could not find root element to mount to");
 }
 
+// The quick-add overlay window loads this page without the app shell
+const isQuickAdd = window.location.hash.startsWith('#/quick-add');
+
 const root = ReactDOM.createRoot(rootElement);
 root.render(
   <React.StrictMode>
-    <App />
+    {isQuickAdd ? <QuickAddPage /> : <App />}
   </React.StrictMode>
 );
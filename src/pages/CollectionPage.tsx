@@ -10,6 +10,7 @@ import { useTranslation } from 'react-i18next';
 import { checkUpdates, repairMediaItem } from '../services/aiService';
 import { AddMediaModal } from '../components/AddMediaModal';
 import { save } from '@tauri-apps/plugin-dialog';
+import { useSearchParams } from 'react-router-dom';
 
 import {
   DndContext, 
@@ -91,6 +92,16 @@ export const CollectionPage: React.FC = () => {
   const [selectedIds, setSelectedIds] = useState<string[]>([]);
   const [collectionInitiatorId, setCollectionInitiatorId] = useState<string | null>(null);
   const [viewingCollectionId, setViewingCollectionId] = useState<string | null>(null);
+  const [searchParams, setSearchParams] = useSearchParams();
+
+  // Open an item requested via ?item=<id> (e.g. from a clicked notification)
+  useEffect(() => {
+    const itemId = searchParams.get('item');
+    if (itemId && collection.some(i => i.id === itemId)) {
+      setSelectedId(itemId);
+      setSearchParams({}, { replace: true });
+    }
+  }, [searchParams, collection, setSearchParams]);
 
   const fileInputRef = React.useRef<HTMLInputElement>(null);
   const menuRef = React.useRef<HTMLDivElement>(null);
@@ -10,10 +10,12 @@ import { useTranslation } from 'react-i18next';
 
 export const LoginPage: React.FC = () => {
   const { t } = useTranslation();
-  const { login, register } = useAuthStore();
+  const { login, register, resetWithRecoveryCode, loginLocal } = useAuthStore();
   const navigate = useNavigate();
-  const [mode, setMode] = useState<'login' | 'register'>('login');
+  const [mode, setMode] = useState<'login' | 'register' | 'reset'>('login');
   const [serverError, setServerError] = useState<string | null>(null);
+  // Shown once after registering; the user continues when they have saved them
+  const [recoveryCodes, setRecoveryCodes] = useState<string[] | null>(null);
 
   const loginSchema = z.object({
     username: z.string().trim().min(3, t('login.username_error')),
@@ -28,11 +30,19 @@ export const LoginPage: React.FC = () => {
     path: ['confirmPassword'],
   });
   
+  const resetSchema = z.object({
+    username: z.string().trim().min(3, t('login.username_error')),
+    code: z.string().trim().min(16, t('login.recovery_code_error')),
+    password: z.string().min(6, t('login.password_error')),
+  });
+
   type LoginForm = z.infer<typeof loginSchema>;
   type RegisterForm = z.infer<typeof registerSchema>;
+  type ResetForm = z.infer<typeof resetSchema>;
   
   const loginForm = useForm<LoginForm>({ resolver: zodResolver(loginSchema) });
   const registerForm = useForm<RegisterForm>({ resolver: zodResolver(registerSchema) });
+  const resetForm = useForm<ResetForm>({ resolver: zodResolver(resetSchema) });
 
   const onLogin = async (data: LoginForm) => {
     setServerError(null);
@@ -41,20 +51,53 @@ export const LoginPage: React.FC = () => {
       navigate('/');
     } catch (e: any) {
       const msg = typeof e === 'string' ? e : e?.message;
+      if (typeof msg === 'string' && msg.startsWith('TOO_MANY_ATTEMPTS:')) {
+        setServerError(t('login.too_many_attempts', { seconds: msg.split(':')[1] }));
+        return;
+      }
       setServerError(msg === 'INVALID_CREDENTIALS' ? t('login.invalid_credentials') : (msg || t('login.invalid_credentials')));
     }
   };
   const onRegister = async (data: RegisterForm) => {
     setServerError(null);
     try {
-      await register(data.username, data.password);
-      navigate('/');
+      const codes = await register(data.username, data.password);
+      if (codes.length > 0) {
+        setRecoveryCodes(codes);
+      } else {
+        navigate('/');
+      }
     } catch (e: any) {
       const msg = typeof e === 'string' ? e : e?.message;
       setServerError(msg === 'USER_EXISTS' ? t('login.user_exists') : (msg || t('login.user_exists')));
     }
   };
 
+  const onLocal = async () => {
+    setServerError(null);
+    try {
+      await loginLocal();
+      navigate('/');
+    } catch (e: any) {
+      setServerError(typeof e === 'string' ? e : e?.message);
+    }
+  };
+
+  const onReset = async (data: ResetForm) => {
+    setServerError(null);
+    try {
+      await resetWithRecoveryCode(data.username, data.code, data.password);
+      navigate('/');
+    } catch (e: any) {
+      const msg = typeof e === 'string' ? e : e?.message;
+      if (typeof msg === 'string' && msg.startsWith('TOO_MANY_ATTEMPTS:')) {
+        setServerError(t('login.too_many_attempts', { seconds: msg.split(':')[1] }));
+        return;
+      }
+      setServerError(msg === 'INVALID_RECOVERY_CODE' ? t('login.invalid_recovery_code') : (msg || t('login.invalid_recovery_code')));
+    }
+  };
+
   return (
     <div className="min-h-[80vh] flex items-center justify-center px-4 relative">
        {/* Background Glow */}
@@ -75,7 +118,77 @@ export const LoginPage: React.FC = () => {
           </div>
         )}
 
-        {mode === 'login' ? (
+        {recoveryCodes ? (
+        <div className="space-y-4">
+          <p className="text-sm text-theme-text">{t('login.recovery_codes_hint')}</p>
+          <ul className="grid grid-cols-2 gap-2 font-mono text-sm p-4 rounded-lg border bg-theme-bg border-theme-border text-theme-text select-all">
+            {recoveryCodes.map((code) => <li key={code}>{code}</li>)}
+          </ul>
+          <button
+            type="button"
+            onClick={() => navigate('/')}
+            className="w-full py-3 font-medium rounded-lg transition-all shadow-lg bg-theme-accent text-theme-bg hover:bg-theme-accent-hover"
+          >
+            {t('login.recovery_codes_saved')}
+          </button>
+        </div>
+        ) : mode === 'reset' ? (
+        <form onSubmit={resetForm.handleSubmit(onReset)} className="space-y-6">
+          <div>
+            <label className="block text-sm font-medium mb-1 text-theme-text">{t('login.username_label')}</label>
+            <input
+              {...resetForm.register('username')}
+              type="text"
+              className="w-full px-4 py-2 rounded-lg border outline-none transition-all bg-theme-bg border-theme-border text-theme-text focus:border-theme-accent focus:ring-1 focus:ring-theme-accent placeholder-theme-subtext"
+              placeholder={t('login.username_placeholder')}
+            />
+            {resetForm.formState.errors.username && (
+              <p className="mt-1 text-sm text-red-600">{resetForm.formState.errors.username.message as string}</p>
+            )}
+          </div>
+
+          <div>
+            <label className="block text-sm font-medium mb-1 text-theme-text">{t('login.recovery_code_label')}</label>
+            <input
+              {...resetForm.register('code')}
+              type="text"
+              autoComplete="off"
+              className="w-full px-4 py-2 rounded-lg border outline-none transition-all font-mono bg-theme-bg border-theme-border text-theme-text focus:border-theme-accent focus:ring-1 focus:ring-theme-accent placeholder-theme-subtext"
+              placeholder="XXXX-XXXX-XXXX-XXXX"
+            />
+            {resetForm.formState.errors.code && (
+              <p className="mt-1 text-sm text-red-600">{resetForm.formState.errors.code.message as string}</p>
+            )}
+          </div>
+
+          <div>
+            <label className="block text-sm font-medium mb-1 text-theme-text">{t('login.new_password_label')}</label>
+            <input
+              {...resetForm.register('password')}
+              type="password"
+              className="w-full px-4 py-2 rounded-lg border outline-none transition-all bg-theme-bg border-theme-border text-theme-text focus:border-theme-accent focus:ring-1 focus:ring-theme-accent placeholder-theme-subtext"
+              placeholder="••••••••"
+            />
+            {resetForm.formState.errors.password && (
+              <p className="mt-1 text-sm text-red-600">{resetForm.formState.errors.password.message as string}</p>
+            )}
+          </div>
+
+          <button
+            type="submit"
+            disabled={resetForm.formState.isSubmitting}
+            className="w-full py-3 font-medium rounded-lg transition-all shadow-lg disabled:opacity-70 disabled:cursor-not-allowed bg-theme-accent text-theme-bg hover:bg-theme-accent-hover"
+          >
+            {t('login.reset_btn')}
+          </button>
+
+          <div className="mt-4 text-center">
+            <button type="button" className="text-theme-accent hover:underline" onClick={() => setMode('login')}>
+              {t('login.switch_to_login')}
+            </button>
+          </div>
+        </form>
+        ) : mode === 'login' ? (
         <form onSubmit={loginForm.handleSubmit(onLogin)} className="space-y-6">
           <div>
             <label className="block text-sm font-medium mb-1 text-theme-text">{t('login.username_label')}</label>
@@ -114,6 +227,12 @@ export const LoginPage: React.FC = () => {
             <button type="button" className="text-theme-accent hover:underline" onClick={() => setMode('register')}>
               {t('login.switch_to_register')}
             </button>
+            <button type="button" className="block mx-auto mt-2 text-sm text-theme-subtext hover:underline" onClick={() => setMode('reset')}>
+              {t('login.forgot_password')}
+            </button>
+            <button type="button" className="block mx-auto mt-2 text-sm text-theme-subtext hover:underline" onClick={onLocal}>
+              {t('login.continue_local')}
+            </button>
           </div>
         </form>
         ) : (
@@ -0,0 +1,142 @@
+import React, { useEffect, useRef, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+import { emit, listen } from '@tauri-apps/api/event';
+import { getCurrentWindow } from '@tauri-apps/api/window';
+import { Search, Loader2 } from 'lucide-react';
+import { useTranslation } from 'react-i18next';
+import clsx from 'clsx';
+import { useThemeStore } from '../store/useThemeStore';
+import { QuickAddHit } from '../types/types';
+
+// The overlay opened by the global shortcut. It runs in its own window, so it
+// asks the main window for the logged-in session every time it is shown.
+export const QuickAddPage: React.FC = () => {
+  const { t } = useTranslation();
+  const { theme } = useThemeStore();
+  const [session, setSession] = useState<string | null>(null);
+  const [query, setQuery] = useState('');
+  const [hits, setHits] = useState<QuickAddHit[]>([]);
+  const [selected, setSelected] = useState(0);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const inputRef = useRef<HTMLInputElement>(null);
+
+  useEffect(() => {
+    document.documentElement.setAttribute('data-theme', theme);
+  }, [theme]);
+
+  useEffect(() => {
+    const unlistenSession = listen<{ session: string | null }>('quick-add-session', (event) => {
+      setSession(event.payload.session);
+    });
+    // Fresh state and session on every show
+    const reset = () => {
+      setQuery('');
+      setHits([]);
+      setError(null);
+      inputRef.current?.focus();
+      emit('quick-add-ready');
+    };
+    const unlistenFocus = getCurrentWindow().onFocusChanged(({ payload: focused }) => {
+      if (focused) reset();
+    });
+    reset();
+    return () => {
+      unlistenSession.then(f => f());
+      unlistenFocus.then(f => f());
+    };
+  }, []);
+
+  useEffect(() => {
+    if (!session || !query.trim()) {
+      setHits([]);
+      return;
+    }
+    let stale = false;
+    const timer = setTimeout(async () => {
+      setLoading(true);
+      try {
+        const found = await invoke<QuickAddHit[]>('quick_add_search', { session, query });
+        if (!stale) {
+          setHits(found);
+          setSelected(0);
+          setError(null);
+        }
+      } catch (e) {
+        if (!stale) setError(String(e));
+      } finally {
+        if (!stale) setLoading(false);
+      }
+    }, 300);
+    return () => {
+      stale = true;
+      clearTimeout(timer);
+    };
+  }, [query, session]);
+
+  const close = () => invoke('close_quick_add').catch(console.error);
+
+  const save = async (hit: QuickAddHit) => {
+    try {
+      // The backend hides the window once the item is saved
+      await invoke('quick_add_save', { session, hit });
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  const onKeyDown = (e: React.KeyboardEvent) => {
+    if (e.key === 'Escape') {
+      close();
+    } else if (e.key === 'ArrowDown') {
+      e.preventDefault();
+      setSelected(i => Math.min(i + 1, hits.length - 1));
+    } else if (e.key === 'ArrowUp') {
+      e.preventDefault();
+      setSelected(i => Math.max(i - 1, 0));
+    } else if (e.key === 'Enter' && hits[selected]) {
+      save(hits[selected]);
+    }
+  };
+
+  return (
+    <div className="h-screen flex flex-col bg-theme-surface text-theme-text font-theme border border-theme-border rounded-theme overflow-hidden" onKeyDown={onKeyDown}>
+      <div className="flex items-center gap-3 px-4 py-3 border-b border-theme-border bg-theme-bg">
+        {loading ? <Loader2 className="w-5 h-5 animate-spin text-theme-subtext" /> : <Search className="w-5 h-5 text-theme-subtext" />}
+        <input
+          ref={inputRef}
+          autoFocus
+          value={query}
+          disabled={!session}
+          onChange={(e) => setQuery(e.target.value)}
+          placeholder={t('quick_add.placeholder', 'Type a title to add to To Watch')}
+          className="flex-1 bg-transparent outline-none text-lg"
+        />
+      </div>
+      <div className="flex-1 overflow-y-auto">
+        {!session && (
+          <p className="p-4 text-sm text-theme-subtext">{t('quick_add.login_first', 'Log in to MediaTracker to use quick add.')}</p>
+        )}
+        {error && <p className="px-4 pt-3 text-sm text-red-500">{error}</p>}
+        {hits.map((hit, i) => (
+          <button
+            key={`${hit.source}:${hit.sourceId}`}
+            onClick={() => save(hit)}
+            onMouseEnter={() => setSelected(i)}
+            className={clsx('w-full flex items-center gap-3 px-4 py-2 text-left', i === selected && 'bg-theme-bg')}
+          >
+            {hit.posterUrl
+              ? <img src={hit.posterUrl} alt="" className="w-8 h-12 object-cover rounded" />
+              : <div className="w-8 h-12 rounded bg-theme-bg" />}
+            <div className="min-w-0">
+              <div className="truncate font-medium">{hit.title}</div>
+              <div className="truncate text-xs text-theme-subtext">
+                {[hit.releaseDate.slice(0, 4), hit.mediaType, hit.originalTitle, hit.source === 'bangumi' ? 'Bangumi' : 'TMDB'].filter(Boolean).join(' · ')}
+              </div>
+            </div>
+          </button>
+        ))}
+      </div>
+    </div>
+  );
+};
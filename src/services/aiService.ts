@@ -487,8 +487,8 @@ export const performClientSideSearch = async (
                   p: { provider: TauriSearchProvider; apiKey?: string; cx?: string; user?: string },
                   search_type: 'text' | 'image'
                 ) => ({
+                  // The backend reads the provider's key from the keychain
                   provider: p.provider,
-                  api_key: p.apiKey,
                   cx: p.cx,
                   user: p.user,
                   search_type,
@@ -935,7 +935,6 @@ export const testAuthoritativeDomain = async (domain: string, sampleQuery: strin
             });
             const rustConfig = {
                 provider: eff.provider,
-                api_key: eff.apiKey,
                 cx: eff.cx,
                 user: eff.user,
                 search_type: 'text',
@@ -1159,7 +1158,8 @@ export const callAI = async (messages: any[], temperature: number = 0.7, options
             const rustConfig = {
                 model,
                 baseURL: effBaseURL,
-                apiKey,
+                // The stored key is read from the keychain by the backend
+                apiKey: override.apiKey ?? (state.getDecryptedApiKey() ? undefined : apiKey),
                 proxy_url: getProxyUrl(),
                 use_system_proxy: useSystemProxy
             };
@@ -1698,7 +1698,9 @@ export const searchMedia = async (query: string, type?: MediaType | 'All'): Prom
       posterUrl: getTMDBPosterUrl(item.poster_path) || undefined,
       tmdbId: typeof item.id === 'number' ? item.id : undefined,
       tmdbMediaType: item.media_type === 'movie' || item.media_type === 'tv' ? item.media_type : undefined,
+      providerIds: typeof item.id === 'number' ? { [item.media_type === 'movie' ? 'tmdb' : 'tmdbTv']: String(item.id) } : undefined,
       rating: item.vote_average ? `${item.vote_average.toFixed(1)}/10` : undefined,
+      ratings: item.vote_average ? { tmdb: { score: item.vote_average, scale: 10, votes: item.vote_count } } : undefined,
       status: 'To Watch',
       addedAt: new Date().toISOString()
   }));
@@ -1726,6 +1728,8 @@ export const searchMedia = async (query: string, type?: MediaType | 'All'): Prom
           isOngoing: false,
           posterUrl,
           rating: (typeof score === 'number' && !Number.isNaN(score)) ? `${score}/10` : undefined,
+          ratings: (typeof score === 'number' && !Number.isNaN(score)) ? { bangumi: { score, scale: 10, votes: item.rating?.total } } : undefined,
+          providerIds: item.id ? { bangumi: String(item.id) } : undefined,
           status: 'To Watch',
           addedAt: new Date().toISOString()
       };
@@ -2525,7 +2529,6 @@ export const fetchPosterFromSearch = async (title: string, year: string, type: s
             const runWebSearch = async (qv: string, p: { provider: TauriSearchProvider; api_key?: string; cx?: string; user?: string }, search_type: 'image' | 'text') => {
                 const rustConfig = {
                     provider: p.provider,
-                    api_key: p.api_key,
                     cx: p.cx,
                     user: p.user,
                     search_type,
@@ -1,6 +1,7 @@
 import { create } from 'zustand';
 import { persist, createJSONStorage } from 'zustand/middleware';
 import CryptoJS from 'crypto-js';
+import { invoke } from '@tauri-apps/api/core';
 import { AIIOLogEntry, SearchDiagnostics } from '../types/types';
 
 // Simple encryption key (In a real app, this should not be hardcoded or should be user-provided)
@@ -8,6 +9,22 @@ import { AIIOLogEntry, SearchDiagnostics } from '../types/types';
 const SECRET_KEY = import.meta.env.VITE_SECRET_KEY || 'media-tracker-ai-config-secret';
 
 
+const isTauri = typeof window !== 'undefined' && (('__TAURI__' in window) || ('__TAURI_INTERNALS__' in window));
+
+// In the desktop app keys live in the OS keychain under these names (the backend
+// looks them up itself) and are only held in memory here, never in localStorage
+const SECRET_NAMES = {
+  apiKey: 'ai',
+  googleSearchApiKey: 'google',
+  serperApiKey: 'serper',
+  yandexSearchApiKey: 'yandex',
+  omdbApiKey: 'omdb',
+  tmdbApiKey: 'tmdb',
+  bangumiToken: 'bangumi',
+} as const;
+type SecretField = keyof typeof SECRET_NAMES;
+const SECRET_FIELDS = Object.keys(SECRET_NAMES) as SecretField[];
+
 export type AIProvider = 'moonshot' | 'openai' | 'deepseek' | 'qwen' | 'google' | 'mistral' | 'custom';
 export type SearchProvider = 'google' | 'serper' | 'yandex' | 'duckduckgo';
 
@@ -247,6 +264,14 @@ Ensure data is accurate.`,
         if (typeof config.baseUrl !== 'undefined') {
           updates.baseUrl = sanitizeBaseUrl(config.baseUrl || '');
         }
+        if (isTauri) {
+          for (const field of SECRET_FIELDS) {
+            const value = config[field];
+            if (typeof value === 'string') {
+              invoke('set_secret', { name: SECRET_NAMES[field], value }).catch(console.error);
+            }
+          }
+        }
         set((state) => ({ ...state, ...updates }));
       },
       getDecryptedApiKey: () => decrypt(get().apiKey),
@@ -335,6 +360,29 @@ Ensure data is accurate.`,
         } catch {}
         return persistedState;
       },
+      // Moves keys saved by older versions into the keychain, then loads them all
+      onRehydrateStorage: () => (state) => {
+        if (!isTauri || !state) return;
+        (async () => {
+          const loaded: Partial<Record<SecretField, string>> = {};
+          for (const field of SECRET_FIELDS) {
+            const name = SECRET_NAMES[field];
+            const legacy = decrypt(state[field]);
+            try {
+              if (legacy) {
+                await invoke('set_secret', { name, value: legacy });
+                loaded[field] = state[field];
+              } else {
+                const stored = await invoke<string | null>('get_secret', { name });
+                loaded[field] = stored ? encrypt(stored) : '';
+              }
+            } catch (e) {
+              console.error(`Keychain unavailable for ${name}`, e);
+            }
+          }
+          useAIStore.setState(loaded);
+        })();
+      },
       partialize: (state) => ({ 
         provider: state.provider,
         apiKey: state.apiKey, 
@@ -367,7 +415,8 @@ Ensure data is accurate.`,
         proxyHost: state.proxyHost,
         proxyPort: state.proxyPort,
         proxyUsername: state.proxyUsername,
-        proxyPassword: state.proxyPassword
+        proxyPassword: state.proxyPassword,
+        ...(isTauri ? Object.fromEntries(SECRET_FIELDS.map((field) => [field, ''])) : {})
       }),
     }
   )
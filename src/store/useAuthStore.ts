@@ -4,32 +4,104 @@ import { User } from '../types/types';
 import { invoke } from '@tauri-apps/api/core';
 const isTauri = typeof window !== 'undefined' && (('__TAURI__' in window) || ('__TAURI_INTERNALS__' in window));
 
+interface SessionInfo {
+  username: string;
+  session: string;
+  expiresAt: number;
+  recoveryCodes?: string[];
+}
+
+export type AccountMergeStrategy = 'preferTarget' | 'preferSource' | 'newest' | 'keepBoth';
+
 interface AuthState {
   user: User | null;
+  // Token from login_user; every user-scoped command takes it instead of a username
+  session: string | null;
   login: (username: string, password?: string) => Promise<void>;
-  register: (username: string, password: string) => Promise<void>;
+  // Resolves to the recovery codes, which are shown once
+  register: (username: string, password: string) => Promise<string[]>;
+  resetWithRecoveryCode: (username: string, code: string, newPassword: string) => Promise<void>;
+  // The built-in profile that needs no account; its username is '@local'
+  loginLocal: () => Promise<void>;
+  // Turns the local profile into a registered account; resolves to its recovery codes
+  promoteToAccount: (username: string, password: string) => Promise<string[]>;
   logout: () => void;
+  // Erases the account and its data for good, then logs out
+  deleteAccount: (password: string) => Promise<void>;
+  // Moves another account's library into this one and deletes that account
+  mergeAccount: (source: string, sourcePassword: string, strategy?: AccountMergeStrategy) => Promise<void>;
+  // Logs out if the backend no longer knows the session (e.g. after a restart)
+  verifySession: () => Promise<void>;
 }
 
 export const useAuthStore = create<AuthState>()(
   persist(
-    (set) => ({
+    (set, get) => ({
       user: null,
+      session: null,
       login: async (username, password) => {
         if (!isTauri) {
           throw new Error('Login not available in web preview');
         }
-        const result = await invoke<{ username: string }>('login_user', { username, password: password || '' });
-        set({ user: { username: result.username, lastBackup: new Date().toISOString() } });
+        const result = await invoke<SessionInfo>('login_user', { username, password: password || '' });
+        set({ user: { username: result.username, lastBackup: new Date().toISOString() }, session: result.session });
       },
       register: async (username, password) => {
         if (!isTauri) {
           throw new Error('Register not available in web preview');
         }
-        const result = await invoke<{ username: string }>('register_user', { username, password });
-        set({ user: { username: result.username, lastBackup: new Date().toISOString() } });
+        const result = await invoke<SessionInfo>('register_user', { username, password });
+        set({ user: { username: result.username, lastBackup: new Date().toISOString() }, session: result.session });
+        return result.recoveryCodes ?? [];
+      },
+      resetWithRecoveryCode: async (username, code, newPassword) => {
+        if (!isTauri) {
+          throw new Error('Password reset not available in web preview');
+        }
+        const result = await invoke<SessionInfo>('reset_password_with_recovery_code', { username, code, newPassword });
+        set({ user: { username: result.username, lastBackup: new Date().toISOString() }, session: result.session });
+      },
+      loginLocal: async () => {
+        if (!isTauri) {
+          throw new Error('Login not available in web preview');
+        }
+        const result = await invoke<SessionInfo>('start_local_session');
+        set({ user: { username: result.username, lastBackup: new Date().toISOString() }, session: result.session });
+      },
+      promoteToAccount: async (username, password) => {
+        const { session } = get();
+        const result = await invoke<SessionInfo>('promote_to_account', { session, username, password });
+        set({ user: { username: result.username, lastBackup: new Date().toISOString() }, session: result.session });
+        return result.recoveryCodes ?? [];
+      },
+      logout: () => {
+        const { session } = get();
+        if (isTauri && session) {
+          invoke('logout_user', { session }).catch(console.error);
+        }
+        set({ user: null, session: null });
+      },
+      deleteAccount: async (password) => {
+        const { session } = get();
+        if (!isTauri || !session) return;
+        await invoke('delete_account', { session, password });
+        set({ user: null, session: null });
+      },
+      mergeAccount: async (source, sourcePassword, strategy) => {
+        const { session } = get();
+        if (!isTauri || !session) return;
+        await invoke('merge_users', { session, source, sourcePassword, strategy });
+      },
+      verifySession: async () => {
+        const { user, session } = get();
+        if (!isTauri || !user) return;
+        try {
+          if (!session) throw new Error('SESSION_EXPIRED');
+          await invoke('check_session', { session });
+        } catch {
+          set({ user: null, session: null });
+        }
       },
-      logout: () => set({ user: null }),
     }),
     {
       name: 'media-tracker-auth',
@@ -35,8 +35,8 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
     set({ isLoading: true });
     try {
         if (isTauri) {
-            const username = useAuthStore.getState().user?.username || 'guest';
-            const items = await invoke<MediaItem[]>('get_collection', { username });
+            const session = useAuthStore.getState().session || '';
+            const items = await invoke<MediaItem[]>('get_collection', { session });
             set({ collection: items, initialized: true, isLoading: false });
         } else {
             // Fallback to localStorage for Web Mode
@@ -82,8 +82,8 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
         
         // Persist
         if (isTauri) {
-            const username = useAuthStore.getState().user?.username || 'guest';
-            invoke('import_collection', { username, items: newItems }).catch(console.error);
+            const session = useAuthStore.getState().session || '';
+            invoke('import_collection', { session, items: newItems }).catch(console.error);
         } else {
             localStorage.setItem('media-tracker-collection', JSON.stringify({ state: { collection: updatedCollection }, version: 0 }));
         }
@@ -96,9 +96,9 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
       set({ collection: newOrder });
       
       if (isTauri) {
-          const username = useAuthStore.getState().user?.username || 'guest';
+          const session = useAuthStore.getState().session || '';
           const ids = newOrder.map(i => i.id);
-          invoke('reorder_collection', { username, ids }).catch(console.error);
+          invoke('reorder_collection', { session, ids }).catch(console.error);
       } else {
           localStorage.setItem('media-tracker-collection', JSON.stringify({ state: { collection: newOrder }, version: 0 }));
       }
@@ -110,8 +110,8 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
         console.warn('Export not available in web preview');
         return null;
       }
-      const username = useAuthStore.getState().user?.username || 'guest';
-      const path = await invoke<string>('export_collection', { username, targetPath, redactSensitive });
+      const session = useAuthStore.getState().session || '';
+      const path = await invoke<string>('export_collection', { session, targetPath, redactSensitive });
       return path;
     } catch (e) {
       console.error('Export collection failed', e);
@@ -135,8 +135,8 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
 
         // Persist
         if (isTauri) {
-            const username = useAuthStore.getState().user?.username || 'guest';
-            invoke('save_item', { username, item: newItem }).catch(console.error);
+            const session = useAuthStore.getState().session || '';
+            invoke('save_item', { session, item: newItem }).catch(console.error);
         } else {
             localStorage.setItem('media-tracker-collection', JSON.stringify({ state: { collection: updatedCollection }, version: 0 }));
         }
@@ -161,8 +161,8 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
     const updatedCollection = state.collection.filter((item) => item.id !== id);
     
     if (isTauri) {
-        const username = useAuthStore.getState().user?.username || 'guest';
-        invoke('remove_item', { username, id }).catch(console.error);
+        const session = useAuthStore.getState().session || '';
+        invoke('remove_item', { session, id }).catch(console.error);
     } else {
         localStorage.setItem('media-tracker-collection', JSON.stringify({ state: { collection: updatedCollection }, version: 0 }));
     }
@@ -181,8 +181,8 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
     });
 
     if (isTauri && updatedItem) {
-        const username = useAuthStore.getState().user?.username || 'guest';
-        invoke('save_item', { username, item: updatedItem }).catch(console.error);
+        const session = useAuthStore.getState().session || '';
+        invoke('save_item', { session, item: updatedItem }).catch(console.error);
     } else {
         localStorage.setItem('media-tracker-collection', JSON.stringify({ state: { collection: updatedCollection }, version: 0 }));
     }
@@ -201,8 +201,8 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
     });
 
     if (isTauri && updatedItem) {
-        const username = useAuthStore.getState().user?.username || 'guest';
-        invoke('save_item', { username, item: updatedItem }).catch(console.error);
+        const session = useAuthStore.getState().session || '';
+        invoke('save_item', { session, item: updatedItem }).catch(console.error);
     } else {
         localStorage.setItem('media-tracker-collection', JSON.stringify({ state: { collection: updatedCollection }, version: 0 }));
     }
@@ -242,13 +242,15 @@ export const useCollectionStore = create<CollectionState>((set, get) => ({
 
       // Persist
       if (isTauri) {
-          const username = useAuthStore.getState().user?.username || 'guest';
-          // Save collection item
-          invoke('save_item', { username, item: collectionItem }).catch(console.error);
-          // Save updated children
-          itemsToUpdate.forEach(item => {
-              invoke('save_item', { username, item }).catch(console.error);
-          });
+          const session = useAuthStore.getState().session || '';
+          // The container goes first and the children one at a time, so each
+          // link lands on a collection that already exists
+          (async () => {
+              await invoke('save_item', { session, item: collectionItem });
+              for (const item of itemsToUpdate) {
+                  await invoke('save_item', { session, item });
+              }
+          })().catch(console.error);
       } else {
           localStorage.setItem('media-tracker-collection', JSON.stringify({ state: { collection: finalCollection }, version: 0 }));
       }
@@ -5,6 +5,10 @@ export enum MediaType {
   COMIC = 'Comic',
   SHORT_DRAMA = 'Short Drama',
   MUSIC = 'Music',
+  FANFICTION = 'Fanfiction',
+  PODCAST = 'Podcast',
+  AUDIOBOOK = 'Audiobook',
+  BOARD_GAME = 'Board Game',
   OTHER = 'Other'
 }
 
@@ -14,12 +18,71 @@ export enum CollectionCategory {
   WATCHED = 'Watched'
 }
 
+export interface SourceRating {
+  score: number;
+  scale: number;
+  votes?: number;
+  updatedAt?: number;
+}
+
+export interface CachedImage {
+  hash: string;
+  file: string; // served as mtimg://localhost/<file>?size=small|medium
+  sourceUrl: string;
+  contentType: string;
+  size: number;
+  cachedAt: number;
+}
+
+// Returned by the `scrape_url` command
+export interface ScrapedPage {
+  url: string;
+  title?: string;
+  description?: string;
+  image?: string;
+  kind?: string; // og:type or JSON-LD @type
+  releaseDate?: string;
+  creator?: string;
+  siteName?: string;
+  draft: MediaItem;
+}
+
+// Returned by the `quick_add_search` command and sent back to `quick_add_save`
+export interface QuickAddHit {
+  source: string; // tmdb | tmdbTv | bangumi
+  sourceId: string;
+  title: string;
+  originalTitle?: string;
+  releaseDate: string;
+  mediaType: MediaType;
+  posterUrl?: string;
+  description: string;
+}
+
+// Returned by the `get_recovery_report` command when collection.json didn't load cleanly
+export interface RecoveryReport {
+  recoveredFromBackup: boolean;
+  error: string;
+  corruptCopy?: string;
+  locked: boolean; // encrypted and the key is missing; nothing is saved until it is restored
+}
+
+// Returned by the `find_cover_candidates` command
+export interface CoverCandidate {
+  url: string;
+  source: string; // douban | wikipedia | omdb | bangumi | google | serper
+  label?: string;
+  width?: number;
+  height?: number;
+}
+
 export interface MediaItem {
   id: string; // generated UUID or unique ID from AI
   title: string;
   directorOrAuthor: string;
   description: string;
   releaseDate: string;
+  normalizedReleaseDate?: { iso: string; precision: 'year' | 'month' | 'day' }; // releaseDate parsed by the backend on save; sorts as a string
   type: MediaType;
   isOngoing: boolean;
   latestUpdateInfo?: string; // e.g., "Chapter 105" or "Season 3 Episode 2"
@@ -27,6 +90,8 @@ export interface MediaItem {
   savedAt?: number;
   posterUrl?: string; // URL for the poster image
   rating?: string; // e.g., "8.5/10"
+  ratings?: Record<string, SourceRating>; // Per-source scores, e.g. { douban: {...}, imdb: {...} }
+  aggregateRating?: number;
   cast?: string[]; // Main actors (max 5)
 
   tmdbId?: number;
@@ -41,7 +106,9 @@ export interface MediaItem {
   // Customization fields
   userReview?: string; // Rich text content for user review
   customPosterUrl?: string; // User uploaded poster URL
+  posterCache?: CachedImage; // Local copy of the displayed poster
   lastEditedAt?: number; // Timestamp of last edit
+  updatedAt?: number; // Any content change (set by the backend); used by LAN sync
 
   // Backend-aligned fields
   status?: string;
@@ -51,6 +118,67 @@ export interface MediaItem {
   // Collection fields
   parentCollectionId?: string; // If set, this item belongs to a collection
   isCollection?: boolean; // If true, this item is a container for other items
+
+  tags?: string[];
+  providerIds?: Record<string, string>;
+  customFields?: Record<string, string | number>;
+  completions?: Completion[]; // Every finish, oldest first; more than one is a rewatch
+  quotes?: Quote[]; // Highlights, mostly for books
+  notes?: Note[]; // Markdown notes; images are served as mtnote://localhost/<file>
+  reading?: ReadingProgress; // Volume/chapter position and chapters read (comics, manga)
+  contentRating?: { system: string; value: string; minAge?: number }; // Age rating from the provider or implied by tags
+  narrators?: string[]; // Audiobooks
+  durationMinutes?: number; // Audiobook listening length
+  minPlayers?: number; // Board games
+  maxPlayers?: number;
+  minPlaytime?: number; // Board game play time in minutes
+  maxPlaytime?: number;
+  playTimes?: { gameId: string; mainMinutes?: number; extraMinutes?: number; completionistMinutes?: number; fetchedAt: number }; // HowLongToBeat averages
+  playCount?: number; // Last.fm scrobbles of an imported album
+  trailerUrl?: string; // Best YouTube trailer found by find_trailer
+  altTitles?: Record<string, string>; // Titles in other languages by code ("zh", "en", "ja"), from fetch_localized_titles
+  nextAiring?: { episode: string; at: number; sourceTimezone: string; timeKnown: boolean }; // UTC ms; midnight in sourceTimezone when timeKnown is false
+  sharedFrom?: string; // Owner of an item from a collection shared with this account
+}
+
+export interface Note {
+  id: string;
+  markdown: string;
+  attachments: string[];
+  createdAt: number;
+  updatedAt?: number;
+}
+
+export interface Quote {
+  id: string;
+  text: string;
+  location?: string; // Page, chapter or timestamp
+  createdAt: number;
+  updatedAt?: number;
+}
+
+export interface Completion {
+  at: number;
+  rating?: number;
+}
+
+export interface ChapterRead {
+  chapter: string;
+  volume?: number;
+  readAt: number;
+}
+
+export interface ReadingProgress {
+  volume?: number;
+  chapter?: string;
+  read: ChapterRead[];
+}
+
+/** Manual order of one view: item id -> position, plus pinned ids in order. */
+export interface ViewOrder {
+  positions: Record<string, number>;
+  pinned: string[];
+  updatedAt: number;
 }
 
 export interface User {